@@ -28,6 +28,8 @@ object PrettyPrinter {
       s"AddBigInt(${value.get.value})"
     case Transform.TransformInstance.AddKeys(TransformAddKeys(ks)) =>
       s"Insert(${ks.map(buildString).mkString(",")})"
+    case Transform.TransformInstance.RemoveKeys(TransformRemoveKeys(names)) =>
+      s"Remove(${names.mkString(",")})"
     case Transform.TransformInstance.Failure(_)  => "TransformFailure"
     case Transform.TransformInstance.Identity(_) => "Read"
     case Transform.TransformInstance.Write(TransformWrite(mv)) =>
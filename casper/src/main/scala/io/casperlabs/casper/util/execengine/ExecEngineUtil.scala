@@ -45,13 +45,13 @@ object ExecEngineUtil {
   ): F[DeploysCheckpoint] =
     for {
       preStateHash <- computePrestate[F](merged)
-      processedDeploys <- processDeploys[F](
-                           preStateHash,
-                           blocktime,
-                           deploys,
-                           protocolVersion
-                         )
-      processedDeployResults = zipDeploysResults(deploys, processedDeploys).toList
+      execResult <- processDeploys[F](
+                     preStateHash,
+                     blocktime,
+                     deploys,
+                     protocolVersion
+                   )
+      processedDeployResults = zipDeploysResults(deploys, execResult.deployResults).toList
       invalidDeploys <- processedDeployResults.foldM[F, InvalidDeploys](InvalidDeploys(Nil, Nil)) {
                          case (acc, d: InvalidNonceDeploy) =>
                            acc.copy(invalidNonceDeploys = d :: acc.invalidNonceDeploys).pure[F]
@@ -67,9 +67,10 @@ object ExecEngineUtil {
                        }
       deployEffects                 = findCommutingEffects(processedDeployResults)
       (deploysForBlock, transforms) = ExecEngineUtil.unzipEffectsAndDeploys(deployEffects).unzip
-      commitResult                  <- ExecutionEngineService[F].commit(preStateHash, transforms.flatten).rethrow
+      blockTransforms               = transforms.flatten ++ blockLevelTransforms(execResult)
+      commitResult                  <- ExecutionEngineService[F].commit(preStateHash, blockTransforms).rethrow
       //TODO: Remove this logging at some point
-      msgBody = transforms.flatten
+      msgBody = blockTransforms
         .map(t => {
           val k    = PrettyPrinter.buildString(t.key.get)
           val tStr = PrettyPrinter.buildString(t.transform.get)
@@ -93,11 +94,21 @@ object ExecEngineUtil {
       blocktime: Long,
       deploys: Seq[Deploy],
       protocolVersion: state.ProtocolVersion
-  ): F[Seq[DeployResult]] =
+  ): F[ExecResult] =
     ExecutionEngineService[F]
       .exec(prestate, blocktime, deploys.map(ProtoUtil.deployDataToEEDeploy), protocolVersion)
       .rethrow
 
+  /** Transforms of the reward distribution and the rent collection, which the engine runs after
+    * the deploys of a block when asked to. They apply to the block as a whole rather than to one
+    * of its deploys, so they're committed alongside the deploys' effects.
+    */
+  def blockLevelTransforms(execResult: ExecResult): Seq[TransformEntry] =
+    Seq(execResult.finalizationResult, execResult.rentCollectionResult).flatten
+      .flatMap(_.value.executionResult)
+      .flatMap(_.effects)
+      .flatMap(_.transformMap)
+
   private def processGenesisDeploys[F[_]: MonadError[?[_], Throwable]: BlockStore: ExecutionEngineService](
       deploys: Seq[Deploy],
       protocolVersion: state.ProtocolVersion
@@ -190,16 +201,16 @@ object ExecEngineUtil {
       } yield transformMap
     } else {
       for {
-        processedDeploys <- processDeploys[F](
-                             prestate,
-                             blocktime,
-                             deploys,
-                             protocolVersion
-                           )
-        deployEffects = zipDeploysResults(deploys, processedDeploys)
+        execResult <- processDeploys[F](
+                       prestate,
+                       blocktime,
+                       deploys,
+                       protocolVersion
+                     )
+        deployEffects = zipDeploysResults(deploys, execResult.deployResults)
         transformMap = (findCommutingEffects _ andThen unzipEffectsAndDeploys)(deployEffects)
           .flatMap(_._2)
-      } yield transformMap
+      } yield transformMap ++ blockLevelTransforms(execResult)
     }
   }
 
@@ -4,12 +4,14 @@ import cats.kernel.Monoid
 import io.casperlabs.ipc
 import io.casperlabs.casper.consensus.state
 
-import Op.{Add, NoOp, Read, Write}
+import Op.{Add, Create, NoOp, Read, Write}
 
 sealed trait Op { self =>
   def +(other: Op): Op = (self, other) match {
     case (a, NoOp)    => a
     case (NoOp, b)    => b
+    case (Create, _)  => Create
+    case (_, Create)  => Create
     case (Read, Read) => Read
     case (Add, Add)   => Add
     case _            => Write
@@ -26,10 +28,12 @@ sealed trait Op { self =>
 }
 
 object Op {
-  case object NoOp  extends Op
-  case object Write extends Op
-  case object Read  extends Op
-  case object Add   extends Op
+  case object NoOp   extends Op
+  case object Write  extends Op
+  case object Read   extends Op
+  case object Add    extends Op
+  // Write to a key the deploy created itself; it conflicts with any other use of the key.
+  case object Create extends Op
 
   implicit val OpMonoid: Monoid[Op] = new Monoid[Op] {
     def combine(a: Op, b: Op): Op = a + b
@@ -42,17 +46,20 @@ object Op {
   }
 
   def fromIpc(o: ipc.Op): Option[Op] = o.opInstance match {
-    case ipc.Op.OpInstance.Empty    => None
-    case ipc.Op.OpInstance.Write(_) => Some(Write)
-    case ipc.Op.OpInstance.Noop(_)  => Some(NoOp)
-    case ipc.Op.OpInstance.Read(_)  => Some(Read)
-    case ipc.Op.OpInstance.Add(_)   => Some(Add)
+    case ipc.Op.OpInstance.Empty     => None
+    case ipc.Op.OpInstance.Write(_)  => Some(Write)
+    case ipc.Op.OpInstance.Noop(_)   => Some(NoOp)
+    case ipc.Op.OpInstance.Read(_)   => Some(Read)
+    case ipc.Op.OpInstance.Add(_)    => Some(Add)
+    case ipc.Op.OpInstance.Create(_) => Some(Create)
   }
 
   def fromTransform(t: ipc.Transform): Option[Op] = t.transformInstance match {
     case ipc.Transform.TransformInstance.Empty       => None
     case ipc.Transform.TransformInstance.Identity(_) => Some(Read)
     case ipc.Transform.TransformInstance.Write(_)    => Some(Write)
+    // Removing named keys replaces the account or contract rather than adding to it
+    case ipc.Transform.TransformInstance.RemoveKeys(_) => Some(Write)
     // Transform failures should never arise because merging is total
     case ipc.Transform.TransformInstance.Failure(_) => None
     case _                                          => Some(Add) // We treat all types of addition the same (for now)
@@ -327,29 +327,32 @@ object HashSetCasperTestNode {
           blocktime: Long,
           deploys: Seq[Deploy],
           protocolVersion: ProtocolVersion
-      ): F[Either[Throwable, Seq[DeployResult]]] =
+      ): F[Either[Throwable, ipc.ExecResult]] =
         //This function returns the same `DeployResult` for all deploys,
         //regardless of their wasm code. It pretends to have run all the deploys,
         //but it doesn't really; it just returns the same result no matter what.
-        deploys
-          .map { d =>
-            validateNonce(d) match {
-              case 0 =>
-                DeployResult(
-                  ExecutionResult(
-                    ipc.DeployResult.ExecutionResult(Some(getExecutionEffect(d)), None, 10)
-                  )
-                )
-              case 1 =>
-                DeployResult(DeployResult.Value.InvalidNonce(DeployResult.InvalidNonce(d.nonce)))
-              case -1 =>
-                DeployResult(
-                  DeployResult.Value.PreconditionFailure(
-                    DeployResult.PreconditionFailure("Nonce was less then expected.")
-                  )
-                )
-            }
-          }
+        ipc
+          .ExecResult(
+            deploys
+              .map { d =>
+                validateNonce(d) match {
+                  case 0 =>
+                    DeployResult(
+                      ExecutionResult(
+                        ipc.DeployResult.ExecutionResult(Some(getExecutionEffect(d)), None, 10)
+                      )
+                    )
+                  case 1 =>
+                    DeployResult(DeployResult.Value.InvalidNonce(DeployResult.InvalidNonce(d.nonce)))
+                  case -1 =>
+                    DeployResult(
+                      DeployResult.Value.PreconditionFailure(
+                        DeployResult.PreconditionFailure("Nonce was less then expected.")
+                      )
+                    )
+                }
+              }
+          )
           .asRight[Throwable]
           .pure[F]
 
@@ -185,13 +185,16 @@ class ExecEngineUtilTest
                 val opEntry       = OpEntry(Some(key), Some(op))
                 ExecutionEffect(Seq(opEntry), Seq(transforEntry))
               }
-              deploys
-                .map(
-                  d =>
-                    DeployResult(
-                      DeployResult.Value.ExecutionResult(
-                        DeployResult.ExecutionResult(Some(getExecutionEffect(d)), None, 10)
-                      )
+              ipc
+                .ExecResult(
+                  deploys
+                    .map(
+                      d =>
+                        DeployResult(
+                          DeployResult.Value.ExecutionResult(
+                            DeployResult.ExecutionResult(Some(getExecutionEffect(d)), None, 10)
+                          )
+                        )
                     )
                 )
                 .asRight[Throwable]
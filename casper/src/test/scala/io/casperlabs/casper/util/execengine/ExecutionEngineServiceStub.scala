@@ -47,7 +47,7 @@ object ExecutionEngineServiceStub {
           Long,
           Seq[Deploy],
           ProtocolVersion
-      ) => F[Either[Throwable, Seq[DeployResult]]],
+      ) => F[Either[Throwable, ExecResult]],
       commitFunc: (
           ByteString,
           Seq[TransformEntry]
@@ -66,7 +66,7 @@ object ExecutionEngineServiceStub {
         blocktime: Long,
         deploys: Seq[Deploy],
         protocolVersion: ProtocolVersion
-    ): F[Either[Throwable, Seq[DeployResult]]] =
+    ): F[Either[Throwable, ExecResult]] =
       execFunc(prestate, blocktime, deploys, protocolVersion)
     override def commit(
         prestate: ByteString,
@@ -85,7 +85,7 @@ object ExecutionEngineServiceStub {
   def noOpApi[F[_]: Applicative](): ExecutionEngineService[F] =
     mock[F](
       (_, _) => GenesisResult().asRight[Throwable].pure[F],
-      (_, _, _, _) => Seq.empty[DeployResult].asRight[Throwable].pure[F],
+      (_, _, _, _) => ExecResult().asRight[Throwable].pure[F],
       (_, _) =>
         ExecutionEngineService
           .CommitResult(ByteString.EMPTY, Seq.empty[Bond])
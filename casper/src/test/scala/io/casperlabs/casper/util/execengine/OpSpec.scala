@@ -1,5 +1,6 @@
 package io.casperlabs.casper.util.execengine
 
+import io.casperlabs.ipc
 import org.scalacheck.{Arbitrary, Gen}
 
 import org.scalatest.{FlatSpec, Matchers}
@@ -86,17 +87,25 @@ class OpSpec extends FlatSpec with Matchers with PropertyChecks {
       (a ~ b) shouldEqual (a.keySet intersect b.keySet).forall(k => a(k) ~ b(k))
     }
   }
+
+  "Op.fromTransform" should "treat removing named keys as a write" in {
+    val removeKeys = ipc.Transform(
+      ipc.Transform.TransformInstance.RemoveKeys(ipc.TransformRemoveKeys(Seq("counter")))
+    )
+    Op.fromTransform(removeKeys) shouldBe Some(Op.Write)
+  }
 }
 
 object OpSpec {
   object Gens {
-    val noop  = Gen.const(Op.NoOp)
-    val write = Gen.const(Op.Write)
-    val read  = Gen.const(Op.Read)
-    val add   = Gen.const(Op.Add)
-
-    val op           = Gen.oneOf(noop, write, read, add)
-    val nonTrivialOp = Gen.oneOf(write, read, add)
+    val noop   = Gen.const(Op.NoOp)
+    val write  = Gen.const(Op.Write)
+    val read   = Gen.const(Op.Read)
+    val add    = Gen.const(Op.Add)
+    val create = Gen.const(Op.Create)
+
+    val op           = Gen.oneOf(noop, write, read, add, create)
+    val nonTrivialOp = Gen.oneOf(write, read, add, create)
 
     def opMap[Key](keyGen: Gen[Key]): Gen[OpMap[Key]] = Gen.mapOf(Gen.zip(keyGen, op))
   }
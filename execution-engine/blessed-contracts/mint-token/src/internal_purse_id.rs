@@ -40,6 +40,27 @@ impl WithdrawId {
     }
 }
 
+/// Only allows to look up the balance of a purse.
+pub struct BalanceId([u8; 32]);
+
+impl BalanceId {
+    pub fn from_uref(uref: URef) -> Result<Self, PurseIdError> {
+        if !contract_api::is_valid(uref) {
+            return Err(PurseIdError::InvalidURef);
+        }
+
+        if uref.is_readable() {
+            Ok(BalanceId(uref.addr()))
+        } else {
+            Err(PurseIdError::InvalidAccessRights(uref.access_rights()))
+        }
+    }
+
+    pub fn raw_id(&self) -> [u8; 32] {
+        self.0
+    }
+}
+
 pub struct DepositId([u8; 32]);
 
 impl DepositId {
@@ -24,9 +24,12 @@ use cl_std::uref::{AccessRights, URef};
 use cl_std::value::U512;
 
 use capabilities::{ARef, RAWRef};
-use internal_purse_id::{DepositId, WithdrawId};
+use internal_purse_id::{BalanceId, DepositId, WithdrawId};
 use mint::Mint;
 
+/// Set up by the engine in genesis and increased by block rewards.
+const TOTAL_SUPPLY_KEY: &str = "total_supply";
+
 struct CLMint;
 
 impl Mint<ARef<U512>, RAWRef<U512>> for CLMint {
@@ -65,6 +68,15 @@ impl Mint<ARef<U512>, RAWRef<U512>> for CLMint {
     }
 }
 
+impl CLMint {
+    /// Unlike `lookup`, only requires READ rights on the purse.
+    fn balance(&self, p: BalanceId) -> Option<U512> {
+        contract_api::read_local(p.raw_id())
+            .and_then(|key: Key| key.try_into().ok())
+            .map(|balance_uref: RAWRef<U512>| contract_api::read(balance_uref.into()))
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn call() {
     let mint = CLMint;
@@ -79,12 +91,22 @@ pub extern "C" fn call() {
 
         "balance" => {
             let key: URef = contract_api::get_arg(1);
-            let purse_id: WithdrawId = WithdrawId::from_uref(key).unwrap();
-            let balance_uref = mint.lookup(purse_id);
-            let balance: Option<U512> = balance_uref.map(|uref| contract_api::read(uref.into()));
+            let purse_id: BalanceId = BalanceId::from_uref(key).unwrap();
+            let balance: Option<U512> = mint.balance(purse_id);
             contract_api::ret(&balance, &vec![])
         }
 
+        "total_supply" => {
+            let total_supply: Option<U512> = if contract_api::has_uref(TOTAL_SUPPLY_KEY) {
+                contract_api::get_uref(TOTAL_SUPPLY_KEY)
+                    .to_u_ptr()
+                    .map(contract_api::read)
+            } else {
+                None
+            };
+            contract_api::ret(&total_supply, &vec![])
+        }
+
         "transfer" => {
             let source: URef = contract_api::get_arg(1);
             let target: URef = contract_api::get_arg(2);
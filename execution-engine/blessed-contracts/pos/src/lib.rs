@@ -13,6 +13,7 @@ use alloc::vec::Vec;
 
 use cl_std::contract_api;
 use cl_std::key::Key;
+use cl_std::pos_parameters::{BOND_DELAY, MAX_BOND_LEN, MAX_UNBOND_LEN, UNBOND_DELAY};
 use cl_std::uref::URef;
 use cl_std::value::account::{BlockTime, PublicKey, PurseId};
 use cl_std::value::U512;
@@ -25,27 +26,6 @@ use crate::stakes::{ContractStakes, StakesProvider};
 /// tokens that are yet to be paid out.
 const PURSE_KEY: &str = "pos_purse";
 
-/// The time from a bonding request until the bond becomes effective and part of the stake.
-const BOND_DELAY: u64 = 0;
-/// The time from an unbonding request until the stakes are paid out.
-const UNBOND_DELAY: u64 = 0;
-/// The maximum number of pending bonding requests.
-const MAX_BOND_LEN: usize = 100;
-/// The maximum number of pending unbonding requests.
-const MAX_UNBOND_LEN: usize = 1000;
-/// The maximum difference between the largest and the smallest stakes.
-// TODO: Should this be a percentage instead?
-// TODO: Pick a reasonable value.
-const MAX_SPREAD: U512 = U512::MAX;
-/// The maximum increase of stakes in a single bonding request.
-const MAX_INCREASE: U512 = U512::MAX;
-/// The maximum decrease of stakes in a single unbonding request.
-const MAX_DECREASE: U512 = U512::MAX;
-/// The maximum increase of stakes in millionths of the total stakes in a single bonding request.
-const MAX_REL_INCREASE: u64 = 1_000_000_000;
-/// The maximum decrease of stakes in millionths of the total stakes in a single unbonding request.
-const MAX_REL_DECREASE: u64 = 900_000;
-
 /// Enqueues the deploy's creator for becoming a validator. The bond `amount` is paid from the
 /// purse `source`.
 fn bond<Q: QueueProvider, S: StakesProvider>(
@@ -208,7 +188,9 @@ mod tests {
     use crate::error::Result;
     use crate::queue::{Queue, QueueProvider};
     use crate::stakes::{Stakes, StakesProvider};
-    use crate::{bond, step, unbond, BOND_DELAY, UNBOND_DELAY};
+    use cl_std::pos_parameters::{BOND_DELAY, UNBOND_DELAY};
+
+    use crate::{bond, step, unbond};
 
     const KEY1: [u8; 32] = [1; 32];
     const KEY2: [u8; 32] = [2; 32];
@@ -4,14 +4,12 @@ use core::result;
 
 use cl_std::bytesrepr::{self, FromBytes, ToBytes};
 use cl_std::contract_api;
+use cl_std::pos_parameters::{BONDING_QUEUE_KEY, UNBONDING_QUEUE_KEY};
 use cl_std::value::account::{BlockTime, PublicKey};
 use cl_std::value::{Value, U512};
 
 use crate::error::{Error, Result};
 
-const BONDING_KEY: u8 = 1;
-const UNBONDING_KEY: u8 = 2;
-
 /// A pending entry in the bonding or unbonding queue.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct QueueEntry {
@@ -77,22 +75,22 @@ pub struct QueueLocal;
 impl QueueProvider for QueueLocal {
     /// Reads bonding queue from the local state of the contract.
     fn read_bonding() -> Queue {
-        contract_api::read_local(BONDING_KEY).unwrap_or_default()
+        contract_api::read_local(BONDING_QUEUE_KEY).unwrap_or_default()
     }
 
     /// Reads unbonding queue from the local state of the contract.
     fn read_unbonding() -> Queue {
-        contract_api::read_local(UNBONDING_KEY).unwrap_or_default()
+        contract_api::read_local(UNBONDING_QUEUE_KEY).unwrap_or_default()
     }
 
     /// Writes bonding queue to the local state of the contract.
     fn write_bonding(queue: &Queue) {
-        contract_api::write_local(BONDING_KEY, queue);
+        contract_api::write_local(BONDING_QUEUE_KEY, queue);
     }
 
     /// Writes unbonding queue to the local state of the contract.
     fn write_unbonding(queue: &Queue) {
-        contract_api::write_local(UNBONDING_KEY, queue);
+        contract_api::write_local(UNBONDING_QUEUE_KEY, queue);
     }
 }
 
@@ -4,12 +4,13 @@ use core::fmt::Write;
 
 use cl_std::contract_api;
 use cl_std::key::Key;
+use cl_std::pos_parameters::{
+    MAX_DECREASE, MAX_INCREASE, MAX_REL_DECREASE, MAX_REL_INCREASE, MAX_SPREAD,
+};
 use cl_std::value::{account::PublicKey, U512};
 
 use crate::error::{Error, Result};
 
-use super::{MAX_DECREASE, MAX_INCREASE, MAX_REL_DECREASE, MAX_REL_INCREASE, MAX_SPREAD};
-
 pub trait StakesProvider {
     fn read() -> Result<Stakes>;
     fn write(stakes: &Stakes);
@@ -182,6 +183,7 @@ impl Stakes {
 
 #[cfg(test)]
 mod tests {
+    use cl_std::pos_parameters::{MAX_REL_DECREASE, MAX_REL_INCREASE};
     use cl_std::value::{account::PublicKey, U512};
 
     use crate::error::Error;
@@ -229,7 +231,7 @@ mod tests {
             Err(Error::BondTooLarge),
             stakes.validate_bonding(
                 &PublicKey::new(KEY1),
-                U512::from(crate::MAX_REL_INCREASE * total / 1_000_000 + 1)
+                U512::from(MAX_REL_INCREASE * total / 1_000_000 + 1)
             ),
             "Successfully bonded more than the maximum amount."
         );
@@ -237,7 +239,7 @@ mod tests {
             Ok(()),
             stakes.validate_bonding(
                 &PublicKey::new(KEY1),
-                U512::from(crate::MAX_REL_INCREASE * total / 1_000_000)
+                U512::from(MAX_REL_INCREASE * total / 1_000_000)
             ),
             "Failed to bond the maximum amount."
         );
@@ -280,15 +282,15 @@ mod tests {
             Err(Error::UnbondTooLarge),
             stakes.unbond(
                 &PublicKey::new(KEY1),
-                Some(U512::from(crate::MAX_REL_DECREASE * total / 1_000_000 + 1))
+                Some(U512::from(MAX_REL_DECREASE * total / 1_000_000 + 1))
             ),
             "Successfully unbonded more than the maximum amount."
         );
         assert_eq!(
-            Ok(U512::from(crate::MAX_REL_DECREASE * total / 1_000_000)),
+            Ok(U512::from(MAX_REL_DECREASE * total / 1_000_000)),
             stakes.unbond(
                 &PublicKey::new(KEY1),
-                Some(U512::from(crate::MAX_REL_DECREASE * total / 1_000_000))
+                Some(U512::from(MAX_REL_DECREASE * total / 1_000_000))
             ),
             "Failed to unbond the maximum amount."
         );
@@ -0,0 +1,240 @@
+//! `#[derive(ToBytes, FromBytes)]` for the `bytesrepr` traits of `casperlabs-contract-ffi`.
+//!
+//! Fields are serialized one after another in declaration order. Enums are prefixed with the
+//! index of the variant as a `u8`, followed by the fields of the variant, so reordering variants
+//! or fields changes the serialized form.
+//!
+//! The generated code refers to the contract library as `common`. Crates which import it under
+//! another name say so with e.g. `#[bytesrepr(crate_path = "cl_std")]`.
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Fields, Generics, Ident, Lit, Meta,
+    NestedMeta, Path, Type,
+};
+
+const ATTRIBUTE: &str = "bytesrepr";
+const DEFAULT_CRATE: &str = "common";
+const MAX_VARIANTS: usize = 256;
+
+#[proc_macro_derive(ToBytes, attributes(bytesrepr))]
+pub fn derive_to_bytes(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_to_bytes(&input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+#[proc_macro_derive(FromBytes, attributes(bytesrepr))]
+pub fn derive_from_bytes(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_bytes(&input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+fn expand_to_bytes(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let bytesrepr = bytesrepr_path(input)?;
+    let name = &input.ident;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = bind_fields(&data.fields);
+            let pattern = fields.pattern(quote!(#name));
+            let serialize = fields.serialize(&bytesrepr, quote!(&()));
+            quote! {
+                let #pattern = self;
+                #serialize
+            }
+        }
+        Data::Enum(data) => {
+            check_variant_count(input, data.variants.len())?;
+            let arms = data.variants.iter().enumerate().map(|(index, variant)| {
+                let tag = index as u8;
+                let variant_name = &variant.ident;
+                let fields = bind_fields(&variant.fields);
+                let pattern = fields.pattern(quote!(#name::#variant_name));
+                let serialize = fields.serialize(&bytesrepr, quote!(&#tag));
+                quote! { #pattern => { #serialize } }
+            });
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => return Err(unsupported_union(input)),
+    };
+
+    let generics = add_bounds(&input.generics, parse_quote!(#bytesrepr::ToBytes));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #bytesrepr::ToBytes for #name #ty_generics #where_clause {
+            fn to_bytes(
+                &self,
+            ) -> Result<#bytesrepr::derive_support::Vec<u8>, #bytesrepr::Error> {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_from_bytes(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let bytesrepr = bytesrepr_path(input)?;
+    let name = &input.ident;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = bind_fields(&data.fields);
+            fields.deserialize(&bytesrepr, quote!(#name), quote!(bytes))
+        }
+        Data::Enum(data) => {
+            check_variant_count(input, data.variants.len())?;
+            let arms = data.variants.iter().enumerate().map(|(index, variant)| {
+                let tag = index as u8;
+                let variant_name = &variant.ident;
+                let fields = bind_fields(&variant.fields);
+                let deserialize =
+                    fields.deserialize(&bytesrepr, quote!(#name::#variant_name), quote!(rest));
+                quote! { #tag => { #deserialize } }
+            });
+            quote! {
+                let (tag, rest): (u8, &[u8]) = #bytesrepr::FromBytes::from_bytes(bytes)?;
+                match tag {
+                    #(#arms)*
+                    _ => Err(#bytesrepr::Error::FormattingError),
+                }
+            }
+        }
+        Data::Union(_) => return Err(unsupported_union(input)),
+    };
+
+    let generics = add_bounds(&input.generics, parse_quote!(#bytesrepr::FromBytes));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #bytesrepr::FromBytes for #name #ty_generics #where_clause {
+            fn from_bytes(bytes: &[u8]) -> Result<(Self, &[u8]), #bytesrepr::Error> {
+                #body
+            }
+        }
+    })
+}
+
+/// The fields of a struct or variant, along with the names they are bound to. Fields are bound
+/// to `field_<index>` whether they are named or not, so they can't shadow the generated locals.
+struct BoundFields<'a> {
+    fields: &'a Fields,
+    bindings: Vec<(Ident, &'a Type)>,
+}
+
+fn bind_fields(fields: &Fields) -> BoundFields {
+    let bindings = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let binding = Ident::new(&format!("field_{}", index), Span::call_site());
+            (binding, &field.ty)
+        })
+        .collect();
+    BoundFields { fields, bindings }
+}
+
+impl<'a> BoundFields<'a> {
+    /// Pattern binding every field, or the expression building the value from the bindings.
+    fn pattern(&self, path: TokenStream2) -> TokenStream2 {
+        let names = self.bindings.iter().map(|(name, _)| name);
+        match self.fields {
+            Fields::Named(fields) => {
+                let members = fields.named.iter().map(|field| &field.ident);
+                quote!(#path { #(#members: #names),* })
+            }
+            Fields::Unnamed(_) => quote!(#path(#(#names),*)),
+            Fields::Unit => path,
+        }
+    }
+
+    /// Serializes `prefix` followed by the bound fields.
+    fn serialize(&self, bytesrepr: &Path, prefix: TokenStream2) -> TokenStream2 {
+        let names = self.bindings.iter().map(|(name, _)| name);
+        quote! {
+            let mut result = #bytesrepr::ToBytes::to_bytes(#prefix)?;
+            #(result.append(&mut #bytesrepr::ToBytes::to_bytes(#names)?);)*
+            Ok(result)
+        }
+    }
+
+    /// Deserializes the fields from `input` in order and builds the value at `path`.
+    fn deserialize(
+        &self,
+        bytesrepr: &Path,
+        path: TokenStream2,
+        input: TokenStream2,
+    ) -> TokenStream2 {
+        let names = self.bindings.iter().map(|(name, _)| name);
+        let types = self.bindings.iter().map(|(_, ty)| ty);
+        let value = self.pattern(path);
+        quote! {
+            let rest: &[u8] = #input;
+            #(let (#names, rest): (#types, &[u8]) = #bytesrepr::FromBytes::from_bytes(rest)?;)*
+            Ok((#value, rest))
+        }
+    }
+}
+
+/// Path of the `bytesrepr` module, taken from the `#[bytesrepr(crate_path = "...")]` attribute.
+fn bytesrepr_path(input: &DeriveInput) -> syn::Result<Path> {
+    let mut krate: Path = syn::parse_str(DEFAULT_CRATE)?;
+    for attr in &input.attrs {
+        if attr.path.segments.len() != 1 || attr.path.segments[0].ident != ATTRIBUTE {
+            continue;
+        }
+        let invalid = || {
+            syn::Error::new_spanned(
+                attr,
+                "expected `#[bytesrepr(crate_path = \"path::to::crate\")]`",
+            )
+        };
+        let nested = match attr.parse_meta()? {
+            Meta::List(list) => list.nested,
+            _ => return Err(invalid()),
+        };
+        for meta in nested {
+            match meta {
+                NestedMeta::Meta(Meta::NameValue(ref name_value))
+                    if name_value.ident == "crate_path" =>
+                {
+                    match &name_value.lit {
+                        Lit::Str(path) => krate = path.parse()?,
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(invalid()),
+            }
+        }
+    }
+    Ok(parse_quote!(#krate::bytesrepr))
+}
+
+fn add_bounds(generics: &Generics, bound: syn::TypeParamBound) -> Generics {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(bound.clone());
+    }
+    generics
+}
+
+fn check_variant_count(input: &DeriveInput, count: usize) -> syn::Result<()> {
+    if count > MAX_VARIANTS {
+        Err(syn::Error::new_spanned(
+            &input.ident,
+            "bytesrepr tags variants with a u8, so enums can have at most 256 variants",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn unsupported_union(input: &DeriveInput) -> syn::Error {
+    syn::Error::new_spanned(&input.ident, "bytesrepr can't be derived for unions")
+}
@@ -0,0 +1,54 @@
+#![feature(test)]
+extern crate casperlabs_engine_grpc_server;
+extern crate common;
+extern crate shared;
+extern crate test;
+
+use std::convert::TryInto;
+
+use test::black_box;
+use test::Bencher;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::TransformEntry;
+use casperlabs_engine_grpc_server::engine_server::mappings::CommitTransforms;
+use common::key::Key;
+use common::value::Value;
+use shared::transform::Transform;
+
+const ENTRY_COUNT: u8 = 100;
+const VALUE_SIZE: usize = 4096;
+
+fn transform_entries() -> Vec<TransformEntry> {
+    (0..ENTRY_COUNT)
+        .map(|i| {
+            let key = Key::Hash([i; 32]);
+            let transform = Transform::Write(Value::ByteArray(vec![i; VALUE_SIZE]));
+            (key, transform).into()
+        })
+        .collect()
+}
+
+#[bench]
+fn parse_commit_transforms_borrowed(b: &mut Bencher) {
+    let entries = transform_entries();
+    b.iter(|| {
+        let result: CommitTransforms = black_box(entries.as_slice()).try_into().unwrap();
+        result
+    });
+}
+
+// Includes the cost of cloning the entries; subtract `clone_transform_entries`.
+#[bench]
+fn parse_commit_transforms_owned(b: &mut Bencher) {
+    let entries = transform_entries();
+    b.iter(|| {
+        let result: CommitTransforms = black_box(entries.clone()).try_into().unwrap();
+        result
+    });
+}
+
+#[bench]
+fn clone_transform_entries(b: &mut Bencher) {
+    let entries = transform_entries();
+    b.iter(|| black_box(&entries).clone());
+}
@@ -0,0 +1,587 @@
+//! Server configuration.
+//!
+//! Settings are layered, each layer overriding the previous one:
+//! built-in defaults, then an optional TOML file, then `CL_ENGINE_*`
+//! environment variables, and finally command line arguments.
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+
+use common::value::account::PublicKey;
+use execution_engine::engine_state::engine_config::{
+    EngineConfig, DEFAULT_MAX_DEPLOY_ARGS_SIZE, DEFAULT_MAX_DEPLOY_SIZE, DEFAULT_MODULE_CACHE_SIZE,
+    DEFAULT_TRACKING_COPY_CACHE_SIZE,
+};
+use shared::gas::Gas;
+use shared::logging::metric_sampling::MetricSampling;
+#[cfg(unix)]
+use shared::logging::syslog::{Facility, SyslogTarget};
+use shared::utils;
+
+use engine_server::throttle::MethodLimits;
+
+/// Prefix of the environment variables overriding the configuration.
+pub const ENV_PREFIX: &str = "CL_ENGINE_";
+
+// 750 GiB = 805306368000 bytes
+// page size on x86_64 linux = 4096 bytes
+// 805306368000 / 4096 = 196608000
+pub const DEFAULT_PAGES: usize = 196_608_000;
+
+/// Storage metrics are reported every 10 minutes by default.
+pub const DEFAULT_STORAGE_METRICS_INTERVAL_SECS: u64 = 600;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Toml(PathBuf, toml::de::Error),
+    InvalidEnvVar { name: String, value: String },
+    UnknownEnvVar(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, error) => {
+                write!(f, "could not read config file {:?}: {}", path, error)
+            }
+            ConfigError::Toml(path, error) => {
+                write!(f, "could not parse config file {:?}: {}", path, error)
+            }
+            ConfigError::InvalidEnvVar { name, value } => write!(
+                f,
+                "invalid value {:?} of environment variable {}",
+                value, name
+            ),
+            ConfigError::UnknownEnvVar(name) => write!(f, "unknown environment variable {}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Path of the Unix socket, or `host:port` to listen on TCP.
+    pub socket: Option<String>,
+    /// Chainspec manifest of the network, see `shared::chainspec::Chainspec`; the engine's built-in
+    /// protocol versions are used if not set.
+    pub chainspec: Option<PathBuf>,
+    pub storage: StorageConfig,
+    pub logging: LoggingConfig,
+    pub cache: CacheConfig,
+    pub gas: GasConfig,
+    pub limits: LimitsConfig,
+    pub admin: AdminConfig,
+    pub metrics: MetricsConfig,
+    pub debug: DebugConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Data directory; `~/.casperlabs` if not set.
+    pub data_dir: Option<PathBuf>,
+    /// Max number of pages used by lmdb's mmap.
+    pub pages: usize,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            data_dir: None,
+            pages: DEFAULT_PAGES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// One of `fatal`, `error`, `warning`, `info` or `debug`.
+    pub level: Option<String>,
+    /// Also send log messages to `syslog` or `journald`; stdout only if not set. Unix only.
+    #[cfg(unix)]
+    pub syslog: Option<SyslogTarget>,
+    #[cfg(unix)]
+    pub syslog_facility: Facility,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub tracking_copy_size: usize,
+    /// Max number of preprocessed modules kept in memory; 0 disables the module cache.
+    pub modules: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            tracking_copy_size: DEFAULT_TRACKING_COPY_CACHE_SIZE,
+            modules: DEFAULT_MODULE_CACHE_SIZE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GasConfig {
+    pub max_deploy_gas_limit: Option<u64>,
+    /// Wall-clock limit of a single deploy, in milliseconds, whatever its gas limit.
+    pub deploy_timeout_millis: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_pending_exec: usize,
+    pub max_pending_commit: usize,
+    pub max_pending_query: usize,
+    pub max_pending_validate: usize,
+    /// Max size in bytes of a deploy's session code, arguments and address.
+    pub max_deploy_size: usize,
+    /// Max size in bytes of a deploy's arguments.
+    pub max_deploy_args_size: usize,
+    /// Max size in bytes of the requests the server accepts.
+    pub max_receive_message_size: usize,
+    /// Max size in bytes of the responses the server sends.
+    pub max_send_message_size: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        let limits = MethodLimits::default();
+        LimitsConfig {
+            max_pending_exec: limits.exec,
+            max_pending_commit: limits.commit,
+            max_pending_query: limits.query,
+            max_pending_validate: limits.validate,
+            max_deploy_size: DEFAULT_MAX_DEPLOY_SIZE,
+            max_deploy_args_size: DEFAULT_MAX_DEPLOY_ARGS_SIZE,
+            max_receive_message_size: limits.max_receive_message_size,
+            max_send_message_size: limits.max_send_message_size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// File containing the shared secret required by administrative RPCs.
+    pub secret_file: Option<PathBuf>,
+    /// Accounts whose deploys are rejected before execution.
+    pub frozen_accounts: Vec<AccountKey>,
+}
+
+/// Public key of an account, written as 64 hex digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountKey(pub PublicKey);
+
+impl FromStr for AccountKey {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        utils::decode_hex_32(input)
+            .map(|bytes| AccountKey(PublicKey::new(bytes)))
+            .map_err(|_| format!("invalid account public key {:?}", input))
+    }
+}
+
+impl<'de> Deserialize<'de> for AccountKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        hex.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Seconds between reports of the database size, stored roots and trie shape; 0 disables them.
+    pub storage_interval_secs: u64,
+    /// Only every Nth duration of each metric is reported; 1 reports all of them.
+    pub duration_sample_every: u64,
+    /// Further tags of a metric are reported as `other`; unbounded if not set.
+    pub max_tags_per_metric: Option<usize>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            storage_interval_secs: DEFAULT_STORAGE_METRICS_INTERVAL_SECS,
+            duration_sample_every: 1,
+            max_tags_per_metric: None,
+        }
+    }
+}
+
+impl MetricsConfig {
+    pub fn storage_interval(&self) -> Option<Duration> {
+        if self.storage_interval_secs == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(self.storage_interval_secs))
+        }
+    }
+
+    pub fn sampling(&self) -> MetricSampling {
+        MetricSampling {
+            duration_sample_every: self.duration_sample_every.max(1),
+            max_tags_per_metric: self.max_tags_per_metric,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DebugConfig {
+    /// Whether exec responses carry the state root of each deploy's effects committed alone.
+    pub deploy_state_roots: bool,
+}
+
+impl Config {
+    /// Reads the configuration from a TOML file. Missing entries take default values.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let contents =
+            fs::read_to_string(path).map_err(|error| ConfigError::Io(path.to_owned(), error))?;
+        Config::from_toml(&contents).map_err(|error| ConfigError::Toml(path.to_owned(), error))
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Overrides settings with the `CL_ENGINE_*` variables of the process environment.
+    pub fn apply_env(&mut self) -> Result<(), ConfigError> {
+        self.apply_vars(std::env::vars())
+    }
+
+    /// Overrides settings with the `CL_ENGINE_*` entries of `vars`; other entries are ignored.
+    pub fn apply_vars<I>(&mut self, vars: I) -> Result<(), ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        for (name, value) in vars {
+            let suffix = match name.get(..ENV_PREFIX.len()) {
+                Some(prefix) if prefix == ENV_PREFIX => &name[ENV_PREFIX.len()..],
+                _ => continue,
+            };
+            let invalid = || ConfigError::InvalidEnvVar {
+                name: name.clone(),
+                value: value.clone(),
+            };
+            match suffix {
+                "SOCKET" => self.socket = Some(value.clone()),
+                "CHAINSPEC" => self.chainspec = Some(PathBuf::from(&value)),
+                "DATA_DIR" => self.storage.data_dir = Some(PathBuf::from(&value)),
+                "PAGES" => self.storage.pages = parse(&value).ok_or_else(invalid)?,
+                "LOG_LEVEL" => self.logging.level = Some(value.clone()),
+                #[cfg(unix)]
+                "LOG_SYSLOG" => self.logging.syslog = Some(parse(&value).ok_or_else(invalid)?),
+                #[cfg(unix)]
+                "LOG_SYSLOG_FACILITY" => {
+                    self.logging.syslog_facility = parse(&value).ok_or_else(invalid)?
+                }
+                "TRACKING_COPY_CACHE_SIZE" => {
+                    self.cache.tracking_copy_size = parse(&value).ok_or_else(invalid)?
+                }
+                "MODULE_CACHE_SIZE" => self.cache.modules = parse(&value).ok_or_else(invalid)?,
+                "MAX_DEPLOY_GAS_LIMIT" => {
+                    self.gas.max_deploy_gas_limit = Some(parse(&value).ok_or_else(invalid)?)
+                }
+                "DEPLOY_TIMEOUT_MILLIS" => {
+                    self.gas.deploy_timeout_millis = Some(parse(&value).ok_or_else(invalid)?)
+                }
+                "MAX_PENDING_EXEC" => {
+                    self.limits.max_pending_exec = parse(&value).ok_or_else(invalid)?
+                }
+                "MAX_PENDING_COMMIT" => {
+                    self.limits.max_pending_commit = parse(&value).ok_or_else(invalid)?
+                }
+                "MAX_PENDING_QUERY" => {
+                    self.limits.max_pending_query = parse(&value).ok_or_else(invalid)?
+                }
+                "MAX_PENDING_VALIDATE" => {
+                    self.limits.max_pending_validate = parse(&value).ok_or_else(invalid)?
+                }
+                "MAX_DEPLOY_SIZE" => {
+                    self.limits.max_deploy_size = parse(&value).ok_or_else(invalid)?
+                }
+                "MAX_DEPLOY_ARGS_SIZE" => {
+                    self.limits.max_deploy_args_size = parse(&value).ok_or_else(invalid)?
+                }
+                "MAX_RECEIVE_MESSAGE_SIZE" => {
+                    self.limits.max_receive_message_size = parse(&value).ok_or_else(invalid)?
+                }
+                "MAX_SEND_MESSAGE_SIZE" => {
+                    self.limits.max_send_message_size = parse(&value).ok_or_else(invalid)?
+                }
+                "ADMIN_SECRET_FILE" => self.admin.secret_file = Some(PathBuf::from(&value)),
+                "FROZEN_ACCOUNTS" => {
+                    self.admin.frozen_accounts = value
+                        .split(',')
+                        .filter(|hex| !hex.trim().is_empty())
+                        .map(parse)
+                        .collect::<Option<Vec<AccountKey>>>()
+                        .ok_or_else(invalid)?
+                }
+                "STORAGE_METRICS_INTERVAL_SECS" => {
+                    self.metrics.storage_interval_secs = parse(&value).ok_or_else(invalid)?
+                }
+                "METRICS_DURATION_SAMPLE_EVERY" => {
+                    self.metrics.duration_sample_every = parse(&value).ok_or_else(invalid)?
+                }
+                "METRICS_MAX_TAGS_PER_METRIC" => {
+                    self.metrics.max_tags_per_metric = Some(parse(&value).ok_or_else(invalid)?)
+                }
+                "DEBUG_DEPLOY_STATE_ROOTS" => {
+                    self.debug.deploy_state_roots = parse(&value).ok_or_else(invalid)?
+                }
+                _ => return Err(ConfigError::UnknownEnvVar(name.clone())),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn engine_config(&self) -> EngineConfig {
+        EngineConfig {
+            tracking_copy_cache_size: self.cache.tracking_copy_size,
+            module_cache_size: self.cache.modules,
+            max_deploy_gas_limit: self.gas.max_deploy_gas_limit.map(Gas::new),
+            deploy_timeout: self.gas.deploy_timeout_millis.map(Duration::from_millis),
+            max_deploy_size: self.limits.max_deploy_size,
+            max_deploy_args_size: self.limits.max_deploy_args_size,
+            deploy_state_roots: self.debug.deploy_state_roots,
+            frozen_accounts: self
+                .admin
+                .frozen_accounts
+                .iter()
+                .map(|account| account.0)
+                .collect(),
+        }
+    }
+
+    pub fn method_limits(&self) -> MethodLimits {
+        MethodLimits {
+            exec: self.limits.max_pending_exec,
+            commit: self.limits.max_pending_commit,
+            query: self.limits.max_pending_query,
+            validate: self.limits.max_pending_validate,
+            max_receive_message_size: self.limits.max_receive_message_size,
+            max_send_message_size: self.limits.max_send_message_size,
+            ..MethodLimits::default()
+        }
+    }
+}
+
+fn parse<T: FromStr>(value: &str) -> Option<T> {
+    value.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use common::value::account::PublicKey;
+    use shared::gas::Gas;
+    use shared::logging::metric_sampling::MetricSampling;
+    #[cfg(unix)]
+    use shared::logging::syslog::{Facility, SyslogTarget};
+
+    use super::{AccountKey, Config, ConfigError, DEFAULT_PAGES};
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn should_fill_missing_entries_with_defaults() {
+        let config = Config::from_toml(
+            r#"
+            socket = "/tmp/engine.sock"
+
+            [storage]
+            data_dir = "/var/lib/casperlabs"
+
+            [gas]
+            max_deploy_gas_limit = 1000000
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.socket, Some("/tmp/engine.sock".to_string()));
+        assert_eq!(config.storage.pages, DEFAULT_PAGES);
+        assert_eq!(config.gas.max_deploy_gas_limit, Some(1_000_000));
+        assert_eq!(config.limits, Default::default());
+        assert_eq!(
+            config.engine_config().max_deploy_gas_limit,
+            Some(Gas::new(1_000_000))
+        );
+        assert_eq!(config.engine_config().deploy_timeout, None);
+    }
+
+    #[test]
+    fn deploy_timeout_should_be_in_millis() {
+        let config = Config::from_toml("[gas]\ndeploy_timeout_millis = 1500").unwrap();
+        assert_eq!(
+            config.engine_config().deploy_timeout,
+            Some(Duration::from_millis(1500))
+        );
+    }
+
+    #[test]
+    fn should_reject_unknown_entries() {
+        assert!(Config::from_toml("[storage]\npagez = 1").is_err());
+    }
+
+    #[test]
+    fn env_vars_should_override_file() {
+        let mut config = Config::from_toml("[storage]\npages = 10").unwrap();
+        config
+            .apply_vars(vars(&[
+                ("CL_ENGINE_PAGES", "20"),
+                ("CL_ENGINE_MAX_PENDING_QUERY", "5"),
+                ("PATH", "/usr/bin"),
+            ]))
+            .unwrap();
+        assert_eq!(config.storage.pages, 20);
+        assert_eq!(config.method_limits().query, 5);
+
+        config
+            .apply_vars(vars(&[("CL_ENGINE_MAX_RECEIVE_MESSAGE_SIZE", "1024")]))
+            .unwrap();
+        assert_eq!(config.method_limits().max_receive_message_size, 1024);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn syslog_should_be_off_by_default() {
+        let mut config = Config::default();
+        assert_eq!(config.logging.syslog, None);
+        assert_eq!(config.logging.syslog_facility, Facility::Daemon);
+
+        let config_file =
+            Config::from_toml("[logging]\nsyslog = \"journald\"\nsyslog_facility = \"local2\"")
+                .unwrap();
+        assert_eq!(config_file.logging.syslog, Some(SyslogTarget::Journald));
+        assert_eq!(config_file.logging.syslog_facility, Facility::Local2);
+
+        config
+            .apply_vars(vars(&[
+                ("CL_ENGINE_LOG_SYSLOG", "syslog"),
+                ("CL_ENGINE_LOG_SYSLOG_FACILITY", "user"),
+            ]))
+            .unwrap();
+        assert_eq!(config.logging.syslog, Some(SyslogTarget::Syslog));
+        assert_eq!(config.logging.syslog_facility, Facility::User);
+        assert_matches_invalid(
+            config.apply_vars(vars(&[("CL_ENGINE_LOG_SYSLOG_FACILITY", "kern")])),
+        );
+    }
+
+    #[test]
+    fn zero_storage_metrics_interval_should_disable_reports() {
+        let config = Config::from_toml("[metrics]\nstorage_interval_secs = 0").unwrap();
+        assert_eq!(config.metrics.storage_interval(), None);
+        assert!(Config::default().metrics.storage_interval().is_some());
+    }
+
+    #[test]
+    fn metrics_should_not_be_sampled_by_default() {
+        assert_eq!(
+            Config::default().metrics.sampling(),
+            MetricSampling::default()
+        );
+
+        let mut config = Config::from_toml("[metrics]\nduration_sample_every = 0").unwrap();
+        assert_eq!(config.metrics.sampling().duration_sample_every, 1);
+
+        config
+            .apply_vars(vars(&[
+                ("CL_ENGINE_METRICS_DURATION_SAMPLE_EVERY", "100"),
+                ("CL_ENGINE_METRICS_MAX_TAGS_PER_METRIC", "16"),
+            ]))
+            .unwrap();
+        assert_eq!(
+            config.metrics.sampling(),
+            MetricSampling {
+                duration_sample_every: 100,
+                max_tags_per_metric: Some(16),
+            }
+        );
+    }
+
+    #[test]
+    fn debug_env_vars_should_be_booleans() {
+        let mut config = Config::default();
+        assert!(!config.engine_config().deploy_state_roots);
+        config
+            .apply_vars(vars(&[("CL_ENGINE_DEBUG_DEPLOY_STATE_ROOTS", "true")]))
+            .unwrap();
+        assert!(config.engine_config().deploy_state_roots);
+        assert_matches_invalid(
+            config.apply_vars(vars(&[("CL_ENGINE_DEBUG_DEPLOY_STATE_ROOTS", "yes")])),
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_env_vars() {
+        let mut config = Config::default();
+        assert_matches_invalid(config.apply_vars(vars(&[("CL_ENGINE_PAGES", "many")])));
+    }
+
+    #[test]
+    fn should_reject_unknown_env_vars() {
+        let mut config = Config::default();
+        match config.apply_vars(vars(&[("CL_ENGINE_PAGEZ", "1")])) {
+            Err(ConfigError::UnknownEnvVar(name)) => assert_eq!(name, "CL_ENGINE_PAGEZ"),
+            other => panic!("expected UnknownEnvVar, got {:?}", other),
+        }
+        assert!(config.apply_vars(vars(&[("OTHER_PAGEZ", "1")])).is_ok());
+    }
+
+    #[test]
+    fn frozen_accounts_should_be_hex_public_keys() {
+        let hex_a = "01".repeat(32);
+        let hex_b = "0A".repeat(32);
+        let config =
+            Config::from_toml(&format!("[admin]\nfrozen_accounts = [\"{}\"]", hex_a)).unwrap();
+        assert_eq!(
+            config.engine_config().frozen_accounts,
+            vec![PublicKey::new([1u8; 32])].into_iter().collect()
+        );
+        assert!(Config::from_toml("[admin]\nfrozen_accounts = [\"01\"]").is_err());
+
+        let mut config = Config::default();
+        config
+            .apply_vars(vars(&[(
+                "CL_ENGINE_FROZEN_ACCOUNTS",
+                &format!("{}, {}", hex_a, hex_b),
+            )]))
+            .unwrap();
+        assert_eq!(
+            config.admin.frozen_accounts,
+            vec![
+                AccountKey(PublicKey::new([1u8; 32])),
+                AccountKey(PublicKey::new([10u8; 32]))
+            ]
+        );
+        assert_matches_invalid(config.apply_vars(vars(&[("CL_ENGINE_FROZEN_ACCOUNTS", "zz")])));
+    }
+
+    fn assert_matches_invalid(result: Result<(), ConfigError>) {
+        match result {
+            Err(ConfigError::InvalidEnvVar { .. }) => (),
+            other => panic!("expected InvalidEnvVar, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use shared::logging;
+
+/// Name of the request metadata entry carrying the admin secret.
+pub const ADMIN_TOKEN_METADATA_KEY: &str = "x-casperlabs-admin-token";
+
+/// Guards administrative RPCs (upgrades, log level changes, pruning, etc.).
+///
+/// Read and execution paths are never subject to this check. When no secret
+/// is configured at server start every administrative call is rejected.
+#[derive(Clone, Default)]
+pub struct AdminAuth {
+    secret: Option<Vec<u8>>,
+}
+
+impl AdminAuth {
+    pub fn new(secret: Option<String>) -> AdminAuth {
+        AdminAuth {
+            secret: secret
+                .filter(|secret| !secret.is_empty())
+                .map(String::into_bytes),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.secret.is_some()
+    }
+
+    /// Checks the admin token passed in the request metadata against the configured secret.
+    pub fn authorize(&self, request_options: &grpc::RequestOptions) -> Result<(), grpc::Error> {
+        let secret = match self.secret {
+            Some(ref secret) => secret,
+            None => {
+                return Err(grpc_error(
+                    grpc::GrpcStatus::PermissionDenied,
+                    "administrative RPCs are disabled",
+                ))
+            }
+        };
+        match request_options.metadata.get(ADMIN_TOKEN_METADATA_KEY) {
+            Some(token) if constant_time_eq(token, secret) => Ok(()),
+            Some(_) => Err(grpc_error(
+                grpc::GrpcStatus::PermissionDenied,
+                "invalid admin token",
+            )),
+            None => Err(grpc_error(
+                grpc::GrpcStatus::Unauthenticated,
+                "missing admin token",
+            )),
+        }
+    }
+}
+
+impl std::fmt::Debug for AdminAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Never print the secret itself.
+        write!(f, "AdminAuth {{ enabled: {} }}", self.is_enabled())
+    }
+}
+
+fn grpc_error(status: grpc::GrpcStatus, message: &str) -> grpc::Error {
+    logging::log_warning(message);
+    grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: status as i32,
+        grpc_message: message.to_owned(),
+    })
+}
+
+/// Compares two byte slices without short-circuiting on the first difference.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{constant_time_eq, AdminAuth};
+
+    #[test]
+    fn empty_secret_disables_admin_rpcs() {
+        assert!(!AdminAuth::new(None).is_enabled());
+        assert!(!AdminAuth::new(Some(String::new())).is_enabled());
+        assert!(AdminAuth::new(Some("secret".to_string())).is_enabled());
+    }
+
+    #[test]
+    fn requests_without_token_are_rejected() {
+        let auth = AdminAuth::new(Some("secret".to_string()));
+        assert!(auth.authorize(&Default::default()).is_err());
+        assert!(AdminAuth::new(None).authorize(&Default::default()).is_err());
+    }
+
+    #[test]
+    fn compares_tokens() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+    }
+}
@@ -1,68 +1,195 @@
 use std::collections::{BTreeMap, HashMap};
 use std::convert::{TryFrom, TryInto};
-use std::fmt::Display;
+use std::fmt::{self, Display};
 use std::string::ToString;
 
 use protobuf::ProtobufEnum;
 
+use common::bytesrepr::ToBytes;
 use common::uref::URef;
 use common::value::account::{
     AccountActivity, ActionThresholds, AssociatedKeys, BlockTime, PublicKey, PurseId, Weight,
 };
 use common::value::U512;
 use engine_server::{ipc, state};
-use execution_engine::engine_state::error::{Error as EngineError, RootNotFound};
-use execution_engine::engine_state::execution_effect::ExecutionEffect;
+use execution_engine::engine_state::error::{Error as EngineError, ExecuteError, RootNotFound};
+use execution_engine::engine_state::execute_request::{DeployItem, InvalidNoncePolicy};
+use execution_engine::engine_state::execution_effect::{
+    BatchedEffects, ContractEvent, ExecutionEffect, TransferEvent,
+};
 use execution_engine::engine_state::execution_result::ExecutionResult;
 use execution_engine::engine_state::op::Op;
+use execution_engine::engine_state::slashing::{Slash, SlashRequest, SlashResult};
+use execution_engine::engine_state::staking::{StakingCheck, StakingCheckResult, StakingRequest};
+use execution_engine::engine_state::upgrade::{SystemContract, UpgradeRequest, UpgradeResult};
 use execution_engine::execution::Error as ExecutionError;
 use execution_engine::utils;
+use shared::gas::Gas;
 use shared::logging;
 use shared::logging::log_level;
+use shared::motes::{ConversionRate, Motes};
 use shared::newtypes::Blake2bHash;
 use shared::transform::{self, TypeMismatch};
 use storage::global_state::{CommitResult, History};
 
 mod uint;
 
+/// The canonical bytes of `value`, along with the name of its type.
+pub fn serialized_value(
+    value: &common::value::Value,
+) -> Result<ipc::SerializedValue, common::bytesrepr::Error> {
+    let mut serialized_value = ipc::SerializedValue::new();
+    serialized_value.set_field_type(value.type_string());
+    serialized_value.set_bytes(value.to_bytes()?);
+    Ok(serialized_value)
+}
+
 /// Helper method for turning instances of Value into Transform::Write.
 fn transform_write(v: common::value::Value) -> Result<transform::Transform, ParsingError> {
     Ok(transform::Transform::Write(v))
 }
 
-#[derive(Debug)]
-pub struct ParsingError(pub String);
+/// Why a message received over IPC couldn't be mapped to its domain representation. `message`
+/// and `field` name the protobuf message and the field of it that was rejected, e.g.
+/// `Key.Hash` and `hash`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsingError {
+    /// A bytes field, e.g. a hash or a public key, doesn't have the length of what it holds.
+    InvalidLength {
+        message: &'static str,
+        field: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    /// A required field, or every case of a oneof, is unset.
+    MissingField {
+        message: &'static str,
+        field: &'static str,
+    },
+    /// A field holds a value the engine doesn't accept.
+    InvalidValue {
+        message: &'static str,
+        field: &'static str,
+        reason: String,
+    },
+}
 
 impl ParsingError {
-    /// Creates custom error given any type that implements Display.
-    ///
-    /// This includes types derived from Fail (for example) so it enables
-    /// short syntax for functions returning Result<_, ParsingError>:
-    ///
-    ///   any_func().map_err(ParsingError::custom)
-    fn custom<T: Display>(value: T) -> ParsingError {
-        ParsingError(value.to_string())
+    fn invalid_value<T: Display>(message: &'static str, field: &'static str, reason: T) -> Self {
+        ParsingError::InvalidValue {
+            message,
+            field,
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn code(&self) -> ipc::ParsingFailure_Code {
+        match self {
+            ParsingError::InvalidLength { .. } => ipc::ParsingFailure_Code::INVALID_LENGTH,
+            ParsingError::MissingField { .. } => ipc::ParsingFailure_Code::MISSING_FIELD,
+            ParsingError::InvalidValue { .. } => ipc::ParsingFailure_Code::INVALID_VALUE,
+        }
+    }
+}
+
+impl Display for ParsingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParsingError::InvalidLength {
+                message,
+                field,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}.{} has to be {} bytes long, got {} bytes",
+                message, field, expected, actual
+            ),
+            ParsingError::MissingField { message, field } => {
+                write!(f, "{}.{} is missing", message, field)
+            }
+            ParsingError::InvalidValue {
+                message,
+                field,
+                reason,
+            } => write!(f, "{}.{} is invalid: {}", message, field, reason),
+        }
+    }
+}
+
+impl From<&ParsingError> for ipc::ParsingFailure {
+    fn from(error: &ParsingError) -> Self {
+        let mut failure = ipc::ParsingFailure::new();
+        failure.set_code(error.code());
+        match error {
+            ParsingError::InvalidLength {
+                message,
+                field,
+                expected,
+                actual,
+            } => {
+                failure.set_message_type(message.to_string());
+                failure.set_field(field.to_string());
+                failure.set_expected_length(*expected as u32);
+                failure.set_actual_length(*actual as u32);
+            }
+            ParsingError::MissingField { message, field }
+            | ParsingError::InvalidValue { message, field, .. } => {
+                failure.set_message_type(message.to_string());
+                failure.set_field(field.to_string());
+            }
+        }
+        failure.set_description(error.to_string());
+        failure
+    }
+}
+
+/// Copies `source` into a 32-byte array, e.g. a key address or a public key.
+fn parse_bytes32(
+    message: &'static str,
+    field: &'static str,
+    source: &[u8],
+) -> Result<[u8; 32], ParsingError> {
+    if source.len() != 32 {
+        return Err(ParsingError::InvalidLength {
+            message,
+            field,
+            expected: 32,
+            actual: source.len(),
+        });
     }
+    let mut dest = [0u8; 32];
+    dest.copy_from_slice(source);
+    Ok(dest)
 }
 
-/// Smart constructor for parse errors
-fn parse_error<T>(message: String) -> Result<T, ParsingError> {
-    Err(ParsingError(message))
+fn parse_hash(
+    message: &'static str,
+    field: &'static str,
+    source: &[u8],
+) -> Result<Blake2bHash, ParsingError> {
+    parse_bytes32(message, field, source).map(Blake2bHash::from)
+}
+
+/// Describes where a type mismatch happened, e.g. `Type mismatch at Hash(..)/a/b: expected
+/// Account or Contract, found Int32`.
+fn type_mismatch_msg(key: &common::key::Key, path: &[String], mismatch: &TypeMismatch) -> String {
+    let mut location = format!("{:?}", key);
+    for name in path {
+        location.push('/');
+        location.push_str(name);
+    }
+    format!(
+        "Type mismatch at {}: expected {}, found {}",
+        location, mismatch.expected, mismatch.found
+    )
 }
 
 impl TryFrom<&super::state::Key_URef> for URef {
     type Error = ParsingError;
 
     fn try_from(ipc_uref: &super::state::Key_URef) -> Result<Self, Self::Error> {
-        let addr = {
-            let source = &ipc_uref.uref;
-            if source.len() != 32 {
-                return parse_error("URef key has to be 32 bytes long.".to_string());
-            }
-            let mut ret = [0u8; 32];
-            ret.copy_from_slice(source);
-            ret
-        };
+        let addr = parse_bytes32("Key.URef", "uref", &ipc_uref.uref)?;
         let uref = {
             let access_rights_value: i32 = ipc_uref.access_rights.value();
             if access_rights_value != 0 {
@@ -102,11 +229,15 @@ impl TryFrom<&super::ipc::Transform> for transform::Transform {
                 .get_value()
                 .iter()
                 .map(|nk| {
-                    let local_nk = nk.clone();
-                    local_nk.get_key().try_into().map(|k| (local_nk.name, k))
+                    nk.get_key()
+                        .try_into()
+                        .map(|k| (nk.get_name().to_string(), k))
                 })
                 .collect::<Result<BTreeMap<String, common::key::Key>, ParsingError>>()?;
             Ok(transform::Transform::AddKeys(keys_map))
+        } else if tr.has_remove_keys() {
+            let names = tr.get_remove_keys().get_value().iter().cloned().collect();
+            Ok(transform::Transform::RemoveKeys(names))
         } else if tr.has_add_i32() {
             Ok(transform::Transform::AddInt32(tr.get_add_i32().value))
         } else if tr.has_add_u64() {
@@ -118,20 +249,40 @@ impl TryFrom<&super::ipc::Transform> for transform::Transform {
                 common::value::Value::UInt128(u) => Ok(u.into()),
                 common::value::Value::UInt256(u) => Ok(u.into()),
                 common::value::Value::UInt512(u) => Ok(u.into()),
-                other => parse_error(format!("Through some impossibility a BigInt was turned into a non-uint value type: ${:?}", other))
+                other => Err(ParsingError::invalid_value(
+                    "Transform.AddBigInt",
+                    "value",
+                    format!("{:?} isn't an unsigned integer", other),
+                )),
             }
         } else if tr.has_write() {
             let v = tr.get_write().get_value();
             transform_write(v.try_into()?)
         } else {
-            parse_error("TransformEntry couldn't be parsed to known Transform.".to_owned())
+            Err(ParsingError::MissingField {
+                message: "Transform",
+                field: "transform_instance",
+            })
+        }
+    }
+}
+
+/// Takes ownership of the written value instead of copying it.
+impl TryFrom<super::ipc::Transform> for transform::Transform {
+    type Error = ParsingError;
+    fn try_from(mut tr: super::ipc::Transform) -> Result<transform::Transform, ParsingError> {
+        if tr.has_write() {
+            let v = tr.mut_write().take_value();
+            transform_write(v.try_into()?)
+        } else {
+            (&tr).try_into()
         }
     }
 }
 
 impl From<common::value::Contract> for super::state::Contract {
     fn from(contract: common::value::Contract) -> Self {
-        let (bytes, known_urefs, protocol_version) = contract.destructure();
+        let (bytes, known_urefs, protocol_version, access_groups) = contract.destructure();
         let mut contract = super::state::Contract::new();
         let urefs = URefMap(known_urefs).into();
         contract.set_body(bytes);
@@ -139,6 +290,8 @@ impl From<common::value::Contract> for super::state::Contract {
         let mut protocol = super::state::ProtocolVersion::new();
         protocol.set_value(protocol_version);
         contract.set_protocol_version(protocol);
+        let access_groups = access_groups.into_iter().map(Into::into).collect();
+        contract.set_access_groups(protobuf::RepeatedField::from_vec(access_groups));
         contract
     }
 }
@@ -148,14 +301,40 @@ impl TryFrom<&super::state::Contract> for common::value::Contract {
 
     fn try_from(value: &super::state::Contract) -> Result<Self, Self::Error> {
         let known_urefs: URefMap = value.get_known_urefs().try_into()?;
+        let access_groups = access_groups_from(value)?;
         Ok(common::value::Contract::new(
             value.get_body().to_vec(),
             known_urefs.0,
             value.get_protocol_version().value,
-        ))
+        )
+        .with_access_groups(access_groups))
+    }
+}
+
+/// Takes ownership of the contract body instead of copying it.
+impl TryFrom<super::state::Contract> for common::value::Contract {
+    type Error = ParsingError;
+
+    fn try_from(mut value: super::state::Contract) -> Result<Self, Self::Error> {
+        let known_urefs: URefMap = value.get_known_urefs().try_into()?;
+        let access_groups = access_groups_from(&value)?;
+        Ok(common::value::Contract::new(
+            value.take_body(),
+            known_urefs.0,
+            value.get_protocol_version().value,
+        )
+        .with_access_groups(access_groups))
     }
 }
 
+fn access_groups_from(contract: &super::state::Contract) -> Result<Vec<URef>, ParsingError> {
+    contract
+        .get_access_groups()
+        .iter()
+        .map(TryInto::try_into)
+        .collect()
+}
+
 impl From<common::value::Value> for super::state::Value {
     fn from(v: common::value::Value) -> Self {
         let mut tv = super::state::Value::new();
@@ -179,13 +358,13 @@ impl From<common::value::Value> for super::state::Value {
             }
             common::value::Value::ListString(list_string) => {
                 let mut string_list = super::state::StringList::new();
-                string_list.set_values(protobuf::RepeatedField::from_ref(list_string));
+                string_list.set_values(protobuf::RepeatedField::from_vec(list_string));
                 tv.set_string_list(string_list);
             }
             common::value::Value::NamedKey(name, key) => {
                 let named_key = {
                     let mut nk = super::state::NamedKey::new();
-                    nk.set_name(name.to_string());
+                    nk.set_name(name);
                     nk.set_key((&key).into());
                     nk
                 };
@@ -246,31 +425,82 @@ impl TryFrom<&super::state::Value> for common::value::Value {
         } else if value.has_long_value() {
             Ok(common::value::Value::UInt64(value.get_long_value()))
         } else {
-            parse_error(format!(
-                "IPC Value {:?} couldn't be parsed to domain representation.",
-                value
+            Err(ParsingError::MissingField {
+                message: "Value",
+                field: "value",
+            })
+        }
+    }
+}
+
+/// Takes ownership of the byte vectors and strings of `value` instead of copying them.
+impl TryFrom<super::state::Value> for common::value::Value {
+    type Error = ParsingError;
+
+    fn try_from(mut value: super::state::Value) -> Result<Self, Self::Error> {
+        if value.has_bytes_value() {
+            Ok(common::value::Value::ByteArray(value.take_bytes_value()))
+        } else if value.has_int_list() {
+            Ok(common::value::Value::ListInt32(
+                value.mut_int_list().take_values(),
+            ))
+        } else if value.has_string_value() {
+            Ok(common::value::Value::String(value.take_string_value()))
+        } else if value.has_contract() {
+            let contract: common::value::Contract = value.take_contract().try_into()?;
+            Ok(common::value::Value::Contract(contract))
+        } else if value.has_string_list() {
+            Ok(common::value::Value::ListString(
+                value.mut_string_list().take_values().into_vec(),
             ))
+        } else if value.has_named_key() {
+            let mut named_key = value.take_named_key();
+            let key = named_key.get_key().try_into()?;
+            Ok(common::value::Value::NamedKey(named_key.take_name(), key))
+        } else {
+            (&value).try_into()
         }
     }
 }
 
+fn to_ipc_associated_keys(keys: &AssociatedKeys) -> Vec<super::state::Account_AssociatedKey> {
+    keys.get_all()
+        .iter()
+        .map(|(key, weight)| {
+            let mut ipc_associated_key = super::state::Account_AssociatedKey::new();
+            ipc_associated_key.set_public_key(key.value().to_vec());
+            ipc_associated_key.set_weight(u32::from(weight.value()));
+            ipc_associated_key
+        })
+        .collect()
+}
+
+fn parse_associated_keys(
+    ipc_keys: &[super::state::Account_AssociatedKey],
+) -> Result<AssociatedKeys, ParsingError> {
+    let mut keys = AssociatedKeys::empty();
+    ipc_keys.iter().try_for_each(|k| {
+        let (pub_key, weight) = k.try_into()?;
+        match keys.add_key(pub_key, weight) {
+            Err(add_key_failure) => Err(ParsingError::invalid_value(
+                "Account",
+                "associated_keys",
+                format!("{:?}", add_key_failure),
+            )),
+            Ok(_) => Ok(()),
+        }
+    })?;
+    Ok(keys)
+}
+
 impl From<common::value::account::Account> for super::state::Account {
     fn from(account: common::value::account::Account) -> Self {
         let mut ipc_account = super::state::Account::new();
         ipc_account.set_public_key(account.pub_key().to_vec());
         ipc_account.set_nonce(account.nonce());
         ipc_account.set_purse_id(account.purse_id().value().into());
-        let associated_keys: Vec<super::state::Account_AssociatedKey> = account
-            .get_associated_keys()
-            .get_all()
-            .iter()
-            .map(|(key, weight)| {
-                let mut ipc_associated_key = super::state::Account_AssociatedKey::new();
-                ipc_associated_key.set_public_key(key.value().to_vec());
-                ipc_associated_key.set_weight(u32::from(weight.value()));
-                ipc_associated_key
-            })
-            .collect();
+        let associated_keys = to_ipc_associated_keys(account.get_associated_keys());
+        let recovery_keys = to_ipc_associated_keys(account.recovery_keys());
         let action_thresholds = {
             let mut tmp = state::Account_ActionThresholds::new();
             tmp.set_key_management_threshold(u32::from(
@@ -297,6 +527,7 @@ impl From<common::value::account::Account> for super::state::Account {
         ipc_account.set_known_urefs(ipc_urefs.into());
         ipc_account.set_associated_keys(associated_keys.into());
         ipc_account.set_account_activity(account_activity);
+        ipc_account.set_recovery_keys(recovery_keys.into());
         ipc_account
     }
 }
@@ -305,53 +536,48 @@ impl TryFrom<&super::state::Account> for common::value::account::Account {
     type Error = ParsingError;
 
     fn try_from(value: &super::state::Account) -> Result<Self, Self::Error> {
-        let pub_key: [u8; 32] = {
-            if value.public_key.len() != 32 {
-                return parse_error("Public key has to be exactly 32 bytes long.".to_string());
-            }
-            let mut buff = [0u8; 32];
-            buff.copy_from_slice(&value.public_key);
-            buff
-        };
+        let pub_key = parse_bytes32("Account", "public_key", &value.public_key)?;
         let uref_map: URefMap = value.get_known_urefs().try_into()?;
         let purse_id: PurseId = PurseId::new(value.get_purse_id().try_into()?);
-        let associated_keys: AssociatedKeys = {
-            let mut keys = AssociatedKeys::empty();
-            value.get_associated_keys().iter().try_for_each(|k| {
-                let (pub_key, weight) = k.try_into()?;
-                match keys.add_key(pub_key, weight) {
-                    Err(add_key_failure) => parse_error(format!(
-                        "Error when parsing associated keys: {:?}",
-                        add_key_failure
-                    )),
-                    Ok(_) => Ok(()),
-                }
-            })?;
-            keys
-        };
+        let associated_keys = parse_associated_keys(value.get_associated_keys())?;
+        let recovery_keys = parse_associated_keys(value.get_recovery_keys())?;
         let action_thresholds: ActionThresholds = {
             if !value.has_action_thresholds() {
-                return parse_error(
-                    "Missing ActionThresholds object of the Account IPC message.".to_string(),
-                );
+                return Err(ParsingError::MissingField {
+                    message: "Account",
+                    field: "action_thresholds",
+                });
             };
             let mut tmp: ActionThresholds = Default::default();
             let action_thresholds_ipc = value.get_action_thresholds();
             tmp.set_deployment_threshold(Weight::new(
                 action_thresholds_ipc.get_deployment_threshold() as u8,
             ))
-            .map_err(ParsingError::custom)?;
+            .map_err(|error| {
+                ParsingError::invalid_value(
+                    "Account.ActionThresholds",
+                    "deployment_threshold",
+                    error,
+                )
+            })?;
             tmp.set_key_management_threshold(Weight::new(
                 action_thresholds_ipc.get_key_management_threshold() as u8,
             ))
-            .map_err(ParsingError::custom)?;
+            .map_err(|error| {
+                ParsingError::invalid_value(
+                    "Account.ActionThresholds",
+                    "key_management_threshold",
+                    error,
+                )
+            })?;
             tmp
         };
         let account_activity: AccountActivity = {
             if !value.has_account_activity() {
-                return parse_error(
-                    "Missing AccountActivity object of the Account IPC message.".to_string(),
-                );
+                return Err(ParsingError::MissingField {
+                    message: "Account",
+                    field: "account_activity",
+                });
             };
             let account_activity_ipc = value.get_account_activity();
             let mut tmp = AccountActivity::new(BlockTime(0), BlockTime(0));
@@ -372,6 +598,7 @@ impl TryFrom<&super::state::Account> for common::value::account::Account {
             associated_keys,
             action_thresholds,
             account_activity,
+            recovery_keys,
         ))
     }
 }
@@ -419,13 +646,20 @@ impl From<transform::Transform> for super::ipc::Transform {
                 add.set_value(protobuf::RepeatedField::from_vec(keys));
                 t.set_add_keys(add);
             }
+            transform::Transform::RemoveKeys(names) => {
+                let mut remove = super::ipc::TransformRemoveKeys::new();
+                remove.set_value(protobuf::RepeatedField::from_vec(
+                    names.into_iter().collect(),
+                ));
+                t.set_remove_keys(remove);
+            }
             transform::Transform::Failure(transform::Error::TypeMismatch(
                 transform::TypeMismatch { expected, found },
             )) => {
                 let mut fail = super::ipc::TransformFailure::new();
                 let mut typemismatch_err = super::ipc::TypeMismatch::new();
-                typemismatch_err.set_expected(expected.to_owned());
-                typemismatch_err.set_found(found.to_owned());
+                typemismatch_err.set_expected(expected);
+                typemismatch_err.set_found(found);
                 fail.set_type_mismatch(typemismatch_err);
                 t.set_failure(fail);
             }
@@ -435,7 +669,7 @@ impl From<transform::Transform> for super::ipc::Transform {
 }
 
 // newtype because trait impl have to be defined in the crate of the type.
-pub struct URefMap(BTreeMap<String, common::key::Key>);
+pub struct URefMap(pub BTreeMap<String, common::key::Key>);
 
 impl TryFrom<&super::state::NamedKey> for (String, common::key::Key) {
     type Error = ParsingError;
@@ -481,14 +715,17 @@ impl TryFrom<&state::Account_AssociatedKey> for (PublicKey, Weight) {
     fn try_from(value: &state::Account_AssociatedKey) -> Result<Self, Self::Error> {
         // Weight is a newtype wrapper around u8 type.
         if value.get_weight() > u8::max_value().into() {
-            parse_error("Key weight cannot be bigger 256.".to_string())
+            Err(ParsingError::invalid_value(
+                "Account.AssociatedKey",
+                "weight",
+                format!("{} is above {}", value.get_weight(), u8::max_value()),
+            ))
         } else {
-            let source = &value.get_public_key();
-            if source.len() != 32 {
-                return parse_error("Public key has to be exactly 32 bytes long.".to_string());
-            }
-            let mut pub_key = [0u8; 32];
-            pub_key.copy_from_slice(source);
+            let pub_key = parse_bytes32(
+                "Account.AssociatedKey",
+                "public_key",
+                value.get_public_key(),
+            )?;
             Ok((
                 PublicKey::new(pub_key),
                 Weight::new(value.get_weight() as u8),
@@ -530,44 +767,22 @@ impl TryFrom<&super::state::Key> for common::key::Key {
 
     fn try_from(ipc_key: &super::state::Key) -> Result<Self, ParsingError> {
         if ipc_key.has_address() {
-            let arr = {
-                let source = &ipc_key.get_address().account;
-                if source.len() != 32 {
-                    return parse_error("Account key has to be 32 bytes long.".to_string());
-                }
-                let mut dest = [0u8; 32];
-                dest.copy_from_slice(source);
-                dest
-            };
+            let arr = parse_bytes32("Key.Address", "account", &ipc_key.get_address().account)?;
             Ok(common::key::Key::Account(arr))
         } else if ipc_key.has_hash() {
-            let arr = {
-                let source = &ipc_key.get_hash().hash;
-                if source.len() != 32 {
-                    return parse_error("Hash key has to be 32 bytes long.".to_string());
-                }
-                let mut dest = [0u8; 32];
-                dest.copy_from_slice(source);
-                dest
-            };
+            let arr = parse_bytes32("Key.Hash", "hash", &ipc_key.get_hash().hash)?;
             Ok(common::key::Key::Hash(arr))
         } else if ipc_key.has_uref() {
             let uref = ipc_key.get_uref().try_into()?;
             Ok(common::key::Key::URef(uref))
         } else if ipc_key.has_local() {
-            let ipc_local_key = ipc_key.get_local();
-            if ipc_local_key.hash.len() != 32 {
-                parse_error("Hash of local key have to be 32 bytes long.".to_string())
-            } else {
-                let mut hash_buff = [0u8; 32];
-                hash_buff.copy_from_slice(&ipc_local_key.hash);
-                Ok(common::key::Key::Local(hash_buff))
-            }
+            let hash = parse_bytes32("Key.Local", "hash", &ipc_key.get_local().hash)?;
+            Ok(common::key::Key::Local(hash))
         } else {
-            parse_error(format!(
-                "ipc Key couldn't be parsed to any Key: {:?}",
-                ipc_key
-            ))
+            Err(ParsingError::MissingField {
+                message: "Key",
+                field: "value",
+            })
         }
     }
 }
@@ -579,12 +794,83 @@ impl From<Op> for super::ipc::Op {
             Op::Read => ipc_op.set_read(super::ipc::ReadOp::new()),
             Op::Write => ipc_op.set_write(super::ipc::WriteOp::new()),
             Op::Add => ipc_op.set_add(super::ipc::AddOp::new()),
+            Op::Create => ipc_op.set_create(super::ipc::CreateOp::new()),
             Op::NoOp => ipc_op.set_noop(super::ipc::NoOp::new()),
         };
         ipc_op
     }
 }
 
+impl TryFrom<&super::ipc::Op> for Op {
+    type Error = ParsingError;
+
+    fn try_from(ipc_op: &super::ipc::Op) -> Result<Self, Self::Error> {
+        if ipc_op.has_read() {
+            Ok(Op::Read)
+        } else if ipc_op.has_write() {
+            Ok(Op::Write)
+        } else if ipc_op.has_add() {
+            Ok(Op::Add)
+        } else if ipc_op.has_create() {
+            Ok(Op::Create)
+        } else if ipc_op.has_noop() {
+            Ok(Op::NoOp)
+        } else {
+            Err(ParsingError::MissingField {
+                message: "Op",
+                field: "op_instance",
+            })
+        }
+    }
+}
+
+/// Transforms gRPC OpEntry into domain tuple of (Key, Op).
+impl TryFrom<&super::ipc::OpEntry> for (common::key::Key, Op) {
+    type Error = ParsingError;
+
+    fn try_from(from: &super::ipc::OpEntry) -> Result<Self, ParsingError> {
+        if from.has_key() {
+            if from.has_operation() {
+                let op = from.get_operation().try_into()?;
+                let key = from.get_key().try_into()?;
+                Ok((key, op))
+            } else {
+                Err(ParsingError::MissingField {
+                    message: "OpEntry",
+                    field: "operation",
+                })
+            }
+        } else {
+            Err(ParsingError::MissingField {
+                message: "OpEntry",
+                field: "key",
+            })
+        }
+    }
+}
+
+impl From<ipc::Deploy> for DeployItem {
+    fn from(mut deploy: ipc::Deploy) -> Self {
+        let mut session = deploy.take_session();
+        // The payment buys as much gas as it covers, rounded down; deploys without a gas price
+        // get no gas at all.
+        let gas_price = ConversionRate::new(deploy.get_gas_price());
+        let gas_limit = Gas::from_motes(
+            Motes::new(U512::from(deploy.get_tokens_transferred_in_payment())),
+            gas_price,
+        )
+        .unwrap_or_default();
+        DeployItem {
+            address: deploy.take_address(),
+            session_code: session.take_code(),
+            session_args: session.take_args(),
+            gas_limit,
+            gas_price,
+            nonce: deploy.get_nonce(),
+        }
+    }
+}
+
 // Newtype wrapper as rustc requires because trait impl have to be defined in the crate of the type.
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct CommitTransforms(HashMap<common::key::Key, transform::Transform>);
@@ -599,11 +885,87 @@ impl CommitTransforms {
     }
 }
 
+// Newtype wrapper as rustc requires because trait impl have to be defined in the crate of the type.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct CommitOps(HashMap<common::key::Key, Op>);
+
+impl CommitOps {
+    pub fn value(self) -> HashMap<common::key::Key, Op> {
+        self.0
+    }
+}
+
+/// Merges the ops of a key recorded by several deploys, the same way as their transforms are.
+impl TryFrom<&[super::ipc::OpEntry]> for CommitOps {
+    type Error = ParsingError;
+
+    fn try_from(value: &[super::ipc::OpEntry]) -> Result<Self, Self::Error> {
+        let mut ops_merged: HashMap<common::key::Key, Op> = HashMap::with_capacity(value.len());
+        for op_entry in value {
+            let (key, op): (common::key::Key, Op) = op_entry.try_into()?;
+            utils::add(&mut ops_merged, key, op);
+        }
+        Ok(CommitOps(ops_merged))
+    }
+}
+
+/// Parses one of the commits of a `BatchCommitRequest`; as for `commit`, no ops means that the
+/// effects are committed unchecked.
+impl TryFrom<&ipc::CommitRequest> for BatchedEffects {
+    type Error = ParsingError;
+
+    fn try_from(commit_request: &ipc::CommitRequest) -> Result<Self, Self::Error> {
+        let prestate_hash = parse_hash(
+            "CommitRequest",
+            "prestate_hash",
+            commit_request.get_prestate_hash(),
+        )?;
+        let ops: CommitOps = commit_request.get_ops().try_into()?;
+        let ops = ops.value();
+        let effects: CommitTransforms = commit_request.get_effects().try_into()?;
+        Ok(BatchedEffects {
+            prestate_hash,
+            ops: if ops.is_empty() { None } else { Some(ops) },
+            effects: effects.value(),
+            expected_poststate_hash: expected_poststate_hash(commit_request)?,
+        })
+    }
+}
+
+/// The post state hash a commit is expected to lead to, `None` if it isn't set.
+pub fn expected_poststate_hash(
+    commit_request: &ipc::CommitRequest,
+) -> Result<Option<Blake2bHash>, ParsingError> {
+    let source = commit_request.get_expected_poststate_hash();
+    if source.is_empty() {
+        Ok(None)
+    } else {
+        parse_hash("CommitRequest", "expected_poststate_hash", source).map(Some)
+    }
+}
+
+pub fn poststate_mismatch_response(
+    expected: Blake2bHash,
+    computed: Blake2bHash,
+) -> ipc::CommitResponse {
+    logging::log_error(&format!(
+        "post state hash mismatch: expected {:?}, computed {:?}",
+        expected, computed
+    ));
+    let mut mismatch = ipc::PoststateMismatch::new();
+    mismatch.set_expected_poststate_hash(expected.to_vec());
+    mismatch.set_computed_poststate_hash(computed.to_vec());
+    let mut commit_response = ipc::CommitResponse::new();
+    commit_response.set_poststate_mismatch(mismatch);
+    commit_response
+}
+
 impl TryFrom<&[super::ipc::TransformEntry]> for CommitTransforms {
     type Error = ParsingError;
 
     fn try_from(value: &[super::ipc::TransformEntry]) -> Result<Self, Self::Error> {
-        let mut transforms_merged: HashMap<common::key::Key, transform::Transform> = HashMap::new();
+        let mut transforms_merged: HashMap<common::key::Key, transform::Transform> =
+            HashMap::with_capacity(value.len());
         for named_key in value.iter() {
             let (key, transform): (common::key::Key, transform::Transform) =
                 named_key.try_into()?;
@@ -613,6 +975,22 @@ impl TryFrom<&[super::ipc::TransformEntry]> for CommitTransforms {
     }
 }
 
+/// Consumes the entries, moving written values out of them instead of copying.
+impl TryFrom<Vec<super::ipc::TransformEntry>> for CommitTransforms {
+    type Error = ParsingError;
+
+    fn try_from(value: Vec<super::ipc::TransformEntry>) -> Result<Self, Self::Error> {
+        let mut transforms_merged: HashMap<common::key::Key, transform::Transform> =
+            HashMap::with_capacity(value.len());
+        for named_key in value {
+            let (key, transform): (common::key::Key, transform::Transform) =
+                named_key.try_into()?;
+            utils::add(&mut transforms_merged, key, transform);
+        }
+        Ok(CommitTransforms(transforms_merged))
+    }
+}
+
 /// Transforms gRPC TransformEntry into domain tuple of (Key, Transform).
 impl TryFrom<&super::ipc::TransformEntry> for (common::key::Key, transform::Transform) {
     type Error = ParsingError;
@@ -623,10 +1001,39 @@ impl TryFrom<&super::ipc::TransformEntry> for (common::key::Key, transform::Tran
                 let key = from.get_key().try_into()?;
                 Ok((key, t))
             } else {
-                parse_error("No transform field in TransformEntry".to_owned())
+                Err(ParsingError::MissingField {
+                    message: "TransformEntry",
+                    field: "transform",
+                })
+            }
+        } else {
+            Err(ParsingError::MissingField {
+                message: "TransformEntry",
+                field: "key",
+            })
+        }
+    }
+}
+
+impl TryFrom<super::ipc::TransformEntry> for (common::key::Key, transform::Transform) {
+    type Error = ParsingError;
+    fn try_from(mut from: super::ipc::TransformEntry) -> Result<Self, ParsingError> {
+        if from.has_key() {
+            if from.has_transform() {
+                let key = from.get_key().try_into()?;
+                let t: transform::Transform = from.take_transform().try_into()?;
+                Ok((key, t))
+            } else {
+                Err(ParsingError::MissingField {
+                    message: "TransformEntry",
+                    field: "transform",
+                })
             }
         } else {
-            parse_error("No key field in TransformEntry".to_owned())
+            Err(ParsingError::MissingField {
+                message: "TransformEntry",
+                field: "key",
+            })
         }
     }
 }
@@ -640,16 +1047,67 @@ impl From<(common::key::Key, transform::Transform)> for super::ipc::TransformEnt
     }
 }
 
+impl From<TransferEvent> for super::ipc::TransferEvent {
+    fn from(transfer: TransferEvent) -> super::ipc::TransferEvent {
+        let mut ipc_transfer = super::ipc::TransferEvent::new();
+        ipc_transfer.set_initiator((&transfer.initiator).into());
+        ipc_transfer.set_source(transfer.source.value().into());
+        ipc_transfer.set_target(transfer.target.value().into());
+        ipc_transfer.set_amount(transfer.amount.into());
+        ipc_transfer
+    }
+}
+
+impl TryFrom<&super::ipc::TransferEvent> for TransferEvent {
+    type Error = ParsingError;
+
+    fn try_from(ipc_transfer: &super::ipc::TransferEvent) -> Result<Self, Self::Error> {
+        let initiator = ipc_transfer.get_initiator().try_into()?;
+        let source: URef = ipc_transfer.get_source().try_into()?;
+        let target: URef = ipc_transfer.get_target().try_into()?;
+        let amount: U512 = ipc_transfer.get_amount().try_into()?;
+        Ok(TransferEvent {
+            initiator,
+            source: PurseId::new(source),
+            target: PurseId::new(target),
+            amount,
+        })
+    }
+}
+
+impl From<ContractEvent> for super::ipc::ContractEvent {
+    fn from(event: ContractEvent) -> super::ipc::ContractEvent {
+        let mut ipc_event = super::ipc::ContractEvent::new();
+        ipc_event.set_emitter((&event.emitter).into());
+        ipc_event.set_data(event.data);
+        ipc_event
+    }
+}
+
+impl TryFrom<&super::ipc::ContractEvent> for ContractEvent {
+    type Error = ParsingError;
+
+    fn try_from(ipc_event: &super::ipc::ContractEvent) -> Result<Self, Self::Error> {
+        let emitter = ipc_event.get_emitter().try_into()?;
+        Ok(ContractEvent {
+            emitter,
+            data: ipc_event.get_data().to_vec(),
+        })
+    }
+}
+
+/// Identity transforms are left out, as they would only be sent back in the commit request.
 impl From<ExecutionEffect> for super::ipc::ExecutionEffect {
-    fn from(ee: ExecutionEffect) -> super::ipc::ExecutionEffect {
+    fn from(mut ee: ExecutionEffect) -> super::ipc::ExecutionEffect {
+        ee.strip_identities();
         let mut eff = super::ipc::ExecutionEffect::new();
         let ipc_ops: Vec<super::ipc::OpEntry> = ee
             .ops
-            .iter()
+            .into_iter()
             .map(|(k, o)| {
                 let mut op_entry = super::ipc::OpEntry::new();
-                let ipc_key = k.into();
-                let ipc_op = o.clone().into();
+                let ipc_key = (&k).into();
+                let ipc_op = o.into();
                 op_entry.set_key(ipc_key);
                 op_entry.set_operation(ipc_op);
                 op_entry
@@ -659,10 +1117,53 @@ impl From<ExecutionEffect> for super::ipc::ExecutionEffect {
             ee.transforms.into_iter().map(Into::into).collect();
         eff.set_op_map(protobuf::RepeatedField::from_vec(ipc_ops));
         eff.set_transform_map(protobuf::RepeatedField::from_vec(ipc_tran));
+        let ipc_transfers: Vec<super::ipc::TransferEvent> =
+            ee.transfers.into_iter().map(Into::into).collect();
+        eff.set_transfers(protobuf::RepeatedField::from_vec(ipc_transfers));
+        let ipc_events: Vec<super::ipc::ContractEvent> =
+            ee.events.into_iter().map(Into::into).collect();
+        eff.set_events(protobuf::RepeatedField::from_vec(ipc_events));
+        eff.set_net_storage_bytes(ee.net_storage_bytes);
         eff
     }
 }
 
+/// Parses the effects attached to an [[ipc::DeployResult]] back into the domain
+/// [[ExecutionEffect]], so that state changes can be attributed to a single deploy.
+impl TryFrom<&super::ipc::ExecutionEffect> for ExecutionEffect {
+    type Error = ParsingError;
+
+    fn try_from(ipc_effect: &super::ipc::ExecutionEffect) -> Result<Self, Self::Error> {
+        let ops = ipc_effect
+            .get_op_map()
+            .iter()
+            .map(TryInto::try_into)
+            .collect::<Result<HashMap<common::key::Key, Op>, ParsingError>>()?;
+        let transforms = ipc_effect
+            .get_transform_map()
+            .iter()
+            .map(TryInto::try_into)
+            .collect::<Result<HashMap<common::key::Key, transform::Transform>, ParsingError>>()?;
+        let transfers = ipc_effect
+            .get_transfers()
+            .iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<TransferEvent>, ParsingError>>()?;
+        let events = ipc_effect
+            .get_events()
+            .iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<ContractEvent>, ParsingError>>()?;
+        Ok(ExecutionEffect {
+            ops,
+            transforms,
+            transfers,
+            events,
+            net_storage_bytes: ipc_effect.get_net_storage_bytes(),
+        })
+    }
+}
+
 impl From<RootNotFound> for ipc::RootNotFound {
     fn from(err: RootNotFound) -> ipc::RootNotFound {
         let RootNotFound(missing_root_hash) = err;
@@ -672,6 +1173,52 @@ impl From<RootNotFound> for ipc::RootNotFound {
     }
 }
 
+impl From<ExecuteError> for ipc::ExecResponse {
+    fn from(error: ExecuteError) -> ipc::ExecResponse {
+        let mut exec_response = ipc::ExecResponse::new();
+        match error {
+            ExecuteError::MissingParent(root_not_found) => {
+                exec_response.set_missing_parent(root_not_found.into())
+            }
+            ExecuteError::InvalidNonce {
+                deploy_index,
+                deploy_nonce,
+                expected_nonce,
+            } => {
+                let mut invalid_nonce = ipc::InvalidNonceAbort::new();
+                invalid_nonce.set_deploy_index(deploy_index as u32);
+                invalid_nonce.set_deploy_nonce(deploy_nonce);
+                invalid_nonce.set_expected_nonce(expected_nonce);
+                exec_response.set_invalid_nonce(invalid_nonce);
+            }
+            ExecuteError::MisorderedDeploy {
+                deploy_index,
+                deploy_nonce,
+                previous_index,
+                previous_nonce,
+            } => {
+                let mut misordered_deploy = ipc::MisorderedDeploy::new();
+                misordered_deploy.set_deploy_index(deploy_index as u32);
+                misordered_deploy.set_deploy_nonce(deploy_nonce);
+                misordered_deploy.set_previous_index(previous_index as u32);
+                misordered_deploy.set_previous_nonce(previous_nonce);
+                exec_response.set_misordered_deploy(misordered_deploy);
+            }
+        }
+        exec_response
+    }
+}
+
+impl From<ipc::InvalidNonceMode> for InvalidNoncePolicy {
+    fn from(mode: ipc::InvalidNonceMode) -> InvalidNoncePolicy {
+        match mode {
+            ipc::InvalidNonceMode::RETRY_LATER => InvalidNoncePolicy::RetryLater,
+            ipc::InvalidNonceMode::FAIL_DEPLOY => InvalidNoncePolicy::FailDeploy,
+            ipc::InvalidNonceMode::ABORT_BLOCK => InvalidNoncePolicy::AbortBlock,
+        }
+    }
+}
+
 impl From<TypeMismatch> for ipc::TypeMismatch {
     fn from(type_mismatch: TypeMismatch) -> ipc::TypeMismatch {
         let TypeMismatch { expected, found } = type_mismatch;
@@ -689,11 +1236,10 @@ impl From<ExecutionResult> for ipc::DeployResult {
                 effect: effects,
                 cost,
             } => {
-                let mut ipc_ee = effects.into();
                 let mut deploy_result = ipc::DeployResult::new();
                 let mut execution_result = ipc::DeployResult_ExecutionResult::new();
-                execution_result.set_effects(ipc_ee);
-                execution_result.set_cost(cost);
+                set_effects(&mut execution_result, effects);
+                execution_result.set_cost(cost.value());
                 deploy_result.set_execution_result(execution_result);
                 deploy_result
             }
@@ -709,12 +1255,27 @@ impl From<ExecutionResult> for ipc::DeployResult {
                     error @ EngineError::InvalidPublicKeyLength { .. } => {
                         precondition_failure(error.to_string())
                     }
+                    error @ EngineError::DeployTooLarge { .. } => {
+                        precondition_failure(error.to_string())
+                    }
+                    error @ EngineError::DeployArgsTooLarge { .. } => {
+                        precondition_failure(error.to_string())
+                    }
+                    error @ EngineError::AccountFrozen(_) => {
+                        precondition_failure(error.to_string())
+                    }
+                    error @ EngineError::InvalidNonce { .. } => {
+                        precondition_failure(error.to_string())
+                    }
                     error @ EngineError::WasmPreprocessingError(_) => {
                         precondition_failure(error.to_string())
                     }
                     error @ EngineError::WasmSerializationError(_) => {
                         precondition_failure(error.to_string())
                     }
+                    error @ EngineError::InvalidGenesisTransform(..) => {
+                        precondition_failure(error.to_string())
+                    }
                     EngineError::StorageError(storage_err) => {
                         execution_error(storage_err.to_string(), cost, effect)
                     }
@@ -729,14 +1290,19 @@ impl From<ExecutionResult> for ipc::DeployResult {
                             let exec_result = {
                                 let mut tmp = ipc::DeployResult_ExecutionResult::new();
                                 tmp.set_error(deploy_error);
-                                tmp.set_cost(cost);
-                                tmp.set_effects(effect.into());
+                                tmp.set_cost(cost.value());
+                                set_effects(&mut tmp, effect);
                                 tmp
                             };
 
                             deploy_result.set_execution_result(exec_result);
                             deploy_result
                         }
+                        // Not deterministic, so it must not be committed.
+                        ExecutionError::Timeout(timeout) => precondition_failure(format!(
+                            "Deploy timed out after {} ms",
+                            timeout.as_millis()
+                        )),
                         ExecutionError::KeyNotFound(key) => {
                             let msg = format!("Key {:?} not found.", key);
                             execution_error(msg, cost, effect)
@@ -765,6 +1331,16 @@ impl From<ExecutionResult> for ipc::DeployResult {
                             let error_msg = format!("Exit code: {}", status);
                             execution_error(error_msg, cost, effect)
                         }
+                        ExecutionError::TypeMismatch {
+                            key,
+                            path,
+                            mismatch,
+                        } => {
+                            execution_error(type_mismatch_msg(&key, &path, &mismatch), cost, effect)
+                        }
+                        ExecutionError::Trap(trap_info) => {
+                            execution_error(trap_info.to_string(), cost, effect)
+                        }
                         ExecutionError::Interpreter(error) => {
                             // If the error happens during contract execution it's mapped to HostError
                             // and wrapped in Interpreter error, so we may end up with InterpreterError(HostError(InterpreterError))).
@@ -784,6 +1360,19 @@ impl From<ExecutionResult> for ipc::DeployResult {
                                             let errors_msg = format!("Key {:?} not found.", key);
                                             execution_error(errors_msg, cost, effect)
                                         }
+                                        ExecutionError::TypeMismatch {
+                                            key,
+                                            path,
+                                            mismatch,
+                                        } => execution_error(
+                                            type_mismatch_msg(key, path, mismatch),
+                                            cost,
+                                            effect,
+                                        ),
+                                        // Trap in a called contract.
+                                        ExecutionError::Trap(trap_info) => {
+                                            execution_error(trap_info.to_string(), cost, effect)
+                                        }
                                         other => {
                                             execution_error(format!("{:?}", other), cost, effect)
                                         }
@@ -825,7 +1414,7 @@ where
             tmp_res.set_missing_prestate(root);
             tmp_res
         }
-        Ok(CommitResult::Success(post_state_hash)) => {
+        Ok(CommitResult::Success(post_state_hash, stats)) => {
             let mut properties: BTreeMap<String, String> = BTreeMap::new();
 
             properties.insert(
@@ -833,6 +1422,17 @@ where
                 format!("{:?}", post_state_hash),
             );
 
+            properties.insert("created-keys".to_string(), stats.keys_created.to_string());
+
+            properties.insert("updated-keys".to_string(), stats.keys_updated.to_string());
+
+            properties.insert(
+                "added-trie-nodes".to_string(),
+                stats.trie_nodes_added.to_string(),
+            );
+
+            properties.insert("bytes-written".to_string(), stats.bytes_written.to_string());
+
             properties.insert("success".to_string(), true.to_string());
 
             logging::log_details(
@@ -844,6 +1444,11 @@ where
             let mut commit_result = ipc::CommitResult::new();
             let mut tmp_res = ipc::CommitResponse::new();
             commit_result.set_poststate_hash(post_state_hash.to_vec());
+            commit_result.set_created_keys_count(stats.keys_created);
+            commit_result.set_updated_keys_count(stats.keys_updated);
+            commit_result.set_written_keys_count(stats.keys_written());
+            commit_result.set_added_trie_nodes_count(stats.trie_nodes_added);
+            commit_result.set_bytes_written(stats.bytes_written);
             tmp_res.set_success(commit_result);
             tmp_res
         }
@@ -859,6 +1464,9 @@ where
             commit_response.set_type_mismatch(type_mismatch.into());
             commit_response
         }
+        Ok(CommitResult::PoststateMismatch { expected, computed }) => {
+            poststate_mismatch_response(expected, computed)
+        }
         // TODO(mateusz.gorski): We should be more specific about errors here.
         Err(storage_error) => {
             let log_message = format!("storage error {:?} when applying effects", storage_error);
@@ -873,7 +1481,7 @@ where
 }
 
 /// Constructs an instance of [[ipc::DeployResult]] with an error set to [[ipc::DeployError_PreconditionFailure]].
-fn precondition_failure(msg: String) -> ipc::DeployResult {
+pub fn precondition_failure(msg: String) -> ipc::DeployResult {
     let mut deploy_result = ipc::DeployResult::new();
     let mut precondition_failure = ipc::DeployResult_PreconditionFailure::new();
     precondition_failure.set_message(msg);
@@ -881,8 +1489,35 @@ fn precondition_failure(msg: String) -> ipc::DeployResult {
     deploy_result
 }
 
+/// Sets the effects of a deploy along with the keys it wrote or added to, which is all an
+/// indexer needs to know which accounts and contracts the deploy touched.
+fn set_effects(execution_result: &mut ipc::DeployResult_ExecutionResult, effect: ExecutionEffect) {
+    let written_keys: Vec<ipc::OpEntry> = effect
+        .ops
+        .iter()
+        .filter(|(_, op)| match op {
+            Op::Write | Op::Add | Op::Create => true,
+            Op::Read | Op::NoOp => false,
+        })
+        .map(|(key, op)| {
+            let mut op_entry = ipc::OpEntry::new();
+            op_entry.set_key(key.into());
+            op_entry.set_operation(op.clone().into());
+            op_entry
+        })
+        .collect();
+    execution_result.set_written_keys(protobuf::RepeatedField::from_vec(written_keys));
+    match effect.checksum() {
+        Ok(checksum) => execution_result.set_effects_checksum(checksum.to_vec()),
+        Err(error) => {
+            logging::log_error(&format!("effects checksum error: {:?}", error));
+        }
+    }
+    execution_result.set_effects(effect.into());
+}
+
 /// Constructs an instance of [[ipc::DeployResult]] with error set to [[ipc::DeployError_ExecutionError]].
-fn execution_error(msg: String, cost: u64, effect: ExecutionEffect) -> ipc::DeployResult {
+fn execution_error(msg: String, cost: Gas, effect: ExecutionEffect) -> ipc::DeployResult {
     let mut deploy_result = ipc::DeployResult::new();
     let deploy_error = {
         let mut tmp = ipc::DeployError::new();
@@ -894,8 +1529,8 @@ fn execution_error(msg: String, cost: u64, effect: ExecutionEffect) -> ipc::Depl
     let execution_result = {
         let mut tmp = ipc::DeployResult_ExecutionResult::new();
         tmp.set_error(deploy_error);
-        tmp.set_cost(cost);
-        tmp.set_effects(effect.into());
+        tmp.set_cost(cost.value());
+        set_effects(&mut tmp, effect);
         tmp
     };
     deploy_result.set_execution_result(execution_result);
@@ -914,29 +1549,265 @@ pub fn to_domain_validators(bond: &ipc::Bond) -> Result<(PublicKey, U512), Strin
     }
 }
 
+pub fn to_ipc_bonds(bonded_validators: &HashMap<PublicKey, U512>) -> Vec<ipc::Bond> {
+    bonded_validators
+        .iter()
+        .map(|(pk, bond)| {
+            let mut ipc_bond = ipc::Bond::new();
+            ipc_bond.set_stake((*bond).into());
+            ipc_bond.set_validator_public_key(pk.value().to_vec());
+            ipc_bond
+        })
+        .collect()
+}
+
+impl TryFrom<&ipc::Slash> for Slash {
+    type Error = ParsingError;
+
+    fn try_from(slash: &ipc::Slash) -> Result<Self, Self::Error> {
+        let validator = PublicKey::new(parse_bytes32(
+            "Slash",
+            "validator_public_key",
+            slash.get_validator_public_key(),
+        )?);
+        Ok(Slash {
+            validator,
+            ratio: slash.get_ratio(),
+        })
+    }
+}
+
+impl TryFrom<&ipc::SlashRequest> for SlashRequest {
+    type Error = ParsingError;
+
+    fn try_from(slash_request: &ipc::SlashRequest) -> Result<Self, Self::Error> {
+        let parent_state_hash = parse_hash(
+            "SlashRequest",
+            "parent_state_hash",
+            slash_request.get_parent_state_hash(),
+        )?;
+        let slashes = slash_request
+            .get_slashes()
+            .iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<Slash>, ParsingError>>()?;
+        Ok(SlashRequest {
+            parent_state_hash,
+            slashes,
+        })
+    }
+}
+
+impl From<SlashResult> for ipc::SlashResponse {
+    fn from(slash_result: SlashResult) -> ipc::SlashResponse {
+        let mut slash_response = ipc::SlashResponse::new();
+        match slash_result {
+            SlashResult::RootNotFound(parent_state_hash) => {
+                logging::log_warning("RootNotFound");
+                slash_response.set_missing_parent(RootNotFound(parent_state_hash).into());
+            }
+            SlashResult::PoSNotFound(key) => {
+                let error = format!("PoS contract not found: {:?}", key);
+                logging::log_error(&error);
+                slash_response.set_failure(error);
+            }
+            SlashResult::Rejected(error) => {
+                let error = error.to_string();
+                logging::log_warning(&error);
+                slash_response.set_failure(error);
+            }
+            SlashResult::Success {
+                effect,
+                bonded_validators,
+                slashed_amount,
+            } => {
+                let mut result = ipc::SlashResult::new();
+                result.set_effect(effect.into());
+                result.set_bonded_validators(to_ipc_bonds(&bonded_validators).into());
+                result.set_slashed_amount(slashed_amount.into());
+                slash_response.set_success(result);
+            }
+        }
+        slash_response
+    }
+}
+
+impl TryFrom<&ipc::UpgradeRequest> for UpgradeRequest {
+    type Error = ParsingError;
+
+    fn try_from(upgrade_request: &ipc::UpgradeRequest) -> Result<Self, Self::Error> {
+        let parent_state_hash = parse_hash(
+            "UpgradeRequest",
+            "parent_state_hash",
+            upgrade_request.get_parent_state_hash(),
+        )?;
+        let mut new_code = BTreeMap::new();
+        if upgrade_request.has_mint_code() {
+            let code = upgrade_request.get_mint_code().get_code().to_vec();
+            new_code.insert(SystemContract::Mint, code);
+        }
+        if upgrade_request.has_proof_of_stake_code() {
+            let code = upgrade_request
+                .get_proof_of_stake_code()
+                .get_code()
+                .to_vec();
+            new_code.insert(SystemContract::ProofOfStake, code);
+        }
+        Ok(UpgradeRequest {
+            parent_state_hash,
+            protocol_version: upgrade_request.get_protocol_version().value,
+            new_code,
+        })
+    }
+}
+
+impl From<UpgradeResult> for ipc::UpgradeResponse {
+    fn from(upgrade_result: UpgradeResult) -> ipc::UpgradeResponse {
+        let mut upgrade_response = ipc::UpgradeResponse::new();
+        match upgrade_result {
+            UpgradeResult::RootNotFound(parent_state_hash) => {
+                logging::log_warning("RootNotFound");
+                upgrade_response.set_missing_parent(RootNotFound(parent_state_hash).into());
+            }
+            UpgradeResult::ContractNotFound(contract) => {
+                let error = format!("System contract not found: {}", contract);
+                logging::log_error(&error);
+                upgrade_response.set_failure(error);
+            }
+            UpgradeResult::InvalidProtocolVersion {
+                contract,
+                current,
+                requested,
+            } => {
+                let error = format!(
+                    "Can't upgrade {} from protocol version {} to {}",
+                    contract, current, requested
+                );
+                logging::log_warning(&error);
+                upgrade_response.set_failure(error);
+            }
+            UpgradeResult::MigrationFailed(contract, error) => {
+                let error = format!("Migration of {} failed: {}", contract, error);
+                logging::log_error(&error);
+                upgrade_response.set_failure(error);
+            }
+            UpgradeResult::Success { effect } => {
+                let mut result = ipc::UpgradeResult::new();
+                result.set_effect(effect.into());
+                upgrade_response.set_success(result);
+            }
+        }
+        upgrade_response
+    }
+}
+
+impl TryFrom<&ipc::StakingRequest> for StakingCheck {
+    type Error = ParsingError;
+
+    fn try_from(staking_request: &ipc::StakingRequest) -> Result<Self, Self::Error> {
+        let state_hash = parse_hash(
+            "StakingRequest",
+            "state_hash",
+            staking_request.get_state_hash(),
+        )?;
+        let validator = PublicKey::new(parse_bytes32(
+            "StakingRequest",
+            "validator_public_key",
+            staking_request.get_validator_public_key(),
+        )?);
+        let request = if staking_request.has_bond() {
+            StakingRequest::Bond(staking_request.get_bond().get_amount().try_into()?)
+        } else if staking_request.has_unbond() {
+            let unbond = staking_request.get_unbond();
+            let maybe_amount = if unbond.has_amount() {
+                Some(unbond.get_amount().try_into()?)
+            } else {
+                None
+            };
+            StakingRequest::Unbond(maybe_amount)
+        } else {
+            return Err(ParsingError::MissingField {
+                message: "StakingRequest",
+                field: "request",
+            });
+        };
+        Ok(StakingCheck {
+            state_hash,
+            validator,
+            timestamp: BlockTime(staking_request.get_timestamp()),
+            request,
+        })
+    }
+}
+
+impl From<StakingCheckResult> for ipc::StakingResponse {
+    fn from(check_result: StakingCheckResult) -> ipc::StakingResponse {
+        let mut staking_response = ipc::StakingResponse::new();
+        match check_result {
+            StakingCheckResult::RootNotFound(state_hash) => {
+                logging::log_warning("RootNotFound");
+                staking_response.set_missing_state(RootNotFound(state_hash).into());
+            }
+            StakingCheckResult::PoSNotFound => {
+                let error = "PoS contract not found".to_string();
+                logging::log_error(&error);
+                staking_response.set_failure(error);
+            }
+            StakingCheckResult::InvalidQueue(error) => {
+                let error = format!("Invalid PoS queue: {:?}", error);
+                logging::log_error(&error);
+                staking_response.set_failure(error);
+            }
+            StakingCheckResult::Invalid(error) => {
+                staking_response.set_invalid(error.to_string());
+            }
+            StakingCheckResult::Valid { stake, due } => {
+                let mut valid = ipc::StakingResponse_Valid::new();
+                valid.set_stake(stake.into());
+                valid.set_due_time(due.0);
+                staking_response.set_valid(valid);
+            }
+        }
+        staking_response
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
-    use std::convert::TryInto;
+    use std::convert::{TryFrom, TryInto};
+    use std::time::Duration;
 
+    use proptest::collection::{hash_map, vec};
     use proptest::prelude::*;
 
-    use common::gens::{account_arb, contract_arb, key_arb, uref_map_arb, value_arb};
+    use common::gens::{
+        account_arb, contract_arb, key_arb, large_contract_arb, u512_arb, uref_arb, uref_map_arb,
+        value_arb,
+    };
     use common::key::Key;
     use common::uref::{AccessRights, URef};
+    use common::value::account::{PublicKey, PurseId};
+    use common::value::{Value, U512};
     use engine_server::mappings::CommitTransforms;
     use execution_engine::engine_state::error::Error::ExecError;
     use execution_engine::engine_state::error::{Error as EngineError, RootNotFound};
-    use execution_engine::engine_state::execution_effect::ExecutionEffect;
+    use execution_engine::engine_state::execution_effect::{
+        BatchedEffects, ContractEvent, ExecutionEffect, TransferEvent,
+    };
     use execution_engine::engine_state::execution_result::ExecutionResult;
-    use execution_engine::execution::Error;
+    use execution_engine::engine_state::op::Op;
+    use execution_engine::engine_state::slashing::{Slash, SlashRequest};
+    use execution_engine::engine_state::upgrade::{SystemContract, UpgradeRequest};
+    use execution_engine::execution::{Error, TrapInfo};
+    use shared::gas::Gas;
     use shared::newtypes::Blake2bHash;
     use shared::transform::gens::transform_arb;
     use shared::transform::Transform;
 
-    use super::execution_error;
     use super::ipc;
     use super::state;
+    use super::{execution_error, expected_poststate_hash, serialized_value, ParsingError};
 
     // Test that wasm_error function actually returns DeployResult with result set to WasmError
     #[test]
@@ -961,6 +1832,86 @@ mod tests {
         assert_eq!(root_hash.to_vec(), ipc_missing_hash);
     }
 
+    #[test]
+    fn slash_request_from_ipc() {
+        let mut slash = ipc::Slash::new();
+        slash.set_validator_public_key(vec![2u8; 32]);
+        slash.set_ratio(500_000);
+        let mut ipc_request = ipc::SlashRequest::new();
+        ipc_request.set_parent_state_hash(vec![1u8; 32]);
+        ipc_request.set_slashes(vec![slash.clone()].into());
+
+        let request = SlashRequest::try_from(&ipc_request).expect("should parse slash request");
+        assert_eq!(request.parent_state_hash, [1u8; 32].into());
+        assert_eq!(
+            request.slashes,
+            vec![Slash {
+                validator: PublicKey::new([2u8; 32]),
+                ratio: 500_000
+            }]
+        );
+
+        slash.set_validator_public_key(vec![2u8; 31]);
+        ipc_request.set_slashes(vec![slash].into());
+        assert!(SlashRequest::try_from(&ipc_request).is_err());
+    }
+
+    #[test]
+    fn upgrade_request_from_ipc() {
+        let mut pos_code = ipc::DeployCode::new();
+        pos_code.set_code(vec![0, 97, 115, 109]);
+        let mut protocol_version = state::ProtocolVersion::new();
+        protocol_version.set_value(2);
+        let mut ipc_request = ipc::UpgradeRequest::new();
+        ipc_request.set_parent_state_hash(vec![1u8; 32]);
+        ipc_request.set_protocol_version(protocol_version);
+        ipc_request.set_proof_of_stake_code(pos_code);
+
+        let request = UpgradeRequest::try_from(&ipc_request).expect("should parse upgrade request");
+        assert_eq!(request.parent_state_hash, [1u8; 32].into());
+        assert_eq!(request.protocol_version, 2);
+        assert_eq!(
+            request.new_code.into_iter().collect::<Vec<_>>(),
+            vec![(SystemContract::ProofOfStake, vec![0, 97, 115, 109])]
+        );
+
+        ipc_request.set_parent_state_hash(vec![1u8; 31]);
+        assert!(UpgradeRequest::try_from(&ipc_request).is_err());
+    }
+
+    #[test]
+    fn parsing_errors_name_the_field() {
+        let mut ipc_hash = state::Key_Hash::new();
+        ipc_hash.set_hash(vec![1u8; 31]);
+        let mut ipc_key = state::Key::new();
+        ipc_key.set_hash(ipc_hash);
+        let error = Key::try_from(&ipc_key).expect_err("should reject a short hash");
+        assert_eq!(
+            error,
+            ParsingError::InvalidLength {
+                message: "Key.Hash",
+                field: "hash",
+                expected: 32,
+                actual: 31,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Key.Hash.hash has to be 32 bytes long, got 31 bytes"
+        );
+
+        let failure: ipc::ParsingFailure = (&error).into();
+        assert_eq!(failure.get_code(), ipc::ParsingFailure_Code::INVALID_LENGTH);
+        assert_eq!(failure.get_message_type(), "Key.Hash");
+        assert_eq!(failure.get_field(), "hash");
+        assert_eq!(failure.get_expected_length(), 32);
+        assert_eq!(failure.get_actual_length(), 31);
+
+        let error = Key::try_from(&state::Key::new()).expect_err("should reject an empty key");
+        assert_eq!(error.code(), ipc::ParsingFailure_Code::MISSING_FIELD);
+        assert_eq!(error.to_string(), "Key.value is missing");
+    }
+
     #[test]
     fn deploy_result_to_ipc_success() {
         let input_transforms: HashMap<Key, Transform> = {
@@ -976,7 +1927,7 @@ mod tests {
         let cost: u64 = 123;
         let execution_result: ExecutionResult = ExecutionResult::Success {
             effect: execution_effect,
-            cost,
+            cost: Gas::new(cost),
         };
         let mut ipc_deploy_result: ipc::DeployResult = execution_result.into();
         assert!(ipc_deploy_result.has_execution_result());
@@ -996,11 +1947,112 @@ mod tests {
         assert_eq!(&input_transforms, &ipc_transforms);
     }
 
+    #[test]
+    fn deploy_result_to_ipc_failure_keeps_effects() {
+        let key = Key::URef(URef::new([1u8; 32], AccessRights::READ_ADD));
+        let mut ops = HashMap::new();
+        ops.insert(key, Op::Add);
+        let mut transforms = HashMap::new();
+        transforms.insert(key, Transform::AddInt32(10));
+        let execution_result = ExecutionResult::Failure {
+            error: ExecError(Error::Revert(1)),
+            effect: ExecutionEffect::new(ops.clone(), transforms.clone()),
+            cost: Gas::new(10),
+        };
+        let ipc_deploy_result: ipc::DeployResult = execution_result.into();
+        let ipc_effects = ipc_deploy_result.get_execution_result().get_effects();
+        let effect: ExecutionEffect = ipc_effects
+            .try_into()
+            .expect("Transforming ipc::ExecutionEffect into ExecutionEffect should work.");
+        assert_eq!(effect, ExecutionEffect::new(ops, transforms));
+    }
+
+    #[test]
+    fn deploy_result_to_ipc_keeps_transfers() {
+        let transfers = vec![
+            TransferEvent {
+                initiator: Key::Account([1u8; 32]),
+                source: PurseId::new(URef::new([2u8; 32], AccessRights::READ_ADD_WRITE)),
+                target: PurseId::new(URef::new([3u8; 32], AccessRights::ADD)),
+                amount: U512::from(100),
+            },
+            TransferEvent {
+                initiator: Key::Hash([4u8; 32]),
+                source: PurseId::new(URef::new([3u8; 32], AccessRights::READ_ADD_WRITE)),
+                target: PurseId::new(URef::new([2u8; 32], AccessRights::ADD)),
+                amount: U512::from(50),
+            },
+        ];
+        let execution_result = ExecutionResult::Success {
+            effect: ExecutionEffect {
+                transfers: transfers.clone(),
+                ..Default::default()
+            },
+            cost: Gas::new(10),
+        };
+        let ipc_deploy_result: ipc::DeployResult = execution_result.into();
+        let ipc_effects = ipc_deploy_result.get_execution_result().get_effects();
+        let effect: ExecutionEffect = ipc_effects
+            .try_into()
+            .expect("Transforming ipc::ExecutionEffect into ExecutionEffect should work.");
+        assert_eq!(effect.transfers, transfers);
+    }
+
+    #[test]
+    fn deploy_result_to_ipc_keeps_events() {
+        let events = vec![
+            ContractEvent {
+                emitter: Key::Hash([1u8; 32]),
+                data: vec![1, 2, 3],
+            },
+            ContractEvent {
+                emitter: Key::Account([2u8; 32]),
+                data: Vec::new(),
+            },
+        ];
+        let execution_result = ExecutionResult::Success {
+            effect: ExecutionEffect {
+                events: events.clone(),
+                net_storage_bytes: -42,
+                ..Default::default()
+            },
+            cost: Gas::new(10),
+        };
+        let ipc_deploy_result: ipc::DeployResult = execution_result.into();
+        let ipc_effects = ipc_deploy_result.get_execution_result().get_effects();
+        let effect: ExecutionEffect = ipc_effects
+            .try_into()
+            .expect("Transforming ipc::ExecutionEffect into ExecutionEffect should work.");
+        assert_eq!(effect.events, events);
+        assert_eq!(effect.net_storage_bytes, -42);
+    }
+
+    #[test]
+    fn deploy_result_to_ipc_has_effects_checksum() {
+        let mut effect = ExecutionEffect::default();
+        effect.ops.insert(Key::Hash([1u8; 32]), Op::Write);
+        effect
+            .transforms
+            .insert(Key::Hash([1u8; 32]), Transform::Write(Value::Int32(1)));
+        let checksum = effect.checksum().unwrap();
+        let execution_result = ExecutionResult::Success {
+            effect,
+            cost: Gas::new(10),
+        };
+        let ipc_deploy_result: ipc::DeployResult = execution_result.into();
+        assert_eq!(
+            ipc_deploy_result
+                .get_execution_result()
+                .get_effects_checksum(),
+            &checksum.to_vec()[..]
+        );
+    }
+
     fn into_execution_failure<E: Into<EngineError>>(error: E, cost: u64) -> ExecutionResult {
         ExecutionResult::Failure {
             error: error.into(),
             effect: Default::default(),
-            cost,
+            cost: Gas::new(cost),
         }
     }
 
@@ -1038,6 +2090,86 @@ mod tests {
         assert_eq!(test_cost(cost, forged_ref_error), cost);
     }
 
+    #[test]
+    fn serialized_value_is_the_canonical_encoding() {
+        let value = Value::String("abc".to_owned());
+        let serialized = serialized_value(&value).expect("should serialize");
+        assert_eq!(serialized.get_field_type(), "String");
+        assert_eq!(
+            common::bytesrepr::deserialize::<Value>(serialized.get_bytes()).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn deploy_result_lists_written_keys() {
+        let mut ops = HashMap::new();
+        ops.insert(Key::Hash([1u8; 32]), Op::Read);
+        ops.insert(Key::Hash([2u8; 32]), Op::Write);
+        ops.insert(Key::Hash([3u8; 32]), Op::Add);
+        ops.insert(Key::Hash([4u8; 32]), Op::Create);
+        let execution_result = ExecutionResult::Success {
+            effect: ExecutionEffect::new(ops, HashMap::new()),
+            cost: Gas::default(),
+        };
+        let ipc_deploy_result: ipc::DeployResult = execution_result.into();
+
+        let mut written_keys: Vec<(Key, Op)> = ipc_deploy_result
+            .get_execution_result()
+            .get_written_keys()
+            .iter()
+            .map(|op_entry| op_entry.try_into().unwrap())
+            .collect();
+        written_keys.sort_by_key(|(key, _)| *key);
+        assert_eq!(
+            written_keys,
+            vec![
+                (Key::Hash([2u8; 32]), Op::Write),
+                (Key::Hash([3u8; 32]), Op::Add),
+                (Key::Hash([4u8; 32]), Op::Create)
+            ]
+        );
+    }
+
+    #[test]
+    fn timeout_is_a_precondition_failure() {
+        let timeout = Error::Timeout(Duration::from_secs(2));
+        let ipc_deploy_result: ipc::DeployResult = into_execution_failure(timeout, 100).into();
+        assert!(!ipc_deploy_result.has_execution_result());
+        assert_eq!(
+            ipc_deploy_result.get_precondition_failure().get_message(),
+            "Deploy timed out after 2000 ms"
+        );
+    }
+
+    #[test]
+    fn expected_poststate_hash_is_optional_and_batched() {
+        let mut commit_request = ipc::CommitRequest::new();
+        commit_request.set_prestate_hash(vec![1u8; 32]);
+        assert_eq!(expected_poststate_hash(&commit_request).unwrap(), None);
+        assert_eq!(
+            BatchedEffects::try_from(&commit_request)
+                .unwrap()
+                .expected_poststate_hash,
+            None
+        );
+
+        commit_request.set_expected_poststate_hash(vec![2u8; 31]);
+        assert!(expected_poststate_hash(&commit_request).is_err());
+
+        commit_request.set_expected_poststate_hash(vec![2u8; 32]);
+        assert_eq!(
+            expected_poststate_hash(&commit_request).unwrap(),
+            Some(Blake2bHash::from([2u8; 32]))
+        );
+        assert_eq!(
+            BatchedEffects::try_from(&commit_request)
+                .unwrap()
+                .expected_poststate_hash,
+            Some(Blake2bHash::from([2u8; 32]))
+        );
+    }
+
     #[test]
     fn commit_effects_merges_transforms() {
         // Tests that transforms made to the same key are merged instead of lost.
@@ -1067,13 +2199,55 @@ mod tests {
         assert_eq!(expected_transform, *commit_transform.unwrap())
     }
 
+    #[test]
+    fn effects_leave_out_identity_transforms() {
+        let read = Key::Hash([1u8; 32]);
+        let written = Key::Hash([2u8; 32]);
+        let ops = vec![(read, Op::Read), (written, Op::Write)]
+            .into_iter()
+            .collect();
+        let transforms = vec![
+            (read, Transform::Identity),
+            (written, Transform::Write(Value::Int32(1))),
+        ]
+        .into_iter()
+        .collect();
+        let ipc_effect: ipc::ExecutionEffect = ExecutionEffect::new(ops, transforms).into();
+
+        assert_eq!(ipc_effect.get_op_map().len(), 2);
+        let effect = ExecutionEffect::try_from(&ipc_effect).unwrap();
+        assert_eq!(effect.ops.get(&read), Some(&Op::Read));
+        assert_eq!(effect.transforms.len(), 1);
+        assert!(effect.transforms.contains_key(&written));
+    }
+
+    #[test]
+    fn trap_maps_to_execution_error_with_location() {
+        let trap_error = Error::Trap(TrapInfo {
+            kind: "integer division by zero".to_string(),
+            function_index: Some(12),
+            function_name: Some("divide".to_string()),
+            last_host_function: Some("read_value"),
+        });
+        let ipc_result: ipc::DeployResult = into_execution_failure(trap_error, 10).into();
+        assert_eq!(
+            ipc_result
+                .get_execution_result()
+                .get_error()
+                .get_exec_error()
+                .message,
+            "Trap: integer division by zero in function 12 (divide) after calling host function \
+             read_value"
+        );
+    }
+
     #[test]
     fn revert_error_maps_to_execution_error() {
         let revert_error = Error::Revert(10);
         let exec_result = ExecutionResult::Failure {
             error: ExecError(revert_error),
             effect: Default::default(),
-            cost: 10,
+            cost: Gas::new(10),
         };
         let ipc_result: ipc::DeployResult = exec_result.into();
         assert!(ipc_result.has_execution_result());
@@ -1085,6 +2259,80 @@ mod tests {
         );
     }
 
+    fn op_arb() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            Just(Op::Read),
+            Just(Op::Write),
+            Just(Op::Add),
+            Just(Op::Create),
+            Just(Op::NoOp),
+        ]
+    }
+
+    fn effect_arb() -> impl Strategy<Value = ExecutionEffect> {
+        (
+            hash_map(key_arb(), op_arb(), 0..5),
+            hash_map(key_arb(), transform_arb(), 0..5),
+            vec((key_arb(), vec(any::<u8>(), 0..16)), 0..3),
+            any::<i64>(),
+        )
+            .prop_map(
+                |(ops, transforms, events, net_storage_bytes)| ExecutionEffect {
+                    ops,
+                    transforms,
+                    events: events
+                        .into_iter()
+                        .map(|(emitter, data)| ContractEvent { emitter, data })
+                        .collect(),
+                    net_storage_bytes,
+                    ..Default::default()
+                },
+            )
+    }
+
+    /// What `effect` should be once sent to the node, i.e. without identity transforms.
+    fn without_identities(effect: &ExecutionEffect) -> ExecutionEffect {
+        let mut expected = ExecutionEffect {
+            ops: effect.ops.clone(),
+            transforms: effect.transforms.clone(),
+            transfers: effect.transfers.clone(),
+            events: effect.events.clone(),
+            net_storage_bytes: effect.net_storage_bytes,
+        };
+        expected.strip_identities();
+        expected
+    }
+
+    /// An `ipc::Key` of each kind holding `bytes` as its address.
+    fn ipc_keys_with_address(bytes: &[u8]) -> Vec<state::Key> {
+        let mut account = state::Key::new();
+        account.mut_address().set_account(bytes.to_vec());
+        let mut hash = state::Key::new();
+        hash.mut_hash().set_hash(bytes.to_vec());
+        let mut uref = state::Key::new();
+        uref.mut_uref().set_uref(bytes.to_vec());
+        let mut local = state::Key::new();
+        local.mut_local().set_hash(bytes.to_vec());
+        vec![account, hash, uref, local]
+    }
+
+    #[test]
+    fn unset_messages_are_missing_fields() {
+        fn assert_missing<T: std::fmt::Debug>(result: Result<T, ParsingError>) {
+            match result {
+                Err(ParsingError::MissingField { .. }) => (),
+                other => panic!("expected a missing field, got {:?}", other),
+            }
+        }
+        assert_missing(Key::try_from(&state::Key::new()));
+        assert_missing(Value::try_from(&state::Value::new()));
+        assert_missing(Value::try_from(state::Value::new()));
+        assert_missing(Transform::try_from(&ipc::Transform::new()));
+        assert_missing(Op::try_from(&ipc::Op::new()));
+        assert_missing(<(Key, Op)>::try_from(&ipc::OpEntry::new()));
+        assert_missing(<(Key, Transform)>::try_from(&ipc::TransformEntry::new()));
+    }
+
     proptest! {
         #[test]
         fn key_roundtrip(key in key_arb()) {
@@ -1119,6 +2367,14 @@ mod tests {
             assert_eq!(contract, contract_back)
         }
 
+        #[test]
+        fn large_contract_roundtrip(contract in large_contract_arb()) {
+            let ipc_contract: super::state::Contract = contract.clone().into();
+            let contract_back = (&ipc_contract).try_into()
+                .expect("Transforming state::Contract into domain Contract should succeed.");
+            assert_eq!(contract, contract_back)
+        }
+
         #[test]
         fn value_roundtrip(value in value_arb()) {
             let ipc_value: super::state::Value = value.clone().into();
@@ -1135,5 +2391,146 @@ mod tests {
             assert_eq!(tuple, (key, transform))
         }
 
+        #[test]
+        fn owned_value_conversion_matches_borrowed(value in value_arb()) {
+            let ipc_value: super::state::Value = value.clone().into();
+            let borrowed: Value = (&ipc_value).try_into()
+                .expect("Transforming &state::Value into domain Value should succeed.");
+            let owned: Value = ipc_value.try_into()
+                .expect("Transforming state::Value into domain Value should succeed.");
+            assert_eq!(borrowed, owned);
+            assert_eq!(value, owned)
+        }
+
+        #[test]
+        fn owned_transform_entry_conversion_matches_borrowed(key in key_arb(), transform in transform_arb()) {
+            let transform_entry: ipc::TransformEntry = (key, transform.clone()).into();
+            let borrowed: (Key, Transform) = (&transform_entry).try_into()
+                .expect("Transforming &TransformEntry into (Key, Transform) tuple should work.");
+            let owned: (Key, Transform) = transform_entry.try_into()
+                .expect("Transforming TransformEntry into (Key, Transform) tuple should work.");
+            assert_eq!(borrowed, owned);
+            assert_eq!(owned, (key, transform))
+        }
+
+        #[test]
+        fn op_entry_roundtrip(key in key_arb(), op in op_arb()) {
+            let mut op_entry = ipc::OpEntry::new();
+            op_entry.set_key((&key).into());
+            op_entry.set_operation(op.into());
+            let tuple: (Key, Op) = (&op_entry).try_into()
+                .expect("Transforming OpEntry into (Key, Op) tuple should work.");
+            assert_eq!(tuple, (key, op))
+        }
+
+        #[test]
+        fn transfer_event_roundtrip(
+            initiator in key_arb(),
+            source in uref_arb(),
+            target in uref_arb(),
+            amount in u512_arb(),
+        ) {
+            let transfer = TransferEvent {
+                initiator,
+                source: PurseId::new(source),
+                target: PurseId::new(target),
+                amount,
+            };
+            let ipc_transfer: ipc::TransferEvent = transfer.clone().into();
+            let transfer_back = TransferEvent::try_from(&ipc_transfer)
+                .expect("Transforming ipc::TransferEvent into TransferEvent should work.");
+            assert_eq!(transfer_back, transfer)
+        }
+
+        #[test]
+        fn effect_roundtrip_leaves_out_identities(effect in effect_arb()) {
+            let expected = without_identities(&effect);
+            let ipc_effect: ipc::ExecutionEffect = effect.into();
+            let effect_back = ExecutionEffect::try_from(&ipc_effect)
+                .expect("Transforming ipc::ExecutionEffect into ExecutionEffect should work.");
+            assert_eq!(effect_back, expected)
+        }
+
+        #[test]
+        fn deploy_result_keeps_cost_and_effects(
+            effect in effect_arb(),
+            cost in any::<u64>(),
+            out_of_gas in any::<bool>(),
+        ) {
+            let expected = without_identities(&effect);
+            let execution_result = if out_of_gas {
+                ExecutionResult::Failure {
+                    error: ExecError(Error::GasLimit),
+                    effect,
+                    cost: Gas::new(cost),
+                }
+            } else {
+                ExecutionResult::Success {
+                    effect,
+                    cost: Gas::new(cost),
+                }
+            };
+            let ipc_deploy_result: ipc::DeployResult = execution_result.into();
+            let ipc_execution_result = ipc_deploy_result.get_execution_result();
+            assert_eq!(ipc_execution_result.get_cost(), cost);
+            assert_eq!(ipc_execution_result.has_error(), out_of_gas);
+            let effect_back = ExecutionEffect::try_from(ipc_execution_result.get_effects())
+                .expect("Transforming ipc::ExecutionEffect into ExecutionEffect should work.");
+            assert_eq!(effect_back, expected)
+        }
+
+        #[test]
+        fn keys_of_other_lengths_are_rejected(
+            bytes in vec(any::<u8>(), 0..64).prop_filter("not 32 bytes", |bytes| bytes.len() != 32)
+        ) {
+            for ipc_key in ipc_keys_with_address(&bytes) {
+                match Key::try_from(&ipc_key) {
+                    Err(ParsingError::InvalidLength { expected: 32, actual, .. }) => {
+                        assert_eq!(actual, bytes.len())
+                    }
+                    other => panic!("expected an invalid length, got {:?}", other),
+                }
+            }
+        }
+
+        #[test]
+        fn big_ints_of_other_widths_are_rejected(
+            value in u512_arb(),
+            bit_width in any::<u32>().prop_filter("supported width", |width| {
+                *width != 128 && *width != 256 && *width != 512
+            }),
+        ) {
+            let mut big_int: state::BigInt = value.into();
+            big_int.set_bit_width(bit_width);
+            match Value::try_from(&big_int) {
+                Err(ParsingError::InvalidValue { field: "bit_width", .. }) => (),
+                other => panic!("expected an invalid bit width, got {:?}", other),
+            }
+            match U512::try_from(&big_int) {
+                Err(ParsingError::InvalidValue { field: "bit_width", .. }) => (),
+                other => panic!("expected an invalid bit width, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn big_ints_over_their_width_are_rejected(value in u512_arb(), extra_digit in 0u8..10) {
+            let mut big_int: state::BigInt = value.into();
+            // Has more digits than the largest U512.
+            let oversized = format!("{}{}{}", U512::MAX, value, extra_digit);
+            big_int.set_value(oversized);
+            match Value::try_from(&big_int) {
+                Err(ParsingError::InvalidValue { field: "value", .. }) => (),
+                other => panic!("expected an invalid value, got {:?}", other),
+            }
+            let mut big_int: state::BigInt = value.into();
+            big_int.set_bit_width(128);
+            match Value::try_from(&big_int) {
+                Ok(Value::UInt128(_)) => assert!(value.bits() <= 128),
+                Err(ParsingError::InvalidValue { field: "value", .. }) => {
+                    assert!(value.bits() > 128)
+                }
+                other => panic!("expected a U128 or an invalid value, got {:?}", other),
+            }
+        }
     }
 }
@@ -4,7 +4,7 @@ use std::convert::TryFrom;
 
 use common::value::U512;
 
-use super::{parse_error, ParsingError};
+use super::ParsingError;
 use engine_server::state;
 
 /// Map a result into the expected error for this module, while also
@@ -16,7 +16,7 @@ where
     E: std::fmt::Debug,
 {
     r.map(common::value::Value::from)
-        .map_err(|e| ParsingError(format!("{:?}", e)))
+        .map_err(|e| ParsingError::invalid_value("BigInt", "value", format!("{:?}", e)))
 }
 
 impl TryFrom<&state::BigInt> for common::value::Value {
@@ -28,7 +28,11 @@ impl TryFrom<&state::BigInt> for common::value::Value {
             128 => result_to_value(common::value::U128::from_dec_str(n)),
             256 => result_to_value(common::value::U256::from_dec_str(n)),
             512 => result_to_value(common::value::U512::from_dec_str(n)),
-            other => parse_error(format!("BigInt bit width of {} is invalid", other)),
+            other => Err(ParsingError::invalid_value(
+                "BigInt",
+                "bit_width",
+                format!("{} isn't one of 128, 256 or 512", other),
+            )),
         }
     }
 }
@@ -39,10 +43,13 @@ impl TryFrom<&state::BigInt> for U512 {
     fn try_from(b: &state::BigInt) -> Result<U512, ParsingError> {
         let n = b.get_value();
         match b.get_bit_width() {
-            512 => {
-                common::value::U512::from_dec_str(n).map_err(|e| ParsingError(format!("{:?}", e)))
-            }
-            other => parse_error(format!("BigInt bit width of {} is invalid", other)),
+            512 => common::value::U512::from_dec_str(n)
+                .map_err(|e| ParsingError::invalid_value("BigInt", "value", format!("{:?}", e))),
+            other => Err(ParsingError::invalid_value(
+                "BigInt",
+                "bit_width",
+                format!("{} has to be 512", other),
+            )),
         }
     }
 }
@@ -0,0 +1,222 @@
+//! Bounds on the size of the messages the server reads and writes.
+//!
+//! The limits are enforced by the marshallers of the service definition, i.e. on the raw bytes of
+//! a request before they are parsed, and on a response before it is serialized. Oversized
+//! messages fail with `RESOURCE_EXHAUSTED`.
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use grpc::rt::{
+    GrpcStreaming, MethodDescriptor, MethodHandlerServerStreaming, MethodHandlerUnary,
+    ServerMethod, ServerServiceDefinition,
+};
+use grpc::Marshaller;
+use protobuf::Message;
+
+use shared::logging;
+
+use super::ipc_grpc::ExecutionEngineService;
+
+const SERVICE_NAME: &str = "/io.casperlabs.ipc.ExecutionEngineService";
+
+/// Fails with `RESOURCE_EXHAUSTED` if `size` is bigger than `limit`, naming the method and
+/// the limit so the failure can be told apart from other transport errors.
+pub fn check_message_size(
+    size: usize,
+    limit: usize,
+    kind: &str,
+    method: &str,
+) -> Result<(), grpc::Error> {
+    if size <= limit {
+        return Ok(());
+    }
+    let message = format!(
+        "{} {} of {} bytes exceeds the limit of {} bytes",
+        method, kind, size, limit
+    );
+    logging::log_warning(&message);
+    Err(grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: grpc::GrpcStatus::ResourceExhausted as i32,
+        grpc_message: message,
+    }))
+}
+
+/// Protobuf marshaller refusing to parse requests bigger than `max_receive_message_size` and to
+/// serialize responses bigger than `max_send_message_size`.
+pub struct LimitedMarshaller<M> {
+    method: &'static str,
+    max_receive_message_size: usize,
+    max_send_message_size: usize,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<M> LimitedMarshaller<M> {
+    pub fn new(
+        method: &'static str,
+        max_receive_message_size: usize,
+        max_send_message_size: usize,
+    ) -> Self {
+        LimitedMarshaller {
+            method,
+            max_receive_message_size,
+            max_send_message_size,
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<M: Message> Marshaller<M> for LimitedMarshaller<M> {
+    fn write(&self, message: &M) -> grpc::Result<Vec<u8>> {
+        let size = message.compute_size() as usize;
+        check_message_size(size, self.max_send_message_size, "response", self.method)?;
+        message.write_to_bytes().map_err(From::from)
+    }
+
+    fn read(&self, bytes: Bytes) -> grpc::Result<M> {
+        check_message_size(
+            bytes.len(),
+            self.max_receive_message_size,
+            "request",
+            self.method,
+        )?;
+        protobuf::parse_from_carllerche_bytes(&bytes).map_err(From::from)
+    }
+}
+
+fn descriptor<Q, R>(
+    method: &'static str,
+    streaming: GrpcStreaming,
+    max_receive_message_size: usize,
+    max_send_message_size: usize,
+) -> Arc<MethodDescriptor<Q, R>>
+where
+    Q: Message,
+    R: Message,
+{
+    Arc::new(MethodDescriptor {
+        name: format!("{}/{}", SERVICE_NAME, method),
+        streaming,
+        req_marshaller: Box::new(LimitedMarshaller::new(
+            method,
+            max_receive_message_size,
+            max_send_message_size,
+        )),
+        resp_marshaller: Box::new(LimitedMarshaller::new(
+            method,
+            max_receive_message_size,
+            max_send_message_size,
+        )),
+    })
+}
+
+/// Same as `ExecutionEngineServiceServer::new_service_def`, except that the messages of every
+/// method are bounded by the given sizes. Every method of the service has to be listed here.
+pub fn service_def<H>(
+    handler: H,
+    max_receive_message_size: usize,
+    max_send_message_size: usize,
+) -> ServerServiceDefinition
+where
+    H: ExecutionEngineService + Sync + Send + 'static,
+{
+    let handler = Arc::new(handler);
+    macro_rules! unary {
+        ($method:ident) => {{
+            let handler = Arc::clone(&handler);
+            ServerMethod::new(
+                descriptor(
+                    stringify!($method),
+                    GrpcStreaming::Unary,
+                    max_receive_message_size,
+                    max_send_message_size,
+                ),
+                MethodHandlerUnary::new(move |options, request| handler.$method(options, request)),
+            )
+        }};
+    }
+    macro_rules! server_streaming {
+        ($method:ident) => {{
+            let handler = Arc::clone(&handler);
+            ServerMethod::new(
+                descriptor(
+                    stringify!($method),
+                    GrpcStreaming::ServerStreaming,
+                    max_receive_message_size,
+                    max_send_message_size,
+                ),
+                MethodHandlerServerStreaming::new(move |options, request| {
+                    handler.$method(options, request)
+                }),
+            )
+        }};
+    }
+    ServerServiceDefinition::new(
+        SERVICE_NAME,
+        vec![
+            unary!(exec),
+            unary!(commit),
+            unary!(batch_commit),
+            unary!(query),
+            unary!(query_total_supply),
+            unary!(query_balance),
+            unary!(get_named_keys),
+            unary!(validate),
+            unary!(run_genesis),
+            unary!(slash),
+            unary!(upgrade),
+            unary!(validate_staking),
+            server_streaming!(subscribe_commits),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use grpc::Marshaller;
+    use protobuf::Message;
+
+    use super::super::ipc;
+    use super::LimitedMarshaller;
+
+    fn assert_resource_exhausted<T: ::std::fmt::Debug>(result: grpc::Result<T>, kind: &str) {
+        match result {
+            Err(grpc::Error::GrpcMessage(error)) => {
+                assert_eq!(
+                    error.grpc_status,
+                    grpc::GrpcStatus::ResourceExhausted as i32
+                );
+                assert!(error.grpc_message.contains(&format!("query {}", kind)));
+            }
+            other => panic!("expected a ResourceExhausted error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn requests_above_the_limit_are_rejected_before_parsing() {
+        let mut request = ipc::QueryRequest::new();
+        request.set_state_hash(vec![0u8; 32]);
+        let bytes = Bytes::from(request.write_to_bytes().unwrap());
+        assert_eq!(bytes.len(), 34);
+
+        let marshaller = LimitedMarshaller::<ipc::QueryRequest>::new("query", 34, 0);
+        assert_eq!(marshaller.read(bytes.clone()).unwrap(), request);
+
+        // Garbage of the same size fails on the size, not on parsing.
+        let marshaller = LimitedMarshaller::<ipc::QueryRequest>::new("query", 33, 0);
+        assert_resource_exhausted(marshaller.read(Bytes::from(vec![0xffu8; 34])), "request");
+    }
+
+    #[test]
+    fn responses_above_the_limit_are_not_sent() {
+        let mut response = ipc::QueryResponse::new();
+        response.set_failure("x".repeat(32));
+
+        let marshaller = LimitedMarshaller::<ipc::QueryResponse>::new("query", 0, 34);
+        assert!(marshaller.write(&response).is_ok());
+
+        let marshaller = LimitedMarshaller::<ipc::QueryResponse>::new("query", 0, 33);
+        assert_resource_exhausted(marshaller.write(&response), "response");
+    }
+}
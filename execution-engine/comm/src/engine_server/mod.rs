@@ -1,50 +1,82 @@
 use std::collections::HashMap;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::fmt::Debug;
-use std::io::ErrorKind;
 use std::marker::{Send, Sync};
+use std::net::SocketAddr;
 use std::time::Instant;
 
 use common::key::Key;
-use common::value::account::{BlockTime, PublicKey};
+use common::uref::URef;
+use common::value::account::{BlockTime, PublicKey, PurseId};
 use common::value::U512;
 use engine_server::ipc::CommitResponse;
-use execution_engine::engine_state::error::Error as EngineError;
+use execution_engine::engine_state::error::{Error as EngineError, ExecuteError, RootNotFound};
+use execution_engine::engine_state::execute_request::{dedup_deploys, ExecuteRequest};
+use execution_engine::engine_state::execution_effect::{
+    ApplyEffectResult, BatchApplyEffectsResult, BatchedEffects,
+};
 use execution_engine::engine_state::execution_result::ExecutionResult;
 use execution_engine::engine_state::genesis::GenesisURefsSource;
+use execution_engine::engine_state::query::{
+    BalanceResult, NamedKeysResult, QueryRequest, QueryResult, TotalSupplyResult,
+};
+use execution_engine::engine_state::slashing::SlashRequest;
+use execution_engine::engine_state::staking::StakingCheck;
+use execution_engine::engine_state::upgrade::{AddSystemPurses, UpgradeRequest};
+use execution_engine::engine_state::utils::validate_module;
 use execution_engine::engine_state::{
     genesis::GenesisResult, get_bonded_validators, EngineState, GetBondedValidatorsError,
 };
-use execution_engine::execution::{Executor, WasmiExecutor};
-use execution_engine::tracking_copy::QueryResult;
 use shared::logging;
 use shared::logging::{log_duration, log_info};
+use shared::motes::Motes;
 use shared::newtypes::{Blake2bHash, CorrelationId};
+#[cfg(unix)]
+use shared::socket::Socket;
 use storage::global_state::{CommitResult, History};
-use wasm_prep::wasm_costs::WasmCosts;
-use wasm_prep::{Preprocessor, WasmiPreprocessor};
 
+use self::auth::AdminAuth;
 use self::ipc_grpc::ExecutionEngineService;
 use self::mappings::*;
+use self::throttle::{MethodLimits, ThrottledService};
 
+pub mod auth;
 pub mod ipc;
 pub mod ipc_grpc;
 pub mod mappings;
+pub mod message_size;
+pub mod notifications;
 pub mod state;
+pub mod throttle;
 
-const EXPECTED_PUBLIC_KEY_LENGTH: usize = 32;
+// Used by `validate` when the node doesn't specify the protocol version.
+const DEFAULT_VALIDATE_PROTOCOL_VERSION: u64 = 1;
 
 const METRIC_DURATION_COMMIT: &str = "commit_duration";
+const METRIC_DURATION_BATCH_COMMIT: &str = "batch_commit_duration";
 const METRIC_DURATION_EXEC: &str = "exec_duration";
 const METRIC_DURATION_QUERY: &str = "query_duration";
+const METRIC_DURATION_QUERY_TOTAL_SUPPLY: &str = "query_total_supply_duration";
+const METRIC_DURATION_QUERY_BALANCE: &str = "query_balance_duration";
+const METRIC_DURATION_GET_NAMED_KEYS: &str = "get_named_keys_duration";
 const METRIC_DURATION_VALIDATE: &str = "validate_duration";
 const METRIC_DURATION_GENESIS: &str = "genesis_duration";
+const METRIC_DURATION_SLASH: &str = "slash_duration";
+const METRIC_DURATION_UPGRADE: &str = "upgrade_duration";
+const METRIC_DURATION_VALIDATE_STAKING: &str = "validate_staking_duration";
 
 const TAG_RESPONSE_COMMIT: &str = "commit_response";
+const TAG_RESPONSE_BATCH_COMMIT: &str = "batch_commit_response";
 const TAG_RESPONSE_EXEC: &str = "exec_response";
 const TAG_RESPONSE_QUERY: &str = "query_response";
+const TAG_RESPONSE_QUERY_TOTAL_SUPPLY: &str = "query_total_supply_response";
+const TAG_RESPONSE_QUERY_BALANCE: &str = "query_balance_response";
+const TAG_RESPONSE_GET_NAMED_KEYS: &str = "get_named_keys_response";
 const TAG_RESPONSE_VALIDATE: &str = "validate_response";
 const TAG_RESPONSE_GENESIS: &str = "genesis_response";
+const TAG_RESPONSE_SLASH: &str = "slash_response";
+const TAG_RESPONSE_UPGRADE: &str = "upgrade_response";
+const TAG_RESPONSE_VALIDATE_STAKING: &str = "validate_staking_response";
 
 // Idea is that Engine will represent the core of the execution engine project.
 // It will act as an entry point for execution of Wasm binaries.
@@ -59,45 +91,16 @@ where
     fn query(
         &self,
         _request_options: ::grpc::RequestOptions,
-        query_request: ipc::QueryRequest,
+        mut query_request: ipc::QueryRequest,
     ) -> grpc::SingleResponse<ipc::QueryResponse> {
         let start = Instant::now();
         let correlation_id = CorrelationId::new();
         // TODO: don't unwrap
         let state_hash: Blake2bHash = query_request.get_state_hash().try_into().unwrap();
 
-        let mut tracking_copy = match self.tracking_copy(state_hash) {
-            Err(storage_error) => {
-                let mut result = ipc::QueryResponse::new();
-                let error = format!("Error during checkout out Trie: {:?}", storage_error);
-                logging::log_error(&error);
-                result.set_failure(error);
-                log_duration(
-                    correlation_id,
-                    METRIC_DURATION_QUERY,
-                    "tracking_copy_error",
-                    start.elapsed(),
-                );
-                return grpc::SingleResponse::completed(result);
-            }
-            Ok(None) => {
-                let mut result = ipc::QueryResponse::new();
-                let error = format!("Root not found: {:?}", state_hash);
-                logging::log_warning(&error);
-                result.set_failure(error);
-                log_duration(
-                    correlation_id,
-                    METRIC_DURATION_QUERY,
-                    "tracking_copy_root_not_found",
-                    start.elapsed(),
-                );
-                return grpc::SingleResponse::completed(result);
-            }
-            Ok(Some(tracking_copy)) => tracking_copy,
-        };
-
-        let key = match query_request.get_base_key().try_into() {
-            Err(ParsingError(err_msg)) => {
+        let base_key = match Key::try_from(query_request.get_base_key()) {
+            Err(error) => {
+                let err_msg = error.to_string();
                 logging::log_error(&err_msg);
                 let mut result = ipc::QueryResponse::new();
                 result.set_failure(err_msg);
@@ -112,9 +115,14 @@ where
             Ok(key) => key,
         };
 
-        let path = query_request.get_path();
+        let request = QueryRequest {
+            state_hash,
+            base_key,
+            path: query_request.take_path().into_vec(),
+        };
+        let serialized = query_request.get_serialized();
 
-        let response = match tracking_copy.query(correlation_id, key, path) {
+        let response = match self.run_query(correlation_id, &request) {
             Err(err) => {
                 let mut result = ipc::QueryResponse::new();
                 let error = format!("{:?}", err);
@@ -122,6 +130,13 @@ where
                 result.set_failure(error);
                 result
             }
+            Ok(QueryResult::RootNotFound) => {
+                let mut result = ipc::QueryResponse::new();
+                let error = format!("Root not found: {:?}", state_hash);
+                logging::log_warning(&error);
+                result.set_failure(error);
+                result
+            }
             Ok(QueryResult::ValueNotFound(full_path)) => {
                 let mut result = ipc::QueryResponse::new();
                 let error = format!("Value not found: {:?}", full_path);
@@ -131,7 +146,18 @@ where
             }
             Ok(QueryResult::Success(value)) => {
                 let mut result = ipc::QueryResponse::new();
-                result.set_success(value.into());
+                if serialized {
+                    match serialized_value(&value) {
+                        Ok(serialized_value) => result.set_serialized_value(serialized_value),
+                        Err(err) => {
+                            let error = format!("Value couldn't be serialized: {:?}", err);
+                            logging::log_error(&error);
+                            result.set_failure(error);
+                        }
+                    }
+                } else {
+                    result.set_success(value.into());
+                }
                 result
             }
         };
@@ -146,54 +172,306 @@ where
         grpc::SingleResponse::completed(response)
     }
 
-    fn exec(
+    fn query_total_supply(
         &self,
         _request_options: ::grpc::RequestOptions,
-        exec_request: ipc::ExecRequest,
-    ) -> grpc::SingleResponse<ipc::ExecResponse> {
+        total_supply_request: ipc::TotalSupplyRequest,
+    ) -> grpc::SingleResponse<ipc::TotalSupplyResponse> {
         let start = Instant::now();
         let correlation_id = CorrelationId::new();
+        let mut response = ipc::TotalSupplyResponse::new();
 
-        let protocol_version = exec_request.get_protocol_version();
+        match total_supply_request.get_state_hash().try_into() {
+            Err(_) => {
+                let error = "State hash has to be 32 bytes long.".to_string();
+                logging::log_error(&error);
+                response.set_failure(error);
+            }
+            Ok(state_hash) => match self.run_total_supply_query(correlation_id, state_hash) {
+                Ok(TotalSupplyResult::Success(total_supply)) => {
+                    response.set_success(total_supply.into())
+                }
+                Ok(TotalSupplyResult::RootNotFound) => {
+                    logging::log_warning("RootNotFound");
+                    response.set_missing_state(RootNotFound(state_hash).into());
+                }
+                Ok(TotalSupplyResult::NotTracked) => {
+                    let error = format!("Total supply is not tracked at state {:?}", state_hash);
+                    logging::log_warning(&error);
+                    response.set_failure(error);
+                }
+                Err(error) => {
+                    let error = error.to_string();
+                    logging::log_error(&error);
+                    response.set_failure(error);
+                }
+            },
+        }
 
-        // TODO: don't unwrap
-        let prestate_hash: Blake2bHash = exec_request.get_parent_state_hash().try_into().unwrap();
+        log_duration(
+            correlation_id,
+            METRIC_DURATION_QUERY_TOTAL_SUPPLY,
+            TAG_RESPONSE_QUERY_TOTAL_SUPPLY,
+            start.elapsed(),
+        );
 
-        let blocktime = BlockTime(exec_request.get_block_time());
+        grpc::SingleResponse::completed(response)
+    }
 
-        // TODO: don't unwrap
-        let wasm_costs = WasmCosts::from_version(protocol_version.value).unwrap();
+    fn query_balance(
+        &self,
+        _request_options: ::grpc::RequestOptions,
+        balance_request: ipc::BalanceRequest,
+    ) -> grpc::SingleResponse<ipc::BalanceResponse> {
+        let start = Instant::now();
+        let correlation_id = CorrelationId::new();
+        let mut response = ipc::BalanceResponse::new();
+
+        let request: Result<(Blake2bHash, PurseId), String> = balance_request
+            .get_state_hash()
+            .try_into()
+            .map_err(|_| "State hash has to be 32 bytes long.".to_string())
+            .and_then(|state_hash| {
+                let purse: URef = balance_request
+                    .get_purse()
+                    .try_into()
+                    .map_err(|error: ParsingError| error.to_string())?;
+                Ok((state_hash, PurseId::new(purse)))
+            });
+
+        match request {
+            Err(error) => {
+                logging::log_error(&error);
+                response.set_failure(error);
+            }
+            Ok((state_hash, purse_id)) => {
+                match self.get_purse_balance(correlation_id, state_hash, purse_id) {
+                    Ok(BalanceResult::Success(balance)) => response.set_success(balance.into()),
+                    Ok(BalanceResult::RootNotFound) => {
+                        logging::log_warning("RootNotFound");
+                        response.set_missing_state(RootNotFound(state_hash).into());
+                    }
+                    Ok(BalanceResult::PurseNotFound) => {
+                        let error = format!("Purse not found: {:?}", purse_id);
+                        logging::log_warning(&error);
+                        response.set_failure(error);
+                    }
+                    Err(error) => {
+                        let error = error.to_string();
+                        logging::log_error(&error);
+                        response.set_failure(error);
+                    }
+                }
+            }
+        }
+
+        log_duration(
+            correlation_id,
+            METRIC_DURATION_QUERY_BALANCE,
+            TAG_RESPONSE_QUERY_BALANCE,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(response)
+    }
 
-        let deploys = exec_request.get_deploys();
+    fn get_named_keys(
+        &self,
+        _request_options: ::grpc::RequestOptions,
+        named_keys_request: ipc::NamedKeysRequest,
+    ) -> grpc::SingleResponse<ipc::NamedKeysResponse> {
+        let start = Instant::now();
+        let correlation_id = CorrelationId::new();
+        let mut response = ipc::NamedKeysResponse::new();
+
+        let request: Result<(Blake2bHash, Key), String> = named_keys_request
+            .get_state_hash()
+            .try_into()
+            .map_err(|_| "State hash has to be 32 bytes long.".to_string())
+            .and_then(|state_hash| {
+                let key = named_keys_request
+                    .get_key()
+                    .try_into()
+                    .map_err(|error: ParsingError| error.to_string())?;
+                Ok((state_hash, key))
+            });
+
+        match request {
+            Err(error) => {
+                logging::log_error(&error);
+                response.set_failure(error);
+            }
+            Ok((state_hash, key)) => match self.get_named_keys(correlation_id, state_hash, key) {
+                Ok(NamedKeysResult::Success(named_keys)) => {
+                    let mut ipc_named_keys = ipc::NamedKeysResponse_NamedKeys::new();
+                    let named_keys: Vec<state::NamedKey> = URefMap(named_keys).into();
+                    ipc_named_keys.set_named_keys(named_keys.into());
+                    response.set_success(ipc_named_keys);
+                }
+                Ok(NamedKeysResult::RootNotFound) => {
+                    logging::log_warning("RootNotFound");
+                    response.set_missing_state(RootNotFound(state_hash).into());
+                }
+                Ok(NamedKeysResult::ValueNotFound) => {
+                    let error = format!("Value not found: {:?}", key);
+                    logging::log_warning(&error);
+                    response.set_failure(error);
+                }
+                Ok(NamedKeysResult::InvalidType(type_string)) => {
+                    let error = format!(
+                        "Expected an account or a contract under {:?}, found {}",
+                        key, type_string
+                    );
+                    logging::log_warning(&error);
+                    response.set_failure(error);
+                }
+                Err(error) => {
+                    let error = error.to_string();
+                    logging::log_error(&error);
+                    response.set_failure(error);
+                }
+            },
+        }
 
-        let preprocessor: WasmiPreprocessor = WasmiPreprocessor::new(wasm_costs);
+        log_duration(
+            correlation_id,
+            METRIC_DURATION_GET_NAMED_KEYS,
+            TAG_RESPONSE_GET_NAMED_KEYS,
+            start.elapsed(),
+        );
 
-        let executor = WasmiExecutor;
+        grpc::SingleResponse::completed(response)
+    }
 
-        let deploys_result: Result<Vec<ipc::DeployResult>, ipc::RootNotFound> = run_deploys(
-            &self,
-            &executor,
-            &preprocessor,
-            prestate_hash,
-            blocktime,
+    fn exec(
+        &self,
+        _request_options: ::grpc::RequestOptions,
+        mut exec_request: ipc::ExecRequest,
+    ) -> grpc::SingleResponse<ipc::ExecResponse> {
+        let start = Instant::now();
+        let correlation_id = CorrelationId::new();
+
+        // TODO: don't unwrap
+        let parent_state_hash: Blake2bHash =
+            exec_request.get_parent_state_hash().try_into().unwrap();
+
+        let block_reward: Option<Result<Motes, ParsingError>> = if exec_request.has_block_reward() {
+            Some(exec_request.get_block_reward().try_into().map(Motes::new))
+        } else {
+            None
+        };
+
+        // Duplicates of a deploy aren't executed again but get a copy of its result.
+        let (deploys, result_indices) = dedup_deploys(
+            exec_request
+                .take_deploys()
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        );
+        if deploys.len() < result_indices.len() {
+            log_info(&format!(
+                "skipping {} duplicate deploys",
+                result_indices.len() - deploys.len()
+            ));
+        }
+
+        let block_time = BlockTime(exec_request.get_block_time());
+        let collect_rent = exec_request.get_collect_rent();
+        let protocol_version = exec_request.get_protocol_version().value;
+        let request = ExecuteRequest {
+            parent_state_hash,
+            block_time,
             deploys,
             protocol_version,
-            correlation_id,
-        );
+            invalid_nonce_policy: exec_request.get_invalid_nonce_mode().into(),
+            check_deploy_order: exec_request.get_check_deploy_order(),
+        };
 
-        let exec_response = match deploys_result {
-            Ok(deploy_results) => {
-                let mut exec_response = ipc::ExecResponse::new();
+        // The first deploy of the request executed as the one at `deploy_index`.
+        let request_index = |deploy_index: usize| {
+            result_indices
+                .iter()
+                .position(|index| *index == deploy_index)
+                .unwrap_or(deploy_index)
+        };
+        let exec_result: Result<ipc::ExecResult, ExecuteError> = self
+            .run_execute(correlation_id, request)
+            .map_err(|error| match error {
+                ExecuteError::InvalidNonce {
+                    deploy_index,
+                    deploy_nonce,
+                    expected_nonce,
+                } => ExecuteError::InvalidNonce {
+                    deploy_index: request_index(deploy_index),
+                    deploy_nonce,
+                    expected_nonce,
+                },
+                ExecuteError::MisorderedDeploy {
+                    deploy_index,
+                    deploy_nonce,
+                    previous_index,
+                    previous_nonce,
+                } => ExecuteError::MisorderedDeploy {
+                    deploy_index: request_index(deploy_index),
+                    deploy_nonce,
+                    previous_index: request_index(previous_index),
+                    previous_nonce,
+                },
+                error => error,
+            })
+            .and_then(|results| {
+                // Collected before the results are converted, to leave alone the rent records
+                // the deploys write.
+                let rent_collection_result = if collect_rent {
+                    Some(self.run_rent_collection(
+                        correlation_id,
+                        parent_state_hash,
+                        block_time,
+                        protocol_version,
+                        &results,
+                    )?)
+                } else {
+                    None
+                };
+                let results: Vec<ipc::DeployResult> = results
+                    .into_iter()
+                    .map(|result| deploy_result(self, correlation_id, parent_state_hash, result))
+                    .collect();
+                let deploy_results = result_indices
+                    .iter()
+                    .map(|index| results[*index].clone())
+                    .collect();
                 let mut exec_result = ipc::ExecResult::new();
                 exec_result.set_deploy_results(protobuf::RepeatedField::from_vec(deploy_results));
+                match block_reward {
+                    None => (),
+                    Some(Ok(block_reward)) => {
+                        let finalization_result =
+                            self.run_finalization(correlation_id, parent_state_hash, block_reward)?;
+                        exec_result.set_finalization_result(finalization_result.into());
+                    }
+                    Some(Err(error)) => {
+                        let error_message = error.to_string();
+                        logging::log_error(&error_message);
+                        exec_result.set_finalization_result(precondition_failure(error_message));
+                    }
+                }
+                if let Some(rent_collection_result) = rent_collection_result {
+                    exec_result.set_rent_collection_result(rent_collection_result.into());
+                }
+                Ok(exec_result)
+            });
+
+        let exec_response = match exec_result {
+            Ok(exec_result) => {
+                let mut exec_response = ipc::ExecResponse::new();
                 exec_response.set_success(exec_result);
                 exec_response
             }
             Err(error) => {
-                logging::log_error("deploy results error: RootNotFound");
-                let mut exec_response = ipc::ExecResponse::new();
-                exec_response.set_missing_parent(error);
-                exec_response
+                logging::log_error(&format!("deploy results error: {:?}", error));
+                error.into()
             }
         };
 
@@ -210,7 +488,7 @@ where
     fn commit(
         &self,
         _request_options: ::grpc::RequestOptions,
-        commit_request: ipc::CommitRequest,
+        mut commit_request: ipc::CommitRequest,
     ) -> grpc::SingleResponse<ipc::CommitResponse> {
         let start = Instant::now();
         let correlation_id = CorrelationId::new();
@@ -218,41 +496,51 @@ where
         // TODO: don't unwrap
         let prestate_hash: Blake2bHash = commit_request.get_prestate_hash().try_into().unwrap();
 
-        let effects_result: Result<CommitTransforms, ParsingError> =
-            commit_request.get_effects().try_into();
+        let ops_result: Result<CommitOps, ParsingError> = commit_request.get_ops().try_into();
+        let effects_result: Result<
+            (CommitOps, CommitTransforms, Option<Blake2bHash>),
+            ParsingError,
+        > = ops_result.and_then(|ops| {
+            let expected_poststate_hash = expected_poststate_hash(&commit_request)?;
+            let effects = commit_request.take_effects().into_vec().try_into()?;
+            Ok((ops, effects, expected_poststate_hash))
+        });
 
         let commit_response = match effects_result {
-            Err(ParsingError(error_message)) => {
-                logging::log_error(&error_message);
-                let mut commit_response = ipc::CommitResponse::new();
-                let mut err = ipc::PostEffectsError::new();
-                err.set_message(error_message);
-                commit_response.set_failed_transform(err);
-                commit_response
+            Err(error) => {
+                logging::log_error(&error.to_string());
+                parsing_failure(&error)
             }
 
-            Ok(effects) => {
-                let commit_result =
-                    self.apply_effect(correlation_id, prestate_hash, effects.value());
-                if let Ok(storage::global_state::CommitResult::Success(poststate_hash)) =
-                    commit_result
-                {
-                    let pos_key = Key::URef(GenesisURefsSource::default().get_pos_address());
-                    let bonded_validators_res = get_bonded_validators(
-                        self.state(),
-                        poststate_hash,
-                        &pos_key,
+            Ok((ops, effects, expected_poststate_hash)) => {
+                // Ops are optional; without them the effects are committed unchecked.
+                let ops = ops.value();
+                let ops = if ops.is_empty() { None } else { Some(&ops) };
+                match self.apply_effect(
+                    correlation_id,
+                    prestate_hash,
+                    ops,
+                    effects.value(),
+                    expected_poststate_hash,
+                ) {
+                    Ok(ApplyEffectResult::Rejected(inconsistent_op)) => {
+                        let error_message = format!("Effects rejected: {}", inconsistent_op);
+                        logging::log_error(&error_message);
+                        let mut commit_response = ipc::CommitResponse::new();
+                        let mut err = ipc::PostEffectsError::new();
+                        err.set_message(error_message);
+                        commit_response.set_failed_transform(err);
+                        commit_response
+                    }
+                    Ok(ApplyEffectResult::Applied(commit_result)) => commit_result_response(
+                        self,
                         correlation_id,
-                    );
-                    bonded_validators_and_commit_result(
                         prestate_hash,
-                        poststate_hash,
-                        commit_result,
-                        bonded_validators_res,
-                    )
-                } else {
-                    // Commit unsuccessful.
-                    grpc_response_from_commit_result::<H>(prestate_hash, commit_result)
+                        Ok(commit_result),
+                    ),
+                    Err(error) => {
+                        commit_result_response(self, correlation_id, prestate_hash, Err(error))
+                    }
                 }
             }
         };
@@ -267,6 +555,75 @@ where
         grpc::SingleResponse::completed(commit_response)
     }
 
+    fn batch_commit(
+        &self,
+        _request_options: ::grpc::RequestOptions,
+        batch_commit_request: ipc::BatchCommitRequest,
+    ) -> grpc::SingleResponse<ipc::BatchCommitResponse> {
+        let start = Instant::now();
+        let correlation_id = CorrelationId::new();
+
+        let batch_result = batch_commit_request
+            .get_commits()
+            .iter()
+            .enumerate()
+            .map(|(index, commit_request)| {
+                BatchedEffects::try_from(commit_request).map_err(|error| (index, error))
+            })
+            .collect::<Result<Vec<BatchedEffects>, (usize, ParsingError)>>();
+
+        let batch_commit_response = match batch_result {
+            Err((index, error)) => {
+                logging::log_error(&error.to_string());
+                batch_commit_failure(index, parsing_failure(&error))
+            }
+            Ok(batch) => {
+                let prestate_hashes: Vec<Blake2bHash> = batch
+                    .iter()
+                    .map(|batched_effects| batched_effects.prestate_hash)
+                    .collect();
+                match self.apply_effects_batch(correlation_id, batch) {
+                    Ok(BatchApplyEffectsResult::Failed {
+                        index,
+                        result: ApplyEffectResult::Rejected(inconsistent_op),
+                    }) => {
+                        let error_message = format!("Effects rejected: {}", inconsistent_op);
+                        logging::log_error(&error_message);
+                        batch_commit_failure(index, failed_transform(error_message))
+                    }
+                    Ok(BatchApplyEffectsResult::Failed {
+                        index,
+                        result: ApplyEffectResult::Applied(commit_result),
+                    }) => batch_commit_failure(
+                        index,
+                        grpc_response_from_commit_result::<H>(
+                            prestate_hashes[index],
+                            Ok(commit_result),
+                        ),
+                    ),
+                    Ok(BatchApplyEffectsResult::Applied(commit_results)) => {
+                        batch_commit_success(self, correlation_id, &prestate_hashes, commit_results)
+                    }
+                    Err(error) => {
+                        // Storage errors aren't tied to a particular commit of the batch.
+                        let error_message = format!("{:?}", error);
+                        logging::log_error(&error_message);
+                        batch_commit_failure(0, failed_transform(error_message))
+                    }
+                }
+            }
+        };
+
+        log_duration(
+            correlation_id,
+            METRIC_DURATION_BATCH_COMMIT,
+            TAG_RESPONSE_BATCH_COMMIT,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(batch_commit_response)
+    }
+
     fn validate(
         &self,
         _request_options: ::grpc::RequestOptions,
@@ -275,11 +632,17 @@ where
         let start = Instant::now();
         let correlation_id = CorrelationId::new();
 
-        let pay_mod = wabt::Module::read_binary(
-            validate_request.payment_code,
-            &wabt::ReadBinaryOptions::default(),
-        )
-        .and_then(|x| x.validate());
+        let protocol_version = if validate_request.has_protocol_version() {
+            validate_request.get_protocol_version().value
+        } else {
+            DEFAULT_VALIDATE_PROTOCOL_VERSION
+        };
+
+        let pay_mod = validate_module(
+            validate_request.get_payment_code(),
+            protocol_version,
+            self.protocols(),
+        );
 
         log_duration(
             correlation_id,
@@ -288,11 +651,11 @@ where
             start.elapsed(),
         );
 
-        let ses_mod = wabt::Module::read_binary(
-            validate_request.session_code,
-            &wabt::ReadBinaryOptions::default(),
-        )
-        .and_then(|x| x.validate());
+        let ses_mod = validate_module(
+            validate_request.get_session_code(),
+            protocol_version,
+            self.protocols(),
+        );
 
         log_duration(
             correlation_id,
@@ -475,75 +838,263 @@ where
 
         grpc::SingleResponse::completed(genesis_response)
     }
+
+    fn slash(
+        &self,
+        _request_options: ::grpc::RequestOptions,
+        slash_request: ipc::SlashRequest,
+    ) -> grpc::SingleResponse<ipc::SlashResponse> {
+        let start = Instant::now();
+        let correlation_id = CorrelationId::new();
+
+        let slash_response = match SlashRequest::try_from(&slash_request) {
+            Err(error) => {
+                let error = error.to_string();
+                logging::log_error(&error);
+                let mut slash_response = ipc::SlashResponse::new();
+                slash_response.set_failure(error);
+                slash_response
+            }
+            Ok(request) => match self.run_slash(correlation_id, &request) {
+                Ok(slash_result) => slash_result.into(),
+                Err(error) => {
+                    let error = error.to_string();
+                    logging::log_error(&error);
+                    let mut slash_response = ipc::SlashResponse::new();
+                    slash_response.set_failure(error);
+                    slash_response
+                }
+            },
+        };
+
+        log_duration(
+            correlation_id,
+            METRIC_DURATION_SLASH,
+            TAG_RESPONSE_SLASH,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(slash_response)
+    }
+
+    fn upgrade(
+        &self,
+        _request_options: ::grpc::RequestOptions,
+        upgrade_request: ipc::UpgradeRequest,
+    ) -> grpc::SingleResponse<ipc::UpgradeResponse> {
+        let start = Instant::now();
+        let correlation_id = CorrelationId::new();
+
+        let upgrade_response = match UpgradeRequest::try_from(&upgrade_request) {
+            Err(error) => {
+                let error = error.to_string();
+                logging::log_error(&error);
+                let mut upgrade_response = ipc::UpgradeResponse::new();
+                upgrade_response.set_failure(error);
+                upgrade_response
+            }
+            Ok(request) => match self.run_upgrade(correlation_id, &request, &AddSystemPurses) {
+                Ok(upgrade_result) => upgrade_result.into(),
+                Err(error) => {
+                    let error = error.to_string();
+                    logging::log_error(&error);
+                    let mut upgrade_response = ipc::UpgradeResponse::new();
+                    upgrade_response.set_failure(error);
+                    upgrade_response
+                }
+            },
+        };
+
+        log_duration(
+            correlation_id,
+            METRIC_DURATION_UPGRADE,
+            TAG_RESPONSE_UPGRADE,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(upgrade_response)
+    }
+
+    fn validate_staking(
+        &self,
+        _request_options: ::grpc::RequestOptions,
+        staking_request: ipc::StakingRequest,
+    ) -> grpc::SingleResponse<ipc::StakingResponse> {
+        let start = Instant::now();
+        let correlation_id = CorrelationId::new();
+
+        let staking_response = match StakingCheck::try_from(&staking_request) {
+            Err(error) => {
+                let error = error.to_string();
+                logging::log_error(&error);
+                let mut staking_response = ipc::StakingResponse::new();
+                staking_response.set_failure(error);
+                staking_response
+            }
+            Ok(check) => match self.run_staking_check(correlation_id, &check) {
+                Ok(check_result) => check_result.into(),
+                Err(error) => {
+                    let error = error.to_string();
+                    logging::log_error(&error);
+                    let mut staking_response = ipc::StakingResponse::new();
+                    staking_response.set_failure(error);
+                    staking_response
+                }
+            },
+        };
+
+        log_duration(
+            correlation_id,
+            METRIC_DURATION_VALIDATE_STAKING,
+            TAG_RESPONSE_VALIDATE_STAKING,
+            start.elapsed(),
+        );
+
+        grpc::SingleResponse::completed(staking_response)
+    }
+
+    /// Commit notifications are published by the `ThrottledService` wrapping the engine.
+    fn subscribe_commits(
+        &self,
+        _request_options: ::grpc::RequestOptions,
+        _subscribe_request: ipc::SubscribeCommitsRequest,
+    ) -> grpc::StreamingResponse<ipc::CommitNotification> {
+        grpc::StreamingResponse::err(grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+            grpc_status: grpc::GrpcStatus::Unimplemented as i32,
+            grpc_message: "commit notifications are not published by the engine itself".to_owned(),
+        }))
+    }
+}
+
+/// Rejects a commit whose effects couldn't be applied, with the reason as message.
+fn failed_transform(error_message: String) -> CommitResponse {
+    let mut commit_response = ipc::CommitResponse::new();
+    let mut err = ipc::PostEffectsError::new();
+    err.set_message(error_message);
+    commit_response.set_failed_transform(err);
+    commit_response
+}
+
+/// Rejects effects which couldn't be parsed, telling the node which field was malformed.
+fn parsing_failure(error: &ParsingError) -> CommitResponse {
+    let mut commit_response = ipc::CommitResponse::new();
+    let mut err = ipc::PostEffectsError::new();
+    err.set_message(error.to_string());
+    err.set_parsing_failure(error.into());
+    commit_response.set_failed_transform(err);
+    commit_response
 }
 
-#[allow(clippy::too_many_arguments)]
-fn run_deploys<A, H, E, P>(
+fn batch_commit_failure(index: usize, result: CommitResponse) -> ipc::BatchCommitResponse {
+    let mut failure = ipc::BatchCommitResponse_Failure::new();
+    failure.set_index(index as u32);
+    failure.set_result(result);
+    let mut batch_commit_response = ipc::BatchCommitResponse::new();
+    batch_commit_response.set_failure(failure);
+    batch_commit_response
+}
+
+fn batch_commit_success<H>(
     engine_state: &EngineState<H>,
-    executor: &E,
-    preprocessor: &P,
-    prestate_hash: Blake2bHash,
-    blocktime: BlockTime,
-    deploys: &[ipc::Deploy],
-    protocol_version: &state::ProtocolVersion,
     correlation_id: CorrelationId,
-) -> Result<Vec<ipc::DeployResult>, ipc::RootNotFound>
+    prestate_hashes: &[Blake2bHash],
+    commit_results: Vec<CommitResult>,
+) -> ipc::BatchCommitResponse
 where
     H: History,
-    E: Executor<A>,
-    P: Preprocessor<A>,
-    EngineError: From<H::Error>,
-    H::Error: Into<execution_engine::execution::Error>,
+    H::Error: Into<EngineError> + Into<execution_engine::execution::Error> + Debug,
 {
-    // We want to treat RootNotFound error differently b/c it should short-circuit
-    // the execution of ALL deploys within the block. This is because all of them share
-    // the same prestate and all of them would fail.
-    // Iterator (Result<_, _> + collect()) will short circuit the execution
-    // when run_deploy returns Err.
-    deploys
-        .iter()
-        .map(|deploy| {
-            let session_contract = deploy.get_session();
-            let module_bytes = &session_contract.code;
-            let args = &session_contract.args;
-            let address = {
-                let address_len = deploy.address.len();
-                if address_len != EXPECTED_PUBLIC_KEY_LENGTH {
-                    let err = EngineError::InvalidPublicKeyLength {
-                        expected: EXPECTED_PUBLIC_KEY_LENGTH,
-                        actual: address_len,
-                    };
-                    let failure = ExecutionResult::precondition_failure(err);
-                    return Ok(failure.into());
-                }
-                let mut dest = [0; EXPECTED_PUBLIC_KEY_LENGTH];
-                dest.copy_from_slice(&deploy.address);
-                Key::Account(dest)
-            };
-
-            let nonce = deploy.nonce;
-            // TODO: is the rounding in this division ok?
-            let gas_limit =
-                (deploy.tokens_transferred_in_payment as u64) / (deploy.gas_price as u64);
-            let protocol_version = protocol_version.value;
-            engine_state
-                .run_deploy(
-                    module_bytes,
-                    args,
-                    address,
-                    blocktime,
-                    nonce,
-                    prestate_hash,
-                    gas_limit,
-                    protocol_version,
-                    correlation_id,
-                    executor,
-                    preprocessor,
-                )
-                .map(Into::into)
-                .map_err(Into::into)
-        })
-        .collect()
+    let mut results = Vec::with_capacity(commit_results.len());
+    for (commit_result, prestate_hash) in commit_results.into_iter().zip(prestate_hashes) {
+        let mut commit_response = commit_result_response(
+            engine_state,
+            correlation_id,
+            *prestate_hash,
+            Ok(commit_result),
+        );
+        if !commit_response.has_success() {
+            // The batch has been stored already, but the bonded validators at this post state
+            // couldn't be read.
+            return batch_commit_failure(results.len(), commit_response);
+        }
+        results.push(commit_response.take_success());
+    }
+    let mut success = ipc::BatchCommitResponse_Success::new();
+    success.set_results(results.into());
+    let mut batch_commit_response = ipc::BatchCommitResponse::new();
+    batch_commit_response.set_success(success);
+    batch_commit_response
+}
+
+/// Maps `result` to its message, along with the state root of its effects if the engine is
+/// configured with `deploy_state_roots`.
+fn deploy_result<H>(
+    engine_state: &EngineState<H>,
+    correlation_id: CorrelationId,
+    parent_state_hash: Blake2bHash,
+    result: ExecutionResult,
+) -> ipc::DeployResult
+where
+    H: History,
+    H::Error: Into<execution_engine::execution::Error> + Debug,
+{
+    let state_root = if engine_state.config().deploy_state_roots {
+        let effect = match &result {
+            ExecutionResult::Success { effect, .. } | ExecutionResult::Failure { effect, .. } => {
+                effect
+            }
+        };
+        engine_state
+            .deploy_state_root(correlation_id, parent_state_hash, effect)
+            .unwrap_or_else(|error| {
+                logging::log_error(&format!("deploy state root error: {:?}", error));
+                None
+            })
+    } else {
+        None
+    };
+    let mut deploy_result: ipc::DeployResult = result.into();
+    if let Some(state_root) = state_root {
+        // Results without execution, e.g. precondition failures, have no effects to speak of.
+        if deploy_result.has_execution_result() {
+            deploy_result
+                .mut_execution_result()
+                .set_debug_state_root(state_root.to_vec());
+        }
+    }
+    deploy_result
+}
+
+/// Builds the response to a commit, including the bonded validators at the post state if it
+/// succeeded.
+fn commit_result_response<H>(
+    engine_state: &EngineState<H>,
+    correlation_id: CorrelationId,
+    prestate_hash: Blake2bHash,
+    commit_result: Result<CommitResult, H::Error>,
+) -> CommitResponse
+where
+    H: History,
+    H::Error: Into<EngineError> + Into<execution_engine::execution::Error> + Debug,
+{
+    if let Ok(CommitResult::Success(poststate_hash, _)) = commit_result {
+        let pos_key = Key::URef(GenesisURefsSource::default().get_pos_address());
+        let bonded_validators_res = get_bonded_validators::<H>(
+            engine_state.snapshots(),
+            poststate_hash,
+            &pos_key,
+            correlation_id,
+        );
+        bonded_validators_and_commit_result(
+            prestate_hash,
+            poststate_hash,
+            commit_result,
+            bonded_validators_res,
+        )
+    } else {
+        // Commit unsuccessful.
+        grpc_response_from_commit_result::<H>(prestate_hash, commit_result)
+    }
 }
 
 // TODO: Refactor.
@@ -562,19 +1113,9 @@ where
         Ok(bonded_validators) => {
             let mut grpc_response =
                 grpc_response_from_commit_result::<H>(prestate_hash, commit_result);
-            let grpc_bonded_validators = bonded_validators
-                .iter()
-                .map(|(pk, bond)| {
-                    let mut ipc_bond = ipc::Bond::new();
-                    ipc_bond.set_stake((*bond).into());
-                    ipc_bond.set_validator_public_key(pk.value().to_vec());
-                    ipc_bond
-                })
-                .collect::<Vec<ipc::Bond>>()
-                .into();
             grpc_response
                 .mut_success() // We know it's a success because of the check few lines earlier.
-                .set_bonded_validators(grpc_bonded_validators);
+                .set_bonded_validators(to_ipc_bonds(&bonded_validators).into());
             grpc_response
         }
         Err(GetBondedValidatorsError::StorageErrors(error)) => {
@@ -603,22 +1144,62 @@ where
     }
 }
 
-// Helper method which returns single DeployResult that is set to be a WasmError.
+/// Loopback address listened on when Unix domain sockets are not available and
+/// the given socket is not a `host:port` address.
+pub const DEFAULT_LOOPBACK_ADDR: &str = "127.0.0.1:40404";
+
+/// Listens on TCP when `socket` is a `host:port` address, and on a Unix domain
+/// socket at that path otherwise. A socket file still served by another engine is
+/// left alone, and the id of this process is written next to it in a pidfile.
+#[cfg(unix)]
+fn set_listen_addr(server: &mut grpc::ServerBuilder, socket: &str) {
+    if let Ok(addr) = socket.parse::<SocketAddr>() {
+        server
+            .http
+            .set_addr(addr)
+            .expect("failed to bind TCP address");
+        return;
+    }
+
+    if let Err(e) = Socket::new(socket.to_owned()).claim() {
+        panic!("failed to claim socket file: {}", e);
+    }
+
+    server.http.set_unix_addr(socket.to_owned()).unwrap();
+}
+
+/// Without Unix domain sockets the server always listens on TCP: either on the
+/// given `host:port` address, or on [`DEFAULT_LOOPBACK_ADDR`] if `socket` is a path.
+#[cfg(not(unix))]
+fn set_listen_addr(server: &mut grpc::ServerBuilder, socket: &str) {
+    let addr = socket.parse::<SocketAddr>().unwrap_or_else(|_| {
+        logging::log_warning(&format!(
+            "Unix domain sockets are not supported on this platform, listening on {} instead of {}",
+            DEFAULT_LOOPBACK_ADDR, socket
+        ));
+        DEFAULT_LOOPBACK_ADDR
+            .parse()
+            .expect("default loopback address should parse")
+    });
+    server
+        .http
+        .set_addr(addr)
+        .expect("failed to bind TCP address");
+}
+
 pub fn new<E: ExecutionEngineService + Sync + Send + 'static>(
     socket: &str,
     e: E,
+    limits: MethodLimits,
+    admin_auth: AdminAuth,
 ) -> grpc::ServerBuilder {
-    let socket_path = std::path::Path::new(socket);
-
-    if let Err(e) = std::fs::remove_file(socket_path) {
-        if e.kind() != ErrorKind::NotFound {
-            panic!("failed to remove old socket file: {:?}", e);
-        }
-    }
-
     let mut server = grpc::ServerBuilder::new_plain();
-    server.http.set_unix_addr(socket.to_owned()).unwrap();
+    set_listen_addr(&mut server, socket);
     server.http.set_cpu_pool_threads(1);
-    server.add_service(ipc_grpc::ExecutionEngineServiceServer::new_service_def(e));
+    server.add_service(message_size::service_def(
+        ThrottledService::new(e, limits, admin_auth),
+        limits.max_receive_message_size,
+        limits.max_send_message_size,
+    ));
     server
 }
@@ -0,0 +1,153 @@
+use std::sync::{Arc, Mutex};
+
+use futures::sync::mpsc::{self, Receiver, Sender};
+use futures::Stream;
+
+use super::ipc;
+
+/// Number of notifications that can be queued for a single subscriber.
+const SUBSCRIBER_BUFFER_SIZE: usize = 64;
+
+/// Fans out a notification to every subscriber after each successful commit.
+///
+/// Subscribers which don't keep up, i.e. whose buffer is full, are
+/// disconnected rather than slowing down commits or growing memory usage.
+/// They are expected to re-subscribe and catch up by querying the state.
+#[derive(Clone, Default)]
+pub struct CommitNotifier {
+    subscribers: Arc<Mutex<Vec<Sender<ipc::CommitNotification>>>>,
+}
+
+impl CommitNotifier {
+    pub fn subscribe(&self) -> Receiver<ipc::CommitNotification> {
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_BUFFER_SIZE);
+        self.subscribers
+            .lock()
+            .expect("subscribers lock poisoned")
+            .push(sender);
+        receiver
+    }
+
+    /// Sends `notification` to all subscribers, dropping the closed and lagging ones.
+    pub fn publish(&self, notification: ipc::CommitNotification) {
+        let mut subscribers = self.subscribers.lock().expect("subscribers lock poisoned");
+        let mut live = Vec::with_capacity(subscribers.len());
+        for mut subscriber in subscribers.drain(..) {
+            if subscriber.try_send(notification.clone()).is_ok() {
+                live.push(subscriber);
+            }
+        }
+        *subscribers = live;
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers
+            .lock()
+            .expect("subscribers lock poisoned")
+            .len()
+    }
+
+    pub fn streaming_response(&self) -> grpc::StreamingResponse<ipc::CommitNotification> {
+        let stream = self
+            .subscribe()
+            .map_err(|()| grpc::Error::Other("commit notifications closed"));
+        grpc::StreamingResponse::no_metadata(stream)
+    }
+}
+
+/// Builds the notification for a successful commit of `commit_request`.
+pub fn commit_notification(
+    commit_request: &ipc::CommitRequest,
+    commit_result: &ipc::CommitResult,
+) -> ipc::CommitNotification {
+    let mut notification = ipc::CommitNotification::new();
+    notification.set_prestate_hash(commit_request.get_prestate_hash().to_vec());
+    notification.set_poststate_hash(commit_result.get_poststate_hash().to_vec());
+    notification.set_deploy_hashes(protobuf::RepeatedField::from_slice(
+        commit_request.get_deploy_hashes(),
+    ));
+    let keys = commit_request
+        .get_effects()
+        .iter()
+        .map(|entry| entry.get_key().clone())
+        .collect();
+    notification.set_keys(protobuf::RepeatedField::from_vec(keys));
+    notification.set_transfers(protobuf::RepeatedField::from_slice(
+        commit_request.get_transfers(),
+    ));
+    notification.set_events(protobuf::RepeatedField::from_slice(
+        commit_request.get_events(),
+    ));
+    notification
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::Stream;
+
+    use super::super::ipc;
+    use super::{commit_notification, CommitNotifier, SUBSCRIBER_BUFFER_SIZE};
+
+    fn notification(hash: u8) -> ipc::CommitNotification {
+        let mut notification = ipc::CommitNotification::new();
+        notification.set_poststate_hash(vec![hash; 32]);
+        notification
+    }
+
+    #[test]
+    fn subscribers_receive_published_notifications() {
+        let notifier = CommitNotifier::default();
+        let first = notifier.subscribe();
+        let second = notifier.subscribe();
+        notifier.publish(notification(1));
+        notifier.publish(notification(2));
+        drop(notifier);
+
+        for receiver in vec![first, second] {
+            let received: Vec<Vec<u8>> = receiver
+                .wait()
+                .map(|n| n.unwrap().get_poststate_hash().to_vec())
+                .collect();
+            assert_eq!(received, vec![vec![1; 32], vec![2; 32]]);
+        }
+    }
+
+    #[test]
+    fn notification_carries_transfers_and_events_of_the_commit() {
+        let mut event = ipc::ContractEvent::new();
+        event.set_data(vec![1, 2, 3]);
+        let mut commit_request = ipc::CommitRequest::new();
+        commit_request.set_prestate_hash(vec![1; 32]);
+        commit_request.set_deploy_hashes(protobuf::RepeatedField::from_vec(vec![vec![2; 32]]));
+        commit_request.set_transfers(protobuf::RepeatedField::from_vec(vec![
+            ipc::TransferEvent::new(),
+        ]));
+        commit_request.set_events(protobuf::RepeatedField::from_vec(vec![event.clone()]));
+        let mut commit_result = ipc::CommitResult::new();
+        commit_result.set_poststate_hash(vec![3; 32]);
+
+        let notification = commit_notification(&commit_request, &commit_result);
+        assert_eq!(notification.get_poststate_hash(), &[3; 32][..]);
+        assert_eq!(notification.get_deploy_hashes(), &[vec![2; 32]][..]);
+        assert_eq!(notification.get_transfers().len(), 1);
+        assert_eq!(notification.get_events(), &[event][..]);
+    }
+
+    #[test]
+    fn closed_and_lagging_subscribers_are_dropped() {
+        let notifier = CommitNotifier::default();
+        let closed = notifier.subscribe();
+        let lagging = notifier.subscribe();
+        drop(closed);
+
+        notifier.publish(notification(0));
+        assert_eq!(notifier.subscriber_count(), 1);
+
+        for i in 0..=SUBSCRIBER_BUFFER_SIZE {
+            notifier.publish(notification(i as u8));
+        }
+        assert_eq!(notifier.subscriber_count(), 0);
+        // Notifications queued before the disconnect are still delivered.
+        assert!(lagging.wait().next().is_some());
+    }
+}
@@ -0,0 +1,423 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures::{future, Future};
+use futures_cpupool::CpuPool;
+use protobuf::Message;
+
+use shared::logging;
+
+use super::auth::AdminAuth;
+use super::ipc;
+use super::ipc_grpc::ExecutionEngineService;
+use super::notifications::{commit_notification, CommitNotifier};
+
+const DEFAULT_MAX_PENDING_EXEC: usize = 16;
+const DEFAULT_MAX_PENDING_COMMIT: usize = 16;
+const DEFAULT_MAX_PENDING_QUERY: usize = 64;
+const DEFAULT_MAX_PENDING_VALIDATE: usize = 16;
+const DEFAULT_MAX_PENDING_GENESIS: usize = 1;
+const DEFAULT_MAX_PENDING_SLASH: usize = 4;
+const DEFAULT_MAX_PENDING_UPGRADE: usize = 1;
+/// Blocks with many deploys or big contracts make for large exec and commit messages.
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Upper bounds on the number of requests of each kind that can be either
+/// queued or being processed at the same time, and on the size of the messages
+/// of every method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MethodLimits {
+    pub exec: usize,
+    pub commit: usize,
+    pub query: usize,
+    pub validate: usize,
+    pub run_genesis: usize,
+    pub slash: usize,
+    pub upgrade: usize,
+    /// Max size in bytes of a request, enforced before it is parsed.
+    pub max_receive_message_size: usize,
+    /// Max size in bytes of a response.
+    pub max_send_message_size: usize,
+}
+
+impl Default for MethodLimits {
+    fn default() -> Self {
+        MethodLimits {
+            exec: DEFAULT_MAX_PENDING_EXEC,
+            commit: DEFAULT_MAX_PENDING_COMMIT,
+            query: DEFAULT_MAX_PENDING_QUERY,
+            validate: DEFAULT_MAX_PENDING_VALIDATE,
+            run_genesis: DEFAULT_MAX_PENDING_GENESIS,
+            slash: DEFAULT_MAX_PENDING_SLASH,
+            upgrade: DEFAULT_MAX_PENDING_UPGRADE,
+            max_receive_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_send_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+}
+
+/// Counts requests in flight for a single method.
+struct Permits {
+    method: &'static str,
+    limit: usize,
+    in_flight: AtomicUsize,
+}
+
+impl Permits {
+    fn new(method: &'static str, limit: usize) -> Arc<Permits> {
+        Arc::new(Permits {
+            method,
+            limit,
+            in_flight: AtomicUsize::new(0),
+        })
+    }
+
+    /// Reserves a slot for a request, or returns `None` if the method is saturated.
+    fn try_acquire(permits: &Arc<Permits>) -> Option<Permit> {
+        let mut current = permits.in_flight.load(Ordering::SeqCst);
+        loop {
+            if current >= permits.limit {
+                return None;
+            }
+            let previous =
+                permits
+                    .in_flight
+                    .compare_and_swap(current, current + 1, Ordering::SeqCst);
+            if previous == current {
+                return Some(Permit(Arc::clone(permits)));
+            }
+            current = previous;
+        }
+    }
+}
+
+/// Releases its slot when dropped, i.e. once the request has been served.
+struct Permit(Arc<Permits>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn resource_exhausted(method: &str) -> grpc::Error {
+    let message = format!("too many pending {} requests", method);
+    logging::log_warning(&message);
+    grpc::Error::GrpcMessage(grpc::GrpcMessageError {
+        grpc_status: grpc::GrpcStatus::ResourceExhausted as i32,
+        grpc_message: message,
+    })
+}
+
+/// Wraps an `ExecutionEngineService` so that every method is served from a
+/// worker pool with a bounded number of pending requests per method.
+///
+/// Requests above the bound are rejected with `RESOURCE_EXHAUSTED` instead of
+/// being queued, so that e.g. a flood of `query` calls cannot delay `exec` and
+/// `commit` calls indefinitely.
+///
+/// Commits are served one at a time from a pool of their own: each one reads the
+/// latest roots and writes a new one, so running them concurrently would only
+/// contend on the global state, and would complete them out of the order they
+/// were received in.
+///
+/// It also publishes a notification to `subscribe_commits` subscribers after
+/// every successful commit.
+pub struct ThrottledService<E> {
+    inner: Arc<E>,
+    pool: CpuPool,
+    commit_pool: CpuPool,
+    admin_auth: AdminAuth,
+    notifier: CommitNotifier,
+    exec: Arc<Permits>,
+    commit: Arc<Permits>,
+    query: Arc<Permits>,
+    validate: Arc<Permits>,
+    run_genesis: Arc<Permits>,
+    slash: Arc<Permits>,
+    upgrade: Arc<Permits>,
+}
+
+impl<E> ThrottledService<E>
+where
+    E: ExecutionEngineService + Sync + Send + 'static,
+{
+    pub fn new(inner: E, limits: MethodLimits, admin_auth: AdminAuth) -> ThrottledService<E> {
+        ThrottledService {
+            inner: Arc::new(inner),
+            pool: CpuPool::new_num_cpus(),
+            commit_pool: CpuPool::new(1),
+            admin_auth,
+            notifier: CommitNotifier::default(),
+            exec: Permits::new("exec", limits.exec),
+            commit: Permits::new("commit", limits.commit),
+            query: Permits::new("query", limits.query),
+            validate: Permits::new("validate", limits.validate),
+            run_genesis: Permits::new("run_genesis", limits.run_genesis),
+            slash: Permits::new("slash", limits.slash),
+            upgrade: Permits::new("upgrade", limits.upgrade),
+        }
+    }
+
+    fn throttle<Q, F, R>(&self, permits: &Arc<Permits>, request: Q, f: F) -> grpc::SingleResponse<R>
+    where
+        Q: Message,
+        F: FnOnce(&E, Q) -> grpc::SingleResponse<R> + Send + 'static,
+        R: Message,
+    {
+        self.throttle_on(&self.pool, permits, request, f)
+    }
+
+    /// Like `throttle`, but serves the request from `pool`.
+    fn throttle_on<Q, F, R>(
+        &self,
+        pool: &CpuPool,
+        permits: &Arc<Permits>,
+        request: Q,
+        f: F,
+    ) -> grpc::SingleResponse<R>
+    where
+        Q: Message,
+        F: FnOnce(&E, Q) -> grpc::SingleResponse<R> + Send + 'static,
+        R: Message,
+    {
+        match Permits::try_acquire(permits) {
+            None => grpc::SingleResponse::err(resource_exhausted(permits.method)),
+            Some(permit) => {
+                let inner = Arc::clone(&self.inner);
+                let response = pool.spawn_fn(move || -> Result<R, grpc::Error> {
+                    let _permit = permit;
+                    f(&inner, request).drop_metadata().wait()
+                });
+                grpc::SingleResponse::no_metadata(response)
+            }
+        }
+    }
+}
+
+impl<E> ExecutionEngineService for ThrottledService<E>
+where
+    E: ExecutionEngineService + Sync + Send + 'static,
+{
+    fn exec(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        exec_request: ipc::ExecRequest,
+    ) -> grpc::SingleResponse<ipc::ExecResponse> {
+        self.throttle(&self.exec, exec_request, move |inner, exec_request| {
+            inner.exec(request_options, exec_request)
+        })
+    }
+
+    fn commit(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        commit_request: ipc::CommitRequest,
+    ) -> grpc::SingleResponse<ipc::CommitResponse> {
+        let notifier = self.notifier.clone();
+        self.throttle_on(
+            &self.commit_pool,
+            &self.commit,
+            commit_request,
+            move |inner, commit_request| {
+                // Only pay for the copy of the effects if someone is listening.
+                let notification_request = if notifier.subscriber_count() > 0 {
+                    Some(commit_request.clone())
+                } else {
+                    None
+                };
+                let result = inner
+                    .commit(request_options, commit_request)
+                    .drop_metadata()
+                    .wait();
+                if let (Some(request), Ok(response)) = (notification_request, &result) {
+                    if response.has_success() {
+                        notifier.publish(commit_notification(&request, response.get_success()));
+                    }
+                }
+                grpc::SingleResponse::no_metadata(future::result(result))
+            },
+        )
+    }
+
+    /// Shares the limit and the pool of `commit`, and notifies the subscribers of each commit of the batch.
+    fn batch_commit(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        batch_commit_request: ipc::BatchCommitRequest,
+    ) -> grpc::SingleResponse<ipc::BatchCommitResponse> {
+        let notifier = self.notifier.clone();
+        self.throttle_on(
+            &self.commit_pool,
+            &self.commit,
+            batch_commit_request,
+            move |inner, batch_commit_request| {
+                let notification_request = if notifier.subscriber_count() > 0 {
+                    Some(batch_commit_request.clone())
+                } else {
+                    None
+                };
+                let result = inner
+                    .batch_commit(request_options, batch_commit_request)
+                    .drop_metadata()
+                    .wait();
+                if let (Some(request), Ok(response)) = (notification_request, &result) {
+                    if response.has_success() {
+                        let commit_results = response.get_success().get_results();
+                        for (commit_request, commit_result) in
+                            request.get_commits().iter().zip(commit_results)
+                        {
+                            notifier.publish(commit_notification(commit_request, commit_result));
+                        }
+                    }
+                }
+                grpc::SingleResponse::no_metadata(future::result(result))
+            },
+        )
+    }
+
+    fn query(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        query_request: ipc::QueryRequest,
+    ) -> grpc::SingleResponse<ipc::QueryResponse> {
+        self.throttle(&self.query, query_request, move |inner, query_request| {
+            inner.query(request_options, query_request)
+        })
+    }
+
+    /// Shares the limit of `query`, being just another read of the global state.
+    fn query_total_supply(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        total_supply_request: ipc::TotalSupplyRequest,
+    ) -> grpc::SingleResponse<ipc::TotalSupplyResponse> {
+        self.throttle(
+            &self.query,
+            total_supply_request,
+            move |inner, total_supply_request| {
+                inner.query_total_supply(request_options, total_supply_request)
+            },
+        )
+    }
+
+    /// Shares the limit of `query`, being just another read of the global state.
+    fn query_balance(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        balance_request: ipc::BalanceRequest,
+    ) -> grpc::SingleResponse<ipc::BalanceResponse> {
+        self.throttle(
+            &self.query,
+            balance_request,
+            move |inner, balance_request| inner.query_balance(request_options, balance_request),
+        )
+    }
+
+    /// Shares the limit of `query`, being just another read of the global state.
+    fn get_named_keys(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        named_keys_request: ipc::NamedKeysRequest,
+    ) -> grpc::SingleResponse<ipc::NamedKeysResponse> {
+        self.throttle(
+            &self.query,
+            named_keys_request,
+            move |inner, named_keys_request| {
+                inner.get_named_keys(request_options, named_keys_request)
+            },
+        )
+    }
+
+    fn validate(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        validate_request: ipc::ValidateRequest,
+    ) -> grpc::SingleResponse<ipc::ValidateResponse> {
+        self.throttle(
+            &self.validate,
+            validate_request,
+            move |inner, validate_request| inner.validate(request_options, validate_request),
+        )
+    }
+
+    fn run_genesis(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        genesis_request: ipc::GenesisRequest,
+    ) -> grpc::SingleResponse<ipc::GenesisResponse> {
+        self.throttle(
+            &self.run_genesis,
+            genesis_request,
+            move |inner, genesis_request| inner.run_genesis(request_options, genesis_request),
+        )
+    }
+
+    fn slash(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        slash_request: ipc::SlashRequest,
+    ) -> grpc::SingleResponse<ipc::SlashResponse> {
+        self.throttle(&self.slash, slash_request, move |inner, slash_request| {
+            inner.slash(request_options, slash_request)
+        })
+    }
+
+    fn upgrade(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        upgrade_request: ipc::UpgradeRequest,
+    ) -> grpc::SingleResponse<ipc::UpgradeResponse> {
+        // Administrative: checked before taking a permit, so rejected calls can't starve others.
+        if let Err(error) = self.admin_auth.authorize(&request_options) {
+            return grpc::SingleResponse::err(error);
+        }
+        self.throttle(
+            &self.upgrade,
+            upgrade_request,
+            move |inner, upgrade_request| inner.upgrade(request_options, upgrade_request),
+        )
+    }
+
+    fn validate_staking(
+        &self,
+        request_options: ::grpc::RequestOptions,
+        staking_request: ipc::StakingRequest,
+    ) -> grpc::SingleResponse<ipc::StakingResponse> {
+        self.throttle(
+            &self.validate,
+            staking_request,
+            move |inner, staking_request| inner.validate_staking(request_options, staking_request),
+        )
+    }
+
+    fn subscribe_commits(
+        &self,
+        _request_options: ::grpc::RequestOptions,
+        _subscribe_request: ipc::SubscribeCommitsRequest,
+    ) -> grpc::StreamingResponse<ipc::CommitNotification> {
+        self.notifier.streaming_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Permits;
+
+    #[test]
+    fn permits_are_bounded_and_released_on_drop() {
+        let permits = Permits::new("test", 2);
+        let first = Permits::try_acquire(&permits).expect("first permit should be granted");
+        let second = Permits::try_acquire(&permits).expect("second permit should be granted");
+        assert!(Permits::try_acquire(&permits).is_none());
+        drop(first);
+        let third = Permits::try_acquire(&permits).expect("released permit should be reused");
+        assert!(Permits::try_acquire(&permits).is_none());
+        drop(second);
+        drop(third);
+        assert_eq!(
+            permits.in_flight.load(std::sync::atomic::Ordering::SeqCst),
+            0
+        );
+    }
+}
@@ -1,15 +1,20 @@
+extern crate bytes;
 extern crate common;
 extern crate execution_engine;
+extern crate futures;
+extern crate futures_cpupool;
 extern crate grpc;
 extern crate lmdb;
 extern crate proptest;
 extern crate protobuf;
+extern crate serde;
 extern crate shared;
 extern crate storage;
-extern crate wabt;
+extern crate toml;
 extern crate wasm_prep;
 
 #[cfg(test)]
 extern crate parity_wasm;
 
+pub mod config;
 pub mod engine_server;
@@ -12,26 +12,47 @@ extern crate shared;
 extern crate storage;
 
 use std::collections::btree_map::BTreeMap;
-use std::fs;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use clap::{App, Arg, ArgMatches};
+use clap::{App, Arg, ArgGroup, ArgMatches};
 use dirs::home_dir;
+use execution_engine::engine_state::account_export::{
+    write_accounts, AccountExportResult, ExportFormat,
+};
+use execution_engine::engine_state::engine_config::EngineConfig;
+use execution_engine::engine_state::genesis::{
+    genesis_post_state_hash, imported_state_hash, GenesisResult,
+};
+use execution_engine::engine_state::state_file::{
+    read_state_file, write_state_file, StateExportResult,
+};
 use execution_engine::engine_state::EngineState;
+use execution_engine::protocol_registry::ProtocolRegistry;
 use lmdb::DatabaseFlags;
 
+use shared::chainspec::Chainspec;
 use shared::logging::log_settings::{LogLevelFilter, LogSettings};
-use shared::logging::{log_level, log_settings};
+#[cfg(unix)]
+use shared::logging::syslog::{self, SyslogSink};
+use shared::logging::{log_level, log_settings, metric_sampling};
+use shared::newtypes::{Blake2bHash, CorrelationId};
 use shared::os::get_page_size;
 use shared::{logging, socket};
+use storage::data_dir;
 use storage::global_state::lmdb::LmdbGlobalState;
 use storage::trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
 
+use casperlabs_engine_grpc_server::config::Config;
 use casperlabs_engine_grpc_server::engine_server;
+use casperlabs_engine_grpc_server::engine_server::auth::AdminAuth;
+use casperlabs_engine_grpc_server::engine_server::throttle::MethodLimits;
 
 // exe / proc
 const PROC_NAME: &str = "casperlabs-engine-grpc-server";
@@ -53,6 +74,11 @@ const CREATE_DATA_DIR_EXPECT: &str = "Could not create directory";
 const LMDB_ENVIRONMENT_EXPECT: &str = "Could not create LmdbEnvironment";
 const LMDB_TRIE_STORE_EXPECT: &str = "Could not create LmdbTrieStore";
 const LMDB_GLOBAL_STATE_EXPECT: &str = "Could not create LmdbGlobalState";
+const STORAGE_METRICS_ERROR: &str = "Could not report storage metrics";
+const UPGRADE_DATA_DIR_EXPECT: &str = "Could not upgrade data directory";
+const DATA_DIR_MIGRATION_MESSAGE: &str = "migrated data directory";
+const WRITE_AHEAD_LOG_EXPECT: &str = "Could not recover commits from the write-ahead log";
+const COMMIT_REPLAYED_MESSAGE: &str = "replayed commit interrupted by a crash";
 
 // pages / lmdb
 const ARG_PAGES: &str = "pages";
@@ -60,22 +86,92 @@ const ARG_PAGES_SHORT: &str = "p";
 const ARG_PAGES_VALUE: &str = "NUM";
 const ARG_PAGES_HELP: &str = "Sets the max number of pages to use for lmdb's mmap";
 const GET_PAGES_EXPECT: &str = "Could not parse pages argument";
-// 750 GiB = 805306368000 bytes
-// page size on x86_64 linux = 4096 bytes
-// 805306368000 / 4096 = 196608000
-const DEFAULT_PAGES: usize = 196_608_000;
 
 // socket
 const ARG_SOCKET: &str = "socket";
-const ARG_SOCKET_HELP: &str = "socket file";
-const ARG_SOCKET_EXPECT: &str = "socket required";
-const REMOVING_SOCKET_FILE_MESSAGE: &str = "removing old socket file";
-const REMOVING_SOCKET_FILE_EXPECT: &str = "failed to remove old socket file";
+const ARG_SOCKET_HELP: &str =
+    "socket file, or host:port to listen on TCP (loopback TCP is used where Unix sockets are unavailable)";
+const ARG_SOCKET_EXPECT: &str =
+    "socket required, either as an argument, in the config file, or as CL_ENGINE_SOCKET";
+const REMOVING_SOCKET_FILE_MESSAGE: &str = "removing socket file and pidfile";
+const REMOVING_SOCKET_FILE_ERROR: &str = "failed to remove socket file or pidfile";
 
 // loglevel
 const ARG_LOG_LEVEL: &str = "loglevel";
 const ARG_LOG_LEVEL_VALUE: &str = "LOGLEVEL";
 const ARG_LOG_LEVEL_HELP: &str = "[ fatal | error | warning | info | debug ]";
+#[cfg(unix)]
+const SYSLOG_CONNECT_ERROR: &str = "Could not connect to syslog";
+
+// request limits
+const ARG_MAX_PENDING_EXEC: &str = "max-pending-exec";
+const ARG_MAX_PENDING_COMMIT: &str = "max-pending-commit";
+const ARG_MAX_PENDING_QUERY: &str = "max-pending-query";
+const ARG_MAX_PENDING_VALIDATE: &str = "max-pending-validate";
+const ARG_MAX_PENDING_VALUE: &str = "NUM";
+const ARG_MAX_PENDING_EXEC_HELP: &str = "Sets the max number of pending exec requests";
+const ARG_MAX_PENDING_COMMIT_HELP: &str = "Sets the max number of pending commit requests";
+const ARG_MAX_PENDING_QUERY_HELP: &str = "Sets the max number of pending query requests";
+const ARG_MAX_PENDING_VALIDATE_HELP: &str = "Sets the max number of pending validate requests";
+const GET_MAX_PENDING_EXPECT: &str = "Could not parse max pending requests argument";
+
+// admin-secret-file
+const ARG_ADMIN_SECRET_FILE: &str = "admin-secret-file";
+const ARG_ADMIN_SECRET_FILE_VALUE: &str = "FILE";
+const ARG_ADMIN_SECRET_FILE_HELP: &str =
+    "Sets the file containing the shared secret required by administrative RPCs";
+const READ_ADMIN_SECRET_FILE_EXPECT: &str = "Could not read admin secret file";
+
+// import-genesis-state
+const ARG_IMPORT_GENESIS_STATE: &str = "import-genesis-state";
+const ARG_IMPORT_GENESIS_STATE_VALUE: &str = "FILE";
+const ARG_IMPORT_GENESIS_STATE_HELP: &str =
+    "Writes the entries of the global state file on top of the empty state, instead of running genesis";
+const READ_GENESIS_STATE_EXPECT: &str = "Could not read genesis state file";
+const IMPORT_GENESIS_STATE_EXPECT: &str = "Could not import genesis state";
+const GENESIS_STATE_IMPORTED_MESSAGE: &str = "imported genesis state";
+const GENESIS_STATE_STORED_MESSAGE: &str = "genesis state already imported";
+
+// export-accounts
+const ARG_EXPORT_ACCOUNTS: &str = "export-accounts";
+const ARG_EXPORT_ACCOUNTS_VALUE: &str = "STATE_HASH";
+const ARG_EXPORT_ACCOUNTS_HELP: &str =
+    "Writes the accounts at the state hash, with their main purse balances and named keys, to the export file and exits";
+const ARG_EXPORT_FILE: &str = "export-file";
+const ARG_EXPORT_FILE_VALUE: &str = "FILE";
+const ARG_EXPORT_FILE_HELP: &str = "Sets the file accounts or the state are exported to";
+const ARG_EXPORT_FORMAT: &str = "export-format";
+const ARG_EXPORT_FORMAT_VALUE: &str = "FORMAT";
+const ARG_EXPORT_FORMAT_HELP: &str = "[ csv | json ], csv by default";
+const EXPORT_ACCOUNTS_EXPECT: &str = "Could not export accounts";
+const ACCOUNTS_EXPORTED_MESSAGE: &str = "exported accounts";
+
+// export-state
+const ARG_EXPORT_STATE: &str = "export-state";
+const ARG_EXPORT_STATE_VALUE: &str = "STATE_HASH";
+const ARG_EXPORT_STATE_HELP: &str =
+    "Writes the entries of the global state at the state hash to the export file, in the format read by import-genesis-state, and exits";
+const EXPORT_STATE_EXPECT: &str = "Could not export state";
+// Exports of which at most one is run, both requiring export-file.
+const GROUP_EXPORT: &str = "export";
+const STATE_EXPORTED_MESSAGE: &str = "exported state";
+
+// chainspec
+const ARG_CHAINSPEC: &str = "chainspec";
+const ARG_CHAINSPEC_VALUE: &str = "FILE";
+const ARG_CHAINSPEC_HELP: &str =
+    "Sets the chainspec manifest of the network; the engine's built-in protocol versions are used if not set";
+const LOAD_CHAINSPEC_EXPECT: &str = "Could not load chainspec";
+const GENESIS_EXPECT: &str = "Could not commit the genesis of the chainspec";
+const GENESIS_COMMITTED_MESSAGE: &str = "committed the genesis of the chainspec";
+
+// config
+const ARG_CONFIG: &str = "config";
+const ARG_CONFIG_SHORT: &str = "c";
+const ARG_CONFIG_VALUE: &str = "FILE";
+const ARG_CONFIG_HELP: &str =
+    "Sets the TOML config file; CL_ENGINE_* environment variables and arguments take precedence";
+const LOAD_CONFIG_EXPECT: &str = "Could not load configuration";
 
 // runnable
 const SIGINT_HANDLE_EXPECT: &str = "Error setting Ctrl-C handler";
@@ -86,6 +182,11 @@ lazy_static! {
     static ref ARG_MATCHES: clap::ArgMatches<'static> = get_args();
 }
 
+// Configuration merged from the config file, environment and command line arguments
+lazy_static! {
+    static ref CONFIG: Config = get_config();
+}
+
 // LogSettings instance to be used within this application
 lazy_static! {
     static ref LOG_SETTINGS: log_settings::LogSettings = get_log_settings();
@@ -96,34 +197,88 @@ fn main() {
 
     log_settings::set_log_settings_provider(&*LOG_SETTINGS);
 
+    #[cfg(unix)]
+    set_syslog_sink(&*CONFIG);
+
+    metric_sampling::set_metric_sampling(CONFIG.metrics.sampling());
+
     logging::log_info(SERVER_START_MESSAGE);
 
-    let matches: &clap::ArgMatches = &*ARG_MATCHES;
+    let config: &Config = &*CONFIG;
 
-    let socket = get_socket(matches);
+    let data_dir = get_data_dir(config);
 
-    match socket.remove_file() {
-        Err(e) => panic!("{}: {:?}", REMOVING_SOCKET_FILE_EXPECT, e),
-        Ok(_) => logging::log_info(REMOVING_SOCKET_FILE_MESSAGE),
-    };
+    let map_size = get_map_size(config);
+
+    let limits = config.method_limits();
+
+    let admin_auth = get_admin_auth(config);
+
+    let engine_config = config.engine_config();
+
+    let chainspec = get_chainspec(config);
+
+    let protocols = chainspec
+        .as_ref()
+        .map_or_else(ProtocolRegistry::default, ProtocolRegistry::from_chainspec);
+
+    let engine_state = get_engine_state(data_dir, map_size, engine_config, protocols);
+
+    if let Some(ref chainspec) = chainspec {
+        commit_chainspec_genesis(&engine_state, chainspec);
+    }
+
+    import_genesis_state(&engine_state, chainspec.as_ref());
+
+    if let Some(state_hash) = ARG_MATCHES.value_of(ARG_EXPORT_ACCOUNTS) {
+        export_accounts(&engine_state, state_hash);
+        return;
+    }
 
-    let data_dir = get_data_dir(matches);
+    if let Some(state_hash) = ARG_MATCHES.value_of(ARG_EXPORT_STATE) {
+        export_state(&engine_state, state_hash);
+        return;
+    }
+
+    let socket = get_socket(config);
 
-    let map_size = get_map_size(matches);
+    let global_state = engine_state.state();
 
-    let _server = get_grpc_server(&socket, data_dir, map_size);
+    let _server = get_grpc_server(&socket, engine_state, limits, admin_auth);
 
     log_listening_message(&socket);
 
     let interval = Duration::from_secs(RUNNABLE_CHECK_INTERVAL_SECONDS);
 
+    let storage_metrics_interval = config.metrics.storage_interval();
+
+    let mut last_storage_metrics = Instant::now();
+
     let runnable = get_sigint_handle();
 
     while runnable.load(Ordering::SeqCst) {
         std::thread::park_timeout(interval);
+
+        match storage_metrics_interval {
+            Some(report_interval) if last_storage_metrics.elapsed() >= report_interval => {
+                // Only the probe is taken under the lock, so commits go on while it measures.
+                let storage_probe = global_state.lock().storage_probe();
+                let result = storage_probe.log_storage_metrics(CorrelationId::new());
+                if let Err(error) = result {
+                    logging::log_error(&format!("{}: {}", STORAGE_METRICS_ERROR, error));
+                }
+                last_storage_metrics = Instant::now();
+            }
+            _ => (),
+        }
     }
 
     logging::log_info(SERVER_STOP_MESSAGE);
+
+    match socket.release() {
+        Err(e) => logging::log_error(&format!("{}: {:?}", REMOVING_SOCKET_FILE_ERROR, e)),
+        Ok(_) => logging::log_info(REMOVING_SOCKET_FILE_MESSAGE),
+    }
 }
 
 /// Sets panic hook for logging panic info
@@ -149,6 +304,21 @@ fn set_panic_hook() {
 /// Gets command line arguments
 fn get_args() -> ArgMatches<'static> {
     App::new(APP_NAME)
+        .arg(
+            Arg::with_name(ARG_CONFIG)
+                .short(ARG_CONFIG_SHORT)
+                .long(ARG_CONFIG)
+                .value_name(ARG_CONFIG_VALUE)
+                .help(ARG_CONFIG_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_CHAINSPEC)
+                .long(ARG_CHAINSPEC)
+                .value_name(ARG_CHAINSPEC_VALUE)
+                .help(ARG_CHAINSPEC_HELP)
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name(ARG_LOG_LEVEL)
                 .required(false)
@@ -173,12 +343,87 @@ fn get_args() -> ArgMatches<'static> {
                 .help(ARG_PAGES_HELP)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name(ARG_MAX_PENDING_EXEC)
+                .long(ARG_MAX_PENDING_EXEC)
+                .value_name(ARG_MAX_PENDING_VALUE)
+                .help(ARG_MAX_PENDING_EXEC_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_PENDING_COMMIT)
+                .long(ARG_MAX_PENDING_COMMIT)
+                .value_name(ARG_MAX_PENDING_VALUE)
+                .help(ARG_MAX_PENDING_COMMIT_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_PENDING_QUERY)
+                .long(ARG_MAX_PENDING_QUERY)
+                .value_name(ARG_MAX_PENDING_VALUE)
+                .help(ARG_MAX_PENDING_QUERY_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_PENDING_VALIDATE)
+                .long(ARG_MAX_PENDING_VALIDATE)
+                .value_name(ARG_MAX_PENDING_VALUE)
+                .help(ARG_MAX_PENDING_VALIDATE_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_ADMIN_SECRET_FILE)
+                .long(ARG_ADMIN_SECRET_FILE)
+                .value_name(ARG_ADMIN_SECRET_FILE_VALUE)
+                .help(ARG_ADMIN_SECRET_FILE_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_IMPORT_GENESIS_STATE)
+                .long(ARG_IMPORT_GENESIS_STATE)
+                .value_name(ARG_IMPORT_GENESIS_STATE_VALUE)
+                .help(ARG_IMPORT_GENESIS_STATE_HELP)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_EXPORT_ACCOUNTS)
+                .long(ARG_EXPORT_ACCOUNTS)
+                .value_name(ARG_EXPORT_ACCOUNTS_VALUE)
+                .help(ARG_EXPORT_ACCOUNTS_HELP)
+                .requires(ARG_EXPORT_FILE)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_EXPORT_STATE)
+                .long(ARG_EXPORT_STATE)
+                .value_name(ARG_EXPORT_STATE_VALUE)
+                .help(ARG_EXPORT_STATE_HELP)
+                .requires(ARG_EXPORT_FILE)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_EXPORT_FILE)
+                .long(ARG_EXPORT_FILE)
+                .value_name(ARG_EXPORT_FILE_VALUE)
+                .help(ARG_EXPORT_FILE_HELP)
+                .requires(GROUP_EXPORT)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARG_EXPORT_FORMAT)
+                .long(ARG_EXPORT_FORMAT)
+                .value_name(ARG_EXPORT_FORMAT_VALUE)
+                .help(ARG_EXPORT_FORMAT_HELP)
+                .requires(ARG_EXPORT_ACCOUNTS)
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name(ARG_SOCKET)
-                .required(true)
+                .required(false)
                 .help(ARG_SOCKET_HELP)
                 .index(1),
         )
+        .group(ArgGroup::with_name(GROUP_EXPORT).args(&[ARG_EXPORT_ACCOUNTS, ARG_EXPORT_STATE]))
         .get_matches()
 }
 
@@ -193,49 +438,145 @@ fn get_sigint_handle() -> Arc<AtomicBool> {
     handle
 }
 
-/// Gets value of socket argument
-fn get_socket(matches: &ArgMatches) -> socket::Socket {
-    let socket = matches.value_of(ARG_SOCKET).expect(ARG_SOCKET_EXPECT);
+/// Loads the config file given in the config argument, if any, and applies
+/// environment variable and command line argument overrides. Exits the process
+/// on an invalid config; logging can't be set up without one.
+fn get_config() -> Config {
+    let matches: &clap::ArgMatches = &*ARG_MATCHES;
 
-    socket::Socket::new(socket.to_owned())
+    let config = matches
+        .value_of(ARG_CONFIG)
+        .map_or(Ok(Config::default()), Config::from_file)
+        .and_then(|mut config| config.apply_env().map(|_| config));
+
+    match config {
+        Ok(mut config) => {
+            apply_args(&mut config, matches);
+            config
+        }
+        Err(error) => {
+            eprintln!("{}: {}", LOAD_CONFIG_EXPECT, error);
+            process::exit(1)
+        }
+    }
 }
 
-/// Gets value of data-dir argument
-fn get_data_dir(matches: &ArgMatches) -> PathBuf {
-    let mut buf = matches.value_of(ARG_DATA_DIR).map_or(
-        {
-            let mut dir = home_dir().expect(GET_HOME_DIR_EXPECT);
-            dir.push(DEFAULT_DATA_DIR_RELATIVE);
-            dir
-        },
-        PathBuf::from,
-    );
+/// Overrides config with the values of given command line arguments
+fn apply_args(config: &mut Config, matches: &ArgMatches) {
+    let parse = |arg: &str, expect: &str| {
+        matches
+            .value_of(arg)
+            .map(|value| usize::from_str(value).expect(expect))
+    };
+
+    if let Some(socket) = matches.value_of(ARG_SOCKET) {
+        config.socket = Some(socket.to_owned());
+    }
+    if let Some(chainspec) = matches.value_of(ARG_CHAINSPEC) {
+        config.chainspec = Some(PathBuf::from(chainspec));
+    }
+    if let Some(data_dir) = matches.value_of(ARG_DATA_DIR) {
+        config.storage.data_dir = Some(PathBuf::from(data_dir));
+    }
+    if let Some(pages) = parse(ARG_PAGES, GET_PAGES_EXPECT) {
+        config.storage.pages = pages;
+    }
+    if let Some(log_level) = matches.value_of(ARG_LOG_LEVEL) {
+        config.logging.level = Some(log_level.to_owned());
+    }
+    if let Some(limit) = parse(ARG_MAX_PENDING_EXEC, GET_MAX_PENDING_EXPECT) {
+        config.limits.max_pending_exec = limit;
+    }
+    if let Some(limit) = parse(ARG_MAX_PENDING_COMMIT, GET_MAX_PENDING_EXPECT) {
+        config.limits.max_pending_commit = limit;
+    }
+    if let Some(limit) = parse(ARG_MAX_PENDING_QUERY, GET_MAX_PENDING_EXPECT) {
+        config.limits.max_pending_query = limit;
+    }
+    if let Some(limit) = parse(ARG_MAX_PENDING_VALIDATE, GET_MAX_PENDING_EXPECT) {
+        config.limits.max_pending_validate = limit;
+    }
+    if let Some(path) = matches.value_of(ARG_ADMIN_SECRET_FILE) {
+        config.admin.secret_file = Some(PathBuf::from(path));
+    }
+}
+
+/// Gets configured socket
+fn get_socket(config: &Config) -> socket::Socket {
+    let socket = config.socket.clone().expect(ARG_SOCKET_EXPECT);
+
+    socket::Socket::new(socket)
+}
+
+/// Gets configured data dir, creating it if necessary
+fn get_data_dir(config: &Config) -> PathBuf {
+    let mut buf = config.storage.data_dir.clone().unwrap_or_else(|| {
+        let mut dir = home_dir().expect(GET_HOME_DIR_EXPECT);
+        dir.push(DEFAULT_DATA_DIR_RELATIVE);
+        dir
+    });
     buf.push(GLOBAL_STATE_DIR);
     fs::create_dir_all(&buf).unwrap_or_else(|_| panic!("{}: {:?}", CREATE_DATA_DIR_EXPECT, buf));
+    upgrade_data_dir(&buf);
     buf
 }
 
-///  Parses pages argument and returns map size
-fn get_map_size(matches: &ArgMatches) -> usize {
+/// Brings the on-disk layout to the version of this engine before the database is opened
+fn upgrade_data_dir(data_dir: &Path) {
+    let migrations = data_dir::upgrade(data_dir)
+        .unwrap_or_else(|e| panic!("{}: {:?}: {}", UPGRADE_DATA_DIR_EXPECT, data_dir, e));
+    for migration in migrations {
+        logging::log_info(&format!(
+            "{} from format version {}: {}",
+            DATA_DIR_MIGRATION_MESSAGE, migration.from_version, migration.description
+        ));
+    }
+}
+
+/// Returns map size based on configured number of pages
+fn get_map_size(config: &Config) -> usize {
     let page_size = get_page_size().unwrap();
-    let pages = matches
-        .value_of(ARG_PAGES)
-        .map_or(Ok(DEFAULT_PAGES), usize::from_str)
-        .expect(GET_PAGES_EXPECT);
-    page_size * pages
+    page_size * config.storage.pages
 }
 
-/// Builds and returns a gRPC server.
-fn get_grpc_server(socket: &socket::Socket, data_dir: PathBuf, map_size: usize) -> grpc::Server {
-    let engine_state = get_engine_state(data_dir, map_size);
+/// Reads the admin secret from the configured admin secret file, if any
+fn get_admin_auth(config: &Config) -> AdminAuth {
+    let secret = config.admin.secret_file.as_ref().map(|path| {
+        fs::read_to_string(path)
+            .unwrap_or_else(|_| panic!("{}: {:?}", READ_ADMIN_SECRET_FILE_EXPECT, path))
+            .trim()
+            .to_owned()
+    });
+    AdminAuth::new(secret)
+}
+
+/// Loads the configured chainspec, if any
+fn get_chainspec(config: &Config) -> Option<Chainspec> {
+    config.chainspec.as_ref().map(|path| {
+        Chainspec::from_file(path)
+            .unwrap_or_else(|e| panic!("{}: {:?}: {}", LOAD_CHAINSPEC_EXPECT, path, e))
+    })
+}
 
-    engine_server::new(socket.as_str(), engine_state)
+/// Builds and returns a gRPC server.
+fn get_grpc_server(
+    socket: &socket::Socket,
+    engine_state: EngineState<LmdbGlobalState>,
+    limits: MethodLimits,
+    admin_auth: AdminAuth,
+) -> grpc::Server {
+    engine_server::new(socket.as_str(), engine_state, limits, admin_auth)
         .build()
         .expect(SERVER_START_EXPECT)
 }
 
-/// Builds and returns engine global state
-fn get_engine_state(data_dir: PathBuf, map_size: usize) -> EngineState<LmdbGlobalState> {
+/// Builds and returns an engine executing deploys of the versions in `protocols`
+fn get_engine_state(
+    data_dir: PathBuf,
+    map_size: usize,
+    engine_config: EngineConfig,
+    protocols: ProtocolRegistry,
+) -> EngineState<LmdbGlobalState> {
     let environment = {
         let ret = LmdbEnvironment::new(&data_dir, map_size).expect(LMDB_ENVIRONMENT_EXPECT);
         Arc::new(ret)
@@ -247,21 +588,197 @@ fn get_engine_state(data_dir: PathBuf, map_size: usize) -> EngineState<LmdbGloba
         Arc::new(ret)
     };
 
-    let global_state = LmdbGlobalState::empty(Arc::clone(&environment), Arc::clone(&trie_store))
-        .expect(LMDB_GLOBAL_STATE_EXPECT);
+    let mut global_state =
+        LmdbGlobalState::empty(Arc::clone(&environment), Arc::clone(&trie_store))
+            .expect(LMDB_GLOBAL_STATE_EXPECT);
+
+    let replayed = global_state
+        .open_write_ahead_log(CorrelationId::new())
+        .expect(WRITE_AHEAD_LOG_EXPECT);
+    for commit_result in replayed {
+        logging::log_info(&format!("{}: {}", COMMIT_REPLAYED_MESSAGE, commit_result));
+    }
+
+    EngineState::with_protocols(global_state, engine_config, protocols)
+}
+
+/// Commits the genesis described by the chainspec unless its post state is already stored, and
+/// checks it's the one every node of the network arrives at
+fn commit_chainspec_genesis(engine_state: &EngineState<LmdbGlobalState>, chainspec: &Chainspec) {
+    let genesis = &chainspec.genesis;
+    let expected_hash = genesis_post_state_hash(
+        genesis.account_addr,
+        genesis.initial_tokens,
+        &genesis.mint_code,
+        &genesis.pos_code,
+        genesis.validators.clone(),
+        genesis.protocol_version,
+    )
+    .unwrap_or_else(|e| panic!("{}: {}", GENESIS_EXPECT, e));
+    match engine_state.tracking_copy(expected_hash) {
+        Ok(Some(_)) => return,
+        Ok(None) => (),
+        Err(e) => panic!("{}: {:?}", GENESIS_EXPECT, e),
+    }
+    let result = engine_state.commit_genesis(
+        CorrelationId::new(),
+        genesis.account_addr,
+        genesis.initial_tokens,
+        &genesis.mint_code,
+        &genesis.pos_code,
+        genesis.validators.clone(),
+        genesis.protocol_version,
+    );
+    match result {
+        Ok(GenesisResult::Success {
+            post_state_hash, ..
+        }) if post_state_hash == expected_hash => logging::log_info(&format!(
+            "{} of {}: {}",
+            GENESIS_COMMITTED_MESSAGE, chainspec.network.name, post_state_hash
+        )),
+        Ok(GenesisResult::Success {
+            post_state_hash, ..
+        }) => panic!(
+            "{}: post state hash {} differs from the expected {}",
+            GENESIS_EXPECT, post_state_hash, expected_hash
+        ),
+        Ok(other) => panic!("{}: {}", GENESIS_EXPECT, other),
+        Err(e) => panic!("{}: {}", GENESIS_EXPECT, e),
+    }
+}
+
+/// Imports the state file given in the import-genesis-state argument, if any, unless its post
+/// state is already stored, logging the resulting post state hash for the node to start from
+fn import_genesis_state(
+    engine_state: &EngineState<LmdbGlobalState>,
+    chainspec: Option<&Chainspec>,
+) {
+    let path = match ARG_MATCHES.value_of(ARG_IMPORT_GENESIS_STATE) {
+        Some(path) => path,
+        None => return,
+    };
+    let entries = read_state_file(path)
+        .unwrap_or_else(|e| panic!("{}: {:?}: {}", READ_GENESIS_STATE_EXPECT, path, e));
+    let entry_count = entries.len();
+    let expected_hash = imported_state_hash(&entries)
+        .unwrap_or_else(|e| panic!("{}: {}", IMPORT_GENESIS_STATE_EXPECT, e));
+    match engine_state.tracking_copy(expected_hash) {
+        Ok(Some(_)) => {
+            logging::log_info(&format!(
+                "{} from {:?}: {}",
+                GENESIS_STATE_STORED_MESSAGE, path, expected_hash
+            ));
+            return;
+        }
+        Ok(None) => (),
+        Err(e) => panic!("{}: {:?}", IMPORT_GENESIS_STATE_EXPECT, e),
+    }
+    // The network starts with the genesis version of its chainspec, or with the newest version
+    // the engine knows of.
+    let protocol_version = match chainspec {
+        Some(chainspec) => chainspec.genesis.protocol_version,
+        None => engine_state
+            .protocols()
+            .versions()
+            .last()
+            .expect("the engine knows of at least one protocol version"),
+    };
+    match engine_state.import_genesis_state(CorrelationId::new(), entries, protocol_version) {
+        Ok(GenesisResult::Success {
+            post_state_hash, ..
+        }) => logging::log_info(&format!(
+            "{} of {} entries from {:?}: {}",
+            GENESIS_STATE_IMPORTED_MESSAGE, entry_count, path, post_state_hash
+        )),
+        Ok(other) => panic!("{}: {}", IMPORT_GENESIS_STATE_EXPECT, other),
+        Err(e) => panic!("{}: {}", IMPORT_GENESIS_STATE_EXPECT, e),
+    }
+}
+
+/// Writes the accounts at `state_hash` to the file given in the export-file argument, in the
+/// format given in the export-format argument
+fn export_accounts(engine_state: &EngineState<LmdbGlobalState>, state_hash: &str) {
+    let format = ARG_MATCHES
+        .value_of(ARG_EXPORT_FORMAT)
+        .map_or(Ok(ExportFormat::Csv), ExportFormat::from_str)
+        .unwrap_or_else(|e| panic!("{}: {}", EXPORT_ACCOUNTS_EXPECT, e));
+    let state_hash = Blake2bHash::from_str(state_hash)
+        .unwrap_or_else(|e| panic!("{}: {:?}: {}", EXPORT_ACCOUNTS_EXPECT, state_hash, e));
+    let records = match engine_state.export_accounts(CorrelationId::new(), state_hash) {
+        Ok(AccountExportResult::Success(records)) => records,
+        Ok(AccountExportResult::RootNotFound) => {
+            panic!("{}: root {} not found", EXPORT_ACCOUNTS_EXPECT, state_hash)
+        }
+        Err(e) => panic!("{}: {}", EXPORT_ACCOUNTS_EXPECT, e),
+    };
+    let path = ARG_MATCHES
+        .value_of(ARG_EXPORT_FILE)
+        .expect("export-file is required by export-accounts");
+    let mut file = BufWriter::new(
+        File::create(path)
+            .unwrap_or_else(|e| panic!("{}: {:?}: {}", EXPORT_ACCOUNTS_EXPECT, path, e)),
+    );
+    write_accounts(&mut file, &records, format)
+        .and_then(|_| file.flush())
+        .unwrap_or_else(|e| panic!("{}: {:?}: {}", EXPORT_ACCOUNTS_EXPECT, path, e));
+    logging::log_info(&format!(
+        "{} at {} to {:?}: {}",
+        ACCOUNTS_EXPORTED_MESSAGE,
+        state_hash,
+        path,
+        records.len()
+    ));
+}
 
-    EngineState::new(global_state)
+/// Writes the entries of the global state at `state_hash` to the file given in the export-file
+/// argument, as a state file
+fn export_state(engine_state: &EngineState<LmdbGlobalState>, state_hash: &str) {
+    let state_hash = Blake2bHash::from_str(state_hash)
+        .unwrap_or_else(|e| panic!("{}: {:?}: {}", EXPORT_STATE_EXPECT, state_hash, e));
+    let entries = match engine_state.export_state(state_hash) {
+        Ok(StateExportResult::Success(entries)) => entries,
+        Ok(StateExportResult::RootNotFound) => {
+            panic!("{}: root {} not found", EXPORT_STATE_EXPECT, state_hash)
+        }
+        Err(e) => panic!("{}: {}", EXPORT_STATE_EXPECT, e),
+    };
+    let path = ARG_MATCHES
+        .value_of(ARG_EXPORT_FILE)
+        .expect("export-file is required by export-state");
+    write_state_file(path, &entries)
+        .unwrap_or_else(|e| panic!("{}: {:?}: {}", EXPORT_STATE_EXPECT, path, e));
+    logging::log_info(&format!(
+        "{} at {} to {:?}: {} entries",
+        STATE_EXPORTED_MESSAGE,
+        state_hash,
+        path,
+        entries.len()
+    ));
 }
 
 /// Builds and returns log_settings
 fn get_log_settings() -> log_settings::LogSettings {
-    let matches: &clap::ArgMatches = &*ARG_MATCHES;
+    let config: &Config = &*CONFIG;
 
-    let log_level_filter = LogLevelFilter::from_input(matches.value_of(ARG_LOG_LEVEL));
+    let log_level_filter =
+        LogLevelFilter::from_input(config.logging.level.as_ref().map(String::as_str));
 
     LogSettings::new(PROC_NAME, log_level_filter)
 }
 
+/// Sends log messages to syslog or journald as well, if configured
+#[cfg(unix)]
+fn set_syslog_sink(config: &Config) {
+    if let Some(target) = config.logging.syslog {
+        match SyslogSink::connect(target, config.logging.syslog_facility) {
+            Ok(sink) => syslog::set_syslog_sink(sink),
+            Err(error) => {
+                logging::log_error(&format!("{}: {}", SYSLOG_CONNECT_ERROR, error));
+            }
+        }
+    }
+}
+
 /// Logs listening on socket message
 fn log_listening_message(socket: &socket::Socket) {
     let mut properties: BTreeMap<String, String> = BTreeMap::new();
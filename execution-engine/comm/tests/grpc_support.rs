@@ -0,0 +1,115 @@
+//! Runs the gRPC server in-process on a Unix socket in a temporary directory, so tests can go
+//! through the generated client, the protobuf mappings and the throttling layer like the node
+//! does, rather than calling `EngineState` methods directly.
+extern crate casperlabs_engine_grpc_server;
+extern crate execution_engine;
+extern crate grpc;
+extern crate storage;
+extern crate tempfile;
+
+use std::fmt::Debug;
+
+use grpc::{ClientStubExt, RequestOptions};
+use tempfile::TempDir;
+
+use casperlabs_engine_grpc_server::engine_server;
+use casperlabs_engine_grpc_server::engine_server::auth::AdminAuth;
+use casperlabs_engine_grpc_server::engine_server::ipc;
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::{
+    ExecutionEngineService, ExecutionEngineServiceClient,
+};
+use casperlabs_engine_grpc_server::engine_server::throttle::MethodLimits;
+use execution_engine::engine_state::error::Error as EngineError;
+use execution_engine::engine_state::EngineState;
+use storage::global_state::in_memory::InMemoryGlobalState;
+use storage::global_state::History;
+
+const SOCKET_FILE_NAME: &str = "engine.sock";
+
+pub struct GrpcTestServer {
+    client: ExecutionEngineServiceClient,
+    // Dropped in declaration order: the client before the server, the socket's directory last.
+    _server: grpc::Server,
+    _socket_dir: TempDir,
+}
+
+impl GrpcTestServer {
+    /// Serves an empty in-memory global state with the default limits.
+    pub fn new() -> GrpcTestServer {
+        let global_state = InMemoryGlobalState::empty().expect("should create global state");
+        GrpcTestServer::with_engine_state(EngineState::new(global_state), MethodLimits::default())
+    }
+
+    pub fn with_engine_state<H>(
+        engine_state: EngineState<H>,
+        limits: MethodLimits,
+    ) -> GrpcTestServer
+    where
+        H: History + Send + Sync + 'static,
+        EngineError: From<H::Error>,
+        H::Error: Into<execution_engine::execution::Error> + Debug,
+    {
+        let socket_dir = tempfile::tempdir().expect("should create temp dir");
+        let socket = socket_dir.path().join(SOCKET_FILE_NAME);
+        let socket = socket.to_str().expect("socket path should be valid UTF-8");
+        let server = engine_server::new(socket, engine_state, limits, AdminAuth::new(None))
+            .build()
+            .expect("should start server");
+        let client = ExecutionEngineServiceClient::new_plain_unix(socket, Default::default())
+            .expect("should connect client");
+        GrpcTestServer {
+            client,
+            _server: server,
+            _socket_dir: socket_dir,
+        }
+    }
+
+    pub fn client(&self) -> &ExecutionEngineServiceClient {
+        &self.client
+    }
+
+    pub fn exec(&self, request: ipc::ExecRequest) -> Result<ipc::ExecResponse, grpc::Error> {
+        self.client
+            .exec(RequestOptions::new(), request)
+            .wait_drop_metadata()
+    }
+
+    pub fn commit(&self, request: ipc::CommitRequest) -> Result<ipc::CommitResponse, grpc::Error> {
+        self.client
+            .commit(RequestOptions::new(), request)
+            .wait_drop_metadata()
+    }
+
+    pub fn query(&self, request: ipc::QueryRequest) -> Result<ipc::QueryResponse, grpc::Error> {
+        self.client
+            .query(RequestOptions::new(), request)
+            .wait_drop_metadata()
+    }
+
+    pub fn query_balance(
+        &self,
+        request: ipc::BalanceRequest,
+    ) -> Result<ipc::BalanceResponse, grpc::Error> {
+        self.client
+            .query_balance(RequestOptions::new(), request)
+            .wait_drop_metadata()
+    }
+
+    pub fn validate(
+        &self,
+        request: ipc::ValidateRequest,
+    ) -> Result<ipc::ValidateResponse, grpc::Error> {
+        self.client
+            .validate(RequestOptions::new(), request)
+            .wait_drop_metadata()
+    }
+
+    pub fn run_genesis(
+        &self,
+        request: ipc::GenesisRequest,
+    ) -> Result<ipc::GenesisResponse, grpc::Error> {
+        self.client
+            .run_genesis(RequestOptions::new(), request)
+            .wait_drop_metadata()
+    }
+}
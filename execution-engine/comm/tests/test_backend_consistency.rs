@@ -0,0 +1,162 @@
+extern crate casperlabs_engine_grpc_server;
+extern crate common;
+extern crate execution_engine;
+extern crate grpc;
+extern crate shared;
+extern crate storage;
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use common::bytesrepr::Error as BytesReprError;
+use common::contract_api::argsparser::ArgsParser;
+use common::key::Key;
+use common::value::account::PublicKey;
+use execution_engine::engine_state::error::Error as EngineError;
+use execution_engine::execution::Error as ExecutionError;
+use shared::transform::Transform;
+use storage::global_state::History;
+
+use test_support::{WasmTestBuilder, DEFAULT_BLOCK_TIME};
+
+#[allow(dead_code)]
+mod test_support;
+
+const GENESIS_ADDR: [u8; 32] = [6u8; 32];
+const ACCOUNT_1_ADDR: [u8; 32] = [1u8; 32];
+
+/// Arguments serialized up front, so that the steps of a scenario are plain data.
+struct SerializedArgs(Vec<Vec<u8>>);
+
+impl ArgsParser for SerializedArgs {
+    fn parse(&self) -> Result<Vec<Vec<u8>>, BytesReprError> {
+        Ok(self.0.clone())
+    }
+}
+
+/// A deploy of the genesis account, committed on top of the previous step.
+struct Step {
+    wasm_file: &'static str,
+    nonce: u64,
+    args: Vec<Vec<u8>>,
+}
+
+fn step(wasm_file: &'static str, nonce: u64, args: impl ArgsParser) -> Step {
+    Step {
+        wasm_file,
+        nonce,
+        args: args.parse().expect("should serialize args"),
+    }
+}
+
+/// What a backend reports for a step. Effects are compared as a map, as the order of the
+/// transform entries of a deploy result isn't specified.
+#[derive(Debug, PartialEq)]
+struct Observation {
+    cost: u64,
+    error: Option<String>,
+    effects_checksum: Vec<u8>,
+    transforms: HashMap<Key, Transform>,
+    post_state_hash: Vec<u8>,
+}
+
+/// Runs genesis and `steps` on `builder`, returning the genesis hash and an observation per step.
+fn run_scenario<S>(mut builder: WasmTestBuilder<S>, steps: &[Step]) -> (Vec<u8>, Vec<Observation>)
+where
+    S: History,
+    EngineError: From<S::Error>,
+    S::Error: Into<ExecutionError> + Debug,
+{
+    builder.run_genesis(GENESIS_ADDR, HashMap::new());
+    let genesis_hash = builder.get_genesis_hash();
+
+    let observations = steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| {
+            builder
+                .exec_with_args(
+                    GENESIS_ADDR,
+                    step.wasm_file,
+                    DEFAULT_BLOCK_TIME,
+                    step.nonce,
+                    SerializedArgs(step.args.clone()),
+                )
+                .commit();
+            let execution_result = builder
+                .get_exec_response(index)
+                .expect("should have exec response")
+                .get_success()
+                .get_deploy_results()
+                .get(0)
+                .expect("should have deploy result")
+                .get_execution_result();
+            Observation {
+                cost: execution_result.get_cost(),
+                error: if execution_result.has_error() {
+                    Some(format!("{:?}", execution_result.get_error()))
+                } else {
+                    None
+                },
+                effects_checksum: execution_result.get_effects_checksum().to_vec(),
+                transforms: builder
+                    .get_transforms()
+                    .pop()
+                    .expect("should have transforms"),
+                post_state_hash: builder.get_poststate_hash(),
+            }
+        })
+        .collect();
+
+    (genesis_hash, observations)
+}
+
+/// Runs `steps` on every backend and expects identical results and state roots at each step, as
+/// any divergence would make nodes using different backends disagree on blocks.
+fn assert_consistent_backends(steps: &[Step]) {
+    let (expected_genesis_hash, expected) = run_scenario(WasmTestBuilder::default(), steps);
+    let (genesis_hash, observed) = run_scenario(WasmTestBuilder::new_with_lmdb(), steps);
+
+    assert_eq!(
+        genesis_hash, expected_genesis_hash,
+        "lmdb and in-memory genesis hashes differ"
+    );
+    for (index, (observed, expected)) in observed.iter().zip(&expected).enumerate() {
+        assert_eq!(
+            observed, expected,
+            "lmdb and in-memory backends diverged at step {} ({})",
+            index, steps[index].wasm_file
+        );
+    }
+}
+
+#[ignore]
+#[test]
+fn should_have_the_same_roots_with_local_state() {
+    assert_consistent_backends(&[
+        step("local_state.wasm", 1, ()),
+        step("local_state.wasm", 2, ()),
+        step("local_state.wasm", 3, ()),
+    ]);
+}
+
+#[ignore]
+#[test]
+fn should_have_the_same_roots_with_transfers() {
+    assert_consistent_backends(&[
+        step("transfer_to_account_01.wasm", 1, ACCOUNT_1_ADDR),
+        step("get_caller.wasm", 2, PublicKey::new(GENESIS_ADDR)),
+        step("transfer_to_account_01.wasm", 3, ACCOUNT_1_ADDR),
+    ]);
+}
+
+#[ignore]
+#[test]
+fn should_have_the_same_roots_with_mixed_contracts() {
+    assert_consistent_backends(&[
+        step("known_urefs.wasm", 1, ()),
+        step("get_blocktime.wasm", 2, DEFAULT_BLOCK_TIME),
+        step("local_state.wasm", 3, ()),
+        step("known_urefs.wasm", 4, ()),
+    ]);
+}
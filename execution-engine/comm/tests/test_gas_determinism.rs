@@ -0,0 +1,168 @@
+extern crate casperlabs_engine_grpc_server;
+extern crate common;
+extern crate execution_engine;
+extern crate grpc;
+extern crate shared;
+extern crate storage;
+extern crate toml;
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+
+use common::value::account::PublicKey;
+
+use test_support::{WasmTestBuilder, DEFAULT_BLOCK_TIME};
+
+#[allow(dead_code)]
+mod test_support;
+
+const GENESIS_ADDR: [u8; 32] = [6u8; 32];
+const ACCOUNT_1_ADDR: [u8; 32] = [1u8; 32];
+
+/// Protocol versions whose gas usage is pinned by the fixtures.
+const PROTOCOL_VERSIONS: &[u64] = &[1];
+
+/// Reference contracts, each executed as the first deploy after genesis.
+const CORPUS: &[&str] = &[
+    "get_blocktime.wasm",
+    "get_caller.wasm",
+    "known_urefs.wasm",
+    "local_state.wasm",
+    "transfer_to_account_01.wasm",
+];
+
+const FIXTURES_PATH: &str = "tests/fixtures/gas_costs.toml";
+
+/// When set, the fixtures are overwritten with the observed costs instead of being checked.
+const RECORD_FIXTURES_ENV_VAR: &str = "CL_RECORD_GAS_FIXTURES";
+
+const FIXTURES_HEADER: &str = "\
+# Gas used by the reference contracts of `test_gas_determinism.rs`, per protocol version.
+# Gas usage is consensus-critical: only re-record these after an intended cost change, with
+#   CL_RECORD_GAS_FIXTURES=1 cargo test --test test_gas_determinism -- --ignored
+";
+
+/// Costs by contract file name, by protocol version.
+type GasCosts = BTreeMap<String, BTreeMap<String, u64>>;
+
+fn exec_cost(protocol_version: u64, wasm_file: &str) -> u64 {
+    let mut builder = WasmTestBuilder::default();
+    builder
+        .with_protocol_version(protocol_version)
+        .run_genesis(GENESIS_ADDR, HashMap::new());
+    match wasm_file {
+        "get_blocktime.wasm" => builder.exec_with_args(
+            GENESIS_ADDR,
+            wasm_file,
+            DEFAULT_BLOCK_TIME,
+            1,
+            DEFAULT_BLOCK_TIME,
+        ),
+        "get_caller.wasm" => builder.exec_with_args(
+            GENESIS_ADDR,
+            wasm_file,
+            DEFAULT_BLOCK_TIME,
+            1,
+            PublicKey::new(GENESIS_ADDR),
+        ),
+        "transfer_to_account_01.wasm" => builder.exec_with_args(
+            GENESIS_ADDR,
+            wasm_file,
+            DEFAULT_BLOCK_TIME,
+            1,
+            ACCOUNT_1_ADDR,
+        ),
+        _ => builder.exec(GENESIS_ADDR, wasm_file, DEFAULT_BLOCK_TIME, 1),
+    };
+    builder.expect_success().get_gas_cost(0)
+}
+
+fn observed_costs() -> GasCosts {
+    PROTOCOL_VERSIONS
+        .iter()
+        .map(|protocol_version| {
+            let costs = CORPUS
+                .iter()
+                .map(|wasm_file| {
+                    let cost = exec_cost(*protocol_version, wasm_file);
+                    assert_eq!(
+                        exec_cost(*protocol_version, wasm_file),
+                        cost,
+                        "{} used different amounts of gas on identical runs",
+                        wasm_file
+                    );
+                    (wasm_file.to_string(), cost)
+                })
+                .collect();
+            (protocol_version.to_string(), costs)
+        })
+        .collect()
+}
+
+// Ignored like the other tests running compiled contracts.
+#[ignore]
+#[test]
+fn should_use_the_recorded_amount_of_gas() {
+    let observed = observed_costs();
+
+    if env::var_os(RECORD_FIXTURES_ENV_VAR).is_some() {
+        let contents = toml::to_string(&observed).expect("should serialize gas costs");
+        fs::write(FIXTURES_PATH, format!("{}\n{}", FIXTURES_HEADER, contents))
+            .expect("should write gas fixtures");
+        return;
+    }
+
+    let contents = fs::read_to_string(FIXTURES_PATH).expect("should read gas fixtures");
+    let expected: GasCosts = toml::from_str(&contents).expect("should parse gas fixtures");
+    for (protocol_version, costs) in &observed {
+        for (wasm_file, cost) in costs {
+            assert!(
+                expected
+                    .get(protocol_version)
+                    .map_or(false, |expected_costs| expected_costs
+                        .contains_key(wasm_file)),
+                "no gas cost recorded for {} at protocol version {} (it used {}); record the \
+                 fixtures with {}=1",
+                wasm_file,
+                protocol_version,
+                cost,
+                RECORD_FIXTURES_ENV_VAR
+            );
+        }
+    }
+    assert_eq!(
+        observed, expected,
+        "gas usage changed; if that is intended, re-record the fixtures with {}=1",
+        RECORD_FIXTURES_ENV_VAR
+    );
+}
+
+#[ignore]
+#[test]
+fn should_charge_repeated_deploys_the_same() {
+    let mut builder = WasmTestBuilder::default();
+    builder
+        .run_genesis(GENESIS_ADDR, HashMap::new())
+        .exec_with_args(
+            GENESIS_ADDR,
+            "get_blocktime.wasm",
+            DEFAULT_BLOCK_TIME,
+            1,
+            DEFAULT_BLOCK_TIME,
+        )
+        .expect_success()
+        .commit()
+        .exec_with_args(
+            GENESIS_ADDR,
+            "get_blocktime.wasm",
+            DEFAULT_BLOCK_TIME,
+            2,
+            DEFAULT_BLOCK_TIME,
+        )
+        .expect_success();
+
+    let first_cost = builder.get_gas_cost(0);
+    assert!(first_cost > 0, "deploys should use some gas");
+    builder.expect_gas_cost(1, first_cost);
+}
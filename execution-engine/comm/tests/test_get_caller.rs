@@ -8,7 +8,7 @@ extern crate storage;
 use std::collections::HashMap;
 
 use common::value::account::PublicKey;
-use test_support::{WasmTestBuilder, DEFAULT_BLOCK_TIME};
+use test_support::{DeployBuilder, WasmTestBuilder, DEFAULT_BLOCK_TIME};
 
 #[allow(dead_code)]
 mod test_support;
@@ -53,6 +53,23 @@ fn should_run_get_caller_contract() {
         .expect_success();
 }
 
+#[ignore]
+#[test]
+fn should_run_get_caller_contract_with_explicit_deploy() {
+    let deploy = DeployBuilder::new()
+        .with_address(GENESIS_ADDR)
+        .with_session_code("get_caller.wasm", PublicKey::new(GENESIS_ADDR))
+        .with_gas_price(2)
+        .with_authorization_keys(&[PublicKey::new(GENESIS_ADDR)])
+        .with_nonce(1);
+
+    WasmTestBuilder::default()
+        .run_genesis(GENESIS_ADDR, HashMap::new())
+        .exec_with_deploy(deploy, DEFAULT_BLOCK_TIME)
+        .commit()
+        .expect_success();
+}
+
 #[ignore]
 #[test]
 fn should_run_get_caller_subcall_contract() {
@@ -0,0 +1,135 @@
+extern crate casperlabs_engine_grpc_server;
+extern crate common;
+extern crate execution_engine;
+extern crate grpc;
+extern crate protobuf;
+extern crate shared;
+extern crate storage;
+
+use casperlabs_engine_grpc_server::engine_server::ipc::{
+    BalanceRequest, ExecRequest, QueryRequest, UpgradeRequest, ValidateRequest,
+};
+use casperlabs_engine_grpc_server::engine_server::ipc_grpc::ExecutionEngineService;
+use casperlabs_engine_grpc_server::engine_server::state::Key as IpcKey;
+use casperlabs_engine_grpc_server::engine_server::throttle::MethodLimits;
+use common::key::Key;
+use common::uref::{AccessRights, URef};
+use execution_engine::engine_state::EngineState;
+use shared::test_utils;
+use storage::global_state::in_memory::InMemoryGlobalState;
+
+use grpc_support::GrpcTestServer;
+use test_support::{get_mock_deploy, get_protocol_version};
+
+#[allow(dead_code)]
+mod grpc_support;
+#[allow(dead_code)]
+mod test_support;
+
+const MISSING_STATE_HASH: [u8; 32] = [1u8; 32];
+
+fn query_request(state_hash: &[u8], base_key: IpcKey) -> QueryRequest {
+    let mut request = QueryRequest::new();
+    request.set_state_hash(state_hash.to_vec());
+    request.set_base_key(base_key);
+    request
+}
+
+#[test]
+fn should_report_missing_root_of_query() {
+    let server = GrpcTestServer::new();
+    let base_key: IpcKey = (&Key::Account([7u8; 32])).into();
+    let response = server
+        .query(query_request(&MISSING_STATE_HASH, base_key))
+        .expect("query should be answered");
+    assert!(response.get_failure().starts_with("Root not found"));
+}
+
+#[test]
+fn should_report_unparseable_query_key() {
+    let server = GrpcTestServer::new();
+    let response = server
+        .query(query_request(&MISSING_STATE_HASH, IpcKey::new()))
+        .expect("query should be answered");
+    assert!(response.has_failure());
+}
+
+#[test]
+fn should_report_missing_root_of_balance_query() {
+    let server = GrpcTestServer::new();
+    let mut request = BalanceRequest::new();
+    request.set_state_hash(MISSING_STATE_HASH.to_vec());
+    request.set_purse(URef::new([7u8; 32], AccessRights::READ).into());
+    let response = server
+        .query_balance(request.clone())
+        .expect("balance query should be answered");
+    assert!(response.has_missing_state());
+
+    request.set_state_hash(vec![1u8; 31]);
+    let response = server
+        .query_balance(request)
+        .expect("balance query should be answered");
+    assert!(response.has_failure());
+}
+
+#[test]
+fn should_report_missing_parent_of_exec() {
+    let server = GrpcTestServer::new();
+    let mut request = ExecRequest::new();
+    request.set_parent_state_hash(MISSING_STATE_HASH.to_vec());
+    request.set_protocol_version(get_protocol_version());
+    request.set_deploys(vec![get_mock_deploy()].into());
+    let response = server.exec(request).expect("exec should be answered");
+    assert_eq!(
+        response.get_missing_parent().get_hash(),
+        &MISSING_STATE_HASH[..]
+    );
+}
+
+#[test]
+fn should_report_invalid_wasm() {
+    let server = GrpcTestServer::new();
+    let mut request = ValidateRequest::new();
+    request.set_session_code(vec![0u8; 8]);
+    request.set_payment_code(test_utils::create_empty_wasm_module_bytes());
+    let response = server
+        .validate(request)
+        .expect("validate should be answered");
+    assert!(response.has_failure());
+}
+
+#[test]
+fn should_reject_upgrades_without_admin_token() {
+    let server = GrpcTestServer::new();
+    let mut request = UpgradeRequest::new();
+    request.set_parent_state_hash(MISSING_STATE_HASH.to_vec());
+    request.set_protocol_version(get_protocol_version());
+    match server
+        .client()
+        .upgrade(grpc::RequestOptions::new(), request)
+        .wait_drop_metadata()
+    {
+        Err(grpc::Error::GrpcMessage(error)) => {
+            assert_eq!(error.grpc_status, grpc::GrpcStatus::PermissionDenied as i32)
+        }
+        other => panic!("expected a PermissionDenied error, got {:?}", other),
+    }
+}
+
+#[test]
+fn should_reject_requests_above_the_message_size_limit() {
+    let global_state = InMemoryGlobalState::empty().expect("should create global state");
+    let limits = MethodLimits {
+        max_receive_message_size: 16,
+        ..MethodLimits::default()
+    };
+    let server = GrpcTestServer::with_engine_state(EngineState::new(global_state), limits);
+    let base_key: IpcKey = (&Key::Account([7u8; 32])).into();
+    match server.query(query_request(&MISSING_STATE_HASH, base_key)) {
+        Err(grpc::Error::GrpcMessage(error)) => assert_eq!(
+            error.grpc_status,
+            grpc::GrpcStatus::ResourceExhausted as i32
+        ),
+        other => panic!("expected a ResourceExhausted error, got {:?}", other),
+    }
+}
@@ -12,7 +12,7 @@ use common::key::Key;
 use common::value::Value;
 use shared::transform::Transform;
 
-use test_support::{WasmTestBuilder, DEFAULT_BLOCK_TIME};
+use test_support::{PostState, WasmTestBuilder, DEFAULT_BLOCK_TIME};
 
 #[allow(dead_code)]
 mod test_support;
@@ -52,3 +52,64 @@ fn should_run_local_state_contract() {
         &Transform::Write(Value::String(String::from("Hello, world! Hello, world!")))
     );
 }
+
+#[ignore]
+#[test]
+fn should_run_local_state_contract_with_lmdb() {
+    let result = WasmTestBuilder::new_with_lmdb()
+        .run_genesis(GENESIS_ADDR, HashMap::new())
+        .exec(GENESIS_ADDR, "local_state.wasm", DEFAULT_BLOCK_TIME, 1)
+        .expect_success()
+        .commit()
+        .exec(GENESIS_ADDR, "local_state.wasm", DEFAULT_BLOCK_TIME, 2)
+        .expect_success()
+        .commit()
+        .finish();
+
+    let transforms = result.builder().get_transforms();
+
+    let expected_local_key = Key::local(GENESIS_ADDR, &[66u8; 32].to_bytes().unwrap());
+
+    assert_eq!(
+        transforms[1]
+            .get(&expected_local_key)
+            .expect("Should have expected local key"),
+        &Transform::Write(Value::String(String::from("Hello, world! Hello, world!")))
+    );
+}
+
+/// Genesis followed by a single run of the contract.
+fn local_state_written_once() -> PostState {
+    WasmTestBuilder::default()
+        .run_genesis(GENESIS_ADDR, HashMap::new())
+        .exec(GENESIS_ADDR, "local_state.wasm", DEFAULT_BLOCK_TIME, 1)
+        .expect_success()
+        .commit()
+        .finish()
+        .post_state()
+}
+
+#[ignore]
+#[test]
+fn should_run_local_state_contract_from_shared_post_state() {
+    let post_state = local_state_written_once();
+    let expected_local_key = Key::local(GENESIS_ADDR, &[66u8; 32].to_bytes().unwrap());
+
+    // Builders adopting the same post state don't see each other's commits.
+    for _ in 0..2 {
+        let result = WasmTestBuilder::from_post_state(&post_state)
+            .exec(GENESIS_ADDR, "local_state.wasm", DEFAULT_BLOCK_TIME, 2)
+            .expect_success()
+            .commit()
+            .finish();
+
+        let transforms = result.builder().get_transforms();
+        assert_eq!(transforms.len(), 1);
+        assert_eq!(
+            transforms[0]
+                .get(&expected_local_key)
+                .expect("Should have expected local key"),
+            &Transform::Write(Value::String(String::from("Hello, world! Hello, world!")))
+        );
+    }
+}
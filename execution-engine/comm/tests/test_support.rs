@@ -2,16 +2,22 @@ extern crate casperlabs_engine_grpc_server;
 extern crate common;
 extern crate execution_engine;
 extern crate grpc;
+extern crate lmdb;
 extern crate shared;
 extern crate storage;
+extern crate tempfile;
 extern crate wasm_prep;
 
 use std::collections::HashMap;
 use std::convert::TryInto;
+use std::fmt::Debug;
 use std::path::PathBuf;
 use std::rc::Rc;
+use std::sync::Arc;
 
 use grpc::RequestOptions;
+use lmdb::DatabaseFlags;
+use tempfile::TempDir;
 
 use casperlabs_engine_grpc_server::engine_server::ipc;
 use casperlabs_engine_grpc_server::engine_server::ipc::{
@@ -23,19 +29,34 @@ use casperlabs_engine_grpc_server::engine_server::mappings::{
     to_domain_validators, CommitTransforms,
 };
 use casperlabs_engine_grpc_server::engine_server::state::{BigInt, ProtocolVersion};
+use execution_engine::engine_state::error::Error as EngineError;
 use execution_engine::engine_state::utils::WasmiBytes;
 use execution_engine::engine_state::EngineState;
+use execution_engine::execution::Error as ExecutionError;
+use shared::os::get_page_size;
 use shared::test_utils;
 use shared::transform::Transform;
 use storage::global_state::in_memory::InMemoryGlobalState;
+use storage::global_state::lmdb::LmdbGlobalState;
+use storage::global_state::History;
+use storage::trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
 
 pub const DEFAULT_BLOCK_TIME: u64 = 0;
 pub const MOCKED_ACCOUNT_ADDRESS: [u8; 32] = [48u8; 32];
 pub const COMPILED_WASM_PATH: &str = "../target/wasm32-unknown-unknown/debug";
+pub const DEFAULT_PROTOCOL_VERSION: u64 = 1;
+/// Max number of pages used by the mmap of a persistent test global state.
+pub const LMDB_TEST_PAGES: usize = 262_144;
+pub const DEFAULT_PAYMENT_TOKENS: u64 = 1_000_000_000;
+pub const DEFAULT_GAS_PRICE: u64 = 1;
 
 pub fn get_protocol_version() -> ProtocolVersion {
+    protocol_version(DEFAULT_PROTOCOL_VERSION)
+}
+
+pub fn protocol_version(value: u64) -> ProtocolVersion {
     let mut protocol_version: ProtocolVersion = ProtocolVersion::new();
-    protocol_version.set_value(1);
+    protocol_version.set_value(value);
     protocol_version
 }
 
@@ -135,6 +156,109 @@ pub fn create_genesis_request(
     (ret, contracts)
 }
 
+fn deploy_code(
+    wasm_bytes: Vec<u8>,
+    arguments: impl common::contract_api::argsparser::ArgsParser,
+) -> DeployCode {
+    let args = arguments
+        .parse()
+        .and_then(|args_bytes| common::bytesrepr::ToBytes::to_bytes(&args_bytes))
+        .expect("should serialize args");
+    let mut deploy_code = DeployCode::new();
+    deploy_code.set_code(wasm_bytes);
+    deploy_code.set_args(args);
+    deploy_code
+}
+
+/// Builder for a deploy with control over each of its fields. By default the deploy is sent by
+/// [`MOCKED_ACCOUNT_ADDRESS`] with nonce 1, runs an empty session and has no payment code, and
+/// buys gas with [`DEFAULT_PAYMENT_TOKENS`] at [`DEFAULT_GAS_PRICE`].
+#[derive(Debug, Clone)]
+pub struct DeployBuilder {
+    deploy: Deploy,
+}
+
+impl Default for DeployBuilder {
+    fn default() -> Self {
+        let mut deploy = Deploy::new();
+        deploy.set_address(MOCKED_ACCOUNT_ADDRESS.to_vec());
+        deploy.set_tokens_transferred_in_payment(DEFAULT_PAYMENT_TOKENS);
+        deploy.set_gas_price(DEFAULT_GAS_PRICE);
+        deploy.set_nonce(1);
+        deploy.set_session(deploy_code(
+            test_utils::create_empty_wasm_module_bytes(),
+            (),
+        ));
+        DeployBuilder { deploy }
+    }
+}
+
+impl DeployBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_address(mut self, address: [u8; 32]) -> Self {
+        self.deploy.set_address(address.to_vec());
+        self
+    }
+
+    /// Runs the compiled contract `wasm_file` from [`COMPILED_WASM_PATH`] as the session.
+    pub fn with_session_code(
+        self,
+        wasm_file: &str,
+        args: impl common::contract_api::argsparser::ArgsParser,
+    ) -> Self {
+        self.with_session_bytes(read_wasm_file_bytes(wasm_file), args)
+    }
+
+    pub fn with_session_bytes(
+        mut self,
+        wasm_bytes: Vec<u8>,
+        args: impl common::contract_api::argsparser::ArgsParser,
+    ) -> Self {
+        self.deploy.set_session(deploy_code(wasm_bytes, args));
+        self
+    }
+
+    /// Sets the compiled contract `wasm_file` from [`COMPILED_WASM_PATH`] as the payment code.
+    pub fn with_payment_code(
+        mut self,
+        wasm_file: &str,
+        args: impl common::contract_api::argsparser::ArgsParser,
+    ) -> Self {
+        self.deploy
+            .set_payment(deploy_code(read_wasm_file_bytes(wasm_file), args));
+        self
+    }
+
+    pub fn with_tokens_transferred_in_payment(mut self, tokens: u64) -> Self {
+        self.deploy.set_tokens_transferred_in_payment(tokens);
+        self
+    }
+
+    pub fn with_gas_price(mut self, gas_price: u64) -> Self {
+        self.deploy.set_gas_price(gas_price);
+        self
+    }
+
+    pub fn with_nonce(mut self, nonce: u64) -> Self {
+        self.deploy.set_nonce(nonce);
+        self
+    }
+
+    /// Sets the keys the deploy is signed with, replacing any set before.
+    pub fn with_authorization_keys(mut self, keys: &[common::value::account::PublicKey]) -> Self {
+        let keys: Vec<Vec<u8>> = keys.iter().map(|key| key.value().to_vec()).collect();
+        self.deploy.set_authorization_keys(keys.into());
+        self
+    }
+
+    pub fn build(self) -> Deploy {
+        self.deploy
+    }
+}
+
 pub fn create_exec_request(
     address: [u8; 32],
     contract_file_name: &str,
@@ -143,22 +267,20 @@ pub fn create_exec_request(
     nonce: u64,
     arguments: impl common::contract_api::argsparser::ArgsParser,
 ) -> ExecRequest {
-    let args = arguments
-        .parse()
-        .and_then(|args_bytes| common::bytesrepr::ToBytes::to_bytes(&args_bytes))
-        .expect("should serialize args");
-    let bytes_to_deploy = read_wasm_file_bytes(contract_file_name);
-
-    let mut deploy = Deploy::new();
-    deploy.set_address(address.to_vec());
-    deploy.set_tokens_transferred_in_payment(1_000_000_000);
-    deploy.set_gas_price(1);
-    deploy.set_nonce(nonce);
-    let mut deploy_code = DeployCode::new();
-    deploy_code.set_code(bytes_to_deploy);
-    deploy_code.set_args(args);
-    deploy.set_session(deploy_code);
+    let deploy = DeployBuilder::new()
+        .with_address(address)
+        .with_session_code(contract_file_name, arguments)
+        .with_nonce(nonce)
+        .build();
+    create_exec_request_with_deploy(deploy, pre_state_hash, block_time)
+}
 
+/// Creates a request executing `deploy` on top of `pre_state_hash`.
+pub fn create_exec_request_with_deploy(
+    deploy: Deploy,
+    pre_state_hash: &[u8],
+    block_time: u64,
+) -> ExecRequest {
     let mut exec_request = ExecRequest::new();
     let mut deploys: protobuf::RepeatedField<Deploy> = <protobuf::RepeatedField<Deploy>>::new();
     deploys.push(deploy);
@@ -286,10 +408,12 @@ pub fn get_account(
 }
 
 /// Builder for simple WASM test
-#[derive(Clone)]
-pub struct WasmTestBuilder {
+pub struct WasmTestBuilder<S: History = InMemoryGlobalState> {
     /// Engine state is wrapped in Rc<> to workaround missing `impl Clone for EngineState`
-    engine_state: Rc<EngineState<InMemoryGlobalState>>,
+    engine_state: Rc<EngineState<S>>,
+    /// Directory of a persistent global state, removed once the last clone of the builder and
+    /// its results are dropped
+    temp_dir: Option<Rc<TempDir>>,
     exec_responses: Vec<ExecResponse>,
     genesis_hash: Option<Vec<u8>>,
     post_state_hash: Option<Vec<u8>>,
@@ -303,6 +427,27 @@ pub struct WasmTestBuilder {
     genesis_transforms: Option<HashMap<common::key::Key, Transform>>,
     /// Mint contract uref
     mint_contract_uref: Option<common::uref::URef>,
+    /// Protocol version of genesis and exec requests
+    protocol_version: u64,
+}
+
+// Derived `Clone` would require `S: Clone`, while only the `Rc` around the state is cloned.
+impl<S: History> Clone for WasmTestBuilder<S> {
+    fn clone(&self) -> Self {
+        WasmTestBuilder {
+            engine_state: Rc::clone(&self.engine_state),
+            temp_dir: self.temp_dir.clone(),
+            exec_responses: self.exec_responses.clone(),
+            genesis_hash: self.genesis_hash.clone(),
+            post_state_hash: self.post_state_hash.clone(),
+            transforms: self.transforms.clone(),
+            bonded_validators: self.bonded_validators.clone(),
+            genesis_account: self.genesis_account.clone(),
+            genesis_transforms: self.genesis_transforms.clone(),
+            mint_contract_uref: self.mint_contract_uref,
+            protocol_version: self.protocol_version,
+        }
+    }
 }
 
 impl Default for WasmTestBuilder {
@@ -312,36 +457,97 @@ impl Default for WasmTestBuilder {
 }
 
 /// A wrapper type to disambiguate builder from an actual result
-pub struct WasmTestResult(WasmTestBuilder);
+pub struct WasmTestResult<S: History = InMemoryGlobalState>(WasmTestBuilder<S>);
 
-impl WasmTestResult {
+impl<S: History> WasmTestResult<S> {
     /// Access the builder
-    pub fn builder(&self) -> &WasmTestBuilder {
+    pub fn builder(&self) -> &WasmTestBuilder<S> {
         &self.0
     }
-}
 
-impl WasmTestBuilder {
-    /// Carries on attributes from TestResult for further executions
-    pub fn from_result(result: WasmTestResult) -> WasmTestBuilder {
-        WasmTestBuilder {
-            engine_state: result.0.engine_state,
-            exec_responses: Vec::new(),
-            genesis_hash: result.0.genesis_hash,
-            post_state_hash: result.0.post_state_hash,
-            transforms: Vec::new(),
-            bonded_validators: result.0.bonded_validators,
-            genesis_account: result.0.genesis_account,
-            mint_contract_uref: result.0.mint_contract_uref,
-            genesis_transforms: result.0.genesis_transforms,
+    /// Snapshot of the global state the scenario ended with, see [`PostState`].
+    pub fn post_state(&self) -> PostState<S> {
+        let builder = &self.0;
+        PostState {
+            engine_state: Rc::clone(&builder.engine_state),
+            temp_dir: builder.temp_dir.clone(),
+            genesis_hash: builder.genesis_hash.clone(),
+            post_state_hash: builder
+                .post_state_hash
+                .clone()
+                .expect("Should have post-state hash. Please run genesis first."),
+            bonded_validators: builder.bonded_validators.clone(),
+            genesis_account: builder.genesis_account.clone(),
+            genesis_transforms: builder.genesis_transforms.clone(),
+            mint_contract_uref: builder.mint_contract_uref,
+            protocol_version: builder.protocol_version,
         }
     }
+}
+
+/// Global state at the end of a finished scenario, together with what the builder cached about
+/// genesis. Any number of builders can start from it with [`WasmTestBuilder::from_post_state`],
+/// so a setup shared by many tests only has to be written, and run, once. The builders share the
+/// underlying global state, but each of them only sees the roots it committed itself.
+pub struct PostState<S: History = InMemoryGlobalState> {
+    engine_state: Rc<EngineState<S>>,
+    temp_dir: Option<Rc<TempDir>>,
+    genesis_hash: Option<Vec<u8>>,
+    post_state_hash: Vec<u8>,
+    bonded_validators: Vec<HashMap<common::value::account::PublicKey, common::value::U512>>,
+    genesis_account: Option<common::value::Account>,
+    genesis_transforms: Option<HashMap<common::key::Key, Transform>>,
+    mint_contract_uref: Option<common::uref::URef>,
+    protocol_version: u64,
+}
+
+impl<S: History> PostState<S> {
+    pub fn root_hash(&self) -> &[u8] {
+        &self.post_state_hash
+    }
+
+    pub fn genesis_transforms(&self) -> Option<&HashMap<common::key::Key, Transform>> {
+        self.genesis_transforms.as_ref()
+    }
+}
 
+impl WasmTestBuilder {
     pub fn new() -> WasmTestBuilder {
         let global_state = InMemoryGlobalState::empty().expect("should create global state");
-        let engine_state = EngineState::new(global_state);
+        WasmTestBuilder::with_global_state(global_state, None)
+    }
+}
+
+impl WasmTestBuilder<LmdbGlobalState> {
+    /// Creates a builder backed by LMDB in a fresh temporary directory, so tests using the
+    /// persistent backend can run in parallel. The directory is removed along with the builder.
+    pub fn new_with_lmdb() -> WasmTestBuilder<LmdbGlobalState> {
+        let temp_dir = tempfile::tempdir().expect("should create temp dir");
+        let map_size = get_page_size().expect("should get page size") * LMDB_TEST_PAGES;
+        let environment = Arc::new(
+            LmdbEnvironment::new(&temp_dir.path().to_path_buf(), map_size)
+                .expect("should create lmdb environment"),
+        );
+        let trie_store = Arc::new(
+            LmdbTrieStore::new(&environment, None, DatabaseFlags::empty())
+                .expect("should create lmdb trie store"),
+        );
+        let global_state =
+            LmdbGlobalState::empty(environment, trie_store).expect("should create global state");
+        WasmTestBuilder::with_global_state(global_state, Some(Rc::new(temp_dir)))
+    }
+}
+
+impl<S> WasmTestBuilder<S>
+where
+    S: History,
+    EngineError: From<S::Error>,
+    S::Error: Into<ExecutionError> + Debug,
+{
+    fn with_global_state(global_state: S, temp_dir: Option<Rc<TempDir>>) -> WasmTestBuilder<S> {
         WasmTestBuilder {
-            engine_state: Rc::new(engine_state),
+            engine_state: Rc::new(EngineState::new(global_state)),
+            temp_dir,
             exec_responses: Vec::new(),
             genesis_hash: None,
             post_state_hash: None,
@@ -350,16 +556,47 @@ impl WasmTestBuilder {
             genesis_account: None,
             mint_contract_uref: None,
             genesis_transforms: None,
+            protocol_version: DEFAULT_PROTOCOL_VERSION,
+        }
+    }
+
+    /// Carries on attributes from TestResult for further executions
+    pub fn from_result(result: WasmTestResult<S>) -> WasmTestBuilder<S> {
+        WasmTestBuilder::from_post_state(&result.post_state())
+    }
+
+    /// Starts a new builder from the state a previous scenario ended with, without any of its
+    /// exec responses or transforms.
+    pub fn from_post_state(post_state: &PostState<S>) -> WasmTestBuilder<S> {
+        WasmTestBuilder {
+            engine_state: Rc::clone(&post_state.engine_state),
+            temp_dir: post_state.temp_dir.clone(),
+            exec_responses: Vec::new(),
+            genesis_hash: post_state.genesis_hash.clone(),
+            post_state_hash: Some(post_state.post_state_hash.clone()),
+            transforms: Vec::new(),
+            bonded_validators: post_state.bonded_validators.clone(),
+            genesis_account: post_state.genesis_account.clone(),
+            mint_contract_uref: post_state.mint_contract_uref,
+            genesis_transforms: post_state.genesis_transforms.clone(),
+            protocol_version: post_state.protocol_version,
         }
     }
 
+    /// Sets the protocol version used by subsequent genesis and exec requests.
+    pub fn with_protocol_version(&mut self, protocol_version: u64) -> &mut WasmTestBuilder<S> {
+        self.protocol_version = protocol_version;
+        self
+    }
+
     pub fn run_genesis(
         &mut self,
         genesis_addr: [u8; 32],
         genesis_validators: HashMap<common::value::account::PublicKey, common::value::U512>,
-    ) -> &mut WasmTestBuilder {
-        let (genesis_request, contracts) =
+    ) -> &mut WasmTestBuilder<S> {
+        let (mut genesis_request, contracts) =
             create_genesis_request(genesis_addr, genesis_validators.clone());
+        genesis_request.set_protocol_version(protocol_version(self.protocol_version));
 
         let genesis_response = self
             .engine_state
@@ -394,7 +631,7 @@ impl WasmTestBuilder {
 
         let state_root_hash = {
             let state_handle_guard = state_handle.lock();
-            state_handle_guard.root_hash
+            state_handle_guard.current_root()
         };
 
         let genesis_hash = genesis_response.get_success().get_poststate_hash().to_vec();
@@ -416,17 +653,28 @@ impl WasmTestBuilder {
         block_time: u64,
         nonce: u64,
         args: impl common::contract_api::argsparser::ArgsParser,
-    ) -> &mut WasmTestBuilder {
-        let exec_request = create_exec_request(
-            address,
-            &wasm_file,
+    ) -> &mut WasmTestBuilder<S> {
+        let deploy = DeployBuilder::new()
+            .with_address(address)
+            .with_session_code(wasm_file, args)
+            .with_nonce(nonce);
+        self.exec_with_deploy(deploy, block_time)
+    }
+
+    /// Executes the deploy built by `deploy` and caches its transforms, like `exec_with_args`.
+    pub fn exec_with_deploy(
+        &mut self,
+        deploy: DeployBuilder,
+        block_time: u64,
+    ) -> &mut WasmTestBuilder<S> {
+        let mut exec_request = create_exec_request_with_deploy(
+            deploy.build(),
             self.post_state_hash
                 .as_ref()
                 .expect("Should have post state hash"),
             block_time,
-            nonce,
-            args,
         );
+        exec_request.set_protocol_version(protocol_version(self.protocol_version));
 
         let exec_response = self
             .engine_state
@@ -459,12 +707,12 @@ impl WasmTestBuilder {
         wasm_file: &str,
         block_time: u64,
         nonce: u64,
-    ) -> &mut WasmTestBuilder {
+    ) -> &mut WasmTestBuilder<S> {
         self.exec_with_args(address, wasm_file, block_time, nonce, ())
     }
 
     /// Commit effects of previous exec call on the latest post-state hash.
-    pub fn commit(&mut self) -> &mut WasmTestBuilder {
+    pub fn commit(&mut self) -> &mut WasmTestBuilder<S> {
         let prestate_hash = self
             .post_state_hash
             .clone()
@@ -485,7 +733,7 @@ impl WasmTestBuilder {
         &mut self,
         prestate_hash: Vec<u8>,
         effects: HashMap<common::key::Key, Transform>,
-    ) -> &mut WasmTestBuilder {
+    ) -> &mut WasmTestBuilder<S> {
         let commit_request = create_commit_request(&prestate_hash, &effects);
 
         let commit_response = self
@@ -511,7 +759,7 @@ impl WasmTestBuilder {
     }
 
     /// Expects a successful run and caches transformations
-    pub fn expect_success(&mut self) -> &mut WasmTestBuilder {
+    pub fn expect_success(&mut self) -> &mut WasmTestBuilder<S> {
         // Check first result, as only first result is interesting for a simple test
         let exec_response = self
             .exec_responses
@@ -550,6 +798,55 @@ impl WasmTestBuilder {
         deploy_result.get_execution_result().has_error()
     }
 
+    /// Gets the response of the `exec_index`-th exec call, counting from 0.
+    pub fn get_exec_response(&self, exec_index: usize) -> Option<&ExecResponse> {
+        self.exec_responses.get(exec_index)
+    }
+
+    /// Gets the cost of the deploy of every exec call so far, in order.
+    pub fn get_exec_costs(&self) -> Vec<u64> {
+        self.exec_responses
+            .iter()
+            .map(|exec_response| {
+                exec_response
+                    .get_success()
+                    .get_deploy_results()
+                    .get(0)
+                    .expect("Unable to get first deploy result")
+                    .get_execution_result()
+                    .get_cost()
+            })
+            .collect()
+    }
+
+    /// Gets the cost of the deploy of the `deploy_index`-th exec call, counting from 0.
+    pub fn get_gas_cost(&self, deploy_index: usize) -> u64 {
+        let costs = self.get_exec_costs();
+        *costs.get(deploy_index).unwrap_or_else(|| {
+            panic!(
+                "Unable to get deploy {}, only {} were executed",
+                deploy_index,
+                costs.len()
+            )
+        })
+    }
+
+    /// Expects the deploy of the `deploy_index`-th exec call to have cost exactly `expected`.
+    pub fn expect_gas_cost(
+        &mut self,
+        deploy_index: usize,
+        expected: u64,
+    ) -> &mut WasmTestBuilder<S> {
+        let cost = self.get_gas_cost(deploy_index);
+        if cost != expected {
+            panic!(
+                "Expected deploy {} to cost {} gas, but it cost {} instead",
+                deploy_index, expected, cost
+            );
+        }
+        self
+    }
+
     /// Gets the transform map that's cached between runs
     pub fn get_transforms(&self) -> Vec<HashMap<common::key::Key, Transform>> {
         self.transforms.clone()
@@ -594,7 +891,7 @@ impl WasmTestBuilder {
             .expect("Should have post-state hash.")
     }
 
-    pub fn finish(&self) -> WasmTestResult {
+    pub fn finish(&self) -> WasmTestResult<S> {
         WasmTestResult(self.clone())
     }
 }
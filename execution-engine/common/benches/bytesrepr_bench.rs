@@ -376,9 +376,61 @@ fn make_account() -> Account {
         associated_keys,
         action_thresholds,
         account_activity,
+        AssociatedKeys::empty(),
     )
 }
 
+/// Named keys of a long-lived account or contract, e.g. one holding a token's balances.
+const LARGE_NAMED_KEYS_COUNT: usize = 1000;
+
+fn make_large_known_urefs() -> BTreeMap<String, Key> {
+    (0..LARGE_NAMED_KEYS_COUNT)
+        .map(|i| {
+            let mut addr = [0u8; 32];
+            addr[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            (
+                format!("named-key-{}", i),
+                Key::URef(URef::new(addr, AccessRights::READ_ADD_WRITE)),
+            )
+        })
+        .collect()
+}
+
+fn make_large_contract() -> Contract {
+    Contract::new(vec![0u8; 256 * KB], make_large_known_urefs(), 1)
+}
+
+fn make_large_account() -> Account {
+    let mut associated_keys = AssociatedKeys::new(PublicKey::new([0u8; 32]), Weight::new(1));
+    for i in 1..10u8 {
+        associated_keys
+            .add_key(PublicKey::new([i; 32]), Weight::new(1))
+            .unwrap();
+    }
+    Account::new(
+        [0u8; 32],
+        2_635_333_365_164_409_670u64,
+        make_large_known_urefs(),
+        make_purse_id(),
+        associated_keys,
+        Default::default(),
+        AccountActivity::new(BlockTime(0), BlockTime(100)),
+        AssociatedKeys::empty(),
+    )
+}
+
+#[bench]
+fn roundtrip_large_account(b: &mut Bencher) {
+    let account = make_large_account();
+    b.iter(|| Account::from_bytes(&black_box(&account).to_bytes().unwrap()).map(|(a, _)| a));
+}
+
+#[bench]
+fn roundtrip_large_contract(b: &mut Bencher) {
+    let contract = make_large_contract();
+    b.iter(|| Contract::from_bytes(&black_box(&contract).to_bytes().unwrap()).map(|(c, _)| c));
+}
+
 #[bench]
 fn serialize_account(b: &mut Bencher) {
     let account = make_account();
@@ -7,6 +7,9 @@ use core::fmt::Display;
 use core::mem::{size_of, MaybeUninit};
 use failure::Fail;
 
+/// `#[derive(ToBytes, FromBytes)]`, serializing fields in declaration order.
+pub use casperlabs_bytesrepr_derive::{FromBytes, ToBytes};
+
 pub const I32_SIZE: usize = size_of::<i32>();
 pub const U8_SIZE: usize = size_of::<u8>();
 pub const U16_SIZE: usize = size_of::<u16>();
@@ -58,6 +61,13 @@ impl Error {
     }
 }
 
+/// Re-exported for the code generated by the derives, which can't rely on the deriving crate
+/// importing `alloc`.
+#[doc(hidden)]
+pub mod derive_support {
+    pub use crate::alloc::vec::Vec;
+}
+
 pub fn deserialize<T: FromBytes>(bytes: &[u8]) -> Result<T, Error> {
     let (t, rem): (T, &[u8]) = FromBytes::from_bytes(bytes)?;
     if rem.is_empty() {
@@ -570,6 +580,16 @@ mod proptests {
             assert!(test_serialization_roundtrip(&access_right))
         }
 
+        #[test]
+        fn test_any_access_rights(access_rights in any_access_rights_arb()) {
+            assert!(test_serialization_roundtrip(&access_rights))
+        }
+
+        #[test]
+        fn test_large_contract(contract in large_contract_arb()) {
+            assert!(test_serialization_roundtrip(&contract))
+        }
+
         #[test]
         fn test_public_key(pk in public_key_arb()) {
             assert!(test_serialization_roundtrip(&pk))
@@ -577,3 +597,74 @@ mod proptests {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::alloc::string::String;
+    use super::super::alloc::vec::Vec;
+    use super::{deserialize, Error, FromBytes, ToBytes};
+    use crate::test_utils::test_serialization_roundtrip;
+
+    #[derive(Debug, PartialEq, ToBytes, FromBytes)]
+    #[bytesrepr(crate_path = "crate")]
+    struct Named {
+        result: u32,
+        rest: String,
+        bytes: Vec<u8>,
+    }
+
+    #[derive(Debug, PartialEq, ToBytes, FromBytes)]
+    #[bytesrepr(crate_path = "crate")]
+    struct Tuple(u64, Option<u8>);
+
+    #[derive(Debug, PartialEq, ToBytes, FromBytes)]
+    #[bytesrepr(crate_path = "crate")]
+    struct Unit;
+
+    #[derive(Debug, PartialEq, ToBytes, FromBytes)]
+    #[bytesrepr(crate_path = "crate")]
+    enum Enum<T> {
+        Empty,
+        Tuple(T, u8),
+        Named { tag: u8, value: T },
+    }
+
+    #[test]
+    fn derived_impls_should_roundtrip() {
+        assert!(test_serialization_roundtrip(&Named {
+            result: 1,
+            rest: String::from("rest"),
+            bytes: vec![1, 2, 3],
+        }));
+        assert!(test_serialization_roundtrip(&Tuple(7, Some(8))));
+        assert!(test_serialization_roundtrip(&Unit));
+        assert!(test_serialization_roundtrip(&Enum::<u32>::Empty));
+        assert!(test_serialization_roundtrip(&Enum::Tuple(5u32, 6)));
+        assert!(test_serialization_roundtrip(&Enum::Named {
+            tag: 3,
+            value: 9u32
+        }));
+    }
+
+    #[test]
+    fn derived_impls_should_serialize_fields_in_order() {
+        let mut expected = 7u64.to_bytes().unwrap();
+        expected.append(&mut Some(8u8).to_bytes().unwrap());
+        assert_eq!(Tuple(7, Some(8)).to_bytes().unwrap(), expected);
+        assert!(Unit.to_bytes().unwrap().is_empty());
+
+        let mut expected = vec![2u8, 3];
+        expected.append(&mut 9u32.to_bytes().unwrap());
+        let named = Enum::Named {
+            tag: 3,
+            value: 9u32,
+        };
+        assert_eq!(named.to_bytes().unwrap(), expected);
+    }
+
+    #[test]
+    fn derived_impls_should_reject_unknown_variants() {
+        assert_eq!(deserialize::<Enum<u32>>(&[3]), Err(Error::FormattingError));
+        assert_eq!(Unit::from_bytes(&[1]).map(|(_, rest)| rest.len()), Ok(1));
+    }
+}
@@ -0,0 +1,53 @@
+use crate::bytesrepr::{Error, FromBytes, ToBytes, U32_SIZE, U64_SIZE};
+use alloc::vec::Vec;
+
+pub const CHAIN_PARAMETERS_SER_SIZE: usize = U32_SIZE + U64_SIZE;
+
+/// Parameters of the chain a contract runs on, which may change across protocol upgrades.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainParameters {
+    /// Maximum number of keys associated with an account.
+    pub max_associated_keys: u32,
+    /// Most gas the deploys of a block can spend together.
+    pub block_gas_limit: u64,
+}
+
+impl ToBytes for ChainParameters {
+    fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut result = Vec::with_capacity(CHAIN_PARAMETERS_SER_SIZE);
+        result.append(&mut self.max_associated_keys.to_bytes()?);
+        result.append(&mut self.block_gas_limit.to_bytes()?);
+        Ok(result)
+    }
+}
+
+impl FromBytes for ChainParameters {
+    fn from_bytes(bytes: &[u8]) -> Result<(Self, &[u8]), Error> {
+        let (max_associated_keys, rem): (u32, &[u8]) = FromBytes::from_bytes(bytes)?;
+        let (block_gas_limit, rem): (u64, &[u8]) = FromBytes::from_bytes(rem)?;
+        Ok((
+            ChainParameters {
+                max_associated_keys,
+                block_gas_limit,
+            },
+            rem,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChainParameters, CHAIN_PARAMETERS_SER_SIZE};
+    use crate::bytesrepr::{deserialize, ToBytes};
+
+    #[test]
+    fn should_roundtrip_with_fixed_size() {
+        let parameters = ChainParameters {
+            max_associated_keys: 10,
+            block_gas_limit: 1_000_000,
+        };
+        let bytes = parameters.to_bytes().unwrap();
+        assert_eq!(bytes.len(), CHAIN_PARAMETERS_SER_SIZE);
+        assert_eq!(deserialize::<ChainParameters>(&bytes).unwrap(), parameters);
+    }
+}
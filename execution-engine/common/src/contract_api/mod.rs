@@ -4,13 +4,16 @@ pub mod pointers;
 
 use self::alloc_util::*;
 use self::pointers::*;
-use crate::bytesrepr::{deserialize, FromBytes, ToBytes};
+use crate::bytesrepr::{self, deserialize, FromBytes, ToBytes};
+use crate::chain_parameters::{ChainParameters, CHAIN_PARAMETERS_SER_SIZE};
 use crate::ext_ffi;
 use crate::key::{Key, UREF_SIZE};
+use crate::precompile::Precompile;
 use crate::uref::URef;
 use crate::value::account::{
-    Account, ActionType, AddKeyFailure, BlockTime, PublicKey, PurseId, RemoveKeyFailure,
-    SetThresholdFailure, Weight, BLOCKTIME_SER_SIZE, PURSE_ID_SIZE_SERIALIZED,
+    Account, ActionType, AddKeyFailure, AssociatedKeys, BlockTime, PublicKey, PurseId,
+    RecoveryFailure, RemoveKeyFailure, SetThresholdFailure, Weight, BLOCKTIME_SER_SIZE,
+    PURSE_ID_SIZE_SERIALIZED,
 };
 use crate::value::{Contract, Value, U512};
 use alloc::collections::BTreeMap;
@@ -47,20 +50,111 @@ fn read_untyped(key: &Key) -> Option<Value> {
     deserialize(&value_bytes).unwrap()
 }
 
-/// Reads the value at the given key in the context-local partition of global state
-pub fn read_local<K, V>(key: K) -> Option<V>
+/// Reads the value found by following the named keys in `path`, starting from the
+/// account or contract under `key`. Unlike `call_contract`, no code of that contract
+/// is run; only named keys which the current context could read are followed.
+pub fn query<T>(key: &Key, path: &[String]) -> Option<T>
+where
+    T: TryFrom<Value>,
+{
+    query_untyped(key, path).map(|v| {
+        v.try_into()
+            .map_err(|_| "T could not be derived from Value")
+            .unwrap()
+    })
+}
+
+fn query_untyped(key: &Key, path: &[String]) -> Option<Value> {
+    let (key_ptr, key_size, _bytes) = to_ptr(key);
+    let (path_ptr, path_size, _bytes2) = to_ptr(&path.to_vec());
+    let value_size = unsafe { ext_ffi::query_state(key_ptr, key_size, path_ptr, path_size) };
+    let value_ptr = alloc_bytes(value_size);
+    let value_bytes = unsafe {
+        ext_ffi::get_read(value_ptr);
+        Vec::from_raw_parts(value_ptr, value_size, value_size)
+    };
+    deserialize(&value_bytes).unwrap()
+}
+
+/// Creates a new, empty dictionary and stores its `URef` under `name` in the
+/// named keys of the current context. Items are kept under separate keys, so
+/// large dictionaries don't have to be read or written as a whole.
+pub fn new_dictionary(name: &str) -> URef {
+    let pointer: UPointer<Value> = new_uref(Value::Unit);
+    let uref = URef::new(pointer.0, pointer.1);
+    add_uref(name, &Key::URef(uref));
+    uref
+}
+
+/// Reads the item stored under `key` in the dictionary `uref`
+pub fn dictionary_get<V>(uref: URef, key: &str) -> Option<V>
 where
-    K: ToBytes,
     V: TryFrom<Value>,
 {
-    let key_bytes = key.to_bytes().unwrap();
-    read_untyped_local(&key_bytes).map(|v| {
+    let (uref_ptr, uref_size, _bytes) = to_ptr(&uref);
+    let (key_ptr, key_size, _bytes2) = str_ref_to_ptr(key);
+    let value_size = unsafe { ext_ffi::dictionary_get(uref_ptr, uref_size, key_ptr, key_size) };
+    let value_ptr = alloc_bytes(value_size);
+    let value_bytes = unsafe {
+        ext_ffi::get_read(value_ptr);
+        Vec::from_raw_parts(value_ptr, value_size, value_size)
+    };
+    let value: Option<Value> = deserialize(&value_bytes).unwrap();
+    value.map(|v| {
         v.try_into()
             .map_err(|_| "T could not be derived from Value")
             .unwrap()
     })
 }
 
+/// Stores `value` under `key` in the dictionary `uref`
+pub fn dictionary_put<V>(uref: URef, key: &str, value: V)
+where
+    V: Into<Value>,
+{
+    let (uref_ptr, uref_size, _bytes) = to_ptr(&uref);
+    let (key_ptr, key_size, _bytes2) = str_ref_to_ptr(key);
+    let (value_ptr, value_size, _bytes3) = to_ptr(&value.into());
+    unsafe {
+        ext_ffi::dictionary_put(
+            uref_ptr, uref_size, key_ptr, key_size, value_ptr, value_size,
+        )
+    };
+}
+
+/// Reads the value at the given key in the context-local partition of global state
+pub fn read_local<K, V>(key: K) -> Option<V>
+where
+    K: ToBytes,
+    V: TryFrom<Value>,
+{
+    read_local_bytes(&local_key_bytes(key))
+}
+
+/// Reads the value at the given raw key bytes in the context-local partition of global state,
+/// for keys which aren't serialized values, e.g. fixed-size ids or prefixed names.
+///
+/// Unlike `read_local`, the key isn't serialized first, so it has no length prefix:
+/// `read_local_bytes(b"count")` and `read_local("count")` read different entries.
+pub fn read_local_bytes<V>(key_bytes: &[u8]) -> Option<V>
+where
+    V: TryFrom<Value>,
+{
+    read_untyped_local(key_bytes).map(local_value)
+}
+
+/// The key bytes `read_local` and `write_local` address the context-local partition with.
+fn local_key_bytes<K: ToBytes>(key: K) -> Vec<u8> {
+    key.to_bytes().unwrap()
+}
+
+fn local_value<V: TryFrom<Value>>(value: Value) -> V {
+    value
+        .try_into()
+        .map_err(|_| "T could not be derived from Value")
+        .unwrap()
+}
+
 fn read_untyped_local(key_bytes: &[u8]) -> Option<Value> {
     let key_bytes_ptr = key_bytes.as_ptr();
     let key_bytes_size = key_bytes.len();
@@ -97,8 +191,19 @@ where
     K: ToBytes,
     V: Into<Value>,
 {
-    let key_bytes = key.to_bytes().unwrap();
-    write_untyped_local(&key_bytes, &value.into());
+    write_local_bytes(&local_key_bytes(key), value);
+}
+
+/// Writes the given value at the given raw key bytes in the context-local partition of global
+/// state, where `read_local_bytes` reads it back.
+///
+/// Unlike `write_local`, the key is used as is rather than serialized, so values written with
+/// one can't be read with the other.
+pub fn write_local_bytes<V>(key_bytes: &[u8], value: V)
+where
+    V: Into<Value>,
+{
+    write_untyped_local(key_bytes, &value.into());
 }
 
 fn write_untyped_local(key_bytes: &[u8], value: &Value) {
@@ -170,6 +275,59 @@ pub fn list_known_urefs() -> BTreeMap<String, Key> {
     deserialize(&bytes).unwrap()
 }
 
+const NAMED_KEYS_PAGE_SIZE: u32 = 32;
+
+/// Iterator over the named keys of the current context in name order, see `named_keys`.
+pub struct NamedKeys {
+    page: alloc::collections::btree_map::IntoIter<String, Key>,
+    last_name: Option<String>,
+    exhausted: bool,
+}
+
+impl NamedKeys {
+    fn next_page(&mut self) -> BTreeMap<String, Key> {
+        let (after_ptr, after_size, _bytes) = to_ptr(&self.last_name);
+        let page_size =
+            unsafe { ext_ffi::named_keys_page(after_ptr, after_size, NAMED_KEYS_PAGE_SIZE) };
+        let page_ptr = alloc_bytes(page_size);
+        let page_bytes = unsafe {
+            ext_ffi::get_read(page_ptr);
+            Vec::from_raw_parts(page_ptr, page_size, page_size)
+        };
+        deserialize(&page_bytes).unwrap()
+    }
+}
+
+impl Iterator for NamedKeys {
+    type Item = (String, Key);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((name, key)) = self.page.next() {
+                self.last_name = Some(name.clone());
+                return Some((name, key));
+            }
+            if self.exhausted {
+                return None;
+            }
+            let page = self.next_page();
+            self.exhausted = page.len() < NAMED_KEYS_PAGE_SIZE as usize;
+            self.page = page.into_iter();
+        }
+    }
+}
+
+/// Returns the named keys of the current context, fetched from the host a page at a time, so
+/// that contracts with many keys don't need to hold them all in memory like `list_known_urefs`.
+/// Available from protocol version 2.
+pub fn named_keys() -> NamedKeys {
+    NamedKeys {
+        page: BTreeMap::new().into_iter(),
+        last_name: None,
+        exhausted: false,
+    }
+}
+
 // TODO: fn_by_name, fn_bytes_by_name and ext_ffi::serialize_function should be removed.
 // Functions shouldn't be serialized and returned back to the contract because they're never used there.
 // Host should read the function pointer (and correct number of bytes) and persist it on the host side.
@@ -198,6 +356,65 @@ pub fn store_function(name: &str, known_urefs: BTreeMap<String, Key>) -> Contrac
     ContractPointer::Hash(tmp)
 }
 
+/// Like `store_function`, but the stored contract can only be called by contexts holding at
+/// least one of the `access_groups` URefs (with any access rights). A group is just a URef,
+/// e.g. one created with `new_uref`, handed out to the accounts or contracts allowed in.
+pub fn store_function_with_access_groups(
+    name: &str,
+    known_urefs: BTreeMap<String, Key>,
+    access_groups: &[URef],
+) -> ContractPointer {
+    let (fn_ptr, fn_size, _bytes1) = str_ref_to_ptr(name);
+    let (urefs_ptr, urefs_size, _bytes2) = to_ptr(&known_urefs);
+    let (groups_ptr, groups_size, _bytes3) = to_ptr(&access_groups.to_vec());
+    let mut tmp = [0u8; 32];
+    let tmp_ptr = tmp.as_mut_ptr();
+    unsafe {
+        ext_ffi::store_function_with_access_groups(
+            fn_ptr,
+            fn_size,
+            urefs_ptr,
+            urefs_size,
+            groups_ptr,
+            groups_size,
+            tmp_ptr,
+        );
+    }
+    ContractPointer::Hash(tmp)
+}
+
+/// Like `store_function`, but the contract is stored at the `contract_address` of the deploying
+/// account and `contract_name` instead of a fresh hash, so its address can be known before it's
+/// deployed. Returns `None` if a contract is already stored there. Available from protocol
+/// version 2.
+pub fn store_function_at_hash(
+    name: &str,
+    known_urefs: BTreeMap<String, Key>,
+    contract_name: &str,
+) -> Option<ContractPointer> {
+    let (fn_ptr, fn_size, _bytes1) = str_ref_to_ptr(name);
+    let (urefs_ptr, urefs_size, _bytes2) = to_ptr(&known_urefs);
+    let (contract_name_ptr, contract_name_size, _bytes3) = str_ref_to_ptr(contract_name);
+    let mut tmp = [0u8; 32];
+    let tmp_ptr = tmp.as_mut_ptr();
+    let result = unsafe {
+        ext_ffi::store_function_at_hash(
+            fn_ptr,
+            fn_size,
+            urefs_ptr,
+            urefs_size,
+            contract_name_ptr,
+            contract_name_size,
+            tmp_ptr,
+        )
+    };
+    if result == 0 {
+        Some(ContractPointer::Hash(tmp))
+    } else {
+        None
+    }
+}
+
 /// Finds function by the name and stores it at the unforgable name.
 pub fn store_function_at(name: &str, known_urefs: BTreeMap<String, Key>, uref: UPointer<Contract>) {
     let contract = fn_by_name(name, known_urefs);
@@ -242,19 +459,32 @@ pub fn has_uref(name: &str) -> bool {
     result == 0
 }
 
-/// Add the given key to the known_urefs map under the given name
-pub fn add_uref(name: &str, key: &Key) {
+/// Puts the given key under the given name in the current context's map, replacing the key
+/// previously under that name. Replacing a key conflicts with concurrent deploys changing the
+/// named keys of the context, adding a new name doesn't.
+pub fn put_key(name: &str, key: &Key) {
     let (name_ptr, name_size, _bytes) = str_ref_to_ptr(name);
     let (key_ptr, key_size, _bytes2) = to_ptr(key);
     unsafe { ext_ffi::add_uref(name_ptr, name_size, key_ptr, key_size) };
 }
 
-/// Removes Key persisted under [name] in the current context's map.
-pub fn remove_uref(name: &str) {
+/// Removes the key under the given name from the current context's map, if there is one.
+/// Removing a key conflicts with concurrent deploys using the context.
+pub fn remove_key(name: &str) {
     let (name_ptr, name_size, _bytes) = str_ref_to_ptr(name);
     unsafe { ext_ffi::remove_uref(name_ptr, name_size) }
 }
 
+/// Add the given key to the known_urefs map under the given name, same as `put_key`
+pub fn add_uref(name: &str, key: &Key) {
+    put_key(name, key)
+}
+
+/// Removes Key persisted under [name] in the current context's map, same as `remove_key`
+pub fn remove_uref(name: &str) {
+    remove_key(name)
+}
+
 /// Returns caller of current context.
 /// When in root context (not in the sub call) - returns None.
 /// When in the sub call - returns public key of the account that made the deploy.
@@ -275,6 +505,47 @@ pub fn get_blocktime() -> BlockTime {
     deserialize(&bytes).unwrap()
 }
 
+/// Returns the protocol version the contract is running under.
+pub fn get_protocol_version() -> u64 {
+    unsafe { ext_ffi::protocol_version() }
+}
+
+/// Returns the parameters of the chain, e.g. to stay within the number of associated keys.
+/// Available from protocol version 2.
+pub fn get_chain_parameters() -> ChainParameters {
+    let dest_ptr = alloc_bytes(CHAIN_PARAMETERS_SER_SIZE);
+    let bytes = unsafe {
+        ext_ffi::get_chain_parameters(dest_ptr);
+        Vec::from_raw_parts(
+            dest_ptr,
+            CHAIN_PARAMETERS_SER_SIZE,
+            CHAIN_PARAMETERS_SER_SIZE,
+        )
+    };
+    deserialize(&bytes).unwrap()
+}
+
+/// Emits `data` as an event of the current context. Events are reported with the results of
+/// the deploy and, if the engine keeps event logs, added to the log of the context. The log is
+/// rewritten by every event, so deploys emitting events from the same context then conflict
+/// with each other. Available from protocol version 2.
+pub fn emit_event(data: &[u8]) {
+    unsafe { ext_ffi::emit_event(data.as_ptr(), data.len()) }
+}
+
+/// Returns the recent events of the account or contract under `key`, oldest first. Empty if the
+/// engine keeps no event logs. Available from protocol version 2.
+pub fn read_event_log(key: &Key) -> Vec<Vec<u8>> {
+    let (key_ptr, key_size, _bytes) = to_ptr(key);
+    let log_size = unsafe { ext_ffi::read_event_log(key_ptr, key_size) };
+    let log_ptr = alloc_bytes(log_size);
+    let log_bytes = unsafe {
+        ext_ffi::get_read(log_ptr);
+        Vec::from_raw_parts(log_ptr, log_size, log_size)
+    };
+    deserialize(&log_bytes).unwrap()
+}
+
 /// Return `t` to the host, terminating the currently running module.
 /// Note this function is only relevant to contracts stored on chain which
 /// return a value to their caller. The return value of a directly deployed
@@ -298,6 +569,16 @@ pub fn call_contract<A: ArgsParser, T: FromBytes>(
     args: &A,
     extra_urefs: &Vec<Key>,
 ) -> T {
+    deserialize(&call_contract_raw(c_ptr, args, extra_urefs)).unwrap()
+}
+
+/// Calls the given contract, returning the bytes it returned unparsed.
+#[allow(clippy::ptr_arg)]
+fn call_contract_raw<A: ArgsParser>(
+    c_ptr: ContractPointer,
+    args: &A,
+    extra_urefs: &Vec<Key>,
+) -> Vec<u8> {
     let contract_key: Key = c_ptr.into();
     let (key_ptr, key_size, _bytes1) = to_ptr(&contract_key);
     let (args_ptr, args_size, _bytes2) = ArgsParser::parse(args).map(|args| to_ptr(&args)).unwrap();
@@ -308,11 +589,90 @@ pub fn call_contract<A: ArgsParser, T: FromBytes>(
         )
     };
     let res_ptr = alloc_bytes(res_size);
+    unsafe {
+        ext_ffi::get_call_result(res_ptr);
+        Vec::from_raw_parts(res_ptr, res_size, res_size)
+    }
+}
+
+/// Like `ret`, but the value is returned as a `Value`, whose serialization is tagged with its
+/// type, so that callers using `call_contract_typed` can check the type before using it.
+#[allow(clippy::ptr_arg)]
+pub fn ret_typed<T: Into<Value>>(t: T, extra_urefs: &Vec<URef>) -> ! {
+    ret(&t.into(), extra_urefs)
+}
+
+/// The contract called with `call_contract_typed` didn't return a value of the expected type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypedCallError {
+    /// The returned bytes aren't a serialized `Value`, e.g. the contract returned with `ret`.
+    Deserialization(bytesrepr::Error),
+    /// The contract returned a value of another type.
+    TypeMismatch {
+        /// Type of the returned value, see `Value::type_string`.
+        found: String,
+    },
+}
+
+/// Like `call_contract`, for contracts returning with `ret_typed`. Fails rather than
+/// misinterpreting the returned bytes if the contract returned a value of another type.
+#[allow(clippy::ptr_arg)]
+pub fn call_contract_typed<A: ArgsParser, T: TryFrom<Value>>(
+    c_ptr: ContractPointer,
+    args: &A,
+    extra_urefs: &Vec<Key>,
+) -> Result<T, TypedCallError> {
+    let res_bytes: Vec<u8> = call_contract_raw(c_ptr, args, extra_urefs);
+    typed_return(&res_bytes)
+}
+
+/// Interprets the bytes returned by a contract which returned with `ret_typed`.
+fn typed_return<T: TryFrom<Value>>(bytes: &[u8]) -> Result<T, TypedCallError> {
+    let value: Value = deserialize(bytes).map_err(TypedCallError::Deserialization)?;
+    let found = value.type_string();
+    T::try_from(value).map_err(|_| TypedCallError::TypeMismatch { found })
+}
+
+/// The contract called with `call_contract_with_gas` ran out of its gas allowance.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SubcallOutOfGas;
+
+/// Like `call_contract`, but the called contract can spend at most `gas_allowance`. If it
+/// runs out of it, its effects are discarded, the whole allowance is charged and
+/// `SubcallOutOfGas` is returned, so that the caller can carry on. Keeping the changes made so
+/// far to restore them costs the caller `memcpy` gas per byte changed.
+#[allow(clippy::ptr_arg)]
+pub fn call_contract_with_gas<A: ArgsParser, T: FromBytes>(
+    c_ptr: ContractPointer,
+    args: &A,
+    extra_urefs: &Vec<Key>,
+    gas_allowance: u64,
+) -> Result<T, SubcallOutOfGas> {
+    let contract_key: Key = c_ptr.into();
+    let (key_ptr, key_size, _bytes1) = to_ptr(&contract_key);
+    let (args_ptr, args_size, _bytes2) = ArgsParser::parse(args).map(|args| to_ptr(&args)).unwrap();
+    let (urefs_ptr, urefs_size, _bytes3) = to_ptr(extra_urefs);
+    let result = unsafe {
+        ext_ffi::call_contract_with_gas(
+            key_ptr,
+            key_size,
+            args_ptr,
+            args_size,
+            urefs_ptr,
+            urefs_size,
+            gas_allowance,
+        )
+    };
+    if result < 0 {
+        return Err(SubcallOutOfGas);
+    }
+    let res_size = result as usize;
+    let res_ptr = alloc_bytes(res_size);
     let res_bytes = unsafe {
         ext_ffi::get_call_result(res_ptr);
         Vec::from_raw_parts(res_ptr, res_size, res_size)
     };
-    deserialize(&res_bytes).unwrap()
+    Ok(deserialize(&res_bytes).unwrap())
 }
 
 /// Stops execution of a contract and reverts execution effects
@@ -370,6 +730,43 @@ pub fn set_action_threshold(
     }
 }
 
+/// Adds a key allowed to recover the account once it has been inactive for the
+/// account's inactivity period limit.
+pub fn add_recovery_key(public_key: PublicKey, weight: Weight) -> Result<(), AddKeyFailure> {
+    let (public_key_ptr, _public_key_size, _bytes) = to_ptr(&public_key);
+    let result = unsafe { ext_ffi::add_recovery_key(public_key_ptr, weight.value().into()) };
+    match result {
+        d if d == 0 => Ok(()),
+        d => Err(AddKeyFailure::from(d)),
+    }
+}
+
+/// Removes a public key from recovery keys on an account
+pub fn remove_recovery_key(public_key: PublicKey) -> Result<(), RemoveKeyFailure> {
+    let (public_key_ptr, _public_key_size, _bytes) = to_ptr(&public_key);
+    let result = unsafe { ext_ffi::remove_recovery_key(public_key_ptr) };
+    match result {
+        d if d == 0 => Ok(()),
+        d => Err(RemoveKeyFailure::from(d)),
+    }
+}
+
+/// Replaces the associated keys of the inactive `account` with `new_keys`. The calling
+/// account has to be one of `account`'s recovery keys.
+pub fn recover_account(
+    account: PublicKey,
+    new_keys: &AssociatedKeys,
+) -> Result<(), RecoveryFailure> {
+    let (account_ptr, account_size, _account_bytes) = to_ptr(&account);
+    let (keys_ptr, keys_size, _keys_bytes) = to_ptr(new_keys);
+    let result =
+        unsafe { ext_ffi::recover_account(account_ptr, account_size, keys_ptr, keys_size) };
+    match result {
+        d if d == 0 => Ok(()),
+        d => Err(RecoveryFailure::from(d)),
+    }
+}
+
 pub fn create_purse() -> PurseId {
     let purse_id_ptr = alloc_bytes(PURSE_ID_SIZE_SERIALIZED);
     unsafe {
@@ -514,3 +911,145 @@ pub fn transfer_from_purse_to_purse(
     .try_into()
     .expect("Should parse result")
 }
+
+/// Pays `amount` of tokens from `source` purse as rent of the value under `key`, extending the
+/// time it's kept in proportion to its size. Fails if the engine doesn't charge rent or there is
+/// no value under `key`. Available from protocol version 2.
+pub fn pay_rent(key: &Key, source: PurseId, amount: U512) -> PurseTransferResult {
+    let (key_ptr, key_size, _bytes) = to_ptr(key);
+    let (source_ptr, source_size, _bytes) = to_ptr(&source);
+    let (amount_ptr, amount_size, _bytes) = to_ptr(&amount);
+    unsafe {
+        ext_ffi::pay_rent(
+            key_ptr,
+            key_size,
+            source_ptr,
+            source_size,
+            amount_ptr,
+            amount_size,
+        )
+    }
+    .try_into()
+    .expect("Should parse result")
+}
+
+/// Returns the balance of `purse`, or `None` if the mint doesn't know it. Holding READ rights on
+/// the purse is enough, so a deposit can be checked without the right to withdraw from it.
+pub fn get_balance(purse: PurseId) -> Option<U512> {
+    let (purse_ptr, purse_size, _bytes) = to_ptr(&purse);
+    let value_size = unsafe { ext_ffi::get_balance(purse_ptr, purse_size) };
+    let value_ptr = alloc_bytes(value_size);
+    let value_bytes = unsafe {
+        ext_ffi::get_read(value_ptr);
+        Vec::from_raw_parts(value_ptr, value_size, value_size)
+    };
+    deserialize(&value_bytes).unwrap()
+}
+
+/// Returns the total supply of tokens, or `None` if the mint doesn't track it.
+pub fn get_total_supply() -> Option<U512> {
+    let value_size = unsafe { ext_ffi::get_total_supply() };
+    let value_ptr = alloc_bytes(value_size);
+    let value_bytes = unsafe {
+        ext_ffi::get_read(value_ptr);
+        Vec::from_raw_parts(value_ptr, value_size, value_size)
+    };
+    deserialize(&value_bytes).unwrap()
+}
+
+/// Runs `precompile` natively on `input`, returning its raw output.
+pub fn call_precompile(precompile: Precompile, input: &[u8]) -> Vec<u8> {
+    let output_size =
+        unsafe { ext_ffi::call_precompile(precompile as u32, input.as_ptr(), input.len()) };
+    let output_ptr = alloc_bytes(output_size);
+    unsafe {
+        ext_ffi::get_read(output_ptr);
+        Vec::from_raw_parts(output_ptr, output_size, output_size)
+    }
+}
+
+/// Returns the 32-byte BLAKE2b digest of `input`, computed by the host.
+pub fn blake2b256(input: &[u8]) -> [u8; 32] {
+    let output = call_precompile(Precompile::Blake2b256, input);
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(&output);
+    digest
+}
+
+/// Applies one of the checked `U512` arithmetic precompiles to `lhs` and `rhs`, returning
+/// `None` on overflow or division by zero.
+pub fn u512_checked_op(precompile: Precompile, lhs: U512, rhs: U512) -> Option<U512> {
+    let mut input = lhs.to_bytes().unwrap();
+    input.extend(rhs.to_bytes().unwrap());
+    deserialize(&call_precompile(precompile, &input)).unwrap()
+}
+
+/// Returns whether `signature` is a valid Ed25519 signature of `message` by `public_key`,
+/// checked by the host.
+pub fn ed25519_verify(public_key: PublicKey, signature: &[u8; 64], message: &[u8]) -> bool {
+    let mut input = public_key.value().to_vec();
+    input.extend_from_slice(signature);
+    input.extend_from_slice(message);
+    call_precompile(Precompile::Ed25519Verify, &input) == [1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{local_key_bytes, local_value, typed_return, TypedCallError};
+    use crate::bytesrepr::{self, FromBytes, ToBytes};
+    use crate::value::Value;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn typed_return_should_check_the_returned_value() {
+        let bytes = Value::Int32(7).to_bytes().unwrap();
+        assert_eq!(typed_return::<i32>(&bytes), Ok(7));
+        assert_eq!(
+            typed_return::<String>(&bytes),
+            Err(TypedCallError::TypeMismatch {
+                found: Value::Int32(7).type_string()
+            })
+        );
+    }
+
+    #[test]
+    fn typed_return_should_fail_on_untyped_bytes() {
+        // A contract returning with `ret` rather than `ret_typed`, whose first byte isn't a
+        // `Value` tag.
+        let bytes = u64::max_value().to_bytes().unwrap();
+        match typed_return::<i32>(&bytes) {
+            Err(TypedCallError::Deserialization(_)) => (),
+            other => panic!("expected a deserialization error, got {:?}", other),
+        }
+        assert_eq!(
+            typed_return::<i32>(&[]),
+            Err(TypedCallError::Deserialization(
+                bytesrepr::Error::EarlyEndOfStream
+            ))
+        );
+    }
+
+    #[test]
+    fn local_key_bytes_should_be_length_prefixed() {
+        // Which is why `read_local` and `read_local_bytes` don't address the same entries for
+        // the same key.
+        let mut expected = 5u32.to_bytes().unwrap();
+        expected.extend_from_slice(b"count");
+        assert_eq!(local_key_bytes("count"), expected);
+
+        let mut expected = 32u32.to_bytes().unwrap();
+        expected.extend_from_slice(&[7u8; 32]);
+        assert_eq!(local_key_bytes([7u8; 32]), expected);
+    }
+
+    #[test]
+    fn local_value_should_round_trip() {
+        // `write_local_bytes` stores the serialized `Value`, which `read_local_bytes` converts
+        // back.
+        let bytes = Value::from(vec![1u8, 2, 3]).to_bytes().unwrap();
+        let (value, rem) = Value::from_bytes(&bytes).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(local_value::<Vec<u8>>(value), vec![1u8, 2, 3]);
+    }
+}
@@ -38,6 +38,12 @@ impl<T> UPointer<T> {
             Err(AccessRightsError::NoAccessRights)
         }
     }
+
+    /// Returns a pointer to the same value keeping only those of its access rights which are
+    /// also in `access_rights`, e.g. to pass a READ only pointer to another contract.
+    pub fn restrict(self, access_rights: AccessRights) -> UPointer<T> {
+        UPointer(self.0, self.1 & access_rights, PhantomData)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
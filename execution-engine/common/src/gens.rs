@@ -7,7 +7,7 @@ use crate::value::account::{
 use crate::value::*;
 use alloc::collections::BTreeMap;
 use alloc::string::String;
-use proptest::collection::{btree_map, vec};
+use proptest::collection::{btree_map, vec, SizeRange};
 use proptest::prelude::*;
 use proptest::{array, bits, option};
 
@@ -19,7 +19,7 @@ pub fn u8_slice_32() -> impl Strategy<Value = [u8; 32]> {
     })
 }
 
-pub fn uref_map_arb(depth: usize) -> impl Strategy<Value = BTreeMap<String, Key>> {
+pub fn uref_map_arb(depth: impl Into<SizeRange>) -> impl Strategy<Value = BTreeMap<String, Key>> {
     btree_map("\\PC*", key_arb(), depth)
 }
 
@@ -35,6 +35,12 @@ pub fn access_rights_arb() -> impl Strategy<Value = AccessRights> {
     ]
 }
 
+/// Every combination of access rights, including none at all, which is what restricting a
+/// `URef` to rights it doesn't have gives.
+pub fn any_access_rights_arb() -> impl Strategy<Value = AccessRights> {
+    (0u8..8).prop_map(AccessRights::from_bits_truncate)
+}
+
 pub fn uref_arb() -> impl Strategy<Value = URef> {
     (
         array::uniform32(bits::u8::ANY),
@@ -60,7 +66,7 @@ pub fn weight_arb() -> impl Strategy<Value = Weight> {
     any::<u8>().prop_map(Weight::new)
 }
 
-pub fn associated_keys_arb(size: usize) -> impl Strategy<Value = AssociatedKeys> {
+pub fn associated_keys_arb(size: impl Into<SizeRange>) -> impl Strategy<Value = AssociatedKeys> {
     proptest::collection::btree_map(public_key_arb(), weight_arb(), size).prop_map(|keys| {
         let mut associated_keys = AssociatedKeys::empty();
         keys.into_iter().for_each(|(k, v)| {
@@ -70,23 +76,48 @@ pub fn associated_keys_arb(size: usize) -> impl Strategy<Value = AssociatedKeys>
     })
 }
 
+/// Thresholds with a deployment threshold no higher than the key management one.
 pub fn action_threshold_arb() -> impl Strategy<Value = ActionThresholds> {
-    Just(Default::default())
+    any::<u8>()
+        .prop_flat_map(|deployment| (Just(deployment), deployment..=u8::max_value()))
+        .prop_map(|(deployment, key_management)| {
+            let mut thresholds = ActionThresholds::default();
+            thresholds
+                .set_key_management_threshold(Weight::new(u8::max_value()))
+                .unwrap();
+            thresholds
+                .set_deployment_threshold(Weight::new(deployment))
+                .unwrap();
+            thresholds
+                .set_key_management_threshold(Weight::new(key_management))
+                .unwrap();
+            thresholds
+        })
 }
 
 pub fn account_activity_arb() -> impl Strategy<Value = AccountActivity> {
-    Just(AccountActivity::new(BlockTime(1), BlockTime(1000)))
+    (any::<u64>(), any::<u64>(), any::<u64>()).prop_map(
+        |(key_management_last_used, deployment_last_used, inactivity_period_limit)| {
+            let mut account_activity = AccountActivity::new(
+                BlockTime(key_management_last_used),
+                BlockTime(inactivity_period_limit),
+            );
+            account_activity.update_deployment_last_used(BlockTime(deployment_last_used));
+            account_activity
+        },
+    )
 }
 
 prop_compose! {
     pub fn account_arb()(
         pub_key in u8_slice_32(),
         nonce in any::<u64>(),
-        urefs in uref_map_arb(3),
+        urefs in uref_map_arb(0..50),
         purse_id in uref_arb(),
         thresholds in action_threshold_arb(),
         account_activity in account_activity_arb(),
-        mut associated_keys in associated_keys_arb(MAX_KEYS - 1),
+        mut associated_keys in associated_keys_arb(0..MAX_KEYS),
+        recovery_keys in associated_keys_arb(0..=MAX_KEYS),
     ) -> Account {
             let purse_id = PurseId::new(purse_id);
             associated_keys.add_key(pub_key.into(), Weight::new(1)).unwrap();
@@ -98,17 +129,30 @@ prop_compose! {
                 associated_keys.clone(),
                 thresholds.clone(),
                 account_activity.clone(),
+                recovery_keys.clone(),
             )
     }
 }
 
 pub fn contract_arb() -> impl Strategy<Value = Contract> {
-    any::<u64>().prop_flat_map(move |u64arb| {
-        uref_map_arb(20).prop_flat_map(move |urefs| {
-            vec(any::<u8>(), 1..1000)
-                .prop_map(move |body| Contract::new(body, urefs.clone(), u64arb))
+    contract_with_urefs_arb(20)
+}
+
+/// Contracts with thousands of named keys.
+pub fn large_contract_arb() -> impl Strategy<Value = Contract> {
+    contract_with_urefs_arb(1000..3000)
+}
+
+pub fn contract_with_urefs_arb(urefs: impl Into<SizeRange>) -> impl Strategy<Value = Contract> {
+    (
+        any::<u64>(),
+        uref_map_arb(urefs),
+        vec(any::<u8>(), 1..1000),
+        vec(uref_arb(), 0..3),
+    )
+        .prop_map(|(protocol_version, urefs, body, access_groups)| {
+            Contract::new(body, urefs, protocol_version).with_access_groups(access_groups)
         })
-    })
 }
 
 pub fn u128_arb() -> impl Strategy<Value = U128> {
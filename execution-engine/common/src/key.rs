@@ -6,7 +6,7 @@ use blake2::VarBlake2b;
 use crate::alloc::vec::Vec;
 use crate::bytesrepr::{Error, FromBytes, ToBytes, N32, U32_SIZE};
 use crate::contract_api::pointers::*;
-use crate::uref::{URef, UREF_SIZE_SERIALIZED};
+use crate::uref::{AccessRights, URef, UREF_SIZE_SERIALIZED};
 
 const ACCOUNT_ID: u8 = 0;
 const HASH_ID: u8 = 1;
@@ -49,6 +49,17 @@ impl Key {
     }
 }
 
+/// Hash of the contract stored by `account` under `contract_name` with `store_function_at_hash`,
+/// which clients can compute before the contract is deployed.
+pub fn contract_address(account: [u8; 32], contract_name: &str) -> [u8; 32] {
+    let bytes_to_hash: Vec<u8> = account
+        .iter()
+        .chain(contract_name.as_bytes().iter())
+        .cloned()
+        .collect();
+    hash(&bytes_to_hash)
+}
+
 // There is no impl LowerHex for neither [u8; 32] nor &[u8] in std.
 // I can't impl them b/c they're not living in current crate.
 /// Creates a hex string from [u8; 32] table.
@@ -65,7 +76,7 @@ impl core::fmt::Display for Key {
         match self {
             Key::Account(addr) => write!(f, "Key::Account({})", addr_to_hex(addr)),
             Key::Hash(addr) => write!(f, "Key::Hash({})", addr_to_hex(addr)),
-            Key::URef(uref) => write!(f, "Key::{}", uref), // Display impl for URef will append URef(…).
+            Key::URef(uref) => write!(f, "Key::URef({})", uref),
             Key::Local(hash) => write!(f, "Key::Local({})", addr_to_hex(hash)),
         }
     }
@@ -117,6 +128,15 @@ impl Key {
             _ => None,
         }
     }
+
+    /// Restricts the access rights of a URef key, see [`URef::restrict`]. Other keys are
+    /// returned unchanged.
+    pub fn restrict(self, access_rights: AccessRights) -> Key {
+        match self {
+            Key::URef(uref) => Key::URef(uref.restrict(access_rights)),
+            other => other,
+        }
+    }
 }
 
 impl From<URef> for Key {
@@ -278,7 +298,7 @@ mod tests {
         let uref_key = Key::URef(URef::new(addr_array, AccessRights::READ));
         assert_eq!(
             format!("{}", uref_key),
-            format!("Key::URef({}, READ)", expected_hash)
+            format!("Key::URef(uref-{}-001)", expected_hash)
         );
         let hash_key = Key::Hash(addr_array);
         assert_eq!(
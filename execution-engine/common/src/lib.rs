@@ -27,10 +27,13 @@ extern crate proptest;
 pub static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 pub mod bytesrepr;
+pub mod chain_parameters;
 pub mod contract_api;
 #[cfg(any(test, feature = "gens"))]
 pub mod gens;
 pub mod key;
+pub mod pos_parameters;
+pub mod precompile;
 #[cfg(any(test, feature = "gens"))]
 pub mod test_utils;
 pub mod uref;
@@ -40,7 +43,27 @@ mod ext_ffi {
     extern "C" {
         pub fn read_value(key_ptr: *const u8, key_size: usize) -> usize;
         pub fn read_value_local(key_ptr: *const u8, key_size: usize) -> usize;
-        pub fn get_read(value_ptr: *mut u8); //can only be called after `read_value` or `read_value_local`
+        pub fn query_state(
+            key_ptr: *const u8,
+            key_size: usize,
+            path_ptr: *const u8,
+            path_size: usize,
+        ) -> usize;
+        pub fn dictionary_get(
+            uref_ptr: *const u8,
+            uref_size: usize,
+            item_key_ptr: *const u8,
+            item_key_size: usize,
+        ) -> usize;
+        pub fn dictionary_put(
+            uref_ptr: *const u8,
+            uref_size: usize,
+            item_key_ptr: *const u8,
+            item_key_size: usize,
+            value_ptr: *const u8,
+            value_size: usize,
+        );
+        pub fn get_read(value_ptr: *mut u8); //can only be called after `read_value`, `read_value_local`, `query_state`, `dictionary_get`, `get_balance`, `get_total_supply`, `call_precompile`, `named_keys_page` or `read_event_log`
         pub fn write(key_ptr: *const u8, key_size: usize, value_ptr: *const u8, value_size: usize);
         pub fn write_local(
             key_ptr: *const u8,
@@ -59,6 +82,25 @@ mod ext_ffi {
             extra_urefs_size: usize,
             hash_ptr: *const u8,
         );
+        pub fn store_function_with_access_groups(
+            value_ptr: *const u8,
+            value_size: usize,
+            extra_urefs_ptr: *const u8,
+            extra_urefs_size: usize,
+            access_groups_ptr: *const u8,
+            access_groups_size: usize,
+            hash_ptr: *const u8,
+        );
+        // Returns 1 instead of 0 if a contract is already stored at the hash
+        pub fn store_function_at_hash(
+            value_ptr: *const u8,
+            value_size: usize,
+            extra_urefs_ptr: *const u8,
+            extra_urefs_size: usize,
+            contract_name_ptr: *const u8,
+            contract_name_size: usize,
+            hash_ptr: *const u8,
+        ) -> i32;
         pub fn serialize_known_urefs() -> usize;
         // Can only be called after `serialize_known_urefs`.
         pub fn list_known_urefs(dest_ptr: *mut u8);
@@ -80,6 +122,16 @@ mod ext_ffi {
             extra_urefs_ptr: *const u8,
             extra_urefs_size: usize,
         ) -> usize;
+        // Returns -1 instead of the size of the result if the callee ran out of `gas_allowance`
+        pub fn call_contract_with_gas(
+            key_ptr: *const u8,
+            key_size: usize,
+            args_ptr: *const u8,
+            args_size: usize,
+            extra_urefs_ptr: *const u8,
+            extra_urefs_size: usize,
+            gas_allowance: u64,
+        ) -> i32;
         pub fn get_call_result(res_ptr: *mut u8); //can only be called after `call_contract`
         pub fn get_uref(name_ptr: *const u8, name_size: usize) -> usize;
         pub fn has_uref_name(name_ptr: *const u8, name_size: usize) -> i32;
@@ -116,6 +168,29 @@ mod ext_ffi {
             amount_ptr: *const u8,
             amount_size: usize,
         ) -> i32;
+        pub fn get_balance(purse_ptr: *const u8, purse_size: usize) -> usize;
+        pub fn get_total_supply() -> usize;
+        pub fn add_recovery_key(public_key_ptr: *const u8, weight: i32) -> i32;
+        pub fn remove_recovery_key(public_key_ptr: *const u8) -> i32;
+        pub fn recover_account(
+            account_ptr: *const u8,
+            account_size: usize,
+            keys_ptr: *const u8,
+            keys_size: usize,
+        ) -> i32;
+        pub fn call_precompile(id: u32, input_ptr: *const u8, input_size: usize) -> usize;
+        pub fn get_chain_parameters(dest_ptr: *const u8);
+        pub fn named_keys_page(after_ptr: *const u8, after_size: usize, limit: u32) -> usize;
+        pub fn emit_event(data_ptr: *const u8, data_size: usize);
+        pub fn read_event_log(key_ptr: *const u8, key_size: usize) -> usize;
+        pub fn pay_rent(
+            key_ptr: *const u8,
+            key_size: usize,
+            source_ptr: *const u8,
+            source_size: usize,
+            amount_ptr: *const u8,
+            amount_size: usize,
+        ) -> i32;
     }
 }
 
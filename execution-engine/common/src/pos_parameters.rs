@@ -0,0 +1,29 @@
+//! Parameters of the Proof-of-Stake contract, shared with the node's checks of staking requests.
+
+use crate::value::U512;
+
+/// The time from a bonding request until the bond becomes effective and part of the stake.
+pub const BOND_DELAY: u64 = 0;
+/// The time from an unbonding request until the stakes are paid out.
+pub const UNBOND_DELAY: u64 = 0;
+/// The maximum number of pending bonding requests.
+pub const MAX_BOND_LEN: usize = 100;
+/// The maximum number of pending unbonding requests.
+pub const MAX_UNBOND_LEN: usize = 1000;
+/// The maximum difference between the largest and the smallest stakes.
+// TODO: Should this be a percentage instead?
+// TODO: Pick a reasonable value.
+pub const MAX_SPREAD: U512 = U512::MAX;
+/// The maximum increase of stakes in a single bonding request.
+pub const MAX_INCREASE: U512 = U512::MAX;
+/// The maximum decrease of stakes in a single unbonding request.
+pub const MAX_DECREASE: U512 = U512::MAX;
+/// The maximum increase of stakes in millionths of the total stakes in a single bonding request.
+pub const MAX_REL_INCREASE: u64 = 1_000_000_000;
+/// The maximum decrease of stakes in millionths of the total stakes in a single unbonding request.
+pub const MAX_REL_DECREASE: u64 = 900_000;
+
+/// The local key under which the contract stores its bonding queue.
+pub const BONDING_QUEUE_KEY: u8 = 1;
+/// The local key under which the contract stores its unbonding queue.
+pub const UNBONDING_QUEUE_KEY: u8 = 2;
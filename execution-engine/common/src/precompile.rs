@@ -0,0 +1,48 @@
+/// Operations run natively by the host, at a fixed gas cost, instead of as interpreted wasm.
+///
+/// The discriminant is the id passed to the `call_precompile` host function.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precompile {
+    /// 32-byte BLAKE2b digest of the raw input bytes.
+    Blake2b256 = 0,
+    /// The following take two serialized `U512`s and output a serialized `Option<U512>`, which
+    /// is `None` on overflow or division by zero.
+    U512CheckedAdd = 1,
+    U512CheckedSub = 2,
+    U512CheckedMul = 3,
+    U512CheckedDiv = 4,
+    U512CheckedRem = 5,
+    /// Takes a 32-byte Ed25519 public key, followed by a 64-byte signature and the signed
+    /// message, and outputs a single byte which is 1 if the signature is valid and 0 otherwise.
+    Ed25519Verify = 6,
+}
+
+impl Precompile {
+    pub fn from_u32(id: u32) -> Option<Precompile> {
+        match id {
+            0 => Some(Precompile::Blake2b256),
+            1 => Some(Precompile::U512CheckedAdd),
+            2 => Some(Precompile::U512CheckedSub),
+            3 => Some(Precompile::U512CheckedMul),
+            4 => Some(Precompile::U512CheckedDiv),
+            5 => Some(Precompile::U512CheckedRem),
+            6 => Some(Precompile::Ed25519Verify),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Precompile;
+
+    #[test]
+    fn should_roundtrip_ids() {
+        for id in 0..7 {
+            let precompile = Precompile::from_u32(id).unwrap();
+            assert_eq!(precompile as u32, id);
+        }
+        assert_eq!(Precompile::from_u32(7), None);
+    }
+}
@@ -1,3 +1,5 @@
+use core::str::FromStr;
+
 use bitflags;
 
 use crate::alloc::string::String;
@@ -8,6 +10,7 @@ use crate::contract_api::pointers::UPointer;
 
 const UREF_ADDR_SIZE: usize = 32;
 const ACCESS_RIGHTS_SIZE: usize = 1;
+const UREF_FORMATTED_PREFIX: &str = "uref-";
 pub const UREF_SIZE_SERIALIZED: usize =
     U32_SIZE + UREF_ADDR_SIZE + OPTION_SIZE + ACCESS_RIGHTS_SIZE;
 
@@ -73,23 +76,88 @@ impl bytesrepr::FromBytes for AccessRights {
 #[derive(Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct URef([u8; UREF_ADDR_SIZE], Option<AccessRights>);
 
+/// The canonical form, `uref-<address in lower case hex>-<access rights as 3 octal digits>`,
+/// e.g. `uref-0101…01-007` for READ_ADD_WRITE. URefs without access rights have no suffix.
 impl core::fmt::Display for URef {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        let addr = self.addr();
-        let access_rights_o = self.access_rights();
-        if let Some(access_rights) = access_rights_o {
-            write!(
+        let addr = super::key::addr_to_hex(&self.addr());
+        match self.access_rights() {
+            Some(access_rights) => write!(
                 f,
-                "URef({}, {})",
-                super::key::addr_to_hex(&addr),
-                access_rights
-            )
-        } else {
-            write!(f, "URef({}, None)", super::key::addr_to_hex(&addr))
+                "{}{}-{:03o}",
+                UREF_FORMATTED_PREFIX,
+                addr,
+                access_rights.bits()
+            ),
+            None => write!(f, "{}{}", UREF_FORMATTED_PREFIX, addr),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum URefParseError {
+    /// The string doesn't start with `uref-`.
+    InvalidPrefix,
+    /// The address isn't 64 hex digits.
+    InvalidAddress,
+    /// The suffix isn't `-` followed by 3 octal digits of valid access rights.
+    InvalidAccessRights,
+}
+
+impl core::fmt::Display for URefParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            URefParseError::InvalidPrefix => write!(f, "expected a uref starting with uref-"),
+            URefParseError::InvalidAddress => write!(f, "invalid uref address"),
+            URefParseError::InvalidAccessRights => write!(f, "invalid uref access rights"),
         }
     }
 }
 
+/// Value of the ASCII digit `byte` in `radix`, unlike `u8::from_str_radix` without signs.
+fn digit(byte: u8, radix: u32) -> Option<u8> {
+    (byte as char).to_digit(radix).map(|digit| digit as u8)
+}
+
+/// Parses the canonical form printed by `Display`. Upper case hex is accepted too.
+impl FromStr for URef {
+    type Err = URefParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        if !input.starts_with(UREF_FORMATTED_PREFIX) {
+            return Err(URefParseError::InvalidPrefix);
+        }
+        let remainder = &input[UREF_FORMATTED_PREFIX.len()..];
+        let hex_size = 2 * UREF_ADDR_SIZE;
+        if remainder.len() < hex_size || !remainder.is_char_boundary(hex_size) {
+            return Err(URefParseError::InvalidAddress);
+        }
+        let (hex, suffix) = remainder.split_at(hex_size);
+        let mut addr = [0u8; UREF_ADDR_SIZE];
+        for (byte, digits) in addr.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            match (digit(digits[0], 16), digit(digits[1], 16)) {
+                (Some(high), Some(low)) => *byte = high << 4 | low,
+                _ => return Err(URefParseError::InvalidAddress),
+            }
+        }
+        if suffix.is_empty() {
+            return Ok(URef(addr, None));
+        }
+        let suffix = suffix.as_bytes();
+        if suffix.len() != 4 || suffix[0] != b'-' {
+            return Err(URefParseError::InvalidAccessRights);
+        }
+        let access_rights = suffix[1..]
+            .iter()
+            .try_fold(0u8, |bits, &byte| {
+                digit(byte, 8).and_then(|digit| bits.checked_mul(8)?.checked_add(digit))
+            })
+            .and_then(AccessRights::from_bits)
+            .ok_or(URefParseError::InvalidAccessRights)?;
+        Ok(URef(addr, Some(access_rights)))
+    }
+}
+
 impl core::fmt::Debug for URef {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "{}", self)
@@ -126,6 +194,15 @@ impl URef {
         URef(self.0, None)
     }
 
+    /// Returns a copy of this URef keeping only those of its access rights which are also in
+    /// `access_rights`, e.g. to share a READ only reference with another contract.
+    ///
+    /// Rights can't be extended this way; the runtime rejects URefs carrying more rights than
+    /// the context was given as forged.
+    pub fn restrict(self, access_rights: AccessRights) -> Self {
+        URef(self.0, self.1.map(|rights| rights & access_rights))
+    }
+
     pub fn is_readable(self) -> bool {
         if let Some(access_rights) = self.1 {
             access_rights.is_readable()
@@ -150,8 +227,14 @@ impl URef {
         }
     }
 
+    /// The label of this URef in named keys, e.g. of the purses created at genesis. It's stored
+    /// in the global state, so unlike `Display` it can't change.
     pub fn as_string(&self) -> String {
-        format!("{:?}", self)
+        let addr = super::key::addr_to_hex(&self.addr());
+        match self.access_rights() {
+            Some(access_rights) => format!("URef({}, {})", addr, access_rights),
+            None => format!("URef({}, None)", addr),
+        }
     }
 }
 
@@ -217,8 +300,8 @@ mod tests {
 
     use crate::gens;
     use crate::test_utils;
-    use crate::uref::{AccessRights, URef};
-    use alloc::string::String;
+    use crate::uref::{AccessRights, URef, URefParseError};
+    use alloc::string::{String, ToString};
 
     fn test_readable(right: AccessRights, is_true: bool) {
         assert_eq!(right.is_readable(), is_true)
@@ -270,6 +353,84 @@ mod tests {
         fn test_uref(uref in gens::uref_arb()) {
             assert!(test_utils::test_serialization_roundtrip(&uref));
         }
+
+        #[test]
+        fn should_parse_displayed_uref(uref in gens::uref_arb()) {
+            assert_eq!(uref.to_string().parse(), Ok(uref));
+            let without_rights = uref.remove_access_rights();
+            assert_eq!(without_rights.to_string().parse(), Ok(without_rights));
+        }
+    }
+
+    #[test]
+    fn should_display_canonical_uref() {
+        let hex = "01".repeat(32);
+        let uref = URef::new([1u8; 32], AccessRights::READ_ADD_WRITE);
+        assert_eq!(uref.to_string(), format!("uref-{}-007", hex));
+        assert_eq!(
+            URef::new([1u8; 32], AccessRights::READ).to_string(),
+            format!("uref-{}-001", hex)
+        );
+        assert_eq!(
+            uref.remove_access_rights().to_string(),
+            format!("uref-{}", hex)
+        );
+        assert_eq!(format!("uref-{}-007", hex.to_uppercase()).parse(), Ok(uref));
+    }
+
+    #[test]
+    fn should_reject_malformed_urefs() {
+        let hex = "01".repeat(32);
+        let parse = |input: &str| input.parse::<URef>();
+        assert_eq!(
+            parse(&format!("URef({}, READ)", hex)),
+            Err(URefParseError::InvalidPrefix)
+        );
+        assert_eq!(
+            parse(&format!("uref-{}", &hex[1..])),
+            Err(URefParseError::InvalidAddress)
+        );
+        assert_eq!(
+            parse(&format!("uref-{}-007", "0g".repeat(32))),
+            Err(URefParseError::InvalidAddress)
+        );
+        assert_eq!(
+            parse(&format!("uref-{}-010", hex)),
+            Err(URefParseError::InvalidAccessRights)
+        );
+        assert_eq!(
+            parse(&format!("uref-{}-7", hex)),
+            Err(URefParseError::InvalidAccessRights)
+        );
+        assert_eq!(
+            parse(&format!("uref-{}-0007", hex)),
+            Err(URefParseError::InvalidAccessRights)
+        );
+        assert_eq!(
+            parse(&format!("uref-{}-+07", hex)),
+            Err(URefParseError::InvalidAccessRights)
+        );
+    }
+
+    #[test]
+    fn restrict_should_only_remove_rights() {
+        let uref = URef::new([1u8; 32], AccessRights::READ_ADD);
+        assert_eq!(
+            uref.restrict(AccessRights::READ).access_rights(),
+            Some(AccessRights::READ)
+        );
+        assert_eq!(
+            uref.restrict(AccessRights::READ_WRITE).access_rights(),
+            Some(AccessRights::READ)
+        );
+        assert_eq!(
+            uref.restrict(AccessRights::WRITE).access_rights(),
+            Some(AccessRights::empty())
+        );
+        assert_eq!(
+            uref.remove_access_rights().restrict(AccessRights::READ),
+            uref.remove_access_rights()
+        );
     }
 
     #[test]
@@ -219,6 +219,16 @@ impl AccountActivity {
     pub fn inactivity_period_limit(&self) -> BlockTime {
         self.inactivity_period_limit
     }
+
+    /// Tests whether neither deploys nor key management happened for at least the inactivity
+    /// period limit before `current_block_time`, making the account eligible for recovery.
+    pub fn is_inactive(&self, current_block_time: BlockTime) -> bool {
+        let last_used = self
+            .key_management_last_used
+            .0
+            .max(self.deployment_last_used.0);
+        current_block_time.0.saturating_sub(last_used) >= self.inactivity_period_limit.0
+    }
 }
 
 pub const KEY_SIZE: usize = 32;
@@ -376,6 +386,42 @@ impl From<i32> for RemoveKeyFailure {
     }
 }
 
+/// Represents an error that happens when trying to recover an account.
+///
+/// It is represented by `i32` to be easily able to transform this value in an out
+/// through FFI boundaries as a number.
+///
+/// The explicit numbering of the variants is done on purpose and whenever you plan to add
+/// new variant, you should always extend it, and add a variant that does not exist already.
+/// When adding new variants you should also remember to change
+/// `From<i32> for RecoveryFailure`.
+#[derive(Fail, Debug, Eq, PartialEq)]
+#[repr(i32)]
+pub enum RecoveryFailure {
+    #[fail(display = "Unable to recover account because caller is not one of its recovery keys")]
+    NotRecoveryKey = 1,
+    #[fail(display = "Unable to recover account because recovery key weight is below threshold")]
+    InsufficientWeight = 2,
+    #[fail(display = "Unable to recover account before the end of its inactivity period")]
+    AccountActive = 3,
+    #[fail(display = "Unable to recover account because new keys don't meet threshold")]
+    InvalidKeys = 4,
+}
+
+impl From<i32> for RecoveryFailure {
+    fn from(value: i32) -> RecoveryFailure {
+        match value {
+            d if d == RecoveryFailure::NotRecoveryKey as i32 => RecoveryFailure::NotRecoveryKey,
+            d if d == RecoveryFailure::InsufficientWeight as i32 => {
+                RecoveryFailure::InsufficientWeight
+            }
+            d if d == RecoveryFailure::AccountActive as i32 => RecoveryFailure::AccountActive,
+            d if d == RecoveryFailure::InvalidKeys as i32 => RecoveryFailure::InvalidKeys,
+            _ => unreachable!(),
+        }
+    }
+}
+
 #[derive(PartialOrd, Ord, PartialEq, Eq, Clone, Debug)]
 pub struct AssociatedKeys(BTreeMap<PublicKey, Weight>);
 
@@ -420,6 +466,15 @@ impl AssociatedKeys {
     pub fn get_all(&self) -> &BTreeMap<PublicKey, Weight> {
         &self.0
     }
+
+    /// Sum of the weights of all the keys, saturating at the maximum weight.
+    pub fn total_weight(&self) -> Weight {
+        let total = self
+            .0
+            .values()
+            .fold(0u8, |total, weight| total.saturating_add(weight.value()));
+        Weight::new(total)
+    }
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -431,9 +486,12 @@ pub struct Account {
     associated_keys: AssociatedKeys,
     action_thresholds: ActionThresholds,
     account_activity: AccountActivity,
+    /// Keys allowed to replace the associated keys once the account is inactive.
+    recovery_keys: AssociatedKeys,
 }
 
 impl Account {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         public_key: [u8; 32],
         nonce: u64,
@@ -442,6 +500,7 @@ impl Account {
         associated_keys: AssociatedKeys,
         action_thresholds: ActionThresholds,
         account_activity: AccountActivity,
+        recovery_keys: AssociatedKeys,
     ) -> Self {
         Account {
             public_key,
@@ -451,6 +510,7 @@ impl Account {
             associated_keys,
             action_thresholds,
             account_activity,
+            recovery_keys,
         }
     }
 
@@ -472,6 +532,7 @@ impl Account {
             associated_keys,
             action_thresholds,
             account_activity,
+            AssociatedKeys::empty(),
         )
     }
 
@@ -518,6 +579,14 @@ impl Account {
         &self.account_activity
     }
 
+    pub fn get_account_activity_mut(&mut self) -> &mut AccountActivity {
+        &mut self.account_activity
+    }
+
+    pub fn recovery_keys(&self) -> &AssociatedKeys {
+        &self.recovery_keys
+    }
+
     pub fn nonce(&self) -> u64 {
         self.nonce
     }
@@ -550,6 +619,48 @@ impl Account {
         // TODO(mpapierski): Authorized keys check EE-377
         self.action_thresholds.set_threshold(action_type, weight)
     }
+
+    pub fn add_recovery_key(
+        &mut self,
+        public_key: PublicKey,
+        weight: Weight,
+    ) -> Result<(), AddKeyFailure> {
+        self.recovery_keys.add_key(public_key, weight)
+    }
+
+    pub fn remove_recovery_key(&mut self, public_key: PublicKey) -> Result<(), RemoveKeyFailure> {
+        self.recovery_keys.remove_key(&public_key)
+    }
+
+    /// Replaces the associated keys with `new_keys` on behalf of `recovery_key`.
+    ///
+    /// The account has to be inactive at `current_block_time` and the weight of `recovery_key`
+    /// has to meet the key management threshold, as does the total weight of `new_keys` so that
+    /// the recovered account can be managed again. Recovery counts as key management activity.
+    pub fn recover(
+        &mut self,
+        recovery_key: PublicKey,
+        new_keys: AssociatedKeys,
+        current_block_time: BlockTime,
+    ) -> Result<(), RecoveryFailure> {
+        let weight = self
+            .recovery_keys
+            .get(&recovery_key)
+            .ok_or(RecoveryFailure::NotRecoveryKey)?;
+        if weight < self.action_thresholds.key_management() {
+            return Err(RecoveryFailure::InsufficientWeight);
+        }
+        if !self.account_activity.is_inactive(current_block_time) {
+            return Err(RecoveryFailure::AccountActive);
+        }
+        if new_keys.total_weight() < *self.action_thresholds.key_management() {
+            return Err(RecoveryFailure::InvalidKeys);
+        }
+        self.associated_keys = new_keys;
+        self.account_activity
+            .update_key_management_last_used(current_block_time);
+        Ok(())
+    }
 }
 
 impl ToBytes for Weight {
@@ -693,6 +804,8 @@ impl ToBytes for Account {
         let account_activity_size: usize = 3 * (BLOCKTIME_SER_SIZE + U8_SIZE);
         let associated_keys_size =
             self.associated_keys.0.len() * (PUBLIC_KEY_SIZE + WEIGHT_SIZE) + U32_SIZE;
+        let recovery_keys_size =
+            self.recovery_keys.0.len() * (PUBLIC_KEY_SIZE + WEIGHT_SIZE) + U32_SIZE;
         let known_urefs_size = UREF_SIZE * self.known_urefs.len() + U32_SIZE;
         let purse_id_size = UREF_SIZE;
         let serialized_account_size = KEY_SIZE // pub key
@@ -701,7 +814,8 @@ impl ToBytes for Account {
             + purse_id_size
             + associated_keys_size
             + action_thresholds_size
-            + account_activity_size;
+            + account_activity_size
+            + recovery_keys_size;
         if serialized_account_size >= u32::max_value() as usize {
             return Err(Error::OutOfMemoryError);
         }
@@ -713,6 +827,7 @@ impl ToBytes for Account {
         result.append(&mut self.associated_keys.to_bytes()?);
         result.append(&mut self.action_thresholds.to_bytes()?);
         result.append(&mut self.account_activity.to_bytes()?);
+        result.append(&mut self.recovery_keys.to_bytes()?);
         Ok(result)
     }
 }
@@ -726,6 +841,7 @@ impl FromBytes for Account {
         let (associated_keys, rem): (AssociatedKeys, &[u8]) = FromBytes::from_bytes(rem)?;
         let (action_thresholds, rem): (ActionThresholds, &[u8]) = FromBytes::from_bytes(rem)?;
         let (account_activity, rem): (AccountActivity, &[u8]) = FromBytes::from_bytes(rem)?;
+        let (recovery_keys, rem): (AssociatedKeys, &[u8]) = FromBytes::from_bytes(rem)?;
         let purse_id = PurseId::new(purse_id);
         Ok((
             Account {
@@ -736,6 +852,7 @@ impl FromBytes for Account {
                 associated_keys,
                 action_thresholds,
                 account_activity,
+                recovery_keys,
             },
             rem,
         ))
@@ -746,8 +863,8 @@ impl FromBytes for Account {
 mod tests {
     use crate::uref::{AccessRights, URef};
     use crate::value::account::{
-        Account, AccountActivity, AddKeyFailure, AssociatedKeys, BlockTime, PublicKey, PurseId,
-        Weight, KEY_SIZE, MAX_KEYS,
+        Account, AccountActivity, ActionType, AddKeyFailure, AssociatedKeys, BlockTime, PublicKey,
+        PurseId, RecoveryFailure, Weight, KEY_SIZE, MAX_KEYS,
     };
     use alloc::collections::btree_map::BTreeMap;
 
@@ -761,6 +878,7 @@ mod tests {
             AssociatedKeys::new(PublicKey::new([0u8; 32]), Weight::new(1)),
             Default::default(),
             AccountActivity::new(BlockTime(0), BlockTime(0)),
+            AssociatedKeys::empty(),
         );
         assert_eq!(account.nonce(), 0);
         account.increment_nonce();
@@ -811,4 +929,83 @@ mod tests {
         assert!(keys.remove_key(&pk).is_ok());
         assert!(keys.remove_key(&PublicKey([1u8; KEY_SIZE])).is_err());
     }
+
+    fn recoverable_account() -> Account {
+        let mut account = Account::new(
+            [0u8; 32],
+            0,
+            BTreeMap::new(),
+            PurseId::new(URef::new([0u8; 32], AccessRights::READ_ADD_WRITE)),
+            AssociatedKeys::new(PublicKey::new([0u8; 32]), Weight::new(2)),
+            Default::default(),
+            AccountActivity::new(BlockTime(10), BlockTime(100)),
+            AssociatedKeys::empty(),
+        );
+        account
+            .set_action_threshold(ActionType::KeyManagement, Weight::new(2))
+            .unwrap();
+        account
+            .add_recovery_key(PublicKey::new([1u8; 32]), Weight::new(2))
+            .unwrap();
+        account
+            .add_recovery_key(PublicKey::new([2u8; 32]), Weight::new(1))
+            .unwrap();
+        account
+    }
+
+    #[test]
+    fn account_activity_inactive_after_limit() {
+        let mut activity = AccountActivity::new(BlockTime(10), BlockTime(100));
+        assert!(!activity.is_inactive(BlockTime(109)));
+        assert!(activity.is_inactive(BlockTime(110)));
+        activity.update_deployment_last_used(BlockTime(50));
+        assert!(!activity.is_inactive(BlockTime(110)));
+        assert!(activity.is_inactive(BlockTime(150)));
+    }
+
+    #[test]
+    fn recover_replaces_associated_keys() {
+        let mut account = recoverable_account();
+        let new_keys = AssociatedKeys::new(PublicKey::new([3u8; 32]), Weight::new(2));
+        assert!(account
+            .recover(PublicKey::new([1u8; 32]), new_keys.clone(), BlockTime(110))
+            .is_ok());
+        assert_eq!(account.associated_keys(), &new_keys);
+        assert_eq!(
+            account.account_activity().key_management_last_used(),
+            BlockTime(110)
+        );
+        // Recovering resets the inactivity period.
+        assert_eq!(
+            account.recover(PublicKey::new([1u8; 32]), new_keys, BlockTime(111)),
+            Err(RecoveryFailure::AccountActive)
+        );
+    }
+
+    #[test]
+    fn recover_enforces_rules() {
+        let mut account = recoverable_account();
+        let new_keys = AssociatedKeys::new(PublicKey::new([3u8; 32]), Weight::new(2));
+        assert_eq!(
+            account.recover(PublicKey::new([0u8; 32]), new_keys.clone(), BlockTime(110)),
+            Err(RecoveryFailure::NotRecoveryKey)
+        );
+        assert_eq!(
+            account.recover(PublicKey::new([2u8; 32]), new_keys.clone(), BlockTime(110)),
+            Err(RecoveryFailure::InsufficientWeight)
+        );
+        assert_eq!(
+            account.recover(PublicKey::new([1u8; 32]), new_keys, BlockTime(109)),
+            Err(RecoveryFailure::AccountActive)
+        );
+        let light_keys = AssociatedKeys::new(PublicKey::new([3u8; 32]), Weight::new(1));
+        assert_eq!(
+            account.recover(PublicKey::new([1u8; 32]), light_keys, BlockTime(110)),
+            Err(RecoveryFailure::InvalidKeys)
+        );
+        assert_eq!(
+            account.associated_keys(),
+            &AssociatedKeys::new(PublicKey::new([0u8; 32]), Weight::new(2))
+        );
+    }
 }
@@ -1,5 +1,6 @@
 use crate::bytesrepr::{Error, FromBytes, ToBytes, U32_SIZE, U64_SIZE};
 use crate::key::{Key, UREF_SIZE};
+use crate::uref::{URef, UREF_SIZE_SERIALIZED};
 use alloc::collections::btree_map::BTreeMap;
 use alloc::string::String;
 use alloc::vec::Vec;
@@ -9,6 +10,7 @@ pub struct Contract {
     bytes: Vec<u8>,
     known_urefs: BTreeMap<String, Key>,
     protocol_version: u64,
+    access_groups: Vec<URef>,
 }
 
 impl Contract {
@@ -17,9 +19,19 @@ impl Contract {
             bytes,
             known_urefs,
             protocol_version,
+            access_groups: Vec::new(),
         }
     }
 
+    /// Restricts calls to the contract to callers holding at least one of the `access_groups`
+    /// URefs. A contract without access groups can be called by anyone.
+    pub fn with_access_groups(mut self, mut access_groups: Vec<URef>) -> Self {
+        access_groups.sort();
+        access_groups.dedup();
+        self.access_groups = access_groups;
+        self
+    }
+
     pub fn insert_urefs(&mut self, keys: &mut BTreeMap<String, Key>) {
         self.known_urefs.append(keys);
     }
@@ -32,8 +44,13 @@ impl Contract {
         &mut self.known_urefs
     }
 
-    pub fn destructure(self) -> (Vec<u8>, BTreeMap<String, Key>, u64) {
-        (self.bytes, self.known_urefs, self.protocol_version)
+    pub fn destructure(self) -> (Vec<u8>, BTreeMap<String, Key>, u64, Vec<URef>) {
+        (
+            self.bytes,
+            self.known_urefs,
+            self.protocol_version,
+            self.access_groups,
+        )
     }
 
     pub fn bytes(&self) -> &[u8] {
@@ -43,12 +60,19 @@ impl Contract {
     pub fn protocol_version(&self) -> u64 {
         self.protocol_version
     }
+
+    pub fn access_groups(&self) -> &[URef] {
+        &self.access_groups
+    }
 }
 
 impl ToBytes for Contract {
     fn to_bytes(&self) -> Result<Vec<u8>, Error> {
-        if self.bytes.len() + UREF_SIZE * self.known_urefs.len() + U64_SIZE
-            >= u32::max_value() as usize - U32_SIZE * 2
+        if self.bytes.len()
+            + UREF_SIZE * self.known_urefs.len()
+            + U64_SIZE
+            + UREF_SIZE_SERIALIZED * self.access_groups.len()
+            >= u32::max_value() as usize - U32_SIZE * 3
         {
             return Err(Error::OutOfMemoryError);
         }
@@ -56,12 +80,15 @@ impl ToBytes for Contract {
                     self.bytes.len() +                  //size for elements of bytes
                     U32_SIZE +                                 //size for length of known_urefs
                     UREF_SIZE * self.known_urefs.len() + //size for known_urefs elements
-                    U64_SIZE; // size for protocol_version
+                    U64_SIZE +                                 //size for protocol_version
+                    U32_SIZE +                                 //size for length of access_groups
+                    UREF_SIZE_SERIALIZED * self.access_groups.len(); //size for access_groups elements
 
         let mut result = Vec::with_capacity(size);
         result.append(&mut self.bytes.to_bytes()?);
         result.append(&mut self.known_urefs.to_bytes()?);
         result.append(&mut self.protocol_version.to_bytes()?);
+        result.append(&mut self.access_groups.to_bytes()?);
         Ok(result)
     }
 }
@@ -71,13 +98,15 @@ impl FromBytes for Contract {
         let (bytes, rem1): (Vec<u8>, &[u8]) = FromBytes::from_bytes(bytes)?;
         let (known_urefs, rem2): (BTreeMap<String, Key>, &[u8]) = FromBytes::from_bytes(rem1)?;
         let (protocol_version, rem3): (u64, &[u8]) = FromBytes::from_bytes(rem2)?;
+        let (access_groups, rem4): (Vec<URef>, &[u8]) = FromBytes::from_bytes(rem3)?;
         Ok((
             Contract {
                 bytes,
                 known_urefs,
                 protocol_version,
+                access_groups,
             },
-            rem3,
+            rem4,
         ))
     }
 }
@@ -0,0 +1,111 @@
+#![feature(test)]
+extern crate common;
+extern crate execution_engine;
+extern crate shared;
+extern crate storage;
+extern crate test;
+
+use test::black_box;
+use test::Bencher;
+
+use common::key::Key;
+use common::value::Value;
+use execution_engine::meter::heap_meter::HeapSize;
+use execution_engine::tracking_copy::{TrackingCopy, TrackingCopyCache};
+use shared::newtypes::{CorrelationId, Validated};
+use storage::global_state::in_memory::InMemoryGlobalState;
+use storage::global_state::History;
+
+const KEY_COUNT: u8 = 200;
+
+fn key(i: u8) -> Key {
+    Key::Hash([i; 32])
+}
+
+fn value(i: u8) -> Value {
+    Value::ByteArray(vec![i; 128])
+}
+
+fn global_state() -> InMemoryGlobalState {
+    let pairs: Vec<(Key, Value)> = (0..KEY_COUNT).map(|i| (key(i), value(i))).collect();
+    InMemoryGlobalState::from_pairs(CorrelationId::new(), &pairs).unwrap()
+}
+
+#[bench]
+fn cache_insert_reads_with_eviction(b: &mut Bencher) {
+    // Holds only some of the values, so later insertions evict the oldest ones.
+    let max_cache_size = usize::from(KEY_COUNT) * 64;
+    b.iter(|| {
+        let mut cache = TrackingCopyCache::new(max_cache_size, HeapSize);
+        for i in 0..KEY_COUNT {
+            cache.insert_read(key(i), value(i));
+        }
+        cache
+    });
+}
+
+#[bench]
+fn cache_get_hit(b: &mut Bencher) {
+    let mut cache = TrackingCopyCache::new(usize::max_value(), HeapSize);
+    for i in 0..KEY_COUNT {
+        cache.insert_read(key(i), value(i));
+    }
+    let hit = key(KEY_COUNT / 2);
+    b.iter(|| cache.get(black_box(&hit)).cloned());
+}
+
+#[bench]
+fn read_uncached_keys(b: &mut Bencher) {
+    let correlation_id = CorrelationId::new();
+    let state = global_state();
+    let root_hash = state.current_root();
+    b.iter(|| {
+        let mut tracking_copy = TrackingCopy::new(state.checkout(root_hash).unwrap().unwrap());
+        for i in 0..KEY_COUNT {
+            let validated_key = Validated::new(key(i), Validated::valid).unwrap();
+            tracking_copy.read(correlation_id, &validated_key).unwrap();
+        }
+    });
+}
+
+#[bench]
+fn read_cached_key(b: &mut Bencher) {
+    let correlation_id = CorrelationId::new();
+    let state = global_state();
+    let mut tracking_copy =
+        TrackingCopy::new(state.checkout(state.current_root()).unwrap().unwrap());
+    let validated_key = Validated::new(key(0), Validated::valid).unwrap();
+    tracking_copy.read(correlation_id, &validated_key).unwrap();
+    b.iter(|| {
+        tracking_copy
+            .read(correlation_id, black_box(&validated_key))
+            .unwrap()
+    });
+}
+
+#[bench]
+fn write_then_add_keys(b: &mut Bencher) {
+    let correlation_id = CorrelationId::new();
+    let state = global_state();
+    let root_hash = state.current_root();
+    b.iter(|| {
+        let mut tracking_copy = TrackingCopy::new(state.checkout(root_hash).unwrap().unwrap());
+        for i in 0..KEY_COUNT {
+            let counter = key(i);
+            tracking_copy
+                .write(
+                    Validated::new(counter, Validated::valid).unwrap(),
+                    Validated::new(Value::Int32(0), Validated::valid).unwrap(),
+                )
+                .unwrap();
+            tracking_copy
+                .add(
+                    correlation_id,
+                    Validated::new(counter, Validated::valid).unwrap(),
+                    Validated::new(Value::Int32(1), Validated::valid).unwrap(),
+                )
+                .unwrap();
+        }
+        tracking_copy.effect()
+    });
+}
@@ -0,0 +1,208 @@
+//! Accounts of a state with their main purse balances, e.g. for proof of reserve audits, see
+//! `EngineState::export_accounts`.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use common::key::{addr_to_hex, Key};
+use common::uref::URef;
+use common::value::account::PublicKey;
+use common::value::U512;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountRecord {
+    pub account: PublicKey,
+    pub main_purse: URef,
+    /// `None` if the mint holds no balance for the main purse.
+    pub balance: Option<U512>,
+    pub named_keys: BTreeMap<String, Key>,
+}
+
+#[derive(Debug)]
+pub enum AccountExportResult {
+    RootNotFound,
+    /// Records in the order of the account addresses.
+    Success(Vec<AccountRecord>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One line per account, named keys joined as `name=key;name=key`.
+    Csv,
+    /// An array with an object per account. Balances are strings as they don't fit in JSON
+    /// numbers.
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(format!("unknown export format {}", input)),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExportFormat::Csv => write!(f, "csv"),
+            ExportFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Writes `records` to `writer`, account addresses in hex, purses and keys as they're displayed.
+pub fn write_accounts<W: Write>(
+    writer: &mut W,
+    records: &[AccountRecord],
+    format: ExportFormat,
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Csv => write_csv(writer, records),
+        ExportFormat::Json => write_json(writer, records),
+    }
+}
+
+fn balance_string(record: &AccountRecord) -> Option<String> {
+    record.balance.map(|balance| balance.to_string())
+}
+
+fn write_csv<W: Write>(writer: &mut W, records: &[AccountRecord]) -> io::Result<()> {
+    writeln!(writer, "account,main_purse,balance,named_keys")?;
+    for record in records {
+        let named_keys: Vec<String> = record
+            .named_keys
+            .iter()
+            .map(|(name, key)| format!("{}={}", name, key))
+            .collect();
+        writeln!(
+            writer,
+            "{},{},{},{}",
+            addr_to_hex(&record.account.value()),
+            record.main_purse,
+            balance_string(record).unwrap_or_default(),
+            csv_field(&named_keys.join(";"))
+        )?;
+    }
+    Ok(())
+}
+
+/// Quotes fields holding separators, quotes or line breaks, doubling the quotes.
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_json<W: Write>(writer: &mut W, records: &[AccountRecord]) -> io::Result<()> {
+    write!(writer, "[")?;
+    for (index, record) in records.iter().enumerate() {
+        let named_keys: Vec<String> = record
+            .named_keys
+            .iter()
+            .map(|(name, key)| format!("{}:{}", json_string(name), json_string(&key.to_string())))
+            .collect();
+        write!(
+            writer,
+            "{}{{\"account\":\"{}\",\"main_purse\":\"{}\",\"balance\":{},\"named_keys\":{{{}}}}}",
+            if index == 0 { "" } else { "," },
+            addr_to_hex(&record.account.value()),
+            record.main_purse,
+            balance_string(record).map_or("null".to_string(), |balance| json_string(&balance)),
+            named_keys.join(",")
+        )?;
+    }
+    writeln!(writer, "]")
+}
+
+fn json_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use common::key::Key;
+    use common::uref::{AccessRights, URef};
+    use common::value::account::PublicKey;
+    use common::value::U512;
+
+    use super::{write_accounts, AccountRecord, ExportFormat};
+
+    fn records() -> Vec<AccountRecord> {
+        let mut named_keys = BTreeMap::new();
+        named_keys.insert("a,\"b\"".to_string(), Key::Hash([2u8; 32]));
+        vec![
+            AccountRecord {
+                account: PublicKey::new([1u8; 32]),
+                main_purse: URef::new([3u8; 32], AccessRights::READ_ADD_WRITE),
+                balance: Some(U512::from(1000)),
+                named_keys,
+            },
+            AccountRecord {
+                account: PublicKey::new([4u8; 32]),
+                main_purse: URef::new([5u8; 32], AccessRights::READ_ADD_WRITE),
+                balance: None,
+                named_keys: BTreeMap::new(),
+            },
+        ]
+    }
+
+    fn export(format: ExportFormat) -> String {
+        let mut output = Vec::new();
+        write_accounts(&mut output, &records(), format).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn should_export_csv() {
+        let expected = format!(
+            "account,main_purse,balance,named_keys\n\
+             {},{},1000,\"a,\"\"b\"\"={}\"\n\
+             {},{},,\n",
+            "01".repeat(32),
+            format!("uref-{}-007", "03".repeat(32)),
+            Key::Hash([2u8; 32]),
+            "04".repeat(32),
+            format!("uref-{}-007", "05".repeat(32)),
+        );
+        assert_eq!(export(ExportFormat::Csv), expected);
+    }
+
+    #[test]
+    fn should_export_json() {
+        let expected = format!(
+            "[{{\"account\":\"{}\",\"main_purse\":\"{}\",\"balance\":\"1000\",\
+             \"named_keys\":{{\"a,\\\"b\\\"\":\"{}\"}}}},\
+             {{\"account\":\"{}\",\"main_purse\":\"{}\",\"balance\":null,\"named_keys\":{{}}}}]\n",
+            "01".repeat(32),
+            format!("uref-{}-007", "03".repeat(32)),
+            Key::Hash([2u8; 32]),
+            "04".repeat(32),
+            format!("uref-{}-007", "05".repeat(32)),
+        );
+        assert_eq!(export(ExportFormat::Json), expected);
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use common::value::account::PublicKey;
+
+use shared::gas::Gas;
+
+/// Default upper bound, in bytes, on the values cached by a single tracking copy.
+pub const DEFAULT_TRACKING_COPY_CACHE_SIZE: usize = 1024 * 16;
+
+/// Default number of preprocessed modules kept in memory.
+pub const DEFAULT_MODULE_CACHE_SIZE: usize = 256;
+
+/// Default upper bound, in bytes, on the session code, arguments and address of a deploy.
+pub const DEFAULT_MAX_DEPLOY_SIZE: usize = 8 * 1024 * 1024;
+
+/// Default upper bound, in bytes, on the serialized arguments of a deploy.
+pub const DEFAULT_MAX_DEPLOY_ARGS_SIZE: usize = 1024 * 1024;
+
+/// Tunables of the engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineConfig {
+    /// Max size of the read cache of each tracking copy. See `TrackingCopyCache`.
+    pub tracking_copy_cache_size: usize,
+    /// Max number of preprocessed modules kept across deploys; 0 disables the module cache.
+    pub module_cache_size: usize,
+    /// If set, gas limits of deploys are capped to this value before execution.
+    pub max_deploy_gas_limit: Option<Gas>,
+    /// If set, deploys still running after this long fail with `Error::Timeout`, independently
+    /// of their gas. A circuit breaker for interpreter bugs and mispriced opcodes; unlike the gas
+    /// limit it isn't deterministic, so timed out deploys are precondition failures, without cost
+    /// or effects, and it should be generous.
+    pub deploy_timeout: Option<Duration>,
+    /// Deploys whose session code, arguments and address take more bytes in total are rejected
+    /// before execution.
+    pub max_deploy_size: usize,
+    /// Deploys whose serialized arguments take more bytes are rejected before execution.
+    pub max_deploy_args_size: usize,
+    /// Debugging aid: the result of each executed deploy carries the state root its effects lead
+    /// to when committed alone on the prestate, see `EngineState::deploy_state_root`. Costs a
+    /// trie write per written key.
+    pub deploy_state_roots: bool,
+    /// Deploys from these accounts are rejected before execution with `Error::AccountFrozen`,
+    /// e.g. to stop a compromised account on a private network. Nodes have to agree on this
+    /// list, as it decides which deploys fail.
+    pub frozen_accounts: BTreeSet<PublicKey>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            tracking_copy_cache_size: DEFAULT_TRACKING_COPY_CACHE_SIZE,
+            module_cache_size: DEFAULT_MODULE_CACHE_SIZE,
+            max_deploy_gas_limit: None,
+            deploy_timeout: None,
+            max_deploy_size: DEFAULT_MAX_DEPLOY_SIZE,
+            max_deploy_args_size: DEFAULT_MAX_DEPLOY_ARGS_SIZE,
+            deploy_state_roots: false,
+            frozen_accounts: BTreeSet::new(),
+        }
+    }
+}
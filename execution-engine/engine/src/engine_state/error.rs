@@ -1,6 +1,9 @@
 use failure::Fail;
 
+use common::key::Key;
+use common::value::account::PublicKey;
 use shared::newtypes::Blake2bHash;
+use shared::transform::Transform;
 
 use execution;
 
@@ -8,6 +11,26 @@ use execution;
 pub enum Error {
     #[fail(display = "Invalid public key length: expected {}, actual {}", _0, _1)]
     InvalidPublicKeyLength { expected: usize, actual: usize },
+    #[fail(
+        display = "Deploy size {} exceeds the limit of {} bytes",
+        size, max_size
+    )]
+    DeployTooLarge { size: usize, max_size: usize },
+    #[fail(
+        display = "Deploy arguments size {} exceeds the limit of {} bytes",
+        size, max_size
+    )]
+    DeployArgsTooLarge { size: usize, max_size: usize },
+    #[fail(display = "Account {} is frozen", _0)]
+    AccountFrozen(PublicKey),
+    #[fail(
+        display = "Invalid nonce: deploy nonce {}, expected nonce {}",
+        deploy_nonce, expected_nonce
+    )]
+    InvalidNonce {
+        deploy_nonce: u64,
+        expected_nonce: u64,
+    },
     #[fail(display = "Wasm preprocessing error: {:?}", _0)]
     WasmPreprocessingError(wasm_prep::PreprocessingError),
     #[fail(display = "Wasm serialization error: {:?}", _0)]
@@ -16,6 +39,12 @@ pub enum Error {
     ExecError(::execution::Error),
     #[fail(display = "Storage error: {}", _0)]
     StorageError(storage::error::Error),
+    /// The effects of genesis have to be plain writes for its post state to be computed.
+    #[fail(
+        display = "Genesis should only write values, got {:?} under {}",
+        _1, _0
+    )]
+    InvalidGenesisTransform(Key, Transform),
 }
 
 impl From<wasm_prep::PreprocessingError> for Error {
@@ -50,3 +79,29 @@ impl From<!> for Error {
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct RootNotFound(pub Blake2bHash);
+
+/// Failures of `run_execute` rejecting the request as a whole.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ExecuteError {
+    MissingParent(RootNotFound),
+    /// Under `InvalidNoncePolicy::AbortBlock`, the deploy at `deploy_index` had an invalid nonce.
+    InvalidNonce {
+        deploy_index: usize,
+        deploy_nonce: u64,
+        expected_nonce: u64,
+    },
+    /// The deploy at `deploy_index` doesn't have a higher nonce than the deploy of the same
+    /// account at `previous_index`, when the request asked for the deploy order to be checked.
+    MisorderedDeploy {
+        deploy_index: usize,
+        deploy_nonce: u64,
+        previous_index: usize,
+        previous_nonce: u64,
+    },
+}
+
+impl From<RootNotFound> for ExecuteError {
+    fn from(error: RootNotFound) -> Self {
+        ExecuteError::MissingParent(error)
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use common::value::account::BlockTime;
+use shared::gas::Gas;
+use shared::motes::ConversionRate;
+use shared::newtypes::Blake2bHash;
+
+use super::error::ExecuteError;
+
+/// A single deploy to be executed, expressed in domain terms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeployItem {
+    /// Public key of the account which is the context of the execution.
+    pub address: Vec<u8>,
+    pub session_code: Vec<u8>,
+    pub session_args: Vec<u8>,
+    pub gas_limit: Gas,
+    /// Motes paid per unit of gas the deploy costs, see `EngineState::run_execute`.
+    pub gas_price: ConversionRate,
+    pub nonce: u64,
+}
+
+impl DeployItem {
+    /// Hash of everything the execution of the deploy depends on, besides the prestate and the
+    /// block it's executed in.
+    pub fn deploy_hash(&self) -> Blake2bHash {
+        let mut bytes = Vec::with_capacity(
+            self.address.len() + self.session_code.len() + self.session_args.len() + 48,
+        );
+        for field in &[&self.address, &self.session_code, &self.session_args] {
+            bytes.extend_from_slice(&(field.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(field);
+        }
+        bytes.extend_from_slice(&self.gas_limit.value().to_le_bytes());
+        bytes.extend_from_slice(&self.gas_price.value().to_le_bytes());
+        bytes.extend_from_slice(&self.nonce.to_le_bytes());
+        Blake2bHash::new(&bytes)
+    }
+}
+
+/// Drops the deploys with the same hash as an earlier one, as every deploy of a request is
+/// executed on the same prestate and they would have the same result, e.g. when the node resends
+/// a deploy after a timeout. Returns the remaining deploys, and for each of `deploys` the index of
+/// the remaining one whose result it shares.
+pub fn dedup_deploys(deploys: Vec<DeployItem>) -> (Vec<DeployItem>, Vec<usize>) {
+    let mut indices_by_hash: HashMap<Blake2bHash, usize> = HashMap::new();
+    let mut unique_deploys = Vec::with_capacity(deploys.len());
+    let result_indices = deploys
+        .into_iter()
+        .map(|deploy| {
+            let next_index = unique_deploys.len();
+            let index = *indices_by_hash
+                .entry(deploy.deploy_hash())
+                .or_insert(next_index);
+            if index == next_index {
+                unique_deploys.push(deploy);
+            }
+            index
+        })
+        .collect();
+    (unique_deploys, result_indices)
+}
+
+/// Checks that the deploys of each account come in increasing nonce order, as they would be
+/// executed one after the other. Returns the first deploy whose nonce isn't above the one of the
+/// previous deploy of its account.
+pub fn check_deploy_order(deploys: &[DeployItem]) -> Result<(), ExecuteError> {
+    let mut previous_by_account: HashMap<&[u8], (usize, u64)> = HashMap::new();
+    for (deploy_index, deploy) in deploys.iter().enumerate() {
+        if let Some((previous_index, previous_nonce)) =
+            previous_by_account.insert(&deploy.address, (deploy_index, deploy.nonce))
+        {
+            if deploy.nonce <= previous_nonce {
+                return Err(ExecuteError::MisorderedDeploy {
+                    deploy_index,
+                    deploy_nonce: deploy.nonce,
+                    previous_index,
+                    previous_nonce,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What happens to a deploy whose nonce isn't the next one of its account, which depends on how
+/// the node buffers deploys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidNoncePolicy {
+    /// Deploys with a nonce above the expected one are skipped and reported as `InvalidNonce`,
+    /// so the node can retry them in a later block. Those below it fail, as they never will.
+    RetryLater,
+    /// Every deploy with an invalid nonce fails on its own, without cost.
+    FailDeploy,
+    /// A deploy with an invalid nonce fails the whole request, e.g. when validating a block
+    /// proposed by another node.
+    AbortBlock,
+}
+
+impl Default for InvalidNoncePolicy {
+    fn default() -> Self {
+        InvalidNoncePolicy::RetryLater
+    }
+}
+
+/// A batch of deploys executed against the same prestate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecuteRequest {
+    pub parent_state_hash: Blake2bHash,
+    pub block_time: BlockTime,
+    pub deploys: Vec<DeployItem>,
+    pub protocol_version: u64,
+    pub invalid_nonce_policy: InvalidNoncePolicy,
+    /// Whether to reject the request when the deploys of an account are out of nonce order, see
+    /// [`check_deploy_order`].
+    pub check_deploy_order: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::gas::Gas;
+    use shared::motes::ConversionRate;
+
+    use super::super::error::ExecuteError;
+    use super::{check_deploy_order, dedup_deploys, DeployItem};
+
+    fn deploy(nonce: u64) -> DeployItem {
+        account_deploy(1, nonce)
+    }
+
+    fn account_deploy(account: u8, nonce: u64) -> DeployItem {
+        DeployItem {
+            address: vec![account; 32],
+            session_code: vec![0, 1, 2],
+            session_args: vec![],
+            gas_limit: Gas::new(1000),
+            gas_price: ConversionRate::new(1),
+            nonce,
+        }
+    }
+
+    #[test]
+    fn should_hash_every_field() {
+        let base = deploy(1);
+        assert_eq!(base.deploy_hash(), deploy(1).deploy_hash());
+        assert_ne!(base.deploy_hash(), deploy(2).deploy_hash());
+        assert_ne!(
+            base.deploy_hash(),
+            DeployItem {
+                gas_limit: Gas::new(999),
+                ..deploy(1)
+            }
+            .deploy_hash()
+        );
+        assert_ne!(
+            base.deploy_hash(),
+            DeployItem {
+                gas_price: ConversionRate::new(2),
+                ..deploy(1)
+            }
+            .deploy_hash()
+        );
+        // Bytes moved from the code to the args don't hash the same.
+        assert_ne!(
+            base.deploy_hash(),
+            DeployItem {
+                session_code: vec![0, 1],
+                session_args: vec![2],
+                ..deploy(1)
+            }
+            .deploy_hash()
+        );
+    }
+
+    #[test]
+    fn should_dedup_deploys() {
+        let (deploys, result_indices) =
+            dedup_deploys(vec![deploy(1), deploy(2), deploy(1), deploy(3), deploy(2)]);
+        assert_eq!(deploys, vec![deploy(1), deploy(2), deploy(3)]);
+        assert_eq!(result_indices, vec![0, 1, 0, 2, 1]);
+    }
+
+    #[test]
+    fn should_check_deploy_order_per_account() {
+        let deploys = vec![
+            account_deploy(1, 1),
+            account_deploy(2, 5),
+            account_deploy(1, 2),
+            account_deploy(2, 6),
+        ];
+        assert_eq!(check_deploy_order(&deploys), Ok(()));
+
+        let deploys = vec![
+            account_deploy(1, 2),
+            account_deploy(2, 1),
+            account_deploy(1, 1),
+        ];
+        assert_eq!(
+            check_deploy_order(&deploys),
+            Err(ExecuteError::MisorderedDeploy {
+                deploy_index: 2,
+                deploy_nonce: 1,
+                previous_index: 0,
+                previous_nonce: 2,
+            })
+        );
+
+        // Distinct deploys can't share a nonce.
+        let deploys = vec![account_deploy(1, 1), account_deploy(1, 1)];
+        assert!(check_deploy_order(&deploys).is_err());
+    }
+}
@@ -1,18 +1,300 @@
 use std::collections::HashMap;
+use std::fmt;
 
+use common::bytesrepr::{self, ToBytes};
 use common::key::Key;
+use common::value::account::PurseId;
+use common::value::U512;
+use shared::newtypes::Blake2bHash;
 use shared::transform::Transform;
+use storage::global_state::CommitResult;
 
 use super::op::Op;
 
+/// Tokens moved between purses by the `transfer_from_purse_to_purse` host function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferEvent {
+    /// Key of the account or contract on whose behalf the transfer was made.
+    pub initiator: Key,
+    pub source: PurseId,
+    pub target: PurseId,
+    pub amount: U512,
+}
+
+/// Data emitted by a contract with the `emit_event` host function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractEvent {
+    /// Key of the account or contract which emitted the event.
+    pub emitter: Key,
+    pub data: Vec<u8>,
+}
+
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct ExecutionEffect {
     pub ops: HashMap<Key, Op>,
     pub transforms: HashMap<Key, Transform>,
+    /// Transfers in the order they were made.
+    pub transfers: Vec<TransferEvent>,
+    /// Events in the order they were emitted.
+    pub events: Vec<ContractEvent>,
+    /// Bytes the deploy added to the global state, negative if it freed more than it added.
+    pub net_storage_bytes: i64,
 }
 
 impl ExecutionEffect {
     pub fn new(ops: HashMap<Key, Op>, transforms: HashMap<Key, Transform>) -> Self {
-        ExecutionEffect { ops, transforms }
+        ExecutionEffect {
+            ops,
+            transforms,
+            transfers: Vec::new(),
+            events: Vec::new(),
+            net_storage_bytes: 0,
+        }
+    }
+
+    /// Drops the `Identity` transforms recorded for keys which were only read. Committing them
+    /// doesn't change the state, but the `Read` ops are kept as conflict detection between
+    /// deploys depends on them.
+    pub fn strip_identities(&mut self) {
+        self.transforms
+            .retain(|_, transform| *transform != Transform::Identity);
+    }
+
+    /// Hash of the effects in a canonical form, for nodes to compare the effects of a deploy
+    /// without sending them over: ops and transforms in key order, transfers and events in the
+    /// order they happened. `Identity` transforms are left out, as they aren't sent to the node.
+    pub fn checksum(&self) -> Result<Blake2bHash, bytesrepr::Error> {
+        let mut ops: Vec<(&Key, &Op)> = self.ops.iter().collect();
+        ops.sort_by_key(|(key, _)| *key);
+        let mut transforms: Vec<(&Key, &Transform)> = self
+            .transforms
+            .iter()
+            .filter(|(_, transform)| **transform != Transform::Identity)
+            .collect();
+        transforms.sort_by_key(|(key, _)| *key);
+
+        let mut bytes = (ops.len() as u64).to_bytes()?;
+        for (key, op) in ops {
+            bytes.append(&mut key.to_bytes()?);
+            bytes.push(op_tag(op));
+        }
+        bytes.append(&mut (transforms.len() as u64).to_bytes()?);
+        for (key, transform) in transforms {
+            bytes.append(&mut key.to_bytes()?);
+            bytes.append(&mut transform.to_bytes()?);
+        }
+        bytes.append(&mut (self.transfers.len() as u64).to_bytes()?);
+        for transfer in &self.transfers {
+            bytes.append(&mut transfer.initiator.to_bytes()?);
+            bytes.append(&mut transfer.source.to_bytes()?);
+            bytes.append(&mut transfer.target.to_bytes()?);
+            bytes.append(&mut transfer.amount.to_bytes()?);
+        }
+        bytes.append(&mut (self.events.len() as u64).to_bytes()?);
+        for event in &self.events {
+            bytes.append(&mut event.emitter.to_bytes()?);
+            bytes.append(&mut event.data.to_bytes()?);
+        }
+        Ok(Blake2bHash::new(&bytes))
+    }
+}
+
+fn op_tag(op: &Op) -> u8 {
+    match op {
+        Op::Read => 0,
+        Op::Write => 1,
+        Op::Add => 2,
+        Op::Create => 3,
+        Op::NoOp => 4,
+    }
+}
+
+/// Transform which could not have been produced by the op recorded for its key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InconsistentOp {
+    pub key: Key,
+    /// `None` if no op was recorded for `key` at all.
+    pub op: Option<Op>,
+    pub transform: Transform,
+}
+
+impl fmt::Display for InconsistentOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.op {
+            Some(ref op) => write!(
+                f,
+                "transform {:?} under {:?} is inconsistent with op {}",
+                self.transform, self.key, op
+            ),
+            None => write!(
+                f,
+                "transform {:?} under {:?} has no op recorded",
+                self.transform, self.key
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ApplyEffectResult {
+    /// The transforms disagree with the ops; nothing was committed.
+    Rejected(InconsistentOp),
+    Applied(CommitResult),
+}
+
+/// Effects of a block to be committed as part of a batch.
+#[derive(Debug)]
+pub struct BatchedEffects {
+    pub prestate_hash: Blake2bHash,
+    pub ops: Option<HashMap<Key, Op>>,
+    pub effects: HashMap<Key, Transform>,
+    pub expected_poststate_hash: Option<Blake2bHash>,
+}
+
+#[derive(Debug)]
+pub enum BatchApplyEffectsResult {
+    /// The effects at `index` were rejected or couldn't be committed; nothing was stored.
+    Failed {
+        index: usize,
+        result: ApplyEffectResult,
+    },
+    /// All effects were committed, with their results in the order of the batch.
+    Applied(Vec<CommitResult>),
+}
+
+/// Checks that the kind of each transform agrees with the op recorded for its key, e.g. that a
+/// `Write` transform wasn't recorded as a mere `Read`.
+///
+/// Ops and transforms merged over several deploys are checked the same way, as merging a read
+/// with an addition yields a `Write` op while the transform stays an addition.
+pub fn check_ops(
+    ops: &HashMap<Key, Op>,
+    transforms: &HashMap<Key, Transform>,
+) -> Result<(), InconsistentOp> {
+    for (key, transform) in transforms {
+        let op = ops.get(key).cloned();
+        let consistent = match (transform, &op) {
+            (_, None) => false,
+            (Transform::Identity, Some(_)) | (Transform::Failure(_), Some(_)) => true,
+            (Transform::Write(_), Some(op)) | (Transform::RemoveKeys(_), Some(op)) => {
+                *op == Op::Write || *op == Op::Create
+            }
+            (Transform::AddInt32(_), Some(op))
+            | (Transform::AddUInt64(_), Some(op))
+            | (Transform::AddUInt128(_), Some(op))
+            | (Transform::AddUInt256(_), Some(op))
+            | (Transform::AddUInt512(_), Some(op))
+            | (Transform::AddKeys(_), Some(op)) => {
+                *op == Op::Add || *op == Op::Write || *op == Op::Create
+            }
+        };
+        if !consistent {
+            return Err(InconsistentOp {
+                key: *key,
+                op,
+                transform: transform.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use common::key::Key;
+    use common::value::{Value, U512};
+    use shared::transform::Transform;
+
+    use super::{check_ops, ContractEvent, ExecutionEffect, InconsistentOp};
+    use engine_state::op::Op;
+
+    const KEY: Key = Key::Hash([1u8; 32]);
+
+    fn check(op: Option<Op>, transform: Transform) -> Result<(), InconsistentOp> {
+        let ops: HashMap<Key, Op> = op.into_iter().map(|op| (KEY, op)).collect();
+        let transforms = vec![(KEY, transform)].into_iter().collect();
+        check_ops(&ops, &transforms)
+    }
+
+    #[test]
+    fn should_accept_consistent_ops() {
+        assert!(check(Some(Op::Read), Transform::Identity).is_ok());
+        assert!(check(Some(Op::Write), Transform::Write(Value::Int32(1))).is_ok());
+        assert!(check(Some(Op::Add), Transform::AddUInt512(U512::one())).is_ok());
+        // Read followed by an addition.
+        assert!(check(Some(Op::Write), Transform::AddInt32(1)).is_ok());
+        // Keys created by the deploy.
+        assert!(check(Some(Op::Create), Transform::Write(Value::Int32(2))).is_ok());
+    }
+
+    #[test]
+    fn should_reject_inconsistent_ops() {
+        let write = Transform::Write(Value::Int32(1));
+        assert_eq!(
+            check(Some(Op::Read), write.clone()),
+            Err(InconsistentOp {
+                key: KEY,
+                op: Some(Op::Read),
+                transform: write.clone(),
+            })
+        );
+        assert!(check(Some(Op::Add), write.clone()).is_err());
+        assert!(check(Some(Op::Read), Transform::AddInt32(1)).is_err());
+        assert!(check(None, write).is_err());
+    }
+
+    #[test]
+    fn should_strip_identities_but_keep_reads() {
+        let written = Key::Hash([2u8; 32]);
+        let ops = vec![(KEY, Op::Read), (written, Op::Write)]
+            .into_iter()
+            .collect();
+        let transforms = vec![
+            (KEY, Transform::Identity),
+            (written, Transform::Write(Value::Int32(1))),
+        ]
+        .into_iter()
+        .collect();
+        let mut effect = ExecutionEffect::new(ops, transforms);
+        effect.strip_identities();
+
+        assert_eq!(effect.ops.len(), 2);
+        assert_eq!(effect.ops.get(&KEY), Some(&Op::Read));
+        assert_eq!(effect.transforms.len(), 1);
+        assert!(check_ops(&effect.ops, &effect.transforms).is_ok());
+    }
+
+    #[test]
+    fn checksum_should_only_depend_on_the_effects() {
+        let keys: Vec<Key> = (0..16u8).map(|i| Key::Hash([i; 32])).collect();
+        let effect = |keys: &[Key]| {
+            let mut effect = ExecutionEffect::default();
+            for key in keys {
+                effect.ops.insert(*key, Op::Write);
+                effect
+                    .transforms
+                    .insert(*key, Transform::Write(Value::Int32(1)));
+            }
+            effect.ops.insert(KEY, Op::Read);
+            effect.transforms.insert(KEY, Transform::Identity);
+            effect
+        };
+        let mut reversed = keys.clone();
+        reversed.reverse();
+        let checksum = effect(&keys).checksum().unwrap();
+        assert_eq!(effect(&reversed).checksum().unwrap(), checksum);
+
+        let mut stripped = effect(&keys);
+        stripped.strip_identities();
+        assert_eq!(stripped.checksum().unwrap(), checksum);
+
+        let mut with_event = effect(&keys);
+        with_event.events.push(ContractEvent {
+            emitter: KEY,
+            data: vec![1],
+        });
+        assert_ne!(with_event.checksum().unwrap(), checksum);
     }
 }
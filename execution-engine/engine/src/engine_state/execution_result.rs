@@ -1,3 +1,6 @@
+use execution;
+use shared::gas::Gas;
+
 use super::error::Error;
 use super::execution_effect::ExecutionEffect;
 
@@ -7,10 +10,10 @@ pub enum ExecutionResult {
     Failure {
         error: Error,
         effect: ExecutionEffect,
-        cost: u64,
+        cost: Gas,
     },
     /// Execution was finished successfully
-    Success { effect: ExecutionEffect, cost: u64 },
+    Success { effect: ExecutionEffect, cost: Gas },
 }
 
 impl ExecutionResult {
@@ -21,7 +24,22 @@ impl ExecutionResult {
         ExecutionResult::Failure {
             error,
             effect: Default::default(),
-            cost: 0,
+            cost: Gas::default(),
+        }
+    }
+
+    /// Returns the deploy's and the expected nonce if the deploy was rejected for its nonce.
+    pub fn invalid_nonce(&self) -> Option<(u64, u64)> {
+        match self {
+            ExecutionResult::Failure {
+                error:
+                    Error::ExecError(execution::Error::InvalidNonce {
+                        deploy_nonce,
+                        expected_nonce,
+                    }),
+                ..
+            } => Some((*deploy_nonce, *expected_nonce)),
+            _ => None,
         }
     }
 }
@@ -0,0 +1,88 @@
+use std::collections::{BTreeMap, HashMap};
+
+use common::value::account::PublicKey;
+use common::value::U512;
+use shared::motes::Motes;
+
+/// Splits `reward` between the bonded validators in proportion to their stakes.
+///
+/// Shares are rounded down, so up to one token per validator may be left undistributed.
+pub fn reward_shares(
+    bonded_validators: &HashMap<PublicKey, U512>,
+    reward: Motes,
+) -> BTreeMap<PublicKey, Motes> {
+    let reward = reward.value();
+    let total_stake = bonded_validators
+        .values()
+        .fold(U512::zero(), |sum, stake| sum.saturating_add(*stake));
+    if total_stake.is_zero() {
+        return BTreeMap::new();
+    }
+    bonded_validators
+        .iter()
+        .map(|(validator, stake)| {
+            let share = match reward.checked_mul(*stake) {
+                Some(product) => product / total_stake,
+                None => reward / total_stake * *stake,
+            };
+            (*validator, Motes::new(share))
+        })
+        .filter(|(_, share)| !share.value().is_zero())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use common::value::account::PublicKey;
+    use common::value::U512;
+    use shared::motes::Motes;
+
+    use super::reward_shares;
+
+    const KEY1: [u8; 32] = [1; 32];
+    const KEY2: [u8; 32] = [2; 32];
+
+    fn bonds(stakes: &[([u8; 32], u64)]) -> HashMap<PublicKey, U512> {
+        stakes
+            .iter()
+            .map(|&(key, stake)| (PublicKey::new(key), U512::from(stake)))
+            .collect()
+    }
+
+    fn motes(value: u64) -> Motes {
+        Motes::new(U512::from(value))
+    }
+
+    #[test]
+    fn should_split_reward_by_stake() {
+        let shares = reward_shares(&bonds(&[(KEY1, 300), (KEY2, 100)]), motes(1_000));
+        let expected: BTreeMap<PublicKey, Motes> = vec![
+            (PublicKey::new(KEY1), motes(750)),
+            (PublicKey::new(KEY2), motes(250)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(shares, expected);
+    }
+
+    #[test]
+    fn should_round_shares_down() {
+        let shares = reward_shares(&bonds(&[(KEY1, 1), (KEY2, 2)]), motes(10));
+        assert_eq!(shares[&PublicKey::new(KEY1)], motes(3));
+        assert_eq!(shares[&PublicKey::new(KEY2)], motes(6));
+    }
+
+    #[test]
+    fn should_not_reward_without_stakes() {
+        assert!(reward_shares(&HashMap::new(), motes(10)).is_empty());
+        assert!(reward_shares(&bonds(&[(KEY1, 0)]), motes(10)).is_empty());
+    }
+
+    #[test]
+    fn should_not_overflow_on_large_rewards() {
+        let shares = reward_shares(&bonds(&[(KEY1, 2), (KEY2, 2)]), Motes::new(U512::MAX));
+        assert_eq!(shares[&PublicKey::new(KEY1)], Motes::new(U512::MAX / 4 * 2));
+    }
+}
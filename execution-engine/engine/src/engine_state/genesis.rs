@@ -11,13 +11,16 @@ use common::key::Key;
 use common::uref::{AccessRights, URef};
 use common::value::account::{PublicKey, PurseId};
 use common::value::{Account, Contract, Value, U512};
+use engine_state::error::Error;
 use engine_state::execution_effect::ExecutionEffect;
 use engine_state::op::Op;
 use engine_state::utils::WasmiBytes;
 use execution;
-use shared::newtypes::Blake2bHash;
+use shared::newtypes::{Blake2bHash, CorrelationId};
 use shared::transform::{Transform, TypeMismatch};
+use storage::global_state::in_memory::InMemoryGlobalState;
 use storage::global_state::CommitResult;
+use wasm_prep::wasm_costs::WasmCosts;
 
 pub const POS_PURSE: &str = "pos_purse";
 pub const POS_PUBLIC_ADDRESS: &str = "pos_public_address";
@@ -27,6 +30,17 @@ pub const MINT_PRIVATE_ADDRESS: &str = "mint_private_address";
 pub const GENESIS_ACCOUNT_PURSE: &str = "genesis_account_purse";
 pub const MINT_GENESIS_ACCOUNT_BALANCE_UREF: &str = "mint_genesis_account_balance_uref";
 pub const MINT_POS_BALANCE_UREF: &str = "mint_pos_balance_uref";
+pub const MINT_TOTAL_SUPPLY_UREF: &str = "mint_total_supply_uref";
+/// Purse collecting the fees deploys pay during a block, see `EngineState::run_finalization`.
+pub const PAYMENT_PURSE: &str = "payment_purse";
+/// Purse the collected fees are moved to when a block is finalized.
+pub const REWARDS_PURSE: &str = "rewards_purse";
+// Genesis doesn't set up the payment and rewards purses, so that its post state stays the same;
+// the `AddSystemPurses` upgrade migration does.
+pub const MINT_PAYMENT_BALANCE_UREF: &str = "mint_payment_balance_uref";
+pub const MINT_REWARDS_BALANCE_UREF: &str = "mint_rewards_balance_uref";
+/// Name under which the mint keeps the URef of the total supply of tokens.
+pub const MINT_TOTAL_SUPPLY: &str = "total_supply";
 
 /// Structure for tracking URefs generated in the genesis process.
 pub struct GenesisURefsSource(BTreeMap<&'static str, URef>);
@@ -69,6 +83,12 @@ impl Default for GenesisURefsSource {
             create_uref(&mut chacha_rng),
         );
         urefs_map.insert(MINT_POS_BALANCE_UREF, create_uref(&mut chacha_rng));
+        // Generated last so that the addresses of the other URefs don't change.
+        urefs_map.insert(MINT_TOTAL_SUPPLY_UREF, create_uref(&mut chacha_rng));
+        urefs_map.insert(PAYMENT_PURSE, create_uref(&mut chacha_rng));
+        urefs_map.insert(REWARDS_PURSE, create_uref(&mut chacha_rng));
+        urefs_map.insert(MINT_PAYMENT_BALANCE_UREF, create_uref(&mut chacha_rng));
+        urefs_map.insert(MINT_REWARDS_BALANCE_UREF, create_uref(&mut chacha_rng));
 
         GenesisURefsSource(urefs_map)
     }
@@ -80,7 +100,10 @@ fn create_uref<R: RngCore>(rng: &mut R) -> URef {
     URef::new(buff, AccessRights::READ_ADD_WRITE)
 }
 
-fn create_local_key<T: ToBytes>(seed: [u8; 32], key: T) -> Result<Key, common::bytesrepr::Error> {
+pub fn create_local_key<T: ToBytes>(
+    seed: [u8; 32],
+    key: T,
+) -> Result<Key, common::bytesrepr::Error> {
     let local_key_bytes = key.to_bytes()?;
     Ok(Key::local(seed, &local_key_bytes))
 }
@@ -174,12 +197,22 @@ fn create_mint_effects(
 
     tmp.insert(balance_uref_key, balance);
 
+    // Tokens only get created in genesis and as block rewards, so the supply is tracked by the
+    // engine instead of being computed from all the balances.
+    let total_supply_uref = rng.get_uref(MINT_TOTAL_SUPPLY_UREF);
+    let total_supply_uref_key = Key::URef(total_supply_uref);
+    tmp.insert(
+        total_supply_uref_key,
+        Value::UInt512(initial_tokens + pos_bonded_balance),
+    );
+
     // Create mint_contract
     let mint_known_urefs = {
         let mut ret: BTreeMap<String, Key> = BTreeMap::new();
         ret.insert(balance_uref.as_string(), balance_uref_key);
         // Insert PoS balance URef and its initial stakes so that PoS.
         ret.insert(pos_balance_uref.as_string(), pos_balance_uref_key);
+        ret.insert(MINT_TOTAL_SUPPLY.to_string(), total_supply_uref_key);
         ret
     };
 
@@ -296,6 +329,56 @@ pub fn create_genesis_effects(
     Ok(execution_effect)
 }
 
+/// Computes the post-state hash of genesis without touching the node's global state.
+///
+/// Genesis values are written in key order into a fresh in-memory trie, so the hash depends only
+/// on the given arguments and not on the order of `genesis_validators`, the iteration order of
+/// the effects or where the state of the node is stored.
+pub fn genesis_post_state_hash(
+    genesis_account_addr: [u8; 32],
+    initial_tokens: U512,
+    mint_code_bytes: &[u8],
+    pos_code_bytes: &[u8],
+    genesis_validators: Vec<(PublicKey, U512)>,
+    protocol_version: u64,
+) -> Result<Blake2bHash, Error> {
+    let mint_code = WasmiBytes::new(mint_code_bytes, WasmCosts::free())?;
+    let pos_code = WasmiBytes::new(pos_code_bytes, WasmCosts::free())?;
+
+    let effects = create_genesis_effects(
+        genesis_account_addr,
+        initial_tokens,
+        mint_code,
+        pos_code,
+        genesis_validators,
+        protocol_version,
+    )?;
+
+    let mut pairs: Vec<(Key, Value)> = effects
+        .transforms
+        .into_iter()
+        .map(|(key, transform)| match transform {
+            Transform::Write(value) => Ok((key, value)),
+            other => Err(Error::InvalidGenesisTransform(key, other)),
+        })
+        .collect::<Result<_, _>>()?;
+    pairs.sort_by(|(key_a, _), (key_b, _)| key_a.cmp(key_b));
+
+    let state = InMemoryGlobalState::from_pairs(CorrelationId::new(), &pairs)?;
+    Ok(state.root_hash)
+}
+
+/// Computes the post-state hash of importing `entries` with `EngineState::import_genesis_state`,
+/// without touching the node's global state, e.g. to check whether they were already imported.
+pub fn imported_state_hash(entries: &BTreeMap<Key, Value>) -> Result<Blake2bHash, Error> {
+    let pairs: Vec<(Key, Value)> = entries
+        .iter()
+        .map(|(key, value)| (key.normalize(), value.clone()))
+        .collect();
+    let state = InMemoryGlobalState::from_pairs(CorrelationId::new(), &pairs)?;
+    Ok(state.root_hash)
+}
+
 pub enum GenesisResult {
     RootNotFound,
     KeyNotFound(Key),
@@ -328,7 +411,10 @@ impl GenesisResult {
             CommitResult::RootNotFound => GenesisResult::RootNotFound,
             CommitResult::KeyNotFound(key) => GenesisResult::KeyNotFound(key),
             CommitResult::TypeMismatch(type_mismatch) => GenesisResult::TypeMismatch(type_mismatch),
-            CommitResult::Success(post_state_hash) => GenesisResult::Success {
+            CommitResult::PoststateMismatch { .. } => {
+                unreachable!("genesis is committed without an expected post state")
+            }
+            CommitResult::Success(post_state_hash, _) => GenesisResult::Success {
                 post_state_hash,
                 effect,
             },
@@ -347,19 +433,22 @@ mod tests {
     use engine_state::create_genesis_effects;
     use engine_state::genesis::{
         GenesisURefsSource, GENESIS_ACCOUNT_PURSE, MINT_GENESIS_ACCOUNT_BALANCE_UREF,
-        MINT_POS_BALANCE_UREF, MINT_PRIVATE_ADDRESS, MINT_PUBLIC_ADDRESS, POS_PRIVATE_ADDRESS,
-        POS_PUBLIC_ADDRESS,
+        MINT_POS_BALANCE_UREF, MINT_PRIVATE_ADDRESS, MINT_PUBLIC_ADDRESS, MINT_TOTAL_SUPPLY,
+        MINT_TOTAL_SUPPLY_UREF, POS_PRIVATE_ADDRESS, POS_PUBLIC_ADDRESS,
     };
     use engine_state::utils::{pos_validator_key, WasmiBytes};
+    use engine_state::EngineState;
+    use shared::newtypes::{Blake2bHash, CorrelationId};
     use shared::test_utils;
     use shared::transform::Transform;
+    use storage::global_state::in_memory::InMemoryGlobalState;
     use wasm_prep::wasm_costs::WasmCosts;
 
-    use super::{create_local_key, POS_PURSE};
+    use super::{create_local_key, genesis_post_state_hash, GenesisResult, POS_PURSE};
 
     const GENESIS_ACCOUNT_ADDR: [u8; 32] = [6u8; 32];
     const PROTOCOL_VERSION: u64 = 1;
-    const EXPECTED_GENESIS_TRANSFORM_COUNT: usize = 9; // 7 writes for Mint and 2 for PoS.
+    const EXPECTED_GENESIS_TRANSFORM_COUNT: usize = 10; // 8 writes for Mint and 2 for PoS.
     const INITIAL_GENESIS_ACCOUNT_BALANCE: &str = "1000";
     const INITIAL_POS_VALIDATORS_BALANCE: &str = "15000";
 
@@ -516,10 +605,13 @@ mod tests {
         let pos_balance_uref = rng.get_uref(MINT_POS_BALANCE_UREF);
         let pos_balance_uref_key = Key::URef(pos_balance_uref);
 
+        let total_supply_uref_key = Key::URef(rng.get_uref(MINT_TOTAL_SUPPLY_UREF));
+
         let mint_known_urefs = {
             let mut ret: BTreeMap<String, Key> = BTreeMap::new();
             ret.insert(pos_balance_uref.as_string(), pos_balance_uref_key);
             ret.insert(balance_uref.as_string(), balance_uref_key);
+            ret.insert(MINT_TOTAL_SUPPLY.to_string(), total_supply_uref_key);
             ret
         };
 
@@ -637,6 +729,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn create_genesis_effects_stores_total_supply() {
+        let rng = GenesisURefsSource::default();
+        let total_supply_uref_key = Key::URef(rng.get_uref(MINT_TOTAL_SUPPLY_UREF)).normalize();
+
+        let transforms = get_genesis_transforms();
+
+        let actual_total_supply = extract_transform_u512(&transforms, &total_supply_uref_key)
+            .expect("transform was not a write of a U512");
+        let expected_total_supply = get_initial_tokens(INITIAL_GENESIS_ACCOUNT_BALANCE)
+            + get_initial_tokens(INITIAL_POS_VALIDATORS_BALANCE);
+        assert_eq!(actual_total_supply, expected_total_supply);
+    }
+
     #[test]
     fn create_genesis_effects_stores_genesis_account_at_genesis_account_addr() {
         let account_key = Key::Account(GENESIS_ACCOUNT_ADDR);
@@ -760,4 +866,88 @@ mod tests {
         );
     }
 
+    fn get_genesis_post_state_hash(
+        genesis_validators: Vec<(PublicKey, U512)>,
+        protocol_version: u64,
+    ) -> Blake2bHash {
+        let wasm_bytes = test_utils::create_empty_wasm_module_bytes();
+        genesis_post_state_hash(
+            GENESIS_ACCOUNT_ADDR,
+            get_initial_tokens(INITIAL_GENESIS_ACCOUNT_BALANCE),
+            &wasm_bytes,
+            &wasm_bytes,
+            genesis_validators,
+            protocol_version,
+        )
+        .expect("should compute genesis hash")
+    }
+
+    #[test]
+    fn genesis_post_state_hash_does_not_depend_on_validators_order() {
+        let validator_a = (PublicKey::new([1u8; 32]), U512::from(100));
+        let validator_b = (PublicKey::new([2u8; 32]), U512::from(200));
+
+        let hash = get_genesis_post_state_hash(vec![validator_a, validator_b], PROTOCOL_VERSION);
+
+        assert_eq!(
+            get_genesis_post_state_hash(vec![validator_b, validator_a], PROTOCOL_VERSION),
+            hash
+        );
+        assert_ne!(
+            get_genesis_post_state_hash(vec![validator_a], PROTOCOL_VERSION),
+            hash
+        );
+        assert_ne!(
+            get_genesis_post_state_hash(vec![validator_a, validator_b], PROTOCOL_VERSION + 1),
+            hash
+        );
+    }
+
+    /// Post state hash of the genesis below, recorded when its layout last changed on purpose.
+    /// Networks check the genesis of their chainspec against the hash they started with, so any
+    /// other change to it forks them.
+    const GOLDEN_GENESIS_POST_STATE_HASH: &str = "";
+
+    #[test]
+    #[ignore] // Until GOLDEN_GENESIS_POST_STATE_HASH is recorded from a build of this tree.
+    fn genesis_post_state_hash_is_golden() {
+        let validator_a = (PublicKey::new([1u8; 32]), U512::from(100));
+        let validator_b = (PublicKey::new([2u8; 32]), U512::from(200));
+
+        let hash = get_genesis_post_state_hash(vec![validator_a, validator_b], PROTOCOL_VERSION);
+
+        assert_eq!(
+            format!("{:x}", hash),
+            GOLDEN_GENESIS_POST_STATE_HASH,
+            "genesis layout changed, update the hash only if the change is intended"
+        );
+    }
+
+    #[test]
+    fn genesis_post_state_hash_matches_committed_genesis() {
+        let wasm_bytes = test_utils::create_empty_wasm_module_bytes();
+        let genesis_validators = vec![(PublicKey::new([1u8; 32]), U512::from(100))];
+        let engine_state = EngineState::new(InMemoryGlobalState::empty().unwrap());
+
+        let committed_hash = match engine_state.commit_genesis(
+            CorrelationId::new(),
+            GENESIS_ACCOUNT_ADDR,
+            get_initial_tokens(INITIAL_GENESIS_ACCOUNT_BALANCE),
+            &wasm_bytes,
+            &wasm_bytes,
+            genesis_validators.clone(),
+            PROTOCOL_VERSION,
+        ) {
+            Ok(GenesisResult::Success {
+                post_state_hash, ..
+            }) => post_state_hash,
+            Ok(other) => panic!("genesis failed: {}", other),
+            Err(error) => panic!("genesis failed: {}", error),
+        };
+
+        assert_eq!(
+            get_genesis_post_state_hash(genesis_validators, PROTOCOL_VERSION),
+            committed_hash
+        );
+    }
 }
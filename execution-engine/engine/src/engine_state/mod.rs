@@ -1,37 +1,81 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::rc::Rc;
 use std::sync::Arc;
 
 use parking_lot::Mutex;
 
+use common::bytesrepr;
 use common::key::Key;
-use common::value::account::{BlockTime, PublicKey};
-use common::value::{Value, U512};
+use common::pos_parameters::{BONDING_QUEUE_KEY, UNBONDING_QUEUE_KEY};
+use common::uref::URef;
+use common::value::account::{BlockTime, PublicKey, PurseId};
+use common::value::{Contract, Value, U512};
 use engine_state::utils::WasmiBytes;
-use execution::{self, Executor};
-use shared::newtypes::{Blake2bHash, CorrelationId};
+use execution::{self, Executor, WasmiExecutor};
+use protocol_registry::{ProtocolLimits, ProtocolRegistry};
+use resolvers::error::ResolverError;
+use shared::gas::Gas;
+use shared::motes::{ConversionRate, Motes};
+use shared::newtypes::{Blake2bHash, CorrelationId, Validated};
 use shared::transform::Transform;
-use storage::global_state::{CommitResult, History, StateReader};
-use tracking_copy::TrackingCopy;
+use shared::validators;
+use storage::global_state::{CommitResult, History, Snapshots, StateEntries, StateReader};
+use tracking_copy::{self, TrackingCopy};
 use wasm_prep::wasm_costs::WasmCosts;
 use wasm_prep::Preprocessor;
 
-use self::error::{Error, RootNotFound};
+use self::account_export::{AccountExportResult, AccountRecord};
+use self::engine_config::EngineConfig;
+use self::error::{Error, ExecuteError, RootNotFound};
+use self::execute_request::{DeployItem, ExecuteRequest, InvalidNoncePolicy};
+use self::execution_effect::{
+    check_ops, ApplyEffectResult, BatchApplyEffectsResult, BatchedEffects, ExecutionEffect,
+};
 use self::execution_result::ExecutionResult;
-use self::genesis::{create_genesis_effects, GenesisResult};
+use self::genesis::{
+    create_genesis_effects, create_local_key, GenesisResult, GenesisURefsSource,
+    MINT_PRIVATE_ADDRESS, MINT_TOTAL_SUPPLY, PAYMENT_PURSE, REWARDS_PURSE,
+};
+use self::module_cache::{CachingPreprocessor, ModuleCache};
+use self::op::Op;
+use self::query::{BalanceResult, NamedKeysResult, QueryRequest, QueryResult, TotalSupplyResult};
+use self::rent::RentRecord;
+use self::slashing::{SlashRequest, SlashResult};
+use self::staking::{StakingCheck, StakingCheckResult};
+use self::state_file::StateExportResult;
+use self::upgrade::{Migration, UpgradeRequest, UpgradeResult};
 
+pub mod account_export;
+pub mod engine_config;
 pub mod error;
+pub mod execute_request;
 pub mod execution_effect;
 pub mod execution_result;
+pub mod finalization;
 pub mod genesis;
+pub mod module_cache;
 pub mod op;
+pub mod query;
+pub mod rent;
+pub mod slashing;
+pub mod staking;
+pub mod state_file;
+pub mod upgrade;
 pub mod utils;
 
-pub struct EngineState<H> {
+const EXPECTED_PUBLIC_KEY_LENGTH: usize = 32;
+
+pub struct EngineState<H: History> {
     // Tracks the "state" of the blockchain (or is an interface to it).
     // I think it should be constrained with a lifetime parameter.
     state: Arc<Mutex<H>>,
+    // Readers check out states from here, so that they don't wait for the commits which lock
+    // `state`.
+    snapshots: H::Snapshots,
+    config: EngineConfig,
+    module_cache: Mutex<ModuleCache>,
+    protocols: Arc<ProtocolRegistry>,
 }
 
 impl<H> EngineState<H>
@@ -40,8 +84,43 @@ where
     H::Error: Into<execution::Error>,
 {
     pub fn new(state: H) -> EngineState<H> {
+        EngineState::with_config(state, EngineConfig::default())
+    }
+
+    pub fn with_config(state: H, config: EngineConfig) -> EngineState<H> {
+        EngineState::with_protocols(state, config, ProtocolRegistry::default())
+    }
+
+    /// Creates an engine executing deploys of the versions in `protocols`, e.g. the versions
+    /// before and after an upgrade.
+    pub fn with_protocols(
+        state: H,
+        config: EngineConfig,
+        protocols: ProtocolRegistry,
+    ) -> EngineState<H> {
+        let snapshots = state.snapshots();
         let state = Arc::new(Mutex::new(state));
-        EngineState { state }
+        let module_cache = Mutex::new(ModuleCache::new(config.module_cache_size));
+        EngineState {
+            state,
+            snapshots,
+            config,
+            module_cache,
+            protocols: Arc::new(protocols),
+        }
+    }
+
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    pub fn protocols(&self) -> &ProtocolRegistry {
+        &self.protocols
+    }
+
+    /// Modules preprocessed by `run_execute`, shared by all the protocol versions in use.
+    pub fn module_cache(&self) -> &Mutex<ModuleCache> {
+        &self.module_cache
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -77,20 +156,666 @@ where
         Ok(genesis_result)
     }
 
+    /// Writes `entries`, e.g. read from a state file, on top of the empty state instead of
+    /// running genesis. The post state hash only depends on the entries, so every node importing
+    /// the same ones arrives at the same root. Values are checked against the limits of
+    /// `protocol_version`, the version the network starts with.
+    pub fn import_genesis_state(
+        &self,
+        correlation_id: CorrelationId,
+        entries: BTreeMap<Key, Value>,
+        protocol_version: u64,
+    ) -> Result<GenesisResult, Error> {
+        let max_value_size = self
+            .protocols
+            .get(protocol_version)
+            .map_err(execution::Error::from)?
+            .limits
+            .max_value_size;
+        let check_value = validators::storable_value(max_value_size);
+        let mut effects = ExecutionEffect::default();
+        for (key, value) in entries {
+            let key = key.normalize();
+            validators::well_formed_key(&key).map_err(execution::Error::from)?;
+            check_value(&value).map_err(execution::Error::from)?;
+            effects.ops.insert(key, Op::Write);
+            effects.transforms.insert(key, Transform::Write(value));
+        }
+
+        let mut state_guard = self.state.lock();
+        let prestate_hash = state_guard.empty_root();
+        let commit_result = state_guard
+            .commit(correlation_id, prestate_hash, effects.transforms.to_owned())
+            .map_err(Into::into)?;
+
+        Ok(GenesisResult::from_commit_result(commit_result, effects))
+    }
+
     pub fn state(&self) -> Arc<Mutex<H>> {
         Arc::clone(&self.state)
     }
 
+    /// Checkouts which don't wait for the commit in progress, if any.
+    pub fn snapshots(&self) -> &H::Snapshots {
+        &self.snapshots
+    }
+
+    /// Tracking copy for requests which aren't tied to a protocol version, e.g. queries, under
+    /// the default limits.
     pub fn tracking_copy(
         &self,
         hash: Blake2bHash,
     ) -> Result<Option<TrackingCopy<H::Reader>>, Error> {
-        match self.state.lock().checkout(hash).map_err(Into::into)? {
-            Some(tc) => Ok(Some(TrackingCopy::new(tc))),
+        self.tracking_copy_with_limits(hash, &ProtocolLimits::default())
+    }
+
+    /// Tracking copy for deploys of a protocol version with the given `limits`.
+    pub fn tracking_copy_with_limits(
+        &self,
+        hash: Blake2bHash,
+        limits: &ProtocolLimits,
+    ) -> Result<Option<TrackingCopy<H::Reader>>, Error> {
+        match self.snapshots.checkout(hash).map_err(Into::into)? {
+            Some(tc) => Ok(Some(TrackingCopy::with_config(tc, &self.config, limits))),
             None => Ok(None),
         }
     }
 
+    /// Reads the value under `base_key` (following `path`) at the given state hash.
+    pub fn run_query(
+        &self,
+        correlation_id: CorrelationId,
+        query_request: &QueryRequest,
+    ) -> Result<QueryResult, Error> {
+        let mut tracking_copy = match self.tracking_copy(query_request.state_hash)? {
+            Some(tracking_copy) => tracking_copy,
+            None => return Ok(QueryResult::RootNotFound),
+        };
+        match tracking_copy
+            .query(correlation_id, query_request.base_key, &query_request.path)
+            .map_err(Into::into)?
+        {
+            tracking_copy::QueryResult::ValueNotFound(full_path) => {
+                Ok(QueryResult::ValueNotFound(full_path))
+            }
+            tracking_copy::QueryResult::Success(value) => Ok(QueryResult::Success(value)),
+        }
+    }
+
+    /// Reads the total supply of tokens tracked by the mint at the given state hash.
+    pub fn run_total_supply_query(
+        &self,
+        correlation_id: CorrelationId,
+        state_hash: Blake2bHash,
+    ) -> Result<TotalSupplyResult, Error> {
+        let mut tracking_copy = match self.tracking_copy(state_hash)? {
+            Some(tracking_copy) => tracking_copy,
+            None => return Ok(TotalSupplyResult::RootNotFound),
+        };
+        let mint_key = Key::URef(GenesisURefsSource::default().get_uref(MINT_PRIVATE_ADDRESS));
+        match tracking_copy
+            .query(correlation_id, mint_key, &[MINT_TOTAL_SUPPLY.to_string()])
+            .map_err(Into::into)?
+        {
+            tracking_copy::QueryResult::Success(Value::UInt512(total_supply)) => {
+                Ok(TotalSupplyResult::Success(total_supply))
+            }
+            _ => Ok(TotalSupplyResult::NotTracked),
+        }
+    }
+
+    /// Reads the balance the mint holds for `purse_id` at the given state hash.
+    pub fn get_purse_balance(
+        &self,
+        correlation_id: CorrelationId,
+        state_hash: Blake2bHash,
+        purse_id: PurseId,
+    ) -> Result<BalanceResult, Error> {
+        let mut tracking_copy = match self.tracking_copy(state_hash)? {
+            Some(tracking_copy) => tracking_copy,
+            None => return Ok(BalanceResult::RootNotFound),
+        };
+        let mint_address = GenesisURefsSource::default()
+            .get_uref(MINT_PRIVATE_ADDRESS)
+            .addr();
+        match read_purse_balance(
+            correlation_id,
+            &mut tracking_copy,
+            mint_address,
+            purse_id.value(),
+        )? {
+            Some(balance) => Ok(BalanceResult::Success(balance)),
+            None => Ok(BalanceResult::PurseNotFound),
+        }
+    }
+
+    /// Lists the named keys of the account or contract stored under `key` at the given state
+    /// hash.
+    pub fn get_named_keys(
+        &self,
+        correlation_id: CorrelationId,
+        state_hash: Blake2bHash,
+        key: Key,
+    ) -> Result<NamedKeysResult, Error> {
+        let mut tracking_copy = match self.tracking_copy(state_hash)? {
+            Some(tracking_copy) => tracking_copy,
+            None => return Ok(NamedKeysResult::RootNotFound),
+        };
+        let validated_key = Validated::new(key.normalize(), Validated::valid)?;
+        match tracking_copy
+            .read(correlation_id, &validated_key)
+            .map_err(Into::into)?
+        {
+            None => Ok(NamedKeysResult::ValueNotFound),
+            Some(Value::Account(account)) => {
+                Ok(NamedKeysResult::Success(account.urefs_lookup().clone()))
+            }
+            Some(Value::Contract(contract)) => {
+                Ok(NamedKeysResult::Success(contract.urefs_lookup().clone()))
+            }
+            Some(other) => Ok(NamedKeysResult::InvalidType(other.type_string())),
+        }
+    }
+
+    /// Lists every account at the given state hash with the balance of its main purse and its
+    /// named keys. Goes through the whole state, so it's meant for occasional exports rather
+    /// than for serving requests.
+    pub fn export_accounts(
+        &self,
+        correlation_id: CorrelationId,
+        state_hash: Blake2bHash,
+    ) -> Result<AccountExportResult, Error> {
+        let reader = match self.snapshots.checkout(state_hash).map_err(Into::into)? {
+            Some(reader) => reader,
+            None => return Ok(AccountExportResult::RootNotFound),
+        };
+        let mut accounts = Vec::new();
+        reader
+            .for_each_entry(&mut |_, value| {
+                if let Value::Account(account) = value {
+                    accounts.push(account);
+                }
+            })
+            .map_err(Into::into)?;
+        accounts.sort_by_key(|account| account.pub_key());
+
+        let mut tracking_copy =
+            TrackingCopy::with_config(reader, &self.config, &ProtocolLimits::default());
+        let mint_address = GenesisURefsSource::default()
+            .get_uref(MINT_PRIVATE_ADDRESS)
+            .addr();
+        let mut records = Vec::with_capacity(accounts.len());
+        for account in accounts {
+            let main_purse = account.purse_id().value();
+            let balance =
+                read_purse_balance(correlation_id, &mut tracking_copy, mint_address, main_purse)?;
+            records.push(AccountRecord {
+                account: PublicKey::new(account.pub_key()),
+                main_purse,
+                balance,
+                named_keys: account.urefs_lookup().clone(),
+            });
+        }
+        Ok(AccountExportResult::Success(records))
+    }
+
+    /// Reads every entry of the global state at `state_hash`, e.g. to write them to a state file
+    /// another network starts from with `import_genesis_state`.
+    pub fn export_state(&self, state_hash: Blake2bHash) -> Result<StateExportResult, Error> {
+        let reader = match self.snapshots.checkout(state_hash).map_err(Into::into)? {
+            Some(reader) => reader,
+            None => return Ok(StateExportResult::RootNotFound),
+        };
+        let mut entries = BTreeMap::new();
+        reader
+            .for_each_entry(&mut |key, value| {
+                entries.insert(key, value);
+            })
+            .map_err(Into::into)?;
+        Ok(StateExportResult::Success(entries))
+    }
+
+    /// Computes the effect of slashing the bonds of the PoS contract at the parent state of
+    /// `slash_request`. Nothing is committed; the node commits the returned effect along with
+    /// the block's other effects.
+    pub fn run_slash(
+        &self,
+        correlation_id: CorrelationId,
+        slash_request: &SlashRequest,
+    ) -> Result<SlashResult, Error> {
+        let mut tracking_copy = match self.tracking_copy(slash_request.parent_state_hash)? {
+            Some(tracking_copy) => tracking_copy,
+            None => return Ok(SlashResult::RootNotFound(slash_request.parent_state_hash)),
+        };
+        let pos_key = Key::URef(GenesisURefsSource::default().get_pos_address());
+        let validated_key = Validated::new(pos_key, Validated::valid)?;
+        let mut contract = match tracking_copy
+            .read(correlation_id, &validated_key)
+            .map_err(Into::into)?
+        {
+            Some(Value::Contract(contract)) => contract,
+            _ => return Ok(SlashResult::PoSNotFound(pos_key)),
+        };
+        let slashed_amount = match slashing::apply_slashes(
+            contract.get_urefs_lookup_mut(),
+            &slash_request.slashes,
+        ) {
+            Ok(slashed_amount) => slashed_amount,
+            Err(error) => return Ok(SlashResult::Rejected(error)),
+        };
+        let bonded_validators = contract
+            .urefs_lookup()
+            .keys()
+            .filter_map(|entry| utils::pos_validator_to_tuple(entry))
+            .collect();
+        let validated_value = Validated::new(Value::Contract(contract), Validated::valid)?;
+        tracking_copy
+            .write(validated_key, validated_value)
+            .map_err(execution::Error::from)?;
+        Ok(SlashResult::Success {
+            effect: tracking_copy.effect(),
+            bonded_validators,
+            slashed_amount,
+        })
+    }
+
+    /// Computes the effect of replacing the code of the system contracts listed in `request`.
+    /// The contracts keep their keys, named keys and stored values, except for the changes made
+    /// by `migration`. Nothing is committed; the node commits the returned effect when it
+    /// activates the new protocol version.
+    pub fn run_upgrade<M: Migration>(
+        &self,
+        correlation_id: CorrelationId,
+        request: &UpgradeRequest,
+        migration: &M,
+    ) -> Result<UpgradeResult, Error> {
+        let mut tracking_copy = match self.tracking_copy(request.parent_state_hash)? {
+            Some(tracking_copy) => tracking_copy,
+            None => return Ok(UpgradeResult::RootNotFound(request.parent_state_hash)),
+        };
+        for (&system_contract, code) in &request.new_code {
+            let validated_key = Validated::new(system_contract.key(), Validated::valid)?;
+            let (_, mut named_keys, current_version, access_groups) = match tracking_copy
+                .read(correlation_id, &validated_key)
+                .map_err(Into::into)?
+            {
+                Some(Value::Contract(contract)) => contract.destructure(),
+                _ => return Ok(UpgradeResult::ContractNotFound(system_contract)),
+            };
+            if request.protocol_version <= current_version {
+                return Ok(UpgradeResult::InvalidProtocolVersion {
+                    contract: system_contract,
+                    current: current_version,
+                    requested: request.protocol_version,
+                });
+            }
+            // Same preprocessing as at genesis.
+            let bytes = WasmiBytes::new(code, WasmCosts::free())?;
+            if let Err(error) = migration.migrate(
+                correlation_id,
+                system_contract,
+                &mut named_keys,
+                &mut tracking_copy,
+            ) {
+                return Ok(UpgradeResult::MigrationFailed(system_contract, error));
+            }
+            let contract = Contract::new(bytes.into(), named_keys, request.protocol_version)
+                .with_access_groups(access_groups);
+            let validated_value = Validated::new(Value::Contract(contract), Validated::valid)?;
+            tracking_copy
+                .write(validated_key, validated_value)
+                .map_err(execution::Error::from)?;
+        }
+        Ok(UpgradeResult::Success {
+            effect: tracking_copy.effect(),
+        })
+    }
+
+    /// Checks a bonding or unbonding request against the stakes and pending requests of the PoS
+    /// contract at `check.state_hash`, without executing the staking deploy.
+    pub fn run_staking_check(
+        &self,
+        correlation_id: CorrelationId,
+        check: &StakingCheck,
+    ) -> Result<StakingCheckResult, Error> {
+        let mut tracking_copy = match self.tracking_copy(check.state_hash)? {
+            Some(tracking_copy) => tracking_copy,
+            None => return Ok(StakingCheckResult::RootNotFound(check.state_hash)),
+        };
+        let pos_uref = GenesisURefsSource::default().get_pos_address();
+        let validated_key = Validated::new(Key::URef(pos_uref), Validated::valid)?;
+        let stakes = match tracking_copy
+            .read(correlation_id, &validated_key)
+            .map_err(Into::into)?
+        {
+            Some(Value::Contract(contract)) => contract
+                .urefs_lookup()
+                .keys()
+                .filter_map(|entry| utils::pos_validator_to_tuple(entry))
+                .collect(),
+            _ => return Ok(StakingCheckResult::PoSNotFound),
+        };
+
+        let mut queues = Vec::with_capacity(2);
+        for queue_key in &[BONDING_QUEUE_KEY, UNBONDING_QUEUE_KEY] {
+            // `write_local` serializes a `u8` key as the single byte itself.
+            let local_key = Key::local(pos_uref.addr(), &[*queue_key]);
+            let validated_local_key = Validated::new(local_key, Validated::valid)?;
+            let queue = match tracking_copy
+                .read(correlation_id, &validated_local_key)
+                .map_err(Into::into)?
+            {
+                // The contract starts with empty queues.
+                None => Vec::new(),
+                Some(Value::ByteArray(bytes)) => match staking::parse_queue(&bytes) {
+                    Ok(queue) => queue,
+                    Err(error) => return Ok(StakingCheckResult::InvalidQueue(error)),
+                },
+                Some(_) => {
+                    return Ok(StakingCheckResult::InvalidQueue(
+                        bytesrepr::Error::FormattingError,
+                    ))
+                }
+            };
+            queues.push(queue);
+        }
+
+        match staking::check_request(
+            stakes,
+            &queues[0],
+            &queues[1],
+            check.validator,
+            check.request,
+        ) {
+            Ok(stake) => Ok(StakingCheckResult::Valid {
+                stake,
+                due: staking::due_time(check.request, check.timestamp),
+            }),
+            Err(error) => Ok(StakingCheckResult::Invalid(error)),
+        }
+    }
+
+    /// Executes all deploys of `exec_request` against its parent state, returning one
+    /// result per deploy in the same order.
+    ///
+    /// `RootNotFound` short-circuits the execution of ALL deploys within the block, as all
+    /// of them share the same prestate and all of them would fail. So does a deploy with an
+    /// invalid nonce under `InvalidNoncePolicy::AbortBlock`, and deploys out of nonce order when
+    /// the request asks for `check_deploy_order`, before any of them is executed.
+    ///
+    /// Deploys pay their cost at their gas price into the payment purse, see `pay_fee`.
+    pub fn run_execute(
+        &self,
+        correlation_id: CorrelationId,
+        exec_request: ExecuteRequest,
+    ) -> Result<Vec<ExecutionResult>, ExecuteError> {
+        let ExecuteRequest {
+            parent_state_hash,
+            block_time,
+            deploys,
+            protocol_version,
+            invalid_nonce_policy,
+            check_deploy_order,
+        } = exec_request;
+        if check_deploy_order {
+            execute_request::check_deploy_order(&deploys)?;
+        }
+
+        let semantics = match self.protocols.get(protocol_version) {
+            Ok(semantics) => semantics,
+            Err(_) => {
+                return Ok(deploys
+                    .iter()
+                    .map(|_| {
+                        let error = execution::Error::from(ResolverError::UnknownProtocolVersion(
+                            protocol_version,
+                        ));
+                        ExecutionResult::precondition_failure(error.into())
+                    })
+                    .collect())
+            }
+        };
+        let preprocessor = CachingPreprocessor::new(
+            &self.module_cache,
+            semantics.preprocessor(),
+            protocol_version,
+            &semantics.wasm_costs,
+        );
+        let executor = WasmiExecutor::new(self.config.deploy_timeout, Arc::clone(&self.protocols));
+
+        let run_deploy_item = |deploy_item: DeployItem| {
+            let DeployItem {
+                address,
+                session_code,
+                session_args,
+                gas_limit,
+                gas_price,
+                nonce,
+            } = deploy_item;
+            if let Err(error) = self.check_deploy_size(&address, &session_code, &session_args) {
+                return Ok(ExecutionResult::precondition_failure(error));
+            }
+            if address.len() != EXPECTED_PUBLIC_KEY_LENGTH {
+                let error = Error::InvalidPublicKeyLength {
+                    expected: EXPECTED_PUBLIC_KEY_LENGTH,
+                    actual: address.len(),
+                };
+                return Ok(ExecutionResult::precondition_failure(error));
+            }
+            let address = {
+                let mut dest = [0; EXPECTED_PUBLIC_KEY_LENGTH];
+                dest.copy_from_slice(&address);
+                dest
+            };
+            if self
+                .config
+                .frozen_accounts
+                .contains(&PublicKey::new(address))
+            {
+                let error = Error::AccountFrozen(PublicKey::new(address));
+                return Ok(ExecutionResult::precondition_failure(error));
+            }
+            let address = Key::Account(address);
+            let gas_limit = match self.config.max_deploy_gas_limit {
+                Some(max_gas_limit) => gas_limit.min(max_gas_limit),
+                None => gas_limit,
+            };
+            let mut result = self.run_deploy(
+                &session_code,
+                &session_args,
+                address,
+                block_time,
+                nonce,
+                parent_state_hash,
+                gas_limit,
+                protocol_version,
+                correlation_id,
+                &executor,
+                &preprocessor,
+            )?;
+            let paid = match self.tracking_copy(parent_state_hash) {
+                Ok(Some(mut tracking_copy)) => pay_fee(
+                    correlation_id,
+                    &mut tracking_copy,
+                    address,
+                    gas_price,
+                    &mut result,
+                ),
+                // `run_deploy` checked out the same root.
+                Ok(None) => Ok(()),
+                Err(error) => Err(error),
+            };
+            match paid {
+                Ok(()) => Ok(result),
+                Err(error) => Ok(ExecutionResult::precondition_failure(error)),
+            }
+        };
+
+        let mut results = Vec::with_capacity(deploys.len());
+        for (deploy_index, deploy_item) in deploys.into_iter().enumerate() {
+            let result = run_deploy_item(deploy_item)?;
+            let result = match (result.invalid_nonce(), invalid_nonce_policy) {
+                (Some((deploy_nonce, expected_nonce)), InvalidNoncePolicy::FailDeploy) => {
+                    ExecutionResult::precondition_failure(Error::InvalidNonce {
+                        deploy_nonce,
+                        expected_nonce,
+                    })
+                }
+                (Some((deploy_nonce, expected_nonce)), InvalidNoncePolicy::AbortBlock) => {
+                    return Err(ExecuteError::InvalidNonce {
+                        deploy_index,
+                        deploy_nonce,
+                        expected_nonce,
+                    })
+                }
+                _ => result,
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    /// Distributes `block_reward` and the fees in the rewards purse at `parent_state_hash` to the
+    /// validators bonded there in proportion to their stakes, paying each share into the
+    /// validator's main purse. The reward is newly minted, i.e. it increases the total supply,
+    /// while the fees come out of the rewards purse. The fees collected in the payment purse up
+    /// to `parent_state_hash` are moved to the rewards purse, to be paid out by the next
+    /// finalization.
+    ///
+    /// The result is that of a system deploy without cost, to be committed along with the block's
+    /// deploys. Validators without an account at `parent_state_hash` forfeit their share.
+    pub fn run_finalization(
+        &self,
+        correlation_id: CorrelationId,
+        parent_state_hash: Blake2bHash,
+        block_reward: Motes,
+    ) -> Result<ExecutionResult, RootNotFound> {
+        let mut tracking_copy = match self.tracking_copy(parent_state_hash) {
+            Err(error) => return Ok(ExecutionResult::precondition_failure(error)),
+            Ok(None) => return Err(RootNotFound(parent_state_hash)),
+            Ok(Some(tracking_copy)) => tracking_copy,
+        };
+        let finalized = distribute_rewards(correlation_id, &mut tracking_copy, block_reward)
+            .and_then(|()| move_collected_fees(correlation_id, &mut tracking_copy));
+        match finalized {
+            Ok(()) => Ok(ExecutionResult::Success {
+                effect: tracking_copy.effect(),
+                cost: Gas::default(),
+            }),
+            Err(error) => Ok(ExecutionResult::precondition_failure(error)),
+        }
+    }
+
+    /// Marks the keys whose rent ran out before `block_time` as reclaimable, see
+    /// `engine_state::rent`. Goes through the whole state twice, so nodes are expected to run it
+    /// periodically rather than with every block.
+    ///
+    /// `deploy_results` are those of the deploys executed on `parent_state_hash` in the same
+    /// block. The rent records they write are left alone, as their value under
+    /// `parent_state_hash` is stale; those keys are collected next time if still unpaid.
+    ///
+    /// The result is that of a system deploy without cost, to be committed along with the block's
+    /// deploys. Its effect is empty if rent isn't enabled under `protocol_version`.
+    pub fn run_rent_collection(
+        &self,
+        correlation_id: CorrelationId,
+        parent_state_hash: Blake2bHash,
+        block_time: BlockTime,
+        protocol_version: u64,
+        deploy_results: &[ExecutionResult],
+    ) -> Result<ExecutionResult, RootNotFound> {
+        let precondition_failure =
+            |error: execution::Error| Ok(ExecutionResult::precondition_failure(error.into()));
+        let limits = match self.protocols.get(protocol_version) {
+            Ok(semantics) => semantics.limits.clone(),
+            Err(error) => return precondition_failure(error.into()),
+        };
+        let reader = match self.snapshots.checkout(parent_state_hash) {
+            Err(error) => return precondition_failure(error.into()),
+            Ok(None) => return Err(RootNotFound(parent_state_hash)),
+            Ok(Some(reader)) => reader,
+        };
+        let mut reclaimable = Vec::new();
+        if limits.rent.is_some() {
+            let written: HashSet<Key> = deploy_results
+                .iter()
+                .flat_map(|result| match result {
+                    ExecutionResult::Success { effect, .. }
+                    | ExecutionResult::Failure { effect, .. } => effect.transforms.keys(),
+                })
+                .cloned()
+                .collect();
+            // Records don't name their key, so the first pass finds the newly expired records
+            // and the second one the keys they belong to. Only the records to rewrite are kept.
+            let mut expired = HashMap::new();
+            let visited = reader.for_each_entry(&mut |key, value| {
+                if let Key::Local(_) = key {
+                    match rent::record_from_value(&value) {
+                        Some(record)
+                            if !record.reclaimable
+                                && record.is_expired(block_time)
+                                && !written.contains(&key) =>
+                        {
+                            expired.insert(key, record);
+                        }
+                        _ => (),
+                    }
+                }
+            });
+            if let Err(error) = visited {
+                return precondition_failure(error.into());
+            }
+            if !expired.is_empty() {
+                let visited = reader.for_each_entry(&mut |key, _| {
+                    if let Some(record) = expired.remove(&rent::rent_key(&key)) {
+                        reclaimable.push((key, record));
+                    }
+                });
+                if let Err(error) = visited {
+                    return precondition_failure(error.into());
+                }
+            }
+        }
+
+        let mut tracking_copy = TrackingCopy::with_config(reader, &self.config, &limits);
+        for (key, record) in reclaimable {
+            let record = RentRecord {
+                reclaimable: true,
+                ..record
+            };
+            if let Err(error) = tracking_copy.write_rent_record(&key, record) {
+                return precondition_failure(error.into());
+            }
+        }
+        Ok(ExecutionResult::Success {
+            effect: tracking_copy.effect(),
+            cost: Gas::default(),
+        })
+    }
+
+    /// Rejects deploys over the size limits of the config before any of their parts is used.
+    fn check_deploy_size(
+        &self,
+        address: &[u8],
+        session_code: &[u8],
+        session_args: &[u8],
+    ) -> Result<(), Error> {
+        if session_args.len() > self.config.max_deploy_args_size {
+            return Err(Error::DeployArgsTooLarge {
+                size: session_args.len(),
+                max_size: self.config.max_deploy_args_size,
+            });
+        }
+        let size = address.len() + session_code.len() + session_args.len();
+        if size > self.config.max_deploy_size {
+            return Err(Error::DeployTooLarge {
+                size,
+                max_size: self.config.max_deploy_size,
+            });
+        }
+        Ok(())
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn run_deploy<A, P: Preprocessor<A>, E: Executor<A>>(
         &self,
@@ -100,7 +825,7 @@ where
         blocktime: BlockTime,
         nonce: u64,
         prestate_hash: Blake2bHash,
-        gas_limit: u64,
+        gas_limit: Gas,
         protocol_version: u64,
         correlation_id: CorrelationId,
         executor: &E,
@@ -110,7 +835,14 @@ where
             Err(error) => return Ok(ExecutionResult::precondition_failure(error.into())),
             Ok(module) => module,
         };
-        let checkout_result = match self.tracking_copy(prestate_hash) {
+        let limits = match self.protocols.get(protocol_version) {
+            Err(error) => {
+                let error = execution::Error::from(error);
+                return Ok(ExecutionResult::precondition_failure(error.into()));
+            }
+            Ok(semantics) => &semantics.limits,
+        };
+        let checkout_result = match self.tracking_copy_with_limits(prestate_hash, limits) {
             Err(error) => return Ok(ExecutionResult::precondition_failure(error)),
             Ok(checkout_result) => checkout_result,
         };
@@ -131,15 +863,374 @@ where
         ))
     }
 
+    /// Commits `effects` on top of `prestate_hash`.
+    ///
+    /// If the `ops` recorded when executing the deploys are given, the transforms are first
+    /// checked against them (see [`check_ops`]) and rejected on a mismatch, which would point
+    /// to a bug in the executor or in the merging of effects.
+    ///
+    /// Effects which were already applied to `prestate_hash`, e.g. replayed by the node after a
+    /// crash, lead to the stored post state without writing it again. The commit stats report
+    /// it as `already_stored`.
+    ///
+    /// When validating a block from a peer, the node passes the post state hash the block claims
+    /// as `expected_poststate_hash`. A divergent root is then reported as a
+    /// `CommitResult::PoststateMismatch` instead of being stored.
     pub fn apply_effect(
         &self,
         correlation_id: CorrelationId,
         prestate_hash: Blake2bHash,
+        ops: Option<&HashMap<Key, Op>>,
         effects: HashMap<Key, Transform>,
-    ) -> Result<CommitResult, H::Error> {
-        self.state
-            .lock()
-            .commit(correlation_id, prestate_hash, effects)
+        expected_poststate_hash: Option<Blake2bHash>,
+    ) -> Result<ApplyEffectResult, H::Error> {
+        if let Some(ops) = ops {
+            if let Err(inconsistent_op) = check_ops(ops, &effects) {
+                return Ok(ApplyEffectResult::Rejected(inconsistent_op));
+            }
+        }
+        let mut state_guard = self.state.lock();
+        let commit_result = match expected_poststate_hash {
+            Some(expected) => {
+                state_guard.commit_expecting(correlation_id, prestate_hash, effects, expected)
+            }
+            None => state_guard.commit(correlation_id, prestate_hash, effects),
+        };
+        commit_result.map(ApplyEffectResult::Applied)
+    }
+
+    /// Root of the state `effect` would lead to if it was committed alone on top of
+    /// `prestate_hash`, which is computed without storing anything. When nodes disagree on the
+    /// post state of a block, these roots tell which deploy they disagree on. `None` if the
+    /// effect can't be committed.
+    pub fn deploy_state_root(
+        &self,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        effect: &ExecutionEffect,
+    ) -> Result<Option<Blake2bHash>, H::Error> {
+        let commit_result = self.state.lock().dry_run_commit(
+            correlation_id,
+            prestate_hash,
+            effect.transforms.clone(),
+        )?;
+        match commit_result {
+            CommitResult::Success(state_root, _) => Ok(Some(state_root)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Commits the effects of several blocks at once, in order, so that e.g. a node catching up
+    /// doesn't have to wait for each commit to be stored. Each prestate hash may be the post state
+    /// hash of earlier effects in the batch.
+    ///
+    /// Nothing is stored unless all effects can be committed; the ops and expected post state
+    /// hashes are checked as in [`EngineState::apply_effect`].
+    pub fn apply_effects_batch(
+        &self,
+        correlation_id: CorrelationId,
+        batch: Vec<BatchedEffects>,
+    ) -> Result<BatchApplyEffectsResult, H::Error> {
+        for (index, batched_effects) in batch.iter().enumerate() {
+            if let Some(ref ops) = batched_effects.ops {
+                if let Err(inconsistent_op) = check_ops(ops, &batched_effects.effects) {
+                    return Ok(BatchApplyEffectsResult::Failed {
+                        index,
+                        result: ApplyEffectResult::Rejected(inconsistent_op),
+                    });
+                }
+            }
+        }
+        let batch = batch
+            .into_iter()
+            .map(|batched_effects| {
+                (
+                    batched_effects.prestate_hash,
+                    batched_effects.effects,
+                    batched_effects.expected_poststate_hash,
+                )
+            })
+            .collect();
+        let mut commit_results = self.state.lock().batch_commit(correlation_id, batch)?;
+        match commit_results.pop() {
+            Some(CommitResult::Success(poststate_hash, stats)) => {
+                commit_results.push(CommitResult::Success(poststate_hash, stats));
+                Ok(BatchApplyEffectsResult::Applied(commit_results))
+            }
+            Some(commit_result) => Ok(BatchApplyEffectsResult::Failed {
+                index: commit_results.len(),
+                result: ApplyEffectResult::Applied(commit_result),
+            }),
+            None => Ok(BatchApplyEffectsResult::Applied(commit_results)),
+        }
+    }
+}
+
+fn distribute_rewards<R>(
+    correlation_id: CorrelationId,
+    tracking_copy: &mut TrackingCopy<R>,
+    block_reward: Motes,
+) -> Result<(), Error>
+where
+    R: StateReader<Key, Value>,
+    R::Error: Into<execution::Error>,
+{
+    let genesis_urefs = GenesisURefsSource::default();
+    let pos_key = Key::URef(genesis_urefs.get_pos_address());
+    let validated_pos_key = Validated::new(pos_key, Validated::valid)?;
+    let bonded_validators = match tracking_copy
+        .read(correlation_id, &validated_pos_key)
+        .map_err(Into::into)?
+    {
+        Some(Value::Contract(contract)) => contract
+            .urefs_lookup()
+            .keys()
+            .filter_map(|entry| utils::pos_validator_to_tuple(entry))
+            .collect(),
+        _ => return Err(execution::Error::KeyNotFound(pos_key).into()),
+    };
+
+    let mint_uref = genesis_urefs.get_uref(MINT_PRIVATE_ADDRESS);
+    let mint_address = mint_uref.addr();
+    let rewards_purse = genesis_urefs.get_uref(REWARDS_PURSE);
+    // States set up before the engine had a rewards purse have no fees to pay out.
+    let rewards_key = read_balance_key(correlation_id, tracking_copy, mint_address, rewards_purse)?;
+    let fees = match rewards_key {
+        Some(ref rewards_key) => match tracking_copy
+            .read(correlation_id, rewards_key)
+            .map_err(Into::into)?
+        {
+            Some(Value::UInt512(fees)) => fees,
+            _ => U512::zero(),
+        },
+        None => U512::zero(),
+    };
+
+    let reward_shares = finalization::reward_shares(&bonded_validators, block_reward);
+    let fee_shares = finalization::reward_shares(&bonded_validators, Motes::new(fees));
+    let validators: BTreeSet<PublicKey> = reward_shares
+        .keys()
+        .chain(fee_shares.keys())
+        .cloned()
+        .collect();
+    let mut minted = Motes::default();
+    let mut paid_fees = Motes::default();
+    for validator in validators {
+        let share = reward_shares.get(&validator).cloned().unwrap_or_default();
+        let fee_share = fee_shares.get(&validator).cloned().unwrap_or_default();
+        let account_key = Validated::new(Key::Account(validator.value()), Validated::valid)?;
+        let purse = match tracking_copy
+            .read(correlation_id, &account_key)
+            .map_err(Into::into)?
+        {
+            Some(Value::Account(account)) => account.purse_id().value(),
+            _ => continue,
+        };
+        let balance_key =
+            match read_balance_key(correlation_id, tracking_copy, mint_address, purse)? {
+                Some(balance_key) => balance_key,
+                None => continue,
+            };
+        let payout = share.value().saturating_add(fee_share.value());
+        let reward = Validated::new(Value::UInt512(payout), Validated::valid)?;
+        tracking_copy
+            .add(correlation_id, balance_key, reward)
+            .map_err(Into::into)?;
+        // The shares add up to at most the block reward and the fees, so their sums can't
+        // overflow.
+        minted = minted.checked_add(share).unwrap_or(block_reward);
+        paid_fees = paid_fees.checked_add(fee_share).unwrap_or(Motes::new(fees));
+    }
+
+    // Shares forfeited by validators without an account stay in the rewards purse.
+    if let Some(rewards_key) = rewards_key {
+        if !paid_fees.value().is_zero() {
+            // Subtracts by adding the two's complement, as in `move_collected_fees`.
+            let (debit, _) = U512::zero().overflowing_sub(paid_fees.value());
+            let debit = Validated::new(Value::UInt512(debit), Validated::valid)?;
+            tracking_copy
+                .add(correlation_id, rewards_key, debit)
+                .map_err(Into::into)?;
+        }
+    }
+
+    if minted.value().is_zero() {
+        return Ok(());
+    }
+    let validated_mint_key = Validated::new(Key::URef(mint_uref), Validated::valid)?;
+    let total_supply_key = match tracking_copy
+        .read(correlation_id, &validated_mint_key)
+        .map_err(Into::into)?
+    {
+        Some(Value::Contract(contract)) => contract.urefs_lookup().get(MINT_TOTAL_SUPPLY).cloned(),
+        _ => None,
+    };
+    // Mints set up before the supply was tracked have nothing to update.
+    if let Some(total_supply_key) = total_supply_key {
+        let total_supply_key = Validated::new(total_supply_key, Validated::valid)?;
+        let minted = Validated::new(Value::UInt512(minted.value()), Validated::valid)?;
+        tracking_copy
+            .add(correlation_id, total_supply_key, minted)
+            .map_err(Into::into)?;
+    }
+    Ok(())
+}
+
+/// Moves the balance of the payment purse to the rewards purse. States set up before the engine
+/// had these purses have nothing to move.
+fn move_collected_fees<R>(
+    correlation_id: CorrelationId,
+    tracking_copy: &mut TrackingCopy<R>,
+) -> Result<(), Error>
+where
+    R: StateReader<Key, Value>,
+    R::Error: Into<execution::Error>,
+{
+    let genesis_urefs = GenesisURefsSource::default();
+    let mint_address = genesis_urefs.get_uref(MINT_PRIVATE_ADDRESS).addr();
+    let payment_purse = genesis_urefs.get_uref(PAYMENT_PURSE);
+    let rewards_purse = genesis_urefs.get_uref(REWARDS_PURSE);
+    let (payment_key, rewards_key) = match (
+        read_balance_key(correlation_id, tracking_copy, mint_address, payment_purse)?,
+        read_balance_key(correlation_id, tracking_copy, mint_address, rewards_purse)?,
+    ) {
+        (Some(payment_key), Some(rewards_key)) => (payment_key, rewards_key),
+        _ => return Ok(()),
+    };
+    let fees = match tracking_copy
+        .read(correlation_id, &payment_key)
+        .map_err(Into::into)?
+    {
+        Some(Value::UInt512(fees)) if !fees.is_zero() => fees,
+        _ => return Ok(()),
+    };
+    // Additions of U512 wrap around, so adding the two's complement of the fees empties the
+    // purse. Unlike a write, it commutes with the fees the deploys of the block pay into it.
+    let (debit, _) = U512::zero().overflowing_sub(fees);
+    let debit = Validated::new(Value::UInt512(debit), Validated::valid)?;
+    tracking_copy
+        .add(correlation_id, payment_key, debit)
+        .map_err(Into::into)?;
+    let credit = Validated::new(Value::UInt512(fees), Validated::valid)?;
+    tracking_copy
+        .add(correlation_id, rewards_key, credit)
+        .map_err(Into::into)?;
+    Ok(())
+}
+
+/// Pays the fee of a deploy of `account`, i.e. its cost at `gas_price`, from the account's main
+/// purse into the payment purse. The payment is added to the effect of `result`, so failed deploys
+/// pay too. Like `move_collected_fees`, it only adds to the balances, which commutes with the fees
+/// of the other deploys of the block. The fee is capped to the balance the deploy leaves in the
+/// purse. States set up before the engine had a payment purse collect no fees.
+fn pay_fee<R>(
+    correlation_id: CorrelationId,
+    tracking_copy: &mut TrackingCopy<R>,
+    account: Key,
+    gas_price: ConversionRate,
+    result: &mut ExecutionResult,
+) -> Result<(), Error>
+where
+    R: StateReader<Key, Value>,
+    R::Error: Into<execution::Error>,
+{
+    let (effect, cost) = match result {
+        ExecutionResult::Success { effect, cost }
+        | ExecutionResult::Failure { effect, cost, .. } => (effect, *cost),
+    };
+    let fee = Motes::from_gas(cost, gas_price).value();
+    if fee.is_zero() {
+        return Ok(());
+    }
+    let validated_account = Validated::new(account, Validated::valid)?;
+    let main_purse = match tracking_copy
+        .read(correlation_id, &validated_account)
+        .map_err(Into::into)?
+    {
+        Some(Value::Account(account)) => account.purse_id().value(),
+        _ => return Ok(()),
+    };
+    let genesis_urefs = GenesisURefsSource::default();
+    let mint_address = genesis_urefs.get_uref(MINT_PRIVATE_ADDRESS).addr();
+    let payment_purse = genesis_urefs.get_uref(PAYMENT_PURSE);
+    let (purse_key, payment_key) = match (
+        read_balance_key(correlation_id, tracking_copy, mint_address, main_purse)?,
+        read_balance_key(correlation_id, tracking_copy, mint_address, payment_purse)?,
+    ) {
+        (Some(purse_key), Some(payment_key)) => (purse_key.normalize(), payment_key.normalize()),
+        _ => return Ok(()),
+    };
+    let balance = match tracking_copy
+        .read(
+            correlation_id,
+            &Validated::new(purse_key, Validated::valid)?,
+        )
+        .map_err(Into::into)?
+    {
+        Some(balance) => balance,
+        None => return Ok(()),
+    };
+    let balance = match effect.transforms.get(&purse_key) {
+        Some(transform) => transform.clone().apply(balance).ok(),
+        None => Some(balance),
+    };
+    let fee = match balance {
+        Some(Value::UInt512(balance)) => fee.min(balance),
+        _ => return Ok(()),
+    };
+    let (debit, _) = U512::zero().overflowing_sub(fee);
+    for (key, amount) in vec![(purse_key, debit), (payment_key, fee)] {
+        ::utils::add(&mut effect.transforms, key, Transform::AddUInt512(amount));
+        ::utils::add(&mut effect.ops, key, Op::Add);
+    }
+    Ok(())
+}
+
+/// Reads the key of the balance the mint at `mint_address` holds for `purse`, `None` if it holds
+/// none.
+fn read_balance_key<R>(
+    correlation_id: CorrelationId,
+    tracking_copy: &mut TrackingCopy<R>,
+    mint_address: [u8; 32],
+    purse: URef,
+) -> Result<Option<Validated<Key>>, Error>
+where
+    R: StateReader<Key, Value>,
+    R::Error: Into<execution::Error>,
+{
+    // Purse balances are kept in the mint's local state, as set up in genesis.
+    let local_key = create_local_key(mint_address, purse.addr()).map_err(execution::Error::from)?;
+    let validated_local_key = Validated::new(local_key, Validated::valid)?;
+    match tracking_copy
+        .read(correlation_id, &validated_local_key)
+        .map_err(Into::into)?
+    {
+        Some(Value::Key(balance_key)) => Ok(Some(Validated::new(balance_key, Validated::valid)?)),
+        _ => Ok(None),
+    }
+}
+
+/// Reads the balance the mint at `mint_address` holds for `purse`, `None` if it holds none.
+fn read_purse_balance<R>(
+    correlation_id: CorrelationId,
+    tracking_copy: &mut TrackingCopy<R>,
+    mint_address: [u8; 32],
+    purse: URef,
+) -> Result<Option<U512>, Error>
+where
+    R: StateReader<Key, Value>,
+    R::Error: Into<execution::Error>,
+{
+    let balance_key = match read_balance_key(correlation_id, tracking_copy, mint_address, purse)? {
+        Some(balance_key) => balance_key,
+        None => return Ok(None),
+    };
+    match tracking_copy
+        .read(correlation_id, &balance_key)
+        .map_err(Into::into)?
+    {
+        Some(Value::UInt512(balance)) => Ok(Some(balance)),
+        _ => Ok(None),
     }
 }
 
@@ -151,13 +1242,12 @@ pub enum GetBondedValidatorsError<H: History> {
 
 /// Calculates bonded validators at `root_hash` state.
 pub fn get_bonded_validators<H: History>(
-    state: Arc<Mutex<H>>,
+    snapshots: &H::Snapshots,
     root_hash: Blake2bHash,
     pos_key: &Key, // Address of the PoS as currently bonded validators are stored in its known urefs map.
     correlation_id: CorrelationId,
 ) -> Result<HashMap<PublicKey, U512>, GetBondedValidatorsError<H>> {
-    state
-        .lock()
+    snapshots
         .checkout(root_hash)
         .map_err(GetBondedValidatorsError::StorageErrors)
         .and_then(|maybe_reader| match maybe_reader {
@@ -176,3 +1266,1287 @@ pub fn get_bonded_validators<H: History>(
             None => Err(GetBondedValidatorsError::PostStateHashNotFound(root_hash)),
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use common::key::Key;
+    use common::uref::{AccessRights, URef};
+    use common::value::account::{BlockTime, PublicKey, PurseId};
+    use common::value::{Contract, Value, U512};
+    use shared::chainspec::UpgradePoint;
+    use shared::gas::Gas;
+    use shared::motes::{ConversionRate, Motes};
+    use shared::newtypes::{Blake2bHash, CorrelationId};
+    use shared::test_utils;
+    use shared::transform::Transform;
+    use storage::global_state::in_memory::InMemoryGlobalState;
+    use storage::global_state::{CommitResult, History};
+
+    use super::account_export::AccountExportResult;
+    use super::engine_config::EngineConfig;
+    use super::error::{Error, ExecuteError, RootNotFound};
+    use super::execute_request::{DeployItem, ExecuteRequest, InvalidNoncePolicy};
+    use super::execution_effect::{ApplyEffectResult, ExecutionEffect};
+    use super::execution_result::ExecutionResult;
+    use super::genesis::{
+        imported_state_hash, GenesisResult, GenesisURefsSource, GENESIS_ACCOUNT_PURSE,
+        MINT_GENESIS_ACCOUNT_BALANCE_UREF, MINT_PAYMENT_BALANCE_UREF, MINT_REWARDS_BALANCE_UREF,
+        MINT_TOTAL_SUPPLY_UREF, PAYMENT_PURSE, POS_PURSE, REWARDS_PURSE,
+    };
+    use super::query::{
+        BalanceResult, NamedKeysResult, QueryRequest, QueryResult, TotalSupplyResult,
+    };
+    use super::rent::{self, RentConfig, RentRecord};
+    use super::staking::{StakingCheck, StakingCheckResult, StakingError, StakingRequest};
+    use super::state_file::StateExportResult;
+    use super::upgrade::{
+        AddSystemPurses, Migration, NoMigration, SystemContract, UpgradeRequest, UpgradeResult,
+    };
+    use super::utils::pos_validator_key;
+    use super::{pay_fee, EngineState};
+    use execution;
+    use protocol_registry::{ProtocolLimits, ProtocolRegistry, ProtocolSemantics};
+    use storage::global_state::StateReader;
+    use tracking_copy::TrackingCopy;
+
+    fn engine_state_with(
+        key: Key,
+        value: Value,
+    ) -> (EngineState<InMemoryGlobalState>, Blake2bHash) {
+        let global_state =
+            InMemoryGlobalState::from_pairs(CorrelationId::new(), &[(key, value)]).unwrap();
+        let root_hash = global_state.current_root();
+        (EngineState::new(global_state), root_hash)
+    }
+
+    #[test]
+    fn deploy_state_root_is_that_of_the_commit() {
+        let correlation_id = CorrelationId::new();
+        let key = Key::Hash([1u8; 32]);
+        let (engine_state, root_hash) = engine_state_with(key, Value::Int32(1));
+
+        let mut transforms = HashMap::new();
+        transforms.insert(key, Transform::AddInt32(2));
+        let effect = ExecutionEffect::new(HashMap::new(), transforms.clone());
+
+        let state_root = engine_state
+            .deploy_state_root(correlation_id, root_hash, &effect)
+            .unwrap()
+            .expect("should have a state root");
+        assert!(engine_state
+            .state()
+            .lock()
+            .checkout(state_root)
+            .unwrap()
+            .is_none());
+
+        match engine_state
+            .apply_effect(correlation_id, root_hash, None, transforms, None)
+            .unwrap()
+        {
+            ApplyEffectResult::Applied(CommitResult::Success(post_state_hash, _)) => {
+                assert_eq!(post_state_hash, state_root)
+            }
+            other => panic!("expected a successful commit, got {:?}", other),
+        }
+
+        let mut transforms = HashMap::new();
+        transforms.insert(Key::Hash([2u8; 32]), Transform::AddInt32(2));
+        let missing_key = ExecutionEffect::new(HashMap::new(), transforms);
+        assert_eq!(
+            engine_state
+                .deploy_state_root(correlation_id, root_hash, &missing_key)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn apply_effect_should_not_store_an_unexpected_poststate() {
+        let correlation_id = CorrelationId::new();
+        let key = Key::Hash([1u8; 32]);
+        let (engine_state, root_hash) = engine_state_with(key, Value::Int32(1));
+
+        let mut transforms = HashMap::new();
+        transforms.insert(key, Transform::AddInt32(2));
+        let effect = ExecutionEffect::new(HashMap::new(), transforms.clone());
+        let computed_root = engine_state
+            .deploy_state_root(correlation_id, root_hash, &effect)
+            .unwrap()
+            .expect("should have a state root");
+
+        let expected = Blake2bHash::new(&[0u8; 32]);
+        match engine_state
+            .apply_effect(
+                correlation_id,
+                root_hash,
+                None,
+                transforms.clone(),
+                Some(expected),
+            )
+            .unwrap()
+        {
+            ApplyEffectResult::Applied(CommitResult::PoststateMismatch {
+                expected: mismatch_expected,
+                computed,
+            }) => {
+                assert_eq!(mismatch_expected, expected);
+                assert_eq!(computed, computed_root);
+            }
+            other => panic!("expected a post state mismatch, got {:?}", other),
+        }
+        assert!(engine_state
+            .state()
+            .lock()
+            .checkout(computed_root)
+            .unwrap()
+            .is_none());
+
+        assert_matches!(
+            engine_state
+                .apply_effect(correlation_id, root_hash, None, transforms, Some(computed_root))
+                .unwrap(),
+            ApplyEffectResult::Applied(CommitResult::Success(post_state_hash, _))
+                if post_state_hash == computed_root
+        );
+    }
+
+    #[test]
+    fn run_query_reads_value_under_key() {
+        let correlation_id = CorrelationId::new();
+        let key = Key::Hash([1u8; 32]);
+        let (engine_state, root_hash) = engine_state_with(key, Value::Int32(1));
+
+        let request = QueryRequest {
+            state_hash: root_hash,
+            base_key: key,
+            path: vec![],
+        };
+        assert_matches!(
+            engine_state.run_query(correlation_id, &request),
+            Ok(QueryResult::Success(Value::Int32(1)))
+        );
+
+        let missing_root = QueryRequest {
+            state_hash: [2u8; 32].into(),
+            ..request
+        };
+        assert_matches!(
+            engine_state.run_query(correlation_id, &missing_root),
+            Ok(QueryResult::RootNotFound)
+        );
+    }
+
+    #[test]
+    fn run_query_does_not_wait_for_commits() {
+        let correlation_id = CorrelationId::new();
+        let key = Key::Hash([1u8; 32]);
+        let (engine_state, root_hash) = engine_state_with(key, Value::Int32(1));
+        let state = engine_state.state();
+        // Held by commits while they write.
+        let _commit_in_progress = state.lock();
+
+        let request = QueryRequest {
+            state_hash: root_hash,
+            base_key: key,
+            path: vec![],
+        };
+        assert_matches!(
+            engine_state.run_query(correlation_id, &request),
+            Ok(QueryResult::Success(Value::Int32(1)))
+        );
+    }
+
+    #[test]
+    fn import_genesis_state_arrives_at_genesis_root() {
+        let correlation_id = CorrelationId::new();
+        let (_, genesis_hash, effect) =
+            committed_genesis_with(vec![(PublicKey::new([6u8; 32]), U512::from(300))]);
+        let entries: BTreeMap<Key, Value> = effect
+            .transforms
+            .into_iter()
+            .map(|(key, transform)| match transform {
+                Transform::Write(value) => (key, value),
+                other => panic!("genesis should only write values, got {:?}", other),
+            })
+            .collect();
+
+        let imported = EngineState::new(InMemoryGlobalState::empty().unwrap());
+        assert_matches!(
+            imported.import_genesis_state(correlation_id, entries, 1),
+            Ok(GenesisResult::Success { post_state_hash, .. }) if post_state_hash == genesis_hash
+        );
+    }
+
+    #[test]
+    fn export_state_round_trips_through_import() {
+        let (engine_state, genesis_hash) = committed_genesis();
+        let entries = match engine_state.export_state(genesis_hash) {
+            Ok(StateExportResult::Success(entries)) => entries,
+            other => panic!("export failed: {:?}", other),
+        };
+        assert_eq!(imported_state_hash(&entries).unwrap(), genesis_hash);
+
+        let imported = EngineState::new(InMemoryGlobalState::empty().unwrap());
+        assert_matches!(
+            imported.import_genesis_state(CorrelationId::new(), entries, 1),
+            Ok(GenesisResult::Success { post_state_hash, .. }) if post_state_hash == genesis_hash
+        );
+        assert_matches!(
+            engine_state.export_state([2u8; 32].into()),
+            Ok(StateExportResult::RootNotFound)
+        );
+    }
+
+    #[test]
+    fn import_genesis_state_rejects_invalid_values() {
+        let mut protocols = ProtocolRegistry::default();
+        let semantics = protocols.get(1).unwrap().clone();
+        protocols.register(
+            1,
+            ProtocolSemantics {
+                limits: ProtocolLimits {
+                    max_value_size: 8,
+                    ..ProtocolLimits::default()
+                },
+                ..semantics
+            },
+        );
+        let engine_state = EngineState::with_protocols(
+            InMemoryGlobalState::empty().unwrap(),
+            EngineConfig::default(),
+            protocols,
+        );
+        let mut entries = BTreeMap::new();
+        entries.insert(Key::Hash([1u8; 32]), Value::ByteArray(vec![0u8; 100]));
+
+        assert_matches!(
+            engine_state.import_genesis_state(CorrelationId::new(), entries.clone(), 1),
+            Err(Error::ExecError(execution::Error::Validation(_)))
+        );
+        assert_matches!(
+            engine_state.import_genesis_state(CorrelationId::new(), entries, 3),
+            Err(Error::ExecError(execution::Error::ResolverError(_)))
+        );
+    }
+
+    #[test]
+    fn export_accounts_lists_balances_and_named_keys() {
+        let correlation_id = CorrelationId::new();
+        let (engine_state, post_state_hash) = committed_genesis();
+
+        let records = match engine_state.export_accounts(correlation_id, post_state_hash) {
+            Ok(AccountExportResult::Success(records)) => records,
+            other => panic!("export failed: {:?}", other),
+        };
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].account, PublicKey::new([6u8; 32]));
+        assert_eq!(records[0].balance, Some(U512::from(1000)));
+        assert!(records[0].named_keys.contains_key("mint"));
+
+        assert_matches!(
+            engine_state.export_accounts(correlation_id, Blake2bHash::new(&[1u8])),
+            Ok(AccountExportResult::RootNotFound)
+        );
+    }
+
+    #[test]
+    fn run_rent_collection_marks_expired_keys() {
+        let correlation_id = CorrelationId::new();
+        let record = |paid_through, reclaimable| {
+            rent::record_to_value(RentRecord {
+                paid_through: BlockTime(paid_through),
+                reclaimable,
+            })
+            .unwrap()
+        };
+        let expired_key = Key::Hash([1u8; 32]);
+        let paid_key = Key::Hash([2u8; 32]);
+        let reclaimable_key = Key::Hash([3u8; 32]);
+        let genesis_key = Key::Hash([4u8; 32]);
+        let repaid_key = Key::Hash([5u8; 32]);
+        let global_state = InMemoryGlobalState::from_pairs(
+            correlation_id,
+            &[
+                (expired_key, Value::Int32(1)),
+                (rent::rent_key(&expired_key), record(500, false)),
+                (paid_key, Value::Int32(1)),
+                (rent::rent_key(&paid_key), record(5_000, false)),
+                (reclaimable_key, Value::Int32(1)),
+                (rent::rent_key(&reclaimable_key), record(500, true)),
+                (genesis_key, Value::Int32(1)),
+                (repaid_key, Value::Int32(1)),
+                (rent::rent_key(&repaid_key), record(500, false)),
+            ],
+        )
+        .unwrap();
+        let root_hash = global_state.current_root();
+        let mut protocols = ProtocolRegistry::default();
+        let semantics = protocols.get(1).unwrap().clone();
+        protocols.register(
+            1,
+            ProtocolSemantics {
+                limits: ProtocolLimits {
+                    rent: Some(RentConfig {
+                        price_per_byte_day: 1,
+                        grace_period_millis: 100,
+                    }),
+                    ..ProtocolLimits::default()
+                },
+                ..semantics
+            },
+        );
+        let engine_state =
+            EngineState::with_protocols(global_state, EngineConfig::default(), protocols);
+        // A deploy of the block paid the rent of `repaid_key`.
+        let mut transforms = HashMap::new();
+        transforms.insert(
+            rent::rent_key(&repaid_key),
+            Transform::Write(record(5_000, false)),
+        );
+        let deploy_result = ExecutionResult::Success {
+            effect: ExecutionEffect::new(HashMap::new(), transforms),
+            cost: Gas::new(1),
+        };
+
+        match engine_state.run_rent_collection(
+            correlation_id,
+            root_hash,
+            BlockTime(1_000),
+            1,
+            &[deploy_result],
+        ) {
+            Ok(ExecutionResult::Success { effect, cost }) if cost == Gas::default() => {
+                let mut expected = HashMap::new();
+                expected.insert(
+                    rent::rent_key(&expired_key),
+                    Transform::Write(record(500, true)),
+                );
+                assert_eq!(effect.transforms, expected);
+            }
+            other => panic!("expected a successful rent collection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn run_rent_collection_does_nothing_without_rent() {
+        let key = Key::Hash([1u8; 32]);
+        let expired = rent::record_to_value(RentRecord {
+            paid_through: BlockTime(500),
+            reclaimable: false,
+        })
+        .unwrap();
+        let global_state = InMemoryGlobalState::from_pairs(
+            CorrelationId::new(),
+            &[(key, Value::Int32(1)), (rent::rent_key(&key), expired)],
+        )
+        .unwrap();
+        let root_hash = global_state.current_root();
+        let engine_state = EngineState::new(global_state);
+
+        match engine_state.run_rent_collection(
+            CorrelationId::new(),
+            root_hash,
+            BlockTime(1_000),
+            1,
+            &[],
+        ) {
+            Ok(ExecutionResult::Success { effect, .. }) => assert!(effect.transforms.is_empty()),
+            other => panic!("expected a successful rent collection, got {:?}", other),
+        }
+        assert_matches!(
+            engine_state.run_rent_collection(
+                CorrelationId::new(),
+                Blake2bHash::new(&[1u8]),
+                BlockTime(1_000),
+                1,
+                &[]
+            ),
+            Err(RootNotFound(_))
+        );
+    }
+
+    #[test]
+    fn run_finalization_pays_rewards_to_bonded_validators() {
+        let correlation_id = CorrelationId::new();
+        let (engine_state, post_state_hash, _) = committed_genesis_with(vec![
+            (PublicKey::new([6u8; 32]), U512::from(300)),
+            // Has no account, so it forfeits its share.
+            (PublicKey::new([7u8; 32]), U512::from(100)),
+        ]);
+
+        let genesis_urefs = GenesisURefsSource::default();
+        let balance_key =
+            Key::URef(genesis_urefs.get_uref(MINT_GENESIS_ACCOUNT_BALANCE_UREF)).normalize();
+        let total_supply_key =
+            Key::URef(genesis_urefs.get_uref(MINT_TOTAL_SUPPLY_UREF)).normalize();
+        match engine_state.run_finalization(
+            correlation_id,
+            post_state_hash,
+            Motes::new(U512::from(40)),
+        ) {
+            Ok(ExecutionResult::Success { effect, cost }) if cost == Gas::default() => {
+                assert_eq!(
+                    effect.transforms.get(&balance_key),
+                    Some(&Transform::AddUInt512(U512::from(30)))
+                );
+                // Forfeited shares are not minted.
+                assert_eq!(
+                    effect.transforms.get(&total_supply_key),
+                    Some(&Transform::AddUInt512(U512::from(30)))
+                );
+                let rewards = effect
+                    .transforms
+                    .values()
+                    .filter(|transform| match transform {
+                        Transform::AddUInt512(_) => true,
+                        _ => false,
+                    })
+                    .count();
+                assert_eq!(rewards, 2);
+            }
+            other => panic!("unexpected finalization result: {:?}", other),
+        }
+
+        let missing_root: Blake2bHash = [2u8; 32].into();
+        assert_matches!(
+            engine_state.run_finalization(correlation_id, missing_root, Motes::new(U512::from(40))),
+            Err(RootNotFound(_))
+        );
+    }
+
+    #[test]
+    fn run_finalization_moves_fees_to_rewards_purse() {
+        let correlation_id = CorrelationId::new();
+        let (engine_state, genesis_hash) = committed_genesis();
+        let upgraded_hash = upgraded_with_system_purses(&engine_state, genesis_hash);
+        let genesis_urefs = GenesisURefsSource::default();
+        let payment_purse = PurseId::new(genesis_urefs.get_uref(PAYMENT_PURSE));
+        let rewards_purse = PurseId::new(genesis_urefs.get_uref(REWARDS_PURSE));
+        let balance = |state_hash, purse_id| match engine_state.get_purse_balance(
+            correlation_id,
+            state_hash,
+            purse_id,
+        ) {
+            Ok(BalanceResult::Success(balance)) => balance,
+            other => panic!("unexpected balance result: {:?}", other),
+        };
+        assert_eq!(balance(upgraded_hash, payment_purse), U512::zero());
+
+        let payment_key = Key::URef(genesis_urefs.get_uref(MINT_PAYMENT_BALANCE_UREF)).normalize();
+        let mut transforms = HashMap::new();
+        transforms.insert(payment_key, Transform::AddUInt512(U512::from(50)));
+        let fees_hash = match engine_state
+            .apply_effect(correlation_id, upgraded_hash, None, transforms, None)
+            .unwrap()
+        {
+            ApplyEffectResult::Applied(CommitResult::Success(post_state_hash, _)) => {
+                post_state_hash
+            }
+            other => panic!("expected a successful commit, got {:?}", other),
+        };
+
+        let effect =
+            match engine_state.run_finalization(correlation_id, fees_hash, Motes::default()) {
+                Ok(ExecutionResult::Success { effect, .. }) => effect,
+                other => panic!("unexpected finalization result: {:?}", other),
+            };
+        let finalized_hash = match engine_state
+            .apply_effect(correlation_id, fees_hash, None, effect.transforms, None)
+            .unwrap()
+        {
+            ApplyEffectResult::Applied(CommitResult::Success(post_state_hash, _)) => {
+                post_state_hash
+            }
+            other => panic!("expected a successful commit, got {:?}", other),
+        };
+        assert_eq!(balance(finalized_hash, payment_purse), U512::zero());
+        assert_eq!(balance(finalized_hash, rewards_purse), U512::from(50));
+    }
+
+    #[test]
+    fn run_finalization_pays_collected_fees_by_stake() {
+        let correlation_id = CorrelationId::new();
+        let (engine_state, genesis_hash, _) = committed_genesis_with(vec![
+            (PublicKey::new([6u8; 32]), U512::from(300)),
+            // Has no account, so its share stays in the rewards purse.
+            (PublicKey::new([7u8; 32]), U512::from(100)),
+        ]);
+        let upgraded_hash = upgraded_with_system_purses(&engine_state, genesis_hash);
+        let genesis_urefs = GenesisURefsSource::default();
+        let validator_purse = PurseId::new(genesis_urefs.get_uref(GENESIS_ACCOUNT_PURSE));
+        let rewards_purse = PurseId::new(genesis_urefs.get_uref(REWARDS_PURSE));
+        let balance = |state_hash, purse_id| match engine_state.get_purse_balance(
+            correlation_id,
+            state_hash,
+            purse_id,
+        ) {
+            Ok(BalanceResult::Success(balance)) => balance,
+            other => panic!("unexpected balance result: {:?}", other),
+        };
+
+        let rewards_key = Key::URef(genesis_urefs.get_uref(MINT_REWARDS_BALANCE_UREF)).normalize();
+        let mut transforms = HashMap::new();
+        transforms.insert(rewards_key, Transform::AddUInt512(U512::from(80)));
+        let fees_hash = match engine_state
+            .apply_effect(correlation_id, upgraded_hash, None, transforms, None)
+            .unwrap()
+        {
+            ApplyEffectResult::Applied(CommitResult::Success(post_state_hash, _)) => {
+                post_state_hash
+            }
+            other => panic!("expected a successful commit, got {:?}", other),
+        };
+
+        let effect = match engine_state.run_finalization(
+            correlation_id,
+            fees_hash,
+            Motes::new(U512::from(40)),
+        ) {
+            Ok(ExecutionResult::Success { effect, .. }) => effect,
+            other => panic!("unexpected finalization result: {:?}", other),
+        };
+        let finalized_hash = match engine_state
+            .apply_effect(correlation_id, fees_hash, None, effect.transforms, None)
+            .unwrap()
+        {
+            ApplyEffectResult::Applied(CommitResult::Success(post_state_hash, _)) => {
+                post_state_hash
+            }
+            other => panic!("expected a successful commit, got {:?}", other),
+        };
+        // 30 of the block reward and 60 of the fees.
+        assert_eq!(
+            balance(finalized_hash, validator_purse),
+            balance(fees_hash, validator_purse) + U512::from(90)
+        );
+        assert_eq!(balance(finalized_hash, rewards_purse), U512::from(20));
+    }
+
+    #[test]
+    fn pay_fee_moves_cost_at_gas_price_to_payment_purse() {
+        let correlation_id = CorrelationId::new();
+        let (engine_state, genesis_hash) = committed_genesis();
+        let account = Key::Account([6u8; 32]);
+        let genesis_urefs = GenesisURefsSource::default();
+        let payment_purse = PurseId::new(genesis_urefs.get_uref(PAYMENT_PURSE));
+        let fee_effect = |state_hash, cost| {
+            let mut tracking_copy = engine_state.tracking_copy(state_hash).unwrap().unwrap();
+            let mut result = ExecutionResult::Success {
+                effect: ExecutionEffect::default(),
+                cost: Gas::new(cost),
+            };
+            pay_fee(
+                correlation_id,
+                &mut tracking_copy,
+                account,
+                ConversionRate::new(2),
+                &mut result,
+            )
+            .unwrap();
+            match result {
+                ExecutionResult::Success { effect, .. } => effect,
+                other => panic!("unexpected result: {:?}", other),
+            }
+        };
+        let commit = |state_hash, effect: ExecutionEffect| match engine_state
+            .apply_effect(
+                correlation_id,
+                state_hash,
+                Some(&effect.ops),
+                effect.transforms,
+                None,
+            )
+            .unwrap()
+        {
+            ApplyEffectResult::Applied(CommitResult::Success(post_state_hash, _)) => {
+                post_state_hash
+            }
+            other => panic!("expected a successful commit, got {:?}", other),
+        };
+        let balance = |state_hash, purse_id| match engine_state.get_purse_balance(
+            correlation_id,
+            state_hash,
+            purse_id,
+        ) {
+            Ok(BalanceResult::Success(balance)) => balance,
+            other => panic!("unexpected balance result: {:?}", other),
+        };
+
+        // Without a payment purse there is nowhere to pay to.
+        assert!(fee_effect(genesis_hash, 100).transforms.is_empty());
+
+        let upgraded_hash = upgraded_with_system_purses(&engine_state, genesis_hash);
+        let main_purse = match engine_state.run_query(
+            correlation_id,
+            &QueryRequest {
+                state_hash: upgraded_hash,
+                base_key: account,
+                path: vec![],
+            },
+        ) {
+            Ok(QueryResult::Success(Value::Account(account))) => account.purse_id(),
+            other => panic!("unexpected query result: {:?}", other),
+        };
+        let paid_hash = commit(upgraded_hash, fee_effect(upgraded_hash, 100));
+        assert_eq!(balance(paid_hash, main_purse), U512::from(800));
+        assert_eq!(balance(paid_hash, payment_purse), U512::from(200));
+
+        // The fee can't exceed the balance.
+        let drained_hash = commit(paid_hash, fee_effect(paid_hash, 1000));
+        assert_eq!(balance(drained_hash, main_purse), U512::zero());
+        assert_eq!(balance(drained_hash, payment_purse), U512::from(1000));
+    }
+
+    #[test]
+    fn get_named_keys_lists_contract_urefs() {
+        let correlation_id = CorrelationId::new();
+        let key = Key::Hash([1u8; 32]);
+        let named_keys: BTreeMap<String, Key> = vec![("counter".to_string(), Key::Hash([2u8; 32]))]
+            .into_iter()
+            .collect();
+        let contract = Contract::new(vec![], named_keys.clone(), 1);
+        let (engine_state, root_hash) = engine_state_with(key, Value::Contract(contract));
+
+        assert_matches!(
+            engine_state.get_named_keys(correlation_id, root_hash, key),
+            Ok(NamedKeysResult::Success(ref keys)) if *keys == named_keys
+        );
+        assert_matches!(
+            engine_state.get_named_keys(correlation_id, root_hash, Key::Hash([3u8; 32])),
+            Ok(NamedKeysResult::ValueNotFound)
+        );
+        assert_matches!(
+            engine_state.get_named_keys(correlation_id, [2u8; 32].into(), key),
+            Ok(NamedKeysResult::RootNotFound)
+        );
+
+        let (engine_state, root_hash) = engine_state_with(key, Value::Int32(1));
+        assert_matches!(
+            engine_state.get_named_keys(correlation_id, root_hash, key),
+            Ok(NamedKeysResult::InvalidType(_))
+        );
+    }
+
+    #[test]
+    fn run_staking_check_uses_pos_stakes() {
+        let correlation_id = CorrelationId::new();
+        let validator = PublicKey::new([1u8; 32]);
+        let named_keys: BTreeMap<String, Key> = vec![(
+            pos_validator_key(validator, U512::from(100)),
+            Key::Hash([0u8; 32]),
+        )]
+        .into_iter()
+        .collect();
+        let pos_key = Key::URef(GenesisURefsSource::default().get_pos_address());
+        let contract = Contract::new(vec![], named_keys, 1);
+        let (engine_state, root_hash) = engine_state_with(pos_key, Value::Contract(contract));
+
+        let check = StakingCheck {
+            state_hash: root_hash,
+            validator,
+            timestamp: BlockTime(10),
+            request: StakingRequest::Bond(U512::from(50)),
+        };
+        assert_matches!(
+            engine_state.run_staking_check(correlation_id, &check),
+            Ok(StakingCheckResult::Valid { stake, due: BlockTime(10) }) if stake == U512::from(150)
+        );
+
+        let unbond = StakingCheck {
+            request: StakingRequest::Unbond(None),
+            ..check.clone()
+        };
+        assert_matches!(
+            engine_state.run_staking_check(correlation_id, &unbond),
+            Ok(StakingCheckResult::Invalid(
+                StakingError::CannotUnbondLastValidator
+            ))
+        );
+
+        let missing_root = StakingCheck {
+            state_hash: [2u8; 32].into(),
+            ..check
+        };
+        assert_matches!(
+            engine_state.run_staking_check(correlation_id, &missing_root),
+            Ok(StakingCheckResult::RootNotFound(_))
+        );
+    }
+
+    #[test]
+    fn run_total_supply_query_reads_supply_tracked_by_mint() {
+        let correlation_id = CorrelationId::new();
+        let (engine_state, post_state_hash) = committed_genesis();
+
+        match engine_state.run_total_supply_query(correlation_id, post_state_hash) {
+            Ok(TotalSupplyResult::Success(total_supply)) => {
+                assert_eq!(total_supply, U512::from(1300))
+            }
+            other => panic!("unexpected total supply result: {:?}", other),
+        }
+
+        let (engine_state, root_hash) = engine_state_with(Key::Hash([1u8; 32]), Value::Int32(1));
+        assert_matches!(
+            engine_state.run_total_supply_query(correlation_id, root_hash),
+            Ok(TotalSupplyResult::NotTracked)
+        );
+        assert_matches!(
+            engine_state.run_total_supply_query(correlation_id, [2u8; 32].into()),
+            Ok(TotalSupplyResult::RootNotFound)
+        );
+    }
+
+    #[test]
+    fn get_purse_balance_reads_genesis_balances() {
+        let correlation_id = CorrelationId::new();
+        let (engine_state, post_state_hash) = committed_genesis();
+        let query_request = QueryRequest {
+            state_hash: post_state_hash,
+            base_key: Key::Account([6u8; 32]),
+            path: vec![],
+        };
+        let main_purse = match engine_state.run_query(correlation_id, &query_request) {
+            Ok(QueryResult::Success(Value::Account(account))) => account.purse_id(),
+            other => panic!("unexpected query result: {:?}", other),
+        };
+        let pos_purse = PurseId::new(GenesisURefsSource::default().get_uref(POS_PURSE));
+
+        for (purse_id, expected) in vec![(main_purse, 1000), (pos_purse, 300)] {
+            match engine_state.get_purse_balance(correlation_id, post_state_hash, purse_id) {
+                Ok(BalanceResult::Success(balance)) => assert_eq!(balance, U512::from(expected)),
+                other => panic!("unexpected balance result: {:?}", other),
+            }
+        }
+
+        let unknown_purse = PurseId::new(URef::new([9u8; 32], AccessRights::READ_ADD_WRITE));
+        assert_matches!(
+            engine_state.get_purse_balance(correlation_id, post_state_hash, unknown_purse),
+            Ok(BalanceResult::PurseNotFound)
+        );
+        assert_matches!(
+            engine_state.get_purse_balance(correlation_id, Blake2bHash::new(&[1u8]), main_purse),
+            Ok(BalanceResult::RootNotFound)
+        );
+    }
+
+    fn committed_genesis() -> (EngineState<InMemoryGlobalState>, Blake2bHash) {
+        let genesis_validators = vec![(PublicKey::new([6u8; 32]), U512::from(300))];
+        let (engine_state, post_state_hash, _) = committed_genesis_with(genesis_validators);
+        (engine_state, post_state_hash)
+    }
+
+    /// Commits genesis of the account `[6u8; 32]` holding 1000 motes.
+    fn committed_genesis_with(
+        genesis_validators: Vec<(PublicKey, U512)>,
+    ) -> (
+        EngineState<InMemoryGlobalState>,
+        Blake2bHash,
+        ExecutionEffect,
+    ) {
+        let engine_state = EngineState::new(InMemoryGlobalState::empty().unwrap());
+        let wasm_bytes = test_utils::create_empty_wasm_module_bytes();
+        let (post_state_hash, effect) = match engine_state.commit_genesis(
+            CorrelationId::new(),
+            [6u8; 32],
+            U512::from(1000),
+            &wasm_bytes,
+            &wasm_bytes,
+            genesis_validators,
+            1,
+        ) {
+            Ok(GenesisResult::Success {
+                post_state_hash,
+                effect,
+            }) => (post_state_hash, effect),
+            Ok(other) => panic!("genesis failed: {}", other),
+            Err(error) => panic!("genesis failed: {}", error),
+        };
+        (engine_state, post_state_hash, effect)
+    }
+
+    fn upgraded_contract(result: UpgradeResult, contract: SystemContract) -> Contract {
+        match result {
+            UpgradeResult::Success { effect } => {
+                match effect.transforms.get(&contract.key().normalize()) {
+                    Some(Transform::Write(Value::Contract(contract))) => contract.clone(),
+                    other => panic!("unexpected transform: {:?}", other),
+                }
+            }
+            other => panic!("upgrade failed: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn run_upgrade_replaces_code_and_keeps_named_keys() {
+        let correlation_id = CorrelationId::new();
+        let (engine_state, post_state_hash) = committed_genesis();
+        let new_code = test_utils::create_empty_wasm_module_bytes();
+        let request = UpgradeRequest {
+            parent_state_hash: post_state_hash,
+            protocol_version: 2,
+            new_code: vec![(SystemContract::ProofOfStake, new_code)]
+                .into_iter()
+                .collect(),
+        };
+
+        let result = engine_state.run_upgrade(correlation_id, &request, &NoMigration);
+        let pos = upgraded_contract(result.unwrap(), SystemContract::ProofOfStake);
+        assert_eq!(pos.protocol_version(), 2);
+        assert!(pos.urefs_lookup().contains_key(&pos_validator_key(
+            PublicKey::new([6u8; 32]),
+            U512::from(300)
+        )));
+
+        assert_matches!(
+            engine_state.run_upgrade(
+                correlation_id,
+                &UpgradeRequest {
+                    protocol_version: 1,
+                    ..request.clone()
+                },
+                &NoMigration
+            ),
+            Ok(UpgradeResult::InvalidProtocolVersion {
+                contract: SystemContract::ProofOfStake,
+                current: 1,
+                requested: 1,
+            })
+        );
+        assert_matches!(
+            engine_state.run_upgrade(
+                correlation_id,
+                &UpgradeRequest {
+                    parent_state_hash: [2u8; 32].into(),
+                    ..request
+                },
+                &NoMigration
+            ),
+            Ok(UpgradeResult::RootNotFound(_))
+        );
+    }
+
+    #[test]
+    fn upgrade_request_from_upgrade_point_only_replaces_the_listed_contracts() {
+        let new_code = test_utils::create_empty_wasm_module_bytes();
+        let upgrade = UpgradePoint {
+            protocol_version: 2,
+            mint_code: None,
+            pos_code: Some(new_code.clone()),
+        };
+
+        let request = UpgradeRequest::from_upgrade_point(&upgrade, [1u8; 32].into());
+        assert_eq!(request.parent_state_hash, [1u8; 32].into());
+        assert_eq!(request.protocol_version, 2);
+        assert_eq!(
+            request.new_code,
+            vec![(SystemContract::ProofOfStake, new_code)]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    /// Commits the upgrade of both system contracts setting up the payment and rewards purses.
+    fn upgraded_with_system_purses(
+        engine_state: &EngineState<InMemoryGlobalState>,
+        parent_state_hash: Blake2bHash,
+    ) -> Blake2bHash {
+        let correlation_id = CorrelationId::new();
+        let new_code = test_utils::create_empty_wasm_module_bytes();
+        let request = UpgradeRequest {
+            parent_state_hash,
+            protocol_version: 2,
+            new_code: vec![
+                (SystemContract::Mint, new_code.clone()),
+                (SystemContract::ProofOfStake, new_code),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let effect = match engine_state.run_upgrade(correlation_id, &request, &AddSystemPurses) {
+            Ok(UpgradeResult::Success { effect }) => effect,
+            other => panic!("upgrade failed: {:?}", other),
+        };
+        match engine_state
+            .apply_effect(
+                correlation_id,
+                parent_state_hash,
+                None,
+                effect.transforms,
+                None,
+            )
+            .unwrap()
+        {
+            ApplyEffectResult::Applied(CommitResult::Success(post_state_hash, _)) => {
+                post_state_hash
+            }
+            other => panic!("expected a successful commit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn run_upgrade_adds_system_purses() {
+        let correlation_id = CorrelationId::new();
+        let (engine_state, genesis_hash) = committed_genesis();
+        let genesis_urefs = GenesisURefsSource::default();
+        let new_code = test_utils::create_empty_wasm_module_bytes();
+
+        // Proof of stake can't name purses the mint holds no balance for.
+        let request = UpgradeRequest {
+            parent_state_hash: genesis_hash,
+            protocol_version: 2,
+            new_code: vec![(SystemContract::ProofOfStake, new_code.clone())]
+                .into_iter()
+                .collect(),
+        };
+        assert_matches!(
+            engine_state.run_upgrade(correlation_id, &request, &AddSystemPurses),
+            Ok(UpgradeResult::MigrationFailed(SystemContract::ProofOfStake, _))
+        );
+
+        let upgraded_hash = upgraded_with_system_purses(&engine_state, genesis_hash);
+        for &label in &[PAYMENT_PURSE, REWARDS_PURSE] {
+            assert_matches!(
+                engine_state.get_purse_balance(
+                    correlation_id,
+                    upgraded_hash,
+                    PurseId::new(genesis_urefs.get_uref(label))
+                ),
+                Ok(BalanceResult::Success(ref balance)) if balance.is_zero()
+            );
+        }
+
+        // Later upgrades keep the purses as they are.
+        let request = UpgradeRequest {
+            parent_state_hash: upgraded_hash,
+            protocol_version: 3,
+            new_code: vec![(SystemContract::ProofOfStake, new_code)]
+                .into_iter()
+                .collect(),
+        };
+        let result = engine_state.run_upgrade(correlation_id, &request, &AddSystemPurses);
+        let pos = upgraded_contract(result.unwrap(), SystemContract::ProofOfStake);
+        assert_eq!(
+            pos.urefs_lookup().get(PAYMENT_PURSE),
+            Some(&Key::URef(genesis_urefs.get_uref(PAYMENT_PURSE)))
+        );
+    }
+
+    struct RenameKey(&'static str, &'static str);
+
+    impl Migration for RenameKey {
+        fn migrate<R>(
+            &self,
+            _correlation_id: CorrelationId,
+            _contract: SystemContract,
+            named_keys: &mut BTreeMap<String, Key>,
+            _tracking_copy: &mut TrackingCopy<R>,
+        ) -> Result<(), String>
+        where
+            R: StateReader<Key, Value>,
+            R::Error: Into<execution::Error>,
+        {
+            let key = named_keys
+                .remove(self.0)
+                .ok_or_else(|| format!("missing named key {}", self.0))?;
+            named_keys.insert(self.1.to_string(), key);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn run_upgrade_applies_migration() {
+        let correlation_id = CorrelationId::new();
+        let (engine_state, post_state_hash) = committed_genesis();
+        let request = UpgradeRequest {
+            parent_state_hash: post_state_hash,
+            protocol_version: 2,
+            new_code: vec![(
+                SystemContract::Mint,
+                test_utils::create_empty_wasm_module_bytes(),
+            )]
+            .into_iter()
+            .collect(),
+        };
+
+        let result = engine_state.run_upgrade(
+            correlation_id,
+            &request,
+            &RenameKey("total_supply", "supply"),
+        );
+        let mint = upgraded_contract(result.unwrap(), SystemContract::Mint);
+        assert!(mint.urefs_lookup().contains_key("supply"));
+        assert!(!mint.urefs_lookup().contains_key("total_supply"));
+
+        assert_matches!(
+            engine_state.run_upgrade(correlation_id, &request, &RenameKey("missing", "x")),
+            Ok(UpgradeResult::MigrationFailed(SystemContract::Mint, _))
+        );
+    }
+
+    #[test]
+    fn run_execute_rejects_invalid_address() {
+        let correlation_id = CorrelationId::new();
+        let (engine_state, root_hash) = engine_state_with(Key::Hash([1u8; 32]), Value::Int32(1));
+
+        let deploy = DeployItem {
+            address: vec![1u8; 31],
+            session_code: vec![],
+            session_args: vec![],
+            gas_limit: Gas::new(1000),
+            gas_price: ConversionRate::new(1),
+            nonce: 1,
+        };
+        let request = ExecuteRequest {
+            parent_state_hash: root_hash,
+            block_time: BlockTime(0),
+            deploys: vec![deploy],
+            protocol_version: 1,
+            invalid_nonce_policy: InvalidNoncePolicy::default(),
+            check_deploy_order: false,
+        };
+        let results = engine_state.run_execute(correlation_id, request).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_matches!(
+            results[0],
+            ExecutionResult::Failure {
+                error: Error::InvalidPublicKeyLength {
+                    expected: 32,
+                    actual: 31
+                },
+                cost,
+                ..
+            } if cost == Gas::default()
+        );
+    }
+
+    #[test]
+    fn run_execute_rejects_oversized_deploys() {
+        let correlation_id = CorrelationId::new();
+        let global_state = InMemoryGlobalState::empty().unwrap();
+        let root_hash = global_state.current_root();
+        let config = EngineConfig {
+            max_deploy_size: 100,
+            max_deploy_args_size: 10,
+            ..EngineConfig::default()
+        };
+        let engine_state = EngineState::with_config(global_state, config);
+
+        let deploy = |session_code_size: usize, session_args_size: usize| DeployItem {
+            address: vec![1u8; 32],
+            session_code: vec![0u8; session_code_size],
+            session_args: vec![0u8; session_args_size],
+            gas_limit: Gas::new(1000),
+            gas_price: ConversionRate::new(1),
+            nonce: 1,
+        };
+        let request = ExecuteRequest {
+            parent_state_hash: root_hash,
+            block_time: BlockTime(0),
+            deploys: vec![deploy(60, 11), deploy(60, 10)],
+            protocol_version: 1,
+            invalid_nonce_policy: InvalidNoncePolicy::default(),
+            check_deploy_order: false,
+        };
+        let results = engine_state.run_execute(correlation_id, request).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_matches!(
+            results[0],
+            ExecutionResult::Failure {
+                error: Error::DeployArgsTooLarge {
+                    size: 11,
+                    max_size: 10
+                },
+                cost,
+                ..
+            } if cost == Gas::default()
+        );
+        assert_matches!(
+            results[1],
+            ExecutionResult::Failure {
+                error: Error::DeployTooLarge {
+                    size: 102,
+                    max_size: 100
+                },
+                cost,
+                ..
+            } if cost == Gas::default()
+        );
+    }
+
+    #[test]
+    fn run_execute_rejects_deploys_of_frozen_accounts() {
+        let correlation_id = CorrelationId::new();
+        let global_state = InMemoryGlobalState::empty().unwrap();
+        let root_hash = global_state.current_root();
+        let frozen = PublicKey::new([1u8; 32]);
+        let config = EngineConfig {
+            frozen_accounts: vec![frozen].into_iter().collect(),
+            ..EngineConfig::default()
+        };
+        let engine_state = EngineState::with_config(global_state, config);
+
+        let deploy = |address: [u8; 32]| DeployItem {
+            address: address.to_vec(),
+            session_code: vec![],
+            session_args: vec![],
+            gas_limit: Gas::new(1000),
+            gas_price: ConversionRate::new(1),
+            nonce: 1,
+        };
+        let request = ExecuteRequest {
+            parent_state_hash: root_hash,
+            block_time: BlockTime(0),
+            deploys: vec![deploy([1u8; 32]), deploy([2u8; 32])],
+            protocol_version: 1,
+            invalid_nonce_policy: InvalidNoncePolicy::default(),
+            check_deploy_order: false,
+        };
+        let results = engine_state.run_execute(correlation_id, request).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_matches!(
+            results[0],
+            ExecutionResult::Failure {
+                error: Error::AccountFrozen(account),
+                cost,
+                ..
+            } if account == frozen && cost == Gas::default()
+        );
+        assert_matches!(
+            results[1],
+            ExecutionResult::Failure {
+                error: Error::WasmPreprocessingError(_),
+                ..
+            }
+        );
+    }
+
+    #[test]
+    fn run_execute_reports_missing_parent() {
+        let correlation_id = CorrelationId::new();
+        let (engine_state, _) = engine_state_with(Key::Hash([1u8; 32]), Value::Int32(1));
+        let missing_root: Blake2bHash = [2u8; 32].into();
+
+        // (module (memory 1) (func (export "call")))
+        let module_bytes = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x08, 0x01, 0x04, 0x63,
+            0x61, 0x6c, 0x6c, 0x00, 0x00, 0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        ];
+        let deploy = DeployItem {
+            address: vec![1u8; 32],
+            session_code: module_bytes,
+            session_args: vec![],
+            gas_limit: Gas::new(1000),
+            gas_price: ConversionRate::new(1),
+            nonce: 1,
+        };
+        let request = ExecuteRequest {
+            parent_state_hash: missing_root,
+            block_time: BlockTime(0),
+            deploys: vec![deploy],
+            protocol_version: 1,
+            invalid_nonce_policy: InvalidNoncePolicy::default(),
+            check_deploy_order: false,
+        };
+        assert_eq!(
+            engine_state
+                .run_execute(correlation_id, request)
+                .unwrap_err(),
+            ExecuteError::MissingParent(RootNotFound(missing_root))
+        );
+    }
+
+    #[test]
+    fn run_execute_applies_invalid_nonce_policy() {
+        let (engine_state, post_state_hash) = committed_genesis();
+        let request = |invalid_nonce_policy| {
+            let deploy = |nonce| DeployItem {
+                address: vec![6u8; 32],
+                session_code: test_utils::create_empty_wasm_module_bytes(),
+                session_args: vec![],
+                gas_limit: Gas::new(1000),
+                gas_price: ConversionRate::new(1),
+                nonce,
+            };
+            ExecuteRequest {
+                parent_state_hash: post_state_hash,
+                block_time: BlockTime(0),
+                deploys: vec![deploy(1), deploy(3)],
+                protocol_version: 1,
+                invalid_nonce_policy,
+                check_deploy_order: false,
+            }
+        };
+        let run = |invalid_nonce_policy| {
+            engine_state.run_execute(CorrelationId::new(), request(invalid_nonce_policy))
+        };
+
+        let results = run(InvalidNoncePolicy::RetryLater).unwrap();
+        assert_eq!(results[1].invalid_nonce(), Some((3, 1)));
+
+        let results = run(InvalidNoncePolicy::FailDeploy).unwrap();
+        assert_matches!(
+            results[1],
+            ExecutionResult::Failure {
+                error: Error::InvalidNonce {
+                    deploy_nonce: 3,
+                    expected_nonce: 1
+                },
+                cost,
+                ..
+            } if cost == Gas::default()
+        );
+
+        assert_eq!(
+            run(InvalidNoncePolicy::AbortBlock).unwrap_err(),
+            ExecuteError::InvalidNonce {
+                deploy_index: 1,
+                deploy_nonce: 3,
+                expected_nonce: 1
+            }
+        );
+    }
+
+    #[test]
+    fn run_execute_rejects_misordered_deploys_before_executing() {
+        let (engine_state, post_state_hash) = committed_genesis();
+        let deploy = |nonce| DeployItem {
+            address: vec![6u8; 32],
+            session_code: test_utils::create_empty_wasm_module_bytes(),
+            session_args: vec![],
+            gas_limit: Gas::new(1000),
+            gas_price: ConversionRate::new(1),
+            nonce,
+        };
+        let request = |check_deploy_order| ExecuteRequest {
+            parent_state_hash: post_state_hash,
+            block_time: BlockTime(0),
+            deploys: vec![deploy(2), deploy(1)],
+            protocol_version: 1,
+            invalid_nonce_policy: InvalidNoncePolicy::default(),
+            check_deploy_order,
+        };
+
+        assert_eq!(
+            engine_state
+                .run_execute(CorrelationId::new(), request(true))
+                .unwrap_err(),
+            ExecuteError::MisorderedDeploy {
+                deploy_index: 1,
+                deploy_nonce: 1,
+                previous_index: 0,
+                previous_nonce: 2,
+            }
+        );
+        assert_eq!(
+            engine_state
+                .run_execute(CorrelationId::new(), request(false))
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use linked_hash_map::LinkedHashMap;
+use parity_wasm::elements::Module;
+use parking_lot::Mutex;
+
+use shared::newtypes::Blake2bHash;
+use wasm_prep::wasm_costs::WasmCosts;
+use wasm_prep::{PreprocessingError, Preprocessor};
+
+/// Identifies the result of preprocessing some code. The same code is instrumented differently
+/// under each protocol version and cost table, so the cache can hold it once per version while
+/// several of them are active during an upgrade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModuleCacheKey {
+    pub code_hash: Blake2bHash,
+    pub protocol_version: u64,
+    pub wasm_costs_hash: u64,
+}
+
+impl ModuleCacheKey {
+    pub fn new(module_bytes: &[u8], protocol_version: u64, wasm_costs: &WasmCosts) -> Self {
+        ModuleCacheKey {
+            code_hash: Blake2bHash::new(module_bytes),
+            protocol_version,
+            wasm_costs_hash: wasm_costs_hash(wasm_costs),
+        }
+    }
+}
+
+fn wasm_costs_hash(wasm_costs: &WasmCosts) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    wasm_costs.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModuleCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// Preprocessed modules, evicting the least recently used ones above `capacity`.
+/// A capacity of 0 disables caching.
+pub struct ModuleCache {
+    capacity: usize,
+    modules: LinkedHashMap<ModuleCacheKey, Module>,
+    stats: ModuleCacheStats,
+}
+
+impl ModuleCache {
+    pub fn new(capacity: usize) -> ModuleCache {
+        ModuleCache {
+            capacity,
+            modules: LinkedHashMap::new(),
+            stats: ModuleCacheStats::default(),
+        }
+    }
+
+    pub fn get(&mut self, key: &ModuleCacheKey) -> Option<Module> {
+        let module = self.modules.get_refresh(key).map(|module| module.clone());
+        if module.is_some() {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+        module
+    }
+
+    pub fn insert(&mut self, key: ModuleCacheKey, module: Module) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.modules.insert(key, module);
+        let capacity = self.capacity;
+        self.evict_above(capacity);
+    }
+
+    /// Changes the capacity, evicting the least recently used modules above the new one.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict_above(capacity);
+    }
+
+    /// Evicts the modules preprocessed for `protocol_version`, e.g. once an upgrade is over and
+    /// the version can no longer be executed. Returns the number of evicted modules.
+    pub fn evict_protocol_version(&mut self, protocol_version: u64) -> usize {
+        let keys: Vec<ModuleCacheKey> = self
+            .modules
+            .keys()
+            .filter(|key| key.protocol_version == protocol_version)
+            .cloned()
+            .collect();
+        for key in &keys {
+            self.modules.remove(key);
+        }
+        self.stats.evictions += keys.len() as u64;
+        keys.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.stats.evictions += self.modules.len() as u64;
+        self.modules.clear();
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    pub fn stats(&self) -> ModuleCacheStats {
+        self.stats
+    }
+
+    fn evict_above(&mut self, capacity: usize) {
+        while self.modules.len() > capacity {
+            self.modules.pop_front();
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+/// Looks modules up in `cache` before preprocessing them with `preprocessor`, which has to be
+/// set up for `protocol_version` and `wasm_costs`. Failures aren't cached.
+pub struct CachingPreprocessor<'a, P> {
+    cache: &'a Mutex<ModuleCache>,
+    preprocessor: P,
+    protocol_version: u64,
+    wasm_costs_hash: u64,
+}
+
+impl<'a, P> CachingPreprocessor<'a, P> {
+    pub fn new(
+        cache: &'a Mutex<ModuleCache>,
+        preprocessor: P,
+        protocol_version: u64,
+        wasm_costs: &WasmCosts,
+    ) -> Self {
+        CachingPreprocessor {
+            cache,
+            preprocessor,
+            protocol_version,
+            wasm_costs_hash: wasm_costs_hash(wasm_costs),
+        }
+    }
+}
+
+impl<'a, P: Preprocessor<Module>> Preprocessor<Module> for CachingPreprocessor<'a, P> {
+    fn preprocess(&self, module_bytes: &[u8]) -> Result<Module, PreprocessingError> {
+        let key = ModuleCacheKey {
+            code_hash: Blake2bHash::new(module_bytes),
+            protocol_version: self.protocol_version,
+            wasm_costs_hash: self.wasm_costs_hash,
+        };
+        if let Some(module) = self.cache.lock().get(&key) {
+            return Ok(module);
+        }
+        // The lock isn't held while preprocessing, so other deploys aren't blocked on it.
+        let module = self.preprocessor.preprocess(module_bytes)?;
+        self.cache.lock().insert(key, module.clone());
+        Ok(module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use parity_wasm::builder;
+    use parity_wasm::elements::Module;
+    use parking_lot::Mutex;
+
+    use wasm_prep::wasm_costs::WasmCosts;
+    use wasm_prep::{PreprocessingError, Preprocessor};
+
+    use super::{CachingPreprocessor, ModuleCache, ModuleCacheKey};
+
+    /// Counts the modules it "preprocesses", rejecting empty code.
+    struct CountingPreprocessor {
+        calls: Cell<usize>,
+    }
+
+    impl Preprocessor<Module> for CountingPreprocessor {
+        fn preprocess(&self, module_bytes: &[u8]) -> Result<Module, PreprocessingError> {
+            self.calls.set(self.calls.get() + 1);
+            if module_bytes.is_empty() {
+                Err(PreprocessingError::NoExportSection)
+            } else {
+                Ok(builder::module().build())
+            }
+        }
+    }
+
+    fn key(code: &[u8], protocol_version: u64) -> ModuleCacheKey {
+        ModuleCacheKey::new(code, protocol_version, &WasmCosts::free())
+    }
+
+    #[test]
+    fn should_evict_least_recently_used_modules() {
+        let mut cache = ModuleCache::new(2);
+        cache.insert(key(b"a", 1), builder::module().build());
+        cache.insert(key(b"b", 1), builder::module().build());
+        assert!(cache.get(&key(b"a", 1)).is_some());
+        cache.insert(key(b"c", 1), builder::module().build());
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key(b"b", 1)).is_none());
+        assert!(cache.get(&key(b"a", 1)).is_some());
+        assert_eq!(cache.stats().evictions, 1);
+
+        cache.set_capacity(1);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&key(b"a", 1)).is_some());
+    }
+
+    #[test]
+    fn should_evict_protocol_version() {
+        let mut cache = ModuleCache::new(10);
+        cache.insert(key(b"a", 1), builder::module().build());
+        cache.insert(key(b"a", 2), builder::module().build());
+        cache.insert(key(b"b", 1), builder::module().build());
+
+        assert_eq!(cache.evict_protocol_version(1), 2);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&key(b"a", 2)).is_some());
+    }
+
+    #[test]
+    fn should_distinguish_cost_tables() {
+        let free = ModuleCacheKey::new(b"a", 1, &WasmCosts::free());
+        let v1 = ModuleCacheKey::new(b"a", 1, &WasmCosts::from_version(1).unwrap());
+        assert_eq!(free.code_hash, v1.code_hash);
+        assert_ne!(free, v1);
+    }
+
+    #[test]
+    fn should_preprocess_code_once_per_version() {
+        let cache = Mutex::new(ModuleCache::new(10));
+        let wasm_costs = WasmCosts::free();
+        let v1 = CachingPreprocessor::new(
+            &cache,
+            CountingPreprocessor {
+                calls: Cell::new(0),
+            },
+            1,
+            &wasm_costs,
+        );
+        v1.preprocess(b"a").unwrap();
+        v1.preprocess(b"a").unwrap();
+        assert_eq!(v1.preprocessor.calls.get(), 1);
+
+        let v2 = CachingPreprocessor::new(
+            &cache,
+            CountingPreprocessor {
+                calls: Cell::new(0),
+            },
+            2,
+            &wasm_costs,
+        );
+        v2.preprocess(b"a").unwrap();
+        assert_eq!(v2.preprocessor.calls.get(), 1);
+        assert_eq!(cache.lock().len(), 2);
+
+        assert!(v1.preprocess(b"").is_err());
+        assert!(v1.preprocess(b"").is_err());
+        assert_eq!(v1.preprocessor.calls.get(), 3);
+    }
+
+    #[test]
+    fn should_not_cache_when_disabled() {
+        let mut cache = ModuleCache::new(0);
+        cache.insert(key(b"a", 1), builder::module().build());
+        assert!(cache.is_empty());
+    }
+}
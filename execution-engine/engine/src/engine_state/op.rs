@@ -3,9 +3,23 @@ pub enum Op {
     Read,
     Write,
     Add,
+    /// Write to a key created by the deploy itself, e.g. a new `URef` or stored contract.
+    /// Whatever else the deploy does with the key, it didn't exist before.
+    Create,
     NoOp,
 }
 
+impl Op {
+    /// Whether deploys doing `self` and `other` to the same key can be applied in any order.
+    pub fn commutes_with(&self, other: &Op) -> bool {
+        match (self, other) {
+            (_, Op::NoOp) | (Op::NoOp, _) => true,
+            (Op::Read, Op::Read) | (Op::Add, Op::Add) => true,
+            _ => false,
+        }
+    }
+}
+
 impl std::ops::Add for Op {
     type Output = Op;
 
@@ -13,6 +27,7 @@ impl std::ops::Add for Op {
         match (self, other) {
             (a, Op::NoOp) => a,
             (Op::NoOp, b) => b,
+            (Op::Create, _) | (_, Op::Create) => Op::Create,
             (Op::Read, Op::Read) => Op::Read,
             (Op::Add, Op::Add) => Op::Add,
             _ => Op::Write,
@@ -25,3 +40,37 @@ impl std::fmt::Display for Op {
         write!(f, "{:?}", self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Op;
+
+    const OPS: [Op; 5] = [Op::Read, Op::Write, Op::Add, Op::Create, Op::NoOp];
+
+    #[test]
+    fn should_add_commutatively_with_noop_as_identity() {
+        for a in OPS.iter() {
+            assert_eq!(a.clone() + Op::NoOp, a.clone());
+            for b in OPS.iter() {
+                assert_eq!(a.clone() + b.clone(), b.clone() + a.clone());
+            }
+        }
+    }
+
+    #[test]
+    fn should_stay_create_once_created() {
+        assert_eq!(Op::Create + Op::Write, Op::Create);
+        assert_eq!(Op::Create + Op::Add, Op::Create);
+        assert_eq!(Op::Read + Op::Add, Op::Write);
+    }
+
+    #[test]
+    fn should_commute_only_when_order_is_irrelevant() {
+        assert!(Op::Read.commutes_with(&Op::Read));
+        assert!(Op::Add.commutes_with(&Op::Add));
+        assert!(Op::Create.commutes_with(&Op::NoOp));
+        assert!(!Op::Create.commutes_with(&Op::Create));
+        assert!(!Op::Read.commutes_with(&Op::Add));
+        assert!(!Op::Write.commutes_with(&Op::Read));
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+
+use common::key::Key;
+use common::value::{Value, U512};
+use shared::newtypes::Blake2bHash;
+
+/// Request for a value stored under `base_key` (following `path`) at `state_hash`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryRequest {
+    pub state_hash: Blake2bHash,
+    pub base_key: Key,
+    pub path: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum QueryResult {
+    RootNotFound,
+    ValueNotFound(String),
+    Success(Value),
+}
+
+#[derive(Debug)]
+pub enum TotalSupplyResult {
+    RootNotFound,
+    /// The mint at the requested state predates the tracking of the total supply.
+    NotTracked,
+    Success(U512),
+}
+
+#[derive(Debug)]
+pub enum BalanceResult {
+    RootNotFound,
+    /// The mint holds no balance for the requested purse.
+    PurseNotFound,
+    Success(U512),
+}
+
+#[derive(Debug)]
+pub enum NamedKeysResult {
+    RootNotFound,
+    ValueNotFound,
+    /// The value under the requested key is neither an account nor a contract.
+    InvalidType(String),
+    Success(BTreeMap<String, Key>),
+}
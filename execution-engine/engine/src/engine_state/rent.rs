@@ -0,0 +1,219 @@
+//! Rent of the values in global state, enabled by `ProtocolLimits::rent`.
+//!
+//! Keys created by a deploy are paid through the grace period, after which anyone can extend
+//! them with the `pay_rent` host function. The rent step of an exec request marks the keys whose
+//! rent ran out as reclaimable, see `EngineState::run_rent_collection`. Contracts using a
+//! reclaimable key fail with `Error::RentExpired` until someone pays its rent again. Keys created
+//! before rent was enabled, e.g. at genesis, have no rent record and never expire.
+use blake2::digest::{Input, VariableOutput};
+use blake2::VarBlake2b;
+
+use common::bytesrepr::{self, FromBytes, ToBytes, U64_SIZE, U8_SIZE};
+use common::key::{Key, LOCAL_SEED_SIZE};
+use common::value::account::BlockTime;
+use common::value::{Value, U512};
+
+/// Block time, in milliseconds, rent prices are given for.
+pub const RENT_PERIOD_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+/// New keys are paid through 30 days of block time by default.
+pub const DEFAULT_GRACE_PERIOD_MILLIS: u64 = 30 * RENT_PERIOD_MILLIS;
+
+pub const RENT_RECORD_SER_SIZE: usize = U64_SIZE + U8_SIZE;
+
+/// Domain separator of rent record seeds, so that records can't collide with local state.
+const RENT_SEED_PREFIX: &[u8] = b"rent";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RentConfig {
+    /// Motes to keep one byte of a serialized value for a day of block time.
+    pub price_per_byte_day: u64,
+    /// Block time, in milliseconds, new keys are paid through without a payment.
+    pub grace_period_millis: u64,
+}
+
+/// Rent of a key, stored under its `rent_key`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RentRecord {
+    pub paid_through: BlockTime,
+    /// Set by the rent step once `paid_through` has passed, cleared by paying rent again.
+    pub reclaimable: bool,
+}
+
+impl RentRecord {
+    pub fn is_expired(&self, block_time: BlockTime) -> bool {
+        self.paid_through.0 < block_time.0
+    }
+}
+
+impl ToBytes for RentRecord {
+    fn to_bytes(&self) -> Result<Vec<u8>, bytesrepr::Error> {
+        let mut result = Vec::with_capacity(RENT_RECORD_SER_SIZE);
+        result.append(&mut self.paid_through.to_bytes()?);
+        result.push(self.reclaimable as u8);
+        Ok(result)
+    }
+}
+
+impl FromBytes for RentRecord {
+    fn from_bytes(bytes: &[u8]) -> Result<(Self, &[u8]), bytesrepr::Error> {
+        let (paid_through, rem): (BlockTime, &[u8]) = FromBytes::from_bytes(bytes)?;
+        let (reclaimable, rem): (u8, &[u8]) = FromBytes::from_bytes(rem)?;
+        let reclaimable = match reclaimable {
+            0 => false,
+            1 => true,
+            _ => return Err(bytesrepr::Error::FormattingError),
+        };
+        Ok((
+            RentRecord {
+                paid_through,
+                reclaimable,
+            },
+            rem,
+        ))
+    }
+}
+
+/// Derives the key of the rent record of `key`. The record is a `Value::ByteArray` holding a
+/// serialized `RentRecord`.
+pub fn rent_key(key: &Key) -> Key {
+    let (tag, addr) = match key.normalize() {
+        Key::Account(addr) => (0u8, addr),
+        Key::Hash(addr) => (1, addr),
+        Key::URef(uref) => (2, uref.addr()),
+        Key::Local(hash) => (3, hash),
+    };
+    let mut hasher = VarBlake2b::new(LOCAL_SEED_SIZE).unwrap();
+    hasher.input(RENT_SEED_PREFIX);
+    hasher.input(&[tag]);
+    hasher.input(&addr);
+    let mut seed = [0u8; LOCAL_SEED_SIZE];
+    hasher.variable_result(|hash| seed.clone_from_slice(hash));
+    Key::local(seed, &[])
+}
+
+pub fn record_to_value(record: RentRecord) -> Result<Value, bytesrepr::Error> {
+    Ok(Value::ByteArray(record.to_bytes()?))
+}
+
+/// `None` if `value` isn't a rent record.
+pub fn record_from_value(value: &Value) -> Option<RentRecord> {
+    match value {
+        Value::ByteArray(bytes) => bytesrepr::deserialize(bytes).ok(),
+        _ => None,
+    }
+}
+
+/// Record of a key holding `size` bytes after paying `amount` of rent for it at `block_time`.
+/// Rent paid for an expired key starts at `block_time`, the time it was unpaid isn't owed.
+pub fn pay(
+    record: Option<RentRecord>,
+    block_time: BlockTime,
+    amount: U512,
+    size: usize,
+    config: &RentConfig,
+) -> RentRecord {
+    let start = match record {
+        Some(record) if !record.is_expired(block_time) => record.paid_through.0,
+        _ => block_time.0,
+    };
+    let price = U512::from(size.max(1) as u64) * U512::from(config.price_per_byte_day);
+    let millis = if price.is_zero() {
+        u64::max_value()
+    } else {
+        match amount.checked_mul(U512::from(RENT_PERIOD_MILLIS)) {
+            Some(product) => product / price,
+            None => (amount / price).saturating_mul(U512::from(RENT_PERIOD_MILLIS)),
+        }
+        .min(U512::from(u64::max_value()))
+        .as_u64()
+    };
+    RentRecord {
+        paid_through: BlockTime(start.saturating_add(millis)),
+        reclaimable: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::bytesrepr::{deserialize, ToBytes};
+    use common::key::Key;
+    use common::uref::{AccessRights, URef};
+    use common::value::account::BlockTime;
+    use common::value::U512;
+
+    use super::{pay, rent_key, RentConfig, RentRecord, RENT_PERIOD_MILLIS};
+
+    const CONFIG: RentConfig = RentConfig {
+        price_per_byte_day: 10,
+        grace_period_millis: 1000,
+    };
+
+    fn record(paid_through: u64) -> RentRecord {
+        RentRecord {
+            paid_through: BlockTime(paid_through),
+            reclaimable: false,
+        }
+    }
+
+    #[test]
+    fn should_roundtrip_records() {
+        let record = RentRecord {
+            paid_through: BlockTime(42),
+            reclaimable: true,
+        };
+        let bytes = record.to_bytes().unwrap();
+        assert_eq!(deserialize::<RentRecord>(&bytes).unwrap(), record);
+    }
+
+    #[test]
+    fn should_derive_distinct_rent_keys() {
+        let addr = [1u8; 32];
+        let uref = URef::new(addr, AccessRights::READ_ADD_WRITE);
+        assert_ne!(rent_key(&Key::Account(addr)), rent_key(&Key::Hash(addr)));
+        assert_ne!(rent_key(&Key::Hash(addr)), rent_key(&Key::URef(uref)));
+        assert_eq!(
+            rent_key(&Key::URef(uref)),
+            rent_key(&Key::URef(URef::new(addr, AccessRights::READ)))
+        );
+    }
+
+    #[test]
+    fn should_extend_unexpired_rent() {
+        // A day of rent for 10 bytes.
+        let paid = pay(
+            Some(record(5_000)),
+            BlockTime(1_000),
+            U512::from(100),
+            10,
+            &CONFIG,
+        );
+        assert_eq!(paid, record(5_000 + RENT_PERIOD_MILLIS));
+    }
+
+    #[test]
+    fn should_restart_expired_rent() {
+        let expired = RentRecord {
+            paid_through: BlockTime(500),
+            reclaimable: true,
+        };
+        let paid = pay(Some(expired), BlockTime(1_000), U512::from(50), 10, &CONFIG);
+        assert_eq!(paid, record(1_000 + RENT_PERIOD_MILLIS / 2));
+        let paid = pay(None, BlockTime(1_000), U512::from(50), 10, &CONFIG);
+        assert_eq!(paid, record(1_000 + RENT_PERIOD_MILLIS / 2));
+    }
+
+    #[test]
+    fn should_not_overflow_on_large_payments() {
+        let paid = pay(None, BlockTime(1_000), U512::MAX, 10, &CONFIG);
+        assert_eq!(paid, record(u64::max_value()));
+        let free = RentConfig {
+            price_per_byte_day: 0,
+            ..CONFIG
+        };
+        assert_eq!(
+            pay(None, BlockTime(1_000), U512::zero(), 10, &free),
+            record(u64::max_value())
+        );
+    }
+}
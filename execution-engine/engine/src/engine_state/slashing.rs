@@ -0,0 +1,213 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use common::key::Key;
+use common::value::account::PublicKey;
+use common::value::U512;
+use shared::newtypes::Blake2bHash;
+
+use super::execution_effect::ExecutionEffect;
+use super::utils::{pos_validator_key, pos_validator_to_tuple};
+
+/// Slash ratios are expressed in millionths of the offender's stake.
+pub const SLASH_RATIO_DENOMINATOR: u32 = 1_000_000;
+
+/// Penalty for a validator whose equivocation has been proven to the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slash {
+    pub validator: PublicKey,
+    /// Portion of the validator's stake to slash, in millionths.
+    pub ratio: u32,
+}
+
+/// Request to slash validators bonded at `parent_state_hash`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlashRequest {
+    pub parent_state_hash: Blake2bHash,
+    pub slashes: Vec<Slash>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlashError {
+    InvalidRatio(PublicKey, u32),
+    NotBonded(PublicKey),
+    NoBondedValidatorsLeft,
+}
+
+impl fmt::Display for SlashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SlashError::InvalidRatio(validator, ratio) => write!(
+                f,
+                "Slash ratio {} of validator {} exceeds {}",
+                ratio, validator, SLASH_RATIO_DENOMINATOR
+            ),
+            SlashError::NotBonded(validator) => write!(f, "Validator {} is not bonded", validator),
+            SlashError::NoBondedValidatorsLeft => {
+                write!(f, "Slashing would leave no bonded validators")
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SlashResult {
+    RootNotFound(Blake2bHash),
+    PoSNotFound(Key),
+    Rejected(SlashError),
+    /// `effect` rewrites the PoS bonds and has to be committed by the node. The slashed tokens
+    /// stay in the PoS purse, i.e. they are burned from the point of view of the stakes.
+    Success {
+        effect: ExecutionEffect,
+        bonded_validators: HashMap<PublicKey, U512>,
+        slashed_amount: U512,
+    },
+}
+
+/// Reduces the stakes stored as bond entries in the PoS contract's `named_keys`, removing the
+/// validators left without stake. Slashes of the same validator are applied one after the other.
+///
+/// Returns the total amount slashed; `named_keys` is left untouched on error.
+pub fn apply_slashes(
+    named_keys: &mut BTreeMap<String, Key>,
+    slashes: &[Slash],
+) -> Result<U512, SlashError> {
+    let mut stakes: BTreeMap<PublicKey, U512> = named_keys
+        .keys()
+        .filter_map(|name| pos_validator_to_tuple(name))
+        .collect();
+    let denominator = U512::from(SLASH_RATIO_DENOMINATOR);
+    let mut slashed_amount = U512::zero();
+    for slash in slashes {
+        if slash.ratio > SLASH_RATIO_DENOMINATOR {
+            return Err(SlashError::InvalidRatio(slash.validator, slash.ratio));
+        }
+        let stake = stakes
+            .get_mut(&slash.validator)
+            .ok_or(SlashError::NotBonded(slash.validator))?;
+        // Split the stake to not overflow on large bonds.
+        let ratio = U512::from(slash.ratio);
+        let amount = *stake / denominator * ratio + *stake % denominator * ratio / denominator;
+        *stake -= amount;
+        slashed_amount += amount;
+    }
+    if stakes.values().all(U512::is_zero) {
+        return Err(SlashError::NoBondedValidatorsLeft);
+    }
+
+    let bond_names: Vec<String> = named_keys
+        .keys()
+        .filter(|name| pos_validator_to_tuple(name).is_some())
+        .cloned()
+        .collect();
+    for name in bond_names {
+        named_keys.remove(&name);
+    }
+    // Same encoding as the PoS contract uses for its stakes.
+    for (validator, stake) in stakes {
+        if !stake.is_zero() {
+            named_keys.insert(pos_validator_key(validator, stake), Key::Hash([0u8; 32]));
+        }
+    }
+    Ok(slashed_amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use common::key::Key;
+    use common::value::account::PublicKey;
+    use common::value::U512;
+
+    use super::{apply_slashes, Slash, SlashError};
+    use engine_state::utils::{pos_validator_key, pos_validator_to_tuple};
+
+    const KEY1: [u8; 32] = [1; 32];
+    const KEY2: [u8; 32] = [2; 32];
+
+    fn named_keys(stakes: &[([u8; 32], u64)]) -> BTreeMap<String, Key> {
+        let mut named_keys: BTreeMap<String, Key> = stakes
+            .iter()
+            .map(|&(key, stake)| {
+                let name = pos_validator_key(PublicKey::new(key), U512::from(stake));
+                (name, Key::Hash([0u8; 32]))
+            })
+            .collect();
+        named_keys.insert("pos_purse".to_string(), Key::Hash([3u8; 32]));
+        named_keys
+    }
+
+    fn slash(key: [u8; 32], ratio: u32) -> Slash {
+        Slash {
+            validator: PublicKey::new(key),
+            ratio,
+        }
+    }
+
+    fn stakes(named_keys: &BTreeMap<String, Key>) -> Vec<(PublicKey, U512)> {
+        named_keys
+            .keys()
+            .filter_map(|name| pos_validator_to_tuple(name))
+            .collect()
+    }
+
+    #[test]
+    fn should_slash_portion_of_stake() {
+        let mut keys = named_keys(&[(KEY1, 1_000), (KEY2, 500)]);
+        let slashed = apply_slashes(&mut keys, &[slash(KEY1, 250_000)]).unwrap();
+        assert_eq!(slashed, U512::from(250));
+        assert_eq!(
+            stakes(&keys),
+            vec![
+                (PublicKey::new(KEY1), U512::from(750)),
+                (PublicKey::new(KEY2), U512::from(500))
+            ]
+        );
+        assert!(keys.contains_key("pos_purse"));
+    }
+
+    #[test]
+    fn should_remove_fully_slashed_validator() {
+        let mut keys = named_keys(&[(KEY1, 1_000), (KEY2, 500)]);
+        let slashed = apply_slashes(&mut keys, &[slash(KEY2, 1_000_000)]).unwrap();
+        assert_eq!(slashed, U512::from(500));
+        assert_eq!(
+            stakes(&keys),
+            vec![(PublicKey::new(KEY1), U512::from(1_000))]
+        );
+    }
+
+    #[test]
+    fn should_not_overflow_on_max_stake() {
+        let mut keys = BTreeMap::new();
+        keys.insert(
+            pos_validator_key(PublicKey::new(KEY1), U512::MAX),
+            Key::Hash([0u8; 32]),
+        );
+        let slashed = apply_slashes(&mut keys, &[slash(KEY1, 500_000)]).unwrap();
+        assert_eq!(
+            stakes(&keys),
+            vec![(PublicKey::new(KEY1), U512::MAX - slashed)]
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_slashes() {
+        let keys = named_keys(&[(KEY1, 1_000)]);
+        let mut unchanged = keys.clone();
+        assert_eq!(
+            apply_slashes(&mut unchanged, &[slash(KEY2, 1)]),
+            Err(SlashError::NotBonded(PublicKey::new(KEY2)))
+        );
+        assert_eq!(
+            apply_slashes(&mut unchanged, &[slash(KEY1, 1_000_001)]),
+            Err(SlashError::InvalidRatio(PublicKey::new(KEY1), 1_000_001))
+        );
+        assert_eq!(
+            apply_slashes(&mut unchanged, &[slash(KEY1, 1_000_000)]),
+            Err(SlashError::NoBondedValidatorsLeft)
+        );
+        assert_eq!(unchanged, keys);
+    }
+}
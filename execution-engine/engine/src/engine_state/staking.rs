@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use common::bytesrepr::{self, FromBytes};
+use common::pos_parameters::{
+    BOND_DELAY, MAX_BOND_LEN, MAX_DECREASE, MAX_INCREASE, MAX_REL_DECREASE, MAX_REL_INCREASE,
+    MAX_SPREAD, MAX_UNBOND_LEN, UNBOND_DELAY,
+};
+use common::value::account::{BlockTime, PublicKey};
+use common::value::U512;
+use shared::newtypes::Blake2bHash;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StakingRequest {
+    Bond(U512),
+    /// Unbonds the whole stake if the amount is `None`.
+    Unbond(Option<U512>),
+}
+
+/// Asks whether a staking deploy of `validator` would be accepted by the PoS contract at
+/// `state_hash`, if executed at `timestamp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StakingCheck {
+    pub state_hash: Blake2bHash,
+    pub validator: PublicKey,
+    pub timestamp: BlockTime,
+    pub request: StakingRequest,
+}
+
+/// Reasons for the PoS contract to reject a staking request, named as its errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StakingError {
+    NotBonded,
+    TooManyEventsInQueue,
+    CannotUnbondLastValidator,
+    SpreadTooHigh,
+    MultipleRequests,
+    BondTooLarge,
+    UnbondTooLarge,
+}
+
+impl fmt::Display for StakingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StakingError::NotBonded => write!(f, "Validator is not bonded"),
+            StakingError::TooManyEventsInQueue => write!(f, "Too many pending requests"),
+            StakingError::CannotUnbondLastValidator => write!(f, "The last validator can't unbond"),
+            StakingError::SpreadTooHigh => write!(f, "Stakes would spread too much"),
+            StakingError::MultipleRequests => write!(f, "Validator already has a pending request"),
+            StakingError::BondTooLarge => write!(f, "Bond is too large"),
+            StakingError::UnbondTooLarge => write!(f, "Unbond is too large"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StakingCheckResult {
+    RootNotFound(Blake2bHash),
+    PoSNotFound,
+    /// The PoS contract's queues couldn't be parsed.
+    InvalidQueue(bytesrepr::Error),
+    Invalid(StakingError),
+    Valid {
+        /// Validator's stake once the request is applied.
+        stake: U512,
+        /// When the bond becomes effective, or the unbonded tokens are paid out.
+        due: BlockTime,
+    },
+}
+
+/// A pending entry in the bonding or unbonding queue of the PoS contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueEntry {
+    pub validator: PublicKey,
+    pub amount: U512,
+    pub timestamp: BlockTime,
+}
+
+/// Parses a queue as serialized by the PoS contract.
+pub fn parse_queue(bytes: &[u8]) -> Result<Vec<QueueEntry>, bytesrepr::Error> {
+    let (len, mut bytes): (u64, &[u8]) = FromBytes::from_bytes(bytes)?;
+    let mut queue = Vec::new();
+    for _ in 0..len {
+        let (validator, rest): (PublicKey, &[u8]) = FromBytes::from_bytes(bytes)?;
+        let (amount, rest): (U512, &[u8]) = FromBytes::from_bytes(rest)?;
+        let (timestamp, rest): (BlockTime, &[u8]) = FromBytes::from_bytes(rest)?;
+        queue.push(QueueEntry {
+            validator,
+            amount,
+            timestamp,
+        });
+        bytes = rest;
+    }
+    if !bytes.is_empty() {
+        return Err(bytesrepr::Error::LeftOverBytes);
+    }
+    Ok(queue)
+}
+
+/// Applies the PoS contract's checks of `request` to the given stakes and queues.
+///
+/// Returns the validator's stake once the request is applied.
+pub fn check_request(
+    mut stakes: BTreeMap<PublicKey, U512>,
+    bonding_queue: &[QueueEntry],
+    unbonding_queue: &[QueueEntry],
+    validator: PublicKey,
+    request: StakingRequest,
+) -> Result<U512, StakingError> {
+    match request {
+        StakingRequest::Bond(amount) => {
+            if bonding_queue.len() >= MAX_BOND_LEN {
+                return Err(StakingError::TooManyEventsInQueue);
+            }
+            // Like the contract, take the earlier bonds into account.
+            for entry in bonding_queue {
+                *stakes.entry(entry.validator).or_insert_with(U512::zero) += entry.amount;
+            }
+            validate_bonding(&stakes, &validator, amount)?;
+            if bonding_queue
+                .iter()
+                .any(|entry| entry.validator == validator)
+            {
+                return Err(StakingError::MultipleRequests);
+            }
+            Ok(stakes
+                .get(&validator)
+                .map_or(amount, |stake| *stake + amount))
+        }
+        StakingRequest::Unbond(maybe_amount) => {
+            if unbonding_queue.len() >= MAX_UNBOND_LEN {
+                return Err(StakingError::TooManyEventsInQueue);
+            }
+            unbond(&mut stakes, &validator, maybe_amount)?;
+            if unbonding_queue
+                .iter()
+                .any(|entry| entry.validator == validator)
+            {
+                return Err(StakingError::MultipleRequests);
+            }
+            Ok(stakes.get(&validator).cloned().unwrap_or_else(U512::zero))
+        }
+    }
+}
+
+/// Time at which a request made at `timestamp` is applied by the PoS contract.
+pub fn due_time(request: StakingRequest, timestamp: BlockTime) -> BlockTime {
+    let delay = match request {
+        StakingRequest::Bond(_) => BOND_DELAY,
+        StakingRequest::Unbond(_) => UNBOND_DELAY,
+    };
+    BlockTime(timestamp.0.saturating_add(delay))
+}
+
+fn validate_bonding(
+    stakes: &BTreeMap<PublicKey, U512>,
+    validator: &PublicKey,
+    amount: U512,
+) -> Result<(), StakingError> {
+    let max = min_without(stakes, validator)
+        .unwrap_or(U512::MAX)
+        .saturating_add(MAX_SPREAD);
+    let min = max_without(stakes, validator)
+        .unwrap_or_else(U512::zero)
+        .saturating_sub(MAX_SPREAD);
+    let stake = stakes.get(validator).map_or(amount, |s| *s + amount);
+    if stake > max || stake < min {
+        return Err(StakingError::SpreadTooHigh);
+    }
+    let max_increase = MAX_INCREASE.min(sum(stakes) * MAX_REL_INCREASE / 1_000_000);
+    if stake > min.saturating_add(max_increase) && amount > max_increase {
+        return Err(StakingError::BondTooLarge);
+    }
+    Ok(())
+}
+
+fn unbond(
+    stakes: &mut BTreeMap<PublicKey, U512>,
+    validator: &PublicKey,
+    maybe_amount: Option<U512>,
+) -> Result<U512, StakingError> {
+    let min = max_without(stakes, validator)
+        .unwrap_or_else(U512::zero)
+        .saturating_sub(MAX_SPREAD);
+    let max_decrease = MAX_DECREASE.min(sum(stakes) * MAX_REL_DECREASE / 1_000_000);
+
+    if let Some(amount) = maybe_amount {
+        let stake = stakes.get_mut(validator).ok_or(StakingError::NotBonded)?;
+        if *stake > amount {
+            if *stake - amount < min {
+                return Err(StakingError::SpreadTooHigh);
+            }
+            if amount > max_decrease {
+                return Err(StakingError::UnbondTooLarge);
+            }
+            *stake -= amount;
+            return Ok(amount);
+        }
+    }
+    if stakes.len() == 1 {
+        return Err(StakingError::CannotUnbondLastValidator);
+    }
+    let stake = stakes.remove(validator).ok_or(StakingError::NotBonded)?;
+    if stake > min.saturating_add(max_decrease) && stake > max_decrease {
+        return Err(StakingError::UnbondTooLarge);
+    }
+    Ok(stake)
+}
+
+fn min_without(stakes: &BTreeMap<PublicKey, U512>, validator: &PublicKey) -> Option<U512> {
+    stakes
+        .iter()
+        .filter(|(v, _)| *v != validator)
+        .map(|(_, s)| *s)
+        .min()
+}
+
+fn max_without(stakes: &BTreeMap<PublicKey, U512>, validator: &PublicKey) -> Option<U512> {
+    stakes
+        .iter()
+        .filter(|(v, _)| *v != validator)
+        .map(|(_, s)| *s)
+        .max()
+}
+
+fn sum(stakes: &BTreeMap<PublicKey, U512>) -> U512 {
+    stakes
+        .values()
+        .fold(U512::zero(), |sum, s| sum.saturating_add(*s))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use common::bytesrepr::ToBytes;
+    use common::value::account::{BlockTime, PublicKey};
+    use common::value::U512;
+
+    use super::{
+        check_request, parse_queue, QueueEntry, StakingError, StakingRequest, MAX_REL_DECREASE,
+    };
+
+    const KEY1: [u8; 32] = [1; 32];
+    const KEY2: [u8; 32] = [2; 32];
+    const KEY3: [u8; 32] = [3; 32];
+
+    fn stakes(stakes: &[([u8; 32], u64)]) -> BTreeMap<PublicKey, U512> {
+        stakes
+            .iter()
+            .map(|&(key, stake)| (PublicKey::new(key), U512::from(stake)))
+            .collect()
+    }
+
+    fn entry(key: [u8; 32], amount: u64) -> QueueEntry {
+        QueueEntry {
+            validator: PublicKey::new(key),
+            amount: U512::from(amount),
+            timestamp: BlockTime(0),
+        }
+    }
+
+    #[test]
+    fn should_accept_bond_including_pending_bonds() {
+        let result = check_request(
+            stakes(&[(KEY1, 100)]),
+            &[entry(KEY2, 50)],
+            &[],
+            PublicKey::new(KEY3),
+            StakingRequest::Bond(U512::from(10)),
+        );
+        assert_eq!(result, Ok(U512::from(10)));
+
+        let result = check_request(
+            stakes(&[(KEY1, 100)]),
+            &[entry(KEY2, 50)],
+            &[],
+            PublicKey::new(KEY2),
+            StakingRequest::Bond(U512::from(10)),
+        );
+        assert_eq!(result, Err(StakingError::MultipleRequests));
+    }
+
+    #[test]
+    fn should_check_unbonding_rules() {
+        let key1 = PublicKey::new(KEY1);
+        let check = |current_stakes, amount: Option<u64>| {
+            check_request(
+                current_stakes,
+                &[],
+                &[],
+                key1,
+                StakingRequest::Unbond(amount.map(U512::from)),
+            )
+        };
+        assert_eq!(
+            check(stakes(&[(KEY1, 100), (KEY2, 100)]), Some(10)),
+            Ok(U512::from(90))
+        );
+        assert_eq!(
+            check(stakes(&[(KEY1, 100), (KEY2, 100)]), None),
+            Ok(U512::zero())
+        );
+        assert_eq!(
+            check(stakes(&[(KEY1, 100)]), None),
+            Err(StakingError::CannotUnbondLastValidator)
+        );
+        assert_eq!(
+            check(stakes(&[(KEY2, 100)]), Some(10)),
+            Err(StakingError::NotBonded)
+        );
+        let total = 200;
+        let too_large = MAX_REL_DECREASE * total / 1_000_000 + 1;
+        assert_eq!(
+            check(stakes(&[(KEY1, 190), (KEY2, 10)]), Some(too_large)),
+            Err(StakingError::UnbondTooLarge)
+        );
+    }
+
+    #[test]
+    fn should_parse_queue_as_written_by_pos() {
+        let entries = vec![entry(KEY1, 10), entry(KEY2, 20)];
+        let mut bytes = (entries.len() as u64).to_bytes().unwrap();
+        for entry in &entries {
+            bytes.extend(entry.validator.to_bytes().unwrap());
+            bytes.extend(entry.amount.to_bytes().unwrap());
+            bytes.extend(entry.timestamp.to_bytes().unwrap());
+        }
+        assert_eq!(parse_queue(&bytes), Ok(entries));
+        bytes.push(0);
+        assert!(parse_queue(&bytes).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! Files holding the entries of a global state, e.g. exported from one network to start another.
+//!
+//! Entries are stored in key order, so a state always has the same file, and importing it gives
+//! the same root hash on every node:
+//!
+//! ```text
+//! <magic: 8 bytes> <blake2b hash of the payload: 32 bytes> <payload>
+//! payload = <entry count: u64> (<key> <value>)*
+//! ```
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use common::bytesrepr::{self, FromBytes, ToBytes};
+use common::key::Key;
+use common::value::Value;
+use shared::newtypes::Blake2bHash;
+
+pub const STATE_FILE_MAGIC: [u8; 8] = *b"CLSTATE1";
+
+const CHECKSUM_SIZE: usize = 32;
+
+#[derive(Debug)]
+pub enum StateFileError {
+    Io(io::Error),
+    Serialization(bytesrepr::Error),
+    /// The file doesn't start with [`STATE_FILE_MAGIC`].
+    UnknownFormat,
+    /// The payload doesn't match its hash, the file was truncated or corrupted.
+    ChecksumMismatch,
+    /// Entries aren't in strictly increasing key order, `Key` coming after itself or a greater
+    /// key. Keys are compared without their access rights, as stored in the global state.
+    UnsortedKey(Key),
+}
+
+#[derive(Debug)]
+pub enum StateExportResult {
+    RootNotFound,
+    Success(BTreeMap<Key, Value>),
+}
+
+impl fmt::Display for StateFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateFileError::Io(error) => write!(f, "{}", error),
+            StateFileError::Serialization(error) => write!(f, "{}", error),
+            StateFileError::UnknownFormat => write!(f, "not a global state file"),
+            StateFileError::ChecksumMismatch => write!(f, "checksum mismatch"),
+            StateFileError::UnsortedKey(key) => write!(f, "duplicate or unsorted key {}", key),
+        }
+    }
+}
+
+impl From<io::Error> for StateFileError {
+    fn from(error: io::Error) -> Self {
+        StateFileError::Io(error)
+    }
+}
+
+impl From<bytesrepr::Error> for StateFileError {
+    fn from(error: bytesrepr::Error) -> Self {
+        StateFileError::Serialization(error)
+    }
+}
+
+/// Writes `entries` to `writer` in the state file format.
+pub fn write_state<W: Write>(
+    writer: &mut W,
+    entries: &BTreeMap<Key, Value>,
+) -> Result<(), StateFileError> {
+    let mut payload = (entries.len() as u64).to_bytes()?;
+    for (key, value) in entries {
+        payload.append(&mut key.to_bytes()?);
+        payload.append(&mut value.to_bytes()?);
+    }
+    writer.write_all(&STATE_FILE_MAGIC)?;
+    writer.write_all(&Blake2bHash::new(&payload).to_vec())?;
+    writer.write_all(&payload)?;
+    Ok(())
+}
+
+/// Reads the entries of a state file from `reader`.
+pub fn read_state<R: Read>(reader: &mut R) -> Result<BTreeMap<Key, Value>, StateFileError> {
+    let mut contents = Vec::new();
+    reader.read_to_end(&mut contents)?;
+    if contents.len() < STATE_FILE_MAGIC.len() + CHECKSUM_SIZE
+        || contents[..STATE_FILE_MAGIC.len()] != STATE_FILE_MAGIC
+    {
+        return Err(StateFileError::UnknownFormat);
+    }
+    let (checksum, payload) = contents[STATE_FILE_MAGIC.len()..].split_at(CHECKSUM_SIZE);
+    if Blake2bHash::new(payload).to_vec() != checksum {
+        return Err(StateFileError::ChecksumMismatch);
+    }
+
+    let (count, mut stream): (u64, &[u8]) = FromBytes::from_bytes(payload)?;
+    let mut entries = BTreeMap::new();
+    let mut last_key: Option<Key> = None;
+    for _ in 0..count {
+        let (key, rem): (Key, &[u8]) = FromBytes::from_bytes(stream)?;
+        let (value, rem): (Value, &[u8]) = FromBytes::from_bytes(rem)?;
+        let key = key.normalize();
+        if last_key.map_or(false, |last_key| last_key >= key) {
+            return Err(StateFileError::UnsortedKey(key));
+        }
+        last_key = Some(key);
+        entries.insert(key, value);
+        stream = rem;
+    }
+    if stream.is_empty() {
+        Ok(entries)
+    } else {
+        Err(bytesrepr::Error::LeftOverBytes.into())
+    }
+}
+
+pub fn read_state_file<P: AsRef<Path>>(path: P) -> Result<BTreeMap<Key, Value>, StateFileError> {
+    read_state(&mut File::open(path)?)
+}
+
+pub fn write_state_file<P: AsRef<Path>>(
+    path: P,
+    entries: &BTreeMap<Key, Value>,
+) -> Result<(), StateFileError> {
+    let mut file = File::create(path)?;
+    write_state(&mut file, entries)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use common::bytesrepr::ToBytes;
+    use common::key::Key;
+    use common::uref::{AccessRights, URef};
+    use common::value::{Value, U512};
+    use shared::newtypes::Blake2bHash;
+
+    use super::{read_state, write_state, StateFileError, STATE_FILE_MAGIC};
+
+    fn entries() -> BTreeMap<Key, Value> {
+        let mut entries = BTreeMap::new();
+        entries.insert(Key::Hash([2u8; 32]), Value::String("contract".to_string()));
+        entries.insert(Key::Account([1u8; 32]), Value::Int32(1));
+        entries.insert(
+            Key::URef(URef::new([3u8; 32], AccessRights::READ_ADD_WRITE)).normalize(),
+            Value::UInt512(U512::from(1000)),
+        );
+        entries
+    }
+
+    #[test]
+    fn should_read_written_entries() {
+        let mut file = Vec::new();
+        write_state(&mut file, &entries()).unwrap();
+        assert_eq!(read_state(&mut file.as_slice()).unwrap(), entries());
+    }
+
+    #[test]
+    fn should_reject_corrupted_files() {
+        let mut file = Vec::new();
+        write_state(&mut file, &entries()).unwrap();
+
+        let mut truncated = file.clone();
+        truncated.pop();
+        assert_matches!(
+            read_state(&mut truncated.as_slice()),
+            Err(StateFileError::ChecksumMismatch)
+        );
+        assert_matches!(
+            read_state(&mut &file[1..]),
+            Err(StateFileError::UnknownFormat)
+        );
+    }
+
+    #[test]
+    fn should_reject_keys_differing_only_in_access_rights() {
+        let uref = URef::new([3u8; 32], AccessRights::READ);
+        let mut payload = 2u64.to_bytes().unwrap();
+        for access_rights in &[AccessRights::READ, AccessRights::READ_ADD_WRITE] {
+            let key = Key::URef(URef::new(uref.addr(), *access_rights));
+            payload.append(&mut key.to_bytes().unwrap());
+            payload.append(&mut Value::Int32(1).to_bytes().unwrap());
+        }
+        let mut file = STATE_FILE_MAGIC.to_vec();
+        file.extend_from_slice(&Blake2bHash::new(&payload).to_vec());
+        file.extend_from_slice(&payload);
+
+        assert_matches!(
+            read_state(&mut file.as_slice()),
+            Err(StateFileError::UnsortedKey(duplicate)) if duplicate == Key::URef(uref).normalize()
+        );
+    }
+
+    #[test]
+    fn should_reject_duplicate_keys() {
+        let key = Key::Hash([2u8; 32]);
+        let mut payload = 2u64.to_bytes().unwrap();
+        for _ in 0..2 {
+            payload.append(&mut key.to_bytes().unwrap());
+            payload.append(&mut Value::Int32(1).to_bytes().unwrap());
+        }
+        let mut file = STATE_FILE_MAGIC.to_vec();
+        file.extend_from_slice(&Blake2bHash::new(&payload).to_vec());
+        file.extend_from_slice(&payload);
+
+        assert_matches!(
+            read_state(&mut file.as_slice()),
+            Err(StateFileError::UnsortedKey(duplicate)) if duplicate == key
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use common::key::Key;
+use common::uref::URef;
+use common::value::{Value, U512};
+use execution;
+use shared::chainspec::UpgradePoint;
+use shared::newtypes::{Blake2bHash, CorrelationId, Validated};
+use storage::global_state::StateReader;
+use tracking_copy::TrackingCopy;
+
+use super::error::Error;
+use super::execution_effect::ExecutionEffect;
+use super::genesis::{
+    create_local_key, GenesisURefsSource, MINT_PAYMENT_BALANCE_UREF, MINT_PRIVATE_ADDRESS,
+    MINT_REWARDS_BALANCE_UREF, PAYMENT_PURSE, POS_PRIVATE_ADDRESS, REWARDS_PURSE,
+};
+
+/// Purses of the engine paired with the URef of the balance the mint holds for them.
+const SYSTEM_PURSES: [(&str, &str); 2] = [
+    (PAYMENT_PURSE, MINT_PAYMENT_BALANCE_UREF),
+    (REWARDS_PURSE, MINT_REWARDS_BALANCE_UREF),
+];
+
+/// System contracts installed at genesis whose code can be replaced by an upgrade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SystemContract {
+    Mint,
+    ProofOfStake,
+}
+
+impl SystemContract {
+    /// Key of the contract. It doesn't change on upgrades, so the URefs held by accounts and
+    /// other contracts stay valid.
+    pub fn key(self) -> Key {
+        let label = match self {
+            SystemContract::Mint => MINT_PRIVATE_ADDRESS,
+            SystemContract::ProofOfStake => POS_PRIVATE_ADDRESS,
+        };
+        Key::URef(GenesisURefsSource::default().get_uref(label))
+    }
+}
+
+impl fmt::Display for SystemContract {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SystemContract::Mint => write!(f, "mint"),
+            SystemContract::ProofOfStake => write!(f, "proof of stake"),
+        }
+    }
+}
+
+/// Replaces the code of system contracts when the network activates `protocol_version`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeRequest {
+    pub parent_state_hash: Blake2bHash,
+    pub protocol_version: u64,
+    /// New wasm of the contracts to upgrade; contracts not listed keep their code.
+    pub new_code: BTreeMap<SystemContract, Vec<u8>>,
+}
+
+impl UpgradeRequest {
+    /// Request replacing the system contracts of a chainspec's `upgrade` on top of
+    /// `parent_state_hash`.
+    pub fn from_upgrade_point(upgrade: &UpgradePoint, parent_state_hash: Blake2bHash) -> Self {
+        let mut new_code = BTreeMap::new();
+        if let Some(ref mint_code) = upgrade.mint_code {
+            new_code.insert(SystemContract::Mint, mint_code.clone());
+        }
+        if let Some(ref pos_code) = upgrade.pos_code {
+            new_code.insert(SystemContract::ProofOfStake, pos_code.clone());
+        }
+        UpgradeRequest {
+            parent_state_hash,
+            protocol_version: upgrade.protocol_version,
+            new_code,
+        }
+    }
+}
+
+/// Hook reshaping the values stored by a system contract whose code was replaced, e.g. when the
+/// new code expects a different layout of its named keys or local state.
+pub trait Migration {
+    /// Called once per upgraded contract, with the contract's named keys. Other values can be
+    /// read and rewritten through `tracking_copy`.
+    fn migrate<R>(
+        &self,
+        correlation_id: CorrelationId,
+        contract: SystemContract,
+        named_keys: &mut BTreeMap<String, Key>,
+        tracking_copy: &mut TrackingCopy<R>,
+    ) -> Result<(), String>
+    where
+        R: StateReader<Key, Value>,
+        R::Error: Into<execution::Error>;
+}
+
+/// Keeps the stored values as they are.
+pub struct NoMigration;
+
+impl Migration for NoMigration {
+    fn migrate<R>(
+        &self,
+        _correlation_id: CorrelationId,
+        _contract: SystemContract,
+        _named_keys: &mut BTreeMap<String, Key>,
+        _tracking_copy: &mut TrackingCopy<R>,
+    ) -> Result<(), String>
+    where
+        R: StateReader<Key, Value>,
+        R::Error: Into<execution::Error>,
+    {
+        Ok(())
+    }
+}
+
+/// Sets up the payment and rewards purses on states created before the engine had them. Both
+/// purses start empty. The mint holds their balances and proof of stake their URefs, so the mint
+/// has to be upgraded first or along with proof of stake. Purses already set up are left alone.
+pub struct AddSystemPurses;
+
+impl Migration for AddSystemPurses {
+    fn migrate<R>(
+        &self,
+        correlation_id: CorrelationId,
+        contract: SystemContract,
+        named_keys: &mut BTreeMap<String, Key>,
+        tracking_copy: &mut TrackingCopy<R>,
+    ) -> Result<(), String>
+    where
+        R: StateReader<Key, Value>,
+        R::Error: Into<execution::Error>,
+    {
+        let genesis_urefs = GenesisURefsSource::default();
+        let mint_address = genesis_urefs.get_uref(MINT_PRIVATE_ADDRESS).addr();
+        for &(purse_label, balance_label) in &SYSTEM_PURSES {
+            let purse = genesis_urefs.get_uref(purse_label);
+            let has_balance = has_balance(correlation_id, tracking_copy, mint_address, purse)
+                .map_err(|error| error.to_string())?;
+            match contract {
+                SystemContract::Mint if !has_balance => {
+                    let balance_uref = genesis_urefs.get_uref(balance_label);
+                    add_empty_balance(tracking_copy, mint_address, purse, balance_uref)
+                        .map_err(|error| error.to_string())?;
+                    named_keys.insert(balance_uref.as_string(), Key::URef(balance_uref));
+                }
+                SystemContract::Mint => {}
+                SystemContract::ProofOfStake if !has_balance => {
+                    return Err(format!(
+                        "The mint holds no balance for the {}, it has to be upgraded first",
+                        purse_label
+                    ));
+                }
+                SystemContract::ProofOfStake => {
+                    named_keys.insert(purse_label.to_string(), Key::URef(purse));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the mint at `mint_address` holds a balance for `purse`.
+fn has_balance<R>(
+    correlation_id: CorrelationId,
+    tracking_copy: &mut TrackingCopy<R>,
+    mint_address: [u8; 32],
+    purse: URef,
+) -> Result<bool, Error>
+where
+    R: StateReader<Key, Value>,
+    R::Error: Into<execution::Error>,
+{
+    let local_key = create_local_key(mint_address, purse.addr()).map_err(execution::Error::from)?;
+    let local_key = Validated::new(local_key, Validated::valid)?;
+    let balance_key = tracking_copy
+        .read(correlation_id, &local_key)
+        .map_err(Into::into)?;
+    Ok(balance_key.is_some())
+}
+
+/// Makes the mint at `mint_address` hold an empty balance for `purse` under `balance_uref`, the
+/// way genesis sets up the balances of purses.
+fn add_empty_balance<R>(
+    tracking_copy: &mut TrackingCopy<R>,
+    mint_address: [u8; 32],
+    purse: URef,
+    balance_uref: URef,
+) -> Result<(), Error>
+where
+    R: StateReader<Key, Value>,
+    R::Error: Into<execution::Error>,
+{
+    let local_key = create_local_key(mint_address, purse.addr()).map_err(execution::Error::from)?;
+    for (key, value) in vec![
+        (local_key, Value::Key(Key::URef(balance_uref))),
+        (Key::URef(balance_uref), Value::UInt512(U512::zero())),
+    ] {
+        let key = Validated::new(key, Validated::valid)?;
+        let value = Validated::new(value, Validated::valid)?;
+        tracking_copy
+            .write(key, value)
+            .map_err(execution::Error::from)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum UpgradeResult {
+    RootNotFound(Blake2bHash),
+    ContractNotFound(SystemContract),
+    /// Contracts can only be upgraded to a newer protocol version than the one they run at.
+    InvalidProtocolVersion {
+        contract: SystemContract,
+        current: u64,
+        requested: u64,
+    },
+    MigrationFailed(SystemContract, String),
+    /// `effect` replaces the contracts and has to be committed by the node.
+    Success {
+        effect: ExecutionEffect,
+    },
+}
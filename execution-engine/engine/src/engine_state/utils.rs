@@ -4,6 +4,8 @@ use common::key::addr_to_hex;
 use common::value::account::PublicKey;
 use common::value::U512;
 use engine_state;
+use execution;
+use protocol_registry::ProtocolRegistry;
 use wasm_prep::wasm_costs::WasmCosts;
 use wasm_prep::{Preprocessor, WasmiPreprocessor};
 
@@ -26,6 +28,21 @@ impl Into<Vec<u8>> for WasmiBytes {
     }
 }
 
+/// Checks that `raw_bytes` would be accepted by `run_deploy`, i.e. that it passes
+/// the same preprocessing and instantiation steps, without executing it.
+pub fn validate_module(
+    raw_bytes: &[u8],
+    protocol_version: u64,
+    protocols: &ProtocolRegistry,
+) -> Result<(), engine_state::Error> {
+    let semantics = protocols
+        .get(protocol_version)
+        .map_err(execution::Error::from)?;
+    let module = semantics.preprocessor().preprocess(raw_bytes)?;
+    execution::validate_module(module, semantics.host_functions)?;
+    Ok(())
+}
+
 /// Helper function to create validator labels as they are constructed in PoS.
 pub fn pos_validator_key(pk: PublicKey, stakes: U512) -> String {
     let public_key_hex: String = addr_to_hex(&pk.value());
@@ -58,8 +75,9 @@ mod tests {
     use common::key::addr_to_hex;
     use common::value::account::PublicKey;
     use common::value::U512;
+    use protocol_registry::ProtocolRegistry;
 
-    use super::{pos_validator_key, pos_validator_to_tuple};
+    use super::{pos_validator_key, pos_validator_to_tuple, validate_module};
 
     #[test]
     fn should_to_string_pos_validator() {
@@ -89,4 +107,18 @@ mod tests {
         let not_validator_stake = "v_10_ab".to_string();
         assert!(pos_validator_to_tuple(&not_validator_stake).is_none());
     }
+
+    #[test]
+    fn should_validate_module_like_exec_does() {
+        // (module (memory 1) (func (export "call")))
+        let module_bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x08, 0x01, 0x04, 0x63,
+            0x61, 0x6c, 0x6c, 0x00, 0x00, 0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        ];
+        let protocols = ProtocolRegistry::default();
+        assert!(validate_module(&module_bytes, 1, &protocols).is_ok());
+        assert!(validate_module(&module_bytes, 0, &protocols).is_err());
+        assert!(validate_module(&[0xde, 0xad, 0xbe, 0xef], 1, &protocols).is_err());
+    }
 }
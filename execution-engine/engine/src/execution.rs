@@ -4,6 +4,8 @@ use std::convert::TryFrom;
 use std::fmt;
 use std::iter::IntoIterator;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use blake2::digest::{Input, VariableOutput};
 use blake2::VarBlake2b;
@@ -13,36 +15,50 @@ use rand::SeedableRng;
 use rand_chacha::ChaChaRng;
 use wasmi::{
     Error as InterpreterError, Externals, HostError, ImportsBuilder, MemoryRef, ModuleInstance,
-    ModuleRef, RuntimeArgs, RuntimeValue, Trap,
+    ModuleRef, RuntimeArgs, RuntimeValue, Trap, TrapKind,
 };
 
 use args::Args;
 use common::bytesrepr::{deserialize, Error as BytesReprError, ToBytes, U32_SIZE};
+use common::chain_parameters::ChainParameters;
 use common::contract_api::argsparser::ArgsParser;
 use common::contract_api::{PurseTransferResult, TransferResult};
 use common::key::Key;
+use common::precompile::Precompile;
 use common::uref::{AccessRights, URef};
 use common::value::account::{
-    ActionType, AddKeyFailure, BlockTime, PublicKey, PurseId, RemoveKeyFailure,
-    SetThresholdFailure, Weight, PUBLIC_KEY_SIZE,
+    ActionType, AddKeyFailure, AssociatedKeys, BlockTime, PublicKey, PurseId, RecoveryFailure,
+    RemoveKeyFailure, SetThresholdFailure, Weight, MAX_KEYS, PUBLIC_KEY_SIZE,
 };
 use common::value::{Account, Value, U512};
+use engine_state::execution_effect::TransferEvent;
 use engine_state::execution_result::ExecutionResult;
+use engine_state::genesis::{GenesisURefsSource, POS_PURSE};
+use engine_state::rent;
 use execution::Error::{KeyNotFound, URefNotFound};
 use function_index::FunctionIndex;
-use resolvers::create_module_resolver;
+use precompiles;
+use protocol_registry::ProtocolRegistry;
 use resolvers::error::ResolverError;
 use resolvers::memory_resolver::MemoryResolver;
+use resolvers::{self, create_module_resolver, HostFunctions};
 use runtime_context::RuntimeContext;
+use shared::gas::Gas;
 use shared::newtypes::{CorrelationId, Validated};
 use shared::transform::TypeMismatch;
+use shared::validators::ValidationError;
 use storage::global_state::StateReader;
 use tracking_copy::TrackingCopy;
+use wasm_prep::trap_frames::{self, TRAP_FRAME_EXPORT};
 use URefAddr;
 
 pub const MINT_NAME: &str = "mint";
 const POS_NAME: &str = "pos";
 
+/// Returned by `call_contract_with_gas` instead of the size of the result when the callee ran
+/// out of its gas allowance.
+const CALL_OUT_OF_GAS: i32 = -1;
+
 #[derive(Debug)]
 pub enum Error {
     Interpreter(InterpreterError),
@@ -50,7 +66,13 @@ pub enum Error {
     BytesRepr(BytesReprError),
     KeyNotFound(Key),
     AccountNotFound(Key),
-    TypeMismatch(TypeMismatch),
+    /// A value of unexpected type was found under `key`, or at the end of `path` when following
+    /// named keys from the account or contract under `key`.
+    TypeMismatch {
+        key: Key,
+        path: Vec<String>,
+        mismatch: TypeMismatch,
+    },
     InvalidAccess {
         required: AccessRights,
     },
@@ -72,6 +94,98 @@ pub enum Error {
     AddKeyFailure(AddKeyFailure),
     RemoveKeyFailure(RemoveKeyFailure),
     SetThresholdFailure(SetThresholdFailure),
+    RecoveryFailure(RecoveryFailure),
+    /// The caller holds none of the access groups of the called contract.
+    AccessGroupDenied(Key),
+    /// Adding named keys to the account or contract under `key` would exceed `limit`.
+    NamedKeysLimitExceeded {
+        key: Key,
+        limit: usize,
+    },
+    /// The contract tried to store a byte array of `length` bytes, longer than `limit`.
+    ByteArrayTooLong {
+        length: usize,
+        limit: usize,
+    },
+    /// The wasm code itself trapped, e.g. on `unreachable` after a panic.
+    Trap(TrapInfo),
+    /// `call_precompile` was called with an id which isn't a known precompile.
+    UnknownPrecompile(u32),
+    /// The deploy ran for longer than the executor's wall-clock limit.
+    Timeout(Duration),
+    /// A key or value written to global state was rejected, see `shared::validators`.
+    Validation(ValidationError),
+    /// The rent of the key ran out and it was marked as reclaimable. It can't be used until its
+    /// rent is paid again, see `engine_state::rent`.
+    RentExpired(Key),
+}
+
+/// Describes a trap raised by a wasm instruction, as opposed to one raised by a host function.
+///
+/// The function which trapped is recorded by the instrumentation of `wasm_prep::trap_frames`,
+/// and named if the module kept its name section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrapInfo {
+    pub kind: String,
+    /// Index of the function in the module as deployed, imports included.
+    pub function_index: Option<u32>,
+    pub function_name: Option<String>,
+    pub last_host_function: Option<&'static str>,
+}
+
+impl TrapInfo {
+    fn new(
+        kind: &TrapKind,
+        function_index: Option<u32>,
+        function_name: Option<String>,
+        last_host_function: Option<&'static str>,
+    ) -> Self {
+        let kind = match kind {
+            TrapKind::Unreachable => {
+                "unreachable instruction executed (the contract probably panicked)".to_string()
+            }
+            TrapKind::MemoryAccessOutOfBounds => "out of bounds memory access".to_string(),
+            TrapKind::TableAccessOutOfBounds => "out of bounds table access".to_string(),
+            TrapKind::ElemUninitialized => "call through uninitialized table element".to_string(),
+            TrapKind::DivisionByZero => "integer division by zero".to_string(),
+            TrapKind::InvalidConversionToInt => "invalid conversion to integer".to_string(),
+            TrapKind::StackOverflow => "call stack exhausted".to_string(),
+            TrapKind::UnexpectedSignature => "indirect call signature mismatch".to_string(),
+            other => format!("{:?}", other),
+        };
+        TrapInfo {
+            kind,
+            function_index,
+            function_name,
+            last_host_function,
+        }
+    }
+}
+
+impl fmt::Display for TrapInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Trap: {}", self.kind)?;
+        if let Some(index) = self.function_index {
+            write!(f, " in function {}", index)?;
+            if let Some(ref name) = self.function_name {
+                write!(f, " ({})", name)?;
+            }
+        }
+        if let Some(name) = self.last_host_function {
+            write!(f, " after calling host function {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error {
+    pub fn type_mismatch(key: Key, mismatch: TypeMismatch) -> Self {
+        Error::TypeMismatch {
+            key,
+            path: Vec::new(),
+            mismatch,
+        }
+    }
 }
 
 impl fmt::Display for Error {
@@ -134,6 +248,18 @@ impl From<SetThresholdFailure> for Error {
     }
 }
 
+impl From<RecoveryFailure> for Error {
+    fn from(err: RecoveryFailure) -> Error {
+        Error::RecoveryFailure(err)
+    }
+}
+
+impl From<ValidationError> for Error {
+    fn from(err: ValidationError) -> Error {
+        Error::Validation(err)
+    }
+}
+
 impl HostError for Error {}
 
 pub struct Runtime<'a, R> {
@@ -142,6 +268,19 @@ pub struct Runtime<'a, R> {
     result: Vec<u8>,
     host_buf: Vec<u8>,
     context: RuntimeContext<'a, R>,
+    // Reported in traps to help locating them.
+    last_host_function: Option<FunctionIndex>,
+    // Shared with the contracts called by the deploy.
+    deadline: Option<Deadline>,
+    // Looked up for the version of the code being run, which differs in called contracts stored
+    // before an upgrade.
+    protocols: &'a ProtocolRegistry,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Deadline {
+    instant: Instant,
+    timeout: Duration,
 }
 
 /// Rename function called `name` in the `module` to `call`.
@@ -165,13 +304,80 @@ where
     R::Error: Into<Error>,
 {
     #[allow(clippy::too_many_arguments)]
-    pub fn new(memory: MemoryRef, module: Module, context: RuntimeContext<'a, R>) -> Self {
+    pub fn new(
+        memory: MemoryRef,
+        module: Module,
+        context: RuntimeContext<'a, R>,
+        protocols: &'a ProtocolRegistry,
+    ) -> Self {
         Runtime {
             memory,
             module,
             result: Vec::new(),
             host_buf: Vec::new(),
             context,
+            last_host_function: None,
+            deadline: None,
+            protocols,
+        }
+    }
+
+    /// Aborts execution with `Error::Timeout` once `timeout` has elapsed. It's checked on every
+    /// host call, and as the gas function is called at the start of every basic block, even a
+    /// contract looping without calling anything else is stopped shortly after the deadline.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Deadline {
+            instant: Instant::now() + timeout,
+            timeout,
+        });
+        self
+    }
+
+    fn check_deadline(&self) -> Result<(), Error> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline.instant => {
+                Err(Error::Timeout(deadline.timeout))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Turns an error of the wasm interpreter into a `Trap` error if it was raised by wasm code
+    /// of `instance`.
+    fn trap_error(&self, instance: &ModuleRef, error: InterpreterError) -> Error {
+        let trap_info = match error {
+            InterpreterError::Trap(ref trap) => match trap.kind() {
+                // Surfaced as is rather than as an interpreter error, so the node can tell a deploy
+                // which was cut off apart from one which failed on its own.
+                TrapKind::Host(host_error) => match host_error.downcast_ref::<Error>() {
+                    Some(Error::Timeout(timeout)) => return Error::Timeout(*timeout),
+                    _ => None,
+                },
+                kind => {
+                    let host_functions = self
+                        .protocols
+                        .get(self.context.protocol_version())
+                        .ok()
+                        .map(|semantics| semantics.host_functions);
+                    let last_host_function = self
+                        .last_host_function
+                        .and_then(|func| resolvers::host_function_name(host_functions?, func));
+                    let function_index = trap_frame(instance);
+                    let function_name = function_index
+                        .and_then(|index| trap_frames::function_name(&self.module, index));
+                    Some(TrapInfo::new(
+                        kind,
+                        function_index,
+                        function_name,
+                        last_host_function,
+                    ))
+                }
+            },
+            _ => None,
+        };
+        match trap_info {
+            Some(trap_info) => Error::Trap(trap_info),
+            None => Error::Interpreter(error),
         }
     }
 
@@ -200,6 +406,72 @@ where
         }
     }
 
+    /// Charges the cost of calling `func` in the current protocol version.
+    fn charge_host_function(&mut self, func: FunctionIndex) -> Result<(), Trap> {
+        let semantics = self
+            .protocols
+            .get(self.context.protocol_version())
+            .map_err(Error::from)?;
+        let cost = func.cost(&semantics.wasm_costs);
+        self.gas(cost)
+    }
+
+    /// Charges for `size` bytes the host allocates on behalf of the contract, which unlike its
+    /// own memory aren't bounded by the memory limit, e.g. values read from the global state.
+    fn charge_host_alloc(&mut self, size: usize) -> Result<(), Error> {
+        let host_alloc = self
+            .protocols
+            .get(self.context.protocol_version())?
+            .wasm_costs
+            .host_alloc;
+        if self.charge_gas(host_alloc_cost(size, host_alloc)) {
+            Ok(())
+        } else {
+            Err(Error::GasLimit)
+        }
+    }
+
+    /// Charges for `size` bytes copied by the host.
+    fn charge_memcpy(&mut self, size: usize) -> Result<(), Error> {
+        let memcpy = self
+            .protocols
+            .get(self.context.protocol_version())?
+            .wasm_costs
+            .memcpy;
+        if self.charge_gas(host_alloc_cost(size, memcpy)) {
+            Ok(())
+        } else {
+            Err(Error::GasLimit)
+        }
+    }
+
+    /// Charges for the bytes of the values written to the global state by the last host call,
+    /// which the tracking copy serialized to check their size.
+    fn charge_written_bytes(&mut self) -> Result<(), Error> {
+        let written_bytes = self.context.state().borrow_mut().take_written_bytes();
+        if written_bytes == 0 {
+            return Ok(());
+        }
+        let host_write_per_byte = self
+            .protocols
+            .get(self.context.protocol_version())?
+            .wasm_costs
+            .host_write_per_byte;
+        if self.charge_gas(written_bytes.saturating_mul(u64::from(host_write_per_byte))) {
+            Ok(())
+        } else {
+            Err(Error::GasLimit)
+        }
+    }
+
+    /// Places `bytes` in the host buffer, for the contract to copy into its memory, and returns
+    /// their size.
+    fn set_host_buf(&mut self, bytes: Vec<u8>) -> Result<usize, Error> {
+        self.charge_host_alloc(bytes.len())?;
+        self.host_buf = bytes;
+        Ok(self.host_buf.len())
+    }
+
     fn bytes_from_mem(&self, ptr: u32, size: usize) -> Result<Vec<u8>, Error> {
         self.memory.get(ptr, size).map_err(Into::into)
     }
@@ -255,8 +527,8 @@ where
     /// to the caller.
     pub fn load_arg(&mut self, i: usize) -> Result<usize, Trap> {
         if i < self.context.args().len() {
-            self.host_buf = self.context.args()[i].clone();
-            Ok(self.host_buf.len())
+            let arg = self.context.args()[i].clone();
+            self.set_host_buf(arg).map_err(Into::into)
         } else {
             Err(Error::ArgIndexOutOfBounds(i).into())
         }
@@ -271,8 +543,7 @@ where
             .ok_or_else(|| Error::URefNotFound(name))?;
         let uref_bytes = uref.to_bytes().map_err(Error::BytesRepr)?;
 
-        self.host_buf = uref_bytes;
-        Ok(self.host_buf.len())
+        self.set_host_buf(uref_bytes).map_err(Into::into)
     }
 
     pub fn has_uref(&mut self, name_ptr: u32, name_size: u32) -> Result<i32, Trap> {
@@ -331,6 +602,42 @@ where
             .map_err(|e| Error::Interpreter(e).into())
     }
 
+    /// Emits the bytes at `data_ptr` as an event of the current context.
+    fn emit_event(&mut self, data_ptr: u32, data_size: u32) -> Result<(), Trap> {
+        let data = self.bytes_from_mem(data_ptr, data_size as usize)?;
+        self.context.emit_event(data).map_err(Into::into)
+    }
+
+    /// Serializes the event log of the account or contract under the key at `key_ptr` into the
+    /// host buffer and returns its byte size.
+    fn read_event_log(&mut self, key_ptr: u32, key_size: u32) -> Result<usize, Trap> {
+        let key = self.key_from_mem(key_ptr, key_size)?;
+        let bytes = self
+            .context
+            .read_event_log(&key)?
+            .to_bytes()
+            .map_err(Error::BytesRepr)?;
+        self.set_host_buf(bytes).map_err(Into::into)
+    }
+
+    /// Writes the chain parameters of the current protocol version to [dest_ptr] in Wasm memory.
+    fn get_chain_parameters(&self, dest_ptr: u32) -> Result<(), Trap> {
+        let limits = &self
+            .protocols
+            .get(self.context.protocol_version())
+            .map_err(Error::from)?
+            .limits;
+        let chain_parameters = ChainParameters {
+            max_associated_keys: MAX_KEYS as u32,
+            block_gas_limit: limits.block_gas_limit,
+        }
+        .to_bytes()
+        .map_err(Error::BytesRepr)?;
+        self.memory
+            .set(dest_ptr, &chain_parameters)
+            .map_err(|e| Error::Interpreter(e).into())
+    }
+
     pub fn set_mem_from_buf(&mut self, dest_ptr: u32) -> Result<(), Trap> {
         self.memory
             .set(dest_ptr, &self.host_buf)
@@ -369,17 +676,26 @@ where
     }
 
     /// Calls contract living under a `key`, with supplied `args` and extra `urefs`.
+    ///
+    /// With a `gas_allowance`, the callee can spend at most that much gas, on top of what was
+    /// already spent, within the gas limit of the current call.
     pub fn call_contract(
         &mut self,
         key: Key,
         args_bytes: Vec<u8>,
         urefs_bytes: Vec<u8>,
+        gas_allowance: Option<u64>,
     ) -> Result<usize, Error> {
+        // The arguments are copied once more into the memory of the callee.
+        self.charge_host_alloc(args_bytes.len())?;
         let (args, module, mut refs, protocol_version) = {
             match self.context.read_gs(&key)? {
                 None => Err(Error::KeyNotFound(key)),
                 Some(value) => {
                     if let Value::Contract(contract) = value {
+                        if !self.context.holds_any_uref(contract.access_groups()) {
+                            return Err(Error::AccessGroupDenied(key));
+                        }
                         let args: Vec<Vec<u8>> = deserialize(&args_bytes)?;
                         let module = parity_wasm::deserialize_buffer(contract.bytes())?;
 
@@ -400,6 +716,11 @@ where
         }?;
 
         let extra_urefs = self.context.deserialize_keys(&urefs_bytes)?;
+        let gas_limit = subcall_gas_limit(
+            self.context.gas_counter(),
+            self.context.gas_limit(),
+            gas_allowance,
+        );
         let result = sub_call(
             module,
             args,
@@ -408,15 +729,46 @@ where
             self,
             extra_urefs,
             protocol_version,
+            gas_limit,
         )?;
-        self.host_buf = result;
-        Ok(self.host_buf.len())
+        self.set_host_buf(result)
+    }
+
+    /// Calls a contract which can spend at most `gas_allowance`. If it runs out of it, its
+    /// effects are discarded, the whole allowance is charged and `CALL_OUT_OF_GAS` is returned
+    /// instead of the size of the result, so that the caller can carry on.
+    pub fn call_contract_with_gas(
+        &mut self,
+        key: Key,
+        args_bytes: Vec<u8>,
+        urefs_bytes: Vec<u8>,
+        gas_allowance: u64,
+    ) -> Result<i32, Error> {
+        let gas_limit = subcall_gas_limit(
+            self.context.gas_counter(),
+            self.context.gas_limit(),
+            Some(gas_allowance),
+        );
+        // Running out of gas within the limit of the current call fails the whole call as usual.
+        let bounded = gas_limit < self.context.gas_limit();
+        // The changes made so far are copied, so that they can be restored.
+        let checkpoint_size = self.context.state().borrow().checkpoint_size();
+        self.charge_memcpy(checkpoint_size)?;
+        let checkpoint = self.context.state().borrow().checkpoint();
+        match self.call_contract(key, args_bytes, urefs_bytes, Some(gas_allowance)) {
+            Ok(size) => Ok(size as i32),
+            Err(Error::GasLimit) if bounded => {
+                self.context.state().borrow_mut().restore(checkpoint);
+                self.context.set_gas_counter(gas_limit);
+                Ok(CALL_OUT_OF_GAS)
+            }
+            Err(error) => Err(error),
+        }
     }
 
     pub fn serialize_function(&mut self, name_ptr: u32, name_size: u32) -> Result<usize, Trap> {
         let fn_bytes = self.get_function_by_name(name_ptr, name_size)?;
-        self.host_buf = fn_bytes;
-        Ok(self.host_buf.len())
+        self.set_host_buf(fn_bytes).map_err(Into::into)
     }
 
     fn serialize_known_urefs(&mut self) -> Result<usize, Trap> {
@@ -425,9 +777,25 @@ where
             .list_known_urefs()
             .to_bytes()
             .map_err(Error::BytesRepr)?;
-        let length = bytes.len();
-        self.host_buf = bytes;
-        Ok(length)
+        self.set_host_buf(bytes).map_err(Into::into)
+    }
+
+    /// Serializes up to `limit` named keys coming after the name at `after_ptr`, a serialized
+    /// `Option<String>`, into the host buffer and returns their byte size.
+    fn serialize_named_keys_page(
+        &mut self,
+        after_ptr: u32,
+        after_size: u32,
+        limit: u32,
+    ) -> Result<usize, Trap> {
+        let after_bytes = self.bytes_from_mem(after_ptr, after_size as usize)?;
+        let after: Option<String> = deserialize(&after_bytes).map_err(Error::BytesRepr)?;
+        let bytes = self
+            .context
+            .named_keys_page(after.as_ref().map(String::as_str), limit as usize)
+            .to_bytes()
+            .map_err(Error::BytesRepr)?;
+        self.set_host_buf(bytes).map_err(Into::into)
     }
 
     /// Tries to store a function, represented as bytes from the Wasm memory, into the GlobalState
@@ -436,16 +804,35 @@ where
         &mut self,
         fn_bytes: Vec<u8>,
         urefs: BTreeMap<String, Key>,
+        access_groups: Vec<URef>,
     ) -> Result<[u8; 32], Error> {
         let contract = common::value::contract::Contract::new(
             fn_bytes,
             urefs,
             self.context.protocol_version(),
-        );
+        )
+        .with_access_groups(access_groups);
         let new_hash = self.context.store_contract(contract.into())?;
         Ok(new_hash)
     }
 
+    /// Stores a function at the address derived from the deploying account and
+    /// `contract_name`, returning `None` if the address is taken.
+    pub fn store_function_at_hash(
+        &mut self,
+        fn_bytes: Vec<u8>,
+        urefs: BTreeMap<String, Key>,
+        contract_name: &str,
+    ) -> Result<Option<[u8; 32]>, Error> {
+        let contract = common::value::contract::Contract::new(
+            fn_bytes,
+            urefs,
+            self.context.protocol_version(),
+        );
+        self.context
+            .store_contract_at_hash(contract.into(), contract_name)
+    }
+
     /// Writes function address (`hash_bytes`) into the Wasm memory (at `dest_ptr` pointer).
     fn function_address(&mut self, hash_bytes: [u8; 32], dest_ptr: u32) -> Result<(), Trap> {
         self.memory
@@ -509,8 +896,7 @@ where
         let key = self.key_from_mem(key_ptr, key_size)?;
         let value: Option<Value> = self.context.read_gs(&key)?;
         let value_bytes = value.to_bytes().map_err(Error::BytesRepr)?;
-        self.host_buf = value_bytes;
-        Ok(self.host_buf.len())
+        self.set_host_buf(value_bytes).map_err(Into::into)
     }
 
     /// Similar to `read`, this function is for reading from the "local cluster" of global state
@@ -518,8 +904,64 @@ where
         let key_bytes = self.bytes_from_mem(key_ptr, key_size as usize)?;
         let value: Option<Value> = self.context.read_ls(&key_bytes)?;
         let value_bytes = value.to_bytes().map_err(Error::BytesRepr)?;
-        self.host_buf = value_bytes;
-        Ok(self.host_buf.len())
+        self.set_host_buf(value_bytes).map_err(Into::into)
+    }
+
+    /// Reads the value found by following the named keys in the path (defined as
+    /// `path_ptr` and `path_size` tuple) from the key (defined as `key_ptr` and
+    /// `key_size` tuple), without calling into the contract owning it.
+    pub fn query_state(
+        &mut self,
+        key_ptr: u32,
+        key_size: u32,
+        path_ptr: u32,
+        path_size: u32,
+    ) -> Result<usize, Trap> {
+        let key = self.key_from_mem(key_ptr, key_size)?;
+        let path_bytes = self.bytes_from_mem(path_ptr, path_size as usize)?;
+        let path: Vec<String> = deserialize(&path_bytes).map_err(Error::BytesRepr)?;
+        let value: Option<Value> = self.context.query_gs(&key, &path)?;
+        let value_bytes = value.to_bytes().map_err(Error::BytesRepr)?;
+        self.set_host_buf(value_bytes).map_err(Into::into)
+    }
+
+    /// Reads a `URef` (defined as `uref_ptr` and `uref_size` tuple) from Wasm memory.
+    fn uref_from_mem(&mut self, uref_ptr: u32, uref_size: u32) -> Result<URef, Error> {
+        let bytes = self.bytes_from_mem(uref_ptr, uref_size as usize)?;
+        deserialize(&bytes).map_err(Into::into)
+    }
+
+    /// Reads an item of a dictionary into the host buffer, returning its size.
+    pub fn dictionary_get(
+        &mut self,
+        uref_ptr: u32,
+        uref_size: u32,
+        item_key_ptr: u32,
+        item_key_size: u32,
+    ) -> Result<usize, Trap> {
+        let uref = self.uref_from_mem(uref_ptr, uref_size)?;
+        let item_key = self.string_from_mem(item_key_ptr, item_key_size)?;
+        let value: Option<Value> = self.context.dictionary_get(&uref, &item_key)?;
+        let value_bytes = value.to_bytes().map_err(Error::BytesRepr)?;
+        self.set_host_buf(value_bytes).map_err(Into::into)
+    }
+
+    /// Writes `value` as an item of a dictionary.
+    pub fn dictionary_put(
+        &mut self,
+        uref_ptr: u32,
+        uref_size: u32,
+        item_key_ptr: u32,
+        item_key_size: u32,
+        value_ptr: u32,
+        value_size: u32,
+    ) -> Result<(), Trap> {
+        let uref = self.uref_from_mem(uref_ptr, uref_size)?;
+        let item_key = self.string_from_mem(item_key_ptr, item_key_size)?;
+        let value = self.value_from_mem(value_ptr, value_size)?;
+        self.context
+            .dictionary_put(&uref, &item_key, value)
+            .map_err(Into::into)
     }
 
     /// Reverts contract execution with a status specified.
@@ -584,6 +1026,65 @@ where
         }
     }
 
+    fn add_recovery_key(&mut self, public_key_ptr: u32, weight_value: u8) -> Result<i32, Trap> {
+        let public_key = {
+            let source_serialized =
+                self.bytes_from_mem(public_key_ptr, PUBLIC_KEY_SIZE + U32_SIZE)?;
+            let source: PublicKey = deserialize(&source_serialized).map_err(Error::BytesRepr)?;
+            source
+        };
+        let weight = Weight::new(weight_value);
+        match self.context.add_recovery_key(public_key, weight) {
+            Ok(_) => Ok(0),
+            Err(Error::AddKeyFailure(e)) => Ok(e as i32),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn remove_recovery_key(&mut self, public_key_ptr: u32) -> Result<i32, Trap> {
+        let public_key = {
+            let source_serialized =
+                self.bytes_from_mem(public_key_ptr, PUBLIC_KEY_SIZE + U32_SIZE)?;
+            let source: PublicKey = deserialize(&source_serialized).map_err(Error::BytesRepr)?;
+            source
+        };
+        match self.context.remove_recovery_key(public_key) {
+            Ok(_) => Ok(0),
+            Err(Error::RemoveKeyFailure(e)) => Ok(e as i32),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replaces the associated keys of the (inactive) account `account_ptr` with the serialized
+    /// `BTreeMap<PublicKey, Weight>` at `keys_ptr`, using the caller's key as the recovery key.
+    fn recover_account(
+        &mut self,
+        account_ptr: u32,
+        account_size: u32,
+        keys_ptr: u32,
+        keys_size: u32,
+    ) -> Result<i32, Trap> {
+        let account: PublicKey = {
+            let bytes = self.bytes_from_mem(account_ptr, account_size as usize)?;
+            deserialize(&bytes).map_err(Error::BytesRepr)?
+        };
+        let keys_map: BTreeMap<PublicKey, Weight> = {
+            let bytes = self.bytes_from_mem(keys_ptr, keys_size as usize)?;
+            deserialize(&bytes).map_err(Error::BytesRepr)?
+        };
+        let mut new_keys = AssociatedKeys::empty();
+        for (key, weight) in keys_map {
+            if new_keys.add_key(key, weight).is_err() {
+                return Ok(RecoveryFailure::InvalidKeys as i32);
+            }
+        }
+        match self.context.recover_account(account, new_keys) {
+            Ok(_) => Ok(0),
+            Err(Error::RecoveryFailure(e)) => Ok(e as i32),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     /// looks up the public mint contract key in the caller's [uref_lookup] map.
     fn get_mint_contract_public_uref_key(&mut self) -> Result<Key, Error> {
         match self.context.get_uref(MINT_NAME) {
@@ -628,7 +1129,7 @@ where
 
         let urefs_bytes = Vec::<Key>::new().to_bytes()?;
 
-        self.call_contract(mint_contract_key, args_bytes, urefs_bytes)?;
+        self.call_contract(mint_contract_key, args_bytes, urefs_bytes, None)?;
 
         let result: URef = deserialize(&self.host_buf)?;
 
@@ -658,7 +1159,7 @@ where
 
         let urefs_bytes = vec![Key::URef(source_value), Key::URef(target_value)].to_bytes()?;
 
-        self.call_contract(mint_contract_key, args_bytes, urefs_bytes)?;
+        self.call_contract(mint_contract_key, args_bytes, urefs_bytes, None)?;
 
         let result: String = deserialize(&self.host_buf)?;
 
@@ -778,7 +1279,9 @@ where
         }
     }
 
-    /// Transfers `amount` of tokens from `source` purse to `target` purse.
+    /// Transfers `amount` of tokens from `source` purse to `target` purse. The caller needs WRITE
+    /// rights on `source` and ADD rights on `target`; successful transfers are recorded in the
+    /// effects of the deploy.
     fn transfer_from_purse_to_purse(
         &mut self,
         source_ptr: u32,
@@ -803,15 +1306,161 @@ where
             deserialize(&bytes).map_err(Error::BytesRepr)?
         };
 
+        let source_key = Key::URef(source_purse.value());
+        let target_key = Key::URef(target_purse.value());
+        self.context.validate_key(&source_key)?;
+        self.context.validate_key(&target_key)?;
+        if !self.context.is_writeable(&source_key) {
+            return Err(Error::InvalidAccess {
+                required: AccessRights::WRITE,
+            });
+        }
+        if !self.context.is_addable(&target_key) {
+            return Err(Error::InvalidAccess {
+                required: AccessRights::ADD,
+            });
+        }
+
         let mint_contract_key = Key::URef(self.get_mint_contract_uref()?);
 
         if self.mint_transfer(mint_contract_key, source_purse, target_purse, amount)? {
+            let initiator = self.context.base_key();
+            self.context.record_transfer(TransferEvent {
+                initiator,
+                source: source_purse,
+                target: target_purse,
+                amount,
+            });
             Ok(PurseTransferResult::TransferSuccessful)
         } else {
             // TODO: Improve returned type (insufficient funds/access rights, non-existent purses)
             Ok(PurseTransferResult::TransferError)
         }
     }
+
+    /// Pays `amount` from the `source` purse into the PoS purse as rent of the value under `key`,
+    /// extending the time it's paid through in proportion to its size. Anyone can pay the rent of
+    /// any existing key; the caller needs WRITE rights on `source`. Fails without transferring
+    /// anything if rent isn't enabled.
+    fn pay_rent(
+        &mut self,
+        key_ptr: u32,
+        key_size: u32,
+        source_ptr: u32,
+        source_size: u32,
+        amount_ptr: u32,
+        amount_size: u32,
+    ) -> Result<PurseTransferResult, Error> {
+        let key = self.key_from_mem(key_ptr, key_size)?.normalize();
+        let source_purse: PurseId = {
+            let bytes = self.bytes_from_mem(source_ptr, source_size as usize)?;
+            deserialize(&bytes).map_err(Error::BytesRepr)?
+        };
+        let amount: U512 = {
+            let bytes = self.bytes_from_mem(amount_ptr, amount_size as usize)?;
+            deserialize(&bytes).map_err(Error::BytesRepr)?
+        };
+
+        let source_key = Key::URef(source_purse.value());
+        self.context.validate_key(&source_key)?;
+        if !self.context.is_writeable(&source_key) {
+            return Err(Error::InvalidAccess {
+                required: AccessRights::WRITE,
+            });
+        }
+        let config = match self.context.state().borrow().rent_config() {
+            Some(config) => *config,
+            None => return Ok(PurseTransferResult::TransferError),
+        };
+        let correlation_id = self.context.correlation_id();
+        let validated_key = Validated::new(key, Validated::valid)?;
+        let size = match self
+            .context
+            .state()
+            .borrow_mut()
+            .read(correlation_id, &validated_key)
+            .map_err(Into::into)?
+        {
+            Some(value) => value.to_bytes()?.len(),
+            None => return Ok(PurseTransferResult::TransferError),
+        };
+
+        let mint_contract_key = Key::URef(self.get_mint_contract_uref()?);
+        let pos_purse = URef::new(
+            GenesisURefsSource::default().get_uref(POS_PURSE).addr(),
+            AccessRights::ADD,
+        );
+        self.context.insert_uref(pos_purse);
+        let target_purse = PurseId::new(pos_purse);
+        if !self.mint_transfer(mint_contract_key, source_purse, target_purse, amount)? {
+            return Ok(PurseTransferResult::TransferError);
+        }
+        let initiator = self.context.base_key();
+        self.context.record_transfer(TransferEvent {
+            initiator,
+            source: source_purse,
+            target: target_purse,
+            amount,
+        });
+
+        let blocktime = self.context.get_blocktime();
+        let state = self.context.state();
+        let mut state = state.borrow_mut();
+        let record = state
+            .read_rent_record(correlation_id, &key)
+            .map_err(Into::into)?;
+        state.write_rent_record(&key, rent::pay(record, blocktime, amount, size, &config))?;
+        Ok(PurseTransferResult::TransferSuccessful)
+    }
+
+    /// Asks the mint for the balance of a purse, placing the serialized `Option<U512>` in the
+    /// host buffer and returning its size. Only READ rights on the purse are required, so that
+    /// e.g. escrow contracts can check deposits without being able to withdraw from the purse.
+    fn get_balance(&mut self, purse_ptr: u32, purse_size: u32) -> Result<usize, Error> {
+        let purse_id: PurseId = {
+            let bytes = self.bytes_from_mem(purse_ptr, purse_size as usize)?;
+            deserialize(&bytes).map_err(Error::BytesRepr)?
+        };
+        let purse_key = Key::URef(purse_id.value());
+        self.context.validate_key(&purse_key)?;
+        if !self.context.is_readable(&purse_key) {
+            return Err(Error::InvalidAccess {
+                required: AccessRights::READ,
+            });
+        }
+
+        let mint_contract_key = Key::URef(self.get_mint_contract_uref()?);
+        // The mint only gets to read the purse, whatever the rights of the caller.
+        let purse = URef::new(purse_id.value().addr(), AccessRights::READ);
+        let args_bytes = {
+            let args = ("balance", purse);
+            ArgsParser::parse(&args).and_then(|args| args.to_bytes())?
+        };
+        let urefs_bytes = vec![Key::URef(purse)].to_bytes()?;
+        self.call_contract(mint_contract_key, args_bytes, urefs_bytes, None)
+    }
+
+    /// Asks the mint for the total supply of tokens, placing the serialized `Option<U512>` in
+    /// the host buffer and returning its size.
+    fn get_total_supply(&mut self) -> Result<usize, Error> {
+        let mint_contract_key = Key::URef(self.get_mint_contract_uref()?);
+        let args_bytes = {
+            let args = "total_supply";
+            ArgsParser::parse(&args).and_then(|args| args.to_bytes())?
+        };
+        let urefs_bytes = Vec::<Key>::new().to_bytes()?;
+        self.call_contract(mint_contract_key, args_bytes, urefs_bytes, None)
+    }
+
+    /// Charges the fixed cost of the precompile `id` and runs it natively on the input, placing
+    /// its output in the host buffer and returning its size.
+    fn call_precompile(&mut self, id: u32, input_ptr: u32, input_size: u32) -> Result<usize, Trap> {
+        let precompile = Precompile::from_u32(id).ok_or(Error::UnknownPrecompile(id))?;
+        self.gas(precompiles::cost(precompile, input_size as usize))?;
+        let input = self.bytes_from_mem(input_ptr, input_size as usize)?;
+        let output = precompiles::run(precompile, &input).map_err(Error::BytesRepr)?;
+        self.set_host_buf(output).map_err(Into::into)
+    }
 }
 
 fn as_usize(u: u32) -> usize {
@@ -828,7 +1477,12 @@ where
         args: RuntimeArgs,
     ) -> Result<Option<RuntimeValue>, Trap> {
         let func = FunctionIndex::try_from(index).expect("unknown function index");
-        match func {
+        if func != FunctionIndex::GasFuncIndex {
+            self.last_host_function = Some(func);
+        }
+        self.check_deadline()?;
+        self.charge_host_function(func)?;
+        let result = match func {
             FunctionIndex::ReadFuncIndex => {
                 // args(0) = pointer to key in Wasm memory
                 // args(1) = size of key in Wasm memory
@@ -960,10 +1614,37 @@ where
                 let urefs_bytes =
                     self.bytes_from_mem(extra_urefs_ptr, extra_urefs_size as usize)?;
 
-                let size = self.call_contract(key_contract, args_bytes, urefs_bytes)?;
+                let size = self.call_contract(key_contract, args_bytes, urefs_bytes, None)?;
                 Ok(Some(RuntimeValue::I32(size as i32)))
             }
 
+            FunctionIndex::CallContractWithGasIndex => {
+                // args(0) to args(5) are the same as for `call_contract`
+                // args(6) = max gas the callee can spend
+                let (
+                    key_ptr,
+                    key_size,
+                    args_ptr,
+                    args_size,
+                    extra_urefs_ptr,
+                    extra_urefs_size,
+                    gas_allowance,
+                ): (u32, u32, u32, u32, u32, u32, u64) = Args::parse(args)?;
+
+                let key_contract: Key = self.key_from_mem(key_ptr, key_size)?;
+                let args_bytes: Vec<u8> = self.bytes_from_mem(args_ptr, args_size as usize)?;
+                let urefs_bytes =
+                    self.bytes_from_mem(extra_urefs_ptr, extra_urefs_size as usize)?;
+
+                let result = self.call_contract_with_gas(
+                    key_contract,
+                    args_bytes,
+                    urefs_bytes,
+                    gas_allowance,
+                )?;
+                Ok(Some(RuntimeValue::I32(result)))
+            }
+
             FunctionIndex::GetCallResultFuncIndex => {
                 // args(0) = pointer to destination in Wasm memory
                 let dest_ptr = Args::parse(args)?;
@@ -1047,11 +1728,74 @@ where
                     .get(urefs_ptr, urefs_size as usize)
                     .map_err(Error::Interpreter)?;
                 let urefs = deserialize(&uref_bytes).map_err(Error::BytesRepr)?;
-                let contract_hash = self.store_function(fn_bytes, urefs)?;
+                let contract_hash = self.store_function(fn_bytes, urefs, Vec::new())?;
+                self.function_address(contract_hash, hash_ptr)?;
+                Ok(None)
+            }
+
+            FunctionIndex::StoreFnWithAccessGroupsIndex => {
+                // args(0) = pointer to function name in Wasm memory
+                // args(1) = size of the name
+                // args(2) = pointer to additional unforgable names
+                //           to be saved with the function body
+                // args(3) = size of the additional unforgable names
+                // args(4) = pointer to the access group URefs
+                // args(5) = size of the access group URefs
+                // args(6) = pointer to a Wasm memory where we will save
+                //           hash of the new function
+                let (name_ptr, name_size, urefs_ptr, urefs_size, groups_ptr, groups_size, hash_ptr) =
+                    Args::parse(args)?;
+                let fn_bytes = self.get_function_by_name(name_ptr, name_size)?;
+                let uref_bytes = self
+                    .memory
+                    .get(urefs_ptr, urefs_size as usize)
+                    .map_err(Error::Interpreter)?;
+                let urefs = deserialize(&uref_bytes).map_err(Error::BytesRepr)?;
+                let group_bytes = self
+                    .memory
+                    .get(groups_ptr, groups_size as usize)
+                    .map_err(Error::Interpreter)?;
+                let access_groups = deserialize(&group_bytes).map_err(Error::BytesRepr)?;
+                let contract_hash = self.store_function(fn_bytes, urefs, access_groups)?;
                 self.function_address(contract_hash, hash_ptr)?;
                 Ok(None)
             }
 
+            FunctionIndex::StoreFnAtHashIndex => {
+                // args(0) = pointer to function name in Wasm memory
+                // args(1) = size of the name
+                // args(2) = pointer to additional unforgable names
+                //           to be saved with the function body
+                // args(3) = size of the additional unforgable names
+                // args(4) = pointer to the name the contract address is derived from
+                // args(5) = size of the contract name
+                // args(6) = pointer to a Wasm memory where we will save
+                //           hash of the new function
+                let (
+                    name_ptr,
+                    name_size,
+                    urefs_ptr,
+                    urefs_size,
+                    contract_name_ptr,
+                    contract_name_size,
+                    hash_ptr,
+                ) = Args::parse(args)?;
+                let fn_bytes = self.get_function_by_name(name_ptr, name_size)?;
+                let uref_bytes = self
+                    .memory
+                    .get(urefs_ptr, urefs_size as usize)
+                    .map_err(Error::Interpreter)?;
+                let urefs = deserialize(&uref_bytes).map_err(Error::BytesRepr)?;
+                let contract_name = self.string_from_mem(contract_name_ptr, contract_name_size)?;
+                match self.store_function_at_hash(fn_bytes, urefs, &contract_name)? {
+                    Some(contract_hash) => {
+                        self.function_address(contract_hash, hash_ptr)?;
+                        Ok(Some(RuntimeValue::I32(0)))
+                    }
+                    None => Ok(Some(RuntimeValue::I32(1))),
+                }
+            }
+
             FunctionIndex::ProtocolVersionFuncIndex => {
                 Ok(Some(self.context.protocol_version().into()))
             }
@@ -1099,6 +1843,32 @@ where
                 Ok(Some(RuntimeValue::I32(value)))
             }
 
+            FunctionIndex::AddRecoveryKeyIndex => {
+                // args(0) = pointer to array of bytes of a public key
+                // args(1) = weight of the key
+                let (public_key_ptr, weight_value): (u32, u8) = Args::parse(args)?;
+                let value = self.add_recovery_key(public_key_ptr, weight_value)?;
+                Ok(Some(RuntimeValue::I32(value)))
+            }
+
+            FunctionIndex::RemoveRecoveryKeyIndex => {
+                // args(0) = pointer to array of bytes of a public key
+                let public_key_ptr: u32 = Args::parse(args)?;
+                let value = self.remove_recovery_key(public_key_ptr)?;
+                Ok(Some(RuntimeValue::I32(value)))
+            }
+
+            FunctionIndex::RecoverAccountIndex => {
+                // args(0) = pointer to array of bytes of the recovered account's public key
+                // args(1) = size of the public key
+                // args(2) = pointer to array of bytes of the new associated keys
+                // args(3) = size of the new associated keys
+                let (account_ptr, account_size, keys_ptr, keys_size): (u32, u32, u32, u32) =
+                    Args::parse(args)?;
+                let value = self.recover_account(account_ptr, account_size, keys_ptr, keys_size)?;
+                Ok(Some(RuntimeValue::I32(value)))
+            }
+
             FunctionIndex::CreatePurseIndex => {
                 // args(0) = pointer to array for return value
                 // args(1) = length of array for return value
@@ -1182,16 +1952,142 @@ where
                 )?;
                 Ok(Some(RuntimeValue::I32(ret.into())))
             }
-        }
+
+            FunctionIndex::QueryStateIndex => {
+                // args(0) = pointer to base key in Wasm memory
+                // args(1) = size of base key in Wasm memory
+                // args(2) = pointer to serialized path (list of names) in Wasm memory
+                // args(3) = size of serialized path in Wasm memory
+                let (key_ptr, key_size, path_ptr, path_size) = Args::parse(args)?;
+                let size = self.query_state(key_ptr, key_size, path_ptr, path_size)?;
+                Ok(Some(RuntimeValue::I32(size as i32)))
+            }
+
+            FunctionIndex::DictionaryGetIndex => {
+                // args(0) = pointer to dictionary URef in Wasm memory
+                // args(1) = size of dictionary URef in Wasm memory
+                // args(2) = pointer to item key in Wasm memory
+                // args(3) = size of item key in Wasm memory
+                let (uref_ptr, uref_size, item_key_ptr, item_key_size) = Args::parse(args)?;
+                let size = self.dictionary_get(uref_ptr, uref_size, item_key_ptr, item_key_size)?;
+                Ok(Some(RuntimeValue::I32(size as i32)))
+            }
+
+            FunctionIndex::DictionaryPutIndex => {
+                // args(0) = pointer to dictionary URef in Wasm memory
+                // args(1) = size of dictionary URef in Wasm memory
+                // args(2) = pointer to item key in Wasm memory
+                // args(3) = size of item key in Wasm memory
+                // args(4) = pointer to value in Wasm memory
+                // args(5) = size of value in Wasm memory
+                let (uref_ptr, uref_size, item_key_ptr, item_key_size, value_ptr, value_size) =
+                    Args::parse(args)?;
+                self.dictionary_put(
+                    uref_ptr,
+                    uref_size,
+                    item_key_ptr,
+                    item_key_size,
+                    value_ptr,
+                    value_size,
+                )?;
+                Ok(None)
+            }
+
+            FunctionIndex::GetBalanceIndex => {
+                // args(0) = pointer to purse in Wasm memory
+                // args(1) = size of purse in Wasm memory
+                let (purse_ptr, purse_size) = Args::parse(args)?;
+                let size = self.get_balance(purse_ptr, purse_size)?;
+                Ok(Some(RuntimeValue::I32(size as i32)))
+            }
+
+            FunctionIndex::GetTotalSupplyIndex => {
+                let size = self.get_total_supply()?;
+                Ok(Some(RuntimeValue::I32(size as i32)))
+            }
+
+            FunctionIndex::CallPrecompileIndex => {
+                // args(0) = id of the precompile
+                // args(1) = pointer to input in Wasm memory
+                // args(2) = size of input
+                let (id, input_ptr, input_size) = Args::parse(args)?;
+                let size = self.call_precompile(id, input_ptr, input_size)?;
+                Ok(Some(RuntimeValue::I32(size as i32)))
+            }
+
+            FunctionIndex::GetChainParametersIndex => {
+                // args(0) = pointer to Wasm memory where to write.
+                let dest_ptr = Args::parse(args)?;
+                self.get_chain_parameters(dest_ptr)?;
+                Ok(None)
+            }
+
+            FunctionIndex::EmitEventIndex => {
+                // args(0) = pointer to the event data in Wasm memory
+                // args(1) = size of the event data
+                let (data_ptr, data_size) = Args::parse(args)?;
+                self.emit_event(data_ptr, data_size)?;
+                Ok(None)
+            }
+
+            FunctionIndex::ReadEventLogIndex => {
+                // args(0) = pointer to the key of the account or contract in Wasm memory
+                // args(1) = size of the key
+                let (key_ptr, key_size) = Args::parse(args)?;
+                let size = self.read_event_log(key_ptr, key_size)?;
+                Ok(Some(RuntimeValue::I32(size as i32)))
+            }
+
+            FunctionIndex::NamedKeysPageIndex => {
+                // args(0) = pointer to the serialized name to list the keys after
+                // args(1) = size of the serialized name
+                // args(2) = maximum number of keys to list
+                let (after_ptr, after_size, limit) = Args::parse(args)?;
+                let size = self.serialize_named_keys_page(after_ptr, after_size, limit)?;
+                Ok(Some(RuntimeValue::I32(size as i32)))
+            }
+
+            FunctionIndex::PayRentIndex => {
+                // args(0) = pointer to the serialized key to pay the rent of
+                // args(1) = size of the serialized key
+                // args(2) = pointer to the serialized source purse
+                // args(3) = size of the serialized source purse
+                // args(4) = pointer to the serialized amount
+                // args(5) = size of the serialized amount
+                let (key_ptr, key_size, source_ptr, source_size, amount_ptr, amount_size) =
+                    Args::parse(args)?;
+                let ret = self.pay_rent(
+                    key_ptr,
+                    key_size,
+                    source_ptr,
+                    source_size,
+                    amount_ptr,
+                    amount_size,
+                )?;
+                Ok(Some(RuntimeValue::I32(ret.into())))
+            }
+        }?;
+        self.charge_written_bytes()?;
+        Ok(result)
+    }
+}
+
+/// Index of the function `instance` was running when it trapped, if it was instrumented to
+/// track it.
+fn trap_frame(instance: &ModuleRef) -> Option<u32> {
+    let frame = instance.export_by_name(TRAP_FRAME_EXPORT)?;
+    match frame.as_global()?.get() {
+        RuntimeValue::I32(index) if index >= 0 => Some(index as u32),
+        _ => None,
     }
 }
 
 fn instance_and_memory(
     parity_module: Module,
-    protocol_version: u64,
+    host_functions: HostFunctions,
 ) -> Result<(ModuleRef, MemoryRef), Error> {
     let module = wasmi::Module::from_parity_wasm_module(parity_module)?;
-    let resolver = create_module_resolver(protocol_version)?;
+    let resolver = create_module_resolver(host_functions)?;
     let mut imports = ImportsBuilder::new();
     imports.push_resolver("env", &resolver);
     let instance = ModuleInstance::new(&module, &imports)?.assert_no_start();
@@ -1200,6 +2096,19 @@ fn instance_and_memory(
     Ok((instance, memory))
 }
 
+/// Checks that a preprocessed module can be instantiated against `host_functions`, without
+/// running any of its code.
+pub fn validate_module(parity_module: Module, host_functions: HostFunctions) -> Result<(), Error> {
+    let module = wasmi::Module::from_parity_wasm_module(parity_module)?;
+    let resolver = create_module_resolver(host_functions)?;
+    let mut imports = ImportsBuilder::new();
+    imports.push_resolver("env", &resolver);
+    // The start function is deliberately not run here.
+    let _ = ModuleInstance::new(&module, &imports)?;
+    resolver.memory_ref()?;
+    Ok(())
+}
+
 fn sub_call<R: StateReader<Key, Value>>(
     parity_module: Module,
     args: Vec<Vec<u8>>,
@@ -1210,11 +2119,16 @@ fn sub_call<R: StateReader<Key, Value>>(
     //(necessary if the contract takes a uref argument).
     extra_urefs: Vec<Key>,
     protocol_version: u64,
+    gas_limit: u64,
 ) -> Result<Vec<u8>, Error>
 where
     R::Error: Into<Error>,
 {
-    let (instance, memory) = instance_and_memory(parity_module.clone(), protocol_version)?;
+    let host_functions = current_runtime
+        .protocols
+        .get(protocol_version)?
+        .host_functions;
+    let (instance, memory) = instance_and_memory(parity_module.clone(), host_functions)?;
 
     let known_urefs = extract_access_rights_from_keys(refs.values().cloned().chain(extra_urefs));
 
@@ -1223,6 +2137,9 @@ where
         module: parity_module,
         result: Vec::new(),
         host_buf: Vec::new(),
+        last_host_function: None,
+        deadline: current_runtime.deadline,
+        protocols: current_runtime.protocols,
         context: RuntimeContext::new(
             current_runtime.context.state(),
             refs,
@@ -1231,7 +2148,7 @@ where
             &current_runtime.context.account(),
             key,
             current_runtime.context.get_blocktime(),
-            current_runtime.context.gas_limit(),
+            gas_limit,
             current_runtime.context.gas_counter(),
             current_runtime.context.fn_store_id(),
             current_runtime.context.rng(),
@@ -1241,6 +2158,11 @@ where
     };
 
     let result = instance.invoke_export("call", &[], &mut runtime);
+    // The callee counts on from the gas spent by the caller, so its counter covers both. It's
+    // carried back whatever the outcome, otherwise the gas spent in called contracts is free.
+    current_runtime
+        .context
+        .set_gas_counter(runtime.context.gas_counter());
 
     match result {
         Ok(_) => Ok(runtime.result),
@@ -1263,14 +2185,29 @@ where
                         // InterpreterError.
                         return Err(Error::Revert(*status));
                     }
+                    // Likewise, so that a caller which bounded the gas of the callee can tell.
+                    Error::GasLimit => return Err(Error::GasLimit),
                     _ => {}
                 }
             }
-            Err(Error::Interpreter(e))
+            Err(runtime.trap_error(&instance, e))
         }
     }
 }
 
+/// Gas for `size` bytes allocated by the host, at `host_alloc` per byte.
+fn host_alloc_cost(size: usize, host_alloc: u32) -> u64 {
+    (size as u64).saturating_mul(u64::from(host_alloc))
+}
+
+/// Gas limit of a subcall which can spend at most `gas_allowance` on top of `gas_counter`,
+/// within the `gas_limit` of the caller.
+fn subcall_gas_limit(gas_counter: u64, gas_limit: u64, gas_allowance: Option<u64>) -> u64 {
+    gas_allowance
+        .and_then(|allowance| gas_counter.checked_add(allowance))
+        .map_or(gas_limit, |limit| limit.min(gas_limit))
+}
+
 /// Groups a collection of urefs by their addresses and accumulates access rights per key
 pub fn extract_access_rights_from_urefs<I: IntoIterator<Item = URef>>(
     input: I,
@@ -1371,7 +2308,7 @@ pub trait Executor<A> {
         account: Key,
         blocktime: BlockTime,
         nonce: u64,
-        gas_limit: u64,
+        gas_limit: Gas,
         protocol_version: u64,
         correlation_id: CorrelationId,
         tc: Rc<RefCell<TrackingCopy<R>>>,
@@ -1380,7 +2317,28 @@ pub trait Executor<A> {
         R::Error: Into<Error>;
 }
 
-pub struct WasmiExecutor;
+#[derive(Debug, Clone, Default)]
+pub struct WasmiExecutor {
+    deploy_timeout: Option<Duration>,
+    protocols: Arc<ProtocolRegistry>,
+}
+
+impl WasmiExecutor {
+    /// Creates an executor failing deploys which run for longer than `deploy_timeout` with
+    /// `Error::Timeout`, whatever gas they have left. A timed out deploy is reported as a
+    /// precondition failure and its account isn't charged, as the time it took depends on the
+    /// node. The timeout guards against interpreter bugs and mispriced opcodes, so it should be
+    /// well above the time any deploy takes within its gas.
+    ///
+    /// Deploys, and the contracts they call, are run with the host functions and costs of their
+    /// protocol version in `protocols`.
+    pub fn new(deploy_timeout: Option<Duration>, protocols: Arc<ProtocolRegistry>) -> Self {
+        WasmiExecutor {
+            deploy_timeout,
+            protocols,
+        }
+    }
+}
 
 impl Executor<Module> for WasmiExecutor {
     fn exec<R: StateReader<Key, Value>>(
@@ -1390,7 +2348,7 @@ impl Executor<Module> for WasmiExecutor {
         acct_key: Key,
         blocktime: BlockTime,
         nonce: u64,
-        gas_limit: u64,
+        gas_limit: Gas,
         protocol_version: u64,
         correlation_id: CorrelationId,
         tc: Rc<RefCell<TrackingCopy<R>>>,
@@ -1398,8 +2356,16 @@ impl Executor<Module> for WasmiExecutor {
     where
         R::Error: Into<Error>,
     {
+        let semantics = on_fail_charge!(self.protocols.get(protocol_version));
+        let host_functions = semantics.host_functions;
+        // Refunding more than is charged for writing a byte would let deploys mint gas by
+        // rewriting values.
+        let storage_refund_per_byte = semantics
+            .wasm_costs
+            .storage_refund_per_byte
+            .min(semantics.wasm_costs.host_write_per_byte);
         let (instance, memory) =
-            on_fail_charge!(instance_and_memory(parity_module.clone(), protocol_version));
+            on_fail_charge!(instance_and_memory(parity_module.clone(), host_functions));
         #[allow(unreachable_code)]
         let validated_key = on_fail_charge!(Validated::new(acct_key, Validated::valid));
         let value = on_fail_charge! {
@@ -1414,7 +2380,8 @@ impl Executor<Module> for WasmiExecutor {
             Value::Account(a) => a,
             other => {
                 return ExecutionResult::precondition_failure(
-                    ::engine_state::error::Error::ExecError(Error::TypeMismatch(
+                    ::engine_state::error::Error::ExecError(Error::type_mismatch(
+                        acct_key,
                         TypeMismatch::new("Account".to_string(), other.type_string()),
                     )),
                 )
@@ -1440,11 +2407,17 @@ impl Executor<Module> for WasmiExecutor {
         // Increment nonce in the account that would be later used through the execution
         // lifecycle.
         account.increment_nonce();
+        // Deploys keep the account active, postponing its recovery.
+        account
+            .get_account_activity_mut()
+            .update_deployment_last_used(blocktime);
         // Store updated account with new nonce
-        tc.borrow_mut().write(
+        on_fail_charge!(tc.borrow_mut().write(
             validated_key,
             Validated::new(account.clone().into(), Validated::valid).unwrap(),
-        );
+        ));
+        // Every deploy updates its account, which isn't charged as a write of the contract.
+        tc.borrow_mut().take_written_bytes();
 
         let mut uref_lookup_local = account.urefs_lookup().clone();
         let known_urefs: HashMap<URefAddr, HashSet<AccessRights>> =
@@ -1463,7 +2436,11 @@ impl Executor<Module> for WasmiExecutor {
         } else {
             // TODO: figure out how this works with the cost model
             // https://casperlabs.atlassian.net/browse/EE-239
-            on_fail_charge!(deserialize(args), args.len() as u64, effects_snapshot)
+            on_fail_charge!(
+                deserialize(args),
+                Gas::new(args.len() as u64),
+                effects_snapshot
+            )
         };
 
         let context = RuntimeContext::new(
@@ -1474,7 +2451,7 @@ impl Executor<Module> for WasmiExecutor {
             &account,
             acct_key,
             blocktime,
-            gas_limit,
+            gas_limit.value(),
             gas_counter,
             fn_store_id,
             Rc::new(RefCell::new(rng)),
@@ -1482,20 +2459,75 @@ impl Executor<Module> for WasmiExecutor {
             correlation_id,
         );
 
-        let mut runtime = Runtime::new(memory, parity_module, context);
+        let mut runtime = Runtime::new(memory, parity_module, context, &self.protocols);
+        if let Some(timeout) = self.deploy_timeout {
+            runtime = runtime.with_timeout(timeout);
+        }
+        let result = instance
+            .invoke_export("call", &[], &mut runtime)
+            .map_err(|error| runtime.trap_error(&instance, error));
+        // How long a deploy runs depends on the node, so a deploy which timed out can't be part
+        // of a block: nothing is charged and its effects are dropped.
+        if let Err(Error::Timeout(timeout)) = result {
+            return ExecutionResult::precondition_failure(Error::Timeout(timeout).into());
+        }
         on_fail_charge!(
-            instance.invoke_export("call", &[], &mut runtime),
-            runtime.context.gas_counter(),
+            result,
+            Gas::new(runtime.context.gas_counter()),
             effects_snapshot
         );
 
+        on_fail_charge!(
+            runtime
+                .context
+                .state()
+                .borrow_mut()
+                .start_rent(correlation_id, blocktime),
+            Gas::new(runtime.context.gas_counter()),
+            effects_snapshot
+        );
+        let net_storage_bytes = on_fail_charge!(
+            runtime
+                .context
+                .state()
+                .borrow_mut()
+                .net_storage_bytes(correlation_id),
+            Gas::new(runtime.context.gas_counter()),
+            effects_snapshot
+        );
+        // The rent records and the values sized for the refund are charged like writes.
+        on_fail_charge!(
+            runtime.charge_written_bytes(),
+            Gas::new(runtime.context.gas_counter()),
+            effects_snapshot
+        );
+
+        let cost = Gas::new(runtime.context.gas_counter());
+        let mut effect = runtime.context.effect();
+        effect.net_storage_bytes = net_storage_bytes;
         ExecutionResult::Success {
-            effect: runtime.context.effect(),
-            cost: runtime.context.gas_counter(),
+            cost: refunded_cost(cost, net_storage_bytes, storage_refund_per_byte),
+            effect,
         }
     }
 }
 
+/// Cost of a successful deploy once refunded `refund_per_byte` for each byte it freed in the
+/// global state. The refund is capped at half of the cost, so that cleaning up state never makes
+/// a deploy free.
+fn refunded_cost(cost: Gas, net_storage_bytes: i64, refund_per_byte: u32) -> Gas {
+    if net_storage_bytes >= 0 {
+        return cost;
+    }
+    let freed_bytes = net_storage_bytes
+        .checked_neg()
+        .map_or(u64::max_value(), |freed_bytes| freed_bytes as u64);
+    let refund = freed_bytes
+        .saturating_mul(u64::from(refund_per_byte))
+        .min(cost.value() / 2);
+    Gas::new(cost.value() - refund)
+}
+
 /// Turns `key` into a `([u8; 32], AccessRights)` tuple.
 /// Returns None if `key` is not `Key::URef` as it wouldn't have `AccessRights` associated with it.
 /// Helper function for creating `known_urefs` associating addresses and corresponding `AccessRights`.
@@ -1514,9 +2546,12 @@ mod tests {
     use std::collections::btree_map::BTreeMap;
     use std::collections::HashMap;
     use std::rc::Rc;
+    use std::time::Duration;
 
     use parity_wasm::builder::ModuleBuilder;
-    use parity_wasm::elements::{External, ImportEntry, MemoryType, Module};
+    use parity_wasm::elements::{
+        BlockType, External, ImportEntry, Instruction, Instructions, MemoryType, Module,
+    };
     use rand::RngCore;
     use rand_chacha::ChaChaRng;
 
@@ -1529,27 +2564,30 @@ mod tests {
     use engine_state::execution_effect::ExecutionEffect;
     use engine_state::execution_result::ExecutionResult;
     use execution::{create_rng, Executor, WasmiExecutor};
+    use shared::gas::Gas;
     use shared::newtypes::CorrelationId;
     use storage::global_state::StateReader;
     use tracking_copy::TrackingCopy;
+    use wasm_prep::wasm_costs::WasmCosts;
+    use wasm_prep::{Preprocessor, WasmiPreprocessor};
 
-    use super::Error;
+    use super::{host_alloc_cost, refunded_cost, subcall_gas_limit, Error};
 
     fn on_fail_charge_test_helper<T>(
         f: impl Fn() -> Result<T, Error>,
         success_cost: u64,
         error_cost: u64,
     ) -> ExecutionResult {
-        let _result = on_fail_charge!(f(), error_cost);
+        let _result = on_fail_charge!(f(), Gas::new(error_cost));
         ExecutionResult::Success {
             effect: Default::default(),
-            cost: success_cost,
+            cost: Gas::new(success_cost),
         }
     }
     #[test]
     fn on_fail_charge_ok_test() {
         match on_fail_charge_test_helper(|| Ok(()), 123, 456) {
-            ExecutionResult::Success { cost, .. } => assert_eq!(cost, 123),
+            ExecutionResult::Success { cost, .. } => assert_eq!(cost, Gas::new(123)),
             ExecutionResult::Failure { .. } => panic!("Should be success"),
         }
     }
@@ -1557,7 +2595,7 @@ mod tests {
     fn on_fail_charge_err_laziness_test() {
         match on_fail_charge_test_helper(|| Err(Error::GasLimit) as Result<(), _>, 123, 456) {
             ExecutionResult::Success { .. } => panic!("Should fail"),
-            ExecutionResult::Failure { cost, .. } => assert_eq!(cost, 456),
+            ExecutionResult::Failure { cost, .. } => assert_eq!(cost, Gas::new(456)),
         }
     }
     #[test]
@@ -1568,7 +2606,7 @@ mod tests {
         use shared::transform::Transform;
         let f = || {
             let input: Result<(), Error> = Err(Error::GasLimit);
-            on_fail_charge!(input, 456, {
+            on_fail_charge!(input, Gas::new(456), {
                 let mut effect = ExecutionEffect::default();
 
                 effect.ops.insert(Key::Hash([42u8; 32]), Op::Read);
@@ -1580,13 +2618,13 @@ mod tests {
             });
             ExecutionResult::Success {
                 effect: Default::default(),
-                cost: 0,
+                cost: Gas::default(),
             }
         };
         match f() {
             ExecutionResult::Success { .. } => panic!("Should fail"),
             ExecutionResult::Failure { cost, effect, .. } => {
-                assert_eq!(cost, 456);
+                assert_eq!(cost, Gas::new(456));
                 // Check if the containers are non-empty
                 assert_eq!(effect.ops.len(), 1);
                 assert_eq!(effect.transforms.len(), 1);
@@ -1594,37 +2632,39 @@ mod tests {
         }
     }
 
+    /// Reads an account with nonce 1 under any account key.
+    struct DummyReader;
+    impl StateReader<Key, Value> for DummyReader {
+        type Error = ::storage::error::Error;
+
+        fn read(
+            &self,
+            _correlation_id: CorrelationId,
+            key: &Key,
+        ) -> Result<Option<Value>, Self::Error> {
+            let pub_key: [u8; 32] = match key {
+                Key::Account(pub_key) => *pub_key,
+                _ => panic!("Key must be of an Account type"),
+            };
+            let acc = Account::new(
+                pub_key,
+                1,
+                BTreeMap::new(),
+                PurseId::new(URef::new([0u8; 32], AccessRights::READ_ADD_WRITE)),
+                AssociatedKeys::new(PublicKey::new(pub_key), Weight::new(1)),
+                Default::default(),
+                AccountActivity::new(BlockTime(0), BlockTime(0)),
+                AssociatedKeys::empty(),
+            );
+            Ok(Some(Value::Account(acc)))
+        }
+    }
+
     #[test]
     fn invalid_nonce_no_cost_effect() {
         let init_nonce = 1u64;
         let invalid_nonce = init_nonce + 2;
-        struct DummyReader;
-        impl StateReader<Key, Value> for DummyReader {
-            type Error = ::storage::error::Error;
-
-            fn read(
-                &self,
-                _correlation_id: CorrelationId,
-                key: &Key,
-            ) -> Result<Option<Value>, Self::Error> {
-                let pub_key: [u8; 32] = match key {
-                    Key::Account(pub_key) => *pub_key,
-                    _ => panic!("Key must be of an Account type"),
-                };
-                let acc = Account::new(
-                    pub_key,
-                    1,
-                    BTreeMap::new(),
-                    PurseId::new(URef::new([0u8; 32], AccessRights::READ_ADD_WRITE)),
-                    AssociatedKeys::new(PublicKey::new(pub_key), Weight::new(1)),
-                    Default::default(),
-                    AccountActivity::new(BlockTime(0), BlockTime(0)),
-                );
-                Ok(Some(Value::Account(acc)))
-            }
-        }
-
-        let executor = WasmiExecutor;
+        let executor = WasmiExecutor::default();
         let account_address = [0u8; 32];
         let account_key: Key = Key::Account(account_address);
         let parity_module: Module = ModuleBuilder::new()
@@ -1644,7 +2684,7 @@ mod tests {
             account_key,
             BlockTime(0),
             invalid_nonce,
-            100u64,
+            Gas::new(100),
             1u64,
             CorrelationId::new(),
             tc,
@@ -1658,7 +2698,7 @@ mod tests {
                 cost,
             } => {
                 assert_eq!(effect, ExecutionEffect::new(HashMap::new(), HashMap::new()));
-                assert_eq!(cost, 0);
+                assert_eq!(cost, Gas::default());
                 if let ::engine_state::error::Error::ExecError(Error::InvalidNonce {
                     deploy_nonce,
                     expected_nonce,
@@ -1673,6 +2713,99 @@ mod tests {
         }
     }
 
+    #[test]
+    fn should_stop_deploy_after_timeout() {
+        // An endless loop, metered so that it calls the gas function on every iteration.
+        let module = ModuleBuilder::new()
+            .with_import(ImportEntry::new(
+                "env".to_string(),
+                "memory".to_string(),
+                External::Memory(MemoryType::new(16, Some(::wasm_prep::MEM_PAGES))),
+            ))
+            .function()
+            .signature()
+            .build()
+            .body()
+            .with_instructions(Instructions::new(vec![
+                Instruction::Loop(BlockType::NoResult),
+                Instruction::Br(0),
+                Instruction::End,
+                Instruction::End,
+            ]))
+            .build()
+            .build()
+            .export()
+            .field("call")
+            .internal()
+            .func(0)
+            .build()
+            .build();
+        let module_bytes = ::parity_wasm::serialize(module).unwrap();
+        let wasm_costs = WasmCosts::from_version(1).unwrap();
+        let parity_module = WasmiPreprocessor::new(wasm_costs)
+            .preprocess(&module_bytes)
+            .unwrap();
+
+        let timeout = Duration::from_millis(10);
+        let executor = WasmiExecutor::new(Some(timeout), Default::default());
+        let tc = Rc::new(RefCell::new(TrackingCopy::new(DummyReader)));
+        let exec_result = executor.exec(
+            parity_module,
+            &[],
+            Key::Account([0u8; 32]),
+            BlockTime(0),
+            2,
+            Gas::new(u64::max_value()),
+            1,
+            CorrelationId::new(),
+            tc,
+        );
+
+        match exec_result {
+            ExecutionResult::Failure {
+                error: ::engine_state::error::Error::ExecError(Error::Timeout(limit)),
+                effect,
+                cost,
+            } => {
+                assert_eq!(limit, timeout);
+                assert_eq!(cost, Gas::default());
+                assert!(effect.transforms.is_empty());
+            }
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn subcall_gas_limit_should_stay_within_caller_limit() {
+        assert_eq!(subcall_gas_limit(10, 100, None), 100);
+        assert_eq!(subcall_gas_limit(10, 100, Some(20)), 30);
+        assert_eq!(subcall_gas_limit(10, 100, Some(200)), 100);
+        assert_eq!(subcall_gas_limit(10, 100, Some(u64::max_value())), 100);
+    }
+
+    #[test]
+    fn host_alloc_cost_should_not_overflow() {
+        assert_eq!(host_alloc_cost(1000, 0), 0);
+        assert_eq!(host_alloc_cost(1000, 2), 2000);
+        assert_eq!(
+            host_alloc_cost(usize::max_value(), u32::max_value()),
+            u64::max_value()
+        );
+    }
+
+    #[test]
+    fn refunded_cost_should_only_refund_freed_bytes() {
+        assert_eq!(refunded_cost(Gas::new(100), 10, 2), Gas::new(100));
+        assert_eq!(refunded_cost(Gas::new(100), -10, 0), Gas::new(100));
+        assert_eq!(refunded_cost(Gas::new(100), -10, 2), Gas::new(80));
+        // At most half of the cost is refunded.
+        assert_eq!(refunded_cost(Gas::new(100), -1000, 2), Gas::new(50));
+        assert_eq!(
+            refunded_cost(Gas::new(100), i64::min_value(), 2),
+            Gas::new(50)
+        );
+    }
+
     fn gen_random(rng: &mut ChaChaRng) -> [u8; 32] {
         let mut buff = [0u8; 32];
         rng.fill_bytes(&mut buff);
@@ -1,7 +1,8 @@
 use num_traits::{FromPrimitive, ToPrimitive};
 use std::convert::TryFrom;
+use wasm_prep::wasm_costs::WasmCosts;
 
-#[derive(Debug, PartialEq, FromPrimitive, ToPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, FromPrimitive, ToPrimitive)]
 #[repr(usize)]
 pub enum FunctionIndex {
     WriteFuncIndex = 0,
@@ -38,6 +39,86 @@ pub enum FunctionIndex {
     TransferToAccountIndex = 31,
     TransferFromPurseToAccountIndex = 32,
     TransferFromPurseToPurseIndex = 33,
+    QueryStateIndex = 34,
+    DictionaryGetIndex = 35,
+    DictionaryPutIndex = 36,
+    StoreFnWithAccessGroupsIndex = 37,
+    GetBalanceIndex = 38,
+    GetTotalSupplyIndex = 39,
+    AddRecoveryKeyIndex = 40,
+    RemoveRecoveryKeyIndex = 41,
+    RecoverAccountIndex = 42,
+    CallPrecompileIndex = 43,
+    CallContractWithGasIndex = 44,
+    GetChainParametersIndex = 45,
+    NamedKeysPageIndex = 46,
+    StoreFnAtHashIndex = 47,
+    EmitEventIndex = 48,
+    ReadEventLogIndex = 49,
+    PayRentIndex = 50,
+}
+
+impl FunctionIndex {
+    /// Gas charged by the runtime for a single call of this host function.
+    ///
+    /// `gas` is free as it is the function through which the wasm code itself is metered.
+    pub fn cost(self, wasm_costs: &WasmCosts) -> u64 {
+        let extra_cost = match self {
+            FunctionIndex::GasFuncIndex => return 0,
+            FunctionIndex::ReadFuncIndex
+            | FunctionIndex::ReadLocalFuncIndex
+            | FunctionIndex::QueryStateIndex
+            | FunctionIndex::DictionaryGetIndex
+            | FunctionIndex::ReadEventLogIndex => wasm_costs.host_read,
+            FunctionIndex::WriteFuncIndex
+            | FunctionIndex::WriteLocalFuncIndex
+            | FunctionIndex::AddFuncIndex
+            | FunctionIndex::NewFuncIndex
+            | FunctionIndex::AddURefFuncIndex
+            | FunctionIndex::RemoveURef
+            | FunctionIndex::StoreFnIndex
+            | FunctionIndex::StoreFnWithAccessGroupsIndex
+            | FunctionIndex::StoreFnAtHashIndex
+            | FunctionIndex::DictionaryPutIndex
+            | FunctionIndex::EmitEventIndex
+            | FunctionIndex::AddAssociatedKeyFuncIndex
+            | FunctionIndex::RemoveAssociatedKeyFuncIndex
+            | FunctionIndex::SetActionThresholdFuncIndex
+            | FunctionIndex::AddRecoveryKeyIndex
+            | FunctionIndex::RemoveRecoveryKeyIndex
+            | FunctionIndex::RecoverAccountIndex
+            | FunctionIndex::CreatePurseIndex => wasm_costs.host_write,
+            FunctionIndex::CallContractFuncIndex
+            | FunctionIndex::CallContractWithGasIndex
+            | FunctionIndex::TransferToAccountIndex
+            | FunctionIndex::TransferFromPurseToAccountIndex
+            | FunctionIndex::TransferFromPurseToPurseIndex
+            | FunctionIndex::GetBalanceIndex
+            | FunctionIndex::GetTotalSupplyIndex
+            | FunctionIndex::PayRentIndex => wasm_costs.host_call_contract,
+            FunctionIndex::GetReadFuncIndex
+            | FunctionIndex::SerFnFuncIndex
+            | FunctionIndex::GetFnFuncIndex
+            | FunctionIndex::LoadArgFuncIndex
+            | FunctionIndex::GetArgFuncIndex
+            | FunctionIndex::RetFuncIndex
+            | FunctionIndex::GetCallResultFuncIndex
+            | FunctionIndex::GetURefFuncIndex
+            | FunctionIndex::HasURefFuncIndex
+            | FunctionIndex::ProtocolVersionFuncIndex
+            | FunctionIndex::IsValidFnIndex
+            | FunctionIndex::RevertFuncIndex
+            | FunctionIndex::SerKnownURefs
+            | FunctionIndex::ListKnownURefsIndex
+            | FunctionIndex::NamedKeysPageIndex
+            | FunctionIndex::GetCallerIndex
+            | FunctionIndex::GetBlocktimeIndex
+            | FunctionIndex::GetChainParametersIndex => 0,
+            // The precompile itself is charged by the runtime, as its cost depends on the input.
+            FunctionIndex::CallPrecompileIndex => 0,
+        };
+        u64::from(wasm_costs.host_call) + u64::from(extra_cost)
+    }
 }
 
 impl Into<usize> for FunctionIndex {
@@ -59,6 +140,7 @@ impl TryFrom<usize> for FunctionIndex {
 mod tests {
     use super::FunctionIndex;
     use std::convert::TryFrom;
+    use wasm_prep::wasm_costs::WasmCosts;
 
     #[test]
     fn primitive_to_enum() {
@@ -76,4 +158,25 @@ mod tests {
     fn invalid_index() {
         FunctionIndex::try_from(123_456_789usize).unwrap();
     }
+    #[test]
+    fn host_function_costs() {
+        let wasm_costs = WasmCosts::from_version(1).unwrap();
+        assert_eq!(FunctionIndex::GasFuncIndex.cost(&wasm_costs), 0);
+        assert_eq!(
+            FunctionIndex::GetCallerIndex.cost(&wasm_costs),
+            u64::from(wasm_costs.host_call)
+        );
+        assert!(
+            FunctionIndex::ReadFuncIndex.cost(&wasm_costs)
+                < FunctionIndex::WriteFuncIndex.cost(&wasm_costs)
+        );
+        assert!(
+            FunctionIndex::WriteFuncIndex.cost(&wasm_costs)
+                < FunctionIndex::CallContractFuncIndex.cost(&wasm_costs)
+        );
+        assert_eq!(
+            FunctionIndex::CallContractFuncIndex.cost(&WasmCosts::free()),
+            0
+        );
+    }
 }
@@ -6,6 +6,7 @@ extern crate core;
 
 // third-party dependencies
 extern crate blake2;
+extern crate ed25519_dalek;
 extern crate failure;
 extern crate itertools;
 extern crate linked_hash_map;
@@ -39,6 +40,8 @@ pub mod engine_state;
 pub mod execution;
 pub mod function_index;
 pub mod meter;
+pub mod precompiles;
+pub mod protocol_registry;
 pub mod resolvers;
 pub mod runtime_context;
 pub mod tracking_copy;
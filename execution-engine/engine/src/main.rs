@@ -22,10 +22,11 @@ use std::str;
 use common::key::Key;
 use common::value::account::BlockTime;
 use execution_engine::engine_state::error::RootNotFound;
-use execution_engine::engine_state::execution_effect::ExecutionEffect;
+use execution_engine::engine_state::execution_effect::{ApplyEffectResult, ExecutionEffect};
 use execution_engine::engine_state::execution_result::ExecutionResult;
 use execution_engine::engine_state::EngineState;
 use execution_engine::execution::WasmiExecutor;
+use shared::gas::Gas;
 use shared::init::mocked_account;
 use shared::logging;
 use shared::logging::log_level::LogLevel;
@@ -35,7 +36,7 @@ use shared::newtypes::{Blake2bHash, CorrelationId};
 use storage::global_state::in_memory::InMemoryGlobalState;
 use storage::global_state::CommitResult;
 use storage::global_state::History;
-use wasm_prep::{wasm_costs::WasmCosts, WasmiPreprocessor};
+use wasm_prep::WasmiPreprocessor;
 
 // exe / proc
 const PROC_NAME: &str = "execution-engine";
@@ -85,24 +86,43 @@ where
     H: History,
     H::Error: Into<execution_engine::execution::Error> + Debug,
 {
-    match engine_state.apply_effect(correlation_id, *pre_state_hash, effects.transforms) {
-        Ok(CommitResult::RootNotFound) => {
+    match engine_state.apply_effect(
+        correlation_id,
+        *pre_state_hash,
+        Some(&effects.ops),
+        effects.transforms,
+        None,
+    ) {
+        Ok(ApplyEffectResult::Rejected(inconsistent_op)) => {
+            let properties: BTreeMap<String, String> = BTreeMap::new();
+            let error_message = format!("effects rejected: {}", inconsistent_op);
+            (LogLevel::Error, error_message, properties, None)
+        }
+        Ok(ApplyEffectResult::Applied(CommitResult::PoststateMismatch { expected, computed })) => {
+            let properties: BTreeMap<String, String> = BTreeMap::new();
+            let error_message = format!(
+                "post state hash mismatch: expected {:?}, computed {:?}",
+                expected, computed
+            );
+            (LogLevel::Error, error_message, properties, None)
+        }
+        Ok(ApplyEffectResult::Applied(CommitResult::RootNotFound)) => {
             let mut properties: BTreeMap<String, String> = BTreeMap::new();
             let error_message = format!("root {:?} not found", pre_state_hash);
             properties.insert(String::from("root-hash"), format!("{:?}", pre_state_hash));
             (LogLevel::Warning, error_message, properties, None)
         }
-        Ok(CommitResult::KeyNotFound(key)) => {
+        Ok(ApplyEffectResult::Applied(CommitResult::KeyNotFound(key))) => {
             let mut properties: BTreeMap<String, String> = BTreeMap::new();
             let error_message = format!("key {:?} not found", key);
             (LogLevel::Warning, error_message, properties, None)
         }
-        Ok(CommitResult::TypeMismatch(type_mismatch)) => {
+        Ok(ApplyEffectResult::Applied(CommitResult::TypeMismatch(type_mismatch))) => {
             let mut properties: BTreeMap<String, String> = BTreeMap::new();
             let error_message = format!("type mismatch: {:?} ", type_mismatch);
             (LogLevel::Warning, error_message, properties, None)
         }
-        Ok(CommitResult::Success(new_root_hash)) => {
+        Ok(ApplyEffectResult::Applied(CommitResult::Success(new_root_hash, _))) => {
             let mut properties: BTreeMap<String, String> = BTreeMap::new();
             properties.insert(
                 String::from("post-state-hash"),
@@ -207,14 +227,12 @@ fn main() {
     let mut state_hash: Blake2bHash = global_state.root_hash;
     let engine_state = EngineState::new(global_state);
 
-    let wasmi_executor = WasmiExecutor;
-    let wasm_costs = WasmCosts::from_version(protocol_version).unwrap_or_else(|| {
-        panic!(
-            "Wasm cost table wasn't defined for protocol version: {}",
-            protocol_version
-        )
-    });
-    let wasmi_preprocessor: WasmiPreprocessor = WasmiPreprocessor::new(wasm_costs);
+    let wasmi_executor = WasmiExecutor::default();
+    let semantics = engine_state
+        .protocols()
+        .get(protocol_version)
+        .unwrap_or_else(|_| panic!("Protocol version {} isn't supported", protocol_version));
+    let wasmi_preprocessor: WasmiPreprocessor = semantics.preprocessor();
 
     for (i, wasm_bytes) in wasm_files.iter().enumerate() {
         let correlation_id = CorrelationId::new();
@@ -226,7 +244,7 @@ fn main() {
             BlockTime(timestamp),
             nonce,
             state_hash,
-            gas_limit,
+            Gas::new(gas_limit),
             protocol_version,
             correlation_id,
             &wasmi_executor,
@@ -250,7 +268,7 @@ fn main() {
                 effect: effects,
                 cost,
             }) => {
-                properties.insert("gas-cost".to_string(), format!("{:?}", cost));
+                properties.insert("gas-cost".to_string(), format!("{:?}", cost.value()));
                 properties.insert(
                     "effects".to_string(),
                     format!("{:?}", effects.transforms.clone()),
@@ -271,7 +289,7 @@ fn main() {
                 cost,
             }) => {
                 let log_level = LogLevel::Error;
-                properties.insert("gas-cost".to_string(), format!("{:?}", cost));
+                properties.insert("gas-cost".to_string(), format!("{:?}", cost.value()));
 
                 let (new_log_level, new_error_message, mut new_properties, new_state_hash) =
                     apply_effects(correlation_id, &engine_state, &state_hash, effects);
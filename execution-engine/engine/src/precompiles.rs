@@ -0,0 +1,205 @@
+//! Native implementations of the operations contracts can run through `call_precompile`.
+//!
+//! Their costs are fixed rather than taken from the wasm cost table, as they don't depend on
+//! how the operation would have been compiled to wasm.
+use blake2::digest::{Input, VariableOutput};
+use blake2::VarBlake2b;
+use ed25519_dalek::{PublicKey, Signature, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+
+use common::bytesrepr::{self, FromBytes, ToBytes};
+use common::precompile::Precompile;
+use common::value::U512;
+
+const BLAKE2B_DIGEST_LENGTH: usize = 32;
+
+/// Cost of hashing, plus `BLAKE2B_WORD_COST` for every started 32-byte word of input.
+const BLAKE2B_BASE_COST: u64 = 60;
+const BLAKE2B_WORD_COST: u64 = 12;
+
+const U512_ADD_SUB_COST: u64 = 20;
+const U512_MUL_COST: u64 = 60;
+const U512_DIV_REM_COST: u64 = 120;
+
+/// Cost of verifying a signature, plus `ED25519_WORD_COST` for every started 32-byte word of
+/// the message, which is hashed as part of the verification.
+const ED25519_VERIFY_BASE_COST: u64 = 2_000;
+const ED25519_WORD_COST: u64 = 12;
+
+/// Gas charged for running `precompile` on `input_size` bytes, on top of the host call itself.
+pub fn cost(precompile: Precompile, input_size: usize) -> u64 {
+    match precompile {
+        Precompile::Blake2b256 => {
+            let words = (input_size as u64 + 31) / 32;
+            BLAKE2B_BASE_COST + BLAKE2B_WORD_COST * words
+        }
+        Precompile::U512CheckedAdd | Precompile::U512CheckedSub => U512_ADD_SUB_COST,
+        Precompile::U512CheckedMul => U512_MUL_COST,
+        Precompile::U512CheckedDiv | Precompile::U512CheckedRem => U512_DIV_REM_COST,
+        Precompile::Ed25519Verify => {
+            let message_size = input_size.saturating_sub(PUBLIC_KEY_LENGTH + SIGNATURE_LENGTH);
+            let words = (message_size as u64 + 31) / 32;
+            ED25519_VERIFY_BASE_COST + ED25519_WORD_COST * words
+        }
+    }
+}
+
+/// Runs `precompile` on `input`, returning its serialized output.
+pub fn run(precompile: Precompile, input: &[u8]) -> Result<Vec<u8>, bytesrepr::Error> {
+    let op: fn(U512, U512) -> Option<U512> = match precompile {
+        Precompile::Blake2b256 => return Ok(blake2b256(input).to_vec()),
+        Precompile::Ed25519Verify => return ed25519_verify(input).map(|valid| vec![valid as u8]),
+        Precompile::U512CheckedAdd => |lhs: U512, rhs| lhs.checked_add(rhs),
+        Precompile::U512CheckedSub => |lhs: U512, rhs| lhs.checked_sub(rhs),
+        Precompile::U512CheckedMul => |lhs: U512, rhs| lhs.checked_mul(rhs),
+        Precompile::U512CheckedDiv => |lhs: U512, rhs| lhs.checked_div(rhs),
+        Precompile::U512CheckedRem => |lhs: U512, rhs| lhs.checked_rem(rhs),
+    };
+    let (lhs, rest) = U512::from_bytes(input)?;
+    let (rhs, rest) = U512::from_bytes(rest)?;
+    if !rest.is_empty() {
+        return Err(bytesrepr::Error::LeftOverBytes);
+    }
+    op(lhs, rhs).to_bytes()
+}
+
+/// Checks the signature in `input`, laid out as described on `Precompile::Ed25519Verify`.
+///
+/// Key or signature bytes which aren't valid curve points only make the signature invalid, but
+/// an input too short to hold them is malformed.
+fn ed25519_verify(input: &[u8]) -> Result<bool, bytesrepr::Error> {
+    if input.len() < PUBLIC_KEY_LENGTH + SIGNATURE_LENGTH {
+        return Err(bytesrepr::Error::EarlyEndOfStream);
+    }
+    let (public_key, rest) = input.split_at(PUBLIC_KEY_LENGTH);
+    let (signature, message) = rest.split_at(SIGNATURE_LENGTH);
+    let valid = match (
+        PublicKey::from_bytes(public_key),
+        Signature::from_bytes(signature),
+    ) {
+        (Ok(public_key), Ok(signature)) => public_key.verify(message, &signature).is_ok(),
+        _ => false,
+    };
+    Ok(valid)
+}
+
+fn blake2b256(input: &[u8]) -> [u8; BLAKE2B_DIGEST_LENGTH] {
+    let mut digest = [0u8; BLAKE2B_DIGEST_LENGTH];
+    // Safe to unwrap as the digest length is constant and valid.
+    let mut hasher = VarBlake2b::new(BLAKE2B_DIGEST_LENGTH).unwrap();
+    hasher.input(input);
+    hasher.variable_result(|hash| digest.clone_from_slice(hash));
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use common::bytesrepr::{deserialize, Error, ToBytes};
+    use common::key::Key;
+    use common::precompile::Precompile;
+    use common::value::U512;
+
+    use super::{cost, run};
+
+    fn u512_input(lhs: U512, rhs: U512) -> Vec<u8> {
+        let mut input = lhs.to_bytes().unwrap();
+        input.extend(rhs.to_bytes().unwrap());
+        input
+    }
+
+    fn run_u512(precompile: Precompile, lhs: u64, rhs: u64) -> Option<U512> {
+        let output = run(precompile, &u512_input(lhs.into(), rhs.into())).unwrap();
+        deserialize(&output).unwrap()
+    }
+
+    #[test]
+    fn blake2b256_matches_local_key_hashing() {
+        // Local keys are hashed with the same function inside the contract.
+        let seed = [7u8; 32];
+        let key = b"key".to_vec();
+        let mut input = seed.to_vec();
+        input.extend(&key);
+        let output = run(Precompile::Blake2b256, &input).unwrap();
+        match Key::local(seed, &key) {
+            Key::Local(hash) => assert_eq!(output, hash.to_vec()),
+            other => panic!("expected a local key, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn u512_ops_are_checked() {
+        assert_eq!(run_u512(Precompile::U512CheckedAdd, 2, 3), Some(5.into()));
+        assert_eq!(run_u512(Precompile::U512CheckedSub, 2, 3), None);
+        assert_eq!(run_u512(Precompile::U512CheckedMul, 2, 3), Some(6.into()));
+        assert_eq!(run_u512(Precompile::U512CheckedDiv, 7, 2), Some(3.into()));
+        assert_eq!(run_u512(Precompile::U512CheckedRem, 7, 2), Some(1.into()));
+        assert_eq!(run_u512(Precompile::U512CheckedDiv, 7, 0), None);
+
+        let output = run(
+            Precompile::U512CheckedAdd,
+            &u512_input(U512::max_value(), U512::one()),
+        )
+        .unwrap();
+        assert_eq!(deserialize::<Option<U512>>(&output).unwrap(), None);
+    }
+
+    #[test]
+    fn u512_ops_reject_malformed_input() {
+        let mut input = u512_input(1.into(), 2.into());
+        input.push(0);
+        assert_eq!(
+            run(Precompile::U512CheckedAdd, &input),
+            Err(Error::LeftOverBytes)
+        );
+        assert!(run(Precompile::U512CheckedMul, &[]).is_err());
+    }
+
+    // The first test vector of RFC 8032, signing the empty message.
+    const RFC8032_PUBLIC_KEY: &str =
+        "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+    const RFC8032_SIGNATURE: &str = "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e0652249\
+                                     01555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e\
+                                     7a100b";
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn ed25519_input(message: &[u8]) -> Vec<u8> {
+        let mut input = from_hex(RFC8032_PUBLIC_KEY);
+        input.extend(from_hex(RFC8032_SIGNATURE));
+        input.extend(message);
+        input
+    }
+
+    #[test]
+    fn ed25519_verify_checks_signature() {
+        let valid = run(Precompile::Ed25519Verify, &ed25519_input(b"")).unwrap();
+        assert_eq!(valid, vec![1]);
+
+        let tampered = run(Precompile::Ed25519Verify, &ed25519_input(b"x")).unwrap();
+        assert_eq!(tampered, vec![0]);
+    }
+
+    #[test]
+    fn ed25519_verify_rejects_truncated_input() {
+        let mut input = ed25519_input(b"");
+        input.pop();
+        assert_eq!(
+            run(Precompile::Ed25519Verify, &input),
+            Err(Error::EarlyEndOfStream)
+        );
+    }
+
+    #[test]
+    fn blake2b256_cost_grows_with_input() {
+        assert_eq!(
+            cost(Precompile::Blake2b256, 1),
+            cost(Precompile::Blake2b256, 32)
+        );
+        assert!(cost(Precompile::Blake2b256, 33) > cost(Precompile::Blake2b256, 32));
+        assert!(cost(Precompile::U512CheckedAdd, 0) < cost(Precompile::U512CheckedDiv, 0));
+    }
+}
@@ -0,0 +1,297 @@
+//! What executing a deploy means under each protocol version.
+//!
+//! Blocks are executed under the protocol version they were proposed with, and stored contracts
+//! under the version they were stored with. An engine can replay blocks from both sides of an
+//! upgrade as long as it knows the semantics of every version involved.
+use std::collections::BTreeMap;
+
+use engine_state::rent::{RentConfig, DEFAULT_GRACE_PERIOD_MILLIS};
+use resolvers::error::ResolverError;
+use resolvers::{self, HostFunctions};
+use shared::chainspec::{Chainspec, LimitsConfig};
+use wasm_prep::passes::{self, ModulePasses};
+use wasm_prep::wasm_costs::WasmCosts;
+use wasm_prep::WasmiPreprocessor;
+
+/// Default upper bound on the number of named keys of a single account or contract.
+pub const DEFAULT_MAX_NAMED_KEYS: usize = 10_000;
+
+/// Default upper bound on the length of the byte arrays contracts can store.
+pub const DEFAULT_MAX_BYTE_ARRAY_LENGTH: usize = 64 * 1024;
+
+/// Default upper bound, in bytes, on a single serialized value written to global state.
+pub const DEFAULT_MAX_VALUE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Default gas limit of a block, i.e. no limit.
+pub const DEFAULT_BLOCK_GAS_LIMIT: u64 = u64::max_value();
+
+/// Bounds on what deploys can do. Deploys going over them fail, so every node of a network has
+/// to use the same ones, set by the chainspec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolLimits {
+    /// Max number of named keys an account or contract can reach by adding keys to itself.
+    pub max_named_keys: usize,
+    /// Contracts storing a `Value::ByteArray` longer than this fail.
+    pub max_byte_array_length: usize,
+    /// Writes of values taking more bytes once serialized fail with `Error::Validation`.
+    pub max_value_size: usize,
+    /// Number of the most recent events of each account or contract kept in global state, for
+    /// other contracts to read with the `read_event_log` host function; 0 keeps none.
+    pub event_log_capacity: usize,
+    /// Most gas the deploys of a block can spend together. The node enforces it when proposing
+    /// blocks; the engine reports it to contracts with `get_chain_parameters`.
+    pub block_gas_limit: u64,
+    /// If set, keys created by deploys have to be paid for to not become reclaimable, see
+    /// `engine_state::rent`.
+    pub rent: Option<RentConfig>,
+}
+
+impl Default for ProtocolLimits {
+    fn default() -> Self {
+        ProtocolLimits {
+            max_named_keys: DEFAULT_MAX_NAMED_KEYS,
+            max_byte_array_length: DEFAULT_MAX_BYTE_ARRAY_LENGTH,
+            max_value_size: DEFAULT_MAX_VALUE_SIZE,
+            event_log_capacity: 0,
+            block_gas_limit: DEFAULT_BLOCK_GAS_LIMIT,
+            rent: None,
+        }
+    }
+}
+
+/// Limits left out of the chainspec keep their default value.
+impl<'a> From<&'a LimitsConfig> for ProtocolLimits {
+    fn from(limits: &LimitsConfig) -> Self {
+        let defaults = ProtocolLimits::default();
+        ProtocolLimits {
+            max_named_keys: limits.max_named_keys.unwrap_or(defaults.max_named_keys),
+            max_byte_array_length: limits
+                .max_byte_array_length
+                .unwrap_or(defaults.max_byte_array_length),
+            max_value_size: limits.max_value_size.unwrap_or(defaults.max_value_size),
+            event_log_capacity: limits
+                .event_log_capacity
+                .unwrap_or(defaults.event_log_capacity),
+            block_gas_limit: limits.block_gas_limit.unwrap_or(defaults.block_gas_limit),
+            rent: limits
+                .rent_price_per_byte_day
+                .map(|price_per_byte_day| RentConfig {
+                    price_per_byte_day,
+                    grace_period_millis: limits
+                        .rent_grace_period_millis
+                        .unwrap_or(DEFAULT_GRACE_PERIOD_MILLIS),
+                }),
+        }
+    }
+}
+
+/// The cost table, host functions and limits of a protocol version. The interpreter is the same
+/// for every version, so a single executor runs all of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolSemantics {
+    pub wasm_costs: WasmCosts,
+    pub host_functions: HostFunctions,
+    pub limits: ProtocolLimits,
+    /// Passes checking or rewriting modules on top of the built-in ones, e.g. validations
+    /// added by a network.
+    pub extra_passes: ModulePasses,
+}
+
+impl ProtocolSemantics {
+    /// Instruments modules with the cost table, rejecting imports outside the host functions.
+    /// The extra passes run on the validated module, before it's instrumented.
+    pub fn preprocessor(&self) -> WasmiPreprocessor {
+        let mut passes =
+            passes::validation_passes(Some(resolvers::import_allowlist(self.host_functions)));
+        passes.extend(self.extra_passes.iter().cloned());
+        passes.extend(passes::instrumentation_passes(&self.wasm_costs));
+        WasmiPreprocessor::with_passes(passes)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolRegistry {
+    versions: BTreeMap<u64, ProtocolSemantics>,
+}
+
+impl ProtocolRegistry {
+    pub fn empty() -> Self {
+        ProtocolRegistry {
+            versions: BTreeMap::new(),
+        }
+    }
+
+    /// Registers the semantics of `protocol_version`, returning the ones it replaces.
+    pub fn register(
+        &mut self,
+        protocol_version: u64,
+        semantics: ProtocolSemantics,
+    ) -> Option<ProtocolSemantics> {
+        self.versions.insert(protocol_version, semantics)
+    }
+
+    pub fn get(&self, protocol_version: u64) -> Result<&ProtocolSemantics, ResolverError> {
+        self.versions
+            .get(&protocol_version)
+            .ok_or(ResolverError::UnknownProtocolVersion(protocol_version))
+    }
+
+    /// The registered versions, in increasing order.
+    pub fn versions(&self) -> impl Iterator<Item = u64> + '_ {
+        self.versions.keys().cloned()
+    }
+
+    /// Registry of every protocol version `chainspec` defines a cost table for, each exposing
+    /// the host functions released as of that version.
+    pub fn from_chainspec(chainspec: &Chainspec) -> Self {
+        let mut registry = ProtocolRegistry::empty();
+        for protocol_version in chainspec.protocol_versions() {
+            let wasm_costs = chainspec
+                .wasm_costs(protocol_version)
+                .cloned()
+                .expect("the chainspec has a cost table for each of its versions");
+            registry.register(
+                protocol_version,
+                ProtocolSemantics {
+                    wasm_costs,
+                    host_functions: HostFunctions::for_protocol_version(protocol_version),
+                    limits: ProtocolLimits::from(&chainspec.limits(protocol_version)),
+                    extra_passes: Vec::new(),
+                },
+            );
+        }
+        registry
+    }
+}
+
+/// The versions built into the engine.
+impl Default for ProtocolRegistry {
+    fn default() -> Self {
+        let mut registry = ProtocolRegistry::empty();
+        for protocol_version in 1..=2 {
+            registry.register(
+                protocol_version,
+                ProtocolSemantics {
+                    wasm_costs: WasmCosts::from_version(protocol_version)
+                        .expect("built-in versions have a cost table"),
+                    host_functions: HostFunctions::for_protocol_version(protocol_version),
+                    limits: ProtocolLimits::default(),
+                    extra_passes: Vec::new(),
+                },
+            );
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parity_wasm::elements::Module;
+
+    use engine_state::rent::{RentConfig, DEFAULT_GRACE_PERIOD_MILLIS};
+    use resolvers::error::ResolverError;
+    use resolvers::HostFunctions;
+    use shared::chainspec::LimitsConfig;
+    use wasm_prep::passes::ModulePass;
+    use wasm_prep::wasm_costs::WasmCosts;
+    use wasm_prep::PreprocessingError;
+
+    use super::{ProtocolLimits, ProtocolRegistry, ProtocolSemantics};
+
+    #[test]
+    fn should_keep_semantics_of_each_version() {
+        let mut registry = ProtocolRegistry::default();
+        let v3 = ProtocolSemantics {
+            wasm_costs: WasmCosts::free(),
+            host_functions: HostFunctions::V1,
+            limits: ProtocolLimits::default(),
+            extra_passes: Vec::new(),
+        };
+        assert_eq!(registry.register(3, v3.clone()), None);
+
+        assert_eq!(registry.versions().collect::<Vec<u64>>(), vec![1, 2, 3]);
+        assert_eq!(registry.get(3).unwrap(), &v3);
+        assert_eq!(
+            registry.get(1).unwrap().wasm_costs,
+            WasmCosts::from_version(1).unwrap()
+        );
+        assert_eq!(registry.get(1).unwrap().wasm_costs.host_alloc, 0);
+        assert_eq!(registry.get(2).unwrap().wasm_costs.host_alloc, 1);
+        assert_eq!(registry.get(2).unwrap().host_functions, HostFunctions::V2);
+        assert_matches!(
+            registry.get(4),
+            Err(ResolverError::UnknownProtocolVersion(4))
+        );
+    }
+
+    #[test]
+    fn should_default_limits_left_out_of_the_chainspec() {
+        let limits = ProtocolLimits::from(&LimitsConfig {
+            max_named_keys: Some(10),
+            ..LimitsConfig::default()
+        });
+        assert_eq!(limits.max_named_keys, 10);
+        assert_eq!(
+            limits.max_byte_array_length,
+            ProtocolLimits::default().max_byte_array_length
+        );
+        assert_eq!(limits.rent, None);
+        assert_eq!(
+            ProtocolLimits::from(&LimitsConfig::default()),
+            ProtocolLimits::default()
+        );
+
+        let with_rent = ProtocolLimits::from(&LimitsConfig {
+            rent_price_per_byte_day: Some(10),
+            ..LimitsConfig::default()
+        });
+        assert_eq!(
+            with_rent.rent,
+            Some(RentConfig {
+                price_per_byte_day: 10,
+                grace_period_millis: DEFAULT_GRACE_PERIOD_MILLIS,
+            })
+        );
+    }
+
+    struct NoopPass;
+
+    impl ModulePass for NoopPass {
+        fn name(&self) -> &str {
+            "noop"
+        }
+
+        fn run(&self, module: Module) -> Result<Module, PreprocessingError> {
+            Ok(module)
+        }
+    }
+
+    #[test]
+    fn should_run_extra_passes_before_instrumentation() {
+        let noop: Arc<dyn ModulePass> = Arc::new(NoopPass);
+        let semantics = ProtocolSemantics {
+            extra_passes: vec![noop],
+            ..ProtocolRegistry::default().get(1).unwrap().clone()
+        };
+        let preprocessor = semantics.preprocessor();
+        let names: Vec<&str> = preprocessor
+            .passes()
+            .iter()
+            .map(|pass| pass.name())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "validate_imports",
+                "validate_structure",
+                "noop",
+                "externalize_memory",
+                "inject_gas_counters",
+                "track_trap_frames",
+                "inject_stack_limiter",
+            ]
+        );
+    }
+}
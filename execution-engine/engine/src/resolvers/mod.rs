@@ -1,30 +1,137 @@
 pub mod error;
 pub mod memory_resolver;
 mod resolver_v1;
+mod resolver_v2;
 
-use wasmi::ModuleImportResolver;
+use parity_wasm::elements::{FunctionType, ValueType as ParityValueType};
+use wasm_prep::ImportAllowlist;
+use wasmi::{ModuleImportResolver, ValueType};
 
 use self::error::ResolverError;
+use function_index::FunctionIndex;
 use resolvers::memory_resolver::MemoryResolver;
 
-/// Creates a module resolver for given protocol version.
-///
-/// * `protocol_version` Version of the protocol. Can't be lower than 1.
+/// A set of host functions contracts can import. A released set never changes: host functions
+/// behaving differently make up a new set, used by the protocol versions after the upgrade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HostFunctions {
+    V1,
+    /// The version 1 functions, plus chain parameters, paged named keys, contracts stored at a
+    /// hash, events and rent.
+    V2,
+}
+
+impl HostFunctions {
+    /// The newest set released as of `protocol_version`.
+    pub fn for_protocol_version(protocol_version: u64) -> HostFunctions {
+        if protocol_version >= 2 {
+            HostFunctions::V2
+        } else {
+            HostFunctions::V1
+        }
+    }
+
+    fn definitions(self) -> impl Iterator<Item = &'static resolver_v1::HostFunction> {
+        let added: &'static [resolver_v1::HostFunction] = match self {
+            HostFunctions::V1 => &[],
+            HostFunctions::V2 => resolver_v2::HOST_FUNCTIONS,
+        };
+        resolver_v1::HOST_FUNCTIONS.iter().chain(added)
+    }
+}
+
+/// Creates a module resolver for the given set of host functions.
 pub fn create_module_resolver(
-    protocol_version: u64,
+    host_functions: HostFunctions,
 ) -> Result<impl ModuleImportResolver + MemoryResolver, ResolverError> {
-    match protocol_version {
-        1 => Ok(resolver_v1::RuntimeModuleImportResolver::default()),
-        _ => Err(ResolverError::UnknownProtocolVersion(protocol_version)),
+    Ok(resolver_v1::RuntimeModuleImportResolver::new(
+        host_functions,
+    ))
+}
+
+/// Returns the host functions contracts may import, to be checked by the preprocessor before the
+/// module is instantiated.
+pub fn import_allowlist(host_functions: HostFunctions) -> ImportAllowlist {
+    host_functions
+        .definitions()
+        .map(|host_function| {
+            let params = host_function.params.iter().map(to_parity_type).collect();
+            let result = host_function.result.as_ref().map(to_parity_type);
+            (
+                host_function.name.to_string(),
+                FunctionType::new(params, result),
+            )
+        })
+        .collect()
+}
+
+/// Returns the name under which the host function `index` is imported.
+pub fn host_function_name(
+    host_functions: HostFunctions,
+    index: FunctionIndex,
+) -> Option<&'static str> {
+    host_functions
+        .definitions()
+        .find(|host_function| host_function.index == index)
+        .map(|host_function| host_function.name)
+}
+
+fn to_parity_type(value_type: &ValueType) -> ParityValueType {
+    match value_type {
+        ValueType::I32 => ParityValueType::I32,
+        ValueType::I64 => ParityValueType::I64,
+        ValueType::F32 => ParityValueType::F32,
+        ValueType::F64 => ParityValueType::F64,
+    }
+}
+
+#[test]
+fn host_functions_v1_always_resolve() {
+    assert!(create_module_resolver(HostFunctions::V1).is_ok());
+}
+
+#[test]
+fn import_allowlist_matches_resolver() {
+    let allowlist = import_allowlist(HostFunctions::V1);
+    let resolver = create_module_resolver(HostFunctions::V1).unwrap();
+    for (name, function_type) in &allowlist {
+        let func_ref = resolver
+            .resolve_func(name, &wasmi::Signature::new(&[][..], None))
+            .expect("allowed import should resolve");
+        let signature = func_ref.signature();
+        assert_eq!(signature.params().len(), function_type.params().len());
+        assert_eq!(
+            signature.return_type().is_some(),
+            function_type.return_type().is_some()
+        );
     }
+    assert_eq!(
+        allowlist.get("protocol_version"),
+        Some(&FunctionType::new(vec![], Some(ParityValueType::I64)))
+    );
 }
 
 #[test]
-fn resolve_invalid_module() {
-    assert!(create_module_resolver(0).is_err());
+fn host_function_names() {
+    assert_eq!(
+        host_function_name(HostFunctions::V1, FunctionIndex::ReadFuncIndex),
+        Some("read_value")
+    );
 }
 
 #[test]
-fn protocol_version_1_always_resolves() {
-    assert!(create_module_resolver(1).is_ok());
+fn host_functions_v2_extend_v1() {
+    let v1 = import_allowlist(HostFunctions::V1);
+    let v2 = import_allowlist(HostFunctions::V2);
+    assert!(v1.iter().all(|(name, _)| v2.contains_key(name)));
+    for name in &["get_chain_parameters", "emit_event", "pay_rent"] {
+        assert!(!v1.contains_key(*name));
+        assert!(v2.contains_key(*name));
+    }
+    let resolver = create_module_resolver(HostFunctions::V1).unwrap();
+    assert!(resolver
+        .resolve_func("emit_event", &wasmi::Signature::new(&[][..], None))
+        .is_err());
+    assert_eq!(HostFunctions::for_protocol_version(1), HostFunctions::V1);
+    assert_eq!(HostFunctions::for_protocol_version(2), HostFunctions::V2);
 }
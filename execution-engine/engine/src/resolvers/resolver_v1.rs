@@ -8,16 +8,316 @@ use wasmi::{FuncInstance, MemoryRef, ModuleImportResolver};
 
 use super::error::ResolverError;
 use super::memory_resolver::MemoryResolver;
+use super::HostFunctions;
 use function_index::FunctionIndex;
 
+/// A function exported by the host to contracts under the `env` module.
+pub struct HostFunction {
+    pub name: &'static str,
+    pub params: &'static [ValueType],
+    pub result: Option<ValueType>,
+    pub index: FunctionIndex,
+}
+
+impl HostFunction {
+    pub fn signature(&self) -> Signature {
+        Signature::new(self.params, self.result)
+    }
+}
+
+/// Host functions available in protocol version 1.
+pub const HOST_FUNCTIONS: &[HostFunction] = &[
+    HostFunction {
+        name: "read_value",
+        params: &[ValueType::I32; 2],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::ReadFuncIndex,
+    },
+    HostFunction {
+        name: "read_value_local",
+        params: &[ValueType::I32; 2],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::ReadLocalFuncIndex,
+    },
+    HostFunction {
+        name: "serialize_function",
+        params: &[ValueType::I32; 2],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::SerFnFuncIndex,
+    },
+    HostFunction {
+        name: "serialize_known_urefs",
+        params: &[ValueType::I32; 0],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::SerKnownURefs,
+    },
+    HostFunction {
+        name: "write",
+        params: &[ValueType::I32; 4],
+        result: None,
+        index: FunctionIndex::WriteFuncIndex,
+    },
+    HostFunction {
+        name: "write_local",
+        params: &[ValueType::I32; 4],
+        result: None,
+        index: FunctionIndex::WriteLocalFuncIndex,
+    },
+    HostFunction {
+        name: "get_read",
+        params: &[ValueType::I32; 1],
+        result: None,
+        index: FunctionIndex::GetReadFuncIndex,
+    },
+    HostFunction {
+        name: "get_function",
+        params: &[ValueType::I32; 1],
+        result: None,
+        index: FunctionIndex::GetFnFuncIndex,
+    },
+    HostFunction {
+        name: "add",
+        params: &[ValueType::I32; 4],
+        result: None,
+        index: FunctionIndex::AddFuncIndex,
+    },
+    HostFunction {
+        name: "new_uref",
+        params: &[ValueType::I32; 3],
+        result: None,
+        index: FunctionIndex::NewFuncIndex,
+    },
+    HostFunction {
+        name: "load_arg",
+        params: &[ValueType::I32; 1],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::LoadArgFuncIndex,
+    },
+    HostFunction {
+        name: "get_arg",
+        params: &[ValueType::I32; 1],
+        result: None,
+        index: FunctionIndex::GetArgFuncIndex,
+    },
+    HostFunction {
+        name: "ret",
+        params: &[ValueType::I32; 4],
+        result: None,
+        index: FunctionIndex::RetFuncIndex,
+    },
+    HostFunction {
+        name: "call_contract",
+        params: &[ValueType::I32; 6],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::CallContractFuncIndex,
+    },
+    HostFunction {
+        name: "call_contract_with_gas",
+        params: &[
+            ValueType::I32,
+            ValueType::I32,
+            ValueType::I32,
+            ValueType::I32,
+            ValueType::I32,
+            ValueType::I32,
+            ValueType::I64,
+        ],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::CallContractWithGasIndex,
+    },
+    HostFunction {
+        name: "get_call_result",
+        params: &[ValueType::I32; 1],
+        result: None,
+        index: FunctionIndex::GetCallResultFuncIndex,
+    },
+    HostFunction {
+        name: "get_uref",
+        params: &[ValueType::I32; 2],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::GetURefFuncIndex,
+    },
+    HostFunction {
+        name: "has_uref_name",
+        params: &[ValueType::I32; 2],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::HasURefFuncIndex,
+    },
+    HostFunction {
+        name: "add_uref",
+        params: &[ValueType::I32; 4],
+        result: None,
+        index: FunctionIndex::AddURefFuncIndex,
+    },
+    HostFunction {
+        name: "gas",
+        params: &[ValueType::I32; 1],
+        result: None,
+        index: FunctionIndex::GasFuncIndex,
+    },
+    HostFunction {
+        name: "store_function",
+        params: &[ValueType::I32; 5],
+        result: None,
+        index: FunctionIndex::StoreFnIndex,
+    },
+    HostFunction {
+        name: "store_function_with_access_groups",
+        params: &[ValueType::I32; 7],
+        result: None,
+        index: FunctionIndex::StoreFnWithAccessGroupsIndex,
+    },
+    HostFunction {
+        name: "protocol_version",
+        params: &[],
+        result: Some(ValueType::I64),
+        index: FunctionIndex::ProtocolVersionFuncIndex,
+    },
+    HostFunction {
+        name: "is_valid",
+        params: &[ValueType::I32; 2],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::IsValidFnIndex,
+    },
+    HostFunction {
+        name: "revert",
+        params: &[ValueType::I32; 1],
+        result: None,
+        index: FunctionIndex::RevertFuncIndex,
+    },
+    HostFunction {
+        name: "add_associated_key",
+        params: &[ValueType::I32; 2],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::AddAssociatedKeyFuncIndex,
+    },
+    HostFunction {
+        name: "remove_associated_key",
+        params: &[ValueType::I32; 1],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::RemoveAssociatedKeyFuncIndex,
+    },
+    HostFunction {
+        name: "set_action_threshold",
+        params: &[ValueType::I32; 2],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::SetActionThresholdFuncIndex,
+    },
+    HostFunction {
+        name: "list_known_urefs",
+        params: &[ValueType::I32; 1],
+        result: None,
+        index: FunctionIndex::ListKnownURefsIndex,
+    },
+    HostFunction {
+        name: "remove_uref",
+        params: &[ValueType::I32; 2],
+        result: None,
+        index: FunctionIndex::RemoveURef,
+    },
+    HostFunction {
+        name: "get_caller",
+        params: &[ValueType::I32; 1],
+        result: None,
+        index: FunctionIndex::GetCallerIndex,
+    },
+    HostFunction {
+        name: "get_blocktime",
+        params: &[ValueType::I32; 1],
+        result: None,
+        index: FunctionIndex::GetBlocktimeIndex,
+    },
+    HostFunction {
+        name: "create_purse",
+        params: &[ValueType::I32; 2],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::CreatePurseIndex,
+    },
+    HostFunction {
+        name: "transfer_to_account",
+        params: &[ValueType::I32; 4],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::TransferToAccountIndex,
+    },
+    HostFunction {
+        name: "transfer_from_purse_to_account",
+        params: &[ValueType::I32; 6],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::TransferFromPurseToAccountIndex,
+    },
+    HostFunction {
+        name: "transfer_from_purse_to_purse",
+        params: &[ValueType::I32; 6],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::TransferFromPurseToPurseIndex,
+    },
+    HostFunction {
+        name: "query_state",
+        params: &[ValueType::I32; 4],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::QueryStateIndex,
+    },
+    HostFunction {
+        name: "dictionary_get",
+        params: &[ValueType::I32; 4],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::DictionaryGetIndex,
+    },
+    HostFunction {
+        name: "dictionary_put",
+        params: &[ValueType::I32; 6],
+        result: None,
+        index: FunctionIndex::DictionaryPutIndex,
+    },
+    HostFunction {
+        name: "get_balance",
+        params: &[ValueType::I32; 2],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::GetBalanceIndex,
+    },
+    HostFunction {
+        name: "get_total_supply",
+        params: &[],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::GetTotalSupplyIndex,
+    },
+    HostFunction {
+        name: "add_recovery_key",
+        params: &[ValueType::I32; 2],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::AddRecoveryKeyIndex,
+    },
+    HostFunction {
+        name: "remove_recovery_key",
+        params: &[ValueType::I32; 1],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::RemoveRecoveryKeyIndex,
+    },
+    HostFunction {
+        name: "recover_account",
+        params: &[ValueType::I32; 4],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::RecoverAccountIndex,
+    },
+    HostFunction {
+        name: "call_precompile",
+        params: &[ValueType::I32; 3],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::CallPrecompileIndex,
+    },
+];
+
+/// Resolves the imports of a module against a set of host functions.
 pub struct RuntimeModuleImportResolver {
+    host_functions: HostFunctions,
     memory: RefCell<Option<MemoryRef>>,
     max_memory: u32,
 }
 
-impl Default for RuntimeModuleImportResolver {
-    fn default() -> Self {
+impl RuntimeModuleImportResolver {
+    pub fn new(host_functions: HostFunctions) -> Self {
         RuntimeModuleImportResolver {
+            host_functions,
             memory: RefCell::new(None),
             max_memory: 64,
         }
@@ -40,151 +340,20 @@ impl ModuleImportResolver for RuntimeModuleImportResolver {
         field_name: &str,
         _signature: &Signature,
     ) -> Result<FuncRef, InterpreterError> {
-        let func_ref = match field_name {
-            "read_value" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 2][..], Some(ValueType::I32)),
-                FunctionIndex::ReadFuncIndex.into(),
-            ),
-            "read_value_local" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 2][..], Some(ValueType::I32)),
-                FunctionIndex::ReadLocalFuncIndex.into(),
-            ),
-            "serialize_function" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 2][..], Some(ValueType::I32)),
-                FunctionIndex::SerFnFuncIndex.into(),
-            ),
-            "serialize_known_urefs" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 0][..], Some(ValueType::I32)),
-                FunctionIndex::SerKnownURefs.into(),
-            ),
-            "write" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 4][..], None),
-                FunctionIndex::WriteFuncIndex.into(),
-            ),
-            "write_local" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 4][..], None),
-                FunctionIndex::WriteLocalFuncIndex.into(),
-            ),
-            "get_read" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 1][..], None),
-                FunctionIndex::GetReadFuncIndex.into(),
-            ),
-            "get_function" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 1][..], None),
-                FunctionIndex::GetFnFuncIndex.into(),
-            ),
-            "add" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 4][..], None),
-                FunctionIndex::AddFuncIndex.into(),
-            ),
-            "new_uref" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 3][..], None),
-                FunctionIndex::NewFuncIndex.into(),
-            ),
-            "load_arg" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 1][..], Some(ValueType::I32)),
-                FunctionIndex::LoadArgFuncIndex.into(),
-            ),
-            "get_arg" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 1][..], None),
-                FunctionIndex::GetArgFuncIndex.into(),
-            ),
-            "ret" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 4][..], None),
-                FunctionIndex::RetFuncIndex.into(),
-            ),
-            "call_contract" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 6][..], Some(ValueType::I32)),
-                FunctionIndex::CallContractFuncIndex.into(),
-            ),
-            "get_call_result" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 1][..], None),
-                FunctionIndex::GetCallResultFuncIndex.into(),
-            ),
-            "get_uref" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 2][..], Some(ValueType::I32)),
-                FunctionIndex::GetURefFuncIndex.into(),
-            ),
-            "has_uref_name" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 2][..], Some(ValueType::I32)),
-                FunctionIndex::HasURefFuncIndex.into(),
-            ),
-            "add_uref" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 4][..], None),
-                FunctionIndex::AddURefFuncIndex.into(),
-            ),
-            "gas" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 1][..], None),
-                FunctionIndex::GasFuncIndex.into(),
-            ),
-            "store_function" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 5][..], None),
-                FunctionIndex::StoreFnIndex.into(),
-            ),
-            "protocol_version" => FuncInstance::alloc_host(
-                Signature::new(vec![], Some(ValueType::I64)),
-                FunctionIndex::ProtocolVersionFuncIndex.into(),
-            ),
-            "is_valid" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 2][..], Some(ValueType::I32)),
-                FunctionIndex::IsValidFnIndex.into(),
-            ),
-            "revert" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 1][..], None),
-                FunctionIndex::RevertFuncIndex.into(),
-            ),
-            "add_associated_key" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 2][..], Some(ValueType::I32)),
-                FunctionIndex::AddAssociatedKeyFuncIndex.into(),
-            ),
-            "remove_associated_key" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 1][..], Some(ValueType::I32)),
-                FunctionIndex::RemoveAssociatedKeyFuncIndex.into(),
-            ),
-            "set_action_threshold" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 2][..], Some(ValueType::I32)),
-                FunctionIndex::SetActionThresholdFuncIndex.into(),
-            ),
-            "list_known_urefs" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 1][..], None),
-                FunctionIndex::ListKnownURefsIndex.into(),
-            ),
-            "remove_uref" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 2][..], None),
-                FunctionIndex::RemoveURef.into(),
-            ),
-            "get_caller" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 1][..], None),
-                FunctionIndex::GetCallerIndex.into(),
-            ),
-            "get_blocktime" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 1][..], None),
-                FunctionIndex::GetBlocktimeIndex.into(),
-            ),
-            "create_purse" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 2][..], Some(ValueType::I32)),
-                FunctionIndex::CreatePurseIndex.into(),
-            ),
-            "transfer_to_account" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 4][..], Some(ValueType::I32)),
-                FunctionIndex::TransferToAccountIndex.into(),
-            ),
-            "transfer_from_purse_to_account" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 6][..], Some(ValueType::I32)),
-                FunctionIndex::TransferFromPurseToAccountIndex.into(),
-            ),
-            "transfer_from_purse_to_purse" => FuncInstance::alloc_host(
-                Signature::new(&[ValueType::I32; 6][..], Some(ValueType::I32)),
-                FunctionIndex::TransferFromPurseToPurseIndex.into(),
-            ),
-            _ => {
-                return Err(InterpreterError::Function(format!(
+        let host_function = self
+            .host_functions
+            .definitions()
+            .find(|host_function| host_function.name == field_name)
+            .ok_or_else(|| {
+                InterpreterError::Function(format!(
                     "host module doesn't export function with name {}",
                     field_name
-                )));
-            }
-        };
-        Ok(func_ref)
+                ))
+            })?;
+        Ok(FuncInstance::alloc_host(
+            host_function.signature(),
+            host_function.index.into(),
+        ))
     }
 
     fn resolve_memory(
@@ -0,0 +1,44 @@
+use wasmi::ValueType;
+
+use super::resolver_v1::HostFunction;
+use function_index::FunctionIndex;
+
+/// Host functions added in protocol version 2, on top of the version 1 ones.
+pub const HOST_FUNCTIONS: &[HostFunction] = &[
+    HostFunction {
+        name: "get_chain_parameters",
+        params: &[ValueType::I32; 1],
+        result: None,
+        index: FunctionIndex::GetChainParametersIndex,
+    },
+    HostFunction {
+        name: "named_keys_page",
+        params: &[ValueType::I32; 3],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::NamedKeysPageIndex,
+    },
+    HostFunction {
+        name: "store_function_at_hash",
+        params: &[ValueType::I32; 7],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::StoreFnAtHashIndex,
+    },
+    HostFunction {
+        name: "emit_event",
+        params: &[ValueType::I32; 2],
+        result: None,
+        index: FunctionIndex::EmitEventIndex,
+    },
+    HostFunction {
+        name: "read_event_log",
+        params: &[ValueType::I32; 2],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::ReadEventLogIndex,
+    },
+    HostFunction {
+        name: "pay_rent",
+        params: &[ValueType::I32; 6],
+        result: Some(ValueType::I32),
+        index: FunctionIndex::PayRentIndex,
+    },
+];
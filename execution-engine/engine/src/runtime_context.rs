@@ -2,6 +2,7 @@ use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::{TryFrom, TryInto};
 use std::fmt::Display;
+use std::ops::Bound;
 use std::rc::Rc;
 
 use blake2::digest::{Input, VariableOutput};
@@ -10,21 +11,62 @@ use rand::RngCore;
 use rand_chacha::ChaChaRng;
 
 use common::bytesrepr::{deserialize, ToBytes};
-use common::key::{Key, LOCAL_SEED_SIZE};
+use common::key::{contract_address, Key, LOCAL_SEED_SIZE};
 use common::uref::{AccessRights, URef};
 use common::value::account::{
-    Account, ActionType, AddKeyFailure, BlockTime, PublicKey, RemoveKeyFailure,
-    SetThresholdFailure, Weight,
+    Account, ActionType, AddKeyFailure, AssociatedKeys, BlockTime, PublicKey, RecoveryFailure,
+    RemoveKeyFailure, SetThresholdFailure, Weight,
 };
 use common::value::{Contract, Value};
 use shared::newtypes::{CorrelationId, Validated};
+use shared::transform::TypeMismatch;
 use storage::global_state::StateReader;
 
-use engine_state::execution_effect::ExecutionEffect;
+use engine_state::execution_effect::{ContractEvent, ExecutionEffect, TransferEvent};
 use execution::Error;
 use tracking_copy::{AddResult, TrackingCopy};
 use URefAddr;
 
+/// Domain separator of dictionary seeds, so that they can't collide with the seeds
+/// of local state of contracts stored under a `URef`.
+const DICTIONARY_SEED_PREFIX: &[u8] = b"dictionary";
+
+/// Derives the key under which the item `item_key` of the dictionary `uref` is stored.
+pub fn dictionary_item_key(uref: &URef, item_key: &str) -> Key {
+    let mut hasher = VarBlake2b::new(LOCAL_SEED_SIZE).unwrap();
+    hasher.input(DICTIONARY_SEED_PREFIX);
+    hasher.input(&uref.addr());
+    let mut seed = [0u8; LOCAL_SEED_SIZE];
+    hasher.variable_result(|hash| seed.clone_from_slice(hash));
+    Key::local(seed, item_key.as_bytes())
+}
+
+/// Domain separator of event log seeds, so that the log can't collide with the local state of
+/// the contract.
+const EVENT_LOG_SEED_PREFIX: &[u8] = b"event_log";
+
+/// Seed of the local state of the account or contract under `key`.
+fn context_seed(key: &Key) -> [u8; LOCAL_SEED_SIZE] {
+    match key {
+        Key::Account(bytes) => *bytes,
+        Key::Hash(bytes) => *bytes,
+        Key::URef(uref) => uref.addr(),
+        Key::Local(hash) => *hash,
+    }
+}
+
+/// Derives the key of the log of recent events emitted by the account or contract under
+/// `context_key`. The log is a `Value::ByteArray` holding a serialized `Vec<Vec<u8>>`, oldest
+/// event first.
+pub fn event_log_key(context_key: &Key) -> Key {
+    let mut hasher = VarBlake2b::new(LOCAL_SEED_SIZE).unwrap();
+    hasher.input(EVENT_LOG_SEED_PREFIX);
+    hasher.input(&context_seed(context_key));
+    let mut seed = [0u8; LOCAL_SEED_SIZE];
+    hasher.variable_result(|hash| seed.clone_from_slice(hash));
+    Key::local(seed, &[])
+}
+
 /// Holds information specific to the deployed contract.
 pub struct RuntimeContext<'a, R> {
     state: Rc<RefCell<TrackingCopy<R>>>,
@@ -92,6 +134,20 @@ where
         &self.uref_lookup
     }
 
+    /// Returns up to `limit` named keys whose names come after `after`, or the first ones if
+    /// it's `None`, so that contracts with many keys can list them a page at a time.
+    pub fn named_keys_page(&self, after: Option<&str>, limit: usize) -> BTreeMap<String, Key> {
+        let start = match after {
+            Some(name) => Bound::Excluded(name),
+            None => Bound::Unbounded,
+        };
+        self.uref_lookup
+            .range::<str, _>((start, Bound::Unbounded))
+            .take(limit)
+            .map(|(name, key)| (name.clone(), *key))
+            .collect()
+    }
+
     pub fn fn_store_id(&self) -> u32 {
         self.fn_store_id
     }
@@ -114,7 +170,7 @@ where
 
         self.state
             .borrow_mut()
-            .write(validated_uref, validated_value);
+            .write(validated_uref, validated_value)?;
 
         Ok(())
     }
@@ -134,7 +190,7 @@ where
 
                 self.state
                     .borrow_mut()
-                    .write(validated_uref, validated_value);
+                    .write(validated_uref, validated_value)?;
 
                 Ok(())
             }
@@ -152,10 +208,13 @@ where
                         .ok_or_else(|| Error::KeyNotFound(contract_uref))?;
 
                     value.try_into().map_err(|found| {
-                        Error::TypeMismatch(shared::transform::TypeMismatch {
-                            expected: "Contract".to_owned(),
-                            found,
-                        })
+                        Error::type_mismatch(
+                            contract_uref,
+                            TypeMismatch {
+                                expected: "Contract".to_owned(),
+                                found,
+                            },
+                        )
                     })?
                 };
 
@@ -224,12 +283,7 @@ where
     }
 
     pub fn seed(&self) -> [u8; LOCAL_SEED_SIZE] {
-        match self.base_key {
-            Key::Account(bytes) => bytes,
-            Key::Hash(bytes) => bytes,
-            Key::URef(uref) => uref.addr(),
-            Key::Local(hash) => hash,
-        }
+        context_seed(&self.base_key)
     }
 
     pub fn protocol_version(&self) -> u64 {
@@ -268,7 +322,11 @@ where
         };
         let key = Key::URef(uref);
         self.insert_uref(uref);
-        self.write_gs(key, value)?;
+        let validated_key = Validated::new(key, |key| self.validate_key(&key))?;
+        let validated_value = Validated::new(value, |value| self.validate_keys(&value))?;
+        self.state
+            .borrow_mut()
+            .create(validated_key, validated_value)?;
         Ok(key)
     }
 
@@ -292,6 +350,7 @@ where
     pub fn read_ls(&mut self, key: &[u8]) -> Result<Option<Value>, Error> {
         let seed = self.seed();
         let key = Key::local(seed, key);
+        self.check_rent(&key)?;
         let validated_key = Validated::new(key, Validated::valid)?;
         self.state
             .borrow_mut()
@@ -302,24 +361,116 @@ where
     pub fn write_ls(&mut self, key_bytes: &[u8], value: Value) -> Result<(), Error> {
         let seed = self.seed();
         let key = Key::local(seed, key_bytes);
+        self.check_rent(&key)?;
         let validated_key = Validated::new(key, Validated::valid)?;
-        let validated_value = Validated::new(value, Validated::valid)?;
+        let validated_value = Validated::new(value, |value| match value {
+            Value::ByteArray(bytes) => self.validate_byte_array(bytes),
+            _ => Ok(()),
+        })?;
         self.state
             .borrow_mut()
-            .write(validated_key, validated_value);
+            .write(validated_key, validated_value)?;
         Ok(())
     }
 
     pub fn read_gs(&mut self, key: &Key) -> Result<Option<Value>, Error> {
-        let validated_key = Validated::new(*key, |key| {
-            self.validate_readable(&key).and(self.validate_key(&key))
-        })?;
+        let validated_key = self.guarded_key(*key, AccessRights::READ)?;
         self.state
             .borrow_mut()
             .read(self.correlation_id, &validated_key)
             .map_err(Into::into)
     }
 
+    /// Reads an item of the dictionary identified by `uref`, which has to be readable.
+    pub fn dictionary_get(&mut self, uref: &URef, item_key: &str) -> Result<Option<Value>, Error> {
+        self.guarded_key(Key::URef(*uref), AccessRights::READ)?;
+        let validated_key = Validated::new(dictionary_item_key(uref, item_key), Validated::valid)?;
+        self.state
+            .borrow_mut()
+            .read(self.correlation_id, &validated_key)
+            .map_err(Into::into)
+    }
+
+    /// Writes an item of the dictionary identified by `uref`, which has to be writeable.
+    pub fn dictionary_put(
+        &mut self,
+        uref: &URef,
+        item_key: &str,
+        value: Value,
+    ) -> Result<(), Error> {
+        self.guarded_key(Key::URef(*uref), AccessRights::WRITE)?;
+        let validated_key = Validated::new(dictionary_item_key(uref, item_key), Validated::valid)?;
+        let validated_value = Validated::new(value, |value| self.validate_keys(&value))?;
+        self.state
+            .borrow_mut()
+            .write(validated_key, validated_value)?;
+        Ok(())
+    }
+
+    /// Reads the value found by following the named keys in `path`, starting from
+    /// the account or contract under `base_key`, similarly to `TrackingCopy::query`.
+    ///
+    /// Only read access is needed: accounts and contracts other than the current one
+    /// can be used as the base, but every `URef` on the way has to be readable.
+    /// Returns `None` if any of the keys or names along the path is missing, and a
+    /// `TypeMismatch` if the path goes through a value which is neither an account nor a
+    /// contract.
+    pub fn query_gs(&mut self, base_key: &Key, path: &[String]) -> Result<Option<Value>, Error> {
+        if let Key::URef(_) = base_key {
+            self.guarded_key(*base_key, AccessRights::READ)?;
+        }
+        let mut key = *base_key;
+        let mut names = path.iter().enumerate();
+        loop {
+            if !Self::is_queryable(&key) {
+                return Err(Error::InvalidAccess {
+                    required: AccessRights::READ,
+                });
+            }
+            let validated_key = Validated::new(key, Validated::valid)?;
+            let value = match self
+                .state
+                .borrow_mut()
+                .read(self.correlation_id, &validated_key)
+                .map_err(Into::into)?
+            {
+                Some(value) => value,
+                None => return Ok(None),
+            };
+            let (followed, name) = match names.next() {
+                Some(next) => next,
+                None => return Ok(Some(value)),
+            };
+            let next_key = match value {
+                Value::Account(account) => account.urefs_lookup().get(name).cloned(),
+                Value::Contract(contract) => contract.urefs_lookup().get(name).cloned(),
+                other => {
+                    return Err(Error::TypeMismatch {
+                        key: *base_key,
+                        path: path[..followed].to_vec(),
+                        mismatch: TypeMismatch::new(
+                            "Account or Contract".to_owned(),
+                            other.type_string(),
+                        ),
+                    })
+                }
+            };
+            key = match next_key {
+                Some(next_key) => next_key,
+                None => return Ok(None),
+            };
+        }
+    }
+
+    // Tests whether `key` can be read as a part of `query_gs`.
+    fn is_queryable(key: &Key) -> bool {
+        match key {
+            Key::Account(_) | Key::Hash(_) => true,
+            Key::URef(uref) => uref.is_readable(),
+            Key::Local(_) => false,
+        }
+    }
+
     /// This method is a wrapper over `read_gs` in the sense
     /// that it extracts the type held by a Value stored in the
     /// global state in a type safe manner.
@@ -342,13 +493,11 @@ where
     }
 
     pub fn write_gs(&mut self, key: Key, value: Value) -> Result<(), Error> {
-        let validated_key: Validated<Key> = Validated::new(key, |key| {
-            self.validate_writeable(&key).and(self.validate_key(&key))
-        })?;
+        let validated_key = self.guarded_key(key, AccessRights::WRITE)?;
         let validated_value = Validated::new(value, |value| self.validate_keys(&value))?;
         self.state
             .borrow_mut()
-            .write(validated_key, validated_value);
+            .write(validated_key, validated_value)?;
         Ok(())
     }
 
@@ -371,7 +520,7 @@ where
                 Validated::new(Value::Account(account), |value| self.validate_keys(&value))?;
             self.state
                 .borrow_mut()
-                .write(validated_key, validated_value);
+                .write(validated_key, validated_value)?;
             Ok(())
         } else {
             panic!("Do not use this function for writing non-account keys")
@@ -384,10 +533,35 @@ where
         let validated_key = Validated::new(Key::Hash(new_hash), Validated::valid)?;
         self.state
             .borrow_mut()
-            .write(validated_key, validated_value);
+            .create(validated_key, validated_value)?;
         Ok(new_hash)
     }
 
+    /// Stores `contract` at the address derived from the deploying account and `contract_name`,
+    /// returning `None` if something is already stored there.
+    pub fn store_contract_at_hash(
+        &mut self,
+        contract: Value,
+        contract_name: &str,
+    ) -> Result<Option<[u8; 32]>, Error> {
+        let hash = contract_address(self.account().pub_key(), contract_name);
+        let validated_key = Validated::new(Key::Hash(hash), Validated::valid)?;
+        if self
+            .state
+            .borrow_mut()
+            .read(self.correlation_id, &validated_key)
+            .map_err(Into::into)?
+            .is_some()
+        {
+            return Ok(None);
+        }
+        let validated_value = Validated::new(contract, |cntr| self.validate_keys(&cntr))?;
+        self.state
+            .borrow_mut()
+            .create(validated_key, validated_value)?;
+        Ok(Some(hash))
+    }
+
     pub fn insert_named_uref(&mut self, name: String, key: Validated<Key>) {
         if let Key::URef(uref) = *key {
             self.insert_uref(uref);
@@ -409,14 +583,69 @@ where
         self.state.borrow_mut().effect()
     }
 
-    /// Validates whether keys used in the `value` are not forged.
+    pub fn record_transfer(&mut self, transfer: TransferEvent) {
+        self.state.borrow_mut().record_transfer(transfer);
+    }
+
+    /// Records an event emitted by the current context and, if the engine keeps event logs,
+    /// appends it to the log of the context, dropping the oldest events over the capacity.
+    ///
+    /// The log is a single value rewritten as a whole by every event, and its bytes are charged
+    /// like those of any other write. It's recorded as an `Op::Write` of the log key, so two
+    /// deploys emitting events from the same context conflict and can't go in the same block.
+    pub fn emit_event(&mut self, data: Vec<u8>) -> Result<(), Error> {
+        self.validate_byte_array(&data)?;
+        let base_key = self.base_key();
+        let capacity = self.state.borrow().event_log_capacity();
+        if capacity > 0 {
+            let mut log = self.read_event_log(&base_key)?;
+            log.push(data.clone());
+            if log.len() > capacity {
+                let excess = log.len() - capacity;
+                log.drain(..excess);
+            }
+            let validated_key = Validated::new(event_log_key(&base_key), Validated::valid)?;
+            let validated_value =
+                Validated::new(Value::ByteArray(log.to_bytes()?), Validated::valid)?;
+            self.state
+                .borrow_mut()
+                .write(validated_key, validated_value)?;
+        }
+        self.state.borrow_mut().record_event(ContractEvent {
+            emitter: base_key,
+            data,
+        });
+        Ok(())
+    }
+
+    /// Reads the recent events of the account or contract under `context_key`, oldest first.
+    /// Event logs can be read by any context.
+    pub fn read_event_log(&mut self, context_key: &Key) -> Result<Vec<Vec<u8>>, Error> {
+        let validated_key = Validated::new(event_log_key(context_key), Validated::valid)?;
+        let value = self
+            .state
+            .borrow_mut()
+            .read(self.correlation_id, &validated_key)
+            .map_err(Into::into)?;
+        match value {
+            None => Ok(Vec::new()),
+            Some(Value::ByteArray(bytes)) => deserialize(&bytes).map_err(Into::into),
+            Some(other) => Err(Error::type_mismatch(
+                *validated_key,
+                TypeMismatch::new("ByteArray".to_string(), other.type_string()),
+            )),
+        }
+    }
+
+    /// Validates whether keys used in the `value` are not forged, and whether a byte array
+    /// is within the configured length.
     pub fn validate_keys(&self, value: &Value) -> Result<(), Error> {
         match value {
+            Value::ByteArray(bytes) => self.validate_byte_array(bytes),
             Value::Int32(_)
             | Value::UInt128(_)
             | Value::UInt256(_)
             | Value::UInt512(_)
-            | Value::ByteArray(_)
             | Value::ListInt32(_)
             | Value::String(_)
             | Value::ListString(_)
@@ -432,10 +661,28 @@ where
                     .values()
                     .try_for_each(|key| self.validate_key(key))
             }
-            Value::Contract(contract) => contract
-                .urefs_lookup()
-                .values()
-                .try_for_each(|key| self.validate_key(key)),
+            Value::Contract(contract) => {
+                contract
+                    .urefs_lookup()
+                    .values()
+                    .try_for_each(|key| self.validate_key(key))?;
+                contract
+                    .access_groups()
+                    .iter()
+                    .try_for_each(|uref| self.validate_uref(uref))
+            }
+        }
+    }
+
+    fn validate_byte_array(&self, bytes: &[u8]) -> Result<(), Error> {
+        let limit = self.state.borrow().max_byte_array_length();
+        if bytes.len() > limit {
+            Err(Error::ByteArrayTooLong {
+                length: bytes.len(),
+                limit,
+            })
+        } else {
+            Ok(())
         }
     }
 
@@ -467,7 +714,7 @@ where
         if let Some(new_rights) = uref.access_rights() {
             self.known_urefs
                 .get(&uref.addr()) // Check if the `key` is known
-                .map(|known_rights| {
+                .filter(|known_rights| {
                     known_rights
                         .iter()
                         .any(|right| *right & new_rights == new_rights)
@@ -479,6 +726,15 @@ where
         }
     }
 
+    /// Checks whether the current context holds at least one of `urefs`, regardless of access
+    /// rights. An empty slice is always satisfied.
+    pub fn holds_any_uref(&self, urefs: &[URef]) -> bool {
+        urefs.is_empty()
+            || urefs
+                .iter()
+                .any(|uref| self.known_urefs.contains_key(&uref.addr()))
+    }
+
     pub fn deserialize_keys(&self, bytes: &[u8]) -> Result<Vec<Key>, Error> {
         let keys: Vec<Key> = deserialize(bytes)?;
         keys.iter().try_for_each(|k| self.validate_key(k))?;
@@ -491,33 +747,42 @@ where
         Ok(keys)
     }
 
-    fn validate_readable(&self, key: &Key) -> Result<(), Error> {
-        if self.is_readable(&key) {
-            Ok(())
-        } else {
-            Err(Error::InvalidAccess {
-                required: AccessRights::READ,
-            })
-        }
+    /// Guards a key chosen by the contract before it's handed to the tracking copy: the key has
+    /// to grant the `required` rights in the current context and, if it's a `URef`, must not be
+    /// forged. Host functions get both checks by going through here instead of each
+    /// remembering to make them.
+    fn guarded_key(&self, key: Key, required: AccessRights) -> Result<Validated<Key>, Error> {
+        Validated::new(key, |key| {
+            self.validate_access(key, required)
+                .and_then(|_| self.validate_key(key))
+                .and_then(|_| self.check_rent(key))
+        })
     }
 
-    fn validate_addable(&self, key: &Key) -> Result<(), Error> {
-        if self.is_addable(&key) {
-            Ok(())
-        } else {
-            Err(Error::InvalidAccess {
-                required: AccessRights::ADD,
-            })
+    /// Fails with `Error::RentExpired` if the rent step marked `key` as reclaimable. Reads the
+    /// rent record only if rent is enabled.
+    fn check_rent(&self, key: &Key) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        if state.rent_config().is_none() {
+            return Ok(());
+        }
+        match state
+            .read_rent_record(self.correlation_id, key)
+            .map_err(Into::into)?
+        {
+            Some(record) if record.reclaimable => Err(Error::RentExpired(*key)),
+            _ => Ok(()),
         }
     }
 
-    fn validate_writeable(&self, key: &Key) -> Result<(), Error> {
-        if self.is_writeable(&key) {
+    fn validate_access(&self, key: &Key, required: AccessRights) -> Result<(), Error> {
+        let granted = (!required.contains(AccessRights::READ) || self.is_readable(key))
+            && (!required.contains(AccessRights::ADD) || self.is_addable(key))
+            && (!required.contains(AccessRights::WRITE) || self.is_writeable(key));
+        if granted {
             Ok(())
         } else {
-            Err(Error::InvalidAccess {
-                required: AccessRights::WRITE,
-            })
+            Err(Error::InvalidAccess { required })
         }
     }
 
@@ -554,9 +819,7 @@ where
     /// either because they're not a Monoid or if the value stored under `key` has different type,
     /// then `TypeMismatch` errors is returned.
     pub fn add_gs(&mut self, key: Key, value: Value) -> Result<(), Error> {
-        let validated_key = Validated::new(key, |k| {
-            self.validate_addable(&k).and(self.validate_key(&k))
-        })?;
+        let validated_key = self.guarded_key(key, AccessRights::ADD)?;
         let validated_value = Validated::new(value, |v| self.validate_keys(&v))?;
         self.add_gs_validated(validated_key, validated_value)
     }
@@ -566,6 +829,7 @@ where
         validated_key: Validated<Key>,
         validated_value: Validated<Value>,
     ) -> Result<(), Error> {
+        let key = *validated_key;
         match self
             .state
             .borrow_mut()
@@ -574,7 +838,13 @@ where
             Err(storage_error) => Err(storage_error.into()),
             Ok(AddResult::Success) => Ok(()),
             Ok(AddResult::KeyNotFound(key)) => Err(Error::KeyNotFound(key)),
-            Ok(AddResult::TypeMismatch(type_mismatch)) => Err(Error::TypeMismatch(type_mismatch)),
+            Ok(AddResult::TypeMismatch(type_mismatch)) => {
+                Err(Error::type_mismatch(key, type_mismatch))
+            }
+            Ok(AddResult::NamedKeysLimitExceeded { key, limit }) => {
+                Err(Error::NamedKeysLimitExceeded { key, limit })
+            }
+            Ok(AddResult::Invalid(error)) => Err(Error::Validation(error)),
         }
     }
 
@@ -606,7 +876,7 @@ where
 
         self.state
             .borrow_mut()
-            .write(validated_uref, validated_value);
+            .write(validated_uref, validated_value)?;
 
         Ok(())
     }
@@ -635,7 +905,7 @@ where
 
         self.state
             .borrow_mut()
-            .write(validated_uref, validated_value);
+            .write(validated_uref, validated_value)?;
 
         Ok(())
     }
@@ -668,10 +938,72 @@ where
 
         self.state
             .borrow_mut()
-            .write(validated_uref, validated_value);
+            .write(validated_uref, validated_value)?;
 
         Ok(())
     }
+
+    pub fn add_recovery_key(&mut self, public_key: PublicKey, weight: Weight) -> Result<(), Error> {
+        if self.base_key() != Key::Account(self.account().pub_key()) {
+            return Err(AddKeyFailure::PermissionDenied.into());
+        }
+        let key = Key::Account(self.account().pub_key());
+        let mut account: Account = self.read_gs_typed(&key)?;
+        account
+            .add_recovery_key(public_key, weight)
+            .map_err(Error::from)?;
+        self.write_account(key, account)
+    }
+
+    pub fn remove_recovery_key(&mut self, public_key: PublicKey) -> Result<(), Error> {
+        if self.base_key() != Key::Account(self.account().pub_key()) {
+            return Err(RemoveKeyFailure::PermissionDenied.into());
+        }
+        let key = Key::Account(self.account().pub_key());
+        let mut account: Account = self.read_gs_typed(&key)?;
+        account
+            .remove_recovery_key(public_key)
+            .map_err(Error::from)?;
+        self.write_account(key, account)
+    }
+
+    /// Replaces the associated keys of the inactive account `target`. The key of the account
+    /// running the deploy has to be one of `target`'s recovery keys.
+    pub fn recover_account(
+        &mut self,
+        target: PublicKey,
+        new_keys: AssociatedKeys,
+    ) -> Result<(), Error> {
+        // Only the session code of the recovering account may act on its behalf
+        if self.base_key() != Key::Account(self.account().pub_key()) {
+            return Err(RecoveryFailure::NotRecoveryKey.into());
+        }
+        let recovery_key = PublicKey::new(self.account().pub_key());
+        let key = Key::Account(target.value());
+        let mut account: Account = self.read_gs_typed(&key)?;
+        account
+            .recover(recovery_key, new_keys, self.blocktime)
+            .map_err(Error::from)?;
+
+        // Only the associated keys changed, so the account's named keys, which are unknown to
+        // the caller, don't need validating.
+        let validated_uref = Validated::new(key, Validated::valid)?;
+        let validated_value = Validated::new(Value::Account(account), Validated::valid)?;
+        self.state
+            .borrow_mut()
+            .write(validated_uref, validated_value)?;
+        Ok(())
+    }
+
+    fn write_account(&mut self, key: Key, account: Account) -> Result<(), Error> {
+        let validated_uref = Validated::new(key, Validated::valid)?;
+        let validated_value =
+            Validated::new(Value::Account(account), |value| self.validate_keys(value))?;
+        self.state
+            .borrow_mut()
+            .write(validated_uref, validated_value)?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -684,23 +1016,34 @@ mod tests {
     use rand::RngCore;
     use rand_chacha::ChaChaRng;
 
-    use common::key::{Key, LOCAL_SEED_SIZE};
+    use common::key::{contract_address, Key, LOCAL_SEED_SIZE};
     use common::uref::{AccessRights, URef};
     use common::value::{self, Account, Contract, Value};
     use shared::transform::Transform;
     use storage::global_state::in_memory::InMemoryGlobalState;
     use storage::global_state::{CommitResult, History};
 
-    use super::{Error, RuntimeContext, URefAddr, Validated};
+    use super::{event_log_key, Error, RuntimeContext, URefAddr, Validated};
     use common::value::account::{
         AccountActivity, ActionType, AddKeyFailure, AssociatedKeys, BlockTime, PublicKey, PurseId,
         RemoveKeyFailure, SetThresholdFailure, Weight,
     };
+    use engine_state::engine_config::EngineConfig;
+    use engine_state::rent::{RentConfig, RentRecord};
     use execution::{create_rng, extract_access_rights_from_keys};
+    use protocol_registry::{ProtocolLimits, DEFAULT_MAX_BYTE_ARRAY_LENGTH};
     use shared::newtypes::CorrelationId;
     use tracking_copy::TrackingCopy;
 
     fn mock_tc(init_key: Key, init_account: value::Account) -> TrackingCopy<InMemoryGlobalState> {
+        mock_tc_with_limits(init_key, init_account, &ProtocolLimits::default())
+    }
+
+    fn mock_tc_with_limits(
+        init_key: Key,
+        init_account: value::Account,
+        limits: &ProtocolLimits,
+    ) -> TrackingCopy<InMemoryGlobalState> {
         let correlation_id = CorrelationId::new();
         let mut hist = InMemoryGlobalState::empty().unwrap();
         let root_hash = hist.root_hash;
@@ -713,7 +1056,7 @@ mod tests {
             .expect("Creation of mocked account should be a success.");
 
         let new_hash = match commit_result {
-            CommitResult::Success(new_hash) => new_hash,
+            CommitResult::Success(new_hash, _) => new_hash,
             other => panic!("Commiting changes to test History failed: {:?}.", other),
         };
 
@@ -722,7 +1065,7 @@ mod tests {
             .expect("Checkout should not throw errors.")
             .expect("Root hash should exist.");
 
-        TrackingCopy::new(reader)
+        TrackingCopy::with_config(reader, &EngineConfig::default(), limits)
     }
 
     fn mock_account_with_purse_id(addr: [u8; 32], purse_id: [u8; 32]) -> (Key, value::Account) {
@@ -735,6 +1078,7 @@ mod tests {
             associated_keys,
             Default::default(),
             AccountActivity::new(BlockTime(0), BlockTime(100)),
+            AssociatedKeys::empty(),
         );
         let key = Key::Account(addr);
 
@@ -835,6 +1179,122 @@ mod tests {
         query(runtime_context)
     }
 
+    #[test]
+    fn named_keys_should_be_listed_by_page() {
+        let base_acc_addr = [0u8; 32];
+        let (key, account) = mock_account(base_acc_addr);
+        let mut uref_map: BTreeMap<String, Key> = (0..5u8)
+            .map(|i| (format!("key{}", i), Key::Hash([i; 32])))
+            .collect();
+        let expected = uref_map.clone();
+        let chacha_rng = create_rng(base_acc_addr, 0);
+        let rc = mock_runtime_context(&account, key, &mut uref_map, HashMap::new(), chacha_rng);
+
+        let mut listed = BTreeMap::new();
+        let mut after: Option<String> = None;
+        loop {
+            let page = rc.named_keys_page(after.as_ref().map(String::as_str), 2);
+            assert!(page.len() <= 2);
+            after = page.keys().next_back().cloned();
+            let last_page = page.len() < 2;
+            listed.extend(page);
+            if last_page {
+                break;
+            }
+        }
+        assert_eq!(listed, expected);
+        assert!(rc.named_keys_page(Some("key4"), 2).is_empty());
+    }
+
+    #[test]
+    fn emitted_events_should_be_kept_in_a_bounded_log() {
+        let base_acc_addr = [0u8; 32];
+        let (key, account) = mock_account(base_acc_addr);
+        let limits = ProtocolLimits {
+            event_log_capacity: 2,
+            ..ProtocolLimits::default()
+        };
+        let tc = mock_tc_with_limits(key, account.clone(), &limits);
+        let mut uref_map = BTreeMap::new();
+        let mut rc = RuntimeContext::new(
+            Rc::new(RefCell::new(tc)),
+            &mut uref_map,
+            HashMap::new(),
+            Vec::new(),
+            &account,
+            key,
+            BlockTime(0),
+            0,
+            0,
+            0,
+            Rc::new(RefCell::new(create_rng(base_acc_addr, 0))),
+            1,
+            CorrelationId::new(),
+        );
+
+        for event in 1..=3u8 {
+            rc.emit_event(vec![event])
+                .expect("emitting an event should work");
+        }
+
+        assert_eq!(rc.read_event_log(&key).unwrap(), vec![vec![2u8], vec![3u8]]);
+        assert!(rc.read_event_log(&Key::Hash([1u8; 32])).unwrap().is_empty());
+        let effect = rc.effect();
+        let emitted: Vec<Vec<u8>> = effect.events.into_iter().map(|event| event.data).collect();
+        assert_eq!(emitted, vec![vec![1u8], vec![2u8], vec![3u8]]);
+        assert!(effect.transforms.contains_key(&event_log_key(&key)));
+    }
+
+    #[test]
+    fn reclaimable_keys_should_not_be_usable() {
+        let base_acc_addr = [0u8; 32];
+        let (key, account) = mock_account(base_acc_addr);
+        let limits = ProtocolLimits {
+            rent: Some(RentConfig {
+                price_per_byte_day: 1,
+                grace_period_millis: 100,
+            }),
+            ..ProtocolLimits::default()
+        };
+        let mut tc = mock_tc_with_limits(key, account.clone(), &limits);
+        let hash_key = Key::Hash([1u8; 32]);
+        tc.write(
+            Validated::new(hash_key, Validated::valid).unwrap(),
+            Validated::new(Value::Int32(1), Validated::valid).unwrap(),
+        )
+        .unwrap();
+        let record = |reclaimable| RentRecord {
+            paid_through: BlockTime(500),
+            reclaimable,
+        };
+        tc.write_rent_record(&hash_key, record(true)).unwrap();
+        let tc = Rc::new(RefCell::new(tc));
+        let mut uref_map = BTreeMap::new();
+        let mut rc = RuntimeContext::new(
+            Rc::clone(&tc),
+            &mut uref_map,
+            HashMap::new(),
+            Vec::new(),
+            &account,
+            key,
+            BlockTime(1_000),
+            0,
+            0,
+            0,
+            Rc::new(RefCell::new(create_rng(base_acc_addr, 0))),
+            1,
+            CorrelationId::new(),
+        );
+
+        assert_matches!(rc.read_gs(&hash_key), Err(Error::RentExpired(k)) if k == hash_key);
+
+        // Paying the rent clears the mark.
+        tc.borrow_mut()
+            .write_rent_record(&hash_key, record(false))
+            .unwrap();
+        assert_eq!(rc.read_gs(&hash_key).unwrap(), Some(Value::Int32(1)));
+    }
+
     #[test]
     fn use_uref_valid() {
         // Test fixture
@@ -858,6 +1318,39 @@ mod tests {
         assert_forged_reference(query_result);
     }
 
+    #[test]
+    fn byte_arrays_over_limit_are_rejected() {
+        let limit = DEFAULT_MAX_BYTE_ARRAY_LENGTH;
+        let query_result = test(HashMap::new(), |mut rc| {
+            rc.new_uref(Value::ByteArray(vec![0u8; limit]))?;
+            rc.write_ls(b"local", Value::ByteArray(vec![0u8; limit + 1]))
+        });
+        assert_matches!(
+            query_result,
+            Err(Error::ByteArrayTooLong { length, limit: l }) if length == limit + 1 && l == limit
+        );
+
+        let query_result = test(HashMap::new(), |mut rc| {
+            rc.new_uref(Value::ByteArray(vec![0u8; limit + 1]))
+        });
+        assert_matches!(query_result, Err(Error::ByteArrayTooLong { .. }));
+    }
+
+    #[test]
+    fn use_uref_with_restricted_rights() {
+        let mut rng = rand::thread_rng();
+        let uref = random_uref_key(&mut rng, AccessRights::READ_WRITE);
+        let known_urefs = extract_access_rights_from_keys(vec![uref.restrict(AccessRights::READ)]);
+
+        let restricted = uref.restrict(AccessRights::READ);
+        let query_result = test(known_urefs.clone(), |rc| rc.validate_key(&restricted));
+        query_result.expect("using a URef with the rights it was shared with should succeed");
+
+        // Rights dropped when sharing the URef can't be restored.
+        let query_result = test(known_urefs, |rc| rc.validate_key(&uref));
+        assert_forged_reference(query_result);
+    }
+
     #[test]
     fn store_contract_with_uref_valid() {
         let mut rng = rand::thread_rng();
@@ -885,6 +1378,25 @@ mod tests {
         assert_eq!(contract, contract_gs);
     }
 
+    #[test]
+    fn store_contract_at_hash_is_deterministic() {
+        let contract = Value::Contract(Contract::new(Vec::new(), BTreeMap::new(), 1));
+
+        let query_result = test(HashMap::new(), |mut rc| {
+            let first = rc.store_contract_at_hash(contract.clone(), "counter")?;
+            let second = rc.store_contract_at_hash(contract.clone(), "counter")?;
+            let other = rc.store_contract_at_hash(contract.clone(), "other")?;
+            let stored = rc.read_gs(&Key::Hash(contract_address([0u8; 32], "counter")))?;
+            Ok((first, second, other, stored))
+        });
+
+        let (first, second, other, stored) = query_result.expect("storing should work");
+        assert_eq!(first, Some(contract_address([0u8; 32], "counter")));
+        assert_eq!(second, None);
+        assert_eq!(other, Some(contract_address([0u8; 32], "other")));
+        assert_eq!(stored, Some(contract));
+    }
+
     #[test]
     fn store_contract_with_uref_forged() {
         let mut rng = rand::thread_rng();
@@ -900,6 +1412,47 @@ mod tests {
         assert_forged_reference(query_result);
     }
 
+    #[test]
+    fn store_contract_with_forged_access_group() {
+        let mut rng = rand::thread_rng();
+        let group = match random_uref_key(&mut rng, AccessRights::READ) {
+            Key::URef(uref) => uref,
+            _ => unreachable!(),
+        };
+        let contract: Value = Contract::new(Vec::new(), BTreeMap::new(), 1)
+            .with_access_groups(vec![group])
+            .into();
+
+        let query_result = test(HashMap::new(), |mut rc| rc.store_contract(contract.clone()));
+
+        assert_forged_reference(query_result);
+    }
+
+    #[test]
+    fn access_groups_require_holding_a_group_uref() {
+        let mut rng = rand::thread_rng();
+        let group_key = random_uref_key(&mut rng, AccessRights::READ);
+        let group = match group_key {
+            Key::URef(uref) => uref,
+            _ => unreachable!(),
+        };
+        let other = match random_uref_key(&mut rng, AccessRights::READ) {
+            Key::URef(uref) => uref,
+            _ => unreachable!(),
+        };
+        let known_urefs = extract_access_rights_from_keys(vec![group_key]);
+
+        let result = test(known_urefs, |rc| {
+            Ok((
+                rc.holds_any_uref(&[]),
+                rc.holds_any_uref(&[other, group.remove_access_rights()]),
+                rc.holds_any_uref(&[other]),
+            ))
+        });
+
+        assert_eq!(result.expect("should run"), (true, true, false));
+    }
+
     #[test]
     fn store_contract_under_uref_valid() {
         // Test that storing contract under URef that is known and has WRITE access works.
@@ -1065,10 +1618,12 @@ mod tests {
         let contract: Value = Contract::new(Vec::new(), BTreeMap::new(), 1).into();
         let tc = Rc::new(RefCell::new(mock_tc(account_key, account.clone())));
         // Store contract in the GlobalState so that we can mainpulate it later.
-        tc.borrow_mut().write(
-            Validated::new(contract_key, Validated::valid).unwrap(),
-            Validated::new(contract, Validated::valid).unwrap(),
-        );
+        tc.borrow_mut()
+            .write(
+                Validated::new(contract_key, Validated::valid).unwrap(),
+                Validated::new(contract, Validated::valid).unwrap(),
+            )
+            .unwrap();
 
         let mut uref_map = BTreeMap::new();
         let uref = random_uref_key(&mut rng, AccessRights::WRITE);
@@ -1118,10 +1673,12 @@ mod tests {
         let contract: Value = Contract::new(Vec::new(), BTreeMap::new(), 1).into();
         let tc = Rc::new(RefCell::new(mock_tc(account_key, account.clone())));
         // Store contract in the GlobalState so that we can mainpulate it later.
-        tc.borrow_mut().write(
-            Validated::new(contract_key, Validated::valid).unwrap(),
-            Validated::new(contract, Validated::valid).unwrap(),
-        );
+        tc.borrow_mut()
+            .write(
+                Validated::new(contract_key, Validated::valid).unwrap(),
+                Validated::new(contract, Validated::valid).unwrap(),
+            )
+            .unwrap();
 
         let mut uref_map = BTreeMap::new();
         let uref = random_uref_key(&mut rng, AccessRights::WRITE);
@@ -1209,6 +1766,29 @@ mod tests {
         assert_invalid_access(query_result, AccessRights::ADD);
     }
 
+    #[test]
+    fn denied_accesses_leave_no_effects() {
+        let mut rng = rand::thread_rng();
+        let uref_key = random_uref_key(&mut rng, AccessRights::READ);
+        let known_urefs = extract_access_rights_from_keys(vec![uref_key]);
+        let uref = match uref_key {
+            Key::URef(uref) => uref,
+            _ => unreachable!(),
+        };
+        let query_result = test(known_urefs, |mut rc| {
+            assert_invalid_access(rc.write_gs(uref_key, Value::Int32(1)), AccessRights::WRITE);
+            assert_invalid_access(
+                rc.dictionary_put(&uref, "item", Value::Int32(1)),
+                AccessRights::WRITE,
+            );
+            assert_invalid_access(rc.add_gs(uref_key, Value::Int32(1)), AccessRights::ADD);
+            Ok(rc.effect())
+        });
+        let effect = query_result.expect("effects should be available");
+        assert!(effect.ops.is_empty());
+        assert!(effect.transforms.is_empty());
+    }
+
     #[test]
     fn local_key_writeable_valid() {
         let known_urefs = HashMap::new();
@@ -1216,7 +1796,7 @@ mod tests {
             let mut rng = rand::thread_rng();
             let seed = runtime_context.seed();
             let key = random_local_key(&mut rng, seed);
-            runtime_context.validate_writeable(&key)
+            runtime_context.validate_access(&key, AccessRights::WRITE)
         };
         let query_result = test(known_urefs, query);
         assert!(query_result.is_err())
@@ -1229,7 +1809,7 @@ mod tests {
             let mut rng = rand::thread_rng();
             let seed = [1u8; LOCAL_SEED_SIZE];
             let key = random_local_key(&mut rng, seed);
-            runtime_context.validate_writeable(&key)
+            runtime_context.validate_access(&key, AccessRights::WRITE)
         };
         let query_result = test(known_urefs, query);
         assert!(query_result.is_err())
@@ -1242,7 +1822,7 @@ mod tests {
             let mut rng = rand::thread_rng();
             let seed = runtime_context.seed();
             let key = random_local_key(&mut rng, seed);
-            runtime_context.validate_readable(&key)
+            runtime_context.validate_access(&key, AccessRights::READ)
         };
         let query_result = test(known_urefs, query);
         assert!(query_result.is_err())
@@ -1255,7 +1835,7 @@ mod tests {
             let mut rng = rand::thread_rng();
             let seed = [1u8; LOCAL_SEED_SIZE];
             let key = random_local_key(&mut rng, seed);
-            runtime_context.validate_readable(&key)
+            runtime_context.validate_access(&key, AccessRights::READ)
         };
         let query_result = test(known_urefs, query);
         assert!(query_result.is_err())
@@ -1268,7 +1848,7 @@ mod tests {
             let mut rng = rand::thread_rng();
             let seed = runtime_context.seed();
             let key = random_local_key(&mut rng, seed);
-            runtime_context.validate_addable(&key)
+            runtime_context.validate_access(&key, AccessRights::ADD)
         };
         let query_result = test(known_urefs, query);
         assert!(query_result.is_err())
@@ -1281,7 +1861,7 @@ mod tests {
             let mut rng = rand::thread_rng();
             let seed = [1u8; LOCAL_SEED_SIZE];
             let key = random_local_key(&mut rng, seed);
-            runtime_context.validate_addable(&key)
+            runtime_context.validate_access(&key, AccessRights::ADD)
         };
         let query_result = test(known_urefs, query);
         assert!(query_result.is_err())
@@ -1447,6 +2027,152 @@ mod tests {
         let _ = test(known_urefs, query);
     }
 
+    #[test]
+    fn query_gs_follows_named_keys_of_other_contracts() {
+        let mut rng = rand::thread_rng();
+        let readable_uref = random_uref_key(&mut rng, AccessRights::READ_WRITE);
+        let private_uref = random_uref_key(&mut rng, AccessRights::WRITE);
+        let known_urefs = extract_access_rights_from_keys(vec![readable_uref, private_uref]);
+
+        let query_result = test(known_urefs, |mut rc| {
+            rc.write_gs(readable_uref, Value::Int32(42))?;
+            let contract = Contract::new(
+                Vec::new(),
+                vec![
+                    ("price".to_owned(), readable_uref),
+                    ("secret".to_owned(), private_uref),
+                ]
+                .into_iter()
+                .collect(),
+                1,
+            );
+            let contract_key = Key::Hash(rc.store_contract(contract.into())?);
+
+            assert_eq!(
+                rc.query_gs(&contract_key, &["price".to_owned()])?,
+                Some(Value::Int32(42))
+            );
+            assert_eq!(rc.query_gs(&contract_key, &["missing".to_owned()])?, None);
+            rc.query_gs(&contract_key, &["secret".to_owned()])
+        });
+
+        assert_invalid_access(query_result, AccessRights::READ);
+    }
+
+    #[test]
+    fn query_gs_reads_other_accounts() {
+        // Unlike `read_gs`, querying does not require the account to be the base key.
+        let mut rng = rand::thread_rng();
+        let other_acc_key = random_account_key(&mut rng);
+
+        let query_result = test(HashMap::new(), |mut rc| {
+            let base_key = rc.base_key();
+            assert_eq!(
+                rc.query_gs(&base_key, &[])?,
+                Some(Value::Account(rc.account().clone()))
+            );
+            rc.query_gs(&other_acc_key, &[])
+        });
+
+        assert_eq!(query_result.expect("Query should succeed."), None);
+    }
+
+    #[test]
+    fn query_gs_reports_path_of_type_mismatch() {
+        let mut rng = rand::thread_rng();
+        let price_uref = random_uref_key(&mut rng, AccessRights::READ_WRITE);
+        let known_urefs = extract_access_rights_from_keys(vec![price_uref]);
+
+        let query_result = test(known_urefs, |mut rc| {
+            rc.write_gs(price_uref, Value::Int32(42))?;
+            let contract = Contract::new(
+                Vec::new(),
+                once(("price".to_owned(), price_uref)).collect(),
+                1,
+            );
+            let contract_key = Key::Hash(rc.store_contract(contract.into())?);
+            rc.query_gs(&contract_key, &["price".to_owned(), "currency".to_owned()])
+        });
+
+        match query_result {
+            Err(Error::TypeMismatch {
+                key,
+                path,
+                mismatch,
+            }) => {
+                assert_matches!(key, Key::Hash(_));
+                assert_eq!(path, vec!["price".to_owned()]);
+                assert_eq!(mismatch.found, "Int32");
+            }
+            other => panic!("expected a type mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn add_gs_type_mismatch_includes_key() {
+        let mut rng = rand::thread_rng();
+        let uref = random_uref_key(&mut rng, AccessRights::READ_ADD_WRITE);
+        let known_urefs = extract_access_rights_from_keys(vec![uref]);
+
+        let query_result = test(known_urefs, |mut rc| {
+            rc.write_gs(uref, Value::String("total".to_owned()))?;
+            rc.add_gs(uref, Value::Int32(1))
+        });
+
+        match query_result {
+            Err(Error::TypeMismatch { key, path, .. }) => {
+                assert_eq!(key, uref);
+                assert!(path.is_empty());
+            }
+            other => panic!("expected a type mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dictionary_items_roundtrip() {
+        let dictionary = URef::new([7u8; 32], AccessRights::READ_ADD_WRITE);
+        let other_dictionary = URef::new([8u8; 32], AccessRights::READ_WRITE);
+        let known_urefs = extract_access_rights_from_keys(vec![
+            Key::URef(dictionary),
+            Key::URef(other_dictionary),
+        ]);
+
+        let query_result = test(known_urefs, |mut rc| {
+            rc.dictionary_put(&dictionary, "alice", Value::UInt64(10))?;
+            assert_eq!(
+                rc.dictionary_get(&dictionary, "alice")?,
+                Some(Value::UInt64(10))
+            );
+            assert_eq!(rc.dictionary_get(&dictionary, "bob")?, None);
+            // Items are scoped by dictionary.
+            rc.dictionary_get(&other_dictionary, "alice")
+        });
+
+        assert_eq!(
+            query_result.expect("Dictionary access should succeed."),
+            None
+        );
+    }
+
+    #[test]
+    fn dictionary_access_is_checked() {
+        let mut rng = rand::thread_rng();
+        let read_only = URef::new([7u8; 32], AccessRights::READ);
+        let known_urefs = extract_access_rights_from_keys(vec![Key::URef(read_only)]);
+        let forged = match random_uref_key(&mut rng, AccessRights::READ_WRITE) {
+            Key::URef(uref) => uref,
+            _ => unreachable!(),
+        };
+
+        let query_result = test(known_urefs.clone(), |mut rc| {
+            rc.dictionary_put(&read_only, "alice", Value::UInt64(10))
+        });
+        assert_invalid_access(query_result, AccessRights::WRITE);
+
+        let query_result = test(known_urefs, |mut rc| rc.dictionary_get(&forged, "alice"));
+        assert_forged_reference(query_result);
+    }
+
     #[test]
     fn can_roundtrip_key_value_pairs_into_local_state() {
         let known_urefs = HashMap::new();
@@ -1,20 +1,61 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use linked_hash_map::LinkedHashMap;
 use parking_lot::Mutex;
 
+use common::bytesrepr::ToBytes;
 use common::key::Key;
+use common::value::account::BlockTime;
 use common::value::Value;
 use shared::newtypes::{CorrelationId, Validated};
 use shared::transform::{self, Transform, TypeMismatch};
+use shared::validators::{self, ValidationError};
 use storage::global_state::StateReader;
 
-use engine_state::execution_effect::ExecutionEffect;
+use engine_state::engine_config::{EngineConfig, DEFAULT_TRACKING_COPY_CACHE_SIZE};
+use engine_state::execution_effect::{ContractEvent, ExecutionEffect, TransferEvent};
 use engine_state::op::Op;
+use engine_state::rent::{self, RentConfig, RentRecord};
+use execution;
 use meter::heap_meter::HeapSize;
 use meter::Meter;
+use protocol_registry::{
+    ProtocolLimits, DEFAULT_MAX_BYTE_ARRAY_LENGTH, DEFAULT_MAX_NAMED_KEYS, DEFAULT_MAX_VALUE_SIZE,
+};
 use utils::add;
 
+fn named_keys_count(value: &Value) -> usize {
+    match value {
+        Value::Account(account) => account.urefs_lookup().len(),
+        Value::Contract(contract) => contract.urefs_lookup().len(),
+        _ => 0,
+    }
+}
+
+/// Values in global state have been checked to be storable, so they serialize.
+fn serialized_size(value: &Value) -> usize {
+    value.to_bytes().map_or(0, |bytes| bytes.len())
+}
+
+/// Whether `transform` puts a different key under a name `value` already has. Unlike adding new
+/// names, overwriting one doesn't commute with other changes of the named keys, so it's recorded
+/// as a write to conflict with them.
+fn overwrites_named_key(value: &Value, transform: &Transform) -> bool {
+    let named_keys = match value {
+        Value::Account(account) => account.urefs_lookup(),
+        Value::Contract(contract) => contract.urefs_lookup(),
+        _ => return false,
+    };
+    match transform {
+        Transform::AddKeys(keys) => keys.iter().any(|(name, key)| {
+            named_keys
+                .get(name)
+                .map_or(false, |existing| existing != key)
+        }),
+        _ => false,
+    }
+}
+
 #[derive(Debug)]
 pub enum QueryResult {
     Success(Value),
@@ -81,11 +122,35 @@ impl<M: Meter<Key, Value>> TrackingCopyCache<M> {
     }
 }
 
+/// Changes made by a tracking copy up to some point, to go back to with `TrackingCopy::restore`.
+pub struct Checkpoint {
+    muts_cached: HashMap<Key, Value>,
+    value_sizes: HashMap<Key, usize>,
+    ops: HashMap<Key, Op>,
+    fns: HashMap<Key, Transform>,
+    transfers: Vec<TransferEvent>,
+    events: Vec<ContractEvent>,
+}
+
 pub struct TrackingCopy<R> {
     reader: R,
     cache: TrackingCopyCache<HeapSize>,
     ops: HashMap<Key, Op>,
     fns: HashMap<Key, Transform>,
+    transfers: Vec<TransferEvent>,
+    events: Vec<ContractEvent>,
+    max_named_keys: usize,
+    max_byte_array_length: usize,
+    max_value_size: usize,
+    event_log_capacity: usize,
+    rent: Option<RentConfig>,
+    /// Keys of the rent records written so far, which don't pay rent themselves.
+    rent_records: HashSet<Key>,
+    /// Bytes of the values serialized to check their size since the last
+    /// `take_written_bytes`.
+    written_bytes: u64,
+    /// Serialized size of the value of each changed key, as counted by `check_storable`.
+    value_sizes: HashMap<Key, usize>,
 }
 
 #[derive(Debug)]
@@ -93,18 +158,70 @@ pub enum AddResult {
     Success,
     KeyNotFound(Key),
     TypeMismatch(TypeMismatch),
+    NamedKeysLimitExceeded {
+        key: Key,
+        limit: usize,
+    },
+    /// The key or the value resulting from the addition can't be stored.
+    Invalid(ValidationError),
 }
 
 impl<R: StateReader<Key, Value>> TrackingCopy<R> {
     pub fn new(reader: R) -> TrackingCopy<R> {
+        //TODO: Should `max_cache_size` be fraction of Wasm memory limit?
+        TrackingCopy::with_cache_size(reader, DEFAULT_TRACKING_COPY_CACHE_SIZE)
+    }
+
+    pub fn with_cache_size(reader: R, max_cache_size: usize) -> TrackingCopy<R> {
         TrackingCopy {
             reader,
-            cache: TrackingCopyCache::new(1024 * 16, HeapSize), //TODO: Should `max_cache_size` be fraction of Wasm memory limit?
+            cache: TrackingCopyCache::new(max_cache_size, HeapSize),
             ops: HashMap::new(),
             fns: HashMap::new(),
+            transfers: Vec::new(),
+            events: Vec::new(),
+            max_named_keys: DEFAULT_MAX_NAMED_KEYS,
+            max_byte_array_length: DEFAULT_MAX_BYTE_ARRAY_LENGTH,
+            max_value_size: DEFAULT_MAX_VALUE_SIZE,
+            event_log_capacity: 0,
+            rent: None,
+            rent_records: HashSet::new(),
+            written_bytes: 0,
+            value_sizes: HashMap::new(),
+        }
+    }
+
+    /// Tracking copy of a deploy running under a protocol version with the given `limits`.
+    pub fn with_config(
+        reader: R,
+        config: &EngineConfig,
+        limits: &ProtocolLimits,
+    ) -> TrackingCopy<R> {
+        TrackingCopy {
+            max_named_keys: limits.max_named_keys,
+            max_byte_array_length: limits.max_byte_array_length,
+            max_value_size: limits.max_value_size,
+            event_log_capacity: limits.event_log_capacity,
+            rent: limits.rent,
+            ..TrackingCopy::with_cache_size(reader, config.tracking_copy_cache_size)
         }
     }
 
+    /// Max length of the byte arrays contracts can store. See `ProtocolLimits`.
+    pub fn max_byte_array_length(&self) -> usize {
+        self.max_byte_array_length
+    }
+
+    /// Number of recent events kept on-chain for each contract. See `ProtocolLimits`.
+    pub fn event_log_capacity(&self) -> usize {
+        self.event_log_capacity
+    }
+
+    /// Rent of the keys in global state, if enabled. See `ProtocolLimits`.
+    pub fn rent_config(&self) -> Option<&RentConfig> {
+        self.rent.as_ref()
+    }
+
     pub fn get(
         &mut self,
         correlation_id: CorrelationId,
@@ -136,12 +253,38 @@ impl<R: StateReader<Key, Value>> TrackingCopy<R> {
         }
     }
 
-    pub fn write(&mut self, k: Validated<Key>, v: Validated<Value>) {
-        let v_local = v.into_raw();
+    /// Fails, leaving the tracking copy untouched, if the key is malformed or the value can't be
+    /// stored, see `shared::validators`.
+    pub fn write(&mut self, k: Validated<Key>, v: Validated<Value>) -> Result<(), ValidationError> {
+        self.write_op(k, v, Op::Write)
+    }
+
+    /// Writes `v` under `k`, which was just created by the deploy, so that the write is
+    /// recorded as a `Create` rather than an overwrite of an existing value.
+    pub fn create(
+        &mut self,
+        k: Validated<Key>,
+        v: Validated<Value>,
+    ) -> Result<(), ValidationError> {
+        self.write_op(k, v, Op::Create)
+    }
+
+    fn write_op(
+        &mut self,
+        k: Validated<Key>,
+        v: Validated<Value>,
+        op: Op,
+    ) -> Result<(), ValidationError> {
+        // Normalizing strips the access rights the key is checked for.
+        validators::well_formed_key(&k)?;
         let k = k.normalize();
+        let v_local = v.into_raw();
+        let size = self.check_storable(&v_local)?;
+        self.value_sizes.insert(k, size);
         self.cache.insert_write(k, v_local.clone());
-        add(&mut self.ops, k, Op::Write);
+        add(&mut self.ops, k, op);
         add(&mut self.fns, k, Transform::Write(v_local));
+        Ok(())
     }
 
     /// Ok(None) represents missing key to which we want to "add" some value.
@@ -153,6 +296,9 @@ impl<R: StateReader<Key, Value>> TrackingCopy<R> {
         k: Validated<Key>,
         v: Validated<Value>,
     ) -> Result<AddResult, R::Error> {
+        if let Err(error) = validators::well_formed_key(&k) {
+            return Ok(AddResult::Invalid(error));
+        }
         let k = k.normalize();
         match self.get(correlation_id, &k)? {
             None => Ok(AddResult::KeyNotFound(k)),
@@ -174,10 +320,31 @@ impl<R: StateReader<Key, Value>> TrackingCopy<R> {
                         )))
                     }
                 };
+                let is_add_keys = match t {
+                    Transform::AddKeys(_) => true,
+                    _ => false,
+                };
+                let op = if overwrites_named_key(&curr, &t) {
+                    Op::Write
+                } else {
+                    Op::Add
+                };
                 match t.clone().apply(curr) {
+                    Ok(ref new_value)
+                        if is_add_keys && named_keys_count(new_value) > self.max_named_keys =>
+                    {
+                        Ok(AddResult::NamedKeysLimitExceeded {
+                            key: k,
+                            limit: self.max_named_keys,
+                        })
+                    }
                     Ok(new_value) => {
+                        match self.check_storable(&new_value) {
+                            Ok(size) => self.value_sizes.insert(k, size),
+                            Err(error) => return Ok(AddResult::Invalid(error)),
+                        };
                         self.cache.insert_write(k, new_value);
-                        add(&mut self.ops, k, Op::Add);
+                        add(&mut self.ops, k, op);
                         add(&mut self.fns, k, t);
                         Ok(AddResult::Success)
                     }
@@ -189,10 +356,169 @@ impl<R: StateReader<Key, Value>> TrackingCopy<R> {
         }
     }
 
+    /// Checks `value` can be stored, see `validators::storable_value`, counting the bytes it
+    /// serializes to for `take_written_bytes`. Returns its serialized size.
+    fn check_storable(&mut self, value: &Value) -> Result<usize, ValidationError> {
+        validators::urefs_have_access_rights(value)?;
+        let size = value.to_bytes()?.len();
+        self.written_bytes = self.written_bytes.saturating_add(size as u64);
+        validators::check_value_size(size, self.max_value_size)?;
+        Ok(size)
+    }
+
+    /// Returns the bytes of the values written since the last call, including values rejected
+    /// for their size, for the runtime to charge them.
+    pub fn take_written_bytes(&mut self) -> u64 {
+        std::mem::replace(&mut self.written_bytes, 0)
+    }
+
+    /// Records a transfer to be reported along with the effects of the deploy.
+    pub fn record_transfer(&mut self, transfer: TransferEvent) {
+        self.transfers.push(transfer);
+    }
+
+    /// Records an event to be reported along with the effects of the deploy.
+    pub fn record_event(&mut self, event: ContractEvent) {
+        self.events.push(event);
+    }
+
+    /// Bytes `checkpoint` copies, for the runtime to charge: the values changed so far, which
+    /// are held both in the cache and in the transforms, and the data of the events.
+    pub fn checkpoint_size(&self) -> usize {
+        let values: usize = self.value_sizes.values().sum();
+        let events: usize = self.events.iter().map(|event| event.data.len()).sum();
+        values.saturating_mul(2).saturating_add(events)
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            muts_cached: self.cache.muts_cached.clone(),
+            value_sizes: self.value_sizes.clone(),
+            ops: self.ops.clone(),
+            fns: self.fns.clone(),
+            transfers: self.transfers.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    /// Discards the changes made since `checkpoint` was taken. Cached reads are kept, as they
+    /// come from the underlying state which doesn't change.
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.cache.muts_cached = checkpoint.muts_cached;
+        self.value_sizes = checkpoint.value_sizes;
+        self.ops = checkpoint.ops;
+        self.fns = checkpoint.fns;
+        self.transfers = checkpoint.transfers;
+        self.events = checkpoint.events;
+    }
+
     pub fn effect(&self) -> ExecutionEffect {
-        ExecutionEffect::new(self.ops.clone(), self.fns.clone())
+        ExecutionEffect {
+            ops: self.ops.clone(),
+            transforms: self.fns.clone(),
+            transfers: self.transfers.clone(),
+            events: self.events.clone(),
+            net_storage_bytes: 0,
+        }
+    }
+
+    /// Bytes the changes made so far add to the global state: the serialized size of each value
+    /// written or added to, less that of the value it replaces. Negative if they free more than
+    /// they add, e.g. when removing named keys.
+    ///
+    /// The sizes of the new values were counted when they were written. The replaced values are
+    /// serialized here, and their bytes counted for `take_written_bytes` so that the runtime
+    /// charges for them.
+    pub fn net_storage_bytes(&mut self, correlation_id: CorrelationId) -> Result<i64, R::Error> {
+        let mut net_storage_bytes = 0i64;
+        let mut serialized_bytes = 0u64;
+        for key in self.cache.muts_cached.keys() {
+            let previous_size = self
+                .reader
+                .read(correlation_id, key)?
+                .map_or(0, |previous| serialized_size(&previous));
+            serialized_bytes = serialized_bytes.saturating_add(previous_size as u64);
+            let size = self.value_sizes.get(key).cloned().unwrap_or(0);
+            net_storage_bytes += size as i64 - previous_size as i64;
+        }
+        self.written_bytes = self.written_bytes.saturating_add(serialized_bytes);
+        Ok(net_storage_bytes)
     }
 
+    /// Reads the rent record of `key`, `None` if it has none.
+    pub fn read_rent_record(
+        &mut self,
+        correlation_id: CorrelationId,
+        key: &Key,
+    ) -> Result<Option<RentRecord>, R::Error> {
+        let validated_key = Validated::new(rent::rent_key(key), Validated::valid)?;
+        Ok(self
+            .read(correlation_id, &validated_key)?
+            .and_then(|value| rent::record_from_value(&value)))
+    }
+
+    pub fn write_rent_record(
+        &mut self,
+        key: &Key,
+        record: RentRecord,
+    ) -> Result<(), ValidationError> {
+        let rent_key = rent::rent_key(key);
+        let value = rent::record_to_value(record)?;
+        self.write(
+            Validated::new(rent_key, Validated::valid)?,
+            Validated::new(value, Validated::valid)?,
+        )?;
+        self.rent_records.insert(rent_key);
+        Ok(())
+    }
+
+    /// Writes a rent record paid through the grace period for each key the changes made so far
+    /// create, if rent is enabled. Keys which already got a record, e.g. by paying rent for them,
+    /// are left alone. Fails if a record can't be stored, e.g. under a tiny `max_value_size`.
+    pub fn start_rent(
+        &mut self,
+        correlation_id: CorrelationId,
+        block_time: BlockTime,
+    ) -> Result<(), execution::Error>
+    where
+        R::Error: Into<execution::Error>,
+    {
+        let config = match self.rent {
+            Some(config) => config,
+            None => return Ok(()),
+        };
+        let mut new_keys = Vec::new();
+        for key in self.cache.muts_cached.keys() {
+            if !self.rent_records.contains(key)
+                && self
+                    .reader
+                    .read(correlation_id, key)
+                    .map_err(Into::into)?
+                    .is_none()
+            {
+                new_keys.push(*key);
+            }
+        }
+        let record = RentRecord {
+            paid_through: BlockTime(block_time.0.saturating_add(config.grace_period_millis)),
+            reclaimable: false,
+        };
+        for key in new_keys {
+            let rent_key = rent::rent_key(&key);
+            if self
+                .get(correlation_id, &rent_key)
+                .map_err(Into::into)?
+                .is_none()
+            {
+                self.write_rent_record(&key, record)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Follows `path` from the value under `base_key`. Names are looked up among the named
+    /// keys of accounts and contracts, while lists are indexed by the position of the item,
+    /// e.g. `["ids", "0"]` is the first item of the list under the `ids` named key.
     pub fn query(
         &mut self,
         correlation_id: CorrelationId,
@@ -235,8 +561,16 @@ impl<R: StateReader<Key, Value>> TrackingCopy<R> {
                                 }
                             }
 
+                            Value::ListInt32(list) => {
+                                list_item(list, name, i).map(Value::Int32).map_err(Ok)
+                            }
+
+                            Value::ListString(list) => {
+                                list_item(list, name, i).map(Value::String).map_err(Ok)
+                            }
+
                             other => Err(
-                                Ok((i, format!("Name {} cannot be followed from value {:?} because it is neither an account nor contract. Value found at path:", name, other)))
+                                Ok((i, format!("Name {} cannot be followed from value {:?} because it is neither an account, contract nor list. Value found at path:", name, other)))
                                 ),
                         }
                     },
@@ -286,6 +620,26 @@ impl<R: StateReader<Key, Value>> TrackingCopy<R> {
     }
 }
 
+/// Takes the item of `list` at the index given by the query path segment `name`, which is
+/// the `i`th one, or describes why there's no such item.
+fn list_item<T>(mut list: Vec<T>, name: &str, i: usize) -> Result<T, (usize, String)> {
+    match name.parse::<usize>() {
+        Ok(index) if index < list.len() => Ok(list.swap_remove(index)),
+        Ok(index) => Err((
+            i,
+            format!(
+                "Index {} out of bounds of list of length {} at path:",
+                index,
+                list.len()
+            ),
+        )),
+        Err(_) => Err((
+            i,
+            format!("Name {} is not an index into the list at path:", name),
+        )),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::cell::Cell;
@@ -296,19 +650,25 @@ mod tests {
     use proptest::collection::vec;
     use proptest::prelude::*;
 
+    use common::bytesrepr::ToBytes;
     use common::gens::*;
     use common::key::Key;
     use common::uref::{AccessRights, URef};
     use common::value::{Account, Contract, Value};
     use shared::transform::Transform;
+    use shared::validators::ValidationError;
     use storage::global_state::in_memory::InMemoryGlobalState;
-    use storage::global_state::StateReader;
+    use storage::global_state::{History, StateReader};
 
     use super::{AddResult, QueryResult, Validated};
     use common::value::account::{
         AccountActivity, AssociatedKeys, BlockTime, PublicKey, PurseId, Weight, KEY_SIZE,
     };
+    use engine_state::engine_config::EngineConfig;
     use engine_state::op::Op;
+    use engine_state::rent::{RentConfig, RentRecord};
+    use execution;
+    use protocol_registry::ProtocolLimits;
     use shared::newtypes::CorrelationId;
     use tracking_copy::TrackingCopy;
 
@@ -434,7 +794,8 @@ mod tests {
         tc.write(
             Validated::new(k, Validated::valid).unwrap(),
             Validated::new(one.clone(), Validated::valid).unwrap(),
-        );
+        )
+        .unwrap();
         // write does not need to query the DB
         let db_value = counter.get();
         assert_eq!(db_value, 0);
@@ -449,7 +810,8 @@ mod tests {
         tc.write(
             Validated::new(k, Validated::valid).unwrap(),
             Validated::new(two.clone(), Validated::valid).unwrap(),
-        );
+        )
+        .unwrap();
         let db_value = counter.get();
         assert_eq!(db_value, 0);
         assert_eq!(tc.fns.len(), 1);
@@ -458,6 +820,32 @@ mod tests {
         assert_eq!(tc.ops.get(&k), Some(&Op::Write));
     }
 
+    #[test]
+    fn tracking_copy_create() {
+        let counter = Rc::new(Cell::new(0));
+        let db = CountingDb::new(Rc::clone(&counter));
+        let mut tc = TrackingCopy::new(db);
+        let k = Key::Hash([0u8; 32]);
+
+        tc.create(
+            Validated::new(k, Validated::valid).unwrap(),
+            Validated::new(Value::Int32(1), Validated::valid).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(counter.get(), 0);
+        assert_eq!(tc.fns.get(&k), Some(&Transform::Write(Value::Int32(1))));
+        assert_eq!(tc.ops.get(&k), Some(&Op::Create));
+
+        // later writes don't make the key any less new
+        tc.write(
+            Validated::new(k, Validated::valid).unwrap(),
+            Validated::new(Value::Int32(2), Validated::valid).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(tc.fns.get(&k), Some(&Transform::Write(Value::Int32(2))));
+        assert_eq!(tc.ops.get(&k), Some(&Op::Create));
+    }
+
     #[test]
     fn tracking_copy_add_i32() {
         let correlation_id = CorrelationId::new();
@@ -509,6 +897,7 @@ mod tests {
             associated_keys,
             Default::default(),
             AccountActivity::new(BlockTime(0), BlockTime(100)),
+            AssociatedKeys::empty(),
         );
         let db = CountingDb::new_init(Value::Account(account));
         let mut tc = TrackingCopy::new(db);
@@ -565,6 +954,322 @@ mod tests {
         assert_eq!(tc.ops.get(&k), Some(&Op::Add));
     }
 
+    #[test]
+    fn tracking_copy_add_named_key_overwrite() {
+        let correlation_id = CorrelationId::new();
+        let u1 = Key::URef(URef::new([1u8; 32], AccessRights::READ_WRITE));
+        let u2 = Key::URef(URef::new([2u8; 32], AccessRights::READ_WRITE));
+        let mut known_urefs = BTreeMap::new();
+        known_urefs.insert("test".to_string(), u1);
+        let contract = Contract::new(Vec::new(), known_urefs, 1);
+        let db = CountingDb::new_init(Value::Contract(contract));
+        let mut tc = TrackingCopy::new(db);
+        let k = Key::Hash([0u8; 32]);
+
+        // putting the same key again commutes with other additions
+        let add = tc.add(
+            correlation_id,
+            Validated::new(k, Validated::valid).unwrap(),
+            Validated::new(Value::NamedKey("test".to_string(), u1), Validated::valid).unwrap(),
+        );
+        assert_matches!(add, Ok(AddResult::Success));
+        assert_eq!(tc.ops.get(&k), Some(&Op::Add));
+
+        // replacing it doesn't
+        let add = tc.add(
+            correlation_id,
+            Validated::new(k, Validated::valid).unwrap(),
+            Validated::new(Value::NamedKey("test".to_string(), u2), Validated::valid).unwrap(),
+        );
+        assert_matches!(add, Ok(AddResult::Success));
+        assert_eq!(tc.ops.get(&k), Some(&Op::Write));
+        let mut expected = BTreeMap::new();
+        expected.insert("test".to_string(), u2);
+        assert_eq!(tc.fns.get(&k), Some(&Transform::AddKeys(expected.clone())));
+        assert_matches!(
+            tc.get(correlation_id, &k),
+            Ok(Some(Value::Contract(ref contract))) if contract.urefs_lookup() == &expected
+        );
+    }
+
+    #[test]
+    fn tracking_copy_net_storage_bytes() {
+        let correlation_id = CorrelationId::new();
+        let shrunk_key = Key::Hash([1u8; 32]);
+        let counter_key = Key::Hash([2u8; 32]);
+        let new_key = Key::Hash([3u8; 32]);
+        let shrunk_value = Value::ByteArray(vec![1u8; 100]);
+        let global_state = InMemoryGlobalState::from_pairs(
+            correlation_id,
+            &[
+                (shrunk_key, shrunk_value.clone()),
+                (counter_key, Value::Int32(1)),
+            ],
+        )
+        .unwrap();
+        let reader = global_state
+            .checkout(global_state.current_root())
+            .unwrap()
+            .unwrap();
+        let mut tc = TrackingCopy::new(reader);
+        assert_eq!(tc.net_storage_bytes(correlation_id).unwrap(), 0);
+
+        let size = |value: &Value| value.to_bytes().unwrap().len() as i64;
+        let smaller_value = Value::ByteArray(vec![1u8; 10]);
+        let new_value = Value::Int32(5);
+        tc.write(
+            Validated::new(shrunk_key, Validated::valid).unwrap(),
+            Validated::new(smaller_value.clone(), Validated::valid).unwrap(),
+        )
+        .unwrap();
+        tc.write(
+            Validated::new(new_key, Validated::valid).unwrap(),
+            Validated::new(new_value.clone(), Validated::valid).unwrap(),
+        )
+        .unwrap();
+        // Adding to a number doesn't change its size.
+        tc.add(
+            correlation_id,
+            Validated::new(counter_key, Validated::valid).unwrap(),
+            Validated::new(Value::Int32(1), Validated::valid).unwrap(),
+        )
+        .unwrap();
+
+        let expected = size(&smaller_value) - size(&shrunk_value) + size(&new_value);
+        assert!(expected < 0);
+        tc.take_written_bytes();
+        assert_eq!(tc.net_storage_bytes(correlation_id).unwrap(), expected);
+        // The replaced values are serialized, so they are charged for.
+        assert_eq!(
+            tc.take_written_bytes(),
+            (size(&shrunk_value) + size(&Value::Int32(1))) as u64
+        );
+    }
+
+    #[test]
+    fn tracking_copy_start_rent_of_new_keys() {
+        let correlation_id = CorrelationId::new();
+        let existing_key = Key::Hash([1u8; 32]);
+        let new_key = Key::Hash([2u8; 32]);
+        let paid_key = Key::Hash([3u8; 32]);
+        let global_state =
+            InMemoryGlobalState::from_pairs(correlation_id, &[(existing_key, Value::Int32(1))])
+                .unwrap();
+        let reader = global_state
+            .checkout(global_state.current_root())
+            .unwrap()
+            .unwrap();
+        let limits = ProtocolLimits {
+            rent: Some(RentConfig {
+                price_per_byte_day: 1,
+                grace_period_millis: 100,
+            }),
+            ..ProtocolLimits::default()
+        };
+        let mut tc = TrackingCopy::with_config(reader, &EngineConfig::default(), &limits);
+        for key in &[existing_key, new_key, paid_key] {
+            tc.write(
+                Validated::new(*key, Validated::valid).unwrap(),
+                Validated::new(Value::Int32(2), Validated::valid).unwrap(),
+            )
+            .unwrap();
+        }
+        let paid = RentRecord {
+            paid_through: BlockTime(5_000),
+            reclaimable: false,
+        };
+        tc.write_rent_record(&paid_key, paid).unwrap();
+
+        tc.start_rent(correlation_id, BlockTime(1_000)).unwrap();
+
+        let grace = RentRecord {
+            paid_through: BlockTime(1_100),
+            reclaimable: false,
+        };
+        assert_eq!(
+            tc.read_rent_record(correlation_id, &new_key).unwrap(),
+            Some(grace)
+        );
+        assert_eq!(
+            tc.read_rent_record(correlation_id, &paid_key).unwrap(),
+            Some(paid)
+        );
+        assert_eq!(
+            tc.read_rent_record(correlation_id, &existing_key).unwrap(),
+            None
+        );
+        // Two records and the three writes, records don't get records of their own.
+        assert_eq!(tc.cache.muts_cached.len(), 5);
+    }
+
+    #[test]
+    fn tracking_copy_start_rent_fails_on_unstorable_records() {
+        let correlation_id = CorrelationId::new();
+        let global_state = InMemoryGlobalState::empty().unwrap();
+        let reader = global_state
+            .checkout(global_state.current_root())
+            .unwrap()
+            .unwrap();
+        // Room for an `Int32`, not for a record.
+        let limits = ProtocolLimits {
+            max_value_size: 8,
+            rent: Some(RentConfig {
+                price_per_byte_day: 1,
+                grace_period_millis: 100,
+            }),
+            ..ProtocolLimits::default()
+        };
+        let mut tc = TrackingCopy::with_config(reader, &EngineConfig::default(), &limits);
+        tc.write(
+            Validated::new(Key::Hash([1u8; 32]), Validated::valid).unwrap(),
+            Validated::new(Value::Int32(1), Validated::valid).unwrap(),
+        )
+        .unwrap();
+
+        assert_matches!(
+            tc.start_rent(correlation_id, BlockTime(1_000)),
+            Err(execution::Error::Validation(ValidationError::ValueTooLarge { .. }))
+        );
+    }
+
+    #[test]
+    fn tracking_copy_restore_checkpoint() {
+        let correlation_id = CorrelationId::new();
+        let db = CountingDb::new_init(Value::Int32(1));
+        let mut tc = TrackingCopy::new(db);
+        let k = Key::Hash([0u8; 32]);
+        let other = Key::Hash([1u8; 32]);
+        let write = |tc: &mut TrackingCopy<CountingDb>, key: Key, value: i32| {
+            tc.write(
+                Validated::new(key, Validated::valid).unwrap(),
+                Validated::new(Value::Int32(value), Validated::valid).unwrap(),
+            )
+            .unwrap()
+        };
+
+        write(&mut tc, k, 2);
+        assert_eq!(
+            tc.checkpoint_size(),
+            2 * Value::Int32(2).to_bytes().unwrap().len()
+        );
+        let checkpoint = tc.checkpoint();
+        write(&mut tc, k, 3);
+        write(&mut tc, other, 4);
+        tc.restore(checkpoint);
+
+        assert_eq!(tc.fns.len(), 1);
+        assert_eq!(tc.fns.get(&k), Some(&Transform::Write(Value::Int32(2))));
+        assert_eq!(tc.ops.get(&other), None);
+        let read = tc.read(
+            correlation_id,
+            &Validated::new(k, Validated::valid).unwrap(),
+        );
+        assert_matches!(read, Ok(Some(Value::Int32(2))));
+    }
+
+    #[test]
+    fn tracking_copy_add_named_key_over_limit() {
+        let correlation_id = CorrelationId::new();
+        let known_urefs: BTreeMap<String, Key> = (0..2u8)
+            .map(|i| (i.to_string(), Key::Hash([i; 32])))
+            .collect();
+        let contract = Contract::new(Vec::new(), known_urefs, 1);
+        let db = CountingDb::new_init(Value::Contract(contract));
+        let limits = ProtocolLimits {
+            max_named_keys: 3,
+            ..ProtocolLimits::default()
+        };
+        let mut tc = TrackingCopy::with_config(db, &EngineConfig::default(), &limits);
+        let k = Key::Hash([0u8; 32]);
+
+        {
+            let mut add_named_key = |name: &str| {
+                tc.add(
+                    correlation_id,
+                    Validated::new(k, Validated::valid).unwrap(),
+                    Validated::new(
+                        Value::NamedKey(name.to_string(), Key::Hash([9u8; 32])),
+                        Validated::valid,
+                    )
+                    .unwrap(),
+                )
+            };
+
+            assert_matches!(add_named_key("2"), Ok(AddResult::Success));
+            // Replacing an existing named key doesn't grow the map.
+            assert_matches!(add_named_key("0"), Ok(AddResult::Success));
+            assert_matches!(
+                add_named_key("3"),
+                Ok(AddResult::NamedKeysLimitExceeded { limit: 3, .. })
+            );
+        }
+        assert_eq!(
+            tc.fns.get(&k),
+            Some(&Transform::AddKeys(
+                vec![
+                    ("0".to_string(), Key::Hash([9u8; 32])),
+                    ("2".to_string(), Key::Hash([9u8; 32]))
+                ]
+                .into_iter()
+                .collect()
+            ))
+        );
+    }
+
+    #[test]
+    fn tracking_copy_rejects_invalid_writes() {
+        let correlation_id = CorrelationId::new();
+        let contract = Contract::new(Vec::new(), BTreeMap::new(), 1);
+        let db = CountingDb::new_init(Value::Contract(contract));
+        let limits = ProtocolLimits {
+            max_value_size: 16,
+            ..ProtocolLimits::default()
+        };
+        let mut tc = TrackingCopy::with_config(db, &EngineConfig::default(), &limits);
+        let k = Key::Hash([0u8; 32]);
+        let uref = URef::new([1u8; 32], AccessRights::READ);
+
+        let too_large = tc.write(
+            Validated::new(k, Validated::valid).unwrap(),
+            Validated::new(Value::ByteArray(vec![0u8; 16]), Validated::valid).unwrap(),
+        );
+        assert_matches!(
+            too_large,
+            Err(ValidationError::ValueTooLarge {
+                size: 21,
+                limit: 16
+            })
+        );
+        let malformed = tc.create(
+            Validated::new(
+                Key::URef(uref.restrict(AccessRights::ADD)),
+                Validated::valid,
+            )
+            .unwrap(),
+            Validated::new(Value::Int32(1), Validated::valid).unwrap(),
+        );
+        assert_matches!(malformed, Err(ValidationError::MalformedKey(_)));
+        let stripped = uref.remove_access_rights();
+        let added = tc.add(
+            correlation_id,
+            Validated::new(k, Validated::valid).unwrap(),
+            Validated::new(
+                Value::NamedKey("a".to_string(), Key::URef(stripped)),
+                Validated::valid,
+            )
+            .unwrap(),
+        );
+        assert_matches!(
+            added,
+            Ok(AddResult::Invalid(ValidationError::MissingAccessRights(_)))
+        );
+        // Only the oversized value got serialized, and is charged although it was rejected.
+        assert_eq!(tc.take_written_bytes(), 21);
+        assert_eq!(tc.take_written_bytes(), 0);
+        assert!(tc.fns.is_empty());
+        assert!(tc.ops.is_empty());
+    }
+
     #[test]
     fn tracking_copy_rw() {
         let correlation_id = CorrelationId::new();
@@ -582,7 +1287,8 @@ mod tests {
         tc.write(
             Validated::new(k, Validated::valid).unwrap(),
             Validated::new(value.clone(), Validated::valid).unwrap(),
-        );
+        )
+        .unwrap();
         assert_eq!(tc.fns.len(), 1);
         assert_eq!(tc.fns.get(&k), Some(&Transform::Write(value)));
         assert_eq!(tc.ops.len(), 1);
@@ -634,13 +1340,52 @@ mod tests {
         tc.write(
             Validated::new(k, Validated::valid).unwrap(),
             Validated::new(write_value.clone(), Validated::valid).unwrap(),
-        );
+        )
+        .unwrap();
         assert_eq!(tc.fns.len(), 1);
         assert_eq!(tc.fns.get(&k), Some(&Transform::Write(write_value)));
         assert_eq!(tc.ops.len(), 1);
         assert_eq!(tc.ops.get(&k), Some(&Op::Write));
     }
 
+    #[test]
+    fn query_list_items() {
+        let correlation_id = CorrelationId::new();
+        let strings = Key::Hash([1u8; 32]);
+        let ints = Key::Hash([2u8; 32]);
+        let mut known_urefs = BTreeMap::new();
+        known_urefs.insert("strings".to_owned(), strings);
+        known_urefs.insert("ints".to_owned(), ints);
+        let contract_key = Key::Hash([3u8; 32]);
+        let contract: Value = Contract::new(vec![0u8], known_urefs, 1).into();
+        let gs = InMemoryGlobalState::from_pairs(
+            correlation_id,
+            &[
+                (
+                    strings,
+                    Value::ListString(vec!["a".to_owned(), "b".to_owned()]),
+                ),
+                (ints, Value::ListInt32(vec![7, 8, 9])),
+                (contract_key, contract),
+            ],
+        )
+        .unwrap();
+        let mut tc = TrackingCopy::new(gs);
+        let mut query = |path: &[&str]| {
+            let path: Vec<String> = path.iter().map(|name| name.to_string()).collect();
+            tc.query(correlation_id, contract_key, &path).unwrap()
+        };
+
+        assert_matches!(
+            query(&["strings", "1"]),
+            QueryResult::Success(Value::String(ref s)) if s == "b"
+        );
+        assert_matches!(query(&["ints", "2"]), QueryResult::Success(Value::Int32(9)));
+        assert_matches!(query(&["ints", "3"]), QueryResult::ValueNotFound(_));
+        assert_matches!(query(&["ints", "first"]), QueryResult::ValueNotFound(_));
+        assert_matches!(query(&["ints", "0", "0"]), QueryResult::ValueNotFound(_));
+    }
+
     proptest! {
         #[test]
         fn query_empty_path(k in key_arb(), missing_key in key_arb(), v in value_arb()) {
@@ -715,7 +1460,8 @@ mod tests {
                 purse_id,
                 associated_keys,
                 Default::default(),
-                AccountActivity::new(BlockTime(0), BlockTime(100))
+                AccountActivity::new(BlockTime(0), BlockTime(100)),
+                AssociatedKeys::empty(),
             );
             let account_key = Key::Account(address);
 
@@ -768,7 +1514,8 @@ mod tests {
                 purse_id,
                 associated_keys,
                 Default::default(),
-                AccountActivity::new(BlockTime(0), BlockTime(100))
+                AccountActivity::new(BlockTime(0), BlockTime(100)),
+                AssociatedKeys::empty(),
             );
             let account_key = Key::Account(address);
 
@@ -0,0 +1,47 @@
+#!/usr/bin/env bash
+
+# Runs the benchmarks at a base revision and at the working tree, and compares them
+# with cargo-benchcmp (`cargo install cargo-benchcmp`).
+#
+# Usage: scripts/compare-benches.sh [BASE_REVISION] [-- BENCH_FILTER]
+
+set -o errexit
+
+BASE="${1:-master}"
+shift || true
+if [ "$1" == "--" ]; then
+    shift
+fi
+
+PACKAGES=(
+    "casperlabs-contract-ffi"
+    "shared"
+    "storage"
+    "execution-engine"
+    "casperlabs-engine-grpc-server"
+)
+
+ROOT="$(git rev-parse --show-toplevel)/execution-engine"
+RESULTS="${ROOT}/target/bench-results"
+WORKTREE="$(mktemp -d)"
+
+trap 'git worktree remove --force "${WORKTREE}"' EXIT
+
+mkdir -p "${RESULTS}"
+
+run_benches() {
+    local DIR="$1"
+    local OUTPUT="$2"
+    shift 2
+    : > "${OUTPUT}"
+    for PACKAGE in "${PACKAGES[@]}"; do
+        (cd "${DIR}" && cargo bench -p "${PACKAGE}" -- "$@") | tee -a "${OUTPUT}"
+    done
+}
+
+git worktree add --detach "${WORKTREE}" "${BASE}"
+# Share the build directory, so that only changed crates are rebuilt.
+CARGO_TARGET_DIR="${ROOT}/target" run_benches "${WORKTREE}/execution-engine" "${RESULTS}/base.txt" "$@"
+run_benches "${ROOT}" "${RESULTS}/head.txt" "$@"
+
+cargo benchcmp "${RESULTS}/base.txt" "${RESULTS}/head.txt"
@@ -0,0 +1,29 @@
+#![feature(test)]
+extern crate test;
+
+use test::black_box;
+use test::Bencher;
+
+use shared::newtypes::Blake2bHash;
+
+// Run with and without `--features simd` to compare the backends.
+
+#[bench]
+fn hash_key_sized_input(b: &mut Bencher) {
+    let data = [7u8; 33];
+    b.iter(|| Blake2bHash::new(black_box(&data)));
+}
+
+#[bench]
+fn hash_trie_node_sized_input(b: &mut Bencher) {
+    // A full pointer block: 256 slots of a tag byte and a 32-byte hash.
+    let data = vec![7u8; 256 * 33];
+    b.iter(|| Blake2bHash::new(black_box(&data)));
+}
+
+#[bench]
+fn hash_contract_sized_input(b: &mut Bencher) {
+    // Contracts are hashed when stored and when their code is cached.
+    let data = vec![7u8; 256 * 1024];
+    b.iter(|| Blake2bHash::new(black_box(&data)));
+}
@@ -0,0 +1,565 @@
+//! Chain specification.
+//!
+//! A chainspec is a TOML manifest shared by all the nodes of a network. It describes the genesis
+//! state, the wasm cost table and limits of every protocol version and the system contracts
+//! installed at genesis and at each upgrade. Wasm paths are relative to the directory of the manifest.
+//!
+//! The chainspec only holds what the manifest says; the engine turns it into its protocol
+//! registry, genesis and upgrade requests.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use common::value::account::PublicKey;
+use common::value::U512;
+use wasm_prep::wasm_costs::WasmCosts;
+
+use crate::utils;
+
+#[derive(Debug)]
+pub enum ChainspecError {
+    Io(PathBuf, io::Error),
+    Toml(PathBuf, toml::de::Error),
+    InvalidPublicKey(String),
+    InvalidAmount(String),
+    /// No cost table is defined for a protocol version used at genesis or by an upgrade.
+    MissingWasmCosts(u64),
+    /// Upgrades have to strictly increase the protocol version.
+    InvalidUpgrade(u64),
+    /// Limits are defined for a protocol version without a cost table.
+    LimitsOfUnknownVersion(u64),
+    /// The cost table of a protocol version refunds more per freed byte than it charges per
+    /// written byte.
+    RefundAboveWriteCost(u64),
+}
+
+impl fmt::Display for ChainspecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainspecError::Io(path, error) => write!(f, "could not read {:?}: {}", path, error),
+            ChainspecError::Toml(path, error) => {
+                write!(f, "could not parse chainspec {:?}: {}", path, error)
+            }
+            ChainspecError::InvalidPublicKey(key) => write!(f, "invalid public key {:?}", key),
+            ChainspecError::InvalidAmount(amount) => write!(f, "invalid amount {:?}", amount),
+            ChainspecError::MissingWasmCosts(protocol_version) => write!(
+                f,
+                "no wasm costs defined for protocol version {}",
+                protocol_version
+            ),
+            ChainspecError::InvalidUpgrade(protocol_version) => write!(
+                f,
+                "upgrade to protocol version {} doesn't follow the previous version",
+                protocol_version
+            ),
+            ChainspecError::LimitsOfUnknownVersion(protocol_version) => write!(
+                f,
+                "limits defined for protocol version {}, which has no wasm costs",
+                protocol_version
+            ),
+            ChainspecError::RefundAboveWriteCost(protocol_version) => write!(
+                f,
+                "storage_refund_per_byte of protocol version {} is above its host_write_per_byte",
+                protocol_version
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkConfig {
+    pub name: String,
+    /// Timestamp of the genesis block, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenesisConfig {
+    pub protocol_version: u64,
+    pub account_addr: [u8; 32],
+    pub initial_tokens: U512,
+    pub mint_code: Vec<u8>,
+    pub pos_code: Vec<u8>,
+    pub validators: Vec<(PublicKey, U512)>,
+}
+
+/// System contracts replaced when the network switches to `protocol_version`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpgradePoint {
+    pub protocol_version: u64,
+    pub mint_code: Option<Vec<u8>>,
+    pub pos_code: Option<Vec<u8>>,
+}
+
+/// Limits of a protocol version; the ones left out keep the engine's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LimitsConfig {
+    pub max_named_keys: Option<usize>,
+    pub max_byte_array_length: Option<usize>,
+    pub max_value_size: Option<usize>,
+    pub event_log_capacity: Option<usize>,
+    pub block_gas_limit: Option<u64>,
+    /// No rent if not set.
+    pub rent_price_per_byte_day: Option<u64>,
+    pub rent_grace_period_millis: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Chainspec {
+    pub network: NetworkConfig,
+    pub genesis: GenesisConfig,
+    /// Sorted by protocol version.
+    pub upgrades: Vec<UpgradePoint>,
+    wasm_costs: BTreeMap<u64, WasmCosts>,
+    /// Versions without an entry use the default limits.
+    limits: BTreeMap<u64, LimitsConfig>,
+}
+
+impl Chainspec {
+    /// Reads the chainspec from a TOML file, loading the wasm files it refers to.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Chainspec, ChainspecError> {
+        let path = path.as_ref();
+        let contents =
+            fs::read_to_string(path).map_err(|error| ChainspecError::Io(path.to_owned(), error))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let manifest: ChainspecToml = toml::from_str(&contents)
+            .map_err(|error| ChainspecError::Toml(path.to_owned(), error))?;
+        Chainspec::from_manifest(manifest, |wasm_path| {
+            let wasm_path = base_dir.join(wasm_path);
+            fs::read(&wasm_path).map_err(|error| ChainspecError::Io(wasm_path, error))
+        })
+    }
+
+    fn from_manifest<F>(manifest: ChainspecToml, mut load: F) -> Result<Chainspec, ChainspecError>
+    where
+        F: FnMut(&Path) -> Result<Vec<u8>, ChainspecError>,
+    {
+        let wasm_costs: BTreeMap<u64, WasmCosts> = manifest
+            .wasm_costs
+            .into_iter()
+            .map(|costs| (costs.protocol_version, costs.into()))
+            .collect();
+        for (protocol_version, costs) in &wasm_costs {
+            if costs.storage_refund_per_byte > costs.host_write_per_byte {
+                return Err(ChainspecError::RefundAboveWriteCost(*protocol_version));
+            }
+        }
+        let mut limits = BTreeMap::new();
+        for limits_toml in manifest.limits {
+            if !wasm_costs.contains_key(&limits_toml.protocol_version) {
+                return Err(ChainspecError::LimitsOfUnknownVersion(
+                    limits_toml.protocol_version,
+                ));
+            }
+            limits.insert(limits_toml.protocol_version, limits_toml.into());
+        }
+
+        let genesis = {
+            let genesis_toml = manifest.genesis;
+            let validators = genesis_toml
+                .validators
+                .iter()
+                .map(|validator| {
+                    Ok((
+                        parse_public_key(&validator.public_key)?,
+                        parse_amount(&validator.stake)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>, ChainspecError>>()?;
+            GenesisConfig {
+                protocol_version: genesis_toml.protocol_version,
+                account_addr: parse_public_key(&genesis_toml.account_public_key)?.value(),
+                initial_tokens: parse_amount(&genesis_toml.initial_tokens)?,
+                mint_code: load(&genesis_toml.mint_code_path)?,
+                pos_code: load(&genesis_toml.pos_code_path)?,
+                validators,
+            }
+        };
+        if !wasm_costs.contains_key(&genesis.protocol_version) {
+            return Err(ChainspecError::MissingWasmCosts(genesis.protocol_version));
+        }
+
+        let mut upgrades = Vec::with_capacity(manifest.upgrades.len());
+        let mut previous_version = genesis.protocol_version;
+        for upgrade in manifest.upgrades {
+            if upgrade.protocol_version <= previous_version {
+                return Err(ChainspecError::InvalidUpgrade(upgrade.protocol_version));
+            }
+            if !wasm_costs.contains_key(&upgrade.protocol_version) {
+                return Err(ChainspecError::MissingWasmCosts(upgrade.protocol_version));
+            }
+            previous_version = upgrade.protocol_version;
+            upgrades.push(UpgradePoint {
+                protocol_version: upgrade.protocol_version,
+                mint_code: upgrade
+                    .mint_code_path
+                    .as_ref()
+                    .map(|path| load(path))
+                    .transpose()?,
+                pos_code: upgrade
+                    .pos_code_path
+                    .as_ref()
+                    .map(|path| load(path))
+                    .transpose()?,
+            });
+        }
+
+        Ok(Chainspec {
+            network: NetworkConfig {
+                name: manifest.network.name,
+                timestamp: manifest.network.timestamp,
+            },
+            genesis,
+            upgrades,
+            wasm_costs,
+            limits,
+        })
+    }
+
+    /// The versions the chainspec defines a cost table for, in increasing order.
+    pub fn protocol_versions(&self) -> impl Iterator<Item = u64> + '_ {
+        self.wasm_costs.keys().cloned()
+    }
+
+    pub fn wasm_costs(&self, protocol_version: u64) -> Option<&WasmCosts> {
+        self.wasm_costs.get(&protocol_version)
+    }
+
+    /// Limits set for `protocol_version`, all left to the defaults if none are.
+    pub fn limits(&self, protocol_version: u64) -> LimitsConfig {
+        self.limits
+            .get(&protocol_version)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn upgrade(&self, protocol_version: u64) -> Option<&UpgradePoint> {
+        self.upgrades
+            .iter()
+            .find(|upgrade| upgrade.protocol_version == protocol_version)
+    }
+}
+
+fn parse_public_key(hex: &str) -> Result<PublicKey, ChainspecError> {
+    utils::decode_hex_32(hex)
+        .map(PublicKey::new)
+        .map_err(|_| ChainspecError::InvalidPublicKey(hex.to_string()))
+}
+
+/// Amounts are decimal strings as they don't fit in TOML integers.
+fn parse_amount(amount: &str) -> Result<U512, ChainspecError> {
+    U512::from_dec_str(amount).map_err(|_| ChainspecError::InvalidAmount(amount.to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChainspecToml {
+    network: NetworkToml,
+    genesis: GenesisToml,
+    wasm_costs: Vec<WasmCostsToml>,
+    #[serde(default)]
+    limits: Vec<LimitsToml>,
+    #[serde(default)]
+    upgrades: Vec<UpgradeToml>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NetworkToml {
+    name: String,
+    timestamp: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GenesisToml {
+    protocol_version: u64,
+    account_public_key: String,
+    initial_tokens: String,
+    mint_code_path: PathBuf,
+    pos_code_path: PathBuf,
+    #[serde(default)]
+    validators: Vec<ValidatorToml>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ValidatorToml {
+    public_key: String,
+    stake: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UpgradeToml {
+    protocol_version: u64,
+    mint_code_path: Option<PathBuf>,
+    pos_code_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WasmCostsToml {
+    protocol_version: u64,
+    regular: u32,
+    div: u32,
+    mul: u32,
+    mem: u32,
+    initial_mem: u32,
+    grow_mem: u32,
+    memcpy: u32,
+    max_stack_height: u32,
+    opcodes_mul: u32,
+    opcodes_div: u32,
+    host_call: u32,
+    host_read: u32,
+    host_write: u32,
+    host_call_contract: u32,
+    #[serde(default)]
+    storage_refund_per_byte: u32,
+    #[serde(default)]
+    host_alloc: u32,
+    #[serde(default)]
+    host_write_per_byte: u32,
+}
+
+impl From<WasmCostsToml> for WasmCosts {
+    fn from(costs: WasmCostsToml) -> Self {
+        WasmCosts {
+            regular: costs.regular,
+            div: costs.div,
+            mul: costs.mul,
+            mem: costs.mem,
+            initial_mem: costs.initial_mem,
+            grow_mem: costs.grow_mem,
+            memcpy: costs.memcpy,
+            max_stack_height: costs.max_stack_height,
+            opcodes_mul: costs.opcodes_mul,
+            opcodes_div: costs.opcodes_div,
+            host_call: costs.host_call,
+            host_read: costs.host_read,
+            host_write: costs.host_write,
+            host_call_contract: costs.host_call_contract,
+            storage_refund_per_byte: costs.storage_refund_per_byte,
+            host_alloc: costs.host_alloc,
+            host_write_per_byte: costs.host_write_per_byte,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LimitsToml {
+    protocol_version: u64,
+    max_named_keys: Option<usize>,
+    max_byte_array_length: Option<usize>,
+    max_value_size: Option<usize>,
+    event_log_capacity: Option<usize>,
+    block_gas_limit: Option<u64>,
+    rent_price_per_byte_day: Option<u64>,
+    rent_grace_period_millis: Option<u64>,
+}
+
+impl From<LimitsToml> for LimitsConfig {
+    fn from(limits: LimitsToml) -> Self {
+        LimitsConfig {
+            max_named_keys: limits.max_named_keys,
+            max_byte_array_length: limits.max_byte_array_length,
+            max_value_size: limits.max_value_size,
+            event_log_capacity: limits.event_log_capacity,
+            block_gas_limit: limits.block_gas_limit,
+            rent_price_per_byte_day: limits.rent_price_per_byte_day,
+            rent_grace_period_millis: limits.rent_grace_period_millis,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use common::value::account::PublicKey;
+    use common::value::U512;
+
+    use super::{Chainspec, ChainspecError, ChainspecToml, LimitsConfig};
+
+    const WASM_COSTS: &str = r#"
+        [[wasm_costs]]
+        protocol_version = 1
+        regular = 1
+        div = 16
+        mul = 4
+        mem = 2
+        initial_mem = 4096
+        grow_mem = 8192
+        memcpy = 1
+        max_stack_height = 65536
+        opcodes_mul = 3
+        opcodes_div = 8
+        host_call = 10
+        host_read = 100
+        host_write = 200
+        host_call_contract = 1000
+        "#;
+
+    const MANIFEST: &str = r#"
+        [network]
+        name = "casperlabs-devnet"
+        timestamp = 1568805354071
+
+        [genesis]
+        protocol_version = 1
+        account_public_key = "0606060606060606060606060606060606060606060606060606060606060606"
+        initial_tokens = "100000000000000000000000000000"
+        mint_code_path = "mint.wasm"
+        pos_code_path = "pos.wasm"
+
+        [[genesis.validators]]
+        public_key = "0101010101010101010101010101010101010101010101010101010101010101"
+        stake = "1000"
+        "#;
+
+    fn load(manifest: &str) -> Result<Chainspec, ChainspecError> {
+        let manifest: ChainspecToml = toml::from_str(manifest).expect("should parse manifest");
+        Chainspec::from_manifest(manifest, |path: &Path| {
+            Ok(path.to_string_lossy().into_owned().into_bytes())
+        })
+    }
+
+    #[test]
+    fn should_load_genesis() {
+        let chainspec = load(&format!("{}{}", MANIFEST, WASM_COSTS)).unwrap();
+        assert_eq!(chainspec.network.name, "casperlabs-devnet");
+        assert_eq!(chainspec.genesis.account_addr, [6u8; 32]);
+        assert_eq!(
+            chainspec.genesis.initial_tokens,
+            U512::from_dec_str("100000000000000000000000000000").unwrap()
+        );
+        assert_eq!(chainspec.genesis.mint_code, b"mint.wasm".to_vec());
+        assert_eq!(
+            chainspec.genesis.validators,
+            vec![(PublicKey::new([1u8; 32]), U512::from(1000))]
+        );
+        assert_eq!(
+            chainspec.wasm_costs(1).map(|costs| costs.host_read),
+            Some(100)
+        );
+        assert!(chainspec.wasm_costs(2).is_none());
+        assert!(chainspec.upgrades.is_empty());
+    }
+
+    #[test]
+    fn should_load_upgrades() {
+        let manifest = format!(
+            "{}{}{}{}",
+            MANIFEST,
+            WASM_COSTS,
+            WASM_COSTS.replace(
+                "protocol_version = 1",
+                "protocol_version = 2\nstorage_refund_per_byte = 2\nhost_write_per_byte = 2"
+            ),
+            "[[upgrades]]\nprotocol_version = 2\npos_code_path = \"pos-2.wasm\"\n"
+        );
+        let chainspec = load(&manifest).unwrap();
+        let upgrade = chainspec.upgrade(2).expect("should have upgrade");
+        assert_eq!(upgrade.mint_code, None);
+        assert_eq!(upgrade.pos_code, Some(b"pos-2.wasm".to_vec()));
+        // Refunds are opt-in, so manifests written before they existed still load.
+        assert_eq!(
+            chainspec
+                .wasm_costs(1)
+                .map(|costs| costs.storage_refund_per_byte),
+            Some(0)
+        );
+        assert_eq!(
+            chainspec
+                .wasm_costs(2)
+                .map(|costs| costs.storage_refund_per_byte),
+            Some(2)
+        );
+        assert_eq!(
+            chainspec.protocol_versions().collect::<Vec<u64>>(),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn should_reject_refunds_above_the_write_cost() {
+        match load(&format!(
+            "{}{}",
+            MANIFEST,
+            WASM_COSTS.replace(
+                "protocol_version = 1",
+                "protocol_version = 1\nstorage_refund_per_byte = 2\nhost_write_per_byte = 1"
+            )
+        )) {
+            Err(ChainspecError::RefundAboveWriteCost(1)) => (),
+            other => panic!("expected RefundAboveWriteCost, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_load_limits() {
+        let manifest = format!(
+            "{}{}{}",
+            MANIFEST, WASM_COSTS, "[[limits]]\nprotocol_version = 1\nmax_named_keys = 10\n"
+        );
+        let chainspec = load(&manifest).unwrap();
+        assert_eq!(
+            chainspec.limits(1),
+            LimitsConfig {
+                max_named_keys: Some(10),
+                ..LimitsConfig::default()
+            }
+        );
+        // Versions without limits use the defaults.
+        assert_eq!(chainspec.limits(2), LimitsConfig::default());
+
+        match load(&manifest.replace(
+            "[[limits]]\nprotocol_version = 1",
+            "[[limits]]\nprotocol_version = 2",
+        )) {
+            Err(ChainspecError::LimitsOfUnknownVersion(2)) => (),
+            other => panic!("expected LimitsOfUnknownVersion, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_require_wasm_costs_of_every_protocol_version() {
+        match load(&format!(
+            "{}{}[[upgrades]]\nprotocol_version = 2\n",
+            MANIFEST, WASM_COSTS
+        )) {
+            Err(ChainspecError::MissingWasmCosts(2)) => (),
+            other => panic!("expected MissingWasmCosts, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_reject_upgrades_not_increasing_protocol_version() {
+        match load(&format!(
+            "{}{}[[upgrades]]\nprotocol_version = 1\n",
+            MANIFEST, WASM_COSTS
+        )) {
+            Err(ChainspecError::InvalidUpgrade(1)) => (),
+            other => panic!("expected InvalidUpgrade, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_reject_invalid_keys_and_amounts() {
+        let manifest = format!("{}{}", MANIFEST, WASM_COSTS);
+        match load(&manifest.replace("0606", "zz06")) {
+            Err(ChainspecError::InvalidPublicKey(_)) => (),
+            other => panic!("expected InvalidPublicKey, got {:?}", other),
+        }
+        match load(&manifest.replace("\"1000\"", "\"-1\"")) {
+            Err(ChainspecError::InvalidAmount(_)) => (),
+            other => panic!("expected InvalidAmount, got {:?}", other),
+        }
+    }
+}
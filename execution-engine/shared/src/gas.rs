@@ -0,0 +1,91 @@
+//! Gas, the unit in which the cost of executing a deploy is measured.
+use std::fmt;
+
+use common::value::U512;
+
+use crate::motes::{ConversionRate, Motes};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Gas(u64);
+
+impl Gas {
+    pub const fn new(value: u64) -> Self {
+        Gas(value)
+    }
+
+    pub fn value(self) -> u64 {
+        self.0
+    }
+
+    /// The gas which `motes` pay for at `conversion_rate`, rounded down. `None` if the rate is
+    /// zero or the result doesn't fit in a `u64`.
+    pub fn from_motes(motes: Motes, conversion_rate: ConversionRate) -> Option<Self> {
+        let rate = conversion_rate.value();
+        if rate == 0 {
+            return None;
+        }
+        let gas = motes.value() / U512::from(rate);
+        if gas > U512::from(u64::max_value()) {
+            return None;
+        }
+        Some(Gas(gas.as_u64()))
+    }
+
+    pub fn checked_add(self, rhs: Gas) -> Option<Gas> {
+        self.0.checked_add(rhs.0).map(Gas)
+    }
+
+    pub fn checked_sub(self, rhs: Gas) -> Option<Gas> {
+        self.0.checked_sub(rhs.0).map(Gas)
+    }
+
+    pub fn saturating_sub(self, rhs: Gas) -> Gas {
+        Gas(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl fmt::Display for Gas {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} gas", self.0)
+    }
+}
+
+impl From<u64> for Gas {
+    fn from(value: u64) -> Self {
+        Gas(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::value::U512;
+
+    use super::Gas;
+    use crate::motes::{ConversionRate, Motes};
+
+    #[test]
+    fn should_check_arithmetic() {
+        assert_eq!(Gas::new(2).checked_add(Gas::new(3)), Some(Gas::new(5)));
+        assert_eq!(Gas::new(u64::max_value()).checked_add(Gas::new(1)), None);
+        assert_eq!(Gas::new(3).checked_sub(Gas::new(2)), Some(Gas::new(1)));
+        assert_eq!(Gas::new(2).checked_sub(Gas::new(3)), None);
+        assert_eq!(Gas::new(2).saturating_sub(Gas::new(3)), Gas::new(0));
+    }
+
+    #[test]
+    fn should_convert_motes_rounding_down() {
+        let rate = ConversionRate::new(10);
+        assert_eq!(
+            Gas::from_motes(Motes::new(U512::from(109)), rate),
+            Some(Gas::new(10))
+        );
+        assert_eq!(
+            Gas::from_motes(Motes::new(U512::from(109)), ConversionRate::new(0)),
+            None
+        );
+        assert_eq!(
+            Gas::from_motes(Motes::new(U512::max_value()), ConversionRate::new(1)),
+            None
+        );
+    }
+}
@@ -11,9 +11,12 @@ extern crate log;
 extern crate num;
 extern crate parity_wasm;
 
+pub mod chainspec;
+pub mod gas;
 pub mod init;
 #[macro_use]
 pub mod logging;
+pub mod motes;
 pub mod newtypes;
 pub mod os;
 pub mod semver;
@@ -21,3 +24,4 @@ pub mod socket;
 pub mod test_utils;
 pub mod transform;
 pub mod utils;
+pub mod validators;
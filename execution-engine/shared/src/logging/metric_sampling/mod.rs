@@ -0,0 +1,168 @@
+//! Limits on the volume of metrics, which at high deploy rates slow down execution.
+//!
+//! Durations can be sampled, reporting only every Nth of each metric, and the number of
+//! distinct tags of a metric can be capped, further tags being reported as [`OTHER_TAG`].
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
+
+/// Tag under which values are reported once a metric has used up its tags.
+pub const OTHER_TAG: &str = "other";
+
+/// Stands for an unbounded number of tags in `MetricSampler::max_tags_per_metric`.
+const UNBOUNDED_TAGS: usize = usize::max_value();
+
+lazy_static! {
+    static ref METRIC_SAMPLER: MetricSampler = MetricSampler::new(MetricSampling::default());
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MetricSampling {
+    /// Only every Nth duration of each metric is reported; 1 reports all of them.
+    pub duration_sample_every: u64,
+    /// Max number of distinct tags per metric; unbounded if `None`.
+    pub max_tags_per_metric: Option<usize>,
+}
+
+impl Default for MetricSampling {
+    fn default() -> Self {
+        MetricSampling {
+            duration_sample_every: 1,
+            max_tags_per_metric: None,
+        }
+    }
+}
+
+/// Every metric goes through the sampler, so the settings are atomics, and the per metric
+/// counts are only touched when sampling or a tag cap is configured. Duration counters are
+/// atomics as well, behind a lock taken for writing only the first time a metric is seen.
+pub struct MetricSampler {
+    duration_sample_every: AtomicU64,
+    max_tags_per_metric: AtomicUsize,
+    durations: RwLock<HashMap<String, AtomicU64>>,
+    tags: Mutex<HashMap<String, BTreeSet<String>>>,
+}
+
+impl MetricSampler {
+    pub fn new(settings: MetricSampling) -> MetricSampler {
+        let sampler = MetricSampler {
+            duration_sample_every: AtomicU64::new(1),
+            max_tags_per_metric: AtomicUsize::new(UNBOUNDED_TAGS),
+            durations: RwLock::new(HashMap::new()),
+            tags: Mutex::new(HashMap::new()),
+        };
+        sampler.configure(settings);
+        sampler
+    }
+
+    /// Replaces the settings, starting the counts over.
+    pub fn configure(&self, settings: MetricSampling) {
+        if let Ok(mut durations) = self.durations.write() {
+            durations.clear();
+        }
+        if let Ok(mut tags) = self.tags.lock() {
+            tags.clear();
+        }
+        self.duration_sample_every
+            .store(settings.duration_sample_every, Ordering::SeqCst);
+        self.max_tags_per_metric.store(
+            settings.max_tags_per_metric.unwrap_or(UNBOUNDED_TAGS),
+            Ordering::SeqCst,
+        );
+    }
+
+    /// Whether to report this duration of `metric`. The first one is always reported.
+    pub fn sample_duration(&self, metric: &str) -> bool {
+        let sample_every = self.duration_sample_every.load(Ordering::Relaxed);
+        if sample_every <= 1 {
+            return true;
+        }
+        let count = match self.durations.read() {
+            Ok(durations) => durations
+                .get(metric)
+                .map(|counter| counter.fetch_add(1, Ordering::Relaxed)),
+            Err(_) => return true,
+        };
+        let count = match count {
+            Some(count) => count,
+            None => match self.durations.write() {
+                Ok(mut durations) => durations
+                    .entry(metric.to_string())
+                    .or_insert_with(|| AtomicU64::new(0))
+                    .fetch_add(1, Ordering::Relaxed),
+                Err(_) => return true,
+            },
+        };
+        count % sample_every == 0
+    }
+
+    /// `tag`, or [`OTHER_TAG`] if `metric` already has as many other tags as allowed.
+    pub fn tag<'a>(&self, metric: &str, tag: &'a str) -> &'a str {
+        let max_tags = self.max_tags_per_metric.load(Ordering::Relaxed);
+        if max_tags == UNBOUNDED_TAGS {
+            return tag;
+        }
+        let mut tags = match self.tags.lock() {
+            Ok(tags) => tags,
+            Err(_) => return tag,
+        };
+        if !tags.contains_key(metric) {
+            tags.insert(metric.to_string(), BTreeSet::new());
+        }
+        let metric_tags = tags.get_mut(metric).expect("tags were just inserted");
+        if metric_tags.contains(tag) {
+            tag
+        } else if metric_tags.len() < max_tags {
+            metric_tags.insert(tag.to_string());
+            tag
+        } else {
+            OTHER_TAG
+        }
+    }
+}
+
+/// Applies `settings` to the metrics logged from now on.
+pub fn set_metric_sampling(settings: MetricSampling) {
+    METRIC_SAMPLER.configure(settings);
+}
+
+pub(crate) fn get_metric_sampler() -> &'static MetricSampler {
+    &METRIC_SAMPLER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetricSampler, MetricSampling, OTHER_TAG};
+
+    #[test]
+    fn should_report_every_nth_duration_of_each_metric() {
+        let sampler = MetricSampler::new(MetricSampling {
+            duration_sample_every: 3,
+            ..MetricSampling::default()
+        });
+        let read: Vec<bool> = (0..7).map(|_| sampler.sample_duration("read")).collect();
+        assert_eq!(read, vec![true, false, false, true, false, false, true]);
+        assert!(sampler.sample_duration("write"));
+
+        sampler.configure(MetricSampling::default());
+        assert!((0..3).all(|_| sampler.sample_duration("read")));
+    }
+
+    #[test]
+    fn should_cap_tags_of_each_metric() {
+        let sampler = MetricSampler::new(MetricSampling {
+            max_tags_per_metric: Some(2),
+            ..MetricSampling::default()
+        });
+        assert_eq!(sampler.tag("read", "a"), "a");
+        assert_eq!(sampler.tag("read", "b"), "b");
+        assert_eq!(sampler.tag("read", "c"), OTHER_TAG);
+        assert_eq!(sampler.tag("read", "a"), "a");
+        assert_eq!(sampler.tag("write", "c"), "c");
+
+        let unbounded = MetricSampler::new(MetricSampling::default());
+        assert!((0..100)
+            .map(|i| i.to_string())
+            .all(|tag| unbounded.tag("read", &tag) == tag));
+    }
+}
@@ -4,6 +4,7 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use crate::logging::log_level::LogLevel;
 use crate::logging::log_message::{LogMessage, MessageId};
 use crate::logging::logger::initialize_terminal_logger;
+use crate::logging::metric_sampling::get_metric_sampler;
 use crate::newtypes::CorrelationId;
 use crate::utils::jsonify;
 
@@ -12,6 +13,9 @@ pub mod log_message;
 pub mod log_settings;
 #[macro_use]
 pub mod logger;
+pub mod metric_sampling;
+#[cfg(unix)]
+pub mod syslog;
 
 #[cfg(test)]
 mod tests;
@@ -46,6 +50,9 @@ pub fn log(log_level: LogLevel, log_message: &str) -> Option<MessageId> {
         payload = json
     );
 
+    #[cfg(unix)]
+    syslog::forward(&log_message);
+
     Some(log_message.message_id)
 }
 
@@ -87,6 +94,9 @@ pub fn log_details(
         payload = json
     );
 
+    #[cfg(unix)]
+    syslog::forward(&log_message);
+
     Some(log_message.message_id)
 }
 
@@ -96,6 +106,8 @@ pub fn log_details(
 /// * `metric` - the name of the metric
 /// * `tag` - a grouping tag for the metric
 /// * `duration` - in seconds
+///
+/// Durations are sampled as set by [`metric_sampling::set_metric_sampling`].
 #[inline]
 pub fn log_duration(
     correlation_id: CorrelationId,
@@ -104,6 +116,9 @@ pub fn log_duration(
     duration: Duration,
 ) -> Option<MessageId> {
     initialize_terminal_logger();
+    if !get_metric_sampler().sample_duration(metric) {
+        return None;
+    }
     let duration_in_seconds: f64 = duration.as_float_secs();
 
     log_metric(
@@ -139,6 +154,8 @@ pub fn log_metric(
         return None;
     }
 
+    let tag = get_metric_sampler().tag(metric, tag);
+
     let mut properties: BTreeMap<String, String> = BTreeMap::new();
 
     let from_epoch = SystemTime::now()
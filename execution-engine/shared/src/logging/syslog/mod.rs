@@ -0,0 +1,402 @@
+//! Sink forwarding log messages to syslog or journald, in addition to stdout.
+//!
+//! Syslog records follow RFC 5424 and carry the same `payload=<json>` as the stdout lines, so
+//! the same parsers work on both. Journald records use its native protocol, where every field
+//! of the message, including each property, becomes a field of the journal entry.
+//!
+//! Both are reached through Unix datagram sockets, so the module is only available on Unix.
+use std::fmt;
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+
+use serde::Deserialize;
+
+use crate::logging::log_message::LogMessage;
+use crate::utils::jsonify;
+
+/// Socket syslog daemons listen on, journald included.
+pub const SYSLOG_SOCKET: &str = "/dev/log";
+/// Socket of journald's native protocol.
+pub const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Prefix of the journal fields holding message properties.
+const PROPERTY_FIELD_PREFIX: &str = "CL_";
+
+lazy_static! {
+    static ref SYSLOG_SINK: RwLock<Option<SyslogSink>> = RwLock::new(None);
+}
+
+/// Where a [`SyslogSink`] sends messages, and in which format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogTarget {
+    Syslog,
+    Journald,
+}
+
+impl SyslogTarget {
+    pub fn socket(self) -> &'static Path {
+        match self {
+            SyslogTarget::Syslog => Path::new(SYSLOG_SOCKET),
+            SyslogTarget::Journald => Path::new(JOURNALD_SOCKET),
+        }
+    }
+}
+
+impl FromStr for SyslogTarget {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "syslog" => Ok(SyslogTarget::Syslog),
+            "journald" => Ok(SyslogTarget::Journald),
+            _ => Err(format!("unknown syslog target {}", input)),
+        }
+    }
+}
+
+/// Syslog facilities available to user processes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Facility {
+    User,
+    Daemon,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Facility {
+    pub fn value(self) -> u8 {
+        match self {
+            Facility::User => 1,
+            Facility::Daemon => 3,
+            Facility::Local0 => 16,
+            Facility::Local1 => 17,
+            Facility::Local2 => 18,
+            Facility::Local3 => 19,
+            Facility::Local4 => 20,
+            Facility::Local5 => 21,
+            Facility::Local6 => 22,
+            Facility::Local7 => 23,
+        }
+    }
+}
+
+impl Default for Facility {
+    fn default() -> Self {
+        Facility::Daemon
+    }
+}
+
+impl FromStr for Facility {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "user" => Ok(Facility::User),
+            "daemon" => Ok(Facility::Daemon),
+            "local0" => Ok(Facility::Local0),
+            "local1" => Ok(Facility::Local1),
+            "local2" => Ok(Facility::Local2),
+            "local3" => Ok(Facility::Local3),
+            "local4" => Ok(Facility::Local4),
+            "local5" => Ok(Facility::Local5),
+            "local6" => Ok(Facility::Local6),
+            "local7" => Ok(Facility::Local7),
+            _ => Err(format!("unknown syslog facility {}", input)),
+        }
+    }
+}
+
+impl fmt::Display for Facility {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_lowercase())
+    }
+}
+
+/// Sends records without blocking the thread logging them: records the daemon can't take right
+/// away are dropped. After a failed send the socket is reconnected on the next message, e.g. once
+/// a restarted daemon listens again.
+pub struct SyslogSink {
+    path: PathBuf,
+    socket: Mutex<Option<UnixDatagram>>,
+    target: SyslogTarget,
+    facility: Facility,
+}
+
+impl SyslogSink {
+    /// Connects to the socket of `target`.
+    pub fn connect(target: SyslogTarget, facility: Facility) -> io::Result<SyslogSink> {
+        SyslogSink::connect_to(target.socket(), target, facility)
+    }
+
+    pub fn connect_to<P: AsRef<Path>>(
+        path: P,
+        target: SyslogTarget,
+        facility: Facility,
+    ) -> io::Result<SyslogSink> {
+        let path = path.as_ref().to_path_buf();
+        let socket = connect_socket(&path)?;
+        Ok(SyslogSink {
+            path,
+            socket: Mutex::new(Some(socket)),
+            target,
+            facility,
+        })
+    }
+
+    /// Sends `log_message` as a single datagram. A full socket buffer isn't an error: the
+    /// message is dropped. Any other error disconnects the sink until the next message.
+    pub fn send(&self, log_message: &LogMessage) -> io::Result<()> {
+        let record = match self.target {
+            SyslogTarget::Syslog => syslog_record(self.facility, log_message),
+            SyslogTarget::Journald => journald_record(self.facility, log_message),
+        };
+        let mut guard = match self.socket.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if guard.is_none() {
+            *guard = Some(connect_socket(&self.path)?);
+        }
+        let result = match guard.as_ref() {
+            Some(socket) => socket.send(&record),
+            None => return Ok(()),
+        };
+        match result {
+            Ok(_) => Ok(()),
+            Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(error) => {
+                *guard = None;
+                Err(error)
+            }
+        }
+    }
+}
+
+fn connect_socket(path: &Path) -> io::Result<UnixDatagram> {
+    let socket = UnixDatagram::unbound()?;
+    socket.set_nonblocking(true)?;
+    socket.connect(path)?;
+    Ok(socket)
+}
+
+/// Sends every message logged from now on to `sink` as well.
+pub fn set_syslog_sink(sink: SyslogSink) {
+    if let Ok(mut guard) = SYSLOG_SINK.write() {
+        *guard = Some(sink);
+    }
+}
+
+/// Forwards `log_message` to the sink, if one is set. Messages the daemon doesn't take are
+/// dropped, they're still written to stdout.
+pub(crate) fn forward(log_message: &LogMessage) {
+    if let Ok(guard) = SYSLOG_SINK.read() {
+        if let Some(sink) = guard.as_ref() {
+            let _ = sink.send(log_message);
+        }
+    }
+}
+
+fn syslog_record(facility: Facility, log_message: &LogMessage) -> Vec<u8> {
+    // <PRI>VERSION TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG
+    format!(
+        "<{pri}>1 {timestamp} {hostname} {app} {pid} {msgid} - {description} payload={payload}",
+        pri = u16::from(facility.value()) * 8 + u16::from(log_message.priority.value()),
+        timestamp = log_message.timestamp,
+        hostname = syslog_header_field(&log_message.host_name.value()),
+        app = syslog_header_field(&log_message.process_name.value()),
+        pid = log_message.process_id.value(),
+        msgid = syslog_header_field(&log_message.message_type.value()),
+        description = log_message.description,
+        payload = jsonify(log_message, false)
+    )
+    .into_bytes()
+}
+
+/// Header fields are printable ASCII without spaces, `-` standing for an empty one.
+fn syslog_header_field(value: &str) -> String {
+    let field: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(48)
+        .collect();
+    if field.is_empty() {
+        "-".to_string()
+    } else {
+        field
+    }
+}
+
+fn journald_record(facility: Facility, log_message: &LogMessage) -> Vec<u8> {
+    let mut record = Vec::new();
+    let mut field = |name: &str, value: &str| journald_field(&mut record, name, value);
+    field("MESSAGE", &log_message.description);
+    field("PRIORITY", &log_message.priority.value().to_string());
+    field("SYSLOG_FACILITY", &facility.value().to_string());
+    field("SYSLOG_IDENTIFIER", &log_message.process_name.value());
+    field("SYSLOG_PID", &log_message.process_id.value().to_string());
+    field("CL_LOG_LEVEL", &log_message.log_level.to_uppercase());
+    field("CL_MESSAGE_TYPE", &log_message.message_type.value());
+    field("CL_MESSAGE_ID", &log_message.message_id.value());
+    field("CL_TIMESTAMP", &log_message.timestamp.to_string());
+    for (key, value) in log_message.properties.value() {
+        field(&journald_field_name(&key), &value);
+    }
+    record
+}
+
+/// Journal field names consist of uppercase letters, digits and underscores.
+fn journald_field_name(key: &str) -> String {
+    let name: String = key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}", PROPERTY_FIELD_PREFIX, name)
+}
+
+/// Appends `NAME=value\n`, or the length prefixed form if the value spans several lines.
+fn journald_field(record: &mut Vec<u8>, name: &str, value: &str) {
+    record.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        record.push(b'\n');
+        record.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        record.push(b'=');
+    }
+    record.extend_from_slice(value.as_bytes());
+    record.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::os::unix::net::UnixDatagram;
+
+    use crate::logging::log_level::LogLevel;
+    use crate::logging::log_message::LogMessage;
+    use crate::logging::log_settings::{LogLevelFilter, LogSettings};
+
+    use super::{journald_record, syslog_record, Facility, SyslogSink, SyslogTarget};
+
+    fn log_message(properties: &[(&str, &str)]) -> LogMessage {
+        let log_settings =
+            LogSettings::new("casperlabs-engine", LogLevelFilter::new(LogLevel::Info));
+        let properties: BTreeMap<String, String> = properties
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        LogMessage::new_props(
+            &log_settings,
+            LogLevel::Error,
+            "{message}".to_string(),
+            properties,
+        )
+    }
+
+    #[test]
+    fn should_parse_facilities_and_targets() {
+        assert_eq!("local3".parse(), Ok(Facility::Local3));
+        assert_eq!(Facility::Local3.to_string(), "local3");
+        assert!("kern".parse::<Facility>().is_err());
+        assert_eq!("journald".parse(), Ok(SyslogTarget::Journald));
+        assert!("stdout".parse::<SyslogTarget>().is_err());
+    }
+
+    #[test]
+    fn syslog_record_should_carry_priority_and_payload() {
+        let log_message = log_message(&[("message", "disk full")]);
+        let record = String::from_utf8(syslog_record(Facility::Local0, &log_message)).unwrap();
+        // local0 * 8 + error
+        assert!(record.starts_with("<131>1 "), "{}", record);
+        assert!(record.contains(" casperlabs-engine "));
+        assert!(record.contains(" ee-structured - disk full payload={"));
+    }
+
+    #[test]
+    fn journald_record_should_have_a_field_per_property() {
+        let log_message = log_message(&[("message", "disk full"), ("free-bytes", "0\n1")]);
+        let record = journald_record(Facility::Daemon, &log_message);
+        let text = String::from_utf8_lossy(&record);
+        assert!(text.starts_with("MESSAGE=disk full\nPRIORITY=3\nSYSLOG_FACILITY=3\n"));
+        assert!(text.contains("SYSLOG_IDENTIFIER=casperlabs-engine\n"));
+        assert!(text.contains("CL_MESSAGE=disk full\n"));
+
+        let mut multiline = b"CL_FREE_BYTES\n".to_vec();
+        multiline.extend_from_slice(&3u64.to_le_bytes());
+        multiline.extend_from_slice(b"0\n1\n");
+        assert!(record
+            .windows(multiline.len())
+            .any(|window| window == multiline.as_slice()));
+    }
+
+    #[test]
+    fn sink_should_send_a_datagram_per_message() {
+        let path = std::env::temp_dir().join(format!("casperlabs-syslog-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let daemon = UnixDatagram::bind(&path).unwrap();
+        let sink = SyslogSink::connect_to(&path, SyslogTarget::Journald, Facility::Daemon).unwrap();
+        let log_message = log_message(&[("message", "hello")]);
+
+        sink.send(&log_message).unwrap();
+
+        let mut buffer = [0u8; 4096];
+        let length = daemon.recv(&mut buffer).unwrap();
+        assert_eq!(
+            &buffer[..length],
+            journald_record(Facility::Daemon, &log_message).as_slice()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sink_should_reconnect_after_the_daemon_restarts() {
+        let path =
+            std::env::temp_dir().join(format!("casperlabs-syslog-restart-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let daemon = UnixDatagram::bind(&path).unwrap();
+        let sink = SyslogSink::connect_to(&path, SyslogTarget::Syslog, Facility::Daemon).unwrap();
+        let log_message = log_message(&[("message", "hello")]);
+
+        drop(daemon);
+        std::fs::remove_file(&path).unwrap();
+        assert!(sink.send(&log_message).is_err());
+
+        let daemon = UnixDatagram::bind(&path).unwrap();
+        sink.send(&log_message).unwrap();
+        let mut buffer = [0u8; 4096];
+        assert!(daemon.recv(&mut buffer).unwrap() > 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sink_should_drop_messages_when_the_daemon_is_busy() {
+        let path =
+            std::env::temp_dir().join(format!("casperlabs-syslog-busy-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let _daemon = UnixDatagram::bind(&path).unwrap();
+        let sink = SyslogSink::connect_to(&path, SyslogTarget::Syslog, Facility::Daemon).unwrap();
+        let log_message = log_message(&[("message", "hello")]);
+
+        // Nobody reads, so the socket buffer fills up; sending must neither block nor fail.
+        for _ in 0..10_000 {
+            sink.send(&log_message).unwrap();
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+//! Motes, the smallest unit of the token which pays for execution.
+use std::fmt;
+
+use common::value::U512;
+
+use crate::gas::Gas;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Motes(U512);
+
+impl Motes {
+    pub fn new(value: U512) -> Self {
+        Motes(value)
+    }
+
+    pub fn value(self) -> U512 {
+        self.0
+    }
+
+    /// The price of `gas` at `conversion_rate`. Can't overflow, as both are at most 64 bits.
+    pub fn from_gas(gas: Gas, conversion_rate: ConversionRate) -> Self {
+        Motes(U512::from(gas.value()) * U512::from(conversion_rate.value()))
+    }
+
+    pub fn checked_add(self, rhs: Motes) -> Option<Motes> {
+        self.0.checked_add(rhs.0).map(Motes)
+    }
+
+    pub fn checked_sub(self, rhs: Motes) -> Option<Motes> {
+        self.0.checked_sub(rhs.0).map(Motes)
+    }
+}
+
+impl fmt::Display for Motes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} motes", self.0)
+    }
+}
+
+impl From<U512> for Motes {
+    fn from(value: U512) -> Self {
+        Motes(value)
+    }
+}
+
+/// The number of motes paid for one unit of gas, i.e. the gas price of a deploy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConversionRate(u64);
+
+impl ConversionRate {
+    pub const fn new(motes_per_gas: u64) -> Self {
+        ConversionRate(motes_per_gas)
+    }
+
+    pub fn value(self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::value::U512;
+
+    use super::{ConversionRate, Motes};
+    use crate::gas::Gas;
+
+    #[test]
+    fn should_price_gas() {
+        let gas = Gas::new(u64::max_value());
+        let rate = ConversionRate::new(u64::max_value());
+        let motes = Motes::from_gas(gas, rate);
+        assert_eq!(
+            motes.value(),
+            U512::from(u64::max_value()) * U512::from(u64::max_value())
+        );
+        assert_eq!(Gas::from_motes(motes, rate), Some(gas));
+    }
+
+    #[test]
+    fn should_check_arithmetic() {
+        let one = Motes::new(U512::one());
+        assert_eq!(Motes::new(U512::max_value()).checked_add(one), None);
+        assert_eq!(Motes::default().checked_sub(one), None);
+        assert_eq!(one.checked_add(one), Some(Motes::new(U512::from(2))));
+    }
+}
@@ -3,14 +3,19 @@ use core::array::TryFromSliceError;
 use std::convert::TryFrom;
 use std::fmt;
 use std::ops::Deref;
+use std::str::FromStr;
 
+#[cfg(not(feature = "simd"))]
 use blake2::digest::{Input, VariableOutput};
+#[cfg(not(feature = "simd"))]
 use blake2::VarBlake2b;
-use serde::Serialize;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use uuid::Uuid;
 
 use common::bytesrepr::{self, FromBytes, ToBytes};
 
+use crate::utils::{self, HexError};
+
 const BLAKE2B_DIGEST_LENGTH: usize = 32;
 
 /// Represents a 32-byte BLAKE2b hash digest
@@ -19,6 +24,7 @@ pub struct Blake2bHash([u8; BLAKE2B_DIGEST_LENGTH]);
 
 impl Blake2bHash {
     /// Creates a 32-byte BLAKE2b hash digest from a given a piece of data
+    #[cfg(not(feature = "simd"))]
     pub fn new(data: &[u8]) -> Self {
         let mut ret = [0u8; BLAKE2B_DIGEST_LENGTH];
         // Safe to unwrap here because our digest length is constant and valid
@@ -28,12 +34,60 @@ impl Blake2bHash {
         Blake2bHash(ret)
     }
 
+    /// Creates a 32-byte BLAKE2b hash digest from a given a piece of data
+    ///
+    /// Uses the SIMD implementation selected at runtime for the current CPU
+    /// (AVX2 where available), falling back to the portable one.
+    #[cfg(feature = "simd")]
+    pub fn new(data: &[u8]) -> Self {
+        let mut ret = [0u8; BLAKE2B_DIGEST_LENGTH];
+        let hash = blake2b_simd::Params::new()
+            .hash_length(BLAKE2B_DIGEST_LENGTH)
+            .hash(data);
+        ret.copy_from_slice(hash.as_bytes());
+        Blake2bHash(ret)
+    }
+
+    /// Creates a hash from a digest, which has to be 32 bytes long
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Blake2bHashError> {
+        <[u8; BLAKE2B_DIGEST_LENGTH]>::try_from(bytes)
+            .map(Blake2bHash)
+            .map_err(|_| Blake2bHashError::InvalidLength {
+                expected: BLAKE2B_DIGEST_LENGTH,
+                actual: bytes.len(),
+            })
+    }
+
     /// Converts the underlying BLAKE2b hash digest array to a `Vec`
     pub fn to_vec(&self) -> Vec<u8> {
         self.0.to_vec()
     }
 }
 
+/// Error creating a `Blake2bHash` from bytes or from a hex string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Blake2bHashError {
+    /// The digest isn't 32 bytes long
+    InvalidLength { expected: usize, actual: usize },
+    /// The string isn't an even number of hex digits
+    InvalidHex(String),
+}
+
+impl fmt::Display for Blake2bHashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Blake2bHashError::InvalidLength { expected, actual } => write!(
+                f,
+                "hash has to be {} bytes long, got {} bytes",
+                expected, actual
+            ),
+            Blake2bHashError::InvalidHex(input) => write!(f, "{:?} is not a hex string", input),
+        }
+    }
+}
+
+impl std::error::Error for Blake2bHashError {}
+
 impl core::fmt::LowerHex for Blake2bHash {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         let hex_string = base16::encode_lower(&self.to_vec());
@@ -66,6 +120,45 @@ impl From<[u8; BLAKE2B_DIGEST_LENGTH]> for Blake2bHash {
     }
 }
 
+/// Parses the lower or upper case hex encoding of the digest, as printed by `{:x}`
+impl FromStr for Blake2bHash {
+    type Err = Blake2bHashError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        utils::decode_hex_32(input)
+            .map(Blake2bHash)
+            .map_err(|error| match error {
+                HexError::InvalidHex => Blake2bHashError::InvalidHex(input.to_string()),
+                HexError::InvalidLength(actual) => Blake2bHashError::InvalidLength {
+                    expected: BLAKE2B_DIGEST_LENGTH,
+                    actual,
+                },
+            })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Blake2bHash {
+    type Error = Blake2bHashError;
+
+    fn try_from(input: &str) -> Result<Blake2bHash, Self::Error> {
+        input.parse()
+    }
+}
+
+/// Serialized as a lower case hex string
+impl Serialize for Blake2bHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:x}", self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Blake2bHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        hex.parse().map_err(de::Error::custom)
+    }
+}
+
 impl<'a> TryFrom<&'a [u8]> for Blake2bHash {
     type Error = TryFromSliceError;
 
@@ -149,8 +242,9 @@ impl fmt::Display for CorrelationId {
 
 #[cfg(test)]
 mod tests {
-    use crate::newtypes::{Blake2bHash, CorrelationId};
+    use crate::newtypes::{Blake2bHash, Blake2bHashError, CorrelationId};
     use crate::utils;
+    use std::convert::TryFrom;
     use std::hash::{Hash, Hasher};
 
     #[test]
@@ -261,6 +355,20 @@ mod tests {
         assert!(hash > 0, "should be hashable");
     }
 
+    #[test]
+    fn should_hash_known_vectors() {
+        let empty = Blake2bHash::new(&[]);
+        assert_eq!(
+            format!("{:x}", empty),
+            "0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8"
+        );
+        let abc = Blake2bHash::new(b"abc");
+        assert_eq!(
+            format!("{:x}", abc),
+            "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319"
+        );
+    }
+
     #[test]
     fn should_display_blake2bhash_in_hex() {
         let hash = Blake2bHash([0u8; 32]);
@@ -290,4 +398,53 @@ mod tests {
             "0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A0A"
         )
     }
+
+    #[test]
+    fn should_parse_blake2bhash_hex() {
+        let hash = Blake2bHash::new(b"abc");
+        assert_eq!(format!("{:x}", hash).parse(), Ok(hash));
+        assert_eq!(
+            Blake2bHash::try_from(format!("{:X}", hash).as_str()),
+            Ok(hash)
+        );
+        assert_eq!(
+            "0a0a".parse::<Blake2bHash>(),
+            Err(Blake2bHashError::InvalidLength {
+                expected: 32,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            "0g".parse::<Blake2bHash>(),
+            Err(Blake2bHashError::InvalidHex("0g".to_string()))
+        );
+        assert!("0a0".parse::<Blake2bHash>().is_err());
+    }
+
+    #[test]
+    fn should_check_blake2bhash_length() {
+        assert_eq!(
+            Blake2bHash::from_slice(&[1u8; 32]),
+            Ok(Blake2bHash([1u8; 32]))
+        );
+        assert_eq!(
+            Blake2bHash::from_slice(&[1u8; 33]),
+            Err(Blake2bHashError::InvalidLength {
+                expected: 32,
+                actual: 33
+            })
+        );
+    }
+
+    #[test]
+    fn should_serialize_blake2bhash_as_hex() {
+        let hash = Blake2bHash([10u8; 32]);
+        let json = utils::jsonify(hash, false);
+        assert_eq!(
+            json,
+            "\"0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a\""
+        );
+        assert_eq!(serde_json::from_str::<Blake2bHash>(&json).unwrap(), hash);
+        assert!(serde_json::from_str::<Blake2bHash>("\"0a\"").is_err());
+    }
 }
@@ -1,8 +1,14 @@
 use std::io;
 
+#[cfg(unix)]
 use libc::{c_long, sysconf, _SC_PAGESIZE};
 
+/// Page size assumed on platforms without `sysconf`.
+#[cfg(not(unix))]
+const DEFAULT_PAGE_SIZE: usize = 4096;
+
 /// Returns OS page size
+#[cfg(unix)]
 pub fn get_page_size() -> Result<usize, io::Error> {
     // https://www.gnu.org/software/libc/manual/html_node/Sysconf.html
     let value: c_long = unsafe { sysconf(_SC_PAGESIZE) };
@@ -13,3 +19,9 @@ pub fn get_page_size() -> Result<usize, io::Error> {
 
     Ok(value as usize)
 }
+
+/// Returns OS page size
+#[cfg(not(unix))]
+pub fn get_page_size() -> Result<usize, io::Error> {
+    Ok(DEFAULT_PAGE_SIZE)
+}
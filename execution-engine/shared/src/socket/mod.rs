@@ -1,5 +1,6 @@
 use std::io;
-use std::path::Path;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 
 pub struct Socket(String);
 
@@ -20,16 +21,120 @@ impl Socket {
         std::path::Path::new(&self.0)
     }
 
+    /// Returns the TCP address to listen on if the socket was given as `host:port`
+    /// rather than as a path to a Unix domain socket.
+    pub fn tcp_addr(&self) -> Option<SocketAddr> {
+        self.0.parse().ok()
+    }
+
     /// Safely removes file pointed out by a path.
     ///
     /// In practice this file tries to remove file, and if
     /// the file does not exist, it ignores it, and propagates
-    /// any other error.
+    /// any other error. TCP addresses have no file and are ignored.
     pub fn remove_file(&self) -> io::Result<()> {
+        if self.tcp_addr().is_some() {
+            return Ok(());
+        }
         let path = self.get_path();
         match std::fs::remove_file(path) {
             Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
             result => result,
         }
     }
+
+    /// Path of the file holding the id of the process listening on the socket.
+    pub fn pidfile_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.pid", self.0))
+    }
+
+    /// Whether some process accepts connections on the socket file.
+    #[cfg(unix)]
+    pub fn is_in_use(&self) -> bool {
+        self.tcp_addr().is_none()
+            && std::os::unix::net::UnixStream::connect(self.get_path()).is_ok()
+    }
+
+    #[cfg(not(unix))]
+    pub fn is_in_use(&self) -> bool {
+        false
+    }
+
+    /// Prepares the socket file to be listened on by this process: removes a stale socket
+    /// file left behind by an engine which is gone, and records the id of this process in
+    /// the pidfile. Fails with `AddrInUse` rather than taking the socket over from an engine
+    /// which is still running. TCP addresses are left to the bind itself.
+    pub fn claim(&self) -> io::Result<()> {
+        if self.tcp_addr().is_some() {
+            return Ok(());
+        }
+        if self.is_in_use() {
+            let owner = std::fs::read_to_string(self.pidfile_path())
+                .map(|pid| format!(" by process {}", pid.trim()))
+                .unwrap_or_default();
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("socket {} is in use{}", self.0, owner),
+            ));
+        }
+        self.remove_file()?;
+        std::fs::write(self.pidfile_path(), std::process::id().to_string())
+    }
+
+    /// Removes the socket file and the pidfile on shutdown.
+    pub fn release(&self) -> io::Result<()> {
+        if self.tcp_addr().is_some() {
+            return Ok(());
+        }
+        self.remove_file()?;
+        match std::fs::remove_file(self.pidfile_path()) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Socket;
+
+    #[test]
+    fn should_recognize_tcp_addresses() {
+        assert!(Socket::new("127.0.0.1:40404".to_string())
+            .tcp_addr()
+            .is_some());
+        assert!(Socket::new("[::1]:40404".to_string()).tcp_addr().is_some());
+        assert!(Socket::new("./.casper-node.sock".to_string())
+            .tcp_addr()
+            .is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_claim_only_sockets_nobody_listens_on() {
+        use std::os::unix::net::UnixListener;
+
+        let path = std::env::temp_dir().join(format!("casperlabs-socket-{}", std::process::id()));
+        let socket = Socket::new(path.to_str().unwrap().to_string());
+        socket.release().unwrap();
+
+        let listener = UnixListener::bind(&path).unwrap();
+        assert_eq!(
+            socket.claim().unwrap_err().kind(),
+            std::io::ErrorKind::AddrInUse
+        );
+        assert!(path.exists());
+
+        // The socket file outlives the listener, but nobody owns it anymore.
+        drop(listener);
+        socket.claim().unwrap();
+        assert!(!path.exists());
+        assert_eq!(
+            std::fs::read_to_string(socket.pidfile_path()).unwrap(),
+            std::process::id().to_string()
+        );
+
+        socket.release().unwrap();
+        assert!(!socket.pidfile_path().exists());
+    }
 }
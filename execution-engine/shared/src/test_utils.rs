@@ -1,8 +1,5 @@
 //! Some functions to use in tests.
 
-use parity_wasm::builder::ModuleBuilder;
-use parity_wasm::elements::{MemorySection, MemoryType, Module, Section, Serialize};
-
 use common::bytesrepr::{deserialize, FromBytes, ToBytes};
 
 /// Returns `true` if a we can serialize and then deserialize a value
@@ -20,12 +17,16 @@ where
     }
 }
 
-/// Returns the serialized form of an empty Wasm Module
+/// Returns the serialized form of a Wasm module with a memory and an empty `call`
+/// function, i.e. the smallest module the preprocessor accepts.
 pub fn create_empty_wasm_module_bytes() -> Vec<u8> {
-    let mem_section = MemorySection::with_entries(vec![MemoryType::new(16, Some(64))]);
-    let section = Section::Memory(mem_section);
-    let parity_module: Module = ModuleBuilder::new().with_section(section).build();
-    let mut wasm_bytes = vec![];
-    parity_module.serialize(&mut wasm_bytes).unwrap();
-    wasm_bytes
+    // (module (memory 16 64) (func (export "call")))
+    vec![
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic number and version
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section: () -> ()
+        0x03, 0x02, 0x01, 0x00, // function section: a function of type 0
+        0x05, 0x04, 0x01, 0x01, 0x10, 0x40, // memory section: 16 to 64 pages
+        0x07, 0x08, 0x01, 0x04, 0x63, 0x61, 0x6c, 0x6c, 0x00, 0x00, // export section: "call"
+        0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section: empty body
+    ]
 }
@@ -1,8 +1,9 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryFrom;
 use std::fmt;
 use std::ops::Add;
 
+use common::bytesrepr::{self, FromBytes, ToBytes};
 use common::key::Key;
 use common::value::{Value, U128, U256, U512};
 use num::traits::{ToPrimitive, WrappingAdd, WrappingSub};
@@ -46,6 +47,8 @@ pub enum Transform {
     AddUInt256(U256),
     AddUInt512(U512),
     AddKeys(BTreeMap<String, Key>),
+    /// Removes the named keys of an account or contract; names which are not present are ignored.
+    RemoveKeys(BTreeSet<String>),
     Failure(Error),
 }
 
@@ -79,6 +82,7 @@ from_try_from_impl!(U128, AddUInt128);
 from_try_from_impl!(U256, AddUInt256);
 from_try_from_impl!(U512, AddUInt512);
 from_try_from_impl!(BTreeMap<String, Key>, AddKeys);
+from_try_from_impl!(BTreeSet<String>, RemoveKeys);
 from_try_from_impl!(Error, Failure);
 
 /// Attempts to add `j` to `i`
@@ -180,11 +184,35 @@ impl Transform {
                     .into())
                 }
             },
+            RemoveKeys(names) => match v {
+                Value::Contract(mut c) => {
+                    remove_named_keys(c.get_urefs_lookup_mut(), &names);
+                    Ok(c.into())
+                }
+                Value::Account(mut a) => {
+                    remove_named_keys(a.get_urefs_lookup_mut(), &names);
+                    Ok(Value::Account(a))
+                }
+                other => {
+                    let expected = String::from("Contract or Account");
+                    Err(TypeMismatch {
+                        expected,
+                        found: other.type_string(),
+                    }
+                    .into())
+                }
+            },
             Failure(error) => Err(error),
         }
     }
 }
 
+fn remove_named_keys(named_keys: &mut BTreeMap<String, Key>, names: &BTreeSet<String>) {
+    for name in names {
+        named_keys.remove(name);
+    }
+}
+
 /// Combines numeric `Transform`s into a single `Transform`. This is
 /// done by unwrapping the `Transform` to obtain the underlying value,
 /// performing the wrapping addition then wrapping up as a `Transform`
@@ -278,6 +306,21 @@ impl Add for Transform {
                     .into(),
                 ),
             },
+            // Adding and removing named keys can't be expressed by a single transform, so mixing
+            // them fails like any other incompatible pair.
+            (RemoveKeys(mut names1), b) => match b {
+                RemoveKeys(mut names2) => {
+                    names1.append(&mut names2);
+                    RemoveKeys(names1)
+                }
+                other => Failure(
+                    TypeMismatch {
+                        expected: "RemoveKeys".to_owned(),
+                        found: format!("{:?}", other),
+                    }
+                    .into(),
+                ),
+            },
         }
     }
 }
@@ -288,10 +331,99 @@ impl fmt::Display for Transform {
     }
 }
 
+const IDENTITY_TAG: u8 = 0;
+const WRITE_TAG: u8 = 1;
+const ADD_INT32_TAG: u8 = 2;
+const ADD_UINT64_TAG: u8 = 3;
+const ADD_UINT128_TAG: u8 = 4;
+const ADD_UINT256_TAG: u8 = 5;
+const ADD_UINT512_TAG: u8 = 6;
+const ADD_KEYS_TAG: u8 = 7;
+const REMOVE_KEYS_TAG: u8 = 8;
+const FAILURE_TAG: u8 = 9;
+
+/// Lets effects be hashed in a canonical form, see `ExecutionEffect::checksum`, and stored
+/// before they're committed by the write-ahead log of the storage.
+impl ToBytes for Transform {
+    fn to_bytes(&self) -> Result<Vec<u8>, bytesrepr::Error> {
+        let (tag, mut payload) = match self {
+            Transform::Identity => (IDENTITY_TAG, Vec::new()),
+            Transform::Write(value) => (WRITE_TAG, value.to_bytes()?),
+            Transform::AddInt32(i) => (ADD_INT32_TAG, i.to_bytes()?),
+            Transform::AddUInt64(u) => (ADD_UINT64_TAG, u.to_bytes()?),
+            Transform::AddUInt128(u) => (ADD_UINT128_TAG, u.to_bytes()?),
+            Transform::AddUInt256(u) => (ADD_UINT256_TAG, u.to_bytes()?),
+            Transform::AddUInt512(u) => (ADD_UINT512_TAG, u.to_bytes()?),
+            Transform::AddKeys(keys) => (ADD_KEYS_TAG, keys.to_bytes()?),
+            Transform::RemoveKeys(names) => {
+                let names: Vec<String> = names.iter().cloned().collect();
+                (REMOVE_KEYS_TAG, names.to_bytes()?)
+            }
+            Transform::Failure(Error::TypeMismatch(TypeMismatch { expected, found })) => {
+                let mut payload = expected.to_bytes()?;
+                payload.append(&mut found.to_bytes()?);
+                (FAILURE_TAG, payload)
+            }
+        };
+        let mut result = Vec::with_capacity(1 + payload.len());
+        result.push(tag);
+        result.append(&mut payload);
+        Ok(result)
+    }
+}
+
+impl FromBytes for Transform {
+    fn from_bytes(bytes: &[u8]) -> Result<(Self, &[u8]), bytesrepr::Error> {
+        let (tag, rest): (u8, &[u8]) = FromBytes::from_bytes(bytes)?;
+        match tag {
+            IDENTITY_TAG => Ok((Transform::Identity, rest)),
+            WRITE_TAG => {
+                let (value, rem) = Value::from_bytes(rest)?;
+                Ok((Transform::Write(value), rem))
+            }
+            ADD_INT32_TAG => {
+                let (i, rem) = i32::from_bytes(rest)?;
+                Ok((Transform::AddInt32(i), rem))
+            }
+            ADD_UINT64_TAG => {
+                let (u, rem) = u64::from_bytes(rest)?;
+                Ok((Transform::AddUInt64(u), rem))
+            }
+            ADD_UINT128_TAG => {
+                let (u, rem) = U128::from_bytes(rest)?;
+                Ok((Transform::AddUInt128(u), rem))
+            }
+            ADD_UINT256_TAG => {
+                let (u, rem) = U256::from_bytes(rest)?;
+                Ok((Transform::AddUInt256(u), rem))
+            }
+            ADD_UINT512_TAG => {
+                let (u, rem) = U512::from_bytes(rest)?;
+                Ok((Transform::AddUInt512(u), rem))
+            }
+            ADD_KEYS_TAG => {
+                let (keys, rem) = BTreeMap::<String, Key>::from_bytes(rest)?;
+                Ok((Transform::AddKeys(keys), rem))
+            }
+            REMOVE_KEYS_TAG => {
+                let (names, rem) = Vec::<String>::from_bytes(rest)?;
+                Ok((Transform::RemoveKeys(names.into_iter().collect()), rem))
+            }
+            FAILURE_TAG => {
+                let (expected, rem) = String::from_bytes(rest)?;
+                let (found, rem) = String::from_bytes(rem)?;
+                let type_mismatch = TypeMismatch::new(expected, found);
+                Ok((Transform::Failure(Error::TypeMismatch(type_mismatch)), rem))
+            }
+            _ => Err(bytesrepr::Error::FormattingError),
+        }
+    }
+}
+
 pub mod gens {
     use super::Transform;
     use common::gens::value_arb;
-    use proptest::collection::vec;
+    use proptest::collection::{btree_set, vec};
     use proptest::prelude::*;
 
     pub fn transform_arb() -> impl Strategy<Value = Transform> {
@@ -311,17 +443,25 @@ pub mod gens {
                 buf.copy_from_slice(&u);
                 Transform::AddUInt512(buf.into())
             }),
+            btree_set("[a-z_]{1,16}", 0..4).prop_map(Transform::RemoveKeys),
         ]
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
     use num::{Bounded, Num, ToPrimitive};
+    use proptest::collection::btree_set;
+    use proptest::prelude::*;
 
-    use common::value::{Value, U128, U256, U512};
+    use common::bytesrepr::{deserialize, ToBytes};
+    use common::key::Key;
+    use common::value::{Contract, Value, U128, U256, U512};
 
-    use super::Transform;
+    use super::gens::transform_arb;
+    use super::{Error, Transform};
 
     #[test]
     fn i32_overflow() {
@@ -406,4 +546,77 @@ mod tests {
             super::u64_wrapping_addition(base_u64, i32::max_value())
         )
     }
+
+    fn contract_with_named_keys(names: &[&str]) -> Value {
+        let named_keys: BTreeMap<String, Key> = names
+            .iter()
+            .map(|name| (name.to_string(), Key::Hash([1u8; 32])))
+            .collect();
+        Contract::new(Vec::new(), named_keys, 1).into()
+    }
+
+    fn names(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn remove_keys_ignores_missing_names() {
+        let contract = contract_with_named_keys(&["a", "b", "c"]);
+        let result = Transform::RemoveKeys(names(&["a", "c", "d"])).apply(contract);
+        assert_eq!(result, Ok(contract_with_named_keys(&["b"])));
+    }
+
+    #[test]
+    fn remove_keys_requires_account_or_contract() {
+        match Transform::RemoveKeys(names(&["a"])).apply(Value::Int32(1)) {
+            Err(Error::TypeMismatch(_)) => (),
+            other => panic!("expected a type mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn adding_and_removing_keys_do_not_combine() {
+        let add_keys = Transform::AddKeys(BTreeMap::new());
+        let remove_keys = Transform::RemoveKeys(names(&["a"]));
+        assert!(match add_keys.clone() + remove_keys.clone() {
+            Transform::Failure(_) => true,
+            _ => false,
+        });
+        assert!(match remove_keys + add_keys {
+            Transform::Failure(_) => true,
+            _ => false,
+        });
+    }
+
+    proptest! {
+        #[test]
+        fn should_serialize_transforms(transform in transform_arb()) {
+            let bytes = transform.to_bytes().unwrap();
+            prop_assert_eq!(deserialize::<Transform>(&bytes), Ok(transform));
+        }
+
+        #[test]
+        fn add_u64_commutes(a in any::<u64>(), b in any::<u64>(), v in any::<u64>()) {
+            let (a, b) = (Transform::AddUInt64(a), Transform::AddUInt64(b));
+            prop_assert_eq!(a.clone() + b.clone(), b.clone() + a.clone());
+
+            let sequential = b.clone().apply(a.clone().apply(Value::UInt64(v)).unwrap());
+            prop_assert_eq!((a + b).apply(Value::UInt64(v)), sequential);
+        }
+
+        #[test]
+        fn remove_keys_commutes(
+            existing in btree_set("[a-d]", 0..4),
+            first in btree_set("[a-d]", 0..4),
+            second in btree_set("[a-d]", 0..4),
+        ) {
+            let existing: Vec<&str> = existing.iter().map(String::as_str).collect();
+            let contract = contract_with_named_keys(&existing);
+            let (a, b) = (Transform::RemoveKeys(first), Transform::RemoveKeys(second));
+            prop_assert_eq!(a.clone() + b.clone(), b.clone() + a.clone());
+
+            let sequential = b.clone().apply(a.clone().apply(contract.clone()).unwrap());
+            prop_assert_eq!((a + b).apply(contract), sequential);
+        }
+    }
 }
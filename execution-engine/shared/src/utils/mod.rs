@@ -1,5 +1,22 @@
+use std::convert::TryFrom;
+
 use serde::Serialize;
 
+/// Error decoding 32 bytes from hex
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexError {
+    /// The string isn't an even number of hex digits
+    InvalidHex,
+    /// The string encodes this many bytes instead of 32
+    InvalidLength(usize),
+}
+
+/// Decodes the lower or upper case hex encoding of 32 bytes, e.g. a public key or a hash
+pub fn decode_hex_32(input: &str) -> Result<[u8; 32], HexError> {
+    let bytes = base16::decode(input).map_err(|_| HexError::InvalidHex)?;
+    <[u8; 32]>::try_from(bytes.as_slice()).map_err(|_| HexError::InvalidLength(bytes.len()))
+}
+
 /// serializes value to json;
 /// pretty_print: false = inline
 /// pretty_print: true  = pretty printed / multiline
@@ -76,4 +93,13 @@ mod tests {
             "instances should contain the same data"
         );
     }
+
+    #[test]
+    fn should_decode_hex_32() {
+        assert_eq!(decode_hex_32(&"0a".repeat(32)), Ok([10u8; 32]));
+        assert_eq!(decode_hex_32(&"0A".repeat(32)), Ok([10u8; 32]));
+        assert_eq!(decode_hex_32("0a0a"), Err(HexError::InvalidLength(2)));
+        assert_eq!(decode_hex_32(&"zz".repeat(32)), Err(HexError::InvalidHex));
+        assert_eq!(decode_hex_32("0a0"), Err(HexError::InvalidHex));
+    }
 }
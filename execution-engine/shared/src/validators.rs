@@ -0,0 +1,167 @@
+//! Guards for `newtypes::Validated`, checking keys and values before they're written to global
+//! state.
+use std::fmt;
+
+use common::bytesrepr::{self, ToBytes};
+use common::key::Key;
+use common::uref::URef;
+use common::value::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The serialized value takes `size` bytes, more than `limit`.
+    ValueTooLarge {
+        size: usize,
+        limit: usize,
+    },
+    /// The key refers to a `URef` whose access rights were all taken away.
+    MalformedKey(Key),
+    /// A `URef` stored in the value carries no access rights, so it can't be used.
+    MissingAccessRights(URef),
+    Serialization(bytesrepr::Error),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::ValueTooLarge { size, limit } => write!(
+                f,
+                "value takes {} bytes, more than the limit of {} bytes",
+                size, limit
+            ),
+            ValidationError::MalformedKey(key) => write!(f, "malformed key {:?}", key),
+            ValidationError::MissingAccessRights(uref) => {
+                write!(f, "stored {} has no access rights", uref)
+            }
+            ValidationError::Serialization(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<bytesrepr::Error> for ValidationError {
+    fn from(error: bytesrepr::Error) -> Self {
+        ValidationError::Serialization(error)
+    }
+}
+
+/// Accepts every key but `URef`s whose access rights are empty, e.g. after restricting them to
+/// rights they didn't have. `URef`s without any access rights are accepted, as that's how they
+/// are stored.
+pub fn well_formed_key(key: &Key) -> Result<(), ValidationError> {
+    match key {
+        Key::URef(uref)
+            if uref
+                .access_rights()
+                .map_or(false, |rights| rights.is_empty()) =>
+        {
+            Err(ValidationError::MalformedKey(*key))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Accepts values whose `URef`s, either as a key or among named keys, carry access rights.
+pub fn urefs_have_access_rights(value: &Value) -> Result<(), ValidationError> {
+    let check = |key: &Key| match key {
+        Key::URef(uref)
+            if uref
+                .access_rights()
+                .map_or(true, |rights| rights.is_empty()) =>
+        {
+            Err(ValidationError::MissingAccessRights(*uref))
+        }
+        _ => Ok(()),
+    };
+    match value {
+        Value::Key(key) | Value::NamedKey(_, key) => check(key),
+        Value::Account(account) => account.urefs_lookup().values().try_for_each(check),
+        Value::Contract(contract) => contract.urefs_lookup().values().try_for_each(check),
+        _ => Ok(()),
+    }
+}
+
+/// Accepts values taking at most `limit` bytes once serialized.
+pub fn max_value_size(limit: usize) -> impl Fn(&Value) -> Result<(), ValidationError> {
+    move |value| check_value_size(value.to_bytes()?.len(), limit)
+}
+
+/// Accepts a value serialized to `size` bytes if it takes at most `limit` bytes, for callers
+/// which already serialized it.
+pub fn check_value_size(size: usize, limit: usize) -> Result<(), ValidationError> {
+    if size > limit {
+        Err(ValidationError::ValueTooLarge { size, limit })
+    } else {
+        Ok(())
+    }
+}
+
+/// Every check a value has to pass to be stored, with values taking at most `max_size` bytes.
+pub fn storable_value(max_size: usize) -> impl Fn(&Value) -> Result<(), ValidationError> {
+    let check_size = max_value_size(max_size);
+    move |value| {
+        urefs_have_access_rights(value)?;
+        check_size(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::key::Key;
+    use common::uref::{AccessRights, URef};
+    use common::value::Value;
+
+    use super::{
+        max_value_size, storable_value, urefs_have_access_rights, well_formed_key, ValidationError,
+    };
+    use crate::newtypes::Validated;
+
+    const ADDR: [u8; 32] = [1u8; 32];
+
+    #[test]
+    fn should_reject_urefs_with_empty_access_rights() {
+        let uref = URef::new(ADDR, AccessRights::READ);
+        assert!(well_formed_key(&Key::URef(uref)).is_ok());
+        assert!(well_formed_key(&Key::URef(uref.remove_access_rights())).is_ok());
+        assert!(well_formed_key(&Key::Hash(ADDR)).is_ok());
+
+        let restricted = Key::URef(uref.restrict(AccessRights::WRITE));
+        assert_eq!(
+            well_formed_key(&restricted),
+            Err(ValidationError::MalformedKey(restricted))
+        );
+    }
+
+    #[test]
+    fn should_reject_stored_urefs_without_access_rights() {
+        let uref = URef::new(ADDR, AccessRights::READ_WRITE);
+        assert!(urefs_have_access_rights(&Value::Key(Key::URef(uref))).is_ok());
+        assert!(urefs_have_access_rights(&Value::Key(Key::Account(ADDR))).is_ok());
+
+        let stripped = uref.remove_access_rights();
+        assert_eq!(
+            urefs_have_access_rights(&Value::NamedKey("a".to_string(), Key::URef(stripped))),
+            Err(ValidationError::MissingAccessRights(stripped))
+        );
+    }
+
+    #[test]
+    fn should_reject_values_over_size_limit() {
+        let value = Value::ByteArray(vec![0u8; 100]);
+        // The length prefix takes 4 bytes, the type tag 1 byte.
+        assert!(max_value_size(105)(&value).is_ok());
+        assert_eq!(
+            max_value_size(104)(&value),
+            Err(ValidationError::ValueTooLarge {
+                size: 105,
+                limit: 104
+            })
+        );
+    }
+
+    #[test]
+    fn should_guard_validated_values() {
+        let value = Value::String("abc".to_string());
+        assert!(Validated::new(value.clone(), storable_value(1024)).is_ok());
+        assert!(Validated::new(value, storable_value(1)).is_err());
+    }
+}
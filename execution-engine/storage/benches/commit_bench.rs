@@ -0,0 +1,44 @@
+#![feature(test)]
+extern crate common;
+extern crate shared;
+extern crate storage;
+extern crate test;
+
+use std::collections::HashMap;
+
+use test::Bencher;
+
+use common::key::Key;
+use common::value::Value;
+use shared::newtypes::CorrelationId;
+use shared::transform::Transform;
+use storage::global_state::in_memory::InMemoryGlobalState;
+use storage::global_state::History;
+
+// Hashing dominates commits, so run with and without `--features simd` to see
+// the effect of the SIMD BLAKE2b backend.
+
+const KEY_COUNT: u8 = 200;
+
+#[bench]
+fn commit_writes_to_fresh_keys(b: &mut Bencher) {
+    let correlation_id = CorrelationId::new();
+    let effects: HashMap<Key, Transform> = (0..KEY_COUNT)
+        .map(|i| {
+            let mut addr = [0u8; 32];
+            addr[0] = i;
+            addr[31] = i.wrapping_mul(31);
+            (
+                Key::Hash(addr),
+                Transform::Write(Value::Int32(i32::from(i))),
+            )
+        })
+        .collect();
+    b.iter(|| {
+        let mut state = InMemoryGlobalState::empty().unwrap();
+        let root_hash = state.current_root();
+        state
+            .commit(correlation_id, root_hash, effects.clone())
+            .unwrap()
+    });
+}
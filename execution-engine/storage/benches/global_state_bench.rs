@@ -0,0 +1,62 @@
+#![feature(test)]
+extern crate common;
+extern crate shared;
+extern crate storage;
+extern crate test;
+
+use test::black_box;
+use test::Bencher;
+
+use common::key::Key;
+use common::value::Value;
+use shared::newtypes::CorrelationId;
+use storage::global_state::in_memory::InMemoryGlobalState;
+use storage::global_state::{History, StateReader};
+
+const KEY_COUNT: u8 = 200;
+
+fn key(i: u8) -> Key {
+    let mut addr = [0u8; 32];
+    addr[0] = i;
+    addr[31] = i.wrapping_mul(31);
+    Key::Hash(addr)
+}
+
+fn pairs() -> Vec<(Key, Value)> {
+    (0..KEY_COUNT)
+        .map(|i| (key(i), Value::Int32(i32::from(i))))
+        .collect()
+}
+
+#[bench]
+fn insert_keys_one_at_a_time(b: &mut Bencher) {
+    let correlation_id = CorrelationId::new();
+    let pairs = pairs();
+    b.iter(|| InMemoryGlobalState::from_pairs(correlation_id, black_box(&pairs)).unwrap());
+}
+
+#[bench]
+fn lookup_existing_key(b: &mut Bencher) {
+    let correlation_id = CorrelationId::new();
+    let state = InMemoryGlobalState::from_pairs(correlation_id, &pairs()).unwrap();
+    let reader = state.checkout(state.current_root()).unwrap().unwrap();
+    let existing_key = key(KEY_COUNT / 2);
+    b.iter(|| {
+        reader
+            .read(correlation_id, black_box(&existing_key))
+            .unwrap()
+    });
+}
+
+#[bench]
+fn lookup_missing_key(b: &mut Bencher) {
+    let correlation_id = CorrelationId::new();
+    let state = InMemoryGlobalState::from_pairs(correlation_id, &pairs()).unwrap();
+    let reader = state.checkout(state.current_root()).unwrap().unwrap();
+    let missing_key = Key::Hash([0xffu8; 32]);
+    b.iter(|| {
+        reader
+            .read(correlation_id, black_box(&missing_key))
+            .unwrap()
+    });
+}
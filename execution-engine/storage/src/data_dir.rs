@@ -0,0 +1,248 @@
+//! Versioning of the on-disk layout of the global state.
+//!
+//! The data directory is stamped with the version of its format in a `VERSION` file. On startup
+//! the layouts written by older engines are upgraded by running the registered migrations in
+//! order, and directories written by a newer engine, or by one too old to be migrated, are
+//! refused instead of being misread.
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Version of the layout written by this engine.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Oldest version which can be brought to [`FORMAT_VERSION`].
+///
+/// Global states written before the format was versioned store accounts without recovery keys
+/// and contracts without access groups. Their trie nodes are hashed over that encoding, so
+/// rewriting the values would change every state hash the network agreed on: such directories
+/// have to be re-synced instead.
+pub const OLDEST_SUPPORTED_VERSION: u32 = 1;
+
+pub const VERSION_FILE: &str = "VERSION";
+
+const LMDB_DATA_FILE: &str = "data.mdb";
+
+/// Upgrades a data directory from `from_version` to `from_version + 1`.
+pub struct Migration {
+    pub from_version: u32,
+    pub description: &'static str,
+    pub run: fn(&Path) -> Result<(), DataDirError>,
+}
+
+/// Migrations of the layouts written by previous engines, in order.
+pub static MIGRATIONS: &[Migration] = &[];
+
+#[derive(Debug, Fail)]
+pub enum DataDirError {
+    #[fail(display = "{}", _0)]
+    Io(#[fail(cause)] io::Error),
+
+    #[fail(display = "Invalid format version: {:?}", _0)]
+    InvalidVersion(String),
+
+    #[fail(
+        display = "Data directory has format version {}, newer than the supported version {}",
+        found, supported
+    )]
+    UnsupportedVersion { found: u32, supported: u32 },
+
+    #[fail(
+        display = "Data directory has format version {}, older than the oldest supported version \
+                   {}; move it away and re-sync the node into an empty data directory",
+        found, oldest
+    )]
+    ObsoleteVersion { found: u32, oldest: u32 },
+
+    #[fail(display = "No migration from format version {}", _0)]
+    MissingMigration(u32),
+
+    #[fail(
+        display = "Migration from format version {} failed: {}",
+        from_version, reason
+    )]
+    MigrationFailed { from_version: u32, reason: String },
+}
+
+impl From<io::Error> for DataDirError {
+    fn from(error: io::Error) -> Self {
+        DataDirError::Io(error)
+    }
+}
+
+/// Reads the format version of `dir`. Directories without a database are reported as `None`,
+/// databases written before the format was versioned as version 0.
+pub fn read_version(dir: &Path) -> Result<Option<u32>, DataDirError> {
+    match fs::read_to_string(dir.join(VERSION_FILE)) {
+        Ok(contents) => {
+            let version = contents
+                .trim()
+                .parse()
+                .map_err(|_| DataDirError::InvalidVersion(contents.clone()))?;
+            Ok(Some(version))
+        }
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => {
+            if dir.join(LMDB_DATA_FILE).exists() {
+                Ok(Some(0))
+            } else {
+                Ok(None)
+            }
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Writes the version file through a temporary file, so an interrupted write doesn't leave a
+/// truncated version behind.
+pub fn write_version(dir: &Path, version: u32) -> Result<(), DataDirError> {
+    let tmp_path = dir.join(format!("{}.tmp", VERSION_FILE));
+    fs::write(&tmp_path, version.to_string())?;
+    fs::rename(&tmp_path, dir.join(VERSION_FILE))?;
+    Ok(())
+}
+
+/// Brings `dir` to [`FORMAT_VERSION`], returning the migrations that were run.
+pub fn upgrade(dir: &Path) -> Result<Vec<&'static Migration>, DataDirError> {
+    upgrade_with(dir, OLDEST_SUPPORTED_VERSION, FORMAT_VERSION, MIGRATIONS)
+}
+
+/// Brings `dir` from `oldest_version` or later to `target_version` by running `migrations`. The
+/// version is stamped after each migration, so an upgrade interrupted by a failure resumes from
+/// the last completed one.
+pub fn upgrade_with<'a>(
+    dir: &Path,
+    oldest_version: u32,
+    target_version: u32,
+    migrations: &'a [Migration],
+) -> Result<Vec<&'a Migration>, DataDirError> {
+    let mut version = match read_version(dir)? {
+        None => {
+            write_version(dir, target_version)?;
+            return Ok(Vec::new());
+        }
+        Some(version) if version > target_version => {
+            return Err(DataDirError::UnsupportedVersion {
+                found: version,
+                supported: target_version,
+            })
+        }
+        Some(version) if version < oldest_version => {
+            return Err(DataDirError::ObsoleteVersion {
+                found: version,
+                oldest: oldest_version,
+            })
+        }
+        Some(version) => version,
+    };
+    let mut applied = Vec::new();
+    while version < target_version {
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.from_version == version)
+            .ok_or(DataDirError::MissingMigration(version))?;
+        (migration.run)(dir).map_err(|error| DataDirError::MigrationFailed {
+            from_version: version,
+            reason: error.to_string(),
+        })?;
+        version += 1;
+        write_version(dir, version)?;
+        applied.push(migration);
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use super::{read_version, upgrade, upgrade_with, write_version, DataDirError, Migration};
+    use super::{FORMAT_VERSION, LMDB_DATA_FILE, OLDEST_SUPPORTED_VERSION};
+
+    fn fail(_dir: &Path) -> Result<(), DataDirError> {
+        Err(io::Error::new(io::ErrorKind::Other, "boom").into())
+    }
+
+    fn touch(dir: &Path) -> Result<(), DataDirError> {
+        fs::write(dir.join("migrated"), b"")?;
+        Ok(())
+    }
+
+    #[test]
+    fn should_stamp_new_data_dir() {
+        let dir = tempdir().unwrap();
+        assert!(upgrade(dir.path()).unwrap().is_empty());
+        assert_eq!(read_version(dir.path()).unwrap(), Some(FORMAT_VERSION));
+    }
+
+    #[test]
+    fn should_refuse_unversioned_database() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join(LMDB_DATA_FILE), b"").unwrap();
+        assert_eq!(read_version(dir.path()).unwrap(), Some(0));
+
+        match upgrade(dir.path()) {
+            Err(DataDirError::ObsoleteVersion { found, oldest }) => {
+                assert_eq!(found, 0);
+                assert_eq!(oldest, OLDEST_SUPPORTED_VERSION);
+            }
+            other => panic!("expected ObsoleteVersion, got {:?}", other.map(|m| m.len())),
+        }
+        // The directory is left as it was.
+        assert_eq!(read_version(dir.path()).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn should_refuse_newer_versions() {
+        let dir = tempdir().unwrap();
+        write_version(dir.path(), FORMAT_VERSION + 1).unwrap();
+        match upgrade(dir.path()) {
+            Err(DataDirError::UnsupportedVersion { found, supported }) => {
+                assert_eq!(found, FORMAT_VERSION + 1);
+                assert_eq!(supported, FORMAT_VERSION);
+            }
+            other => panic!(
+                "expected UnsupportedVersion, got {:?}",
+                other.map(|m| m.len())
+            ),
+        }
+    }
+
+    #[test]
+    fn should_stop_at_failed_migration() {
+        let dir = tempdir().unwrap();
+        write_version(dir.path(), 1).unwrap();
+        let migrations = [
+            Migration {
+                from_version: 1,
+                description: "touch",
+                run: touch,
+            },
+            Migration {
+                from_version: 2,
+                description: "fail",
+                run: fail,
+            },
+        ];
+
+        match upgrade_with(dir.path(), 1, 3, &migrations) {
+            Err(DataDirError::MigrationFailed {
+                from_version: 2, ..
+            }) => (),
+            other => panic!("expected MigrationFailed, got {:?}", other.map(|m| m.len())),
+        }
+        assert!(dir.path().join("migrated").exists());
+        assert_eq!(read_version(dir.path()).unwrap(), Some(2));
+
+        match upgrade_with(dir.path(), 1, 4, &migrations[..1]) {
+            Err(DataDirError::MissingMigration(2)) => (),
+            other => panic!(
+                "expected MissingMigration, got {:?}",
+                other.map(|m| m.len())
+            ),
+        }
+    }
+}
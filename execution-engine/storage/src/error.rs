@@ -1,3 +1,5 @@
+use std::io;
+
 use lmdb;
 use wasmi;
 
@@ -15,6 +17,10 @@ pub enum Error {
 
     #[fail(display = "Another thread panicked while holding a lock")]
     PoisonError,
+
+    /// Reading or writing the write-ahead log failed; holds the description of the I/O error.
+    #[fail(display = "Write-ahead log: {}", _0)]
+    WriteAheadLog(String),
 }
 
 impl wasmi::HostError for Error {}
@@ -31,6 +37,12 @@ impl From<bytesrepr::Error> for Error {
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::WriteAheadLog(e.to_string())
+    }
+}
+
 impl<T> From<std::sync::PoisonError<T>> for Error {
     fn from(_e: std::sync::PoisonError<T>) -> Self {
         Error::PoisonError
@@ -5,8 +5,8 @@ use std::sync::Arc;
 use common::key::Key;
 use common::value::Value;
 use error;
-use global_state::StateReader;
-use global_state::{commit, CommitResult, History};
+use global_state::{batch_commit, commit, dry_run_commit, CommitResult, History, Snapshots};
+use global_state::{StateEntries, StateReader};
 use shared::newtypes::{Blake2bHash, CorrelationId};
 use shared::transform::Transform;
 use trie::operations::create_hashed_empty_trie;
@@ -14,7 +14,7 @@ use trie::Trie;
 use trie_store::in_memory::{
     self, InMemoryEnvironment, InMemoryReadTransaction, InMemoryTrieStore,
 };
-use trie_store::operations::{read, write, ReadResult, WriteResult};
+use trie_store::operations::{for_each_leaf, read, write, ReadResult, WriteResult};
 use trie_store::{Transaction, TransactionSource, TrieStore};
 
 /// Represents a "view" of global state at a particular root hash.
@@ -115,23 +115,72 @@ impl StateReader<Key, Value> for InMemoryGlobalState {
     }
 }
 
-impl History for InMemoryGlobalState {
+impl StateEntries<Key, Value> for InMemoryGlobalState {
+    fn for_each_entry(&self, visit: &mut dyn FnMut(Key, Value)) -> Result<(), Self::Error> {
+        let txn = self.environment.create_read_txn()?;
+        let found = for_each_leaf::<
+            Key,
+            Value,
+            InMemoryReadTransaction,
+            InMemoryTrieStore,
+            Self::Error,
+            _,
+        >(&txn, self.store.deref(), &self.root_hash, |key, value| {
+            visit(key, value)
+        })?;
+        if !found {
+            panic!("InMemoryGlobalState has invalid root");
+        }
+        txn.commit()?;
+        Ok(())
+    }
+}
+
+/// Checkouts of an [`InMemoryGlobalState`], see [`Snapshots`].
+#[derive(Clone)]
+pub struct InMemorySnapshots {
+    environment: Arc<InMemoryEnvironment>,
+    store: Arc<InMemoryTrieStore>,
+    empty_root_hash: Blake2bHash,
+}
+
+impl Snapshots for InMemorySnapshots {
     type Error = error::Error;
 
-    type Reader = Self;
+    type Reader = InMemoryGlobalState;
 
-    fn checkout(&self, prestate_hash: Blake2bHash) -> Result<Option<Self::Reader>, Self::Error> {
+    fn checkout(&self, root_hash: Blake2bHash) -> Result<Option<Self::Reader>, Self::Error> {
         let txn = self.environment.create_read_txn()?;
-        let maybe_root: Option<Trie<Key, Value>> = self.store.get(&txn, &prestate_hash)?;
+        let maybe_root: Option<Trie<Key, Value>> = self.store.get(&txn, &root_hash)?;
         let maybe_state = maybe_root.map(|_| InMemoryGlobalState {
             environment: Arc::clone(&self.environment),
             store: Arc::clone(&self.store),
-            root_hash: prestate_hash,
+            root_hash,
             empty_root_hash: self.empty_root_hash,
         });
         txn.commit()?;
         Ok(maybe_state)
     }
+}
+
+impl History for InMemoryGlobalState {
+    type Error = error::Error;
+
+    type Reader = Self;
+
+    type Snapshots = InMemorySnapshots;
+
+    fn checkout(&self, prestate_hash: Blake2bHash) -> Result<Option<Self::Reader>, Self::Error> {
+        self.snapshots().checkout(prestate_hash)
+    }
+
+    fn snapshots(&self) -> Self::Snapshots {
+        InMemorySnapshots {
+            environment: Arc::clone(&self.environment),
+            store: Arc::clone(&self.store),
+            empty_root_hash: self.empty_root_hash,
+        }
+    }
 
     fn commit(
         &mut self,
@@ -145,13 +194,67 @@ impl History for InMemoryGlobalState {
             correlation_id,
             prestate_hash,
             effects,
+            None,
         )?;
-        if let CommitResult::Success(root_hash) = commit_result {
+        if let CommitResult::Success(root_hash, _) = commit_result {
             self.root_hash = root_hash;
         };
         Ok(commit_result)
     }
 
+    fn commit_expecting(
+        &mut self,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        effects: HashMap<Key, Transform>,
+        expected_poststate_hash: Blake2bHash,
+    ) -> Result<CommitResult, Self::Error> {
+        let commit_result = commit::<InMemoryEnvironment, InMemoryTrieStore, _, Self::Error>(
+            &self.environment,
+            &self.store,
+            correlation_id,
+            prestate_hash,
+            effects,
+            Some(expected_poststate_hash),
+        )?;
+        if let CommitResult::Success(root_hash, _) = commit_result {
+            self.root_hash = root_hash;
+        };
+        Ok(commit_result)
+    }
+
+    fn batch_commit(
+        &mut self,
+        correlation_id: CorrelationId,
+        batch: Vec<(Blake2bHash, HashMap<Key, Transform>, Option<Blake2bHash>)>,
+    ) -> Result<Vec<CommitResult>, Self::Error> {
+        let commit_results = batch_commit::<InMemoryEnvironment, InMemoryTrieStore, _, Self::Error>(
+            &self.environment,
+            &self.store,
+            correlation_id,
+            batch,
+        )?;
+        if let Some(CommitResult::Success(root_hash, _)) = commit_results.last() {
+            self.root_hash = *root_hash;
+        };
+        Ok(commit_results)
+    }
+
+    fn dry_run_commit(
+        &self,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        effects: HashMap<Key, Transform>,
+    ) -> Result<CommitResult, Self::Error> {
+        dry_run_commit::<InMemoryEnvironment, InMemoryTrieStore, _, Self::Error>(
+            &self.environment,
+            &self.store,
+            correlation_id,
+            prestate_hash,
+            effects,
+        )
+    }
+
     fn current_root(&self) -> Blake2bHash {
         self.root_hash
     }
@@ -223,6 +326,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn snapshots_are_read_while_a_commit_is_writing() {
+        let correlation_id = CorrelationId::new();
+        let state = create_test_state();
+        let snapshots = state.snapshots();
+
+        let mut txn = state.environment.create_read_write_txn().unwrap();
+        let (hash, trie) = create_hashed_empty_trie::<Key, Value>().unwrap();
+        state.store.put(&mut txn, &hash, &trie).unwrap();
+
+        let checkout = snapshots.checkout(state.root_hash).unwrap().unwrap();
+        for TestPair { key, value } in TEST_PAIRS.iter().cloned() {
+            assert_eq!(Some(value), checkout.read(correlation_id, &key).unwrap());
+        }
+        txn.commit().unwrap();
+    }
+
     #[test]
     fn checkout_fails_if_unknown_hash_is_given() {
         let state = create_test_state();
@@ -247,7 +367,7 @@ mod tests {
             .collect();
 
         let updated_hash = match state.commit(correlation_id, root_hash, effects).unwrap() {
-            CommitResult::Success(hash) => hash,
+            CommitResult::Success(hash, _) => hash,
             _ => panic!("commit failed"),
         };
 
@@ -261,6 +381,91 @@ mod tests {
         }
     }
 
+    #[test]
+    fn commit_reports_created_and_updated_keys() {
+        let correlation_id = CorrelationId::new();
+
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+
+        let effects: HashMap<Key, Transform> = create_test_pairs_updated()
+            .iter()
+            .cloned()
+            .map(|TestPair { key, value }| (key, Transform::Write(value)))
+            .collect();
+
+        let stats = match state.commit(correlation_id, root_hash, effects).unwrap() {
+            CommitResult::Success(_, stats) => stats,
+            _ => panic!("commit failed"),
+        };
+
+        assert_eq!(stats.keys_created, 1);
+        assert_eq!(stats.keys_updated, 2);
+        assert_eq!(stats.keys_written(), 3);
+        // At least a new leaf per written key, plus the new root.
+        assert!(stats.trie_nodes_added >= 4);
+        assert!(stats.bytes_written > 0);
+    }
+
+    #[test]
+    fn dry_run_commit_stores_nothing() {
+        let correlation_id = CorrelationId::new();
+
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+
+        let effects: HashMap<Key, Transform> = create_test_pairs_updated()
+            .iter()
+            .cloned()
+            .map(|TestPair { key, value }| (key, Transform::Write(value)))
+            .collect();
+
+        let dry_run_hash = match state
+            .dry_run_commit(correlation_id, root_hash, effects.clone())
+            .unwrap()
+        {
+            CommitResult::Success(hash, _) => hash,
+            _ => panic!("dry run failed"),
+        };
+        assert!(state.checkout(dry_run_hash).unwrap().is_none());
+        assert_eq!(state.current_root(), root_hash);
+
+        match state.commit(correlation_id, root_hash, effects).unwrap() {
+            CommitResult::Success(hash, _) => assert_eq!(hash, dry_run_hash),
+            _ => panic!("commit failed"),
+        }
+    }
+
+    #[test]
+    fn commit_of_already_applied_effects_writes_nothing() {
+        let correlation_id = CorrelationId::new();
+
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+
+        let effects: HashMap<Key, Transform> = create_test_pairs_updated()
+            .iter()
+            .cloned()
+            .map(|TestPair { key, value }| (key, Transform::Write(value)))
+            .collect();
+
+        let mut commit = |effects| match state.commit(correlation_id, root_hash, effects) {
+            Ok(CommitResult::Success(hash, stats)) => (hash, stats),
+            _ => panic!("commit failed"),
+        };
+        let (first_hash, first_stats) = commit(effects.clone());
+        // Collected anew, so the keys may well be written in another order.
+        let (second_hash, second_stats) = commit(effects.into_iter().collect());
+
+        assert_eq!(first_hash, second_hash);
+        assert!(!first_stats.already_stored);
+        assert!(first_stats.trie_nodes_added > 0);
+        assert!(second_stats.already_stored);
+        assert_eq!(second_stats.trie_nodes_added, 0);
+        assert_eq!(second_stats.bytes_written, 0);
+        assert_eq!(second_stats.keys_written(), 3);
+    }
+
     #[test]
     fn commit_updates_state_and_original_state_stays_intact() {
         let correlation_id = CorrelationId::new();
@@ -278,7 +483,7 @@ mod tests {
         };
 
         let updated_hash = match state.commit(correlation_id, root_hash, effects).unwrap() {
-            CommitResult::Success(hash) => hash,
+            CommitResult::Success(hash, _) => hash,
             _ => panic!("commit failed"),
         };
 
@@ -305,6 +510,143 @@ mod tests {
         );
     }
 
+    #[test]
+    fn batch_commit_applies_commits_in_order() {
+        let correlation_id = CorrelationId::new();
+        let test_pairs_updated = create_test_pairs_updated();
+        let write = |pair: &TestPair| {
+            let mut effects = HashMap::new();
+            effects.insert(pair.key, Transform::Write(pair.value.to_owned()));
+            effects
+        };
+
+        let root_hash = create_test_state().root_hash;
+        let first_hash = match create_test_state()
+            .commit(correlation_id, root_hash, write(&test_pairs_updated[0]))
+            .unwrap()
+        {
+            CommitResult::Success(hash, _) => hash,
+            _ => panic!("commit failed"),
+        };
+
+        // The second commit builds on the state produced by the first one in the same batch.
+        let mut state = create_test_state();
+        let results = state
+            .batch_commit(
+                correlation_id,
+                vec![
+                    (root_hash, write(&test_pairs_updated[0]), None),
+                    (first_hash, write(&test_pairs_updated[1]), None),
+                ],
+            )
+            .unwrap();
+        let second_hash = match results.as_slice() {
+            [CommitResult::Success(hash1, _), CommitResult::Success(hash2, _)] => {
+                assert_eq!(*hash1, first_hash);
+                *hash2
+            }
+            _ => panic!("batch commit failed"),
+        };
+        assert_eq!(state.root_hash, second_hash);
+
+        let checkout = state.checkout(second_hash).unwrap().unwrap();
+        for TestPair { key, value } in test_pairs_updated[..2].iter().cloned() {
+            assert_eq!(Some(value), checkout.read(correlation_id, &key).unwrap());
+        }
+    }
+
+    #[test]
+    fn batch_commit_stores_nothing_if_a_commit_fails() {
+        let correlation_id = CorrelationId::new();
+        let test_pairs_updated = create_test_pairs_updated();
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+
+        let mut effects = HashMap::new();
+        effects.insert(
+            test_pairs_updated[2].key,
+            Transform::Write(test_pairs_updated[2].value.to_owned()),
+        );
+        let mut missing_key = HashMap::new();
+        missing_key.insert(Key::Account([4u8; 32]), Transform::AddInt32(1));
+        let unknown_root: Blake2bHash = [1u8; 32].into();
+
+        let results = state
+            .batch_commit(
+                correlation_id,
+                vec![
+                    (root_hash, effects.clone(), None),
+                    (root_hash, missing_key, None),
+                    (unknown_root, effects, None),
+                ],
+            )
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        let poststate_hash = match results[0] {
+            CommitResult::Success(hash, _) => hash,
+            _ => panic!("commit failed"),
+        };
+        match results[1] {
+            CommitResult::KeyNotFound(key) => assert_eq!(key, Key::Account([4u8; 32])),
+            _ => panic!("expected key not found"),
+        }
+        assert_eq!(state.root_hash, root_hash);
+        assert!(state.checkout(poststate_hash).unwrap().is_none());
+    }
+
+    #[test]
+    fn batch_commit_checks_expected_poststates() {
+        let correlation_id = CorrelationId::new();
+        let test_pairs_updated = create_test_pairs_updated();
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+
+        let mut effects = HashMap::new();
+        effects.insert(
+            test_pairs_updated[0].key,
+            Transform::Write(test_pairs_updated[0].value.to_owned()),
+        );
+        let computed = match state
+            .dry_run_commit(correlation_id, root_hash, effects.clone())
+            .unwrap()
+        {
+            CommitResult::Success(hash, _) => hash,
+            _ => panic!("commit failed"),
+        };
+        let expected: Blake2bHash = [1u8; 32].into();
+
+        let results = state
+            .batch_commit(
+                correlation_id,
+                vec![
+                    (root_hash, effects.clone(), Some(computed)),
+                    (root_hash, effects.clone(), Some(expected)),
+                ],
+            )
+            .unwrap();
+        match results.as_slice() {
+            [CommitResult::Success(..), CommitResult::PoststateMismatch {
+                expected: mismatch_expected,
+                computed: mismatch_computed,
+            }] => {
+                assert_eq!(*mismatch_expected, expected);
+                assert_eq!(*mismatch_computed, computed);
+            }
+            _ => panic!("expected a post state mismatch, got {:?}", results),
+        }
+        assert_eq!(state.root_hash, root_hash);
+        assert!(state.checkout(computed).unwrap().is_none());
+
+        let results = state
+            .batch_commit(correlation_id, vec![(root_hash, effects, Some(computed))])
+            .unwrap();
+        match results.as_slice() {
+            [CommitResult::Success(..)] => {}
+            _ => panic!("batch commit failed: {:?}", results),
+        }
+        assert_eq!(state.root_hash, computed);
+    }
+
     #[test]
     fn initial_state_has_the_expected_hash() {
         let correlation_id = CorrelationId::new();
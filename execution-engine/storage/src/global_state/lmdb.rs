@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::fs;
+use std::iter;
 use std::ops::Deref;
 use std::sync::Arc;
 
@@ -7,22 +9,56 @@ use lmdb;
 use common::key::Key;
 use common::value::Value;
 use error;
-use global_state::StateReader;
-use global_state::{commit, CommitResult, History};
+use global_state::wal::WriteAheadLog;
+use global_state::{batch_commit, commit, dry_run_commit, CommitResult, History, Snapshots};
+use global_state::{StateEntries, StateReader};
+use shared::logging::{log_metric, GAUGE};
 use shared::newtypes::{Blake2bHash, CorrelationId};
 use shared::transform::Transform;
 use trie::operations::create_hashed_empty_trie;
 use trie::Trie;
 use trie_store::lmdb::{LmdbEnvironment, LmdbTrieStore};
-use trie_store::operations::{read, ReadResult};
+use trie_store::operations::{estimate_shape, for_each_leaf, read, ReadResult, TrieShape};
 use trie_store::{Transaction, TransactionSource, TrieStore};
 
+const STORAGE_DB_SIZE: &str = "storage_db_size";
+const STORAGE_STORED_ROOTS: &str = "storage_stored_roots";
+const STORAGE_TRIE_NODES_ESTIMATE: &str = "storage_trie_nodes_estimate";
+const STORAGE_TRIE_MAX_DEPTH: &str = "storage_trie_max_depth";
+const STORAGE_TRIE_MEAN_DEPTH: &str = "storage_trie_mean_depth";
+const STORAGE: &str = "storage";
+
+/// File in which LMDB keeps the data of an environment.
+const LMDB_DATA_FILE: &str = "data.mdb";
+/// Number of paths descended to estimate the shape of the trie.
+const TRIE_SHAPE_SAMPLES: u32 = 64;
+
 /// Represents a "view" of global state at a particular root hash.
 pub struct LmdbGlobalState {
     pub(super) environment: Arc<LmdbEnvironment>,
     pub(super) store: Arc<LmdbTrieStore>,
     pub(super) root_hash: Blake2bHash,
     pub(super) empty_root_hash: Blake2bHash,
+    wal: Option<WriteAheadLog>,
+}
+
+/// Size and shape of the stored global state, see [`StorageProbe::storage_stats`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StorageStats {
+    /// Size of the database file in bytes, if it could be read.
+    pub db_size: Option<u64>,
+    /// Number of roots stored, including the ones committed by previous runs.
+    pub stored_roots: u64,
+    /// Shape of the trie under the current root, estimated from random paths.
+    pub trie_shape: Option<TrieShape>,
+}
+
+/// Measures the store of a global state as of its root when the probe was taken. Reads go
+/// through their own transaction, so they don't hold up commits made meanwhile.
+pub struct StorageProbe {
+    environment: Arc<LmdbEnvironment>,
+    store: Arc<LmdbTrieStore>,
+    root_hash: Blake2bHash,
 }
 
 impl LmdbGlobalState {
@@ -59,10 +95,101 @@ impl LmdbGlobalState {
             store,
             root_hash,
             empty_root_hash,
+            wal: None,
+        }
+    }
+
+    /// Logs the following commits to a write-ahead log in the directory of the environment,
+    /// after replaying the commits a crash interrupted, whose results are returned. Commits then
+    /// only return once they're durable.
+    pub fn open_write_ahead_log(
+        &mut self,
+        correlation_id: CorrelationId,
+    ) -> Result<Vec<CommitResult>, error::Error> {
+        let (mut wal, pending) = WriteAheadLog::open(self.environment.path())?;
+        let replayed = match pending {
+            Some(batch) => {
+                // The poststate hash a replayed commit was checked against was never reported.
+                let batch = batch
+                    .into_iter()
+                    .map(|(prestate_hash, effects)| (prestate_hash, effects, None))
+                    .collect();
+                let results = self.batch_commit(correlation_id, batch)?;
+                self.environment.sync()?;
+                results
+            }
+            None => Vec::new(),
+        };
+        wal.clear()?;
+        self.wal = Some(wal);
+        Ok(replayed)
+    }
+
+    fn complete_commits(&mut self) -> Result<(), error::Error> {
+        if let Some(wal) = self.wal.as_mut() {
+            self.environment.sync()?;
+            wal.clear()?;
+        }
+        Ok(())
+    }
+
+    /// Probe measuring the store as of the current root, to be used after releasing the state.
+    pub fn storage_probe(&self) -> StorageProbe {
+        StorageProbe {
+            environment: Arc::clone(&self.environment),
+            store: Arc::clone(&self.store),
+            root_hash: self.root_hash,
         }
     }
 }
 
+impl StorageProbe {
+    /// Measures the database file, counts the stored roots and estimates the shape of the trie
+    /// under the probed root.
+    pub fn storage_stats(&self) -> Result<StorageStats, error::Error> {
+        let db_size = fs::metadata(self.environment.path().join(LMDB_DATA_FILE))
+            .map(|metadata| metadata.len())
+            .ok();
+        let txn = self.environment.create_read_txn()?;
+        // Seeded with the root so that the same state is always sampled the same way.
+        let seed = self.root_hash.to_vec()[..8]
+            .iter()
+            .fold(0u64, |seed, byte| (seed << 8) | u64::from(*byte));
+        let trie_shape = estimate_shape::<Key, Value, _, _, error::Error>(
+            &txn,
+            self.store.deref(),
+            &self.root_hash,
+            TRIE_SHAPE_SAMPLES,
+            seed,
+        )?;
+        let stored_roots = self.store.count_roots::<Key, Value>(&txn)?;
+        txn.commit()?;
+        Ok(StorageStats {
+            db_size,
+            stored_roots,
+            trie_shape,
+        })
+    }
+
+    /// Reports [`StorageStats`] through the metrics log.
+    pub fn log_storage_metrics(&self, correlation_id: CorrelationId) -> Result<(), error::Error> {
+        let stats = self.storage_stats()?;
+        let log_gauge = |metric: &str, value: f64| {
+            log_metric(correlation_id, metric, STORAGE, GAUGE, value);
+        };
+        if let Some(db_size) = stats.db_size {
+            log_gauge(STORAGE_DB_SIZE, db_size as f64);
+        }
+        log_gauge(STORAGE_STORED_ROOTS, stats.stored_roots as f64);
+        if let Some(trie_shape) = stats.trie_shape {
+            log_gauge(STORAGE_TRIE_NODES_ESTIMATE, trie_shape.estimated_nodes);
+            log_gauge(STORAGE_TRIE_MAX_DEPTH, f64::from(trie_shape.max_depth));
+            log_gauge(STORAGE_TRIE_MEAN_DEPTH, trie_shape.mean_depth);
+        }
+        Ok(())
+    }
+}
+
 impl StateReader<Key, Value> for LmdbGlobalState {
     type Error = error::Error;
 
@@ -84,22 +211,69 @@ impl StateReader<Key, Value> for LmdbGlobalState {
     }
 }
 
+impl StateEntries<Key, Value> for LmdbGlobalState {
+    fn for_each_entry(&self, visit: &mut dyn FnMut(Key, Value)) -> Result<(), Self::Error> {
+        let txn = self.environment.create_read_txn()?;
+        let found = for_each_leaf::<Key, Value, lmdb::RoTransaction, LmdbTrieStore, Self::Error, _>(
+            &txn,
+            self.store.deref(),
+            &self.root_hash,
+            |key, value| visit(key, value),
+        )?;
+        if !found {
+            panic!("LmdbGlobalState has invalid root");
+        }
+        txn.commit()?;
+        Ok(())
+    }
+}
+
+/// Checkouts of an [`LmdbGlobalState`], see [`Snapshots`].
+#[derive(Clone)]
+pub struct LmdbSnapshots {
+    environment: Arc<LmdbEnvironment>,
+    store: Arc<LmdbTrieStore>,
+    empty_root_hash: Blake2bHash,
+}
+
+impl Snapshots for LmdbSnapshots {
+    type Error = error::Error;
+
+    type Reader = LmdbGlobalState;
+
+    fn checkout(&self, root_hash: Blake2bHash) -> Result<Option<Self::Reader>, Self::Error> {
+        let txn = self.environment.create_read_txn()?;
+        let maybe_root: Option<Trie<Key, Value>> = self.store.get(&txn, &root_hash)?;
+        let maybe_state = maybe_root.map(|_| {
+            LmdbGlobalState::new(
+                Arc::clone(&self.environment),
+                Arc::clone(&self.store),
+                root_hash,
+                self.empty_root_hash,
+            )
+        });
+        txn.commit()?;
+        Ok(maybe_state)
+    }
+}
+
 impl History for LmdbGlobalState {
     type Error = error::Error;
 
     type Reader = Self;
 
+    type Snapshots = LmdbSnapshots;
+
     fn checkout(&self, prestate_hash: Blake2bHash) -> Result<Option<Self::Reader>, Self::Error> {
-        let txn = self.environment.create_read_txn()?;
-        let maybe_root: Option<Trie<Key, Value>> = self.store.get(&txn, &prestate_hash)?;
-        let maybe_state = maybe_root.map(|_| LmdbGlobalState {
+        self.snapshots().checkout(prestate_hash)
+    }
+
+    fn snapshots(&self) -> Self::Snapshots {
+        LmdbSnapshots {
             environment: Arc::clone(&self.environment),
             store: Arc::clone(&self.store),
-            root_hash: prestate_hash,
             empty_root_hash: self.empty_root_hash,
-        });
-        txn.commit()?;
-        Ok(maybe_state)
+        }
     }
 
     fn commit(
@@ -108,19 +282,89 @@ impl History for LmdbGlobalState {
         prestate_hash: Blake2bHash,
         effects: HashMap<Key, Transform>,
     ) -> Result<CommitResult, Self::Error> {
+        if let Some(wal) = self.wal.as_mut() {
+            wal.begin(iter::once((&prestate_hash, &effects)))?;
+        }
         let commit_result = commit::<LmdbEnvironment, LmdbTrieStore, _, Self::Error>(
             &self.environment,
             &self.store,
             correlation_id,
             prestate_hash,
             effects,
+            None,
         )?;
-        if let CommitResult::Success(root_hash) = commit_result {
+        self.complete_commits()?;
+        if let CommitResult::Success(root_hash, _) = commit_result {
             self.root_hash = root_hash;
         };
         Ok(commit_result)
     }
 
+    fn commit_expecting(
+        &mut self,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        effects: HashMap<Key, Transform>,
+        expected_poststate_hash: Blake2bHash,
+    ) -> Result<CommitResult, Self::Error> {
+        if let Some(wal) = self.wal.as_mut() {
+            wal.begin(iter::once((&prestate_hash, &effects)))?;
+        }
+        let commit_result = commit::<LmdbEnvironment, LmdbTrieStore, _, Self::Error>(
+            &self.environment,
+            &self.store,
+            correlation_id,
+            prestate_hash,
+            effects,
+            Some(expected_poststate_hash),
+        )?;
+        self.complete_commits()?;
+        if let CommitResult::Success(root_hash, _) = commit_result {
+            self.root_hash = root_hash;
+        };
+        Ok(commit_result)
+    }
+
+    fn batch_commit(
+        &mut self,
+        correlation_id: CorrelationId,
+        batch: Vec<(Blake2bHash, HashMap<Key, Transform>, Option<Blake2bHash>)>,
+    ) -> Result<Vec<CommitResult>, Self::Error> {
+        if let Some(wal) = self.wal.as_mut() {
+            wal.begin(
+                batch
+                    .iter()
+                    .map(|(prestate_hash, effects, _)| (prestate_hash, effects)),
+            )?;
+        }
+        let commit_results = batch_commit::<LmdbEnvironment, LmdbTrieStore, _, Self::Error>(
+            &self.environment,
+            &self.store,
+            correlation_id,
+            batch,
+        )?;
+        self.complete_commits()?;
+        if let Some(CommitResult::Success(root_hash, _)) = commit_results.last() {
+            self.root_hash = *root_hash;
+        };
+        Ok(commit_results)
+    }
+
+    fn dry_run_commit(
+        &self,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        effects: HashMap<Key, Transform>,
+    ) -> Result<CommitResult, Self::Error> {
+        dry_run_commit::<LmdbEnvironment, LmdbTrieStore, _, Self::Error>(
+            &self.environment,
+            &self.store,
+            correlation_id,
+            prestate_hash,
+            effects,
+        )
+    }
+
     fn current_root(&self) -> Blake2bHash {
         self.root_hash
     }
@@ -212,6 +456,74 @@ mod tests {
         ret
     }
 
+    #[test]
+    fn storage_stats_count_stored_roots() {
+        let correlation_id = CorrelationId::new();
+        let mut state = create_test_state();
+        let root_hash = state.root_hash;
+        let stored_roots = state.storage_probe().storage_stats().unwrap().stored_roots;
+        // The empty root and the ones written along with the test pairs.
+        assert!(stored_roots >= 2);
+        let effects: HashMap<Key, Transform> = create_test_pairs_updated()
+            .iter()
+            .map(|TestPair { key, value }| (*key, Transform::Write(value.to_owned())))
+            .collect();
+        match state.commit(correlation_id, root_hash, effects).unwrap() {
+            CommitResult::Success(..) => (),
+            _ => panic!("commit failed"),
+        }
+
+        let stats = state.storage_probe().storage_stats().unwrap();
+        assert_eq!(stats.stored_roots, stored_roots + 1);
+        let trie_shape = stats.trie_shape.expect("current root should be stored");
+        assert!(trie_shape.max_depth >= 1);
+        assert!(trie_shape.estimated_nodes >= 2.0);
+    }
+
+    #[test]
+    fn should_replay_commits_interrupted_by_a_crash() {
+        let correlation_id = CorrelationId::new();
+        let temp_dir = tempdir().unwrap();
+        let environment =
+            Arc::new(LmdbEnvironment::new(&temp_dir.path().to_path_buf(), *TEST_MAP_SIZE).unwrap());
+        let store =
+            Arc::new(LmdbTrieStore::new(&environment, None, DatabaseFlags::empty()).unwrap());
+        let mut state = LmdbGlobalState::empty(environment, store).unwrap();
+        assert!(state
+            .open_write_ahead_log(correlation_id)
+            .unwrap()
+            .is_empty());
+
+        let effects: HashMap<Key, Transform> = TEST_PAIRS
+            .iter()
+            .map(|TestPair { key, value }| (*key, Transform::Write(value.to_owned())))
+            .collect();
+        // The engine crashed after logging the commit.
+        let (mut wal, _) = WriteAheadLog::open(temp_dir.path()).unwrap();
+        wal.begin(iter::once((&state.empty_root_hash, &effects)))
+            .unwrap();
+
+        let replayed = state.open_write_ahead_log(correlation_id).unwrap();
+        let root_hash = match replayed.as_slice() {
+            [CommitResult::Success(root_hash, _)] => *root_hash,
+            _ => panic!("commit should be replayed: {:?}", replayed),
+        };
+        assert_eq!(state.current_root(), root_hash);
+        let checkout = state.checkout(root_hash).unwrap().unwrap();
+        for TestPair { key, value } in TEST_PAIRS.iter().cloned() {
+            assert_eq!(Some(value), checkout.read(correlation_id, &key).unwrap());
+        }
+
+        // Completed commits are cleared from the log.
+        let effects: HashMap<Key, Transform> = create_test_pairs_updated()
+            .iter()
+            .map(|TestPair { key, value }| (*key, Transform::Write(value.to_owned())))
+            .collect();
+        state.commit(correlation_id, root_hash, effects).unwrap();
+        let (_, pending) = WriteAheadLog::open(temp_dir.path()).unwrap();
+        assert!(pending.is_none());
+    }
+
     #[test]
     fn reads_from_a_checkout_return_expected_values() {
         let correlation_id = CorrelationId::new();
@@ -247,7 +559,7 @@ mod tests {
         };
 
         let updated_hash = match state.commit(correlation_id, root_hash, effects).unwrap() {
-            CommitResult::Success(hash) => hash,
+            CommitResult::Success(hash, _) => hash,
             _ => panic!("commit failed"),
         };
 
@@ -278,7 +590,7 @@ mod tests {
         };
 
         let updated_hash = match state.commit(correlation_id, root_hash, effects).unwrap() {
-            CommitResult::Success(hash) => hash,
+            CommitResult::Success(hash, _) => hash,
             _ => panic!("commit failed"),
         };
 
@@ -9,11 +9,12 @@ use shared::logging::{log_duration, log_metric, GAUGE};
 use shared::newtypes::{Blake2bHash, CorrelationId};
 use shared::transform::{self, Transform, TypeMismatch};
 use trie::Trie;
-use trie_store::operations::{read, write, ReadResult, WriteResult};
-use trie_store::{Transaction, TransactionSource, TrieStore};
+use trie_store::operations::{read, write_with_stats, ReadResult, WriteResult};
+use trie_store::{Readable, Transaction, TransactionSource, TrieStore, Writable};
 
 pub mod in_memory;
 pub mod lmdb;
+pub mod wal;
 
 /// A reader of state
 pub trait StateReader<K, V> {
@@ -24,23 +25,71 @@ pub trait StateReader<K, V> {
     fn read(&self, correlation_id: CorrelationId, key: &K) -> Result<Option<V>, Self::Error>;
 }
 
+/// A reader which can list all the entries of its state, e.g. to export them.
+pub trait StateEntries<K, V>: StateReader<K, V> {
+    /// Calls `visit` with every entry, in the order of their serialized keys.
+    fn for_each_entry(&self, visit: &mut dyn FnMut(K, V)) -> Result<(), Self::Error>;
+}
+
+/// Summary of the changes made to the global state by a successful commit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CommitStats {
+    /// Number of keys which were not present under the prestate root.
+    pub keys_created: u32,
+    /// Number of already existing keys whose value has changed.
+    pub keys_updated: u32,
+    /// Number of trie nodes stored, including the ones replacing the nodes on the path to each
+    /// written key.
+    pub trie_nodes_added: u32,
+    /// Serialized size of the stored trie nodes.
+    pub bytes_written: u64,
+    /// Whether the post state was stored before, e.g. when the node replays commits after a
+    /// crash. A single commit then stores nothing.
+    pub already_stored: bool,
+}
+
+impl CommitStats {
+    /// Number of keys whose value has been written.
+    pub fn keys_written(&self) -> u32 {
+        self.keys_created + self.keys_updated
+    }
+}
+
 #[derive(Debug)]
 pub enum CommitResult {
     RootNotFound,
-    Success(Blake2bHash),
+    Success(Blake2bHash, CommitStats),
     KeyNotFound(Key),
     TypeMismatch(TypeMismatch),
+    /// The effects lead to `computed` rather than the expected post state; nothing was stored.
+    PoststateMismatch {
+        expected: Blake2bHash,
+        computed: Blake2bHash,
+    },
 }
 
 impl fmt::Display for CommitResult {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match self {
             CommitResult::RootNotFound => write!(f, "Root not found"),
-            CommitResult::Success(hash) => write!(f, "Success: {}", hash),
+            CommitResult::Success(hash, stats) => write!(
+                f,
+                "Success: {} (created: {}, updated: {}, trie nodes added: {}, bytes written: {})",
+                hash,
+                stats.keys_created,
+                stats.keys_updated,
+                stats.trie_nodes_added,
+                stats.bytes_written
+            ),
             CommitResult::KeyNotFound(key) => write!(f, "Key not found: {}", key),
             CommitResult::TypeMismatch(type_mismatch) => {
                 write!(f, "Type mismatch: {:?}", type_mismatch)
             }
+            CommitResult::PoststateMismatch { expected, computed } => write!(
+                f,
+                "Post state mismatch: expected {}, computed {}",
+                expected, computed
+            ),
         }
     }
 }
@@ -55,13 +104,31 @@ impl From<transform::Error> for CommitResult {
     }
 }
 
+/// Checks out past states without going through the `History` they belong to.
+///
+/// Stored trie nodes are never changed nor removed, so a reader of a given root sees the same
+/// state whatever is committed meanwhile: a checkout is a consistent snapshot. Each read runs in
+/// its own read transaction, which both stores serve while a commit is writing, so readers don't
+/// wait for commits.
+pub trait Snapshots {
+    type Error;
+    type Reader: StateEntries<Key, Value, Error = Self::Error>;
+
+    /// Checks out the state under `root_hash`, `None` if it isn't stored.
+    fn checkout(&self, root_hash: Blake2bHash) -> Result<Option<Self::Reader>, Self::Error>;
+}
+
 pub trait History {
     type Error;
-    type Reader: StateReader<Key, Value, Error = Self::Error>;
+    type Reader: StateEntries<Key, Value, Error = Self::Error>;
+    type Snapshots: Snapshots<Error = Self::Error, Reader = Self::Reader> + Send + Sync;
 
     /// Checkouts to the post state of a specific block.
     fn checkout(&self, prestate_hash: Blake2bHash) -> Result<Option<Self::Reader>, Self::Error>;
 
+    /// Checkouts which can be made while the history is being committed to, e.g. by queries.
+    fn snapshots(&self) -> Self::Snapshots;
+
     /// Applies changes and returns a new post state hash.
     /// block_hash is used for computing a deterministic and unique keys.
     fn commit(
@@ -71,6 +138,34 @@ pub trait History {
         effects: HashMap<Key, Transform>,
     ) -> Result<CommitResult, Self::Error>;
 
+    /// Applies changes like [`History::commit`], but stores them only if they lead to
+    /// `expected_poststate_hash`. The root is checked within the transaction which stores it.
+    fn commit_expecting(
+        &mut self,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        effects: HashMap<Key, Transform>,
+        expected_poststate_hash: Blake2bHash,
+    ) -> Result<CommitResult, Self::Error>;
+
+    /// Applies the effects of several blocks in order, storing them only if all of them succeed.
+    /// Each commit may name the post state hash it has to lead to. Returns the results up to and
+    /// including the first unsuccessful commit.
+    fn batch_commit(
+        &mut self,
+        correlation_id: CorrelationId,
+        batch: Vec<(Blake2bHash, HashMap<Key, Transform>, Option<Blake2bHash>)>,
+    ) -> Result<Vec<CommitResult>, Self::Error>;
+
+    /// Computes the result of committing `effects` on top of `prestate_hash`, without storing
+    /// anything.
+    fn dry_run_commit(
+        &self,
+        correlation_id: CorrelationId,
+        prestate_hash: Blake2bHash,
+        effects: HashMap<Key, Transform>,
+    ) -> Result<CommitResult, Self::Error>;
+
     fn current_root(&self) -> Blake2bHash;
 
     fn empty_root(&self) -> Blake2bHash;
@@ -83,12 +178,59 @@ const GLOBAL_STATE_COMMIT_READ_DURATION: &str = "global_state_commit_read_durati
 const GLOBAL_STATE_COMMIT_WRITE_DURATION: &str = "global_state_commit_write_duration";
 const COMMIT: &str = "commit";
 
+/// Applies `effects` on top of `prestate_hash` and stores the result, unless it doesn't lead to
+/// `expected_poststate_hash` when that is given.
 pub fn commit<'a, R, S, H, E>(
     environment: &'a R,
     store: &S,
     correlation_id: CorrelationId,
     prestate_hash: Blake2bHash,
     effects: HashMap<Key, Transform, H>,
+    expected_poststate_hash: Option<Blake2bHash>,
+) -> Result<CommitResult, E>
+where
+    R: TransactionSource<'a, Handle = S::Handle>,
+    S: TrieStore<Key, Value>,
+    S::Error: From<R::Error>,
+    E: From<R::Error> + From<S::Error> + From<common::bytesrepr::Error>,
+    H: BuildHasher,
+{
+    let start = Instant::now();
+    let mut txn = environment.create_read_write_txn()?;
+    let commit_result =
+        apply_effects::<_, _, _, E>(correlation_id, &mut txn, store, prestate_hash, effects)?;
+    match check_poststate(commit_result, expected_poststate_hash) {
+        // Dropping the transaction discards the intermediate roots written on the way.
+        CommitResult::Success(post_state_hash, stats) if stats.already_stored => {
+            let stats = CommitStats {
+                trie_nodes_added: 0,
+                bytes_written: 0,
+                ..stats
+            };
+            Ok(CommitResult::Success(post_state_hash, stats))
+        }
+        commit_result @ CommitResult::Success(..) => {
+            txn.commit()?;
+            log_duration(
+                correlation_id,
+                GLOBAL_STATE_COMMIT_DURATION,
+                COMMIT,
+                start.elapsed(),
+            );
+            Ok(commit_result)
+        }
+        // Dropping the transaction discards the written trie nodes.
+        commit_result => Ok(commit_result),
+    }
+}
+
+/// Applies `effects` like [`commit`], but discards the written trie nodes instead of storing them.
+pub fn dry_run_commit<'a, R, S, H, E>(
+    environment: &'a R,
+    store: &S,
+    correlation_id: CorrelationId,
+    prestate_hash: Blake2bHash,
+    effects: HashMap<Key, Transform, H>,
 ) -> Result<CommitResult, E>
 where
     R: TransactionSource<'a, Handle = S::Handle>,
@@ -98,9 +240,86 @@ where
     H: BuildHasher,
 {
     let mut txn = environment.create_read_write_txn()?;
+    // Dropping the transaction discards the written trie nodes.
+    apply_effects::<_, _, _, E>(correlation_id, &mut txn, store, prestate_hash, effects)
+}
+
+/// Applies the effects of several commits in order, within a single transaction. Each commit has
+/// to start from one of the states produced so far, or one already stored, and lead to its
+/// expected post state hash if it has one.
+///
+/// The returned results end with the first commit which didn't succeed, if any. Nothing is
+/// stored unless all commits succeed.
+pub fn batch_commit<'a, R, S, H, E>(
+    environment: &'a R,
+    store: &S,
+    correlation_id: CorrelationId,
+    batch: Vec<(Blake2bHash, HashMap<Key, Transform, H>, Option<Blake2bHash>)>,
+) -> Result<Vec<CommitResult>, E>
+where
+    R: TransactionSource<'a, Handle = S::Handle>,
+    S: TrieStore<Key, Value>,
+    S::Error: From<R::Error>,
+    E: From<R::Error> + From<S::Error> + From<common::bytesrepr::Error>,
+    H: BuildHasher,
+{
+    let start = Instant::now();
+    let mut txn = environment.create_read_write_txn()?;
+    let mut results = Vec::with_capacity(batch.len());
+    for (prestate_hash, effects, expected_poststate_hash) in batch {
+        let commit_result =
+            apply_effects::<_, _, _, E>(correlation_id, &mut txn, store, prestate_hash, effects)?;
+        let commit_result = check_poststate(commit_result, expected_poststate_hash);
+        let succeeded = match commit_result {
+            CommitResult::Success(..) => true,
+            _ => false,
+        };
+        results.push(commit_result);
+        if !succeeded {
+            // Dropping the transaction discards the commits applied so far.
+            return Ok(results);
+        }
+    }
+    txn.commit()?;
+    log_duration(
+        correlation_id,
+        GLOBAL_STATE_COMMIT_DURATION,
+        COMMIT,
+        start.elapsed(),
+    );
+    Ok(results)
+}
+
+/// Turns a successful commit into a mismatch if it didn't lead to `expected_poststate_hash`.
+fn check_poststate(
+    commit_result: CommitResult,
+    expected_poststate_hash: Option<Blake2bHash>,
+) -> CommitResult {
+    match (commit_result, expected_poststate_hash) {
+        (CommitResult::Success(computed, _), Some(expected)) if computed != expected => {
+            CommitResult::PoststateMismatch { expected, computed }
+        }
+        (commit_result, _) => commit_result,
+    }
+}
+
+fn apply_effects<T, S, H, E>(
+    correlation_id: CorrelationId,
+    txn: &mut T,
+    store: &S,
+    prestate_hash: Blake2bHash,
+    effects: HashMap<Key, Transform, H>,
+) -> Result<CommitResult, E>
+where
+    T: Readable<Handle = S::Handle> + Writable<Handle = S::Handle>,
+    S: TrieStore<Key, Value>,
+    S::Error: From<T::Error>,
+    E: From<S::Error> + From<common::bytesrepr::Error>,
+    H: BuildHasher,
+{
     let mut current_root = prestate_hash;
 
-    let maybe_root: Option<Trie<Key, Value>> = store.get(&txn, &current_root)?;
+    let maybe_root: Option<Trie<Key, Value>> = store.get(&*txn, &current_root)?;
 
     if maybe_root.is_none() {
         return Ok(CommitResult::RootNotFound);
@@ -109,9 +328,14 @@ where
     let start = Instant::now();
     let mut reads: i32 = 0;
     let mut writes: i32 = 0;
+    // Without any written key, the post state is the stored prestate.
+    let mut stats = CommitStats {
+        already_stored: true,
+        ..CommitStats::default()
+    };
 
     for (key, transform) in effects.into_iter() {
-        let read_result = read::<_, _, _, _, E>(correlation_id, &txn, store, &current_root, &key)?;
+        let read_result = read::<_, _, _, _, E>(correlation_id, &*txn, store, &current_root, &key)?;
 
         log_duration(
             correlation_id,
@@ -122,20 +346,28 @@ where
 
         reads += 1;
 
-        let value = match (read_result, transform) {
-            (ReadResult::NotFound, Transform::Write(new_value)) => new_value,
+        let (value, created) = match (read_result, transform) {
+            (ReadResult::NotFound, Transform::Write(new_value)) => (new_value, true),
             (ReadResult::NotFound, _) => {
                 return Ok(CommitResult::KeyNotFound(key));
             }
             (ReadResult::Found(current_value), transform) => match transform.apply(current_value) {
-                Ok(updated_value) => updated_value,
+                Ok(updated_value) => (updated_value, false),
                 Err(err) => return Ok(err.into()),
             },
             _x @ (ReadResult::RootNotFound, _) => panic!(stringify!(_x._1)),
         };
 
-        let write_result =
-            write::<_, _, _, _, E>(correlation_id, &mut txn, store, &current_root, &key, &value)?;
+        let (write_result, write_stats) = write_with_stats::<_, _, _, _, E>(
+            correlation_id,
+            &mut *txn,
+            store,
+            &current_root,
+            &key,
+            &value,
+        )?;
+        stats.trie_nodes_added += write_stats.nodes_added;
+        stats.bytes_written += write_stats.bytes_written;
 
         log_duration(
             correlation_id,
@@ -147,22 +379,21 @@ where
         match write_result {
             WriteResult::Written(root_hash) => {
                 current_root = root_hash;
+                // Every write changes the state, so the new root can't have been written by an
+                // earlier one of this commit. It's only found stored if it was stored before.
+                stats.already_stored = write_stats.nodes_added == 0;
                 writes += 1;
+                if created {
+                    stats.keys_created += 1;
+                } else {
+                    stats.keys_updated += 1;
+                }
             }
             WriteResult::AlreadyExists => (),
             _x @ WriteResult::RootNotFound => panic!(stringify!(_x)),
         }
     }
 
-    txn.commit()?;
-
-    log_duration(
-        correlation_id,
-        GLOBAL_STATE_COMMIT_DURATION,
-        COMMIT,
-        start.elapsed(),
-    );
-
     log_metric(
         correlation_id,
         GLOBAL_STATE_COMMIT_READS,
@@ -179,5 +410,5 @@ where
         f64::from(writes),
     );
 
-    Ok(CommitResult::Success(current_root))
+    Ok(CommitResult::Success(current_root, stats))
 }
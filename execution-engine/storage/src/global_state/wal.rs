@@ -0,0 +1,211 @@
+//! Write-ahead log of the commits to the LMDB global state.
+//!
+//! The effects of a commit are logged and synced to disk before the commit starts, and the log
+//! is cleared once LMDB has synced the commit. After a crash, a commit still in the log may or
+//! may not have been stored, so it's replayed on restart: trie nodes are addressed by their
+//! hash, so replaying a commit which was stored changes nothing. A record which wasn't fully
+//! written before the crash is discarded, as the commit it describes never started.
+//!
+//! The log holds at most one record, the commits of a batch being logged together:
+//!
+//! ```text
+//! <payload length: u64 LE> <blake2b hash of the payload: 32 bytes> <payload>
+//! ```
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use common::bytesrepr::{self, FromBytes, ToBytes};
+use common::key::Key;
+use shared::newtypes::Blake2bHash;
+use shared::transform::Transform;
+
+use error;
+
+pub const WAL_FILE: &str = "commit.wal";
+
+const LENGTH_SIZE: usize = 8;
+const CHECKSUM_SIZE: usize = 32;
+
+/// Commits logged but not known to be stored, as pairs of prestate hash and effects.
+pub type PendingCommits = Vec<(Blake2bHash, HashMap<Key, Transform>)>;
+
+pub struct WriteAheadLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl WriteAheadLog {
+    /// Opens the log in `dir`, creating it if needed. Returns the commits a crash interrupted,
+    /// which have to be replayed before the next one is logged.
+    pub fn open(dir: &Path) -> Result<(WriteAheadLog, Option<PendingCommits>), error::Error> {
+        let path = dir.join(WAL_FILE);
+        let created = !path.exists();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&path)?;
+        if created {
+            // The entry of the new file has to be durable too.
+            File::open(dir)?.sync_all()?;
+        }
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        let pending = read_record(&contents)?;
+        Ok((WriteAheadLog { path, file }, pending))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Logs `commits`, replacing the previous record, and syncs the log to disk.
+    pub fn begin<'a, I>(&mut self, commits: I) -> Result<(), error::Error>
+    where
+        I: IntoIterator<Item = (&'a Blake2bHash, &'a HashMap<Key, Transform>)>,
+    {
+        let payload = serialize_commits(commits)?;
+        let mut record = Vec::with_capacity(LENGTH_SIZE + CHECKSUM_SIZE + payload.len());
+        record.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+        record.extend_from_slice(&Blake2bHash::new(&payload).to_vec());
+        record.extend_from_slice(&payload);
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&record)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Empties the log, once the logged commits are durably stored.
+    pub fn clear(&mut self) -> Result<(), error::Error> {
+        self.file.set_len(0)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+fn serialize_commits<'a, I>(commits: I) -> Result<Vec<u8>, bytesrepr::Error>
+where
+    I: IntoIterator<Item = (&'a Blake2bHash, &'a HashMap<Key, Transform>)>,
+{
+    let mut payload = Vec::new();
+    let mut count = 0u32;
+    for (prestate_hash, effects) in commits {
+        payload.append(&mut prestate_hash.to_bytes()?);
+        payload.append(&mut (effects.len() as u32).to_bytes()?);
+        for (key, transform) in effects {
+            payload.append(&mut key.to_bytes()?);
+            payload.append(&mut transform.to_bytes()?);
+        }
+        count += 1;
+    }
+    let mut result = count.to_bytes()?;
+    result.append(&mut payload);
+    Ok(result)
+}
+
+fn deserialize_commits(payload: &[u8]) -> Result<PendingCommits, bytesrepr::Error> {
+    let (count, mut stream): (u32, &[u8]) = FromBytes::from_bytes(payload)?;
+    let mut commits = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (prestate_hash, rem): (Blake2bHash, &[u8]) = FromBytes::from_bytes(stream)?;
+        let (effects_count, mut rem): (u32, &[u8]) = FromBytes::from_bytes(rem)?;
+        let mut effects = HashMap::with_capacity(effects_count as usize);
+        for _ in 0..effects_count {
+            let (key, after_key): (Key, &[u8]) = FromBytes::from_bytes(rem)?;
+            let (transform, after_transform): (Transform, &[u8]) =
+                FromBytes::from_bytes(after_key)?;
+            effects.insert(key, transform);
+            rem = after_transform;
+        }
+        commits.push((prestate_hash, effects));
+        stream = rem;
+    }
+    if stream.is_empty() {
+        Ok(commits)
+    } else {
+        Err(bytesrepr::Error::LeftOverBytes)
+    }
+}
+
+/// Reads the record of `contents`, `None` if there's none or it wasn't fully written.
+fn read_record(contents: &[u8]) -> Result<Option<PendingCommits>, error::Error> {
+    if contents.len() < LENGTH_SIZE + CHECKSUM_SIZE {
+        return Ok(None);
+    }
+    let (length, rest) = contents.split_at(LENGTH_SIZE);
+    let mut length_bytes = [0u8; LENGTH_SIZE];
+    length_bytes.copy_from_slice(length);
+    let length = u64::from_le_bytes(length_bytes) as usize;
+    let (checksum, payload) = rest.split_at(CHECKSUM_SIZE);
+    if payload.len() != length || Blake2bHash::new(payload).to_vec() != checksum {
+        return Ok(None);
+    }
+    // A complete record which can't be read was written by an incompatible engine, replaying
+    // part of it or nothing would lose a commit.
+    deserialize_commits(payload).map(Some).map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs::{self, OpenOptions};
+
+    use tempfile::tempdir;
+
+    use common::key::Key;
+    use common::value::Value;
+    use shared::newtypes::Blake2bHash;
+    use shared::transform::Transform;
+
+    use super::{WriteAheadLog, WAL_FILE};
+
+    fn effects() -> HashMap<Key, Transform> {
+        let mut effects = HashMap::new();
+        effects.insert(Key::Hash([1u8; 32]), Transform::Write(Value::Int32(1)));
+        effects.insert(Key::Hash([2u8; 32]), Transform::AddUInt64(2));
+        effects
+    }
+
+    #[test]
+    fn should_return_logged_commits_until_cleared() {
+        let dir = tempdir().unwrap();
+        let prestate_hash = Blake2bHash::new(&[0u8]);
+        let effects = effects();
+        {
+            let (mut wal, pending) = WriteAheadLog::open(dir.path()).unwrap();
+            assert!(pending.is_none());
+            wal.begin(vec![(&prestate_hash, &effects)]).unwrap();
+        }
+        let (mut wal, pending) = WriteAheadLog::open(dir.path()).unwrap();
+        assert_eq!(pending, Some(vec![(prestate_hash, effects)]));
+
+        wal.clear().unwrap();
+        let (_, pending) = WriteAheadLog::open(dir.path()).unwrap();
+        assert!(pending.is_none());
+    }
+
+    #[test]
+    fn should_discard_partially_written_records() {
+        let dir = tempdir().unwrap();
+        let prestate_hash = Blake2bHash::new(&[0u8]);
+        let effects = effects();
+        {
+            let (mut wal, _) = WriteAheadLog::open(dir.path()).unwrap();
+            wal.begin(vec![(&prestate_hash, &effects)]).unwrap();
+        }
+        let path = dir.path().join(WAL_FILE);
+        let length = fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(length - 1)
+            .unwrap();
+
+        let (_, pending) = WriteAheadLog::open(dir.path()).unwrap();
+        assert!(pending.is_none());
+    }
+}
@@ -24,6 +24,7 @@ extern crate proptest;
 extern crate tempfile;
 
 // modules
+pub mod data_dir;
 pub mod error;
 pub mod global_state;
 pub mod trie;
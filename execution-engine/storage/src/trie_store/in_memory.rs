@@ -307,7 +307,20 @@ impl<K: ToBytes + FromBytes, V: ToBytes + FromBytes> TrieStore<K, V> for InMemor
         T: Writable<Handle = Self::Handle>,
         Self::Error: From<T::Error>,
     {
-        txn.write((), &key.to_bytes()?, &value.to_bytes()?)
+        self.put_bytes(txn, key, &value.to_bytes()?)
+    }
+
+    fn put_bytes<T>(
+        &self,
+        txn: &mut T,
+        key: &Blake2bHash,
+        value_bytes: &[u8],
+    ) -> Result<(), Self::Error>
+    where
+        T: Writable<Handle = Self::Handle>,
+        Self::Error: From<T::Error>,
+    {
+        txn.write((), &key.to_bytes()?, value_bytes)
             .map_err(Into::into)
     }
 }
@@ -106,14 +106,18 @@
 //! tmp_dir.close().unwrap();
 //! ```
 
+use std::collections::HashSet;
 use std::path::PathBuf;
 
-use lmdb::{self, Database, DatabaseFlags, Environment, RoTransaction, RwTransaction, WriteFlags};
+use lmdb::{
+    self, Cursor, Database, DatabaseFlags, Environment, RoTransaction, RwTransaction, WriteFlags,
+};
 
 use common::bytesrepr::{deserialize, FromBytes, ToBytes};
 
 use super::*;
 use error;
+use trie::{self, Pointer};
 
 impl<'a> Transaction for RoTransaction<'a> {
     type Error = lmdb::Error;
@@ -181,6 +185,11 @@ impl LmdbEnvironment {
     pub fn path(&self) -> &PathBuf {
         &self.path
     }
+
+    /// Flushes the committed transactions to disk.
+    pub fn sync(&self) -> Result<(), error::Error> {
+        self.env.sync(true).map_err(Into::into)
+    }
 }
 
 impl<'a> TransactionSource<'a> for LmdbEnvironment {
@@ -223,6 +232,39 @@ impl LmdbTrieStore {
         let db = env.env.open_db(name)?;
         Ok(LmdbTrieStore { db })
     }
+
+    /// Counts the roots stored, i.e. the nodes no other node points to, including the ones
+    /// written by previous runs. Roots aren't indexed, so this scans the whole store and keeps
+    /// the hash of every node pointed to in memory.
+    pub fn count_roots<K: FromBytes, V: FromBytes>(
+        &self,
+        txn: &RoTransaction,
+    ) -> Result<u64, error::Error> {
+        let mut nodes = Vec::new();
+        let mut pointed_to = HashSet::new();
+        let mut cursor = txn.open_ro_cursor(self.db)?;
+        for (hash_bytes, trie_bytes) in cursor.iter_start() {
+            let (tag, _): (u32, &[u8]) = FromBytes::from_bytes(trie_bytes)?;
+            // Leaves point to nothing and are never roots, so their values aren't decoded.
+            if tag == 0 {
+                continue;
+            }
+            let children: Vec<Pointer> = match deserialize::<Trie<K, V>>(trie_bytes)? {
+                Trie::Leaf { .. } => continue,
+                Trie::Node { pointer_block } => (0..trie::RADIX)
+                    .filter_map(|index| pointer_block[index])
+                    .collect(),
+                Trie::Extension { pointer, .. } => vec![pointer],
+            };
+            pointed_to.extend(children.iter().map(|pointer| *pointer.hash()));
+            let (hash, _): (Blake2bHash, &[u8]) = FromBytes::from_bytes(hash_bytes)?;
+            nodes.push(hash);
+        }
+        Ok(nodes
+            .iter()
+            .filter(|hash| !pointed_to.contains(*hash))
+            .count() as u64)
+    }
 }
 
 impl<K: ToBytes + FromBytes, V: ToBytes + FromBytes> TrieStore<K, V> for LmdbTrieStore {
@@ -258,7 +300,20 @@ impl<K: ToBytes + FromBytes, V: ToBytes + FromBytes> TrieStore<K, V> for LmdbTri
         T: Writable<Handle = Self::Handle>,
         Self::Error: From<T::Error>,
     {
-        txn.write(self.db, &key.to_bytes()?, &value.to_bytes()?)
+        self.put_bytes(txn, key, &value.to_bytes()?)
+    }
+
+    fn put_bytes<T: Writable>(
+        &self,
+        txn: &mut T,
+        key: &Blake2bHash,
+        value_bytes: &[u8],
+    ) -> Result<(), Self::Error>
+    where
+        T: Writable<Handle = Self::Handle>,
+        Self::Error: From<T::Error>,
+    {
+        txn.write(self.db, &key.to_bytes()?, value_bytes)
             .map_err(Into::into)
     }
 }
@@ -88,4 +88,16 @@ pub trait TrieStore<K, V> {
     where
         T: Writable<Handle = Self::Handle>,
         Self::Error: From<T::Error>;
+
+    /// Inserts an already serialized [`Trie`] value at a given hash, e.g. the bytes it was
+    /// hashed from.
+    fn put_bytes<T>(
+        &self,
+        txn: &mut T,
+        key: &Blake2bHash,
+        value_bytes: &[u8],
+    ) -> Result<(), Self::Error>
+    where
+        T: Writable<Handle = Self::Handle>,
+        Self::Error: From<T::Error>;
 }
@@ -365,21 +365,20 @@ where
     }
 }
 
-#[allow(clippy::type_complexity)]
+/// Hashes `tip` and its updated `parents`, from the tip up to the new root. Each node is paired
+/// with its serialized bytes, which are stored as they are.
 fn rehash<K, V>(
     mut tip: Trie<K, V>,
     parents: Parents<K, V>,
-) -> Result<Vec<(Blake2bHash, Trie<K, V>)>, bytesrepr::Error>
+) -> Result<Vec<(Blake2bHash, Vec<u8>)>, bytesrepr::Error>
 where
     K: ToBytes + Clone,
     V: ToBytes + Clone,
 {
-    let mut ret: Vec<(Blake2bHash, Trie<K, V>)> = Vec::new();
-    let mut tip_hash = {
-        let trie_bytes = tip.to_bytes()?;
-        Blake2bHash::new(&trie_bytes)
-    };
-    ret.push((tip_hash, tip.to_owned()));
+    let mut ret: Vec<(Blake2bHash, Vec<u8>)> = Vec::new();
+    let trie_bytes = tip.to_bytes()?;
+    let mut tip_hash = Blake2bHash::new(&trie_bytes);
+    ret.push((tip_hash, trie_bytes));
 
     for (index, parent) in parents.into_iter().rev() {
         match parent {
@@ -396,24 +395,17 @@ where
                     pointer_block[index.into()] = Some(pointer);
                     Trie::Node { pointer_block }
                 };
-                tip_hash = {
-                    let node_bytes = tip.to_bytes()?;
-                    Blake2bHash::new(&node_bytes)
-                };
-                ret.push((tip_hash, tip.to_owned()))
             }
             Trie::Extension { affix, pointer } => {
                 tip = {
                     let pointer = pointer.update(tip_hash);
                     Trie::Extension { affix, pointer }
                 };
-                tip_hash = {
-                    let extension_bytes = tip.to_bytes()?;
-                    Blake2bHash::new(&extension_bytes)
-                };
-                ret.push((tip_hash, tip.to_owned()))
             }
         }
+        let tip_bytes = tip.to_bytes()?;
+        tip_hash = Blake2bHash::new(&tip_bytes);
+        ret.push((tip_hash, tip_bytes));
     }
     Ok(ret)
 }
@@ -533,7 +525,7 @@ where
 struct SplitResult<K, V> {
     new_node: Trie<K, V>,
     parents: Parents<K, V>,
-    maybe_hashed_child_extension: Option<(Blake2bHash, Trie<K, V>)>,
+    maybe_hashed_child_extension: Option<(Blake2bHash, Vec<u8>)>,
 }
 
 /// Takes a path to a new leaf, an existing extension that leaf collides with,
@@ -541,7 +533,7 @@ struct SplitResult<K, V> {
 /// and child extensions.  The node pointer contained in the existing extension
 /// is repositioned in the new node or the possible child extension.  The
 /// possible parent extension is added to parents.  Returns the new node,
-/// parents, and the the possible child extension (paired with its hash).
+/// parents, and the the possible child extension (its hash paired with its serialized bytes).
 /// The new node and parents can be used by [`add_node_to_parents`], and the
 /// new hashed child extension can be added to the list of new trie elements.
 fn split_extension<K, V>(
@@ -571,21 +563,22 @@ where
     // node and the node that the existing extension pointed to.
     let child_extension_affix = affix[parent_extension_affix.len() + 1..].to_vec();
     // Create a child extension (paired with its hash) if necessary
-    let maybe_hashed_child_extension: Option<(Blake2bHash, Trie<K, V>)> =
-        if child_extension_affix.is_empty() {
-            None
-        } else {
-            let child_extension = Trie::extension(child_extension_affix.to_vec(), pointer);
-            let child_extension_bytes = child_extension.to_bytes()?;
-            let child_extension_hash = Blake2bHash::new(&child_extension_bytes);
-            Some((child_extension_hash, child_extension))
-        };
+    let maybe_hashed_child_extension: Option<(Blake2bHash, Vec<u8>)> = if child_extension_affix
+        .is_empty()
+    {
+        None
+    } else {
+        let child_extension: Trie<K, V> = Trie::extension(child_extension_affix.to_vec(), pointer);
+        let child_extension_bytes = child_extension.to_bytes()?;
+        let child_extension_hash = Blake2bHash::new(&child_extension_bytes);
+        Some((child_extension_hash, child_extension_bytes))
+    };
     // Assemble a new node.
     let new_node: Trie<K, V> = {
         let index: usize = existing_extension_path[shared_path.len()].into();
         let pointer = maybe_hashed_child_extension
-            .to_owned()
-            .map_or(pointer, |(hash, _)| Pointer::NodePointer(hash));
+            .as_ref()
+            .map_or(pointer, |(hash, _)| Pointer::NodePointer(*hash));
         Trie::node(&[(index, pointer)])
     };
     // Create a parent extension if necessary
@@ -612,6 +605,14 @@ pub enum WriteResult {
     RootNotFound,
 }
 
+/// Trie nodes stored by a write.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WriteStats {
+    pub nodes_added: u32,
+    /// Serialized size of the added nodes.
+    pub bytes_written: u64,
+}
+
 pub fn write<K, V, T, S, E>(
     correlation_id: CorrelationId,
     txn: &mut T,
@@ -620,6 +621,27 @@ pub fn write<K, V, T, S, E>(
     key: &K,
     value: &V,
 ) -> Result<WriteResult, E>
+where
+    K: ToBytes + Clone + Eq + std::fmt::Debug,
+    V: ToBytes + Clone + Eq,
+    T: Readable<Handle = S::Handle> + Writable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+    E: From<S::Error> + From<common::bytesrepr::Error>,
+{
+    write_with_stats::<K, V, T, S, E>(correlation_id, txn, store, root, key, value)
+        .map(|(write_result, _)| write_result)
+}
+
+/// Same as [`write`], also returning the nodes added to the store.
+pub fn write_with_stats<K, V, T, S, E>(
+    correlation_id: CorrelationId,
+    txn: &mut T,
+    store: &S,
+    root: &Blake2bHash,
+    key: &K,
+    value: &V,
+) -> Result<(WriteResult, WriteStats), E>
 where
     K: ToBytes + Clone + Eq + std::fmt::Debug,
     V: ToBytes + Clone + Eq,
@@ -630,9 +652,10 @@ where
 {
     let start = Instant::now();
     let mut put_counter: i32 = 0;
+    let mut stats = WriteStats::default();
 
     match store.get(txn, root)? {
-        None => Ok(WriteResult::RootNotFound),
+        None => Ok((WriteResult::RootNotFound, stats)),
         Some(current_root) => {
             let new_leaf = Trie::Leaf {
                 key: key.to_owned(),
@@ -641,7 +664,8 @@ where
             let path: Vec<u8> = key.to_bytes()?;
             let TrieScan { tip, parents } =
                 scan::<K, V, T, S, E>(correlation_id, txn, store, &path, &current_root)?;
-            let new_elements: Vec<(Blake2bHash, Trie<K, V>)> = match tip {
+            // The new trie nodes, each serialized once for both hashing and storing it.
+            let new_elements: Vec<(Blake2bHash, Vec<u8>)> = match tip {
                 // If the "tip" is the same as the new leaf, then the leaf
                 // is already in the Trie.
                 Trie::Leaf { .. } if new_leaf == tip => Vec::new(),
@@ -699,12 +723,26 @@ where
                     WRITE,
                     start.elapsed(),
                 );
-                return Ok(WriteResult::AlreadyExists);
+                return Ok((WriteResult::AlreadyExists, stats));
+            }
+            // The new root is the last element. If it's stored already, so is every node under
+            // it, e.g. when the same effects are applied to the same root again.
+            let new_root_hash = new_elements[new_elements.len() - 1].0;
+            if store.get(txn, &new_root_hash)?.is_some() {
+                log_duration(
+                    correlation_id,
+                    TRIE_STORE_WRITE_DURATION,
+                    WRITE,
+                    start.elapsed(),
+                );
+                return Ok((WriteResult::Written(new_root_hash), stats));
             }
             let mut root_hash = root.to_owned();
-            for (hash, element) in new_elements.iter() {
+            for (hash, element_bytes) in new_elements.iter() {
                 put_counter += 1;
-                store.put(txn, hash, element)?;
+                stats.nodes_added += 1;
+                stats.bytes_written += element_bytes.len() as u64;
+                store.put_bytes(txn, hash, element_bytes)?;
                 root_hash = *hash;
             }
             log_metric(
@@ -720,7 +758,132 @@ where
                 WRITE,
                 start.elapsed(),
             );
-            Ok(WriteResult::Written(root_hash))
+            Ok((WriteResult::Written(root_hash), stats))
+        }
+    }
+}
+
+/// Shape of a trie, estimated by [`estimate_shape`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TrieShape {
+    pub estimated_nodes: f64,
+    pub max_depth: u32,
+    pub mean_depth: f64,
+}
+
+/// Estimates the shape of the trie under `root` by descending `samples` random paths from it,
+/// choosing uniformly among the children of each node.
+///
+/// The node count follows Knuth's estimator: a path whose nodes have `d1, d2, ...` children
+/// stands for `1 + d1 + d1 * d2 + ...` nodes, which is averaged over all paths. Estimates are
+/// exact for tries whose nodes have the same number of children at each level.
+///
+/// Returns `None` if `root` isn't in the store.
+pub fn estimate_shape<K, V, T, S, E>(
+    txn: &T,
+    store: &S,
+    root: &Blake2bHash,
+    samples: u32,
+    seed: u64,
+) -> Result<Option<TrieShape>, E>
+where
+    K: ToBytes,
+    V: ToBytes,
+    T: Readable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+    E: From<S::Error>,
+{
+    let root_trie: Option<Trie<K, V>> = store.get(txn, root)?;
+    if root_trie.is_none() {
+        return Ok(None);
+    }
+    let samples = samples.max(1);
+    // xorshift64; only needs to spread the paths, not to be unpredictable.
+    let mut state = seed | 1;
+    let mut next_random = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    let mut shape = TrieShape::default();
+    let mut total_depth: u64 = 0;
+    for _ in 0..samples {
+        let mut depth: u32 = 0;
+        let mut width = 1f64;
+        let mut nodes = 1f64;
+        let mut current: Option<Trie<K, V>> = store.get(txn, root)?;
+        while let Some(trie) = current.take() {
+            let children: Vec<Pointer> = match trie {
+                Trie::Leaf { .. } => break,
+                Trie::Node { pointer_block } => (0..trie::RADIX)
+                    .filter_map(|index| pointer_block[index])
+                    .collect(),
+                Trie::Extension { pointer, .. } => vec![pointer],
+            };
+            if children.is_empty() {
+                break;
+            }
+            width *= children.len() as f64;
+            nodes += width;
+            depth += 1;
+            let child = children[(next_random() % children.len() as u64) as usize];
+            current = store.get(txn, child.hash())?;
+        }
+        shape.estimated_nodes += nodes;
+        shape.max_depth = shape.max_depth.max(depth);
+        total_depth += u64::from(depth);
+    }
+    shape.estimated_nodes /= f64::from(samples);
+    shape.mean_depth = total_depth as f64 / f64::from(samples);
+    Ok(Some(shape))
+}
+
+/// Calls `visit` with the key and value of every leaf under `root`, in the order of their
+/// serialized keys.
+///
+/// Returns `false` if `root` isn't in the store.
+pub fn for_each_leaf<K, V, T, S, E, F>(
+    txn: &T,
+    store: &S,
+    root: &Blake2bHash,
+    mut visit: F,
+) -> Result<bool, E>
+where
+    K: ToBytes,
+    V: ToBytes,
+    T: Readable<Handle = S::Handle>,
+    S: TrieStore<K, V>,
+    S::Error: From<T::Error>,
+    E: From<S::Error>,
+    F: FnMut(K, V),
+{
+    let root_trie: Trie<K, V> = match store.get(txn, root)? {
+        Some(root_trie) => root_trie,
+        None => return Ok(false),
+    };
+    // Children are pushed in reverse, so that the lowest index is visited first.
+    let mut pending: Vec<Trie<K, V>> = vec![root_trie];
+    while let Some(trie) = pending.pop() {
+        let children: Vec<Pointer> = match trie {
+            Trie::Leaf { key, value } => {
+                visit(key, value);
+                continue;
+            }
+            Trie::Node { pointer_block } => (0..trie::RADIX)
+                .rev()
+                .filter_map(|index| pointer_block[index])
+                .collect(),
+            Trie::Extension { pointer, .. } => vec![pointer],
+        };
+        for child in children {
+            match store.get(txn, child.hash())? {
+                Some(child_trie) => pending.push(child_trie),
+                None => panic!("trie under {} misses the node {}", root, child.hash()),
+            }
         }
     }
+    Ok(true)
 }
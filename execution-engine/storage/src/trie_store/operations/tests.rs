@@ -865,6 +865,106 @@ mod scan {
     }
 }
 
+mod estimate_shape {
+    use super::*;
+    use trie_store::operations::{estimate_shape, TrieShape};
+
+    fn shape_of(
+        (root_hash, tries): (Blake2bHash, Vec<HashedTestTrie>),
+    ) -> Result<Option<TrieShape>, failure::Error> {
+        let context = InMemoryTestContext::new(&tries)?;
+        let txn = context.environment.create_read_txn()?;
+        let shape = estimate_shape::<TestKey, TestValue, _, _, in_memory::Error>(
+            &txn,
+            &context.store,
+            &root_hash,
+            16,
+            42,
+        )?;
+        txn.commit()?;
+        Ok(shape)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn estimates_are_exact_for_regular_tries() {
+        let shape = shape_of(create_0_leaf_trie().unwrap()).unwrap().unwrap();
+        assert_close(shape.estimated_nodes, 1.0);
+        assert_eq!(shape.max_depth, 0);
+
+        let shape = shape_of(create_1_leaf_trie().unwrap()).unwrap().unwrap();
+        assert_close(shape.estimated_nodes, 2.0);
+        assert_eq!(shape.max_depth, 1);
+
+        // A root node, an extension and a node with two leaves.
+        let shape = shape_of(create_2_leaf_trie().unwrap()).unwrap().unwrap();
+        assert_close(shape.estimated_nodes, 5.0);
+        assert_eq!(shape.max_depth, 3);
+        assert_close(shape.mean_depth, 3.0);
+    }
+
+    #[test]
+    fn returns_none_for_unknown_root() {
+        let (_, tries) = create_1_leaf_trie().unwrap();
+        let unknown_root = (Blake2bHash::new(&[1u8]), tries);
+        assert!(shape_of(unknown_root).unwrap().is_none());
+    }
+}
+
+mod for_each_leaf {
+    use super::*;
+    use trie_store::operations::for_each_leaf;
+
+    fn leaves_of(
+        (root_hash, tries): (Blake2bHash, Vec<HashedTestTrie>),
+    ) -> Result<Option<Vec<(TestKey, TestValue)>>, failure::Error> {
+        let context = LmdbTestContext::new(&tries)?;
+        let txn = context.environment.create_read_txn()?;
+        let mut leaves = Vec::new();
+        let found = for_each_leaf::<TestKey, TestValue, _, _, error::Error, _>(
+            &txn,
+            &context.store,
+            &root_hash,
+            |key, value| leaves.push((key, value)),
+        )?;
+        txn.commit()?;
+        Ok(if found { Some(leaves) } else { None })
+    }
+
+    #[test]
+    fn visits_every_leaf_in_key_order() {
+        let mut expected: Vec<(TestKey, TestValue)> = TEST_LEAVES
+            .iter()
+            .filter_map(|trie| match trie {
+                Trie::Leaf { key, value } => Some((*key, *value)),
+                _ => None,
+            })
+            .collect();
+        expected.sort_by_key(|(key, _)| key.0);
+
+        let leaves = leaves_of(create_6_leaf_trie().unwrap()).unwrap();
+        assert_eq!(leaves, Some(expected));
+
+        let leaves = leaves_of(create_0_leaf_trie().unwrap()).unwrap();
+        assert_eq!(leaves, Some(vec![]));
+    }
+
+    #[test]
+    fn returns_none_for_unknown_root() {
+        let (_, tries) = create_1_leaf_trie().unwrap();
+        let unknown_root = (Blake2bHash::new(&[1u8]), tries);
+        assert!(leaves_of(unknown_root).unwrap().is_none());
+    }
+}
+
 mod write {
     use super::*;
 
@@ -0,0 +1,299 @@
+//! Gas metering instrumentation.
+//!
+//! Each basic block of a function is charged once, on entry, with the sum of the costs of its
+//! instructions, so a run that doesn't trap is charged exactly as if every instruction was
+//! charged on its own. Blocks end at every instruction that branches or can be branched to,
+//! i.e. `block`, `loop`, `if`, `else`, `end`, the `br` family, `return` and `unreachable`.
+use parity_wasm::builder;
+use parity_wasm::elements::{
+    ImportCountType, Instruction, Instructions, Internal, Module, Section, ValueType,
+};
+use pwasm_utils::rules;
+
+use HOST_MODULE;
+
+/// Host function charging gas, taking the amount as its only argument.
+pub const GAS_IMPORT: &str = "gas";
+
+/// Imports the gas host function and charges every basic block of the module's functions.
+///
+/// If memory growth has a cost, `grow_memory` is replaced by a call to an injected function
+/// charging for the number of pages first. Fails if an instruction is forbidden by `rules`.
+pub fn inject_gas_counter(module: Module, rules: &rules::Set) -> Result<Module, ()> {
+    let mut module_builder = builder::from_module(module);
+    let gas_signature =
+        module_builder.push_signature(builder::signature().param().i32().build_sig());
+    module_builder.push_import(
+        builder::import()
+            .module(HOST_MODULE)
+            .field(GAS_IMPORT)
+            .external()
+            .func(gas_signature)
+            .build(),
+    );
+    let mut module = module_builder.build();
+
+    // The gas function is the last imported function, so the defined functions move up by one.
+    let gas_func = module.import_count(ImportCountType::Function) as u32 - 1;
+    let grow_func = module.functions_space() as u32;
+    let meter_grow = rules.grow_cost() > 0;
+    let mut grows_memory = false;
+    let shift = |index: &mut u32| {
+        if *index >= gas_func {
+            *index += 1
+        }
+    };
+
+    for section in module.sections_mut() {
+        match section {
+            Section::Code(code_section) => {
+                for body in code_section.bodies_mut() {
+                    let instructions = body.code_mut().elements_mut();
+                    for instruction in instructions.iter_mut() {
+                        if let Instruction::Call(index) = instruction {
+                            shift(index);
+                        }
+                    }
+                    inject_block_counters(instructions, rules, gas_func)?;
+                    if meter_grow {
+                        for instruction in instructions.iter_mut() {
+                            if let Instruction::GrowMemory(_) = instruction {
+                                *instruction = Instruction::Call(grow_func);
+                                grows_memory = true;
+                            }
+                        }
+                    }
+                }
+            }
+            Section::Export(export_section) => {
+                for entry in export_section.entries_mut() {
+                    if let Internal::Function(index) = entry.internal_mut() {
+                        shift(index);
+                    }
+                }
+            }
+            Section::Element(element_section) => {
+                for segment in element_section.entries_mut() {
+                    segment.members_mut().iter_mut().for_each(shift);
+                }
+            }
+            Section::Start(index) => shift(index),
+            _ => (),
+        }
+    }
+
+    if grows_memory {
+        module = add_grow_counter(module, rules, gas_func);
+    }
+    Ok(module)
+}
+
+fn ends_basic_block(instruction: &Instruction) -> bool {
+    match instruction {
+        Instruction::Block(_)
+        | Instruction::Loop(_)
+        | Instruction::If(_)
+        | Instruction::Else
+        | Instruction::End
+        | Instruction::Br(_)
+        | Instruction::BrIf(_)
+        | Instruction::BrTable(..)
+        | Instruction::Return
+        | Instruction::Unreachable => true,
+        _ => false,
+    }
+}
+
+/// Inserts a call to `gas_func` at the start of every basic block of `instructions` that has a
+/// cost.
+fn inject_block_counters(
+    instructions: &mut Vec<Instruction>,
+    rules: &rules::Set,
+    gas_func: u32,
+) -> Result<(), ()> {
+    // Start index and cost of each block.
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    let mut cost = 0u32;
+    for (index, instruction) in instructions.iter().enumerate() {
+        cost = cost.checked_add(rules.process(instruction)?).ok_or(())?;
+        if ends_basic_block(instruction) {
+            blocks.push((start, cost));
+            start = index + 1;
+            cost = 0;
+        }
+    }
+    blocks.push((start, cost));
+
+    let mut metered = Vec::with_capacity(instructions.len() + 2 * blocks.len());
+    let mut blocks = blocks.into_iter().filter(|&(_, cost)| cost > 0).peekable();
+    for (index, instruction) in instructions.drain(..).enumerate() {
+        if let Some(&(start, cost)) = blocks.peek() {
+            if start == index {
+                metered.push(Instruction::I32Const(cost as i32));
+                metered.push(Instruction::Call(gas_func));
+                blocks.next();
+            }
+        }
+        metered.push(instruction);
+    }
+    *instructions = metered;
+    Ok(())
+}
+
+/// Adds the function replacing `grow_memory`, charging for the requested pages before growing.
+fn add_grow_counter(module: Module, rules: &rules::Set, gas_func: u32) -> Module {
+    let mut module_builder = builder::from_module(module);
+    module_builder.push_function(
+        builder::function()
+            .signature()
+            .param()
+            .i32()
+            .with_return_type(Some(ValueType::I32))
+            .build()
+            .body()
+            .with_instructions(Instructions::new(vec![
+                Instruction::GetLocal(0),
+                Instruction::GetLocal(0),
+                Instruction::I32Const(rules.grow_cost() as i32),
+                Instruction::I32Mul,
+                Instruction::Call(gas_func),
+                Instruction::GrowMemory(0),
+                Instruction::End,
+            ]))
+            .build()
+            .build(),
+    );
+    module_builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use parity_wasm::builder;
+    use parity_wasm::elements::{BlockType, Instruction, Instructions, Internal, Module};
+    use pwasm_utils::rules;
+
+    use super::inject_gas_counter;
+
+    fn module_with_body(instructions: Vec<Instruction>) -> Module {
+        builder::module()
+            .function()
+            .signature()
+            .build()
+            .body()
+            .with_instructions(Instructions::new(instructions))
+            .build()
+            .build()
+            .export()
+            .field("call")
+            .internal()
+            .func(0)
+            .build()
+            .build()
+    }
+
+    fn instrumented_body(module: &Module, function: usize) -> &[Instruction] {
+        module.code_section().unwrap().bodies()[function]
+            .code()
+            .elements()
+    }
+
+    /// Amounts charged by the calls to the gas function, which is the only import.
+    fn charges(instructions: &[Instruction]) -> Vec<i32> {
+        instructions
+            .windows(2)
+            .filter_map(|pair| match pair {
+                [Instruction::I32Const(cost), Instruction::Call(0)] => Some(*cost),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_charge_straight_line_code_once() {
+        let body = vec![
+            Instruction::I32Const(1),
+            Instruction::I32Const(2),
+            Instruction::I32Mul,
+            Instruction::Drop,
+            Instruction::End,
+        ];
+        let mut costs = BTreeMap::new();
+        costs.insert(rules::InstructionType::Mul, rules::Metering::Fixed(4));
+        let rules = rules::Set::new(1, costs);
+
+        let module = inject_gas_counter(module_with_body(body.clone()), &rules).unwrap();
+        let instructions = instrumented_body(&module, 0);
+        assert_eq!(charges(instructions), vec![8]);
+        assert_eq!(&instructions[2..], &body[..]);
+    }
+
+    #[test]
+    fn should_charge_loop_body_on_every_iteration() {
+        let body = vec![
+            Instruction::Loop(BlockType::NoResult),
+            Instruction::I32Const(0),
+            Instruction::BrIf(0),
+            Instruction::End,
+            Instruction::End,
+        ];
+        let module = inject_gas_counter(module_with_body(body), &rules::Set::default()).unwrap();
+        assert_eq!(
+            instrumented_body(&module, 0),
+            &[
+                Instruction::I32Const(1),
+                Instruction::Call(0),
+                Instruction::Loop(BlockType::NoResult),
+                Instruction::I32Const(2),
+                Instruction::Call(0),
+                Instruction::I32Const(0),
+                Instruction::BrIf(0),
+                Instruction::I32Const(1),
+                Instruction::Call(0),
+                Instruction::End,
+                Instruction::I32Const(1),
+                Instruction::Call(0),
+                Instruction::End,
+            ][..]
+        );
+    }
+
+    #[test]
+    fn should_shift_indices_of_defined_functions() {
+        let module = inject_gas_counter(
+            module_with_body(vec![Instruction::End]),
+            &rules::Set::default(),
+        )
+        .unwrap();
+        let export = &module.export_section().unwrap().entries()[0];
+        assert_eq!(export.internal(), &Internal::Function(1));
+    }
+
+    #[test]
+    fn should_charge_memory_growth_by_page() {
+        let body = vec![
+            Instruction::I32Const(1),
+            Instruction::GrowMemory(0),
+            Instruction::Drop,
+            Instruction::End,
+        ];
+        let rules = rules::Set::default().with_grow_cost(10);
+        let module = inject_gas_counter(module_with_body(body), &rules).unwrap();
+        // The grow counter is added after the defined function.
+        assert_eq!(instrumented_body(&module, 0)[3], Instruction::Call(2));
+        assert_eq!(instrumented_body(&module, 1)[2], Instruction::I32Const(10));
+    }
+
+    #[test]
+    fn should_reject_forbidden_instructions() {
+        let body = vec![
+            Instruction::F32Const(0),
+            Instruction::Drop,
+            Instruction::End,
+        ];
+        let rules = rules::Set::default().with_forbidden_floats();
+        assert!(inject_gas_counter(module_with_body(body), &rules).is_err());
+    }
+}
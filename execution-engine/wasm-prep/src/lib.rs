@@ -1,24 +1,73 @@
 extern crate parity_wasm;
 extern crate pwasm_utils;
 
+pub mod gas;
+pub mod passes;
+pub mod trap_frames;
 pub mod wasm_costs;
 
-use parity_wasm::elements::{deserialize_buffer, Error as ParityWasmError, Module};
-use pwasm_utils::{externalize_mem, inject_gas_counter, rules};
+use parity_wasm::elements::{
+    deserialize_buffer, Error as ParityWasmError, External, FunctionType, ImportEntry, Internal,
+    MemoryType, Module, Type,
+};
+use passes::{ModulePass, ModulePasses};
+use pwasm_utils::{externalize_mem, rules};
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::sync::Arc;
 use wasm_costs::WasmCosts;
 
 //NOTE: size of Wasm memory page is 64 KiB
 pub const MEM_PAGES: u32 = 64;
 
+/// Module that host functions and the memory are imported from.
+pub const HOST_MODULE: &str = "env";
+/// Name under which the memory is imported.
+pub const MEMORY_IMPORT: &str = "memory";
+/// Name of the function called to execute a module, which takes no arguments
+/// and returns nothing.
+pub const CALL_EXPORT: &str = "call";
+
+/// Host functions a module is allowed to import, by name, with their expected signatures.
+pub type ImportAllowlist = BTreeMap<String, FunctionType>;
+
 #[derive(Debug)]
 pub enum PreprocessingError {
-    InvalidImportsError(String),
+    /// Descriptions of every import that isn't provided by the host.
+    InvalidImportsError(Vec<String>),
     NoExportSection,
     NoImportSection,
+    /// Modules can't run code on instantiation, only through the `call` export.
+    StartSectionForbidden,
+    /// A module has to define or import exactly one memory.
+    NoMemory,
+    MultipleMemories,
+    /// The memory starts with more pages than the runtime provides.
+    InitialMemoryTooLarge {
+        initial: u32,
+        limit: u32,
+    },
+    /// The memory may grow to more pages than the runtime provides.
+    MaximumMemoryTooLarge {
+        maximum: u32,
+        limit: u32,
+    },
+    /// The memory starts with more pages than it may grow to.
+    InitialMemoryAboveMaximum {
+        initial: u32,
+        maximum: u32,
+    },
+    NoCallExport,
+    /// `call` is not a function without arguments and result.
+    InvalidCallExport(String),
     DeserializeError(String),
     OperationForbiddenByGasRules,
     StackLimiterError,
+    /// A pass added on top of the built-in ones rejected the module.
+    Rejected {
+        pass: String,
+        reason: String,
+    },
 }
 
 use PreprocessingError::*;
@@ -27,31 +76,245 @@ pub trait Preprocessor<A> {
     fn preprocess(&self, module_bytes: &[u8]) -> Result<A, PreprocessingError>;
 }
 
+/// Deserializes modules and runs them through its passes, in order.
 pub struct WasmiPreprocessor {
-    wasm_costs: WasmCosts,
-    // Number of memory pages.
-    mem_pages: u32,
+    passes: ModulePasses,
 }
 
 impl WasmiPreprocessor {
+    /// Creates a preprocessor with the default passes, which don't check imports.
     pub fn new(wasm_costs: WasmCosts) -> WasmiPreprocessor {
-        WasmiPreprocessor {
-            wasm_costs,
-            mem_pages: MEM_PAGES,
-        }
+        WasmiPreprocessor::with_passes(passes::default_passes(&wasm_costs, None))
+    }
+
+    /// Creates a preprocessor which also rejects modules importing anything
+    /// other than the host functions in `import_allowlist` and the memory.
+    pub fn with_import_allowlist(
+        wasm_costs: WasmCosts,
+        import_allowlist: ImportAllowlist,
+    ) -> WasmiPreprocessor {
+        WasmiPreprocessor::with_passes(passes::default_passes(&wasm_costs, Some(import_allowlist)))
+    }
+
+    /// Creates a preprocessor running exactly `passes`. Modules are only instrumented if the
+    /// instrumentation passes are among them.
+    pub fn with_passes(passes: ModulePasses) -> WasmiPreprocessor {
+        WasmiPreprocessor { passes }
+    }
+
+    /// Adds `pass` after the existing ones.
+    pub fn with_pass(mut self, pass: Arc<dyn ModulePass>) -> WasmiPreprocessor {
+        self.passes.push(pass);
+        self
+    }
+
+    pub fn passes(&self) -> &[Arc<dyn ModulePass>] {
+        &self.passes
     }
 }
 
 impl Preprocessor<Module> for WasmiPreprocessor {
     fn preprocess(&self, module_bytes: &[u8]) -> Result<Module, PreprocessingError> {
         let from_parity_err = |err: ParityWasmError| DeserializeError(err.description().to_owned());
-        let deserialized_module = deserialize_buffer(module_bytes).map_err(from_parity_err)?;
-        let ext_mod = externalize_mem(deserialized_module, None, self.mem_pages);
-        let gas_mod = inject_gas_counters(ext_mod, &self.wasm_costs)?;
-        let module =
-            pwasm_utils::stack_height::inject_limiter(gas_mod, self.wasm_costs.max_stack_height)
-                .map_err(|_| StackLimiterError)?;
-        Ok(module)
+        let deserialized_module: Module =
+            deserialize_buffer(module_bytes).map_err(from_parity_err)?;
+        self.passes
+            .iter()
+            .try_fold(deserialized_module, |module, pass| pass.run(module))
+    }
+}
+
+fn validate_imports(
+    module: &Module,
+    import_allowlist: &ImportAllowlist,
+) -> Result<(), PreprocessingError> {
+    let entries = imports(module);
+    let types = module
+        .type_section()
+        .map(|type_section| type_section.types())
+        .unwrap_or(&[]);
+    let invalid_imports: Vec<String> = entries
+        .iter()
+        .filter_map(|entry| invalid_import(entry, types, import_allowlist))
+        .collect();
+    if invalid_imports.is_empty() {
+        Ok(())
+    } else {
+        Err(InvalidImportsError(invalid_imports))
+    }
+}
+
+/// Checks the parts of the module's layout that wasmi would accept, but the
+/// runtime can't execute.
+fn validate_structure(module: &Module, mem_pages: u32) -> Result<(), PreprocessingError> {
+    if module.start_section().is_some() {
+        return Err(StartSectionForbidden);
+    }
+    validate_call_export(module)?;
+    validate_memory(module, mem_pages)
+}
+
+/// Checks that the module has a single memory which fits in `mem_pages` pages. A memory
+/// without a maximum is allowed to grow up to `mem_pages`.
+fn validate_memory(module: &Module, mem_pages: u32) -> Result<(), PreprocessingError> {
+    let imported_memories = imports(module)
+        .iter()
+        .filter_map(|entry| match entry.external() {
+            External::Memory(memory_type) => Some(memory_type),
+            _ => None,
+        });
+    let defined_memories = module
+        .memory_section()
+        .into_iter()
+        .flat_map(|memory_section| memory_section.entries());
+    let memories: Vec<&MemoryType> = imported_memories.chain(defined_memories).collect();
+    let limits = match memories.as_slice() {
+        [] => return Err(NoMemory),
+        [memory] => memory.limits(),
+        _ => return Err(MultipleMemories),
+    };
+    let initial = limits.initial();
+    if initial > mem_pages {
+        return Err(InitialMemoryTooLarge {
+            initial,
+            limit: mem_pages,
+        });
+    }
+    match limits.maximum() {
+        Some(maximum) if maximum > mem_pages => Err(MaximumMemoryTooLarge {
+            maximum,
+            limit: mem_pages,
+        }),
+        Some(maximum) if initial > maximum => Err(InitialMemoryAboveMaximum { initial, maximum }),
+        _ => Ok(()),
+    }
+}
+
+/// Turns a defined memory into the `env.memory` import, and bounds the memory to `mem_pages`
+/// if it doesn't declare a maximum.
+fn externalize_memory(mut module: Module, mem_pages: u32) -> Module {
+    let defines_memory = module
+        .memory_section()
+        .map(|memory_section| !memory_section.entries().is_empty())
+        .unwrap_or(false);
+    if defines_memory {
+        return externalize_mem(module, None, mem_pages);
+    }
+    if let Some(import_section) = module.import_section_mut() {
+        for entry in import_section.entries_mut() {
+            let initial = match entry.external() {
+                External::Memory(memory_type) if memory_type.limits().maximum().is_none() => {
+                    memory_type.limits().initial()
+                }
+                _ => continue,
+            };
+            *entry.external_mut() = External::Memory(MemoryType::new(initial, Some(mem_pages)));
+        }
+    }
+    module
+}
+
+fn validate_call_export(module: &Module) -> Result<(), PreprocessingError> {
+    let export_section = module.export_section().ok_or(NoExportSection)?;
+    let call_export = export_section
+        .entries()
+        .iter()
+        .find(|entry| entry.field() == CALL_EXPORT)
+        .ok_or(NoCallExport)?;
+    let function_index = match call_export.internal() {
+        Internal::Function(function_index) => *function_index,
+        other => return Err(InvalidCallExport(format!("not a function: {:?}", other))),
+    };
+    let function_type = function_type(module, function_index)
+        .ok_or_else(|| InvalidCallExport(format!("unknown function index {}", function_index)))?;
+    if function_type.params().is_empty() && function_type.return_type().is_none() {
+        Ok(())
+    } else {
+        Err(InvalidCallExport(format!(
+            "expected signature (), found {}",
+            format_signature(function_type)
+        )))
+    }
+}
+
+fn imports(module: &Module) -> &[ImportEntry] {
+    module
+        .import_section()
+        .map(|import_section| import_section.entries())
+        .unwrap_or(&[])
+}
+
+/// Looks up the type of a function in the index space shared by imported and
+/// defined functions, imported ones coming first.
+fn function_type(module: &Module, function_index: u32) -> Option<&FunctionType> {
+    let imported_type_refs = imports(module)
+        .iter()
+        .filter_map(|entry| match entry.external() {
+            External::Function(type_ref) => Some(*type_ref),
+            _ => None,
+        });
+    let defined_type_refs = module
+        .function_section()
+        .into_iter()
+        .flat_map(|function_section| function_section.entries())
+        .map(|func| func.type_ref());
+    let type_ref = imported_type_refs
+        .chain(defined_type_refs)
+        .nth(function_index as usize)?;
+    let types = module.type_section()?.types();
+    match types.get(type_ref as usize)? {
+        Type::Function(function_type) => Some(function_type),
+    }
+}
+
+/// Returns a description of the problem with `entry`, if any.
+fn invalid_import(
+    entry: &ImportEntry,
+    types: &[Type],
+    import_allowlist: &ImportAllowlist,
+) -> Option<String> {
+    let name = format!("{}.{}", entry.module(), entry.field());
+    if entry.module() != HOST_MODULE {
+        return Some(format!("{}: unknown module {}", name, entry.module()));
+    }
+    match entry.external() {
+        External::Function(type_index) => {
+            let expected = match import_allowlist.get(entry.field()) {
+                Some(expected) => expected,
+                None => return Some(format!("{}: unknown host function", name)),
+            };
+            match types.get(*type_index as usize) {
+                Some(Type::Function(actual))
+                    if actual.params() == expected.params()
+                        && actual.return_type() == expected.return_type() =>
+                {
+                    None
+                }
+                Some(Type::Function(actual)) => Some(format!(
+                    "{}: expected signature {}, found {}",
+                    name,
+                    format_signature(expected),
+                    format_signature(actual)
+                )),
+                None => Some(format!("{}: unknown type index {}", name, type_index)),
+            }
+        }
+        External::Memory(_) if entry.field() == MEMORY_IMPORT => None,
+        External::Memory(_) => Some(format!("{}: unknown memory", name)),
+        External::Table(_) => Some(format!("{}: tables can't be imported", name)),
+        External::Global(_) => Some(format!("{}: globals can't be imported", name)),
+    }
+}
+
+fn format_signature(function_type: &FunctionType) -> String {
+    let params: Vec<String> = function_type
+        .params()
+        .iter()
+        .map(|param| format!("{:?}", param))
+        .collect();
+    match function_type.return_type() {
+        Some(return_type) => format!("({}) -> {:?}", params.join(", "), return_type),
+        None => format!("({})", params.join(", ")),
     }
 }
 
@@ -84,5 +347,274 @@ fn inject_gas_counters(
     module: Module,
     wasm_costs: &WasmCosts,
 ) -> Result<Module, PreprocessingError> {
-    inject_gas_counter(module, &gas_rules(wasm_costs)).map_err(|_| OperationForbiddenByGasRules)
+    gas::inject_gas_counter(module, &gas_rules(wasm_costs))
+        .map_err(|_| OperationForbiddenByGasRules)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use parity_wasm::elements::{
+        ExportEntry, ExportSection, External, Func, FunctionSection, FunctionType, ImportEntry,
+        ImportSection, Internal, MemorySection, MemoryType, Module, Section, TableType, Type,
+        TypeSection, ValueType,
+    };
+
+    use super::passes::{self, ModulePass};
+    use super::{
+        externalize_memory, validate_imports, validate_structure, ImportAllowlist,
+        PreprocessingError, Preprocessor, WasmiPreprocessor, MEM_PAGES,
+    };
+
+    fn allowlist() -> ImportAllowlist {
+        let mut allowlist = ImportAllowlist::new();
+        allowlist.insert(
+            "read_value".to_string(),
+            FunctionType::new(vec![ValueType::I32; 2], Some(ValueType::I32)),
+        );
+        allowlist
+    }
+
+    fn module_importing(types: Vec<FunctionType>, imports: Vec<ImportEntry>) -> Module {
+        Module::new(vec![
+            Section::Type(TypeSection::with_types(
+                types.into_iter().map(Type::Function).collect(),
+            )),
+            Section::Import(ImportSection::with_entries(imports)),
+        ])
+    }
+
+    fn import(module: &str, field: &str, external: External) -> ImportEntry {
+        ImportEntry::new(module.to_string(), field.to_string(), external)
+    }
+
+    #[test]
+    fn should_accept_known_host_functions_and_memory() {
+        let module = module_importing(
+            vec![FunctionType::new(
+                vec![ValueType::I32; 2],
+                Some(ValueType::I32),
+            )],
+            vec![
+                import("env", "read_value", External::Function(0)),
+                import("env", "memory", External::Memory(MemoryType::new(1, None))),
+            ],
+        );
+        assert!(validate_imports(&module, &allowlist()).is_ok());
+        assert!(validate_imports(&Module::default(), &allowlist()).is_ok());
+    }
+
+    #[test]
+    fn should_list_every_invalid_import() {
+        let module = module_importing(
+            vec![
+                FunctionType::new(vec![ValueType::I32; 2], Some(ValueType::I32)),
+                FunctionType::new(vec![ValueType::I64], None),
+            ],
+            vec![
+                import("env", "read_value", External::Function(0)),
+                import("env", "read_value", External::Function(1)),
+                import("env", "launch_missiles", External::Function(0)),
+                import("wasi", "read_value", External::Function(0)),
+                import("env", "table", External::Table(TableType::new(1, None))),
+            ],
+        );
+        match validate_imports(&module, &allowlist()) {
+            Err(PreprocessingError::InvalidImportsError(invalid_imports)) => assert_eq!(
+                invalid_imports,
+                vec![
+                    "env.read_value: expected signature (I32, I32) -> I32, found (I64)",
+                    "env.launch_missiles: unknown host function",
+                    "wasi.read_value: unknown module wasi",
+                    "env.table: tables can't be imported",
+                ]
+            ),
+            other => panic!("expected InvalidImportsError, got {:?}", other),
+        }
+    }
+
+    /// A module defining `memories` memories and exporting its only function as `call`.
+    fn module_with_call(call_type: FunctionType, memories: usize) -> Module {
+        module_with_memories(call_type, vec![MemoryType::new(1, None); memories])
+    }
+
+    fn module_with_memories(call_type: FunctionType, memories: Vec<MemoryType>) -> Module {
+        Module::new(vec![
+            Section::Type(TypeSection::with_types(vec![Type::Function(call_type)])),
+            Section::Function(FunctionSection::with_entries(vec![Func::new(0)])),
+            Section::Memory(MemorySection::with_entries(memories)),
+            Section::Export(ExportSection::with_entries(vec![ExportEntry::new(
+                "call".to_string(),
+                Internal::Function(0),
+            )])),
+        ])
+    }
+
+    #[test]
+    fn should_accept_module_exporting_call() {
+        let module = module_with_call(FunctionType::new(vec![], None), 1);
+        assert!(validate_structure(&module, MEM_PAGES).is_ok());
+    }
+
+    #[test]
+    fn should_reject_start_section() {
+        let mut module = module_with_call(FunctionType::new(vec![], None), 1);
+        module.sections_mut().push(Section::Start(0));
+        match validate_structure(&module, MEM_PAGES) {
+            Err(PreprocessingError::StartSectionForbidden) => (),
+            other => panic!("expected StartSectionForbidden, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_reject_multiple_memories() {
+        let module = module_with_call(FunctionType::new(vec![], None), 2);
+        match validate_structure(&module, MEM_PAGES) {
+            Err(PreprocessingError::MultipleMemories) => (),
+            other => panic!("expected MultipleMemories, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_reject_missing_memory() {
+        let module = module_with_call(FunctionType::new(vec![], None), 0);
+        match validate_structure(&module, MEM_PAGES) {
+            Err(PreprocessingError::NoMemory) => (),
+            other => panic!("expected NoMemory, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_reject_memory_exceeding_limits() {
+        let memory = |initial, maximum| {
+            let module = module_with_memories(
+                FunctionType::new(vec![], None),
+                vec![MemoryType::new(initial, maximum)],
+            );
+            validate_structure(&module, MEM_PAGES)
+        };
+        assert!(memory(MEM_PAGES, None).is_ok());
+        assert!(memory(1, Some(MEM_PAGES)).is_ok());
+        match memory(MEM_PAGES + 1, None) {
+            Err(PreprocessingError::InitialMemoryTooLarge { initial, limit }) => {
+                assert_eq!((initial, limit), (MEM_PAGES + 1, MEM_PAGES))
+            }
+            other => panic!("expected InitialMemoryTooLarge, got {:?}", other),
+        }
+        match memory(1, Some(MEM_PAGES + 1)) {
+            Err(PreprocessingError::MaximumMemoryTooLarge { maximum, limit }) => {
+                assert_eq!((maximum, limit), (MEM_PAGES + 1, MEM_PAGES))
+            }
+            other => panic!("expected MaximumMemoryTooLarge, got {:?}", other),
+        }
+        match memory(2, Some(1)) {
+            Err(PreprocessingError::InitialMemoryAboveMaximum { initial, maximum }) => {
+                assert_eq!((initial, maximum), (2, 1))
+            }
+            other => panic!("expected InitialMemoryAboveMaximum, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_bound_imported_memory() {
+        let module = module_importing(
+            vec![],
+            vec![import(
+                "env",
+                "memory",
+                External::Memory(MemoryType::new(1, None)),
+            )],
+        );
+        let module = externalize_memory(module, MEM_PAGES);
+        match module.import_section().unwrap().entries()[0].external() {
+            External::Memory(memory_type) => {
+                assert_eq!(memory_type.limits().initial(), 1);
+                assert_eq!(memory_type.limits().maximum(), Some(MEM_PAGES));
+            }
+            other => panic!("expected memory import, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_reject_missing_or_mistyped_call_export() {
+        match validate_structure(&Module::default(), MEM_PAGES) {
+            Err(PreprocessingError::NoExportSection) => (),
+            other => panic!("expected NoExportSection, got {:?}", other),
+        }
+
+        let mut module = module_with_call(FunctionType::new(vec![], None), 1);
+        module.export_section_mut().unwrap().entries_mut().clear();
+        match validate_structure(&module, MEM_PAGES) {
+            Err(PreprocessingError::NoCallExport) => (),
+            other => panic!("expected NoCallExport, got {:?}", other),
+        }
+
+        let module = module_with_call(FunctionType::new(vec![ValueType::I32], None), 1);
+        match validate_structure(&module, MEM_PAGES) {
+            Err(PreprocessingError::InvalidCallExport(message)) => {
+                assert_eq!(message, "expected signature (), found (I32)")
+            }
+            other => panic!("expected InvalidCallExport, got {:?}", other),
+        }
+    }
+
+    /// Logs that it ran, rejecting every module if `reject` is set.
+    struct LoggingPass {
+        name: &'static str,
+        log: Arc<Mutex<Vec<&'static str>>>,
+        reject: bool,
+    }
+
+    impl ModulePass for LoggingPass {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn run(&self, module: Module) -> Result<Module, PreprocessingError> {
+            self.log.lock().unwrap().push(self.name);
+            if self.reject {
+                Err(PreprocessingError::Rejected {
+                    pass: self.name.to_string(),
+                    reason: "rejected".to_string(),
+                })
+            } else {
+                Ok(module)
+            }
+        }
+    }
+
+    #[test]
+    fn should_run_passes_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let pass = |name, reject| {
+            Arc::new(LoggingPass {
+                name,
+                log: Arc::clone(&log),
+                reject,
+            })
+        };
+        let preprocessor = WasmiPreprocessor::with_passes(passes::validation_passes(None))
+            .with_pass(pass("first", false))
+            .with_pass(pass("second", true))
+            .with_pass(pass("third", false));
+
+        let module = module_with_call(FunctionType::new(vec![], None), 1);
+        let module_bytes = ::parity_wasm::serialize(module).unwrap();
+        match preprocessor.preprocess(&module_bytes) {
+            Err(PreprocessingError::Rejected { pass, .. }) => assert_eq!(pass, "second"),
+            other => panic!("expected Rejected, got {:?}", other),
+        }
+        assert_eq!(*log.lock().unwrap(), vec!["first", "second"]);
+
+        // Passes after a failed validation don't run.
+        log.lock().unwrap().clear();
+        let module = module_with_call(FunctionType::new(vec![], None), 0);
+        let module_bytes = ::parity_wasm::serialize(module).unwrap();
+        match preprocessor.preprocess(&module_bytes) {
+            Err(PreprocessingError::NoMemory) => (),
+            other => panic!("expected NoMemory, got {:?}", other),
+        }
+        assert!(log.lock().unwrap().is_empty());
+    }
 }
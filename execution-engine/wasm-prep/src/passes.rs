@@ -0,0 +1,161 @@
+//! The steps `WasmiPreprocessor` runs a module through, in order. Validations come first, as
+//! instrumenting a module adds imports and code of its own, e.g. the `gas` import.
+use std::fmt;
+use std::sync::Arc;
+
+use parity_wasm::elements::Module;
+
+use super::trap_frames::inject_frame_tracker;
+use super::wasm_costs::WasmCosts;
+use super::{
+    externalize_memory, inject_gas_counters, validate_imports, validate_structure, ImportAllowlist,
+    PreprocessingError, MEM_PAGES,
+};
+
+/// A step of preprocessing, which checks or rewrites a deserialized module.
+pub trait ModulePass: Send + Sync {
+    /// Identifies the pass. Pipelines are compared by the names of their passes, so the name
+    /// has to change whenever the pass does something else.
+    fn name(&self) -> &str;
+
+    fn run(&self, module: Module) -> Result<Module, PreprocessingError>;
+}
+
+impl fmt::Debug for dyn ModulePass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl PartialEq for dyn ModulePass {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl Eq for dyn ModulePass {}
+
+/// Passes are shared, as the same pipeline is used by every deploy of a protocol version.
+pub type ModulePasses = Vec<Arc<dyn ModulePass>>;
+
+/// Rejects modules importing anything other than the host functions in the allowlist and the
+/// memory.
+pub struct ValidateImports(pub ImportAllowlist);
+
+impl ModulePass for ValidateImports {
+    fn name(&self) -> &str {
+        "validate_imports"
+    }
+
+    fn run(&self, module: Module) -> Result<Module, PreprocessingError> {
+        validate_imports(&module, &self.0)?;
+        Ok(module)
+    }
+}
+
+/// Rejects modules the runtime can't execute, see `validate_structure`.
+pub struct ValidateStructure {
+    pub mem_pages: u32,
+}
+
+impl ModulePass for ValidateStructure {
+    fn name(&self) -> &str {
+        "validate_structure"
+    }
+
+    fn run(&self, module: Module) -> Result<Module, PreprocessingError> {
+        validate_structure(&module, self.mem_pages)?;
+        Ok(module)
+    }
+}
+
+pub struct ExternalizeMemory {
+    pub mem_pages: u32,
+}
+
+impl ModulePass for ExternalizeMemory {
+    fn name(&self) -> &str {
+        "externalize_memory"
+    }
+
+    fn run(&self, module: Module) -> Result<Module, PreprocessingError> {
+        Ok(externalize_memory(module, self.mem_pages))
+    }
+}
+
+pub struct InjectGasCounters(pub WasmCosts);
+
+impl ModulePass for InjectGasCounters {
+    fn name(&self) -> &str {
+        "inject_gas_counters"
+    }
+
+    fn run(&self, module: Module) -> Result<Module, PreprocessingError> {
+        inject_gas_counters(module, &self.0)
+    }
+}
+
+/// Keeps track of the running function, so that traps can be located, see `trap_frames`.
+pub struct TrackTrapFrames;
+
+impl ModulePass for TrackTrapFrames {
+    fn name(&self) -> &str {
+        "track_trap_frames"
+    }
+
+    fn run(&self, module: Module) -> Result<Module, PreprocessingError> {
+        Ok(inject_frame_tracker(module))
+    }
+}
+
+pub struct InjectStackLimiter {
+    pub max_stack_height: u32,
+}
+
+impl ModulePass for InjectStackLimiter {
+    fn name(&self) -> &str {
+        "inject_stack_limiter"
+    }
+
+    fn run(&self, module: Module) -> Result<Module, PreprocessingError> {
+        ::pwasm_utils::stack_height::inject_limiter(module, self.max_stack_height)
+            .map_err(|_| PreprocessingError::StackLimiterError)
+    }
+}
+
+/// Checks of the module as it was deployed. Imports aren't checked if `import_allowlist` is
+/// `None`.
+pub fn validation_passes(import_allowlist: Option<ImportAllowlist>) -> ModulePasses {
+    let mut passes: ModulePasses = Vec::new();
+    if let Some(import_allowlist) = import_allowlist {
+        passes.push(Arc::new(ValidateImports(import_allowlist)));
+    }
+    passes.push(Arc::new(ValidateStructure {
+        mem_pages: MEM_PAGES,
+    }));
+    passes
+}
+
+/// Rewrites of a valid module charging gas for and bounding its execution under `wasm_costs`.
+pub fn instrumentation_passes(wasm_costs: &WasmCosts) -> ModulePasses {
+    let mut passes: ModulePasses = Vec::new();
+    passes.push(Arc::new(ExternalizeMemory {
+        mem_pages: MEM_PAGES,
+    }));
+    passes.push(Arc::new(InjectGasCounters(wasm_costs.clone())));
+    passes.push(Arc::new(TrackTrapFrames));
+    passes.push(Arc::new(InjectStackLimiter {
+        max_stack_height: wasm_costs.max_stack_height,
+    }));
+    passes
+}
+
+/// The validations followed by the instrumentation.
+pub fn default_passes(
+    wasm_costs: &WasmCosts,
+    import_allowlist: Option<ImportAllowlist>,
+) -> ModulePasses {
+    let mut passes = validation_passes(import_allowlist);
+    passes.extend(instrumentation_passes(wasm_costs));
+    passes
+}
@@ -0,0 +1,231 @@
+//! Instrumentation recording which function is running, so that traps can be located.
+//!
+//! wasmi doesn't report the function a trap was raised in. Instead every function stores its
+//! index in an exported global on entry and again after each call to another function of the
+//! module, so that when a trap unwinds the stack the global names the innermost frame.
+use std::io::{Cursor, Read};
+
+use parity_wasm::builder;
+use parity_wasm::elements::{
+    Deserialize, ExportEntry, External, ImportCountType, Instruction, Internal, Module, Section,
+    VarUint32, VarUint7,
+};
+
+use gas::GAS_IMPORT;
+use HOST_MODULE;
+
+/// Name under which the global holding the index of the running function is exported. It holds
+/// -1 until a function of the module is entered.
+pub const TRAP_FRAME_EXPORT: &str = "__trap_frame";
+
+/// Custom section holding the debug names of a module.
+const NAME_SECTION: &str = "name";
+/// Subsection of the name section mapping function indices to names.
+const FUNCTION_NAMES: u8 = 1;
+
+/// Adds the `__trap_frame` global and makes every function keep it up to date.
+///
+/// Indices are the ones of the deployed module, which are also used by its name section: runs
+/// after gas metering so as not to be charged for, so the `gas` import appended by it is skipped.
+pub fn inject_frame_tracker(module: Module) -> Module {
+    let function_imports = module.import_count(ImportCountType::Function) as u32;
+    let deployed_imports = if imports_gas_last(&module) {
+        function_imports - 1
+    } else {
+        function_imports
+    };
+    let frame_global = module.import_count(ImportCountType::Global) as u32
+        + module
+            .global_section()
+            .map_or(0, |global_section| global_section.entries().len() as u32);
+
+    let mut module = builder::from_module(module)
+        .with_global(
+            builder::global()
+                .value_type()
+                .i32()
+                .mutable()
+                .init_expr(Instruction::I32Const(-1))
+                .build(),
+        )
+        .with_export(ExportEntry::new(
+            TRAP_FRAME_EXPORT.to_string(),
+            Internal::Global(frame_global),
+        ))
+        .build();
+
+    if let Some(code_section) = module.code_section_mut() {
+        for (position, body) in code_section.bodies_mut().iter_mut().enumerate() {
+            let set_frame = [
+                Instruction::I32Const((deployed_imports + position as u32) as i32),
+                Instruction::SetGlobal(frame_global),
+            ];
+            let instructions = body.code_mut().elements_mut();
+            let mut tracked = Vec::with_capacity(instructions.len() + set_frame.len());
+            tracked.extend_from_slice(&set_frame);
+            for instruction in instructions.drain(..) {
+                // Host functions don't change the frame, and are called for every basic block.
+                let calls_module_function = match instruction {
+                    Instruction::Call(index) => index >= function_imports,
+                    Instruction::CallIndirect(..) => true,
+                    _ => false,
+                };
+                tracked.push(instruction);
+                if calls_module_function {
+                    tracked.extend_from_slice(&set_frame);
+                }
+            }
+            *instructions = tracked;
+        }
+    }
+    module
+}
+
+fn imports_gas_last(module: &Module) -> bool {
+    module
+        .import_section()
+        .and_then(|import_section| {
+            import_section
+                .entries()
+                .iter()
+                .filter(|entry| match entry.external() {
+                    External::Function(_) => true,
+                    _ => false,
+                })
+                .last()
+        })
+        .map_or(false, |entry| {
+            entry.module() == HOST_MODULE && entry.field() == GAS_IMPORT
+        })
+}
+
+/// Looks up the name of function `index` in the name section of `module`, if it has one.
+pub fn function_name(module: &Module, index: u32) -> Option<String> {
+    module
+        .sections()
+        .iter()
+        .filter_map(|section| match section {
+            Section::Custom(custom_section) if custom_section.name() == NAME_SECTION => {
+                Some(custom_section.payload())
+            }
+            _ => None,
+        })
+        .filter_map(|payload| find_function_name(payload, index))
+        .next()
+}
+
+/// Reads the function names subsection of a name section's `payload`. Malformed sections are
+/// treated as not naming the function, as they are only used for diagnostics.
+fn find_function_name(payload: &[u8], index: u32) -> Option<String> {
+    let mut reader = Cursor::new(payload);
+    while (reader.position() as usize) < payload.len() {
+        let id: u8 = VarUint7::deserialize(&mut reader).ok()?.into();
+        let size: u32 = VarUint32::deserialize(&mut reader).ok()?.into();
+        let mut subsection = vec![0u8; size as usize];
+        reader.read_exact(&mut subsection).ok()?;
+        if id != FUNCTION_NAMES {
+            continue;
+        }
+        let mut names = Cursor::new(subsection);
+        let count: u32 = VarUint32::deserialize(&mut names).ok()?.into();
+        for _ in 0..count {
+            let function: u32 = VarUint32::deserialize(&mut names).ok()?.into();
+            let name = String::deserialize(&mut names).ok()?;
+            if function == index {
+                return Some(name);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use parity_wasm::builder;
+    use parity_wasm::elements::{Instruction, Instructions, Module};
+
+    use super::{find_function_name, inject_frame_tracker};
+
+    /// A module importing `imports` host functions, whose functions call each other in turn.
+    fn module_calling(imports: &[&str], functions: u32) -> Module {
+        let mut module_builder = builder::module();
+        for field in imports {
+            module_builder = module_builder
+                .import()
+                .module("env")
+                .field(field)
+                .external()
+                .func(0)
+                .build();
+        }
+        let first = imports.len() as u32;
+        for index in 0..functions {
+            let mut body = vec![Instruction::Call(0)];
+            if index + 1 < functions {
+                body.push(Instruction::Call(first + index + 1));
+            }
+            body.push(Instruction::End);
+            module_builder = module_builder
+                .function()
+                .signature()
+                .build()
+                .body()
+                .with_instructions(Instructions::new(body))
+                .build()
+                .build();
+        }
+        module_builder.build()
+    }
+
+    fn body(module: &Module, function: usize) -> &[Instruction] {
+        module.code_section().unwrap().bodies()[function]
+            .code()
+            .elements()
+    }
+
+    #[test]
+    fn should_set_the_frame_on_entry_and_after_module_calls() {
+        let module = inject_frame_tracker(module_calling(&["read_value"], 2));
+        assert_eq!(
+            body(&module, 0),
+            &[
+                Instruction::I32Const(1),
+                Instruction::SetGlobal(0),
+                Instruction::Call(0),
+                Instruction::Call(2),
+                Instruction::I32Const(1),
+                Instruction::SetGlobal(0),
+                Instruction::End,
+            ][..]
+        );
+        assert_eq!(
+            body(&module, 1),
+            &[
+                Instruction::I32Const(2),
+                Instruction::SetGlobal(0),
+                Instruction::Call(0),
+                Instruction::End,
+            ][..]
+        );
+    }
+
+    #[test]
+    fn should_number_functions_without_the_gas_import() {
+        let module = inject_frame_tracker(module_calling(&["read_value", "gas"], 1));
+        assert_eq!(
+            &body(&module, 0)[..2],
+            &[Instruction::I32Const(1), Instruction::SetGlobal(0)][..]
+        );
+    }
+
+    #[test]
+    fn should_read_function_names() {
+        // Function names subsection naming functions 0 and 3.
+        let payload = [1, 10, 2, 0, 2, b'f', b'0', 3, 3, b'r', b'u', b'n'];
+        assert_eq!(find_function_name(&payload, 3), Some("run".to_string()));
+        assert_eq!(find_function_name(&payload, 1), None);
+        // Module name subsection first, then a truncated function names subsection.
+        let payload = [0, 2, 1, b'm', 1, 5, 1, 0];
+        assert_eq!(find_function_name(&payload, 0), None);
+    }
+}
@@ -1,5 +1,5 @@
 // Taken (partially) from parity-ethereum
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct WasmCosts {
     /// Default opcode cost
     pub regular: u32,
@@ -21,6 +21,25 @@ pub struct WasmCosts {
     pub opcodes_mul: u32,
     /// Cost of wasm opcode is calculated as TABLE_ENTRY_COST * `opcodes_mul` / `opcodes_div`
     pub opcodes_div: u32,
+    /// Base cost of every host function call.
+    pub host_call: u32,
+    /// Additional cost of host functions reading from the global state.
+    pub host_read: u32,
+    /// Additional cost of host functions writing to the global state.
+    pub host_write: u32,
+    /// Additional cost of calling a stored contract, including transfers through the mint.
+    pub host_call_contract: u32,
+    /// Gas refunded per byte a successful deploy frees in the global state, up to half of its
+    /// cost, see `ExecutionEffect::net_storage_bytes`. Only up to `host_write_per_byte` is
+    /// refunded, so that freeing a byte never pays more than writing it costs.
+    pub storage_refund_per_byte: u32,
+    /// Gas per byte the host allocates on behalf of a contract: values placed in the host
+    /// buffer, e.g. read from the global state or returned by a called contract, and the
+    /// arguments of called contracts.
+    pub host_alloc: u32,
+    /// Gas per byte of the values host functions write to the global state, measured by
+    /// serializing them to check their size.
+    pub host_write_per_byte: u32,
 }
 
 impl WasmCosts {
@@ -37,6 +56,20 @@ impl WasmCosts {
                 max_stack_height: 64 * 1024,
                 opcodes_mul: 3,
                 opcodes_div: 8,
+                host_call: 10,
+                host_read: 100,
+                host_write: 200,
+                host_call_contract: 1000,
+                storage_refund_per_byte: 0,
+                host_alloc: 0,
+                host_write_per_byte: 0,
+            }),
+            // Charges for the memory the host allocates on behalf of contracts and for the size
+            // of the values they write.
+            2 => Some(WasmCosts {
+                host_alloc: 1,
+                host_write_per_byte: 1,
+                ..WasmCosts::from_version(1)?
             }),
             _ => None,
         }
@@ -54,6 +87,13 @@ impl WasmCosts {
             max_stack_height: 64 * 1024,
             opcodes_mul: 1,
             opcodes_div: 1,
+            host_call: 0,
+            host_read: 0,
+            host_write: 0,
+            host_call_contract: 0,
+            storage_refund_per_byte: 0,
+            host_alloc: 0,
+            host_write_per_byte: 0,
         }
     }
 }
@@ -31,7 +31,7 @@ import io.casperlabs.catscontrib.MonadThrowable
       blocktime: Long,
       deploys: Seq[Deploy],
       protocolVersion: ProtocolVersion
-  ): F[Either[Throwable, Seq[DeployResult]]]
+  ): F[Either[Throwable, ExecResult]]
   def commit(
       prestate: ByteString,
       effects: Seq[TransformEntry]
@@ -72,15 +72,15 @@ class GrpcExecutionEngineService[F[_]: Defer: Sync: Log: TaskLift: Metrics] priv
       blocktime: Long,
       deploys: Seq[Deploy],
       protocolVersion: ProtocolVersion
-  ): F[Either[Throwable, Seq[DeployResult]]] =
+  ): F[Either[Throwable, ExecResult]] =
     for {
       result <- sendMessage(
                  ExecRequest(prestate, blocktime, deploys, Some(protocolVersion)),
                  _.exec
                ) {
                  _.result match {
-                   case ExecResponse.Result.Success(ExecResult(deployResults)) =>
-                     Right(deployResults)
+                   case ExecResponse.Result.Success(execResult) =>
+                     Right(execResult)
                    //TODO: Capture errors better than just as a string
                    case ExecResponse.Result.Empty =>
                      Left(new SmartContractEngineError("empty response"))
@@ -90,13 +90,26 @@ class GrpcExecutionEngineService[F[_]: Defer: Sync: Log: TaskLift: Metrics] priv
                          s"Missing states: ${Base16.encode(missing.toByteArray)}"
                        )
                      )
+                   case ExecResponse.Result.InvalidNonce(abort) =>
+                     Left(
+                       new SmartContractEngineError(
+                         s"Deploy ${abort.deployIndex} has nonce ${abort.deployNonce}, expected ${abort.expectedNonce}"
+                       )
+                     )
+                   case ExecResponse.Result.MisorderedDeploy(misordered) =>
+                     Left(
+                       new SmartContractEngineError(
+                         s"Deploy ${misordered.deployIndex} with nonce ${misordered.deployNonce} comes after deploy ${misordered.previousIndex} with nonce ${misordered.previousNonce}"
+                       )
+                     )
                  }
                }
       _ <- result.fold(
             _ => ().pure[F],
-            deployResults => {
+            execResult => {
               val gasSpent =
-                deployResults.foldLeft(0L)((a, d) => a + d.value.executionResult.fold(0L)(_.cost))
+                execResult.deployResults
+                  .foldLeft(0L)((a, d) => a + d.value.executionResult.fold(0L)(_.cost))
               Metrics[F].incrementCounter("gas_spent", gasSpent)
             }
           )
@@ -149,12 +162,19 @@ class GrpcExecutionEngineService[F[_]: Defer: Sync: Log: TaskLift: Metrics] priv
           Left(SmartContractEngineError("empty response"))
         case CommitResponse.Result.MissingPrestate(RootNotFound(hash)) =>
           Left(SmartContractEngineError(s"Missing pre-state: ${Base16.encode(hash.toByteArray)}"))
-        case CommitResponse.Result.FailedTransform(PostEffectsError(message)) =>
+        case CommitResponse.Result.FailedTransform(PostEffectsError(message, _)) =>
           Left(SmartContractEngineError(s"Error executing transform: $message"))
         case CommitResponse.Result.KeyNotFound(value) =>
           Left(SmartContractEngineError(s"Key not found in global state: $value"))
         case CommitResponse.Result.TypeMismatch(err) =>
           Left(SmartContractEngineError(err.toString))
+        case CommitResponse.Result.PoststateMismatch(PoststateMismatch(expected, computed)) =>
+          Left(
+            SmartContractEngineError(
+              s"Post-state mismatch: expected ${Base16.encode(expected.toByteArray)}, computed ${Base16
+                .encode(computed.toByteArray)}"
+            )
+          )
       }
     }
 
@@ -168,6 +188,8 @@ class GrpcExecutionEngineService[F[_]: Defer: Sync: Log: TaskLift: Metrics] priv
         case QueryResponse.Result.Success(value) => Right(value)
         case QueryResponse.Result.Empty          => Left(SmartContractEngineError("empty response"))
         case QueryResponse.Result.Failure(err)   => Left(SmartContractEngineError(err))
+        case QueryResponse.Result.SerializedValue(_) =>
+          Left(SmartContractEngineError("unexpected serialized value"))
       }
     }
 